@@ -0,0 +1,152 @@
+//! 事件 topic 与 message selector 的计算, 合约自身, 索引合约和链下客户端共用这一份实现.
+//!
+//! topic 的编码与 `#[ink(event)]` 生成的代码一致: 事件签名 topic 为 `PrefixedValue { prefix: b"",
+//! value: b"Erc20::<事件名>" }`, 字段 topic 为 `PrefixedValue { prefix: b"Erc20::<事件名>::<字段名>",
+//! value: &字段值 }`, 编码后不超过 32 字节时直接补零, 否则取 Blake2x256.
+
+use ink_env::{
+    hash::{Blake2x256, CryptoHash, HashOutput},
+    AccountId, Clear, DefaultEnvironment, Environment, Hash,
+};
+use ink_prelude::vec::Vec;
+
+pub use crate::hooks::{
+    LATEST_PRICE_SELECTOR, MINT_CERTIFICATE_SELECTOR, ON_ALLOWANCE_CHANGED_SELECTOR,
+    ON_BALANCE_CHANGE_SELECTOR, ON_TOKEN_TRANSFER_SELECTOR, PSP22_BALANCE_OF_SELECTOR,
+    PSP22_MINT_SELECTOR,
+};
+
+pub type Balance = <DefaultEnvironment as Environment>::Balance;
+
+/// 可以通过 UserOp 派发的消息 selector
+pub const TRANSFER_SELECTOR: [u8; 4] = [0x84, 0xa1, 0x5d, 0xa1];
+pub const APPROVE_SELECTOR: [u8; 4] = [0x68, 0x12, 0x66, 0xa0];
+pub const TRANSFER_FROM_SELECTOR: [u8; 4] = [0x0b, 0x39, 0x6f, 0x18];
+
+/// 事件 topic 的前缀, 即合约名
+pub const CONTRACT_NAME: &str = "Erc20";
+pub const TRANSFER_EVENT: &str = "Transfer";
+pub const APPROVAL_EVENT: &str = "Approval";
+
+/// 带前缀的 topic 值, prefix 按 `&[u8]` 编码(带长度前缀)
+pub struct PrefixedValue<'a, 'b, T> {
+    pub prefix: &'a [u8],
+    pub value: &'b T,
+}
+
+impl<X> scale::Encode for PrefixedValue<'_, '_, X>
+where
+    X: scale::Encode,
+{
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.prefix.size_hint() + self.value.size_hint()
+    }
+
+    #[inline]
+    fn encode_to<T: scale::Output + ?Sized>(&self, dest: &mut T) {
+        self.prefix.encode_to(dest);
+        self.value.encode_to(dest);
+    }
+}
+
+/// 不带长度前缀编码的字节串, 对应生成代码中事件签名的 `b"..."` 数组
+struct RawBytes<'a>(&'a [u8]);
+
+impl scale::Encode for RawBytes<'_> {
+    fn size_hint(&self) -> usize {
+        self.0.len()
+    }
+
+    fn encode_to<T: scale::Output + ?Sized>(&self, dest: &mut T) {
+        dest.write(self.0);
+    }
+}
+
+/// 与 ink 相同的 topic 压缩: 不超过 32 字节补零, 否则取 Blake2x256
+pub fn encoded_into_hash<T>(entity: &T) -> Hash
+where
+    T: scale::Encode,
+{
+    let mut result = Hash::clear();
+    let len_result = result.as_ref().len();
+    let encoded = entity.encode();
+    let len_encoded = encoded.len();
+    if len_encoded <= len_result {
+        result.as_mut()[..len_encoded].copy_from_slice(&encoded);
+        return result;
+    }
+    let mut hash_output = <<Blake2x256 as HashOutput>::Type as Default>::default();
+    <Blake2x256 as CryptoHash>::hash(&encoded, &mut hash_output);
+    let copy_len = core::cmp::min(hash_output.len(), len_result);
+    result.as_mut()[0..copy_len].copy_from_slice(&hash_output[0..copy_len]);
+    result
+}
+
+fn path(segments: &[&str]) -> Vec<u8> {
+    let mut path = Vec::from(CONTRACT_NAME.as_bytes());
+    for segment in segments {
+        path.extend_from_slice(b"::");
+        path.extend_from_slice(segment.as_bytes());
+    }
+    path
+}
+
+/// 事件签名 topic, 即每个事件的第一个 topic
+pub fn event_topic(event: &str) -> Hash {
+    encoded_into_hash(&PrefixedValue {
+        prefix: b"",
+        value: &RawBytes(&path(&[event])),
+    })
+}
+
+/// 事件中标记了 `#[ink(topic)]` 的字段对应的 topic
+pub fn field_topic<T: scale::Encode>(event: &str, field: &str, value: &T) -> Hash {
+    encoded_into_hash(&PrefixedValue {
+        prefix: &path(&[event, field]),
+        value,
+    })
+}
+
+pub fn transfer_topic() -> Hash {
+    event_topic(TRANSFER_EVENT)
+}
+
+pub fn approval_topic() -> Hash {
+    event_topic(APPROVAL_EVENT)
+}
+
+/// Transfer.from 的 topic, 铸币时 from 为 None
+pub fn transfer_from_topic(from: Option<AccountId>) -> Hash {
+    field_topic(TRANSFER_EVENT, "from", &from)
+}
+
+/// Transfer.to 的 topic, 销毁时 to 为 None
+pub fn transfer_to_topic(to: Option<AccountId>) -> Hash {
+    field_topic(TRANSFER_EVENT, "to", &to)
+}
+
+/// 账户作为转出方和转入方时的两个 Transfer topic, 订阅两者即可拿到该账户的全部转账
+pub fn transfer_topic_for_account(account: AccountId) -> [Hash; 2] {
+    [
+        transfer_from_topic(Some(account)),
+        transfer_to_topic(Some(account)),
+    ]
+}
+
+pub fn approval_owner_topic(owner: AccountId) -> Hash {
+    field_topic(APPROVAL_EVENT, "owner", &owner)
+}
+
+pub fn approval_spender_topic(spender: AccountId) -> Hash {
+    field_topic(APPROVAL_EVENT, "spender", &spender)
+}
+
+pub fn approval_value_bucket_topic(bucket: u8) -> Hash {
+    field_topic(APPROVAL_EVENT, "value_bucket", &bucket)
+}
+
+/// Approval 事件中 value 的分桶: 0 对应 0, 否则为 floor(log2(value)) + 1
+pub fn value_bucket(value: Balance) -> u8 {
+    (128 - value.leading_zeros()) as u8
+}
@@ -0,0 +1,60 @@
+//! 编译时计算合约 ABI 哈希: 收集 lib.rs 中所有 message 和 constructor 的 selector,
+//! 排序后拼接做 Blake2x256, 以十六进制通过环境变量 ERC20_ABI_HASH 嵌入合约
+
+use blake2::{
+    digest::{Update, VariableOutput},
+    Blake2bVar,
+};
+use std::fs;
+
+fn blake2x256(input: &[u8]) -> [u8; 32] {
+    let mut hasher = Blake2bVar::new(32).expect("invalid blake2 output size");
+    hasher.update(input);
+    let mut output = [0u8; 32];
+    hasher
+        .finalize_variable(&mut output)
+        .expect("invalid blake2 output size");
+    output
+}
+
+// ink! 默认的 selector 是 message 名字 Blake2x256 的前 4 个字节, 显式声明的 selector 优先
+fn selectors(source: &str) -> Vec<[u8; 4]> {
+    let mut selectors = Vec::new();
+    let mut pending: Option<Option<[u8; 4]>> = None;
+    for line in source.lines().map(str::trim) {
+        if line.starts_with("#[ink(message") || line.starts_with("#[ink(constructor") {
+            pending = Some(explicit_selector(line));
+            continue;
+        }
+        if let (Some(explicit), Some(start)) = (pending, line.find("fn ")) {
+            let selector = explicit.unwrap_or_else(|| {
+                let name: String = line[start + 3..]
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '_')
+                    .collect();
+                let mut selector = [0u8; 4];
+                selector.copy_from_slice(&blake2x256(name.as_bytes())[..4]);
+                selector
+            });
+            selectors.push(selector);
+            pending = None;
+        }
+    }
+    selectors.sort_unstable();
+    selectors
+}
+
+fn explicit_selector(attribute: &str) -> Option<[u8; 4]> {
+    let start = attribute.find("selector")?;
+    let hex = attribute[start..].split("0x").nth(1)?;
+    let value = u32::from_str_radix(hex.get(..8)?, 16).ok()?;
+    Some(value.to_be_bytes())
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=lib.rs");
+    let source = fs::read_to_string("lib.rs").expect("cannot read lib.rs");
+    let hash = blake2x256(&selectors(&source).concat());
+    let hex: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+    println!("cargo:rustc-env=ERC20_ABI_HASH={}", hex);
+}
@@ -0,0 +1,115 @@
+//! 跨合约调用层. 链上通过 `build_call` 发起调用, 链下测试中可以注入桩实现,
+//! 因为 off-chain 环境不支持真正的跨合约调用
+
+use ink_env::{
+    call::{build_call, ExecutionInput, Selector},
+    AccountId, DefaultEnvironment,
+};
+use ink_prelude::vec::Vec;
+
+/// 已经 SCALE 编码好的参数, 原样写入调用输入
+struct RawInput<'a>(&'a [u8]);
+
+impl scale::Encode for RawInput<'_> {
+    fn encode_to<T: scale::Output + ?Sized>(&self, dest: &mut T) {
+        dest.write(self.0);
+    }
+}
+
+/// 原样读取被调用合约的返回数据, 由调用方自行解码
+struct RawOutput(Vec<u8>);
+
+impl scale::Decode for RawOutput {
+    fn decode<I: scale::Input>(input: &mut I) -> Result<Self, scale::Error> {
+        let len = input.remaining_len()?.unwrap_or_default();
+        let mut buf = ink_prelude::vec![0u8; len];
+        input.read(&mut buf)?;
+        Ok(RawOutput(buf))
+    }
+}
+
+/// `Error::ExternalCall` 中 code 的取值, 对 ink_env::Error 做粗粒度分类
+pub const CALL_ERROR_UNKNOWN: u8 = 0;
+pub const CALL_ERROR_CALLEE_TRAPPED: u8 = 1;
+pub const CALL_ERROR_CALLEE_REVERTED: u8 = 2;
+pub const CALL_ERROR_NOT_CALLABLE: u8 = 3;
+
+/// 把 ink_env 的调用错误映射为上面的分类
+pub fn error_code(error: &ink_env::Error) -> u8 {
+    match error {
+        ink_env::Error::CalleeTrapped => CALL_ERROR_CALLEE_TRAPPED,
+        ink_env::Error::CalleeReverted => CALL_ERROR_CALLEE_REVERTED,
+        ink_env::Error::NotCallable => CALL_ERROR_NOT_CALLABLE,
+        _ => CALL_ERROR_UNKNOWN,
+    }
+}
+
+/// 一次跨合约调用的抽象, 方便测试时替换
+pub trait CallLayer {
+    fn call(
+        &mut self,
+        callee: AccountId,
+        selector: [u8; 4],
+        input: &[u8],
+        gas_limit: u64,
+    ) -> Result<Vec<u8>, ink_env::Error>;
+}
+
+/// 链上实现, 直接走 ink_env 的 build_call
+pub struct EnvCallLayer;
+
+impl CallLayer for EnvCallLayer {
+    fn call(
+        &mut self,
+        callee: AccountId,
+        selector: [u8; 4],
+        input: &[u8],
+        gas_limit: u64,
+    ) -> Result<Vec<u8>, ink_env::Error> {
+        build_call::<DefaultEnvironment>()
+            .callee(callee)
+            .gas_limit(gas_limit)
+            .exec_input(ExecutionInput::new(Selector::new(selector)).push_arg(RawInput(input)))
+            .returns::<RawOutput>()
+            .fire()
+            .map(|output| output.0)
+    }
+}
+
+/// 发起跨合约调用, 测试中转发给通过 `set_call_layer` 注入的桩
+pub fn invoke(
+    callee: AccountId,
+    selector: [u8; 4],
+    input: &[u8],
+    gas_limit: u64,
+) -> Result<Vec<u8>, ink_env::Error> {
+    #[cfg(not(test))]
+    {
+        EnvCallLayer.call(callee, selector, input, gas_limit)
+    }
+    #[cfg(test)]
+    {
+        stub::STUB.with(|stub| match stub.borrow_mut().as_mut() {
+            Some(layer) => layer.call(callee, selector, input, gas_limit),
+            None => Err(ink_env::Error::NotCallable),
+        })
+    }
+}
+
+#[cfg(test)]
+pub use stub::set_call_layer;
+
+#[cfg(test)]
+mod stub {
+    use super::CallLayer;
+    use std::{boxed::Box, cell::RefCell};
+
+    thread_local! {
+        pub(super) static STUB: RefCell<Option<Box<dyn CallLayer>>> = RefCell::new(None);
+    }
+
+    /// 替换当前测试线程的调用层
+    pub fn set_call_layer<L: CallLayer + 'static>(layer: L) {
+        STUB.with(|stub| *stub.borrow_mut() = Some(Box::new(layer)));
+    }
+}
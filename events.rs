@@ -0,0 +1,42 @@
+//! 事件中使用的公开取值, 供链下索引器解码
+
+/// `Transfer` 事件的 `kind` 字段, 区分同一事件的不同来源. 事件中按 u8 编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TransferKind {
+    /// 普通转账
+    Normal = 0,
+    /// 增发, from 为 None
+    Mint = 1,
+    /// 销毁, to 为 None
+    Burn = 2,
+    /// 转账手续费
+    Fee = 3,
+    /// owner 强制转移
+    Forced = 4,
+    /// 自动转发
+    Forwarded = 5,
+    /// 转入或转出隔离区
+    Quarantine = 6,
+}
+
+impl TransferKind {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(TransferKind::Normal),
+            1 => Some(TransferKind::Mint),
+            2 => Some(TransferKind::Burn),
+            3 => Some(TransferKind::Fee),
+            4 => Some(TransferKind::Forced),
+            5 => Some(TransferKind::Forwarded),
+            6 => Some(TransferKind::Quarantine),
+            _ => None,
+        }
+    }
+}
+
+impl From<TransferKind> for u8 {
+    fn from(kind: TransferKind) -> u8 {
+        kind as u8
+    }
+}
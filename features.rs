@@ -0,0 +1,68 @@
+//! 每次部署可选开启的功能开关, 在构造函数中一次性确定, 链上可通过 `features()` 查询
+
+/// 允许 owner 暂停所有代币流转
+pub const FEATURE_PAUSABLE: u32 = 1 << 0;
+/// 余额变化时回调奖励合约
+pub const FEATURE_REWARDS_HOOK: u32 = 1 << 1;
+/// 按铸造区块追踪代币年龄
+pub const FEATURE_AGE_TRACKING: u32 = 1 << 2;
+/// 兑换码
+pub const FEATURE_VOUCHERS: u32 = 1 << 3;
+/// 账户抽象 UserOp
+pub const FEATURE_USER_OPS: u32 = 1 << 4;
+/// 链上治理提案与投票
+pub const FEATURE_GOVERNANCE: u32 = 1 << 5;
+/// 弹性供应: 供应偏离目标区间时由合约储备增发或销毁
+pub const FEATURE_ELASTICITY: u32 = 1 << 6;
+/// 转账手续费
+pub const FEATURE_FEES: u32 = 1 << 7;
+/// 中继用的幂等转账
+pub const FEATURE_IDEMPOTENCY: u32 = 1 << 8;
+/// 价格稳定基金
+pub const FEATURE_STABILITY: u32 = 1 << 9;
+/// 质押与质押奖励
+pub const FEATURE_STAKING: u32 = 1 << 10;
+/// 定时转账
+pub const FEATURE_SCHEDULED_TRANSFERS: u32 = 1 << 11;
+/// 确认亏损并销毁代币
+pub const FEATURE_TAX_LOSS: u32 = 1 << 12;
+/// 点对点借贷
+pub const FEATURE_LENDING: u32 = 1 << 13;
+/// 转账后通知已注册的回调合约
+pub const FEATURE_TRANSFER_HOOKS: u32 = 1 << 14;
+/// 记录每个账户最近转出的交易承诺哈希
+pub const FEATURE_TX_COMMITMENTS: u32 = 1 << 15;
+/// 价格随区块线性下降的荷兰式拍卖
+pub const FEATURE_DUTCH_AUCTION: u32 = 1 << 16;
+/// 记录每个账户最近一次活动的区块和时间, 每笔转账为双方各多写一次存储
+pub const FEATURE_ACTIVITY_TRACKING: u32 = 1 << 17;
+/// 代币元数据 (metadata_uri, logo_hash) 的更新需要经过等待期
+pub const FEATURE_METADATA_TIMELOCK: u32 = 1 << 18;
+/// 记录每对 (owner, spender) 授权的历史花费总额和最近使用区块, 每次花费授权多写一次存储
+pub const FEATURE_ALLOWANCE_STATS: u32 = 1 << 19;
+/// 记录曾经持有过代币的账户, 余额归零后仍然保留, 每个新账户多写一次存储
+pub const FEATURE_EVER_HELD: u32 = 1 << 20;
+/// 记录每个 owner 最近的授权变化, 每次授权写入多写一次存储
+pub const FEATURE_APPROVAL_HISTORY: u32 = 1 << 21;
+
+/// `new` 构造函数使用的默认组合
+pub const DEFAULT_FEATURES: u32 = FEATURE_PAUSABLE
+    | FEATURE_REWARDS_HOOK
+    | FEATURE_AGE_TRACKING
+    | FEATURE_VOUCHERS
+    | FEATURE_USER_OPS
+    | FEATURE_GOVERNANCE
+    | FEATURE_ELASTICITY
+    | FEATURE_FEES
+    | FEATURE_IDEMPOTENCY
+    | FEATURE_STABILITY
+    | FEATURE_STAKING
+    | FEATURE_SCHEDULED_TRANSFERS
+    | FEATURE_TAX_LOSS
+    | FEATURE_LENDING
+    | FEATURE_TRANSFER_HOOKS
+    | FEATURE_TX_COMMITMENTS
+    | FEATURE_DUTCH_AUCTION
+    | FEATURE_ACTIVITY_TRACKING
+    | FEATURE_ALLOWANCE_STATS
+    | FEATURE_EVER_HELD;
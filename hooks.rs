@@ -0,0 +1,85 @@
+//! 供外部合约实现的回调接口定义
+
+use ink_env::{AccountId, DefaultEnvironment, Environment};
+use ink_lang as ink;
+
+pub type Balance = <DefaultEnvironment as Environment>::Balance;
+
+/// `RewardsHook::on_balance_change` 的固定 selector, 与下面 trait 中的声明保持一致
+pub const ON_BALANCE_CHANGE_SELECTOR: [u8; 4] = [0x72, 0x38, 0x3c, 0x6d];
+
+/// 奖励合约需要实现的回调接口, 每当账户余额变化时 Erc20 会调用一次.
+///
+/// 调用顺序保证: 一条消息中所有的余额写入(包括手续费和批量转账的每一笔)都完成之后才会发起回调,
+/// 同一账户在一条消息中的多次变化合并为一次通知. 因此回调中读取 `balance_of` 得到的一定是
+/// 最终余额, 与 new_balance 一致.
+#[ink::trait_definition]
+pub trait RewardsHook {
+    /// account 的余额从 old_balance 变为 new_balance
+    #[ink(message, selector = 0x72383c6d)]
+    fn on_balance_change(&mut self, account: AccountId, old_balance: Balance, new_balance: Balance);
+}
+
+/// `PriceOracle::latest_price` 的固定 selector
+pub const LATEST_PRICE_SELECTOR: [u8; 4] = [0x1d, 0x8a, 0x2c, 0x5e];
+
+/// 价格预言机接口, 返回 1 个代币值多少原生币, 以 `PRICE_SCALE` 为精度
+#[ink::trait_definition]
+pub trait PriceOracle {
+    #[ink(message, selector = 0x1d8a2c5e)]
+    fn latest_price(&self) -> Balance;
+}
+
+/// `TaxLossCertificate::mint_certificate` 的固定 selector
+pub const MINT_CERTIFICATE_SELECTOR: [u8; 4] = [0x3a, 0x6e, 0x91, 0x04];
+
+/// 亏损证明 NFT 合约接口, Erc20 在用户确认亏损后为其铸造一张证明
+#[ink::trait_definition]
+pub trait TaxLossCertificate {
+    /// 为 owner 铸造证明, loss_value 为以原生币计的亏损
+    #[ink(message, selector = 0x3a6e9104)]
+    fn mint_certificate(&mut self, owner: AccountId, loss_value: Balance);
+}
+
+/// `TransferHook::on_token_transfer` 的固定 selector
+pub const ON_TOKEN_TRANSFER_SELECTOR: [u8; 4] = [0x5b, 0x1e, 0x0c, 0x93];
+
+/// 转账回调接口, 通过 `register_transfer_hook` 注册后, 每笔账户间转账完成时调用一次.
+/// 与 `RewardsHook` 一样在一条消息的所有余额写入完成后才调用, 不包括增发和销毁
+#[ink::trait_definition]
+pub trait TransferHook {
+    #[ink(message, selector = 0x5b1e0c93)]
+    fn on_token_transfer(&mut self, from: AccountId, to: AccountId, value: Balance);
+}
+
+/// `PSP22::balance_of` 的标准 selector
+pub const PSP22_BALANCE_OF_SELECTOR: [u8; 4] = [0x65, 0x68, 0x38, 0x2f];
+
+/// 其他 PSP22 代币合约中 `portfolio_of` 用到的只读接口
+#[ink::trait_definition]
+pub trait Psp22Balance {
+    #[ink(message, selector = 0x6568382f)]
+    fn balance_of(&self, owner: AccountId) -> Balance;
+}
+
+/// `PSP22Mintable::mint` 的标准 selector
+pub const PSP22_MINT_SELECTOR: [u8; 4] = [0xfc, 0x3c, 0x75, 0xd4];
+
+/// 回收市场中的积分代币接口, Erc20 需要在积分代币中拥有增发权限
+#[ink::trait_definition]
+pub trait Psp22Mintable {
+    #[ink(message, selector = 0xfc3c75d4)]
+    fn mint(&mut self, account: AccountId, amount: Balance);
+}
+
+/// `AllowanceListener::on_allowance_changed` 的固定 selector
+pub const ON_ALLOWANCE_CHANGED_SELECTOR: [u8; 4] = [0x4c, 0x2d, 0x8e, 0x17];
+
+/// 授权回调接口, 合约通过 `register_allowance_listener` 为自己注册后, 每当自己作为 spender
+/// 的授权额度变化时 (创建, 增加, 减少, 清零, 以及 transfer_from 消耗) 收到一次通知.
+/// 调用有 gas 上限且失败会被忽略, 不影响授权本身
+#[ink::trait_definition]
+pub trait AllowanceListener {
+    #[ink(message, selector = 0x4c2d8e17)]
+    fn on_allowance_changed(&mut self, owner: AccountId, old_value: Balance, new_value: Balance);
+}
@@ -0,0 +1,130 @@
+//! 任何人都可以调用的维护任务 (keeper 任务) 以及它们的赏金配置
+
+use ink_env::{DefaultEnvironment, Environment};
+use ink_storage::traits::{PackedLayout, SpreadLayout};
+
+type Balance = <DefaultEnvironment as Environment>::Balance;
+
+/// 发放赏金的维护任务
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    scale::Encode,
+    scale::Decode,
+    SpreadLayout,
+    PackedLayout,
+)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+)]
+pub enum TaskKind {
+    /// 执行到期的定时转账
+    ScheduledTransfer,
+    /// 处理提取队列
+    WithdrawalQueue,
+    /// 清理过期的销毁凭证
+    ReceiptPruning,
+}
+
+/// 赏金金额
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+)]
+pub enum Bounty {
+    None,
+    /// 每处理一项付固定数量
+    Flat(Balance),
+    /// 按处理的金额的比例, 基点
+    Bps(u16),
+}
+
+impl Bounty {
+    /// 处理金额为 basis 的一项应付的赏金
+    pub fn amount(&self, basis: Balance) -> Balance {
+        match self {
+            Bounty::None => 0,
+            Bounty::Flat(amount) => *amount,
+            Bounty::Bps(bps) => {
+                let bps = Balance::from(*bps);
+                (basis / 10_000)
+                    .saturating_mul(bps)
+                    .saturating_add(basis % 10_000 * bps / 10_000)
+            }
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        !matches!(self, Bounty::Bps(bps) if *bps > 10_000)
+    }
+}
+
+/// 赏金从哪里出
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+)]
+pub enum BountySource {
+    /// 增发
+    Mint,
+    /// 从 owner 或任何人注入的赏金池支付, 池子不够时只付剩下的部分
+    Pool,
+    /// 从合约托管的被处理金额中扣除, 不超过被处理的金额
+    Escrow,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+)]
+pub struct BountyConfig {
+    pub bounty: Bounty,
+    pub source: BountySource,
+}
+
+impl Default for BountyConfig {
+    fn default() -> Self {
+        BountyConfig {
+            bounty: Bounty::None,
+            source: BountySource::Mint,
+        }
+    }
+}
+
+/// 一个 keeper 累计领取的赏金
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    scale::Encode,
+    scale::Decode,
+    SpreadLayout,
+    PackedLayout,
+)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+)]
+pub struct KeeperStats {
+    pub earned: Balance,
+    /// 领到非零赏金的处理项数
+    pub tasks: u32,
+}
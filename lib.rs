@@ -1,17 +1,422 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+// dev feature 只给压测网络用, 不能出现在 release 构建里
+#[cfg(all(feature = "dev", not(debug_assertions)))]
+compile_error!("the `dev` feature must not be enabled in release builds");
+
 use ink_lang as ink;
 
+pub mod abi;
+pub mod call;
+pub mod events;
+pub mod features;
+pub mod hooks;
+pub mod keeper;
+pub mod metering;
+#[cfg(feature = "model")]
+pub mod model;
+
+/// 合约对外接口的版本, 任何 message / selector / event 变化都要升级.
+/// 删除或改名已有接口是不兼容变化, 升级主版本; 只新增时升级次版本
+pub const ABI_VERSION: (u16, u16, u16) = (10, 0, 0);
+
+/// ABI_VERSION 发布时的 ABI 哈希. 接口变化后 build.rs 算出的哈希会与它不一致,
+/// 测试失败提醒同时升级 ABI_VERSION 并更新这里
+pub const ABI_HASH_PINNED: &str =
+    "e95c5b5f4a52e8a95e41490db889ec298e20f4f1e78868cc905a8702de75774d";
+
+/// build.rs 根据所有 selector 计算的 ABI 哈希
+pub const ABI_HASH: [u8; 32] = decode_abi_hash(env!("ERC20_ABI_HASH"));
+
+const fn decode_abi_hash(hex: &str) -> [u8; 32] {
+    const fn nibble(c: u8) -> u8 {
+        match c {
+            b'0'..=b'9' => c - b'0',
+            b'a'..=b'f' => c - b'a' + 10,
+            _ => 0,
+        }
+    }
+    let hex = hex.as_bytes();
+    let mut output = [0u8; 32];
+    let mut i = 0;
+    while i < 32 {
+        output[i] = nibble(hex[2 * i]) << 4 | nibble(hex[2 * i + 1]);
+        i += 1;
+    }
+    output
+}
+
 #[ink::contract]
 mod erc20 {
-    use ink_storage::{collections::HashMap, lazy::Lazy};
+    use crate::{
+        call,
+        events::TransferKind,
+        features::*,
+        hooks::{
+            LATEST_PRICE_SELECTOR, MINT_CERTIFICATE_SELECTOR, ON_ALLOWANCE_CHANGED_SELECTOR,
+            ON_BALANCE_CHANGE_SELECTOR, ON_TOKEN_TRANSFER_SELECTOR, PSP22_BALANCE_OF_SELECTOR,
+            PSP22_MINT_SELECTOR,
+        },
+        keeper::{Bounty, BountyConfig, BountySource, KeeperStats, TaskKind},
+    };
+    use ink_env::hash::Blake2x256;
+    use ink_prelude::{collections::BTreeMap, string::String, vec::Vec};
+    use ink_storage::{
+        collections::HashMap,
+        lazy::Lazy,
+        traits::{PackedLayout, SpreadLayout},
+    };
     /// Erc20 的存储结构体
     #[ink(storage)]
     pub struct Erc20 {
         /// total
         total_supply: Lazy<Balance>,
         balances: HashMap<AccountId, Balance>,
-        allowances: HashMap<(AccountId, AccountId), Balance>,
+        allowances: HashMap<(AccountId, AccountId), (Balance, AllowanceKind)>,
+        /// 合约部署者, 拥有 mint 及各种配置权限
+        owner: Lazy<AccountId>,
+        /// 奖励合约地址, 设置后每次余额变化都会回调 on_balance_change
+        rewards_hook: Lazy<Option<AccountId>>,
+        rewards_hook_gas_limit: Lazy<u64>,
+        rewards_hook_policy: Lazy<HookFailurePolicy>,
+        /// 兑换码哈希 -> (金额, 是否已兑换)
+        vouchers: HashMap<Hash, (Balance, bool)>,
+        /// (账户, 铸造区块) -> 该批代币数量, 用于按先进先出追踪代币年龄
+        age_buckets: HashMap<(AccountId, u32), Balance>,
+        /// 账户持有的各批代币的铸造区块, 升序排列
+        age_bucket_blocks: HashMap<AccountId, Vec<u32>>,
+        /// (owner, spender) -> 当前这次授权已经被花掉的总额
+        allowance_spent: HashMap<(AccountId, AccountId), Balance>,
+        /// 账户抽象(ERC-4337)下每个账户的下一个 UserOp nonce
+        user_op_nonces: HashMap<AccountId, u64>,
+        /// 本次部署开启的功能, 见 features 模块中的常量
+        features: Lazy<u32>,
+        /// 暂停, 最小金额, 增发锁定, 等级上限等转账限制, 每笔转账只检查一次
+        policy: Lazy<TransferPolicy>,
+        proposals: HashMap<u64, Proposal>,
+        next_proposal_id: Lazy<u64>,
+        /// (提案, 投票人) -> 是否支持
+        proposal_votes: HashMap<(u64, AccountId), bool>,
+        /// 长期不投票的账户投票权重会衰减
+        voting_weight_decay_enabled: Lazy<bool>,
+        /// 每 1000 个区块不投票衰减的基点数
+        voting_weight_decay_rate: Lazy<u16>,
+        /// 开启衰减的区块, 从未投过票的账户从这里开始计算
+        voting_decay_start_block: Lazy<u32>,
+        last_vote_block: HashMap<AccountId, u32>,
+        /// 弹性供应的目标总量, 0 表示未配置
+        target_supply: Lazy<Balance>,
+        /// 目标上下浮动的区间, 基点
+        supply_band_bps: Lazy<u16>,
+        /// 上一次调节的区块, 从未调节过时为 None
+        last_rebalance_block: Lazy<Option<u32>>,
+        /// 转账手续费的计算方式
+        fee_strategy: Lazy<FeeStrategy>,
+        fee_recipient: Lazy<AccountId>,
+        /// 累计收取的手续费
+        fees_collected: Lazy<Balance>,
+        /// 每个调用者用过的幂等 key 及其使用区块
+        idempotency_keys: HashMap<(AccountId, [u8; 32]), u32>,
+        /// 幂等 key 的有效区块数, 过期后可以再次使用
+        idempotency_key_ttl: Lazy<u32>,
+        price_oracle: Lazy<Option<AccountId>>,
+        /// 稳定基金持有的代币, 存放在合约账户
+        stability_fund_balance: Lazy<Balance>,
+        /// 稳定基金持有的原生币
+        native_reserve: Lazy<Balance>,
+        /// 锚定价格, 以 PRICE_SCALE 为精度
+        stability_peg: Lazy<Balance>,
+        stability_band_bps: Lazy<u16>,
+        /// 账户最近一次收到增发的区块
+        minted_at: HashMap<AccountId, u32>,
+        /// 各账户质押的代币, 存放在合约账户
+        stakes: HashMap<AccountId, Balance>,
+        total_staked: Lazy<Balance>,
+        /// 每单位质押累计的奖励, 以 REWARD_PRECISION 为精度
+        reward_per_token: Lazy<u128>,
+        reward_per_token_paid: HashMap<AccountId, u128>,
+        /// 已结算未领取的奖励
+        staking_rewards: HashMap<AccountId, Balance>,
+        /// 没有人质押时加入的奖励, 留到下一次分配
+        undistributed_rewards: Lazy<Balance>,
+        /// 回购代币中销毁与分给质押者的比例, 基点, 两者之和为 10000
+        buyback_burn_ratio: Lazy<u16>,
+        buyback_stake_ratio: Lazy<u16>,
+        /// 每个 owner 授出的授权总额, (低 128 位, 进位) 精确记账, 读取时饱和到 Balance::MAX
+        total_approved: HashMap<AccountId, (Balance, u32)>,
+        /// 每个 owner 当前授权额度非零的 spender
+        approved_spenders: HashMap<AccountId, Vec<AccountId>>,
+        permit_nonces: HashMap<AccountId, u64>,
+        /// 领取的质押奖励线性释放的区块数, 0 表示立即到账
+        reward_vest_duration: Lazy<u32>,
+        /// (未释放数量, 起始区块, 剩余释放区块数)
+        pending_reward_vests: HashMap<AccountId, (Balance, u32, u32)>,
+        scheduled_transfers: HashMap<u64, ScheduledTransfer>,
+        next_scheduled_id: Lazy<u64>,
+        /// 开启自动复投的账户, 奖励结算时直接计入质押
+        auto_compound_enabled: HashMap<AccountId, bool>,
+        /// 可以触发供应调节的账户
+        authorized_rebasers: HashMap<AccountId, bool>,
+        /// 监控用的计数器
+        transfer_count: Lazy<u64>,
+        total_volume: Lazy<Balance>,
+        /// 余额非零的账户数
+        holder_count: Lazy<u64>,
+        last_activity_block: Lazy<u32>,
+        holder_tiers: HashMap<AccountId, HolderTier>,
+        /// 账户取得代币时的成本价, 以 PRICE_SCALE 为精度, 由预言机写入
+        token_basis_cost: HashMap<AccountId, Balance>,
+        /// 账户累计确认的亏损, 以原生币计
+        harvested_losses: HashMap<AccountId, Balance>,
+        tax_loss_certificate: Lazy<Option<AccountId>>,
+        precision_mode: Lazy<PrecisionMode>,
+        /// 关闭收款的账户
+        receiving_disabled: HashMap<AccountId, bool>,
+        loans: HashMap<u64, Loan>,
+        next_loan_id: Lazy<u64>,
+        /// 转账回调合约, 按注册顺序调用
+        transfer_hooks: Lazy<Vec<AccountId>>,
+        hook_failure_mode: Lazy<HookFailureMode>,
+        /// 把投票权按基点拆分委托给一个或多个账户, 未委托的账户自己持有投票权
+        split_delegations: HashMap<AccountId, Vec<(AccountId, u16)>>,
+        /// 别人委托给该账户的投票权合计
+        delegated_votes: HashMap<AccountId, Balance>,
+        /// 每个账户投票权 (自己的余额加上别人委托的票) 的检查点, 按区块升序, 同一区块只保留最后的值
+        vote_checkpoints: HashMap<AccountId, Vec<(u32, Balance)>>,
+        /// 每个账户最近 MAX_TRANSACTION_COMMITMENTS 笔转出的承诺哈希, 旧的在前
+        transaction_commitments: HashMap<AccountId, Vec<Hash>>,
+        burn_receipts: HashMap<u64, BurnReceipt>,
+        next_burn_receipt_id: Lazy<u64>,
+        /// 最早一张还没清理的销毁凭证
+        oldest_burn_receipt_id: Lazy<u64>,
+        /// 每个账户最近 MAX_BURN_RECEIPTS_PER_ACCOUNT 张凭证的 id
+        burn_receipts_of: HashMap<AccountId, Vec<u64>>,
+        /// 凭证保留的区块数, 0 表示永久保留
+        burn_receipt_retention: Lazy<u32>,
+        /// 销毁数量低于该值的凭证清理时不发赏金, 避免小额销毁刷赏金
+        receipt_bounty_min_amount: Lazy<Balance>,
+        licenses: HashMap<u64, License>,
+        next_license_id: Lazy<u64>,
+        /// 当前的荷兰式拍卖, 代币托管在合约账户
+        auction: Lazy<Option<DutchAuction>>,
+        /// 奖池代币存放在合约账户
+        jackpot_pool: Lazy<Balance>,
+        /// 每笔转账中奖的概率, 基点
+        jackpot_probability_bps: Lazy<u16>,
+        /// 每笔转账注入奖池的比例, 基点
+        jackpot_fee_rate: Lazy<u16>,
+        /// 场外成交, 卖方的代币托管在合约账户
+        deals: HashMap<u64, Deal>,
+        next_deal_id: Lazy<u64>,
+        /// 账户最近一次转入, 转出或授权的 (区块, 时间戳)
+        account_activity: HashMap<AccountId, (u32, Timestamp)>,
+        /// 创世 NFT 的持有人, 部署时为部署者
+        genesis_nft_holder: Lazy<AccountId>,
+        /// 合作质押池, 成员的代币托管在合约账户
+        stake_pools: HashMap<u64, StakePool>,
+        next_stake_pool_id: Lazy<u64>,
+        /// (池, 成员) 上次结算时池的 reward_per_share
+        pool_reward_per_share_paid: HashMap<(u64, AccountId), u128>,
+        /// (池, 成员) 已结算未领取的收益
+        pool_rewards: HashMap<(u64, AccountId), Balance>,
+        /// 可以代用户写入授权的开户运营方
+        onboarders: HashMap<AccountId, bool>,
+        /// 账户声誉分, 影响投票权重和手续费
+        reputation_score: HashMap<AccountId, u32>,
+        /// 每个账户最近的声誉变动, 最多保留 MAX_REPUTATION_EVENTS 条
+        reputation_actions: HashMap<AccountId, Vec<ReputationEvent>>,
+        /// 账户从零开始质押的区块
+        stake_started: HashMap<AccountId, u32>,
+        /// 白皮书内容的 Blake2x256 哈希, 部署时提交
+        whitepaper_hash: Lazy<Hash>,
+        /// 白皮书的位置, 例如 IPFS CID
+        whitepaper_uri: Lazy<String>,
+        /// 排队中的白皮书更新 (哈希, 位置, 最早生效的区块)
+        pending_whitepaper: Lazy<Option<(Hash, String, u32)>>,
+        /// 已迁移的旧账户 -> (新账户, 转发截止的区块)
+        account_forwarding: HashMap<AccountId, (AccountId, u32)>,
+        /// 每个提取周期内即时到账的总额上限, 超出的解除质押和奖励进入提取队列, None 表示不排队
+        instant_withdrawal_limit: Lazy<Option<Balance>>,
+        /// (提取周期序号, 本周期已即时到账的总额)
+        instant_withdrawal_window: Lazy<(u32, Balance)>,
+        /// 排队中的提取请求, 按 id 先进先出
+        withdrawal_requests: HashMap<u64, WithdrawalRequest>,
+        /// 队首请求的 id, 被取消的请求留下的空位在推进时跳过
+        withdrawal_head: Lazy<u64>,
+        next_withdrawal_id: Lazy<u64>,
+        /// 按区块区间生效的手续费率, 覆盖 fee_strategy
+        tax_schedule: Lazy<Vec<TaxScheduleEntry>>,
+        /// 上一次转账时观察到的有效费率, 用于发出 TaxRateChanged
+        last_tax_rate: Lazy<u16>,
+        /// 账户 -> 长期不活动后可以继承其余额的受益人
+        beneficiaries: HashMap<AccountId, Beneficiary>,
+        /// 开启后新质押按当时的全局质押量锁定一段时间
+        dynamic_lockup_enabled: Lazy<bool>,
+        /// (全局质押量门槛, 最短锁定区块数), 门槛递增
+        dynamic_lockup_tiers: Lazy<Vec<(Balance, u32)>>,
+        /// 账户仍在锁定中的质押 (数量, 解锁区块)
+        stake_locks: HashMap<AccountId, Vec<(Balance, u32)>>,
+        /// 部署时是否以 dev feature 编译, 升级代码后仍以部署时为准
+        dev_mode: Lazy<bool>,
+        /// 各维护任务的赏金配置, 没有配置的任务不付赏金
+        task_bounties: HashMap<TaskKind, BountyConfig>,
+        /// 合约账户中留作 BountySource::Pool 赏金的代币
+        bounty_pool: Lazy<Balance>,
+        keeper_stats: HashMap<AccountId, KeeperStats>,
+        /// 协议手续费: 转账手续费中转入金库的比例, 基点
+        protocol_fee_bps: Lazy<u16>,
+        /// 合约账户中属于金库的代币
+        treasury_balance: Lazy<Balance>,
+        treasury_proposals: HashMap<u64, TreasuryProposal>,
+        next_treasury_proposal_id: Lazy<u64>,
+        /// (提案 id, 投票人) -> 是否赞成
+        treasury_votes: HashMap<(u64, AccountId), bool>,
+        /// 发起金库提案至少需要持有的余额
+        treasury_proposal_threshold: Lazy<Balance>,
+        /// 参与投票的权重至少占总供应量的比例, 基点
+        treasury_quorum_bps: Lazy<u16>,
+        escrows: HashMap<u64, Escrow>,
+        next_escrow_id: Lazy<u64>,
+        /// 托管创建后双方可以发起争议的区块数
+        dispute_period_blocks: Lazy<u32>,
+        /// 账户余额从 0 变为正数的区块, 余额清零时删除
+        first_hold_block: HashMap<AccountId, u32>,
+        /// 开启后 cast_vote 按 get_weighted_votes 计票
+        weighted_voting_enabled: Lazy<bool>,
+        /// mint() 每年最多增发年初供应量的比例, 基点, 0 表示不限制
+        max_annual_growth_rate_bps: Lazy<u16>,
+        /// 当前年度通过 mint() 增发的总量
+        total_minted_this_year: Lazy<Balance>,
+        year_start_supply: Lazy<Balance>,
+        year_start_block: Lazy<u32>,
+        /// 当前年度的序号, 部署当年为 0
+        mint_year: Lazy<u32>,
+        /// 大于 0 时 cast_vote 需要销毁这么多代币, 票数等于销毁数量
+        burn_vote_cost: Lazy<Balance>,
+        /// 所有投票累计销毁的数量
+        burned_vote_total: Lazy<Balance>,
+        /// 每个提案收到的投票销毁数量
+        proposal_vote_burns: HashMap<u64, Balance>,
+        /// 钱包展示用的元数据地址, 例如 ipfs://...
+        metadata_uri: Lazy<Option<String>>,
+        logo_hash: Lazy<Option<Hash>>,
+        /// 开启 FEATURE_METADATA_TIMELOCK 时排队中的 (metadata_uri, logo_hash, 生效区块)
+        pending_metadata: Lazy<Option<(Option<String>, Option<Hash>, u32)>>,
+        /// 投票结束后还要等待的区块数, 期间 veto_guardian 可以否决提案
+        execution_grace_period_blocks: Lazy<u32>,
+        veto_guardian: Lazy<Option<AccountId>>,
+        /// 每对授权历史上被花掉的总额和最近一次使用的区块, 撤销授权后保留
+        allowance_usage: HashMap<(AccountId, AccountId), (Balance, u32)>,
+        /// 其他链 ERC-20 快照的 merkle 根, 叶子为 hash(原链地址 || amount)
+        genesis_snapshot_root: Lazy<Hash>,
+        /// 创世领取累计增发的上限
+        genesis_claim_cap: Lazy<Balance>,
+        /// 快照数量换算为本链数量的比例, 基点
+        genesis_conversion_rate: Lazy<u16>,
+        genesis_total_claimed: Lazy<Balance>,
+        genesis_snapshot_claimed: HashMap<AccountId, bool>,
+        /// 已经被领取的快照叶子, 同一个原链地址只能领取一次
+        genesis_claimed_leaves: HashMap<Hash, ()>,
+        /// mint_with_clawback 的增发记录, 追回或到期确认后删除
+        clawback_mints: HashMap<u64, ClawbackMint>,
+        /// 每个账户名下未删除的可追回增发 id
+        clawback_ids_of: HashMap<AccountId, Vec<u64>>,
+        next_clawback_id: Lazy<u64>,
+        team_allocations: HashMap<u64, TeamAllocation>,
+        next_team_allocation_id: Lazy<u64>,
+        /// (发送量门槛, 返还基点), 门槛严格递增; 为空时不统计发送量
+        fee_rebate_tiers: Lazy<Vec<(Balance, u16)>>,
+        fee_rebate_window_blocks: Lazy<u32>,
+        /// 每个发送者最近一个有转出的统计窗口内的发送量和已付手续费
+        sender_fee_windows: HashMap<AccountId, SenderFeeWindow>,
+        /// 已经结束但还没领取的窗口累计的 (最近的窗口编号, 手续费, 返还)
+        settled_fee_rebates: HashMap<AccountId, (u32, Balance, Balance)>,
+        /// 下一个要结算的批次编号
+        optimistic_batch_nonce: Lazy<u64>,
+        /// 链下批量处理转账的运营方, 为 None 时不能结算
+        settlement_operator: Lazy<Option<AccountId>>,
+        /// 结算后可以提出欺诈挑战的区块数
+        challenge_period_blocks: Lazy<u32>,
+        settled_batches: HashMap<u64, SettledBatch>,
+        /// 同意由运营方代为结算转出的账户
+        optimistic_participants: HashMap<AccountId, bool>,
+        /// 每个转出账户下一笔批量转账的 nonce
+        batched_transfer_nonces: HashMap<AccountId, u64>,
+        /// 欢迎奖励活动是否进行中
+        campaign_active: Lazy<bool>,
+        /// 每个新账户的欢迎奖励
+        welcome_bonus: Lazy<Balance>,
+        /// 合约托管的奖励池剩余
+        welcome_pool: Lazy<Balance>,
+        /// 已经处理过首次收款的账户, 每个账户最多领取一次
+        welcomed: HashMap<AccountId, ()>,
+        /// 累计付给 fee_recipient 的手续费
+        recipient_fees_total: Lazy<Balance>,
+        /// 累计转入金库的协议手续费
+        treasury_fees_total: Lazy<Balance>,
+        /// 累计注入奖池的金额
+        jackpot_contributions_total: Lazy<Balance>,
+        /// 收取过手续费的转账笔数
+        fee_collections_count: Lazy<u64>,
+        /// 按区块号保存的手续费统计快照
+        fee_snapshots: HashMap<u32, FeeStatistics>,
+        last_fee_snapshot_block: Lazy<Option<u32>>,
+        next_htlc_id: Lazy<u64>,
+        htlcs: HashMap<u64, Htlc>,
+        /// 领取时公开的原像, 供另一条链上的对手方使用
+        htlc_preimages: HashMap<u64, Vec<u8>>,
+        /// 持有者索引: 位置 -> 账户, 位置从 0 到 holder_count - 1
+        holder_index: HashMap<u64, AccountId>,
+        holder_positions: HashMap<AccountId, u64>,
+        giveaway: Lazy<Option<Giveaway>>,
+        /// 可以签发和撤销合规证明的账户
+        attester: Lazy<Option<AccountId>>,
+        attestations: HashMap<AccountId, ComplianceAttestation>,
+        /// 曾经持有过代币的账户, 只增不减, 需要开启 FEATURE_EVER_HELD
+        ever_held: HashMap<AccountId, ()>,
+        accounts_seen_count: Lazy<u64>,
+        next_recycling_listing_id: Lazy<u64>,
+        recycling_listings: HashMap<u64, RecyclingListing>,
+        /// owner 维护的积分代币白名单: 积分代币 -> 每销毁 1 个代币最多获得的积分
+        credit_token_rates: HashMap<AccountId, Balance>,
+        /// 待分配的协议手续费达到该值时自动分给质押者, 0 表示关闭, 协议手续费照常进金库
+        auto_distribute_threshold: Lazy<Balance>,
+        /// 合约账户中等待自动分配的协议手续费
+        pending_dividends: Lazy<Balance>,
+        next_dividend_snapshot_id: Lazy<u32>,
+        dividend_distributions: HashMap<u32, DividendDistribution>,
+        /// 每个 owner 最近 APPROVAL_HISTORY_LEN 次授权变化, 旧的在前, 需要开启 FEATURE_APPROVAL_HISTORY
+        approval_history: HashMap<AccountId, Vec<ApprovalChange>>,
+        /// 合规签名者的压缩公钥
+        compliance_signer: Lazy<Option<[u8; 33]>>,
+        /// 开启后用户发起的转账 (含批量, 授权转账, UserOp 等入口) 都需要改用带合规签名的版本
+        compliance_required: Lazy<bool>,
+        /// 合规签名验证通过, 正在执行的那一笔 (from, to, value), 由 charge_transfer 消耗
+        compliance_clearance: Lazy<Option<(AccountId, AccountId, Balance)>>,
+        /// 每个转出账户已经用掉的合规签名数
+        compliance_nonces: HashMap<AccountId, u64>,
+        /// (num, den): 以新面额计的数量 = 原始数值 * num / den, 已约分
+        denomination: Lazy<(Balance, Balance)>,
+        /// 排队中的面额调整 (factor_num, factor_den, 生效区块)
+        pending_redenomination: Lazy<Option<(Balance, Balance, u32)>>,
+        /// 每个账户下一笔离线签名转账的 nonce
+        signed_transfer_nonces: HashMap<AccountId, u64>,
+        /// 自己注册为授权回调的合约
+        allowance_listeners: HashMap<AccountId, ()>,
+        /// owner 可以整体关闭授权回调
+        allowance_listeners_enabled: Lazy<bool>,
+        /// 合约账户中替各功能托管的代币, 按托管池记账
+        obligations: HashMap<PoolId, Balance>,
+        /// 开启后只有本人, owner 和被授权的账户能查询余额与授权
+        private_balances: Lazy<bool>,
+        /// (账户, 查看者)
+        view_grants: HashMap<(AccountId, AccountId), ()>,
+        /// (owner, spender) -> (floor, target), 授权低于 floor 时由 keeper 补到 target
+        allowance_maintenance: HashMap<(AccountId, AccountId), (Balance, Balance)>,
+        /// owner 同意每次补足时付给 keeper 的代币
+        maintenance_bounties: HashMap<AccountId, Balance>,
+        /// owner 暂停的授权 (owner, spender), 暂停期间额度保留但不能花费
+        paused_allowances: HashMap<(AccountId, AccountId), ()>,
     }
     /// 事件定义
     #[ink(event)]
@@ -22,6 +427,8 @@ mod erc20 {
         #[ink(topic)]
         to: Option<AccountId>,
         value: Balance,
+        /// `TransferKind` 的 u8 编码
+        kind: u8,
     }
 
     #[ink(event)]
@@ -30,420 +437,22302 @@ mod erc20 {
         owner: AccountId,
         #[ink(topic)]
         spender: AccountId,
+        /// value 的 log2 分桶(0 表示 0), 便于节点按金额量级过滤
+        #[ink(topic)]
+        value_bucket: u8,
         value: Balance,
     }
-    // Error 结构体需要满足的trait bound, 这些trait已经默认引入了
-    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
-    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
-    pub enum Error {
-        InsufficientBalance,
-        InsufficientAllowance,
+
+    #[ink(event)]
+    pub struct VoucherCreated {
+        #[ink(topic)]
+        code_hash: Hash,
+        amount: Balance,
     }
 
-    // 用一个Result类包裹Error
-    pub type Result<T> = core::result::Result<T, Error>;
+    #[ink(event)]
+    pub struct VoucherRedeemed {
+        #[ink(topic)]
+        redeemer: AccountId,
+        amount: Balance,
+    }
 
-    impl Erc20 {
-        //初始化构造函数
-        #[ink(constructor)]
-        pub fn new(supply: Balance) -> Self {
-            let caller = Self::env().caller();
-            let mut balances = HashMap::new();
-            balances.insert(caller, supply);
+    /// 授权额度被完全用尽
+    #[ink(event)]
+    pub struct AllowanceExhausted {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+        total_spent: Balance,
+    }
 
-            Self::env().emit_event(Transfer {
-                from: None,
-                to: Some(caller),
-                value: supply,
-            });
+    #[ink(event)]
+    pub struct UserOpExecuted {
+        #[ink(topic)]
+        sender: AccountId,
+        nonce: u64,
+    }
 
-            Self {
-                total_supply: Lazy::new(supply),
-                balances,
-                allowances: HashMap::new(),
-            }
-        }
-        // 各种get函数
-        #[ink(message)]
-        pub fn total_supply(&self) -> Balance {
-            *self.total_supply
-        }
+    #[ink(event)]
+    pub struct Paused {
+        #[ink(topic)]
+        account: AccountId,
+    }
 
-        #[ink(message)]
-        pub fn balance_of(&self, who: AccountId) -> Balance {
-            self.balances.get(&who).copied().unwrap_or_default()
-        }
+    #[ink(event)]
+    pub struct Unpaused {
+        #[ink(topic)]
+        account: AccountId,
+    }
 
-        #[ink(message)]
-        pub fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
-            self.allowances
-                .get(&(owner, spender))
-                .copied()
-                .unwrap_or_default()
-        }
+    #[ink(event)]
+    pub struct PausedOpsChanged {
+        old_mask: u32,
+        new_mask: u32,
+    }
 
-        //transfer / approve / transfer_from  等会修改状态的方法, 第一参数必须为 &mut self
-        #[ink(message)]
-        pub fn transfer(&mut self, to: AccountId, value: Balance) -> Result<()> {
-            let from = self.env().caller();
+    #[ink(event)]
+    pub struct ProposalCreated {
+        #[ink(topic)]
+        proposal_id: u64,
+        #[ink(topic)]
+        proposer: AccountId,
+        description_hash: Hash,
+        end_block: u32,
+    }
 
-            self.inner_transfer(from, to, value)
-        }
+    #[ink(event)]
+    pub struct VoteCast {
+        #[ink(topic)]
+        proposal_id: u64,
+        #[ink(topic)]
+        voter: AccountId,
+        support: bool,
+        weight: Balance,
+    }
 
-        #[ink(message)]
-        pub fn approve(&mut self, to: AccountId, value: Balance) -> Result<()> {
-            let owner = self.env().caller();
+    #[ink(event)]
+    pub struct VotingWeightDecayed {
+        #[ink(topic)]
+        account: AccountId,
+        old_weight: Balance,
+        new_weight: Balance,
+    }
 
-            self.allowances.insert((owner, to), value);
-            self.env().emit_event(Approval {
-                owner,
-                spender: to,
-                value,
-            });
-            Ok(())
-        }
+    #[ink(event)]
+    pub struct ElasticityRebalanceUp {
+        minted: Balance,
+    }
 
-        #[ink(message)]
-        pub fn transfer_from(
-            &mut self,
-            from: AccountId,
-            to: AccountId,
-            value: Balance,
-        ) -> Result<()> {
-            let caller = self.env().caller();
-            let allowance = self.allowance(from, caller);
-            if allowance < value {
-                return Err(Error::InsufficientAllowance);
-            }
+    #[ink(event)]
+    pub struct ElasticityRebalanceDown {
+        burned: Balance,
+    }
 
-            self.inner_transfer(from, to, value)?;
-            self.allowances.insert((from, caller), allowance - value);
+    #[ink(event)]
+    pub struct IdempotentReplay {
+        #[ink(topic)]
+        caller: AccountId,
+        key: [u8; 32],
+    }
 
-            Ok(())
-        }
-        //私有helper方法
-        fn inner_transfer(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<()> {
-            let from_balance = self.balance_of(from);
-            if from_balance < value {
-                return Err(Error::InsufficientBalance);
-            }
+    #[ink(event)]
+    pub struct StabilityBuy {
+        #[ink(topic)]
+        buyer: AccountId,
+        amount: Balance,
+        paid: Balance,
+    }
 
-            self.balances.insert(from, from_balance - value);
-            let to_balance = self.balance_of(to);
-            self.balances.insert(to, to_balance + value);
-            self.env().emit_event(Transfer {
-                from: Some(from),
-                to: Some(to),
-                value,
-            });
+    #[ink(event)]
+    pub struct StabilitySell {
+        #[ink(topic)]
+        seller: AccountId,
+        amount: Balance,
+        received: Balance,
+    }
 
-            Ok(())
-        }
+    #[ink(event)]
+    pub struct FreshMintLockApplied {
+        #[ink(topic)]
+        account: AccountId,
+        unlock_block: u32,
     }
-    //测试模块, 重点参考https://paritytech.github.io/ink/ink_env/test/index.html 文档
-    //和https://paritytech.github.io/ink-docs/basics/contract-testing
-    #[cfg(test)]
-    mod tests {
-        use super::*;
 
-        // 将事件收敛为一个类型, 参考 https://paritytech.github.io/ink/ink_lang/reflect/trait.ContractEventBase.html
-        // ink_lang::reflect 模块是合约的静态反射, 用来检查合约编译时信息
-        type Event = <Erc20 as ::ink_lang::reflect::ContractEventBase>::Type;
+    #[ink(event)]
+    pub struct MinTransferChanged {
+        minimum: Balance,
+    }
 
-        use ink_lang as ink;
+    #[ink(event)]
+    pub struct Staked {
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct Unstaked {
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct RewardsClaimed {
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct BuybackDistributed {
+        burned: Balance,
+        staking_rewards: Balance,
+    }
+
+    #[ink(event)]
+    pub struct RewardVestStarted {
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+        duration: u32,
+    }
+
+    #[ink(event)]
+    pub struct RewardVestReleased {
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct TransferScheduled {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        creator: AccountId,
+        to: AccountId,
+        value: Balance,
+        execute_at: u32,
+    }
+
+    #[ink(event)]
+    pub struct ScheduledTransferExecuted {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        keeper: AccountId,
+        bounty: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ScheduledTransferCancelled {
+        #[ink(topic)]
+        id: u64,
+    }
+
+    #[ink(event)]
+    pub struct ScheduledTransferExpired {
+        #[ink(topic)]
+        id: u64,
+    }
 
-        struct PrefixedValue<'a, 'b, T> {
-            pub prefix: &'a [u8],
-            pub value: &'b T,
+    #[ink(event)]
+    pub struct AutoCompoundExecuted {
+        #[ink(topic)]
+        account: AccountId,
+        rewards_restaked: Balance,
+    }
+
+    #[ink(event)]
+    pub struct RebaserAdded {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RebaserRemoved {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct TierAssigned {
+        #[ink(topic)]
+        account: AccountId,
+        tier: HolderTier,
+    }
+
+    #[ink(event)]
+    pub struct TaxLossHarvested {
+        #[ink(topic)]
+        account: AccountId,
+        amount_burned: Balance,
+        loss_value: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ReceivingChanged {
+        #[ink(topic)]
+        account: AccountId,
+        enabled: bool,
+    }
+
+    #[ink(event)]
+    pub struct LoanCreated {
+        #[ink(topic)]
+        loan_id: u64,
+        #[ink(topic)]
+        lender: AccountId,
+        principal: Balance,
+        interest_rate: u16,
+        collateral: Balance,
+    }
+
+    #[ink(event)]
+    pub struct LoanAccepted {
+        #[ink(topic)]
+        loan_id: u64,
+        #[ink(topic)]
+        borrower: AccountId,
+        due_block: u32,
+    }
+
+    #[ink(event)]
+    pub struct LoanRepaid {
+        #[ink(topic)]
+        loan_id: u64,
+        repayment: Balance,
+    }
+
+    #[ink(event)]
+    pub struct LoanDefaulted {
+        #[ink(topic)]
+        loan_id: u64,
+        collateral: Balance,
+    }
+
+    #[ink(event)]
+    pub struct HookRegistered {
+        #[ink(topic)]
+        hook: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct HookRemoved {
+        #[ink(topic)]
+        hook: AccountId,
+    }
+
+    /// Lenient 模式下回调失败, reason 为 call 模块中的 CALL_ERROR_* 分类
+    #[ink(event)]
+    pub struct HookCallFailed {
+        #[ink(topic)]
+        hook: AccountId,
+        reason: u8,
+    }
+
+    #[ink(event)]
+    pub struct DelegateChanged {
+        #[ink(topic)]
+        delegator: AccountId,
+        from_delegate: AccountId,
+        to_delegate: AccountId,
+    }
+
+    /// 用户销毁代币时与 Transfer 一起发出, receipt_id 对应 burn_receipt 中的凭证
+    #[ink(event)]
+    pub struct Burn {
+        #[ink(topic)]
+        receipt_id: u64,
+        #[ink(topic)]
+        burner: AccountId,
+        amount: Balance,
+        purpose: [u8; 16],
+    }
+
+    #[ink(event)]
+    pub struct LicenseGranted {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        grantor: AccountId,
+        #[ink(topic)]
+        licensee: AccountId,
+        usage_rights: u8,
+        royalty_rate: u16,
+        expiry_block: u32,
+    }
+
+    #[ink(event)]
+    pub struct LicenseExercised {
+        #[ink(topic)]
+        id: u64,
+        royalty_paid: Balance,
+    }
+
+    #[ink(event)]
+    pub struct LicenseRevoked {
+        #[ink(topic)]
+        id: u64,
+    }
+
+    #[ink(event)]
+    pub struct AuctionStarted {
+        tokens_for_sale: Balance,
+        start_price: Balance,
+        end_price: Balance,
+        end_block: u32,
+    }
+
+    /// price 为本次成交价, 以 PRICE_SCALE 为精度
+    #[ink(event)]
+    pub struct AuctionPurchase {
+        #[ink(topic)]
+        buyer: AccountId,
+        tokens: Balance,
+        price: Balance,
+        cost: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AuctionFinalized {
+        proceeds: Balance,
+        unsold: Balance,
+    }
+
+    #[ink(event)]
+    pub struct JackpotWon {
+        #[ink(topic)]
+        winner: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct JackpotContribution {
+        #[ink(topic)]
+        from: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct DealCreated {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        creator: AccountId,
+        #[ink(topic)]
+        counterparty: AccountId,
+        token_amount: Balance,
+        native_price: Balance,
+        expiry: u32,
+    }
+
+    #[ink(event)]
+    pub struct DealAccepted {
+        #[ink(topic)]
+        id: u64,
+    }
+
+    #[ink(event)]
+    pub struct DealCancelled {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        cancelled_by: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct GenesisNFTTransferred {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct PoolCreated {
+        #[ink(topic)]
+        pool_id: u64,
+        min_members: u8,
+        max_members: u8,
+        yield_rate_bps: u16,
+    }
+
+    #[ink(event)]
+    pub struct PoolJoined {
+        #[ink(topic)]
+        pool_id: u64,
+        #[ink(topic)]
+        member: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct PoolLeft {
+        #[ink(topic)]
+        pool_id: u64,
+        #[ink(topic)]
+        member: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct PoolRewardsClaimed {
+        #[ink(topic)]
+        pool_id: u64,
+        #[ink(topic)]
+        member: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct OnboarderAdded {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct OnboarderRemoved {
+        #[ink(topic)]
+        account: AccountId,
+    }
+
+    /// 开户运营方代 owner 写入授权, 另外还会发出普通的 Approval 事件
+    #[ink(event)]
+    pub struct ApprovalOnBehalf {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+        value: Balance,
+        #[ink(topic)]
+        onboarder: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ReputationChanged {
+        #[ink(topic)]
+        account: AccountId,
+        action: ReputationAction,
+        delta: i32,
+        score: u32,
+    }
+
+    #[ink(event)]
+    pub struct ProposalFinalized {
+        #[ink(topic)]
+        proposal_id: u64,
+        passed: bool,
+    }
+
+    #[ink(event)]
+    pub struct WhitepaperUpdateQueued {
+        #[ink(topic)]
+        new_hash: Hash,
+        new_uri: String,
+        eta: u32,
+    }
+
+    #[ink(event)]
+    pub struct WhitepaperUpdated {
+        #[ink(topic)]
+        old_hash: Hash,
+        #[ink(topic)]
+        new_hash: Hash,
+        new_uri: String,
+    }
+
+    #[ink(event)]
+    pub struct AccountMigrated {
+        #[ink(topic)]
+        old_account: AccountId,
+        #[ink(topic)]
+        new_account: AccountId,
+        balance: Balance,
+        forward_until: u32,
+    }
+
+    #[ink(event)]
+    pub struct BatchBurnExecuted {
+        accounts_count: u32,
+        total_burned: Balance,
+    }
+
+    #[ink(event)]
+    pub struct WithdrawalQueued {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        who: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct WithdrawalProcessed {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        who: AccountId,
+        amount: Balance,
+        #[ink(topic)]
+        keeper: AccountId,
+        bounty: Balance,
+    }
+
+    #[ink(event)]
+    pub struct WithdrawalCancelled {
+        #[ink(topic)]
+        id: u64,
+    }
+
+    #[ink(event)]
+    pub struct TaxScheduleUpdated {
+        entries_count: u32,
+    }
+
+    #[ink(event)]
+    pub struct TaxRateChanged {
+        old_rate: u16,
+        new_rate: u16,
+        block: u32,
+    }
+
+    /// beneficiary 为 None 表示取消
+    #[ink(event)]
+    pub struct BeneficiarySet {
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        beneficiary: Option<AccountId>,
+        inactivity_blocks: u32,
+    }
+
+    #[ink(event)]
+    pub struct InheritanceClaimed {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        beneficiary: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct LockupTierChanged {
+        new_min_blocks: u32,
+    }
+
+    #[ink(event)]
+    pub struct BountyPoolFunded {
+        #[ink(topic)]
+        from: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct TreasuryProposalSubmitted {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        proposer: AccountId,
+        recipient: AccountId,
+        amount: Balance,
+        description_hash: Hash,
+    }
+
+    #[ink(event)]
+    pub struct TreasuryVoteCast {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        voter: AccountId,
+        support: bool,
+        weight: Balance,
+    }
+
+    #[ink(event)]
+    pub struct TreasurySpend {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        recipient: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct EscrowCreated {
+        #[ink(topic)]
+        escrow_id: u64,
+        #[ink(topic)]
+        payer: AccountId,
+        #[ink(topic)]
+        payee: AccountId,
+        amount: Balance,
+        arbitrator: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct EscrowReleased {
+        #[ink(topic)]
+        escrow_id: u64,
+    }
+
+    #[ink(event)]
+    pub struct EscrowDisputed {
+        #[ink(topic)]
+        escrow_id: u64,
+        #[ink(topic)]
+        opener: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct EscrowArbitrated {
+        #[ink(topic)]
+        escrow_id: u64,
+        recipient: AccountId,
+        split: u16,
+    }
+
+    #[ink(event)]
+    pub struct EscrowDisputeExpired {
+        #[ink(topic)]
+        escrow_id: u64,
+        payer: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AnnualCounterReset {
+        year: u32,
+        minted: Balance,
+        start_supply: Balance,
+    }
+
+    #[ink(event)]
+    pub struct BurnedVoteCast {
+        #[ink(topic)]
+        voter: AccountId,
+        #[ink(topic)]
+        proposal_id: u64,
+        support: bool,
+        burned_amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct MetadataUpdateQueued {
+        metadata_uri: Option<String>,
+        logo_hash: Option<Hash>,
+        eta: u32,
+    }
+
+    /// content_hash 为 (metadata_uri, logo_hash) 编码后的 Blake2x256 哈希, 客户端据此判断缓存是否过期
+    #[ink(event)]
+    pub struct MetadataUpdated {
+        #[ink(topic)]
+        content_hash: Hash,
+        metadata_uri: Option<String>,
+        logo_hash: Option<Hash>,
+    }
+
+    #[ink(event)]
+    pub struct ProposalVetoed {
+        #[ink(topic)]
+        proposal_id: u64,
+        #[ink(topic)]
+        guardian: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct GenesisClaimed {
+        #[ink(topic)]
+        claimer: AccountId,
+        original_address: Vec<u8>,
+        /// 快照中的数量
+        amount: Balance,
+        /// 按换算比例实际增发的数量
+        minted: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ClawbackMinted {
+        #[ink(topic)]
+        mint_id: u64,
+        #[ink(topic)]
+        recipient: AccountId,
+        minter: AccountId,
+        amount: Balance,
+        expires_at: u32,
+    }
+
+    #[ink(event)]
+    pub struct ClawedBack {
+        #[ink(topic)]
+        mint_id: u64,
+        #[ink(topic)]
+        recipient: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ClawbackMatured {
+        #[ink(topic)]
+        mint_id: u64,
+        #[ink(topic)]
+        recipient: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AllocationCreated {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        beneficiary: AccountId,
+        total: Balance,
+        cliff_block: u32,
+    }
+
+    /// stage_index 为本次释放时已解锁的最后一个阶段
+    #[ink(event)]
+    pub struct AllocationStageReleased {
+        #[ink(topic)]
+        id: u64,
+        stage_index: u32,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct FeeRebateClaimed {
+        #[ink(topic)]
+        account: AccountId,
+        window: u32,
+        fees_paid: Balance,
+        rebate: Balance,
+    }
+
+    #[ink(event)]
+    pub struct BatchSettled {
+        #[ink(topic)]
+        nonce: u64,
+        transfer_count: u32,
+    }
+
+    #[ink(event)]
+    pub struct BatchChallenged {
+        #[ink(topic)]
+        nonce: u64,
+    }
+
+    #[ink(event)]
+    pub struct BatchFinalized {
+        #[ink(topic)]
+        nonce: u64,
+    }
+
+    #[ink(event)]
+    pub struct CampaignStarted {
+        bonus: Balance,
+        pool: Balance,
+    }
+
+    #[ink(event)]
+    pub struct CampaignStopped {
+        /// 退回 owner 的剩余奖励池
+        refunded: Balance,
+    }
+
+    #[ink(event)]
+    pub struct WelcomeBonusPaid {
+        #[ink(topic)]
+        account: AccountId,
+        bonus: Balance,
+    }
+
+    /// 奖励池不足以支付下一份奖励, 活动仍然进行但不再发放
+    #[ink(event)]
+    pub struct WelcomePoolExhausted {
+        remaining: Balance,
+    }
+
+    #[ink(event)]
+    pub struct HtlcLocked {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        hashlock: Hash,
+        sender: AccountId,
+        recipient: AccountId,
+        value: Balance,
+        timelock: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct HtlcClaimed {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        hashlock: Hash,
+        preimage: Vec<u8>,
+    }
+
+    #[ink(event)]
+    pub struct HtlcRefunded {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        hashlock: Hash,
+    }
+
+    #[ink(event)]
+    pub struct SplitDelegationSet {
+        #[ink(topic)]
+        delegator: AccountId,
+        /// 为空表示取消全部委托
+        delegations: Vec<(AccountId, u16)>,
+    }
+
+    #[ink(event)]
+    pub struct GiveawayStarted {
+        #[ink(topic)]
+        commit: Hash,
+        prize: Balance,
+    }
+
+    /// 抽奖分页进行中, cursor 为下一次从持有者索引的哪个位置继续
+    #[ink(event)]
+    pub struct GiveawayProgress {
+        cursor: u64,
+        cumulative: Balance,
+    }
+
+    #[ink(event)]
+    pub struct GiveawayWinner {
+        #[ink(topic)]
+        winner: AccountId,
+        prize: Balance,
+        /// 抽中的位置, 落在 winner 的累计余额区间内
+        target: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AttestationIssued {
+        #[ink(topic)]
+        subject: AccountId,
+        hash: Hash,
+        expiry: u32,
+    }
+
+    #[ink(event)]
+    pub struct AttestationRevoked {
+        #[ink(topic)]
+        subject: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RecyclingListingCreated {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        lister: AccountId,
+        amount: Balance,
+        credit_token: AccountId,
+        credit_per_burned: Balance,
+    }
+
+    #[ink(event)]
+    pub struct RecyclingListingFilled {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        filler: AccountId,
+        amount: Balance,
+        credits: Balance,
+    }
+
+    #[ink(event)]
+    pub struct RecyclingListingCancelled {
+        #[ink(topic)]
+        id: u64,
+        /// 退回挂单人的押金
+        refunded: Balance,
+    }
+
+    /// 批量转账中连续 count 个收款人的汇总, 金额为转账请求的金额 (含手续费)
+    #[ink(event)]
+    pub struct BatchTransferChunk {
+        #[ink(topic)]
+        from: AccountId,
+        recipients_hash: Hash,
+        total: Balance,
+        count: u32,
+    }
+
+    #[ink(event)]
+    pub struct AutoDistributionTriggered {
+        #[ink(topic)]
+        snapshot_id: u32,
+        total_amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ComplianceCheckPassed {
+        #[ink(topic)]
+        from: AccountId,
+        #[ink(topic)]
+        to: AccountId,
+        value: Balance,
+    }
+
+    #[ink(event)]
+    pub struct RedenominationQueued {
+        factor_num: Balance,
+        factor_den: Balance,
+        eta: u32,
+    }
+
+    #[ink(event)]
+    pub struct Redenominated {
+        factor_num: Balance,
+        factor_den: Balance,
+    }
+
+    #[ink(event)]
+    pub struct SignedTransferExecuted {
+        #[ink(topic)]
+        from: AccountId,
+        nonce: u64,
+        #[ink(topic)]
+        relayer: AccountId,
+        relayer_fee: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AllowanceListenerRegistered {
+        #[ink(topic)]
+        listener: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct AllowanceListenerRemoved {
+        #[ink(topic)]
+        listener: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ViewGranted {
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        viewer: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ViewRevoked {
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        viewer: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct AllowanceMaintained {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+        old_value: Balance,
+        new_value: Balance,
+        keeper: AccountId,
+        bounty: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AllowancePaused {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct AllowanceResumed {
+        #[ink(topic)]
+        owner: AccountId,
+        #[ink(topic)]
+        spender: AccountId,
+    }
+
+    // Error 结构体需要满足的trait bound, 这些trait已经默认引入了
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum Error {
+        InsufficientBalance,
+        InsufficientAllowance,
+        NotOwner,
+        Overflow,
+        /// 跨合约调用失败, code 取值见 call 模块中的 CALL_ERROR_* 常量
+        ExternalCall {
+            callee: AccountId,
+            selector: [u8; 4],
+            code: u8,
+        },
+        VoucherNotFound,
+        VoucherAlreadyRedeemed,
+        VoucherAlreadyExists,
+        /// 列表合计金额超出 Balance 上限
+        AggregateOverflow,
+        BatchTooLarge,
+        InvalidUserOpSignature,
+        InvalidNonce,
+        /// 当前区块不在 UserOp 的 valid_after..=valid_until 之内
+        UserOpOutsideWindow,
+        /// call_data 中的 selector 不是 transfer/approve/transfer_from
+        UnsupportedUserOpCall,
+        /// 本次部署没有开启该功能
+        FeatureDisabled,
+        Paused,
+        ProposalNotFound,
+        VotingClosed,
+        AlreadyVoted,
+        /// 提案创建的区块内不能投票, 投票权按该区块结束时的检查点计算
+        VotingNotStarted,
+        RebalanceTooSoon,
+        ElasticityNotConfigured,
+        /// 基点参数超过 10000
+        InvalidBps,
+        NothingToWithdraw,
+        OracleNotSet,
+        /// 价格仍在锚定区间内, 稳定基金不介入
+        PriceWithinBand,
+        InsufficientStabilityFund,
+        InsufficientNativeReserve,
+        /// 支付的原生币与应付金额不符
+        IncorrectPayment,
+        NativeTransferFailed,
+        /// 刚增发的代币还在锁定期内
+        FreshlyMintedTokensLocked,
+        /// 转账金额低于 minimum
+        BelowMinimum {
+            minimum: Balance,
+        },
+        InsufficientStake,
+        /// 比例之和不等于 10000
+        InvalidRatios,
+        PermitExpired,
+        InvalidPermitSignature,
+        ScheduleNotFound,
+        /// 还没到执行区块
+        ScheduleNotReady,
+        /// 已超过执行宽限期, 只能退款
+        ScheduleExpired,
+        ScheduleNotExpired,
+        NotScheduleCreator,
+        /// 执行区块必须在当前区块之后
+        InvalidSchedule,
+        AutoCompoundDisabled,
+        NotAuthorizedRebaser,
+        /// 收款方余额会超过其等级的持仓上限
+        TierCapExceeded,
+        /// 当前价格不低于成本价, 没有亏损可以确认
+        NoPriceDrop,
+        NotOracle,
+        /// transfer_fixed 传入的小数位数与合约的精度模式不符
+        PrecisionMismatch,
+        /// 收款方已关闭收款
+        RecipientOptedOut,
+        LoanNotFound,
+        /// 借款已被接受, 不能再接受或撤回
+        LoanAlreadyAccepted,
+        /// 借款还没被接受, 或者已经还清/清算
+        LoanNotActive,
+        NotLender,
+        NotBorrower,
+        /// 出借人不能接受自己的借款
+        SelfLoan,
+        /// 还没到期, 不能清算
+        LoanNotDue,
+        /// 转账回调已达 MAX_TRANSFER_HOOKS 个
+        TooManyHooks,
+        HookAlreadyRegistered,
+        HookNotFound,
+        /// 没有设置凭证保留期, 不能清理
+        ReceiptPruningDisabled,
+        /// 数量不能为 0
+        ZeroAmount,
+        LicenseNotFound,
+        LicenseExpired,
+        /// 只有被授权人可以行使许可
+        UnauthorizedLicenseExercise,
+        NotLicenseGrantor,
+        /// 已有拍卖尚未结算
+        AuctionActive,
+        NoAuction,
+        /// 拍卖已售罄或过了结束区块
+        AuctionEnded,
+        AuctionNotEnded,
+        /// 价格需满足 start_price >= end_price > 0, 且 duration_blocks > 0
+        InvalidAuction,
+        DealNotFound,
+        /// 只有指定的对手方可以接受
+        NotDealCounterparty,
+        /// 到期前只有创建者可以取消
+        NotDealCreator,
+        DealExpired,
+        /// 已成交或已取消
+        DealAlreadySettled,
+        NotGenesisHolder,
+        PoolNotFound,
+        /// 成员数已达 max_members
+        PoolFull,
+        /// 第一次加入池的数量低于池的 min_contribution
+        BelowMinContribution,
+        NotPoolMember,
+        /// 需满足 0 < min_members <= max_members
+        InvalidPool,
+        NotOnboarder,
+        /// 投票期还没结束
+        VotingOpen,
+        ProposalAlreadyFinalized,
+        /// 固定手续费超过转账金额
+        FeeExceedsValue,
+        /// 分档门槛需严格递增, 且不超过 MAX_FEE_TIERS 档
+        InvalidFeeTiers,
+        NoPendingWhitepaper,
+        /// 白皮书更新还在等待期内
+        TimelockNotExpired,
+        /// 新账户不能与旧账户相同
+        InvalidMigration,
+        /// 两个列表长度不一致
+        LengthMismatch,
+        /// 提取队列已满, 需要等 keeper 处理
+        WithdrawalQueueFull,
+        WithdrawalNotFound,
+        NotWithdrawalOwner,
+        /// 区间需满足 start_block < end_block, 按 start_block 排序且互不重叠, 至多 MAX_TAX_SCHEDULE_ENTRIES 条
+        InvalidTaxSchedule,
+        /// 受益人不能是自己, 不活动期需大于 0
+        InvalidBeneficiary,
+        NoBeneficiary,
+        NotBeneficiary,
+        /// 账户不活动的时间还不够
+        InheritanceNotClaimable,
+        /// 门槛需严格递增, 锁定区块数不递减, 至多 MAX_LOCKUP_TIERS 档
+        InvalidLockupTiers,
+        /// 解除质押的数量超过已解锁的部分
+        StakeLocked,
+        /// 合约部署时没有开启 dev feature
+        DevModeDisabled,
+        /// 金库余额不足以支付提案金额
+        TreasuryInsufficientFunds,
+        /// 金库提案已经执行过
+        ProposalAlreadyExecuted,
+        /// 持有量低于发起金库提案的门槛
+        BelowProposalThreshold,
+        /// 投票权重没有达到法定人数
+        QuorumNotReached,
+        /// 赞成票没有超过反对票
+        ProposalNotPassed,
+        EscrowNotFound,
+        /// 托管已经放款或已经进入争议
+        EscrowNotOpen,
+        /// 调用者或收款人不是托管的付款人或收款人
+        NotEscrowParty,
+        /// 争议期内只有付款人可以放款
+        EscrowLocked,
+        /// 已经超过可以发起争议的期限
+        DisputePeriodExpired,
+        /// 托管没有处于争议中
+        NoDisputeOpen,
+        /// 调用者不是托管指定的仲裁人
+        NotArbitrator,
+        /// 本年度增发已达到年增长率上限
+        AnnualMintCapExceeded,
+        /// 距离本年度开始还不满一年
+        YearNotElapsed,
+        /// 余额不足以支付投票需要销毁的代币
+        InsufficientBalanceForVote,
+        /// 元数据地址超过 MAX_METADATA_URI_LEN 字节
+        UriTooLong,
+        /// 没有排队中的元数据更新
+        NoPendingMetadata,
+        /// 提案还在投票结束后的等待期内
+        ProposalInGracePeriod,
+        /// 调用者不是否决守护人
+        NotVetoGuardian,
+        /// 只能在投票结束后的等待期内否决
+        NotInGracePeriod,
+        /// 提案已被否决, 不能再结算
+        ProposalWasVetoed,
+        /// merkle 证明与快照根不匹配
+        InvalidMerkleProof,
+        /// 调用者或该原链地址已经领取过
+        GenesisAlreadyClaimed,
+        /// 创世领取累计数量超过上限
+        GenesisClaimCapExceeded,
+        /// 追回窗口必须至少 1 个区块
+        InvalidClawbackWindow,
+        /// 账户名下的可追回增发已达 MAX_CLAWBACK_ENTRIES 条
+        TooManyClawbackEntries,
+        ClawbackNotFound,
+        /// 调用者不是这笔增发的发起人
+        NotMinter,
+        /// 追回窗口已经结束
+        ClawbackWindowClosed,
+        /// 追回窗口还没有结束
+        ClawbackWindowOpen,
+        /// 转出或销毁后余额会低于追回期内锁定的数量
+        ClawbackLocked,
+        /// 释放阶段为空, 超过 MAX_ALLOCATION_STAGES, 区块不递增或比例之和不是 10000
+        InvalidAllocationStages,
+        AllocationNotFound,
+        /// 还没有到分配的 cliff 区块
+        CliffNotReached,
+        /// 已解锁的部分都已释放
+        NothingToRelease,
+        /// 返还档位为空以外的情况下门槛需要严格递增, 基点不超过 10000, 最多 MAX_FEE_REBATE_TIERS 档
+        InvalidRebateTiers,
+        /// 当前窗口的手续费返还已经领取过
+        FeeRebateAlreadyClaimed,
+        /// 调用者不是结算运营方
+        NotSettlementOperator,
+        /// 批次签名不是运营方签的
+        InvalidSettlementSignature,
+        /// 转出账户没有同意由运营方结算
+        NotOptimisticParticipant,
+        BatchNotFound,
+        /// 已经超过挑战期
+        ChallengePeriodOver,
+        /// 证明中的签名是有效的, 转账列表与批次不符, 或者批次的状态根是正确的
+        InvalidFraudProof,
+        BatchAlreadyRolledBack,
+        BatchAlreadyFinalized,
+        /// 还在挑战期内, 不能完成批次
+        ChallengePeriodNotOver,
+        /// 奖励为 0 或奖励池不足一份奖励
+        InvalidCampaign,
+        CampaignAlreadyActive,
+        NoActiveCampaign,
+        /// 距上次手续费统计快照不足 FEE_SNAPSHOT_INTERVAL_BLOCKS
+        FeeSnapshotTooSoon,
+        FeeSnapshotNotFound,
+        HtlcNotFound,
+        /// 哈希时间锁已经被领取或退款
+        HtlcAlreadySettled,
+        /// 时间锁不晚于当前时间
+        InvalidTimelock,
+        NotHtlcRecipient,
+        /// 原像的 Blake2x256 哈希与 hashlock 不符
+        WrongPreimage,
+        /// 已经到达时间锁, 不能再领取
+        HtlcExpired,
+        /// 还没到时间锁, 不能退款
+        HtlcNotExpired,
+        /// 拆分委托为空, 超过 MAX_SPLIT_DELEGATEES 个, 有重复或为 0 的份额, 或基点之和不是 10000
+        InvalidDelegationSplit,
+        GiveawayActive,
+        NoGiveaway,
+        /// 距开始抽奖不足 GIVEAWAY_MIN_DELAY_BLOCKS
+        GiveawayTooEarly,
+        /// 秘密的哈希与开始抽奖时的承诺不符
+        InvalidGiveawaySecret,
+        /// 除合约账户外没有持有者
+        NoEligibleHolders,
+        /// 分页开奖进行中, 开奖结束前余额不能变化
+        GiveawayDrawInProgress,
+        NotAttester,
+        /// 证明的到期区块不晚于当前区块
+        InvalidAttestationExpiry,
+        AttestationNotFound,
+        /// 开启证明模式时, 转账双方中有账户没有未过期的合规证明
+        NotAttested,
+        /// 挂单数量或兑换比例为 0
+        InvalidRecyclingListing,
+        RecyclingListingNotFound,
+        NotRecyclingLister,
+        /// 回收数量超过挂单剩余额度
+        RecyclingAmountExceeded,
+        /// 积分代币不在白名单中, 或者挂单的兑换比例超过白名单上限
+        CreditTokenNotWhitelisted,
+        /// Chunked 的块大小为 0
+        InvalidEventMode,
+        /// 开启了合规模式, 转账需要合规签名
+        ComplianceSignatureMissing,
+        InvalidComplianceSignature,
+        ComplianceSignatureExpired,
+        ComplianceSignerNotSet,
+        /// 面额调整的分子或分母为 0
+        InvalidDenomination,
+        /// 以新面额计的数量换算成原始数值不是整数
+        InexactDenomination,
+        NoPendingRedenomination,
+        InvalidTransferSignature,
+        SignedTransferExpired,
+        AllowanceListenerAlreadyRegistered,
+        AllowanceListenerNotRegistered,
+        NotAuthorizedToView,
+        ViewNotGranted,
+        InvalidMaintenanceRule,
+        MaintenanceRuleNotFound,
+        AllowancePaused,
+        AllowanceNotPaused,
+        /// 各池托管的总额超过了合约账户余额, 账本有缺口
+        EscrowDeficit,
+        /// 争议已过仲裁期限, 只能按默认结果退回付款人
+        ArbitrationExpired,
+    }
+
+    /// 奖励回调失败时的处理策略
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum HookFailurePolicy {
+        /// 回调失败则整笔转账失败
+        Revert,
+        /// 忽略回调失败, 转账照常完成
+        Ignore,
+    }
+
+    /// 一个账户在一次操作中的余额变化, 交给 after_token_transfer 处理
+    #[derive(Debug, Clone, Copy)]
+    struct BalanceChange {
+        account: AccountId,
+        old_balance: Balance,
+        new_balance: Balance,
+    }
+
+    /// 一次操作中的全部余额变化和账户间转账, 写入完成后统一通知外部合约
+    #[derive(Debug, Default)]
+    struct TokenChanges {
+        balances: Vec<BalanceChange>,
+        /// (from, to, value), 不含增发和销毁
+        transfers: Vec<(AccountId, AccountId, Balance)>,
+    }
+
+    impl TokenChanges {
+        fn single(change: BalanceChange) -> Self {
+            TokenChanges {
+                balances: ink_prelude::vec![change],
+                transfers: Vec::new(),
+            }
+        }
+    }
+
+    /// 创世快照 merkle 树中叶子与中间节点的哈希前缀
+    pub const MERKLE_LEAF_PREFIX: u8 = 0x00;
+    pub const MERKLE_NODE_PREFIX: u8 = 0x01;
+    /// 所有接受列表参数的消息共用的最大列表长度, 超出直接报错而不是耗尽 gas
+    pub const MAX_BATCH_LEN: usize = 100;
+
+    /// 可以通过 UserOp 派发的消息 selector, 定义在 abi 模块
+    pub use crate::abi::{APPROVE_SELECTOR, TRANSFER_FROM_SELECTOR, TRANSFER_SELECTOR};
+
+    /// 精简版的 ERC-4337 UserOperation
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct UserOp {
+        pub sender: AccountId,
+        pub nonce: u64,
+        /// selector + SCALE 编码的参数
+        pub call_data: Vec<u8>,
+        /// 65 字节的 ecdsa 签名
+        pub signature: Vec<u8>,
+        /// 从这个区块开始有效
+        pub valid_after: u32,
+        /// 最后有效的区块, 0 表示不过期
+        pub valid_until: u32,
+    }
+
+    /// ERC-4337 的 validationData, 有效期按区块号而不是时间戳
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct ValidationData {
+        pub sig_failed: bool,
+        pub valid_until: u32,
+        pub valid_after: u32,
+    }
+
+    impl ValidationData {
+        /// 按 ERC-4337 打包成大端的 uint256: 低 160 位为 authorizer (签名失败时为 1),
+        /// 160..208 位为 validUntil, 208..256 位为 validAfter
+        pub fn pack(&self) -> [u8; 32] {
+            let mut packed = [0u8; 32];
+            packed[..6].copy_from_slice(&u64::from(self.valid_after).to_be_bytes()[2..]);
+            packed[6..12].copy_from_slice(&u64::from(self.valid_until).to_be_bytes()[2..]);
+            packed[31] = u8::from(self.sig_failed);
+            packed
+        }
+    }
+    /// 治理提案
+    #[derive(
+        Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct Proposal {
+        pub id: u64,
+        pub proposer: AccountId,
+        pub description_hash: Hash,
+        pub for_votes: Balance,
+        pub against_votes: Balance,
+        pub start_block: u32,
+        pub end_block: u32,
+        /// 投票结束后已经结算
+        pub executed: bool,
+        /// 最早可以结算的区块, 为 end_block 加上创建时的等待期
+        pub eta: u32,
+        pub vetoed: bool,
+        /// 提案人以外投过票的账户数
+        pub voters: u32,
+    }
+    pub use crate::abi::value_bucket;
+    /// 两次弹性调节之间至少间隔的区块数
+    pub const REBALANCE_INTERVAL_BLOCKS: u32 = 100;
+    /// 每次调节偏差的比例(基点), 避免一次调节过头
+    pub const REBALANCE_STEP_BPS: u128 = 1_000;
+    /// 幂等 key 默认有效期, 约一天 (6 秒出块)
+    pub const DEFAULT_IDEMPOTENCY_KEY_TTL: u32 = 14_400;
+    /// 预言机价格的精度, 价格为 PRICE_SCALE 表示 1 个代币值 1 个原生币
+    pub const PRICE_SCALE: Balance = 1_000_000;
+    pub const ORACLE_GAS_LIMIT: u64 = 5_000_000_000;
+    /// 质押奖励累计值的精度
+    pub const REWARD_PRECISION: u128 = 1_000_000_000_000;
+    /// 额度为 Balance::MAX 的授权视为无限授权, transfer_from 不会扣减
+    pub const INFINITE_ALLOWANCE: Balance = Balance::MAX;
+    /// 定时转账到期后可以执行的区块数, 超过后只能退款给创建者
+    pub const SCHEDULE_GRACE_BLOCKS: u32 = 1_000;
+
+    /// 定时转账, 金额在创建时转入合约账户托管
+    #[derive(
+        Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct ScheduledTransfer {
+        pub creator: AccountId,
+        pub to: AccountId,
+        pub value: Balance,
+        pub execute_at: u32,
+    }
+    /// `metrics()` 返回的监控快照. 合约不维护事件序号, 也没有供应上限, 所以不提供对应的指标
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Metrics {
+        pub transfer_count: u64,
+        pub total_volume: Balance,
+        pub holder_count: u64,
+        pub total_supply: Balance,
+        pub paused: bool,
+        pub fees_collected: Balance,
+        pub last_activity_block: u32,
+    }
+
+    /// BatchTransferChunk 的 recipients_hash: 这一块 (收款人, 金额) 列表 SCALE 编码的 Blake2x256,
+    /// 供索引器校验链下提供的批量转账明细
+    #[cfg(feature = "std")]
+    pub fn batch_chunk_hash(chunk: &[(AccountId, Balance)]) -> Hash {
+        let mut output = [0u8; 32];
+        ink_env::hash_encoded::<Blake2x256, _>(&chunk, &mut output);
+        Hash::from(output)
+    }
+
+    /// 把监控快照转成 Prometheus 文本格式, 供链下抓取程序使用
+    #[cfg(feature = "std")]
+    pub fn metrics_to_prometheus(metrics: &Metrics) -> String {
+        let samples = [
+            (
+                "transfer_count",
+                "counter",
+                metrics.transfer_count.to_string(),
+            ),
+            ("total_volume", "counter", metrics.total_volume.to_string()),
+            ("holder_count", "gauge", metrics.holder_count.to_string()),
+            ("total_supply", "gauge", metrics.total_supply.to_string()),
+            ("paused", "gauge", (metrics.paused as u8).to_string()),
+            (
+                "fees_collected",
+                "counter",
+                metrics.fees_collected.to_string(),
+            ),
+            (
+                "last_activity_block",
+                "gauge",
+                metrics.last_activity_block.to_string(),
+            ),
+        ];
+        samples
+            .iter()
+            .map(|(name, kind, value)| {
+                format!("# TYPE erc20_{0} {1}\nerc20_{0} {2}\n", name, kind, value)
+            })
+            .collect()
+    }
+    /// 持有者等级, 未指定的账户为 Retail
+    #[derive(
+        Debug,
+        Clone,
+        Copy,
+        PartialEq,
+        Eq,
+        PartialOrd,
+        Ord,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum HolderTier {
+        Retail,
+        Institutional,
+        Protocol,
+    }
+    /// 可以单独暂停的操作, `paused_ops` 中的位
+    pub const PAUSE_TRANSFER: u32 = 1 << 0;
+    pub const PAUSE_TRANSFER_FROM: u32 = 1 << 1;
+    pub const PAUSE_APPROVE: u32 = 1 << 2;
+    pub const PAUSE_MINT: u32 = 1 << 3;
+    pub const PAUSE_BURN: u32 = 1 << 4;
+    /// pause() 设置的全部位. 合约内部划转 (质押, 托管的放款和退款, 手续费等) 只在全部暂停时停止:
+    /// 只暂停 PAUSE_TRANSFER 时, 已经承诺的托管支付和奖励照常发放
+    pub const PAUSE_ALL: u32 =
+        PAUSE_TRANSFER | PAUSE_TRANSFER_FROM | PAUSE_APPROVE | PAUSE_MINT | PAUSE_BURN;
+    /// 所有转账限制的配置, 整体存在一个存储单元里
+    #[derive(
+        Debug,
+        Clone,
+        Default,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct TransferPolicy {
+        /// 被暂停的操作, PAUSE_* 的组合, 只在开启 FEATURE_PAUSABLE 时生效
+        pub paused_ops: u32,
+        /// 单笔转账的最小金额, 0 表示不限制
+        pub min_transfer: Balance,
+        /// 增发后需要等待的区块数, 0 表示不锁定
+        pub mint_transfer_delay_blocks: u32,
+        /// 各等级账户的持仓上限, 未配置的等级不限
+        pub tier_caps: Vec<(HolderTier, Balance)>,
+        /// 用户发起的转账要求双方都有未过期的合规证明
+        pub require_attestation: bool,
+    }
+    /// 一笔转账从哪里发起, 决定适用哪些检查
+    #[derive(Debug, Clone, Copy)]
+    enum TransferOrigin {
+        /// 用户发起, 按用户请求的总额(含手续费)检查最小金额
+        User(Balance),
+        /// spender 通过 transfer_from 发起, 检查同 User
+        Delegated(Balance),
+        /// 合约内部划转: 托管, 清空账户
+        Internal,
+        /// 手续费和奖池注入. 收款方是 owner 配置的协议账户, 不检查收款开关和等级上限,
+        /// 否则收款方一关闭收款, 所有收手续费的转账都会失败
+        Fee,
+        /// 批量转账中的一笔, 检查同 User; 不单独发 Transfer 事件, 由 BatchTransferChunk 汇总
+        Batched(Balance),
+        /// 托管资金退回原主, 不检查收款开关
+        Refund,
+        /// 创世 NFT 持有人紧急取回自己的质押, 不受任何转账限制
+        Emergency,
+    }
+
+    impl TransferOrigin {
+        /// 这次划转受哪些暂停位控制, 全部被设置时才暂停
+        fn pause_bits(self) -> u32 {
+            match self {
+                TransferOrigin::User(_) | TransferOrigin::Batched(_) => PAUSE_TRANSFER,
+                TransferOrigin::Delegated(_) => PAUSE_TRANSFER_FROM,
+                TransferOrigin::Internal
+                | TransferOrigin::Fee
+                | TransferOrigin::Refund
+                | TransferOrigin::Emergency => PAUSE_ALL,
+            }
+        }
+    }
+    /// TransferPolicy::check 的输入. 账户相关的数据只在对应限制开启时才读取
+    struct TransferCtx<'a> {
+        from: AccountId,
+        to: AccountId,
+        value: Balance,
+        origin: TransferOrigin,
+        block: u32,
+        /// 合约自己的账户, 托管资金都存在这里
+        contract: AccountId,
+        pausable: bool,
+        minted_at: &'a HashMap<AccountId, u32>,
+        receiving_disabled: &'a HashMap<AccountId, bool>,
+        holder_tiers: &'a HashMap<AccountId, HolderTier>,
+        balances: &'a HashMap<AccountId, Balance>,
+        clawback_ids_of: &'a HashMap<AccountId, Vec<u64>>,
+        clawback_mints: &'a HashMap<u64, ClawbackMint>,
+        attestations: &'a HashMap<AccountId, ComplianceAttestation>,
+    }
+
+    impl TransferCtx<'_> {
+        fn lookup<V: Copy + PackedLayout>(
+            map: &HashMap<AccountId, V>,
+            key: &AccountId,
+        ) -> Option<V> {
+            crate::metering::note_storage_read();
+            map.get(key).copied()
+        }
+
+        /// account 名下还在追回窗口内的增发总额
+        fn clawback_locked(
+            ids_of: &HashMap<AccountId, Vec<u64>>,
+            mints: &HashMap<u64, ClawbackMint>,
+            account: &AccountId,
+            block: u32,
+        ) -> Balance {
+            crate::metering::note_storage_read();
+            let ids = match ids_of.get(account) {
+                Some(ids) => ids,
+                None => return 0,
+            };
+            ids.iter()
+                .filter_map(|id| {
+                    crate::metering::note_storage_read();
+                    mints.get(id)
+                })
+                .filter(|mint| block < mint.expires_at)
+                .fold(0, |locked: Balance, mint| {
+                    locked.saturating_add(mint.amount)
+                })
+        }
+
+        fn is_attested(&self, account: &AccountId) -> bool {
+            TransferCtx::lookup(self.attestations, account)
+                .map_or(false, |attestation| self.block < attestation.expiry_block)
+        }
+    }
+
+    impl TransferPolicy {
+        /// ops 中的位全部被暂停时拒绝; 未开启 FEATURE_PAUSABLE 时, 即使设置了也不生效
+        fn ensure_op_allowed(&self, ops: u32, pausable: bool) -> Result<()> {
+            if pausable && self.paused_ops & ops == ops {
+                return Err(Error::Paused);
+            }
+            Ok(())
+        }
+
+        /// 按固定顺序检查, 不需要读存储的放前面:
+        /// 暂停, 最小金额, 增发锁定, 收款开关, 等级上限
+        fn check(&self, ctx: &TransferCtx) -> Result<()> {
+            if let TransferOrigin::Emergency = ctx.origin {
+                return Ok(());
+            }
+            crate::metering::note_storage_read();
+            self.ensure_op_allowed(ctx.origin.pause_bits(), ctx.pausable)?;
+            if let TransferOrigin::User(requested)
+            | TransferOrigin::Batched(requested)
+            | TransferOrigin::Delegated(requested) = ctx.origin
+            {
+                self.check_minimum(requested)?;
+            }
+            // 合约账户里是各种托管资金, 弹性调节增发到这里也不能卡住托管的支付和退款.
+            // 持有人自己把新增发的代币存入托管仍然受锁定限制, 否则可以绕过托管转给别人
+            if self.mint_transfer_delay_blocks > 0
+                && ctx.from != ctx.contract
+                && !matches!(ctx.origin, TransferOrigin::Refund)
+            {
+                if let Some(minted) = TransferCtx::lookup(ctx.minted_at, &ctx.from) {
+                    if ctx.block < minted.saturating_add(self.mint_transfer_delay_blocks) {
+                        return Err(Error::FreshlyMintedTokensLocked);
+                    }
+                }
+            }
+            if ctx.from == ctx.to {
+                return Ok(());
+            }
+            let locked = TransferCtx::clawback_locked(
+                ctx.clawback_ids_of,
+                ctx.clawback_mints,
+                &ctx.from,
+                ctx.block,
+            );
+            if locked > 0 {
+                let balance = TransferCtx::lookup(ctx.balances, &ctx.from).unwrap_or(0);
+                // 余额本身不足时交给 InsufficientBalance
+                if balance >= ctx.value && balance - ctx.value < locked {
+                    return Err(Error::ClawbackLocked);
+                }
+            }
+            if !matches!(ctx.origin, TransferOrigin::Refund | TransferOrigin::Fee)
+                && TransferCtx::lookup(ctx.receiving_disabled, &ctx.to).unwrap_or(false)
+            {
+                return Err(Error::RecipientOptedOut);
+            }
+            // 合约账户只是托管方, 不要求证明; 内部划转的另一方 (奖励, 托管支付的最终收款人) 仍然要持证.
+            // 退款只是把托管资金还给原主, 不检查
+            if self.require_attestation && !matches!(ctx.origin, TransferOrigin::Refund) {
+                let attested =
+                    |account: &AccountId| *account == ctx.contract || ctx.is_attested(account);
+                if !(attested(&ctx.from) && attested(&ctx.to)) {
+                    return Err(Error::NotAttested);
+                }
+            }
+            // 上限只约束用户之间的转账. 合约账户里是所有人的托管, 手续费和托管的支付退款
+            // 也不能被收款方的上限卡住
+            if !self.tier_caps.is_empty()
+                && ctx.to != ctx.contract
+                && !matches!(
+                    ctx.origin,
+                    TransferOrigin::Internal | TransferOrigin::Fee | TransferOrigin::Refund
+                )
+            {
+                let tier =
+                    TransferCtx::lookup(ctx.holder_tiers, &ctx.to).unwrap_or(HolderTier::Retail);
+                if let Some(cap) = self.tier_cap(tier) {
+                    let balance = TransferCtx::lookup(ctx.balances, &ctx.to).unwrap_or(0);
+                    match balance.checked_add(ctx.value) {
+                        Some(new_balance) if new_balance <= cap => {}
+                        Some(_) => return Err(Error::TierCapExceeded),
+                        None => return Err(Error::Overflow),
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        fn check_minimum(&self, value: Balance) -> Result<()> {
+            if value < self.min_transfer {
+                return Err(Error::BelowMinimum {
+                    minimum: self.min_transfer,
+                });
+            }
+            Ok(())
+        }
+
+        fn tier_cap(&self, tier: HolderTier) -> Option<Balance> {
+            self.tier_caps
+                .iter()
+                .find(|(configured, _)| *configured == tier)
+                .map(|(_, cap)| *cap)
+        }
+
+        fn set_tier_cap(&mut self, tier: HolderTier, cap: Balance) {
+            match self
+                .tier_caps
+                .iter_mut()
+                .find(|(configured, _)| *configured == tier)
+            {
+                Some(entry) => entry.1 = cap,
+                None => self.tier_caps.push((tier, cap)),
+            }
+        }
+    }
+    /// 决定一笔转账能否通过以及手续费多少的参数. 实际转账借用存储中的当前配置,
+    /// simulate_policy 借用应用了候选修改的副本
+    #[derive(Clone, Copy)]
+    struct PolicyConfig<'a> {
+        policy: &'a TransferPolicy,
+        fee_strategy: &'a FeeStrategy,
+        /// 税率时间表在当前区块的费率, 优先于 fee_strategy
+        scheduled_rate_bps: Option<u16>,
+        fees_enabled: bool,
+        jackpot_fee_rate: u16,
+    }
+    /// simulate_policy 试算的一项参数修改, 与对应的 set_* 消息一致
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum ParamChange {
+        /// set_min_transfer
+        MinTransfer(Balance),
+        /// set_mint_transfer_delay
+        MintTransferDelay(u32),
+        /// set_tier_cap
+        TierCap(HolderTier, Balance),
+        /// set_attestation_mode
+        RequireAttestation(bool),
+        /// set_fee_strategy
+        FeeStrategy(FeeStrategy),
+    }
+    /// 一笔样本转账在当前和候选配置下的结果, Ok 中为手续费
+    #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct SimResult {
+        pub current: Result<Balance>,
+        pub candidate: Result<Balance>,
+    }
+    /// 余额的定点精度, 存储的始终是原始整数
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum PrecisionMode {
+        /// 余额就是代币数量
+        Standard,
+        /// 余额为数量 * 10^18
+        FixedPoint18,
+        /// 余额为数量 * 10^6, 与 6 位小数的稳定币一致
+        FixedPoint6,
+    }
+
+    impl PrecisionMode {
+        pub fn decimals(self) -> u8 {
+            match self {
+                PrecisionMode::Standard => 0,
+                PrecisionMode::FixedPoint18 => 18,
+                PrecisionMode::FixedPoint6 => 6,
+            }
+        }
+
+        /// 1 个完整代币对应的原始数值
+        pub fn unit(self) -> Balance {
+            10u128.pow(u32::from(self.decimals()))
+        }
+    }
+    /// 点对点借款. 出借人创建时代币托管在合约账户, 借款人用原生币作抵押接受
+    #[derive(
+        Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct Loan {
+        pub id: u64,
+        pub lender: AccountId,
+        /// 接受前为 None
+        pub borrower: Option<AccountId>,
+        pub principal: Balance,
+        /// 原生币抵押数量
+        pub collateral: Balance,
+        /// 整个借款期的利率, 基点
+        pub interest_rate: u16,
+        pub duration_blocks: u32,
+        /// 接受后才确定
+        pub due_block: u32,
+        pub repaid: bool,
+        pub defaulted: bool,
+    }
+    /// 转账回调失败时的处理方式
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum HookFailureMode {
+        /// 任一回调失败则整笔转账失败
+        Strict,
+        /// 发出 HookCallFailed 后继续调用后面的回调
+        Lenient,
+    }
+
+    /// 最多注册的转账回调数量
+    pub const MAX_TRANSFER_HOOKS: usize = 5;
+    pub const TRANSFER_HOOK_GAS_LIMIT: u64 = 5_000_000_000;
+    pub const ALLOWANCE_LISTENER_GAS_LIMIT: u64 = 2_000_000_000;
+    /// permit, delegate_by_sig, approve_on_behalf 与 migrate_account 共用一套 nonce,
+    /// 签名哈希以不同的类型字节开头, 一种签名不能被当作另一种提交
+    pub const PERMIT_TYPE_TAG: u8 = 0x01;
+    pub const DELEGATION_TYPE_TAG: u8 = 0x02;
+    pub const ONBOARDING_TYPE_TAG: u8 = 0x03;
+    pub const MIGRATION_TYPE_TAG: u8 = 0x04;
+    /// 结算批次签名的类型字节, 批次有自己的 nonce
+    pub const SETTLEMENT_TYPE_TAG: u8 = 0x05;
+    pub const COMPLIANCE_TYPE_TAG: u8 = 0x06;
+    pub const SIGNED_TRANSFER_TYPE_TAG: u8 = 0x07;
+    pub const SINGLE_USE_PERMIT_TYPE_TAG: u8 = 0x08;
+    pub const ALLOWANCE_REASSIGN_TYPE_TAG: u8 = 0x09;
+    pub const BATCHED_TRANSFER_TYPE_TAG: u8 = 0x0a;
+    /// 每个账户保留的交易承诺数量
+    pub const MAX_TRANSACTION_COMMITMENTS: usize = 20;
+    /// 每个账户保留的代币年龄批次数量, 超出时最早的批次并入下一批
+    pub const MAX_AGE_BUCKETS: usize = 32;
+    /// approval_history 为每个 owner 保留的记录数
+    pub const APPROVAL_HISTORY_LEN: usize = 10;
+    /// 销毁凭证, 供跨链桥和赎回系统证明某账户在某区块销毁了多少代币
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct BurnReceipt {
+        pub burner: AccountId,
+        pub amount: Balance,
+        pub block: u32,
+        /// 由调用方定义的用途, 普通 burn 为全 0
+        pub purpose: [u8; 16],
+    }
+
+    /// 每个账户索引中保留的凭证数量, 更早的凭证仍可通过 id 查询
+    pub const MAX_BURN_RECEIPTS_PER_ACCOUNT: usize = 50;
+    /// 持币人授予他人的使用许可, 每次行使按使用金额向授权人支付版税
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct License {
+        pub id: u64,
+        pub grantor: AccountId,
+        pub licensee: AccountId,
+        /// 使用权限位, 含义由应用自行约定
+        pub usage_rights: u8,
+        /// 版税率, 基点
+        pub royalty_rate: u16,
+        /// 最后有效的区块
+        pub expiry_block: u32,
+    }
+    /// 荷兰式拍卖: 价格从 start_price 按区块线性降到 end_price, 以 PRICE_SCALE 为精度
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct DutchAuction {
+        pub tokens_for_sale: Balance,
+        pub remaining: Balance,
+        pub start_price: Balance,
+        pub end_price: Balance,
+        pub start_block: u32,
+        pub duration_blocks: u32,
+        /// 已收到的原生币
+        pub proceeds: Balance,
+    }
+
+    impl DutchAuction {
+        /// 最后一个可以出价的区块
+        pub fn end_block(&self) -> u32 {
+            self.start_block.saturating_add(self.duration_blocks)
+        }
+
+        /// block 时的价格, 结束后保持 end_price
+        pub fn price_at(&self, block: u32) -> Balance {
+            let elapsed = block
+                .saturating_sub(self.start_block)
+                .min(self.duration_blocks);
+            let drop = self.start_price - self.end_price;
+            self.start_price
+                - drop / Balance::from(self.duration_blocks) * Balance::from(elapsed)
+                - drop % Balance::from(self.duration_blocks) * Balance::from(elapsed)
+                    / Balance::from(self.duration_blocks)
+        }
+
+        pub fn is_over(&self, block: u32) -> bool {
+            self.remaining == 0 || block > self.end_block()
+        }
+    }
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum DealStatus {
+        Open,
+        Settled,
+        Cancelled,
+    }
+
+    /// 场外成交: 创建者以 native_price 原生币把 token_amount 代币卖给指定的对手方
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct Deal {
+        pub creator: AccountId,
+        pub counterparty: AccountId,
+        pub token_amount: Balance,
+        pub native_price: Balance,
+        /// 最后可以接受的区块
+        pub expiry: u32,
+        pub status: DealStatus,
+    }
+    /// yield_rate_bps 是每 POOL_YIELD_PERIOD_BLOCKS 个区块 (约一天) 的收益率
+    pub const POOL_YIELD_PERIOD_BLOCKS: u32 = 14_400;
+
+    /// 合作质押池: 成员数达到 min_members 后按 yield_rate_bps 计息, 不足时减半.
+    /// 份额按加入的代币数量 1:1 记账. 与最初设计的 u64 份额不同, 这里用 Balance 保存,
+    /// 否则 18 位小数的代币只能存入约 18 个
+    #[derive(
+        Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct StakePool {
+        pub id: u64,
+        pub members: Vec<AccountId>,
+        pub total_staked: Balance,
+        pub shares: BTreeMap<AccountId, Balance>,
+        pub yield_rate_bps: u16,
+        pub min_members: u8,
+        pub max_members: u8,
+        /// 新成员第一次加入至少托管的数量, 0 表示只要求非 0
+        pub min_contribution: Balance,
+        /// 每份额累计的收益, 放大 REWARD_PRECISION 倍
+        pub reward_per_share: u128,
+        /// 上次计息的区块
+        pub last_accrual_block: u32,
+    }
+
+    impl StakePool {
+        /// 当前生效的收益率
+        pub fn effective_yield_bps(&self) -> u16 {
+            if self.members.len() >= usize::from(self.min_members) {
+                self.yield_rate_bps
+            } else {
+                self.yield_rate_bps / 2
+            }
+        }
+
+        /// 按当前收益率计息到 block
+        fn accrue(&mut self, block: u32) {
+            let elapsed = block.saturating_sub(self.last_accrual_block);
+            self.last_accrual_block = block;
+            if self.total_staked == 0 || elapsed == 0 {
+                return;
+            }
+            let rate = u128::from(self.effective_yield_bps());
+            let reward = self
+                .total_staked
+                .saturating_mul(rate)
+                .saturating_mul(u128::from(elapsed))
+                / 10_000
+                / u128::from(POOL_YIELD_PERIOD_BLOCKS);
+            self.reward_per_share = self
+                .reward_per_share
+                .saturating_add(reward.saturating_mul(REWARD_PRECISION) / self.total_staked);
+        }
+
+        fn shares_of(&self, member: &AccountId) -> Balance {
+            self.shares.get(member).copied().unwrap_or_default()
+        }
+    }
+    /// 每个账户保留的声誉变动记录数量
+    pub const MAX_REPUTATION_EVENTS: usize = 50;
+    /// 连续质押超过这么多区块 (约一周) 后解除质押记为长期持有
+    pub const LONG_TERM_HOLDER_BLOCKS: u32 = 100_800;
+    /// 声誉加成的上限, 基点
+    pub const MAX_REPUTATION_BONUS_BPS: u16 = 50;
+    /// 借款至少借出这么多区块 (约一天) 才在还清时加分
+    pub const MIN_REPUTATION_LOAN_BLOCKS: u32 = 14_400;
+    /// 借款本金至少为总供应量的这么多基点才在还清时加分
+    pub const MIN_REPUTATION_LOAN_BPS: u16 = 10;
+    /// 通过的提案加分需要的投票总权重, 总供应量的基点
+    pub const REPUTATION_QUORUM_BPS: u16 = 400;
+    /// 通过的提案加分需要的提案人以外的投票账户数
+    pub const MIN_REPUTATION_VOTERS: u32 = 3;
+
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum ReputationAction {
+        /// 借款按时还清
+        CompletedLoan,
+        /// 借款逾期被清算
+        Defaulted,
+        /// 发起的提案通过
+        ProposalPassed,
+        /// 发起的提案未通过
+        ProposalFailed,
+        /// 长期质押后解除
+        LongTermHolder,
+    }
+
+    impl ReputationAction {
+        /// 每种行为对声誉分的影响
+        pub fn delta(self) -> i32 {
+            match self {
+                ReputationAction::CompletedLoan => 1_000,
+                ReputationAction::Defaulted => -5_000,
+                ReputationAction::ProposalPassed => 2_000,
+                ReputationAction::ProposalFailed => -1_000,
+                ReputationAction::LongTermHolder => 3_000,
+            }
+        }
+    }
+
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct ReputationEvent {
+        pub action: ReputationAction,
+        pub block: u32,
+        pub delta: i32,
+    }
+    /// 分档手续费最多的档数
+    pub const MAX_FEE_TIERS: usize = 10;
+
+    /// 转账手续费的计算方式, 每个部署按需选择
+    #[derive(
+        Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum FeeStrategy {
+        None,
+        /// 每笔转账收固定数量
+        FlatPerTransfer(Balance),
+        /// 按转账金额的比例, 基点
+        Bps(u16),
+        /// (门槛, 基点), 取不超过转账金额的最高门槛对应的费率, 低于最低门槛时不收费
+        Tiered(Vec<(Balance, u16)>),
+    }
+
+    impl FeeStrategy {
+        /// 按比例收费时的费率, 其他方式为 0
+        pub fn rate_bps(&self) -> u16 {
+            match self {
+                FeeStrategy::Bps(bps) => *bps,
+                _ => 0,
+            }
+        }
+
+        /// value 对应的手续费, 固定手续费超过 value 时拒绝
+        pub fn compute_fee(&self, value: Balance) -> Result<Balance> {
+            self.compute_discounted_fee(value, 0)
+        }
+
+        /// 按比例收费时费率先减去 discount_bps, 固定手续费不打折
+        pub fn compute_discounted_fee(&self, value: Balance, discount_bps: u16) -> Result<Balance> {
+            let bps = match self {
+                FeeStrategy::None => return Ok(0),
+                FeeStrategy::FlatPerTransfer(fee) => {
+                    if *fee > value {
+                        return Err(Error::FeeExceedsValue);
+                    }
+                    return Ok(*fee);
+                }
+                FeeStrategy::Bps(bps) => *bps,
+                FeeStrategy::Tiered(tiers) => tiers
+                    .iter()
+                    .rev()
+                    .find(|(threshold, _)| *threshold <= value)
+                    .map(|(_, bps)| *bps)
+                    .unwrap_or(0),
+            };
+            let bps = u128::from(bps.saturating_sub(discount_bps));
+            (value / 10_000)
+                .checked_mul(bps)
+                .and_then(|high| high.checked_add(value % 10_000 * bps / 10_000))
+                .ok_or(Error::Overflow)
+        }
+
+        /// 费率不超过 100%, 分档门槛严格递增
+        fn validate(&self) -> Result<()> {
+            match self {
+                FeeStrategy::None | FeeStrategy::FlatPerTransfer(_) => Ok(()),
+                FeeStrategy::Bps(bps) if *bps > 10_000 => Err(Error::InvalidBps),
+                FeeStrategy::Bps(_) => Ok(()),
+                FeeStrategy::Tiered(tiers) => {
+                    if tiers.len() > MAX_FEE_TIERS || tiers.windows(2).any(|w| w[0].0 >= w[1].0) {
+                        return Err(Error::InvalidFeeTiers);
+                    }
+                    if tiers.iter().any(|(_, bps)| *bps > 10_000) {
+                        return Err(Error::InvalidBps);
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+    /// 白皮书更新排队后至少等待的区块数 (约两天), 持币人可以在此期间检查新文档
+    pub const WHITEPAPER_TIMELOCK_BLOCKS: u32 = 28_800;
+    /// 面额调整排队后至少等待的区块数 (约两天)
+    pub const REDENOMINATION_TIMELOCK_BLOCKS: u32 = 28_800;
+    /// 账户迁移后旧账户继续转发收款的区块数 (约一周)
+    pub const MIGRATION_FORWARDING_BLOCKS: u32 = 100_800;
+
+    /// 提取队列中同时存在的请求上限 (含已取消留下的空位)
+    pub const MAX_QUEUED_WITHDRAWALS: u64 = 100;
+    /// 即时提取额度的统计周期 (约一天)
+    pub const WITHDRAWAL_PERIOD_BLOCKS: u32 = 14_400;
+
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct WithdrawalRequest {
+        pub who: AccountId,
+        pub amount: Balance,
+        pub requested_at: u32,
+    }
+
+    pub const MAX_TAX_SCHEDULE_ENTRIES: usize = 10;
+
+    /// [start_block, end_block) 区间内转账手续费率为 fee_rate_bps
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct TaxScheduleEntry {
+        pub start_block: u32,
+        pub end_block: u32,
+        pub fee_rate_bps: u16,
+    }
+
+    impl TaxScheduleEntry {
+        pub fn covers(&self, block: u32) -> bool {
+            self.start_block <= block && block < self.end_block
+        }
+    }
+
+    /// 账户自 last_active_block 起 inactivity_blocks 个区块内没有转出, 销毁或授权时,
+    /// who 可以领取其全部余额
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct Beneficiary {
+        pub who: AccountId,
+        pub inactivity_blocks: u32,
+        pub last_active_block: u32,
+    }
+
+    impl Beneficiary {
+        /// 最早可以领取的区块
+        pub fn claimable_at(&self) -> u32 {
+            self.last_active_block
+                .saturating_add(self.inactivity_blocks)
+        }
+    }
+
+    pub const MAX_LOCKUP_TIERS: usize = 10;
+    /// 每个账户单独记录的锁定批次上限, 超出后并入最后一批
+    pub const MAX_STAKE_LOCKS: usize = 20;
+
+    /// populate_test_holders 单次调用最多写入的账户数
+    pub const MAX_POPULATE_CHUNK: u32 = 1_000;
+
+    /// revoke_all_allowances 单次调用最多撤销的授权数
+    pub const MAX_ALLOWANCES_REVOKED: usize = 50;
+
+    /// 金库提案的投票期
+    pub const TREASURY_VOTING_PERIOD_BLOCKS: u32 = 14_400;
+    pub const DEFAULT_TREASURY_QUORUM_BPS: u16 = 400;
+
+    /// 金库支出提案
+    #[derive(
+        Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct TreasuryProposal {
+        pub id: u64,
+        pub recipient: AccountId,
+        pub amount: Balance,
+        pub description_hash: Hash,
+        pub votes_for: Balance,
+        pub votes_against: Balance,
+        pub executed: bool,
+        pub created_block: u32,
+    }
+
+    impl TreasuryProposal {
+        pub fn voting_end(&self) -> u32 {
+            self.created_block
+                .saturating_add(TREASURY_VOTING_PERIOD_BLOCKS)
+        }
+    }
+
+    pub const DEFAULT_DISPUTE_PERIOD_BLOCKS: u32 = 14_400;
+    /// 发起争议后仲裁人裁决的期限. 过期后任何人都可以按默认结果把托管全额退回付款人
+    pub const ARBITRATION_PERIOD_BLOCKS: u32 = 100_800;
+
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum EscrowStatus {
+        Open,
+        Disputed,
+        Released,
+        Arbitrated,
+        /// 仲裁人逾期未裁决, 已全额退回付款人
+        DisputeExpired,
+    }
+
+    /// 付款人托管在合约账户中的一笔款项
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct Escrow {
+        pub payer: AccountId,
+        pub payee: AccountId,
+        pub arbitrator: AccountId,
+        pub amount: Balance,
+        pub created_block: u32,
+        /// 创建时的 dispute_period_blocks, 之后修改不影响已有托管
+        pub dispute_deadline: u32,
+        /// 发起争议时确定, 仲裁人在此区块之后不能再裁决. 没有争议时为 0
+        pub arbitration_deadline: u32,
+        pub status: EscrowStatus,
+    }
+
+    impl Escrow {
+        fn is_party(&self, account: AccountId) -> bool {
+            account == self.payer || account == self.payee
+        }
+    }
+
+    /// 持有时长每满一档, 投票倍数增加 HOLDING_MULTIPLIER_STEP_BPS
+    pub const HOLDING_MULTIPLIER_STEP_BLOCKS: u32 = 100_800;
+    pub const HOLDING_MULTIPLIER_STEP_BPS: u16 = 2_500;
+    pub const MAX_HOLDING_MULTIPLIER_BPS: u16 = 20_000;
+
+    /// floor(sqrt(n)), 牛顿迭代, 初值取不小于 sqrt(n) 的 2 的幂
+    pub fn integer_sqrt(n: Balance) -> Balance {
+        if n < 2 {
+            return n;
+        }
+        let bits = 128 - n.leading_zeros();
+        let mut x: Balance = 1 << ((bits + 1) / 2);
+        loop {
+            let y = (x + n / x) / 2;
+            if y >= x {
+                return x;
+            }
+            x = y;
+        }
+    }
+
+    /// portfolio_of 一次最多查询的外部代币数
+    pub const MAX_PORTFOLIO_TOKENS: usize = 16;
+    /// portfolio_of 中每次外部 balance_of 调用的 gas 上限
+    pub const PORTFOLIO_GAS_LIMIT: u64 = 1_000_000_000;
+
+    /// 按 6 秒出块估算的一年区块数
+    pub const BLOCKS_PER_YEAR: u32 = 5_256_000;
+
+    /// `contract_info()` 返回的合约概要, 钱包一次调用即可拿到展示需要的信息
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct ContractInfo {
+        pub abi_version: (u16, u16, u16),
+        pub owner: AccountId,
+        pub features: u32,
+        pub whitepaper_hash: Hash,
+        pub metadata_uri: Option<String>,
+        pub logo_hash: Option<Hash>,
+    }
+    /// metadata_uri 的最大字节数
+    pub const MAX_METADATA_URI_LEN: usize = 200;
+    /// 开启 FEATURE_METADATA_TIMELOCK 时元数据更新排队后等待的区块数 (约两天)
+    pub const METADATA_TIMELOCK_BLOCKS: u32 = 28_800;
+
+    /// 追回窗口内可以被发起人追回的增发, 窗口内这部分代币不能转出
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct ClawbackMint {
+        pub minter: AccountId,
+        pub recipient: AccountId,
+        pub amount: Balance,
+        /// 从这个区块起不能再追回
+        pub expires_at: u32,
+    }
+    /// 每个账户同时存在的可追回增发上限, 到期的记录可以用 mature_clawback 清除
+    pub const MAX_CLAWBACK_ENTRIES: usize = 20;
+
+    /// 团队或投资人的分阶段解锁分配, 代币在创建时从 owner 转入合约托管
+    #[derive(
+        Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct TeamAllocation {
+        pub beneficiary: AccountId,
+        pub total: Balance,
+        /// 这个区块之前什么都不释放, 之前到期的阶段在 cliff 时一起释放
+        pub cliff_block: u32,
+        /// (解锁区块, 比例基点), 区块严格递增, 比例之和为 10000
+        pub stages: Vec<(u32, u16)>,
+        pub released: Balance,
+    }
+
+    impl TeamAllocation {
+        /// 截至 block 已解锁的总量和最后一个已解锁阶段的下标
+        pub fn unlocked_at(&self, block: u32) -> (Balance, Option<u32>) {
+            if block < self.cliff_block {
+                return (0, None);
+            }
+            let mut bps: u32 = 0;
+            let mut last = None;
+            for (index, (unlock_block, stage_bps)) in self.stages.iter().enumerate() {
+                if *unlock_block > block {
+                    break;
+                }
+                bps += u32::from(*stage_bps);
+                last = Some(index as u32);
+            }
+            // 全部解锁时直接取 total, 避免按比例取整留下零头
+            let unlocked = if bps >= 10_000 {
+                self.total
+            } else {
+                let bps = Balance::from(bps);
+                self.total / 10_000 * bps + self.total % 10_000 * bps / 10_000
+            };
+            (unlocked, last)
+        }
+    }
+    /// 一个分配最多的解锁阶段数
+    pub const MAX_ALLOCATION_STAGES: usize = 24;
+
+    /// 发送者在一个统计窗口内的发送量, 窗口编号为 区块号 / 窗口长度
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct SenderFeeWindow {
+        pub window: u32,
+        pub volume: Balance,
+        pub fees_paid: Balance,
+        pub claimed: bool,
+    }
+    /// 手续费返还的默认统计窗口, 约 30 天 (6 秒出块)
+    pub const FEE_REBATE_WINDOW_BLOCKS: u32 = 432_000;
+    pub const MAX_FEE_REBATE_TIERS: usize = 10;
+
+    /// 已经在链上执行的链下批次. state_root 应为批次执行后涉及账户余额列表
+    /// (按在 transfers 中首次出现的顺序, 每笔先 from 后 to) SCALE 编码的 Blake2x256 哈希.
+    /// 结算时不校验 state_root, 只记录执行前的余额, 挑战时据此重算
+    #[derive(
+        Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct SettledBatch {
+        /// settlement_hash, 挑战时用来核对提交的转账列表和签名
+        pub batch_hash: Hash,
+        pub state_root: Hash,
+        pub transfers: Vec<(AccountId, AccountId, Balance)>,
+        /// 执行前涉及账户的余额, 顺序同 state_root
+        pub pre_state: Vec<(AccountId, Balance)>,
+        pub settled_block: u32,
+        pub rolled_back: bool,
+        /// 挑战期结束后已付给收款方
+        pub finalized: bool,
+    }
+
+    /// 批次中的一笔转账, signature 为 from 对 batched_transfer_hash 的签名
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct BatchedTransfer {
+        pub from: AccountId,
+        pub to: AccountId,
+        pub value: Balance,
+        pub nonce: u64,
+        pub signature: [u8; 65],
+    }
+
+    /// 挑战已结算批次的欺诈证明
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum FraudProof {
+        /// 批次的完整转账列表, 其中第 index 笔的签名不是转出账户签的
+        UnauthorizedTransfer {
+            transfers: Vec<BatchedTransfer>,
+            index: u32,
+        },
+        /// 批次执行后余额列表的 SCALE 编码. 它必须与链上重算的结果一致,
+        /// 且哈希与运营方提交的 state_root 不同
+        WrongStateRoot { post_state: Vec<u8> },
+    }
+
+    impl SettledBatch {
+        /// 按执行前余额和转账列表重算的执行后余额
+        pub fn post_state(&self) -> Vec<(AccountId, Balance)> {
+            let mut state = self.pre_state.clone();
+            for (from, to, value) in self.transfers.iter() {
+                for (account, balance) in state.iter_mut() {
+                    if account == from {
+                        *balance = balance.saturating_sub(*value);
+                    }
+                }
+                for (account, balance) in state.iter_mut() {
+                    if account == to {
+                        *balance = balance.saturating_add(*value);
+                    }
+                }
+            }
+            state
+        }
+    }
+    /// 默认挑战期, 约一天 (6 秒出块)
+    pub const SETTLEMENT_CHALLENGE_PERIOD_BLOCKS: u32 = 14_400;
+
+    /// 手续费统计. total_fees_collected 为各笔转账的手续费总额, 等于
+    /// recipient_fees + treasury_fees; 奖池注入不算手续费, 单独统计
+    #[derive(
+        Debug,
+        Clone,
+        Copy,
+        Default,
+        PartialEq,
+        Eq,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct FeeStatistics {
+        pub total_fees_collected: Balance,
+        pub recipient_fees: Balance,
+        pub treasury_fees: Balance,
+        pub jackpot_contributions: Balance,
+        pub fee_collections_count: u64,
+        /// 收取过手续费的转账平均每笔手续费, 向下取整
+        pub avg_fee_per_transfer: Balance,
+    }
+
+    impl FeeStatistics {
+        /// 从 earlier 到 self 之间的增量
+        pub fn since(&self, earlier: &FeeStatistics) -> FeeStatistics {
+            let total_fees_collected = self
+                .total_fees_collected
+                .saturating_sub(earlier.total_fees_collected);
+            let fee_collections_count = self
+                .fee_collections_count
+                .saturating_sub(earlier.fee_collections_count);
+            FeeStatistics {
+                total_fees_collected,
+                recipient_fees: self.recipient_fees.saturating_sub(earlier.recipient_fees),
+                treasury_fees: self.treasury_fees.saturating_sub(earlier.treasury_fees),
+                jackpot_contributions: self
+                    .jackpot_contributions
+                    .saturating_sub(earlier.jackpot_contributions),
+                fee_collections_count,
+                avg_fee_per_transfer: Self::average(total_fees_collected, fee_collections_count),
+            }
+        }
+
+        fn average(total: Balance, count: u64) -> Balance {
+            match count {
+                0 => 0,
+                count => total / Balance::from(count),
+            }
+        }
+    }
+    /// 两次手续费统计快照之间至少间隔的区块数
+    pub const FEE_SNAPSHOT_INTERVAL_BLOCKS: u32 = 100;
+
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum HtlcStatus {
+        Locked,
+        Claimed,
+        Refunded,
+    }
+
+    /// 哈希时间锁: recipient 在 timelock 之前出示原像领取, 之后 sender 可以取回
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct Htlc {
+        pub sender: AccountId,
+        pub recipient: AccountId,
+        pub value: Balance,
+        pub hashlock: Hash,
+        /// 毫秒时间戳
+        pub timelock: Timestamp,
+        pub status: HtlcStatus,
+    }
+    /// 原像的最大长度, 防止领取时写入过大的存储
+    pub const MAX_HTLC_PREIMAGE_LEN: usize = 256;
+
+    /// 一次拆分委托最多的被委托人数
+    pub const MAX_SPLIT_DELEGATEES: usize = 10;
+
+    /// 按余额加权的持有者抽奖. 第一次开奖时确定 target, 之后沿持有者索引累加余额,
+    /// 累计值超过 target 的账户中奖
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct Giveaway {
+        pub prize: Balance,
+        /// owner 秘密的 Blake2x256 哈希
+        pub commit: Hash,
+        pub start_block: u32,
+        /// 开奖后确定, 在 [0, 参与抽奖的余额总和) 之间. 为 Some 时分页开奖进行中, 所有余额冻结
+        pub target: Option<Balance>,
+        pub cursor: u64,
+        pub cumulative: Balance,
+    }
+    /// 开始抽奖后至少等待的区块数
+    pub const GIVEAWAY_MIN_DELAY_BLOCKS: u32 = 10;
+
+    /// 链下合规审查的证明, 链上只记录审查材料的哈希
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct ComplianceAttestation {
+        pub attestation_hash: Hash,
+        pub attester: AccountId,
+        pub attestation_block: u32,
+        /// 从这个区块起失效
+        pub expiry_block: u32,
+    }
+
+    /// 回收挂单: 挂单人押入 amount 代币作为额度, 其他持有人在额度内销毁自己的代币,
+    /// 按 credit_per_burned 的比例获得 credit_token 增发的积分
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct RecyclingListing {
+        pub id: u64,
+        pub lister: AccountId,
+        pub amount: Balance,
+        pub credit_token: AccountId,
+        /// 每销毁 1 个代币获得的积分
+        pub credit_per_burned: Balance,
+        pub filled: Balance,
+    }
+    /// 调用积分代币 mint 的 gas 上限
+    pub const CREDIT_MINT_GAS_LIMIT: u64 = 5_000_000_000;
+
+    /// 批量转账的事件发出方式
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub enum EventMode {
+        /// 每个收款人一条 Transfer 事件
+        PerRecipient,
+        /// 整个批次一条 BatchTransferChunk
+        SummaryOnly,
+        /// 每 n 个收款人一条 BatchTransferChunk
+        Chunked(u32),
+    }
+
+    /// 一次自动分红: 分配时质押者的总权重, 每个质押者按当时的权重分得 total_amount
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct DividendDistribution {
+        pub block: u32,
+        pub total_amount: Balance,
+        /// 质押总权重, 为 0 时这笔分红留到下一次有人质押后的分配
+        pub total_weight: Balance,
+    }
+
+    /// 授权额度是经哪条路径改变的
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum ApprovalOrigin {
+        /// approve, 以及直接重设额度的其他路径: UserOp, 批量撤销, 账户迁移, 遗产领取
+        Approve,
+        Increase,
+        Decrease,
+        /// permit 和 approve_on_behalf 等凭 owner 签名的授权
+        Permit,
+        /// transfer_from, burn_from 花费授权
+        TransferFromConsumption,
+        ApproveSingleUse,
+        PermitSingleUse,
+    }
+
+    /// 一对 (owner, spender) 只有一个授权, 最近一次 approve/permit 决定种类,
+    /// increase_allowance 和 decrease_allowance 只改额度, 保留原来的种类
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum AllowanceKind {
+        Standard,
+        /// 第一次成功花费后清零
+        SingleUse,
+    }
+
+    #[derive(
+        Debug, Clone, Copy, PartialEq, Eq, scale::Encode, scale::Decode, SpreadLayout, PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub struct ApprovalChange {
+        pub spender: AccountId,
+        pub old_value: Balance,
+        pub new_value: Balance,
+        pub block: u32,
+        pub origin: ApprovalOrigin,
+    }
+
+    /// 用户离线签名的一笔转账, 由中继批量提交.
+    /// relayer_fee 从 from 额外转给提交的中继, 包含在签名中表示用户同意
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct SignedTransfer {
+        pub from: AccountId,
+        pub to: AccountId,
+        pub value: Balance,
+        pub relayer_fee: Balance,
+        pub nonce: u64,
+        /// 最后有效的区块
+        pub deadline: u32,
+        pub signature: [u8; 65],
+    }
+
+    /// 一次余额写入对余额记录的影响
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum BalanceWrite {
+        /// 没有记录且新余额为 0, 不写
+        Skip,
+        Create,
+        Update,
+    }
+
+    impl BalanceWrite {
+        fn of(had_entry: bool, new_balance: Balance) -> Self {
+            match (had_entry, new_balance) {
+                (false, 0) => BalanceWrite::Skip,
+                (false, _) => BalanceWrite::Create,
+                (true, _) => BalanceWrite::Update,
+            }
+        }
+    }
+
+    /// 余额变化前后账户是否进出持有者集合
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum HolderTransition {
+        Joined,
+        Left,
+        Unchanged,
+    }
+
+    impl HolderTransition {
+        fn of(old_balance: Balance, new_balance: Balance) -> Self {
+            match (old_balance == 0, new_balance == 0) {
+                (true, false) => HolderTransition::Joined,
+                (false, true) => HolderTransition::Left,
+                _ => HolderTransition::Unchanged,
+            }
+        }
+    }
+
+    /// 一笔转账对存储的影响, 由 transfer_footprint 返回
+    #[derive(Debug, Clone, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Footprint {
+        /// 收款方是否新建余额记录
+        pub recipient_entry_created: bool,
+        /// 转出方的余额记录是否被删除. 余额归零的记录会保留, 目前总为 false
+        pub sender_entry_removed: bool,
+        /// 余额记录的写入次数
+        pub balance_writes: u32,
+        /// 其他存储的写入次数: 持有者列表, 币龄批次, 委托票数和投票权检查点, 活动记录, 交易承诺和全局统计
+        pub aux_writes: u32,
+        /// 新建减去删除的存储项字节数估算, 只计值的编码长度
+        pub bytes_delta: i64,
+    }
+
+    /// 在合约账户中托管代币的功能. 手续费, 奖池, 赏金池等协议自有资金不是托管, 计入未分配余额
+    #[derive(
+        Debug,
+        Clone,
+        Copy,
+        PartialEq,
+        Eq,
+        PartialOrd,
+        Ord,
+        scale::Encode,
+        scale::Decode,
+        SpreadLayout,
+        PackedLayout,
+    )]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink_storage::traits::StorageLayout)
+    )]
+    pub enum PoolId {
+        Vouchers,
+        /// 质押本金, 包括复投的奖励
+        Staking,
+        /// 已解除质押, 在提取队列中等待处理
+        WithdrawalQueue,
+        /// 已领取, 正在线性释放的质押奖励
+        RewardVesting,
+        ScheduledTransfers,
+        /// 尚未被接受的借贷报价
+        Loans,
+        Auction,
+        Deals,
+        CooperativePools,
+        Escrows,
+        TeamAllocations,
+        Htlcs,
+        Recycling,
+        /// 已结算, 挑战期结束前还没有付给收款方的批量转账
+        SettlementBatches,
+        /// 抽奖奖品, 开出中奖者后付出
+        Giveaway,
+        /// 已经加入质押奖励累计器, 还没有被领取或复投的奖励
+        StakingRewards,
+        BountyPool,
+        /// 稳定基金持有的代币
+        StabilityFund,
+        Jackpot,
+        /// 欢迎奖励活动的奖励池
+        WelcomeBonus,
+        Treasury,
+        /// 攒到阈值前还没有分配的协议手续费
+        PendingDividends,
+    }
+
+    impl PoolId {
+        pub const ALL: [PoolId; 22] = [
+            PoolId::Vouchers,
+            PoolId::Staking,
+            PoolId::WithdrawalQueue,
+            PoolId::RewardVesting,
+            PoolId::ScheduledTransfers,
+            PoolId::Loans,
+            PoolId::Auction,
+            PoolId::Deals,
+            PoolId::CooperativePools,
+            PoolId::Escrows,
+            PoolId::TeamAllocations,
+            PoolId::Htlcs,
+            PoolId::Recycling,
+            PoolId::SettlementBatches,
+            PoolId::Giveaway,
+            PoolId::StakingRewards,
+            PoolId::BountyPool,
+            PoolId::StabilityFund,
+            PoolId::Jackpot,
+            PoolId::WelcomeBonus,
+            PoolId::Treasury,
+            PoolId::PendingDividends,
+        ];
+    }
+
+    // 用一个Result类包裹Error
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    impl Erc20 {
+        //初始化构造函数
+        #[ink(constructor)]
+        pub fn new(supply: Balance) -> Self {
+            Self::new_with_features(supply, DEFAULT_FEATURES)
+        }
+
+        /// features 为 features 模块中各常量按位或的结果
+        #[ink(constructor)]
+        pub fn new_with_features(supply: Balance, features: u32) -> Self {
+            Self::new_with_whitepaper(supply, features, Hash::default(), String::new())
+        }
+
+        /// 部署时提交白皮书哈希, 用户可以据此核对手中的文档
+        #[ink(constructor)]
+        pub fn new_with_whitepaper(
+            supply: Balance,
+            features: u32,
+            whitepaper_hash: Hash,
+            whitepaper_uri: String,
+        ) -> Self {
+            let caller = Self::env().caller();
+            let mut balances = HashMap::new();
+            let block = Self::env().block_number();
+            let mut first_hold_block = HashMap::new();
+            if supply > 0 {
+                balances.insert(caller, supply);
+                first_hold_block.insert(caller, block);
+            }
+            let mut vote_checkpoints = HashMap::new();
+            if supply > 0 {
+                vote_checkpoints.insert(caller, ink_prelude::vec![(block, supply)]);
+            }
+            let mut age_buckets = HashMap::new();
+            let mut age_bucket_blocks = HashMap::new();
+            if supply > 0 && features & FEATURE_AGE_TRACKING != 0 {
+                age_buckets.insert((caller, block), supply);
+                age_bucket_blocks.insert(caller, ink_prelude::vec![block]);
+            }
+            // 部署者默认可以触发供应调节, 之后可以自行移除
+            let mut authorized_rebasers = HashMap::new();
+            authorized_rebasers.insert(caller, true);
+            let holder_count = if supply > 0 { 1 } else { 0 };
+            let mut holder_index = HashMap::new();
+            let mut holder_positions = HashMap::new();
+            if supply > 0 {
+                holder_index.insert(0, caller);
+                holder_positions.insert(caller, 0);
+            }
+            let mut ever_held = HashMap::new();
+            let mut accounts_seen_count = 0;
+            if supply > 0 && features & FEATURE_EVER_HELD != 0 {
+                ever_held.insert(caller, ());
+                accounts_seen_count = 1;
+            }
+            let mut account_activity = HashMap::new();
+            if features & FEATURE_ACTIVITY_TRACKING != 0 {
+                account_activity.insert(caller, (block, Self::env().block_timestamp()));
+            }
+
+            Self::env().emit_event(Transfer {
+                from: None,
+                to: Some(caller),
+                value: supply,
+                kind: TransferKind::Mint.into(),
+            });
+            // 部署时还没有费率时间表, 有效费率就是初始的 fee_strategy 费率
+            let fee_strategy = FeeStrategy::None;
+            let last_tax_rate = fee_strategy.rate_bps();
+
+            Self {
+                total_supply: Lazy::new(supply),
+                balances,
+                allowances: HashMap::new(),
+                owner: Lazy::new(caller),
+                rewards_hook: Lazy::new(None),
+                rewards_hook_gas_limit: Lazy::new(0),
+                rewards_hook_policy: Lazy::new(HookFailurePolicy::Revert),
+                vouchers: HashMap::new(),
+                age_buckets,
+                age_bucket_blocks,
+                allowance_spent: HashMap::new(),
+                user_op_nonces: HashMap::new(),
+                features: Lazy::new(features),
+                policy: Lazy::new(TransferPolicy::default()),
+                proposals: HashMap::new(),
+                next_proposal_id: Lazy::new(0),
+                proposal_votes: HashMap::new(),
+                voting_weight_decay_enabled: Lazy::new(false),
+                voting_weight_decay_rate: Lazy::new(0),
+                voting_decay_start_block: Lazy::new(block),
+                last_vote_block: HashMap::new(),
+                target_supply: Lazy::new(0),
+                supply_band_bps: Lazy::new(0),
+                last_rebalance_block: Lazy::new(None),
+                fee_strategy: Lazy::new(fee_strategy),
+                fee_recipient: Lazy::new(caller),
+                fees_collected: Lazy::new(0),
+                idempotency_keys: HashMap::new(),
+                idempotency_key_ttl: Lazy::new(DEFAULT_IDEMPOTENCY_KEY_TTL),
+                price_oracle: Lazy::new(None),
+                stability_fund_balance: Lazy::new(0),
+                native_reserve: Lazy::new(0),
+                stability_peg: Lazy::new(PRICE_SCALE),
+                stability_band_bps: Lazy::new(0),
+                minted_at: HashMap::new(),
+                stakes: HashMap::new(),
+                total_staked: Lazy::new(0),
+                reward_per_token: Lazy::new(0),
+                reward_per_token_paid: HashMap::new(),
+                staking_rewards: HashMap::new(),
+                undistributed_rewards: Lazy::new(0),
+                buyback_burn_ratio: Lazy::new(10_000),
+                buyback_stake_ratio: Lazy::new(0),
+                total_approved: HashMap::new(),
+                approved_spenders: HashMap::new(),
+                permit_nonces: HashMap::new(),
+                reward_vest_duration: Lazy::new(0),
+                pending_reward_vests: HashMap::new(),
+                scheduled_transfers: HashMap::new(),
+                next_scheduled_id: Lazy::new(0),
+                auto_compound_enabled: HashMap::new(),
+                authorized_rebasers,
+                transfer_count: Lazy::new(0),
+                total_volume: Lazy::new(0),
+                holder_count: Lazy::new(holder_count),
+                last_activity_block: Lazy::new(block),
+                holder_tiers: HashMap::new(),
+                token_basis_cost: HashMap::new(),
+                harvested_losses: HashMap::new(),
+                tax_loss_certificate: Lazy::new(None),
+                precision_mode: Lazy::new(PrecisionMode::Standard),
+                receiving_disabled: HashMap::new(),
+                loans: HashMap::new(),
+                next_loan_id: Lazy::new(0),
+                transfer_hooks: Lazy::new(Vec::new()),
+                hook_failure_mode: Lazy::new(HookFailureMode::Strict),
+                split_delegations: HashMap::new(),
+                delegated_votes: HashMap::new(),
+                vote_checkpoints,
+                transaction_commitments: HashMap::new(),
+                burn_receipts: HashMap::new(),
+                next_burn_receipt_id: Lazy::new(0),
+                oldest_burn_receipt_id: Lazy::new(0),
+                burn_receipts_of: HashMap::new(),
+                burn_receipt_retention: Lazy::new(0),
+                receipt_bounty_min_amount: Lazy::new(0),
+                licenses: HashMap::new(),
+                next_license_id: Lazy::new(0),
+                auction: Lazy::new(None),
+                jackpot_pool: Lazy::new(0),
+                jackpot_probability_bps: Lazy::new(0),
+                jackpot_fee_rate: Lazy::new(0),
+                deals: HashMap::new(),
+                next_deal_id: Lazy::new(0),
+                account_activity,
+                genesis_nft_holder: Lazy::new(caller),
+                stake_pools: HashMap::new(),
+                next_stake_pool_id: Lazy::new(0),
+                pool_reward_per_share_paid: HashMap::new(),
+                pool_rewards: HashMap::new(),
+                onboarders: HashMap::new(),
+                reputation_score: HashMap::new(),
+                reputation_actions: HashMap::new(),
+                stake_started: HashMap::new(),
+                whitepaper_hash: Lazy::new(whitepaper_hash),
+                whitepaper_uri: Lazy::new(whitepaper_uri),
+                pending_whitepaper: Lazy::new(None),
+                account_forwarding: HashMap::new(),
+                instant_withdrawal_limit: Lazy::new(None),
+                instant_withdrawal_window: Lazy::new((0, 0)),
+                withdrawal_requests: HashMap::new(),
+                withdrawal_head: Lazy::new(0),
+                next_withdrawal_id: Lazy::new(0),
+                tax_schedule: Lazy::new(Vec::new()),
+                last_tax_rate: Lazy::new(last_tax_rate),
+                beneficiaries: HashMap::new(),
+                dynamic_lockup_enabled: Lazy::new(false),
+                dynamic_lockup_tiers: Lazy::new(Vec::new()),
+                stake_locks: HashMap::new(),
+                dev_mode: Lazy::new(cfg!(feature = "dev")),
+                task_bounties: HashMap::new(),
+                bounty_pool: Lazy::new(0),
+                keeper_stats: HashMap::new(),
+                protocol_fee_bps: Lazy::new(0),
+                treasury_balance: Lazy::new(0),
+                treasury_proposals: HashMap::new(),
+                next_treasury_proposal_id: Lazy::new(0),
+                treasury_votes: HashMap::new(),
+                treasury_proposal_threshold: Lazy::new(0),
+                treasury_quorum_bps: Lazy::new(DEFAULT_TREASURY_QUORUM_BPS),
+                escrows: HashMap::new(),
+                next_escrow_id: Lazy::new(0),
+                dispute_period_blocks: Lazy::new(DEFAULT_DISPUTE_PERIOD_BLOCKS),
+                first_hold_block,
+                weighted_voting_enabled: Lazy::new(false),
+                max_annual_growth_rate_bps: Lazy::new(0),
+                total_minted_this_year: Lazy::new(0),
+                year_start_supply: Lazy::new(supply),
+                year_start_block: Lazy::new(block),
+                mint_year: Lazy::new(0),
+                burn_vote_cost: Lazy::new(0),
+                burned_vote_total: Lazy::new(0),
+                proposal_vote_burns: HashMap::new(),
+                metadata_uri: Lazy::new(None),
+                logo_hash: Lazy::new(None),
+                pending_metadata: Lazy::new(None),
+                execution_grace_period_blocks: Lazy::new(0),
+                veto_guardian: Lazy::new(None),
+                allowance_usage: HashMap::new(),
+                genesis_snapshot_root: Lazy::new(Hash::default()),
+                genesis_claim_cap: Lazy::new(0),
+                genesis_conversion_rate: Lazy::new(0),
+                genesis_total_claimed: Lazy::new(0),
+                genesis_snapshot_claimed: HashMap::new(),
+                genesis_claimed_leaves: HashMap::new(),
+                clawback_mints: HashMap::new(),
+                clawback_ids_of: HashMap::new(),
+                next_clawback_id: Lazy::new(0),
+                team_allocations: HashMap::new(),
+                next_team_allocation_id: Lazy::new(0),
+                fee_rebate_tiers: Lazy::new(Vec::new()),
+                fee_rebate_window_blocks: Lazy::new(FEE_REBATE_WINDOW_BLOCKS),
+                sender_fee_windows: HashMap::new(),
+                settled_fee_rebates: HashMap::new(),
+                optimistic_batch_nonce: Lazy::new(0),
+                settlement_operator: Lazy::new(None),
+                challenge_period_blocks: Lazy::new(SETTLEMENT_CHALLENGE_PERIOD_BLOCKS),
+                settled_batches: HashMap::new(),
+                optimistic_participants: HashMap::new(),
+                batched_transfer_nonces: HashMap::new(),
+                campaign_active: Lazy::new(false),
+                welcome_bonus: Lazy::new(0),
+                welcome_pool: Lazy::new(0),
+                welcomed: HashMap::new(),
+                recipient_fees_total: Lazy::new(0),
+                treasury_fees_total: Lazy::new(0),
+                jackpot_contributions_total: Lazy::new(0),
+                fee_collections_count: Lazy::new(0),
+                fee_snapshots: HashMap::new(),
+                last_fee_snapshot_block: Lazy::new(None),
+                next_htlc_id: Lazy::new(0),
+                htlcs: HashMap::new(),
+                htlc_preimages: HashMap::new(),
+                holder_index,
+                holder_positions,
+                giveaway: Lazy::new(None),
+                attester: Lazy::new(None),
+                attestations: HashMap::new(),
+                ever_held,
+                accounts_seen_count: Lazy::new(accounts_seen_count),
+                next_recycling_listing_id: Lazy::new(0),
+                recycling_listings: HashMap::new(),
+                credit_token_rates: HashMap::new(),
+                auto_distribute_threshold: Lazy::new(0),
+                pending_dividends: Lazy::new(0),
+                next_dividend_snapshot_id: Lazy::new(0),
+                dividend_distributions: HashMap::new(),
+                approval_history: HashMap::new(),
+                compliance_signer: Lazy::new(None),
+                compliance_required: Lazy::new(false),
+                compliance_clearance: Lazy::new(None),
+                compliance_nonces: HashMap::new(),
+                denomination: Lazy::new((1, 1)),
+                pending_redenomination: Lazy::new(None),
+                signed_transfer_nonces: HashMap::new(),
+                allowance_listeners: HashMap::new(),
+                allowance_listeners_enabled: Lazy::new(true),
+                obligations: HashMap::new(),
+                private_balances: Lazy::new(false),
+                view_grants: HashMap::new(),
+                allowance_maintenance: HashMap::new(),
+                maintenance_bounties: HashMap::new(),
+                paused_allowances: HashMap::new(),
+            }
+        }
+        // 各种get函数
+        #[ink(message)]
+        pub fn total_supply(&self) -> Balance {
+            *self.total_supply
+        }
+
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId {
+            *self.owner
+        }
+
+        #[ink(message)]
+        pub fn features(&self) -> u32 {
+            *self.features
+        }
+
+        /// bit 中的每一位都开启时返回 true
+        #[ink(message)]
+        pub fn is_feature_enabled(&self, bit: u32) -> bool {
+            bit != 0 && *self.features & bit == bit
+        }
+
+        /// 是否全部暂停, 与拆分暂停位之前的含义相同: pause() 之后为 true, 此时合约内部划转也停止.
+        /// 只暂停了部分操作时为 false, 用 paused_ops 查询
+        #[ink(message)]
+        pub fn paused(&self) -> bool {
+            self.policy.paused_ops == PAUSE_ALL
+        }
+
+        #[ink(message)]
+        pub fn paused_ops(&self) -> u32 {
+            self.policy.paused_ops
+        }
+
+        /// 当前全部转账限制, 供前端一次读取
+        #[ink(message)]
+        pub fn policy(&self) -> TransferPolicy {
+            (*self.policy).clone()
+        }
+
+        #[ink(message)]
+        pub fn rewards_hook(&self) -> Option<AccountId> {
+            *self.rewards_hook
+        }
+
+        #[ink(message)]
+        pub fn rewards_hook_config(&self) -> (Option<AccountId>, u64, HookFailurePolicy) {
+            (
+                *self.rewards_hook,
+                *self.rewards_hook_gas_limit,
+                *self.rewards_hook_policy,
+            )
+        }
+
+        /// 私有余额模式下无权查看时返回 0, 需要区分时用 balance_of_restricted
+        #[ink(message)]
+        pub fn balance_of(&self, who: AccountId) -> Balance {
+            self.balance_of_restricted(who).unwrap_or(0)
+        }
+
+        /// 不检查查看权限, 合约内部的余额校验都用它
+        fn stored_balance(&self, who: AccountId) -> Balance {
+            self.balances.get(&who).copied().unwrap_or_default()
+        }
+
+        /// 一次查找同时得到余额和账户是否有余额记录, 内部的余额写入路径都用它代替 balance_of
+        fn balance_entry(&self, who: AccountId) -> Option<Balance> {
+            crate::metering::note_storage_read();
+            self.balances.get(&who).copied()
+        }
+
+        /// had_entry 来自之前的 balance_entry. 没有记录且新余额为 0 时不写,
+        /// 不为从未持有过代币的账户创建记录
+        fn write_balance(&mut self, who: AccountId, had_entry: bool, new_balance: Balance) {
+            if BalanceWrite::of(had_entry, new_balance) == BalanceWrite::Skip {
+                return;
+            }
+            crate::metering::note_storage_write();
+            self.balances.insert(who, new_balance);
+        }
+
+        /// 私有余额模式下无权查看时返回 0
+        #[ink(message)]
+        pub fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            self.allowance_restricted(owner, spender).unwrap_or(0)
+        }
+
+        fn stored_allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            self.allowances
+                .get(&(owner, spender))
+                .map_or(0, |(value, _)| *value)
+        }
+
+        /// 没有授权时为 Standard
+        #[ink(message)]
+        pub fn allowance_kind(&self, owner: AccountId, spender: AccountId) -> AllowanceKind {
+            self.allowances
+                .get(&(owner, spender))
+                .map_or(AllowanceKind::Standard, |(_, kind)| *kind)
+        }
+
+        /// 一次性授权: 第一次成功花费 (哪怕只花了一部分) 后剩余额度全部清零
+        #[ink(message)]
+        pub fn approve_single_use(&mut self, spender: AccountId, max_value: Balance) -> Result<()> {
+            let owner = self.env().caller();
+            self.inner_approve(owner, spender, max_value, ApprovalOrigin::ApproveSingleUse)
+        }
+
+        //transfer / approve / transfer_from  等会修改状态的方法, 第一参数必须为 &mut self
+        #[ink(message)]
+        pub fn transfer(&mut self, to: AccountId, value: Balance) -> Result<()> {
+            let from = self.env().caller();
+            self.transfer_with_fee(from, to, value)
+        }
+
+        #[ink(message)]
+        pub fn approve(&mut self, to: AccountId, value: Balance) -> Result<()> {
+            let owner = self.env().caller();
+            self.inner_approve(owner, to, value, ApprovalOrigin::Approve)
+        }
+
+        #[ink(message)]
+        pub fn transfer_from(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            let caller = self.env().caller();
+            self.inner_transfer_from(caller, from, to, value)
+        }
+
+        /// 当前这次授权已经被花掉的总额
+        #[ink(message)]
+        pub fn allowance_spent(&self, owner: AccountId, spender: AccountId) -> Balance {
+            self.allowance_spent
+                .get(&(owner, spender))
+                .copied()
+                .unwrap_or_default()
+        }
+
+        /// (当前额度, 历史花费总额, 最近一次使用的区块). 历史不随重新授权或撤销清零,
+        /// 未开启 FEATURE_ALLOWANCE_STATS 期间的花费不计入
+        #[ink(message)]
+        pub fn allowance_stats(
+            &self,
+            owner: AccountId,
+            spender: AccountId,
+        ) -> (Balance, Balance, Option<u32>) {
+            let (spent_total, last_used) = match self.allowance_usage.get(&(owner, spender)) {
+                Some((spent_total, last_used)) => (*spent_total, Some(*last_used)),
+                None => (0, None),
+            };
+            (self.allowance(owner, spender), spent_total, last_used)
+        }
+
+        /// 增发, 仅 owner 可调用, 受年增长率上限约束
+        #[ink(message)]
+        pub fn mint(&mut self, to: AccountId, value: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            let minted = self.annual_minted_after(value)?;
+            self.inner_mint(to, value)?;
+            *self.total_minted_this_year = minted;
+            Ok(())
+        }
+
+        /// 销毁调用者自己的代币
+        #[ink(message)]
+        pub fn burn(&mut self, value: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            self.burn_with_receipt(caller, value, [0; 16]).map(|_| ())
+        }
+
+        /// 设置奖励合约回调, hook 为 None 时关闭回调
+        #[ink(message)]
+        pub fn set_rewards_hook(
+            &mut self,
+            hook: Option<AccountId>,
+            gas_limit: u64,
+            policy: HookFailurePolicy,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_REWARDS_HOOK)?;
+            *self.rewards_hook = hook;
+            *self.rewards_hook_gas_limit = gas_limit;
+            *self.rewards_hook_policy = policy;
+            Ok(())
+        }
+
+        /// 暂停所有代币流转, 等同于 set_paused_ops(PAUSE_ALL), 需要部署时开启 FEATURE_PAUSABLE
+        #[ink(message)]
+        pub fn pause(&mut self) -> Result<()> {
+            self.set_paused_ops(PAUSE_ALL)?;
+            self.env().emit_event(Paused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// 等同于 set_paused_ops(0)
+        #[ink(message)]
+        pub fn unpause(&mut self) -> Result<()> {
+            self.set_paused_ops(0)?;
+            self.env().emit_event(Unpaused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
+
+        /// 只暂停 mask 中的操作, 其余照常
+        #[ink(message)]
+        pub fn set_paused_ops(&mut self, mask: u32) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_PAUSABLE)?;
+            let old_mask = self.policy.paused_ops;
+            let new_mask = mask & PAUSE_ALL;
+            self.policy.paused_ops = new_mask;
+            if old_mask != new_mask {
+                self.env()
+                    .emit_event(PausedOpsChanged { old_mask, new_mask });
+            }
+            Ok(())
+        }
+
+        //私有helper方法
+        fn ensure_owner(&self) -> Result<()> {
+            if self.env().caller() != *self.owner {
+                return Err(Error::NotOwner);
+            }
+            Ok(())
+        }
+
+        fn ensure_feature(&self, bit: u32) -> Result<()> {
+            if !self.is_feature_enabled(bit) {
+                return Err(Error::FeatureDisabled);
+            }
+            Ok(())
+        }
+
+        fn ensure_not_paused(&self, ops: u32) -> Result<()> {
+            self.policy
+                .ensure_op_allowed(ops, self.is_feature_enabled(FEATURE_PAUSABLE))
+        }
+
+        /// 从 ecdsa 签名恢复签名者账户, 账户为压缩公钥的 Blake2x256 哈希
+        fn recover_signer(
+            &self,
+            message_hash: &[u8; 32],
+            signature: &[u8; 65],
+        ) -> Option<AccountId> {
+            let public_key = self.env().ecdsa_recover(signature, message_hash).ok()?;
+            Some(AccountId::from(
+                self.env().hash_bytes::<Blake2x256>(&public_key),
+            ))
+        }
+
+        fn inner_approve(
+            &mut self,
+            owner: AccountId,
+            to: AccountId,
+            value: Balance,
+            origin: ApprovalOrigin,
+        ) -> Result<()> {
+            // 暂停授权时仍然允许撤销
+            if value > 0 {
+                self.ensure_not_paused(PAUSE_APPROVE)?;
+            }
+            self.set_allowance(owner, to, value, origin);
+            // 新的授权重新开始计数
+            self.allowance_spent.take(&(owner, to));
+            self.note_account_activity(owner);
+            self.reset_inheritance_clock(owner);
+            self.emit_approval(owner, to, value);
+            Ok(())
+        }
+
+        fn emit_approval(&self, owner: AccountId, spender: AccountId, value: Balance) {
+            self.env().emit_event(Approval {
+                owner,
+                spender,
+                value_bucket: value_bucket(value),
+                value,
+            });
+        }
+
+        fn inner_transfer_from(
+            &mut self,
+            spender: AccountId,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            self.ensure_allowance_active(from, spender)?;
+            let allowance = self.stored_allowance(from, spender);
+            if allowance < value {
+                return Err(Error::InsufficientAllowance);
+            }
+            // 转给自己: 只校验余额, 不消耗授权, 不写状态也不发事件, 避免 spender 空耗授权
+            if from == to {
+                if self.stored_balance(from) < value {
+                    return Err(Error::InsufficientBalance);
+                }
+                return Ok(());
+            }
+
+            self.charge_and_transfer(from, to, value, TransferOrigin::Delegated(value))?;
+            self.spend_allowance(from, spender, allowance, value);
+
+            Ok(())
+        }
+
+        // 扣减授权额度; 额度刚好用尽时删除存储项, 并发出 Approval(0) 和 AllowanceExhausted
+        fn spend_allowance(
+            &mut self,
+            owner: AccountId,
+            spender: AccountId,
+            allowance: Balance,
+            value: Balance,
+        ) {
+            if value > 0 && self.is_feature_enabled(FEATURE_ALLOWANCE_STATS) {
+                let (spent_total, _) = self
+                    .allowance_usage
+                    .get(&(owner, spender))
+                    .copied()
+                    .unwrap_or_default();
+                self.allowance_usage.insert(
+                    (owner, spender),
+                    (spent_total.saturating_add(value), self.env().block_number()),
+                );
+            }
+            let remaining = allowance - value;
+            let spent = self.allowance_spent(owner, spender).saturating_add(value);
+            // 一次性授权优先于无限授权的规则, 额度多大都在第一次花费后清零
+            if value > 0 && self.allowance_kind(owner, spender) == AllowanceKind::SingleUse {
+                self.set_allowance(owner, spender, 0, ApprovalOrigin::TransferFromConsumption);
+                self.allowance_spent.take(&(owner, spender));
+                self.emit_approval(owner, spender, 0);
+            } else if allowance == INFINITE_ALLOWANCE {
+                if value > 0 {
+                    self.allowance_spent.insert((owner, spender), spent);
+                }
+            } else if value > 0 && remaining == 0 {
+                self.set_allowance(owner, spender, 0, ApprovalOrigin::TransferFromConsumption);
+                self.allowance_spent.take(&(owner, spender));
+                self.emit_approval(owner, spender, 0);
+                self.env().emit_event(AllowanceExhausted {
+                    owner,
+                    spender,
+                    total_spent: spent,
+                });
+            } else {
+                self.set_allowance(
+                    owner,
+                    spender,
+                    remaining,
+                    ApprovalOrigin::TransferFromConsumption,
+                );
+                if value > 0 {
+                    self.allowance_spent.insert((owner, spender), spent);
+                }
+            }
+        }
+
+        fn inner_transfer(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<()> {
+            self.inner_transfer_as(from, to, value, TransferKind::Normal)
+        }
+
+        fn inner_transfer_as(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            kind: TransferKind,
+        ) -> Result<()> {
+            let mut changes = TokenChanges::default();
+            self.write_transfer(
+                from,
+                to,
+                value,
+                kind,
+                TransferOrigin::Internal,
+                &mut changes,
+            )?;
+            self.after_token_transfer(&changes)
+        }
+
+        /// 托管资金退回原主(退款, 解除质押, 释放归属), 不受收款方关闭收款的影响
+        fn inner_refund(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<()> {
+            let mut changes = TokenChanges::default();
+            self.write_transfer(
+                from,
+                to,
+                value,
+                TransferKind::Normal,
+                TransferOrigin::Refund,
+                &mut changes,
+            )?;
+            self.after_token_transfer(&changes)
+        }
+
+        fn transfer_ctx(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            origin: TransferOrigin,
+        ) -> TransferCtx<'_> {
+            TransferCtx {
+                from,
+                to,
+                value,
+                origin,
+                block: self.env().block_number(),
+                contract: self.env().account_id(),
+                pausable: self.is_feature_enabled(FEATURE_PAUSABLE),
+                minted_at: &self.minted_at,
+                receiving_disabled: &self.receiving_disabled,
+                holder_tiers: &self.holder_tiers,
+                balances: &self.balances,
+                clawback_ids_of: &self.clawback_ids_of,
+                clawback_mints: &self.clawback_mints,
+                attestations: &self.attestations,
+            }
+        }
+
+        // 只写余额和发事件, 余额变化和转账追加到 changes, 由调用方在所有写入完成后统一交给 after_token_transfer.
+        // 转账限制统一由 TransferPolicy::check 检查, origin 决定适用哪些检查
+        fn write_transfer(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            kind: TransferKind,
+            origin: TransferOrigin,
+            changes: &mut TokenChanges,
+        ) -> Result<()> {
+            self.ensure_no_draw_in_progress()?;
+            let to = self.forwarding_target(to);
+            self.policy
+                .check(&self.transfer_ctx(from, to, value, origin))?;
+            let from_entry = self.balance_entry(from);
+            let from_balance = from_entry.unwrap_or(0);
+            if from_balance < value {
+                return Err(Error::InsufficientBalance);
+            }
+
+            self.write_balance(from, from_entry.is_some(), from_balance - value);
+            let to_entry = self.balance_entry(to);
+            let to_balance = to_entry.unwrap_or(0);
+            let new_to_balance = to_balance.checked_add(value).ok_or(Error::Overflow)?;
+            self.write_balance(to, to_entry.is_some(), new_to_balance);
+            for (block, amount) in self.age_debit(from, value) {
+                self.age_credit(to, block, amount);
+            }
+            if !matches!(origin, TransferOrigin::Batched(_)) {
+                self.env().emit_event(Transfer {
+                    from: Some(from),
+                    to: Some(to),
+                    value,
+                    kind: kind.into(),
+                });
+            }
+            self.note_holder_change(
+                from,
+                from_entry.is_some(),
+                from_balance,
+                from_balance - value,
+            );
+            self.note_holder_change(to, to_entry.is_some(), to_balance, new_to_balance);
+            self.note_first_hold(from, from_balance, from_balance - value);
+            self.note_first_hold(to, to_balance, new_to_balance);
+            self.note_vote_change(from, from_balance, from_balance - value);
+            self.note_vote_change(to, to_balance, new_to_balance);
+            self.note_account_activity(from);
+            self.note_account_activity(to);
+            self.reset_inheritance_clock(from);
+            self.commit_transaction(from, to, value, from_balance - value);
+            crate::metering::note_aux_write();
+            *self.transfer_count += 1;
+            crate::metering::note_aux_write();
+            *self.total_volume = self.total_volume.saturating_add(value);
+            self.note_activity();
+
+            changes.balances.push(BalanceChange {
+                account: from,
+                old_balance: from_balance,
+                new_balance: from_balance - value,
+            });
+            changes.balances.push(BalanceChange {
+                account: to,
+                old_balance: to_balance,
+                new_balance: new_to_balance,
+            });
+            changes.transfers.push((from, to, value));
+            self.pay_welcome_bonus(to, to_balance, value, changes);
+            Ok(())
+        }
+
+        fn inner_mint(&mut self, to: AccountId, value: Balance) -> Result<()> {
+            self.ensure_not_paused(PAUSE_MINT)?;
+            self.ensure_no_draw_in_progress()?;
+            let to = self.forwarding_target(to);
+            if !self.is_receiving(to) {
+                return Err(Error::RecipientOptedOut);
+            }
+            let to_entry = self.balance_entry(to);
+            let to_balance = to_entry.unwrap_or(0);
+            let new_to_balance = to_balance.checked_add(value).ok_or(Error::Overflow)?;
+            let new_supply = self
+                .total_supply()
+                .checked_add(value)
+                .ok_or(Error::Overflow)?;
+
+            self.write_balance(to, to_entry.is_some(), new_to_balance);
+            *self.total_supply = new_supply;
+            let block = self.env().block_number();
+            self.age_credit(to, block, value);
+            self.apply_fresh_mint_lock(to, block);
+            self.env().emit_event(Transfer {
+                from: None,
+                to: Some(to),
+                value,
+                kind: TransferKind::Mint.into(),
+            });
+            self.note_holder_change(to, to_entry.is_some(), to_balance, new_to_balance);
+            self.note_first_hold(to, to_balance, new_to_balance);
+            self.note_vote_change(to, to_balance, new_to_balance);
+            self.note_account_activity(to);
+            self.note_activity();
+
+            self.after_token_transfer(&TokenChanges::single(BalanceChange {
+                account: to,
+                old_balance: to_balance,
+                new_balance: new_to_balance,
+            }))
+        }
+
+        fn inner_burn(&mut self, from: AccountId, value: Balance) -> Result<()> {
+            self.ensure_not_paused(PAUSE_BURN)?;
+            self.ensure_no_draw_in_progress()?;
+            let from_entry = self.balance_entry(from);
+            let from_balance = from_entry.unwrap_or(0);
+            if from_balance < value {
+                return Err(Error::InsufficientBalance);
+            }
+            if from_balance - value < self.clawback_locked_of(from) {
+                return Err(Error::ClawbackLocked);
+            }
+
+            self.write_balance(from, from_entry.is_some(), from_balance - value);
+            *self.total_supply -= value;
+            self.age_debit(from, value);
+            self.env().emit_event(Transfer {
+                from: Some(from),
+                to: None,
+                value,
+                kind: TransferKind::Burn.into(),
+            });
+            self.note_holder_change(
+                from,
+                from_entry.is_some(),
+                from_balance,
+                from_balance - value,
+            );
+            self.note_first_hold(from, from_balance, from_balance - value);
+            self.note_vote_change(from, from_balance, from_balance - value);
+            self.note_account_activity(from);
+            self.reset_inheritance_clock(from);
+            self.note_activity();
+
+            self.after_token_transfer(&TokenChanges::single(BalanceChange {
+                account: from,
+                old_balance: from_balance,
+                new_balance: from_balance - value,
+            }))
+        }
+
+        // 所有跨合约调用都经过这里, 失败时统一带上被调合约和 selector
+        fn do_call(
+            &self,
+            callee: AccountId,
+            selector: [u8; 4],
+            input: &[u8],
+            gas_limit: u64,
+        ) -> Result<Vec<u8>> {
+            call::invoke(callee, selector, input, gas_limit).map_err(|error| Error::ExternalCall {
+                callee,
+                selector,
+                code: call::error_code(&error),
+            })
+        }
+
+        // 余额写入全部完成后才会调用, 所有的外部调用都放在这里.
+        // 先通知奖励合约, 再按注册顺序通知转账回调
+        fn after_token_transfer(&mut self, changes: &TokenChanges) -> Result<()> {
+            self.notify_rewards_hook(&changes.balances)?;
+            self.notify_transfer_hooks(&changes.transfers)
+        }
+
+        // 同一账户的多次变化合并为一次通知, 回调中读到的余额就是最终余额
+        fn notify_rewards_hook(&mut self, changes: &[BalanceChange]) -> Result<()> {
+            if !self.is_feature_enabled(FEATURE_REWARDS_HOOK) {
+                return Ok(());
+            }
+            let hook = match *self.rewards_hook {
+                Some(hook) => hook,
+                None => return Ok(()),
+            };
+            let mut merged: Vec<BalanceChange> = Vec::new();
+            for change in changes {
+                match merged
+                    .iter_mut()
+                    .find(|merged| merged.account == change.account)
+                {
+                    Some(merged) => merged.new_balance = change.new_balance,
+                    None => merged.push(*change),
+                }
+            }
+            for change in merged {
+                let input = scale::Encode::encode(&(
+                    change.account,
+                    change.old_balance,
+                    change.new_balance,
+                ));
+                let result = self.do_call(
+                    hook,
+                    ON_BALANCE_CHANGE_SELECTOR,
+                    &input,
+                    *self.rewards_hook_gas_limit,
+                );
+                if *self.rewards_hook_policy == HookFailurePolicy::Revert {
+                    result?;
+                }
+            }
+            Ok(())
+        }
+    }
+    // 兑换码: owner 预先把代币锁进合约账户, 持有兑换码原文的人可以领取
+    impl Erc20 {
+        #[ink(message)]
+        pub fn voucher(&self, code_hash: Hash) -> Option<(Balance, bool)> {
+            self.vouchers.get(&code_hash).copied()
+        }
+
+        /// 创建兑换码, 金额从 owner 余额中转入合约账户
+        #[ink(message)]
+        pub fn create_voucher(&mut self, code_hash: Hash, amount: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_VOUCHERS)?;
+            if self.vouchers.contains_key(&code_hash) {
+                return Err(Error::VoucherAlreadyExists);
+            }
+
+            let owner = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(owner, contract, amount)?;
+            self.escrow_in(PoolId::Vouchers, amount);
+            self.vouchers.insert(code_hash, (amount, false));
+            self.env().emit_event(VoucherCreated { code_hash, amount });
+            Ok(())
+        }
+
+        /// 用兑换码原文领取代币
+        #[ink(message)]
+        pub fn redeem_voucher(&mut self, code: Vec<u8>) -> Result<()> {
+            self.ensure_feature(FEATURE_VOUCHERS)?;
+            let code_hash = Hash::from(self.env().hash_bytes::<Blake2x256>(&code));
+            let (amount, redeemed) = self
+                .vouchers
+                .get(&code_hash)
+                .copied()
+                .ok_or(Error::VoucherNotFound)?;
+            if redeemed {
+                return Err(Error::VoucherAlreadyRedeemed);
+            }
+
+            let redeemer = self.env().caller();
+            let contract = self.env().account_id();
+            // 先标记为已兑换再转账
+            self.vouchers.insert(code_hash, (amount, true));
+            self.inner_transfer(contract, redeemer, amount)?;
+            self.escrow_out(PoolId::Vouchers, amount);
+            self.env().emit_event(VoucherRedeemed { redeemer, amount });
+            Ok(())
+        }
+
+        /// 取消未兑换的兑换码, 锁定的代币退回 owner
+        #[ink(message)]
+        pub fn cancel_voucher(&mut self, code_hash: Hash) -> Result<()> {
+            self.ensure_owner()?;
+            let (amount, redeemed) = self
+                .vouchers
+                .get(&code_hash)
+                .copied()
+                .ok_or(Error::VoucherNotFound)?;
+            if redeemed {
+                return Err(Error::VoucherAlreadyRedeemed);
+            }
+
+            self.vouchers.take(&code_hash);
+            let owner = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_refund(contract, owner, amount)?;
+            self.escrow_out(PoolId::Vouchers, amount);
+            Ok(())
+        }
+    }
+    // 批量操作
+    impl Erc20 {
+        /// 一次转给多个账户, 合计金额先校验, 任一失败则全部失败.
+        /// event_mode 决定每个收款人一条 Transfer 事件, 还是按块汇总为 BatchTransferChunk
+        #[ink(message)]
+        pub fn batch_transfer(
+            &mut self,
+            recipients: Vec<(AccountId, Balance)>,
+            event_mode: EventMode,
+        ) -> Result<()> {
+            Self::ensure_batch_len(recipients.len())?;
+            let chunk_len = match event_mode {
+                EventMode::PerRecipient => None,
+                EventMode::SummaryOnly => Some(recipients.len().max(1)),
+                EventMode::Chunked(0) => return Err(Error::InvalidEventMode),
+                EventMode::Chunked(n) => Some(n as usize),
+            };
+            let total = Self::checked_sum(recipients.iter().map(|(_, value)| *value))?;
+            let from = self.env().caller();
+            if self.stored_balance(from) < total {
+                return Err(Error::InsufficientBalance);
+            }
+            // 写入前先整体检查最小金额, 避免只转出一部分
+            for (_, value) in recipients.iter() {
+                self.policy.check_minimum(*value)?;
+            }
+
+            let mut changes = TokenChanges::default();
+            for (to, value) in recipients.iter().copied() {
+                let origin = match chunk_len {
+                    Some(_) => TransferOrigin::Batched(value),
+                    None => TransferOrigin::User(value),
+                };
+                self.charge_transfer(from, to, value, origin, &mut changes)?;
+            }
+            if let Some(chunk_len) = chunk_len {
+                for chunk in recipients.chunks(chunk_len) {
+                    self.env().emit_event(BatchTransferChunk {
+                        from,
+                        recipients_hash: Hash::from(
+                            self.env().hash_encoded::<Blake2x256, _>(&chunk),
+                        ),
+                        total: chunk.iter().map(|(_, value)| value).sum(),
+                        count: chunk.len() as u32,
+                    });
+                }
+            }
+            self.after_token_transfer(&changes)
+        }
+
+        /// 调用者消耗 from 的授权, 把 from 的代币一次分给多个收款人.
+        /// 写入前先按合计金额检查授权和余额, 并逐笔检查转账限制, 任一失败则全部不执行.
+        /// 授权只扣一次: 有剩余时发一条剩余额度的 Approval, 用尽时同 transfer_from.
+        /// 收款人是 from 自己的项不消耗授权; 无限授权不扣减也不发 Approval
+        #[ink(message)]
+        pub fn transfer_from_batch(
+            &mut self,
+            from: AccountId,
+            recipients: Vec<(AccountId, Balance)>,
+        ) -> Result<()> {
+            Self::ensure_batch_len(recipients.len())?;
+            let spender = self.env().caller();
+            self.ensure_allowance_active(from, spender)?;
+            let total = Self::checked_sum(recipients.iter().map(|(_, value)| *value))?;
+            let consumed = Self::checked_sum(
+                recipients
+                    .iter()
+                    .filter(|(to, _)| *to != from)
+                    .map(|(_, value)| *value),
+            )?;
+            let allowance = self.stored_allowance(from, spender);
+            if allowance < consumed {
+                return Err(Error::InsufficientAllowance);
+            }
+            if self.stored_balance(from) < total {
+                return Err(Error::InsufficientBalance);
+            }
+            let config = self.policy_config();
+            for (to, value) in recipients.iter().copied() {
+                self.evaluate_transfer(&config, from, to, value, TransferOrigin::Delegated(value))?;
+            }
+
+            let mut changes = TokenChanges::default();
+            for (to, value) in recipients.iter().copied() {
+                if to != from {
+                    self.charge_transfer(
+                        from,
+                        to,
+                        value,
+                        TransferOrigin::Delegated(value),
+                        &mut changes,
+                    )?;
+                }
+            }
+            self.spend_allowance(from, spender, allowance, consumed);
+            let remaining = self.stored_allowance(from, spender);
+            if allowance != INFINITE_ALLOWANCE && remaining > 0 {
+                self.emit_approval(from, spender, remaining);
+            }
+            self.after_token_transfer(&changes)
+        }
+
+        /// 合规要求下 owner 从多个账户销毁代币, 任何一个账户余额不足则全部不执行
+        #[ink(message)]
+        pub fn batch_burn(
+            &mut self,
+            accounts: Vec<AccountId>,
+            amounts: Vec<Balance>,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            if accounts.len() != amounts.len() {
+                return Err(Error::LengthMismatch);
+            }
+            Self::ensure_batch_len(accounts.len())?;
+            self.ensure_not_paused(PAUSE_BURN)?;
+            let total_burned = Self::checked_sum(amounts.iter().copied())?;
+            // 写入前先按账户汇总检查余额, 同一账户出现多次时合计
+            for account in accounts.iter() {
+                let required = Self::checked_sum(
+                    accounts
+                        .iter()
+                        .zip(amounts.iter())
+                        .filter(|(a, _)| *a == account)
+                        .map(|(_, amount)| *amount),
+                )?;
+                if self.stored_balance(*account) < required {
+                    return Err(Error::InsufficientBalance);
+                }
+            }
+
+            for (account, amount) in accounts.iter().zip(amounts) {
+                self.inner_burn(*account, amount)?;
+            }
+            self.env().emit_event(BatchBurnExecuted {
+                accounts_count: accounts.len() as u32,
+                total_burned,
+            });
+            Ok(())
+        }
+
+        fn ensure_batch_len(len: usize) -> Result<()> {
+            if len > MAX_BATCH_LEN {
+                return Err(Error::BatchTooLarge);
+            }
+            Ok(())
+        }
+
+        /// 列表金额求和, 溢出时返回 AggregateOverflow
+        fn checked_sum<I: IntoIterator<Item = Balance>>(values: I) -> Result<Balance> {
+            values
+                .into_iter()
+                .try_fold(0, |acc: Balance, value| acc.checked_add(value))
+                .ok_or(Error::AggregateOverflow)
+        }
+    }
+    // 代币年龄: 每批代币记录铸造区块, 转账时先转出最早的批次
+    impl Erc20 {
+        /// 账户持有的最早一批代币的铸造区块
+        #[ink(message)]
+        pub fn oldest_tokens_block(&self, account: AccountId) -> Option<u32> {
+            self.age_bucket_blocks
+                .get(&account)
+                .and_then(|blocks| blocks.first().copied())
+        }
+
+        /// 按数量加权的平均代币年龄(区块数)
+        #[ink(message)]
+        pub fn average_token_age_blocks(&self, account: AccountId) -> u64 {
+            let current = self.env().block_number();
+            let blocks = match self.age_bucket_blocks.get(&account) {
+                Some(blocks) => blocks,
+                None => return 0,
+            };
+            let mut total: Balance = 0;
+            let mut weighted: Balance = 0;
+            for block in blocks {
+                let amount = self
+                    .age_buckets
+                    .get(&(account, *block))
+                    .copied()
+                    .unwrap_or_default();
+                let age = Balance::from(current.saturating_sub(*block));
+                total = total.saturating_add(amount);
+                weighted = weighted.saturating_add(amount.saturating_mul(age));
+            }
+            if total == 0 {
+                return 0;
+            }
+            (weighted / total) as u64
+        }
+
+        fn age_credit(&mut self, account: AccountId, block: u32, value: Balance) {
+            if value == 0 || !self.is_feature_enabled(FEATURE_AGE_TRACKING) {
+                return;
+            }
+            let bucket = self
+                .age_buckets
+                .get(&(account, block))
+                .copied()
+                .unwrap_or_default();
+            let mut merge = None;
+            if bucket == 0 {
+                let mut blocks = self
+                    .age_bucket_blocks
+                    .get(&account)
+                    .cloned()
+                    .unwrap_or_default();
+                let pos = blocks.binary_search(&block).unwrap_or_else(|pos| pos);
+                blocks.insert(pos, block);
+                if blocks.len() > MAX_AGE_BUCKETS {
+                    merge = Some((blocks.remove(0), blocks[0]));
+                }
+                crate::metering::note_aux_write();
+                self.age_bucket_blocks.insert(account, blocks);
+            }
+            crate::metering::note_aux_write();
+            self.age_buckets.insert((account, block), bucket + value);
+            if let Some((oldest, next)) = merge {
+                self.merge_age_bucket(account, oldest, next);
+            }
+        }
+
+        // 最早的批次并入下一批, 按较晚的区块计龄, 所以合并只会低估代币年龄
+        fn merge_age_bucket(&mut self, account: AccountId, oldest: u32, next: u32) {
+            crate::metering::note_aux_write();
+            let amount = self
+                .age_buckets
+                .take(&(account, oldest))
+                .unwrap_or_default();
+            let bucket = self
+                .age_buckets
+                .get(&(account, next))
+                .copied()
+                .unwrap_or_default();
+            crate::metering::note_aux_write();
+            self.age_buckets.insert((account, next), bucket + amount);
+        }
+
+        /// 从最早的批次开始扣除 value, 返回被扣除的 (铸造区块, 数量)
+        fn age_debit(&mut self, account: AccountId, value: Balance) -> Vec<(u32, Balance)> {
+            if value == 0 || !self.is_feature_enabled(FEATURE_AGE_TRACKING) {
+                return Vec::new();
+            }
+            let consumed = self.age_consumption(account, value);
+            let mut emptied = 0;
+            for (block, amount, bucket) in consumed.iter().copied() {
+                crate::metering::note_aux_write();
+                if amount == bucket {
+                    self.age_buckets.take(&(account, block));
+                    emptied += 1;
+                } else {
+                    // 只可能是最后一个被部分消耗的批次
+                    self.age_buckets.insert((account, block), bucket - amount);
+                }
+            }
+
+            let mut blocks = self
+                .age_bucket_blocks
+                .get(&account)
+                .cloned()
+                .unwrap_or_default();
+            blocks.drain(..emptied);
+            crate::metering::note_aux_write();
+            if blocks.is_empty() {
+                self.age_bucket_blocks.take(&account);
+            } else {
+                self.age_bucket_blocks.insert(account, blocks);
+            }
+            consumed
+                .into_iter()
+                .map(|(block, amount, _)| (block, amount))
+                .collect()
+        }
+
+        /// 从最早的批次开始扣除 value 时依次经过的 (铸造区块, 扣除数量, 批次原有数量), 不写存储
+        fn age_consumption(
+            &self,
+            account: AccountId,
+            value: Balance,
+        ) -> Vec<(u32, Balance, Balance)> {
+            let mut consumed = Vec::new();
+            let mut remaining = value;
+            for block in self
+                .age_bucket_blocks
+                .get(&account)
+                .cloned()
+                .unwrap_or_default()
+            {
+                if remaining == 0 {
+                    break;
+                }
+                let bucket = self
+                    .age_buckets
+                    .get(&(account, block))
+                    .copied()
+                    .unwrap_or_default();
+                let amount = core::cmp::min(bucket, remaining);
+                remaining -= amount;
+                consumed.push((block, amount, bucket));
+            }
+            consumed
+        }
+    }
+    // 账户抽象: bundler 代替用户提交经用户签名的 UserOp, 用户本身不需要支付 gas
+    impl Erc20 {
+        #[ink(message)]
+        pub fn user_op_nonce(&self, sender: AccountId) -> u64 {
+            self.user_op_nonces
+                .get(&sender)
+                .copied()
+                .unwrap_or_default()
+        }
+
+        /// 用户需要签名的哈希: Blake2x256(合约地址, sender, nonce, call_data, valid_after, valid_until)
+        #[ink(message)]
+        pub fn user_op_hash(&self, user_op: UserOp) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                self.env().account_id(),
+                user_op.sender,
+                user_op.nonce,
+                &user_op.call_data,
+                user_op.valid_after,
+                user_op.valid_until,
+            )))
+        }
+
+        /// 校验 UserOp, 有效期取自 UserOp 本身, 由调用方按 ValidationData::pack 转成 uint256.
+        /// 本合约不向 entry point 预付费用, missing_account_funds 仅为接口兼容保留
+        #[ink(message)]
+        pub fn validate_user_op(
+            &self,
+            user_op: UserOp,
+            user_op_hash: Hash,
+            _missing_account_funds: Balance,
+        ) -> Result<ValidationData> {
+            self.ensure_feature(FEATURE_USER_OPS)?;
+            if user_op.nonce != self.user_op_nonce(user_op.sender) {
+                return Err(Error::InvalidNonce);
+            }
+            let sig_failed = user_op_hash != self.user_op_hash(user_op.clone())
+                || !self.user_op_signature_valid(&user_op, &user_op_hash);
+            Ok(ValidationData {
+                sig_failed,
+                valid_until: user_op.valid_until,
+                valid_after: user_op.valid_after,
+            })
+        }
+
+        /// 校验签名和 nonce 后, 以 sender 的身份执行 call_data
+        #[ink(message)]
+        pub fn execute_user_op(&mut self, user_op: UserOp) -> Result<()> {
+            self.ensure_feature(FEATURE_USER_OPS)?;
+            let nonce = self.user_op_nonce(user_op.sender);
+            if user_op.nonce != nonce {
+                return Err(Error::InvalidNonce);
+            }
+            let user_op_hash = self.user_op_hash(user_op.clone());
+            if !self.user_op_signature_valid(&user_op, &user_op_hash) {
+                return Err(Error::InvalidUserOpSignature);
+            }
+            let block = self.env().block_number();
+            if block < user_op.valid_after
+                || (user_op.valid_until != 0 && block > user_op.valid_until)
+            {
+                return Err(Error::UserOpOutsideWindow);
+            }
+
+            self.user_op_nonces.insert(user_op.sender, nonce + 1);
+            self.dispatch_user_op(user_op.sender, &user_op.call_data)?;
+            self.env().emit_event(UserOpExecuted {
+                sender: user_op.sender,
+                nonce,
+            });
+            Ok(())
+        }
+
+        fn user_op_signature_valid(&self, user_op: &UserOp, user_op_hash: &Hash) -> bool {
+            if user_op.signature.len() != 65 {
+                return false;
+            }
+            let mut signature = [0u8; 65];
+            signature.copy_from_slice(&user_op.signature);
+            let mut message_hash = [0u8; 32];
+            message_hash.copy_from_slice(user_op_hash.as_ref());
+            self.recover_signer(&message_hash, &signature) == Some(user_op.sender)
+        }
+
+        fn dispatch_user_op(&mut self, sender: AccountId, call_data: &[u8]) -> Result<()> {
+            if call_data.len() < 4 {
+                return Err(Error::UnsupportedUserOpCall);
+            }
+            let mut selector = [0u8; 4];
+            selector.copy_from_slice(&call_data[..4]);
+            let mut args = &call_data[4..];
+            match selector {
+                TRANSFER_SELECTOR => {
+                    let (to, value) = scale::Decode::decode(&mut args)
+                        .map_err(|_| Error::UnsupportedUserOpCall)?;
+                    self.transfer_with_fee(sender, to, value)
+                }
+                APPROVE_SELECTOR => {
+                    let (spender, value) = scale::Decode::decode(&mut args)
+                        .map_err(|_| Error::UnsupportedUserOpCall)?;
+                    self.inner_approve(sender, spender, value, ApprovalOrigin::Approve)
+                }
+                TRANSFER_FROM_SELECTOR => {
+                    let (from, to, value) = scale::Decode::decode(&mut args)
+                        .map_err(|_| Error::UnsupportedUserOpCall)?;
+                    self.inner_transfer_from(sender, from, to, value)
+                }
+                _ => Err(Error::UnsupportedUserOpCall),
+            }
+        }
+    }
+    // 治理: 持币人发起提案并投票, 长期不投票的账户权重会衰减
+    impl Erc20 {
+        #[ink(message)]
+        pub fn proposal(&self, proposal_id: u64) -> Option<Proposal> {
+            self.proposals.get(&proposal_id).cloned()
+        }
+
+        /// 账户当前的投票权重, 含声誉加成和衰减. 只读查询, 衰减事件在投票时发出
+        #[ink(message)]
+        pub fn get_votes(&self, account: AccountId) -> Balance {
+            if !self.may_view(account) {
+                return 0;
+            }
+            let base_votes = self.boosted_votes(account, self.base_votes(account));
+            self.decay_votes(account, base_votes)
+        }
+
+        /// account 在 block 结束时的投票权 (不含声誉加成和衰减), block 尚未结束时为当前值.
+        /// 私有余额模式下无权查看时返回 0
+        #[ink(message)]
+        pub fn get_past_votes(&self, account: AccountId, block: u32) -> Balance {
+            if !self.may_view(account) {
+                return 0;
+            }
+            self.checkpointed_votes(account, block)
+        }
+
+        fn checkpointed_votes(&self, account: AccountId, block: u32) -> Balance {
+            let checkpoints = match self.vote_checkpoints.get(&account) {
+                Some(checkpoints) => checkpoints,
+                None => return 0,
+            };
+            match checkpoints.binary_search_by_key(&block, |(at, _)| *at) {
+                Ok(index) => checkpoints[index].1,
+                Err(0) => 0,
+                Err(index) => checkpoints[index - 1].1,
+            }
+        }
+
+        /// 按 block 结束时的检查点计算的投票权, 再加上声誉加成和衰减, 衰减生效时发出 VotingWeightDecayed.
+        /// 快照之后转入的代币不计入, 转走的代币也不会在另一个账户上再投一次
+        fn snapshot_votes(&self, account: AccountId, block: u32) -> Balance {
+            let base_votes = self.boosted_votes(account, self.checkpointed_votes(account, block));
+            let votes = self.decay_votes(account, base_votes);
+            if votes < base_votes {
+                self.env().emit_event(VotingWeightDecayed {
+                    account,
+                    old_weight: base_votes,
+                    new_weight: votes,
+                });
+            }
+            votes
+        }
+
+        fn boosted_votes(&self, account: AccountId, base_votes: Balance) -> Balance {
+            base_votes.saturating_add(Self::bps_of(base_votes, self.reputation_bonus_bps(account)))
+        }
+
+        fn decay_votes(&self, account: AccountId, votes: Balance) -> Balance {
+            if !*self.voting_weight_decay_enabled {
+                return votes;
+            }
+            let last_active = self
+                .last_vote_block
+                .get(&account)
+                .copied()
+                .unwrap_or(*self.voting_decay_start_block);
+            let elapsed = self.env().block_number().saturating_sub(last_active);
+            Self::decayed_votes(votes, *self.voting_weight_decay_rate, elapsed)
+        }
+
+        #[ink(message)]
+        pub fn voting_decay_params(&self) -> (bool, u16) {
+            (
+                *self.voting_weight_decay_enabled,
+                *self.voting_weight_decay_rate,
+            )
+        }
+
+        #[ink(message)]
+        pub fn set_voting_decay_params(&mut self, enabled: bool, rate: u16) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            if enabled && !*self.voting_weight_decay_enabled {
+                *self.voting_decay_start_block = self.env().block_number();
+            }
+            *self.voting_weight_decay_enabled = enabled;
+            *self.voting_weight_decay_rate = rate;
+            Ok(())
+        }
+
+        /// 发起提案, 投票期为 voting_period 个区块
+        #[ink(message)]
+        pub fn propose(&mut self, description_hash: Hash, voting_period: u32) -> Result<u64> {
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            let proposer = self.env().caller();
+            let id = *self.next_proposal_id;
+            let start_block = self.env().block_number();
+            let end_block = start_block.saturating_add(voting_period);
+            self.proposals.insert(
+                id,
+                Proposal {
+                    id,
+                    proposer,
+                    description_hash,
+                    for_votes: 0,
+                    against_votes: 0,
+                    start_block,
+                    end_block,
+                    executed: false,
+                    eta: end_block.saturating_add(*self.execution_grace_period_blocks),
+                    vetoed: false,
+                    voters: 0,
+                },
+            );
+            *self.next_proposal_id += 1;
+            self.env().emit_event(ProposalCreated {
+                proposal_id: id,
+                proposer,
+                description_hash,
+                end_block,
+            });
+            Ok(id)
+        }
+
+        /// 投票, 同时重置调用者的衰减计时. 从提案创建的下一个区块开始, 权重按创建区块结束时的检查点计算
+        #[ink(message)]
+        pub fn cast_vote(&mut self, proposal_id: u64, support: bool) -> Result<()> {
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            let voter = self.env().caller();
+            let mut proposal = self
+                .proposals
+                .get(&proposal_id)
+                .cloned()
+                .ok_or(Error::ProposalNotFound)?;
+            if self.env().block_number() <= proposal.start_block {
+                return Err(Error::VotingNotStarted);
+            }
+            if self.env().block_number() > proposal.end_block {
+                return Err(Error::VotingClosed);
+            }
+            if self.proposal_votes.contains_key(&(proposal_id, voter)) {
+                return Err(Error::AlreadyVoted);
+            }
+
+            let burn_cost = *self.burn_vote_cost;
+            let weight = if burn_cost > 0 {
+                if self.stored_balance(voter) < burn_cost {
+                    return Err(Error::InsufficientBalanceForVote);
+                }
+                // 销毁在记票之前完成, 销毁失败(例如暂停)时不记票
+                self.inner_burn(voter, burn_cost)?;
+                *self.burned_vote_total = self.burned_vote_total.saturating_add(burn_cost);
+                let burned = self
+                    .per_proposal_burn(proposal_id)
+                    .saturating_add(burn_cost);
+                self.proposal_vote_burns.insert(proposal_id, burned);
+                self.env().emit_event(BurnedVoteCast {
+                    voter,
+                    proposal_id,
+                    support,
+                    burned_amount: burn_cost,
+                });
+                burn_cost
+            } else if *self.weighted_voting_enabled {
+                self.snapshot_weighted_votes(voter, proposal.start_block)
+            } else {
+                self.snapshot_votes(voter, proposal.start_block)
+            };
+            if support {
+                proposal.for_votes = proposal.for_votes.saturating_add(weight);
+            } else {
+                proposal.against_votes = proposal.against_votes.saturating_add(weight);
+            }
+            if voter != proposal.proposer {
+                proposal.voters += 1;
+            }
+            self.proposals.insert(proposal_id, proposal);
+            self.proposal_votes.insert((proposal_id, voter), support);
+            self.last_vote_block
+                .insert(voter, self.env().block_number());
+            self.env().emit_event(VoteCast {
+                proposal_id,
+                voter,
+                support,
+                weight,
+            });
+            Ok(())
+        }
+
+        /// 投票期结束后任何人都可以结算, 赞成票多于反对票即通过, 并更新提案人的声誉.
+        /// 通过的提案只有在投票总权重达到 REPUTATION_QUORUM_BPS 且至少有 MIN_REPUTATION_VOTERS
+        /// 个其他账户投票时才加分, 未通过的提案照常扣分
+        #[ink(message)]
+        pub fn finalize_proposal(&mut self, proposal_id: u64) -> Result<()> {
+            let mut proposal = self
+                .proposals
+                .get(&proposal_id)
+                .cloned()
+                .ok_or(Error::ProposalNotFound)?;
+            if self.env().block_number() <= proposal.end_block {
+                return Err(Error::VotingOpen);
+            }
+            if proposal.executed {
+                return Err(Error::ProposalAlreadyFinalized);
+            }
+            if proposal.vetoed {
+                return Err(Error::ProposalWasVetoed);
+            }
+            if self.env().block_number() < proposal.eta {
+                return Err(Error::ProposalInGracePeriod);
+            }
+            let passed = proposal.for_votes > proposal.against_votes;
+            let turnout = proposal.for_votes.saturating_add(proposal.against_votes);
+            let earns_reputation = proposal.voters >= MIN_REPUTATION_VOTERS
+                && turnout >= Self::bps_of(self.total_supply(), REPUTATION_QUORUM_BPS);
+            let proposer = proposal.proposer;
+            proposal.executed = true;
+            self.proposals.insert(proposal_id, proposal);
+            if !passed {
+                self.update_reputation(proposer, ReputationAction::ProposalFailed)?;
+            } else if earns_reputation {
+                self.update_reputation(proposer, ReputationAction::ProposalPassed)?;
+            }
+            self.env().emit_event(ProposalFinalized {
+                proposal_id,
+                passed,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn execution_grace_period_blocks(&self) -> u32 {
+            *self.execution_grace_period_blocks
+        }
+
+        /// 只影响之后创建的提案, 已有提案的 eta 在创建时确定
+        #[ink(message)]
+        pub fn set_execution_grace_period(&mut self, blocks: u32) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            *self.execution_grace_period_blocks = blocks;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn veto_guardian(&self) -> Option<AccountId> {
+            *self.veto_guardian
+        }
+
+        /// None 表示不设守护人, 提案无法被否决
+        #[ink(message)]
+        pub fn set_veto_guardian(&mut self, guardian: Option<AccountId>) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            *self.veto_guardian = guardian;
+            Ok(())
+        }
+
+        /// 守护人在投票结束后, 结算之前的等待期内否决提案, 被否决的提案不能结算
+        #[ink(message)]
+        pub fn veto_proposal(&mut self, proposal_id: u64) -> Result<()> {
+            let guardian = self.env().caller();
+            if *self.veto_guardian != Some(guardian) {
+                return Err(Error::NotVetoGuardian);
+            }
+            let mut proposal = self
+                .proposals
+                .get(&proposal_id)
+                .cloned()
+                .ok_or(Error::ProposalNotFound)?;
+            let now = self.env().block_number();
+            if now <= proposal.end_block || now >= proposal.eta {
+                return Err(Error::NotInGracePeriod);
+            }
+            if proposal.vetoed {
+                return Err(Error::ProposalWasVetoed);
+            }
+            proposal.vetoed = true;
+            self.proposals.insert(proposal_id, proposal);
+            self.env().emit_event(ProposalVetoed {
+                proposal_id,
+                guardian,
+            });
+            Ok(())
+        }
+
+        /// effective = base * max(0, 1 - rate * elapsed / 1000 / 10000)
+        fn decayed_votes(base_votes: Balance, rate: u16, elapsed: u32) -> Balance {
+            let decay_bps = u128::from(rate) * u128::from(elapsed) / 1000;
+            if decay_bps >= 10_000 {
+                return 0;
+            }
+            base_votes / 10_000 * (10_000 - decay_bps)
+                + base_votes % 10_000 * (10_000 - decay_bps) / 10_000
+        }
+    }
+    // 弹性供应: 总量偏离目标区间时, 向合约储备账户增发或从中销毁
+    impl Erc20 {
+        /// (目标总量, 区间基点, 上一次调节的区块), 从未调节过时区块为 0
+        #[ink(message)]
+        pub fn elasticity_params(&self) -> (Balance, u16, u32) {
+            (
+                *self.target_supply,
+                *self.supply_band_bps,
+                self.last_rebalance_block.unwrap_or(0),
+            )
+        }
+
+        #[ink(message)]
+        pub fn set_elasticity_params(
+            &mut self,
+            target_supply: Balance,
+            band_bps: u16,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_ELASTICITY)?;
+            *self.target_supply = target_supply;
+            *self.supply_band_bps = band_bps;
+            Ok(())
+        }
+
+        /// 只有授权的 rebaser 可以调用, 每次调节偏差的 10%, 两次调用至少间隔 100 个区块
+        #[ink(message)]
+        pub fn elasticity_rebalance(&mut self) -> Result<()> {
+            self.ensure_feature(FEATURE_ELASTICITY)?;
+            self.ensure_rebaser()?;
+            let target = *self.target_supply;
+            if target == 0 {
+                return Err(Error::ElasticityNotConfigured);
+            }
+            let now = self.env().block_number();
+            if let Some(last) = *self.last_rebalance_block {
+                if now < last.saturating_add(REBALANCE_INTERVAL_BLOCKS) {
+                    return Err(Error::RebalanceTooSoon);
+                }
+            }
+
+            let supply = self.total_supply();
+            let band = target / 10_000 * u128::from(*self.supply_band_bps)
+                + target % 10_000 * u128::from(*self.supply_band_bps) / 10_000;
+            let reserve = self.env().account_id();
+            if supply < target.saturating_sub(band) {
+                let minted = (target - supply) / 10_000 * REBALANCE_STEP_BPS
+                    + (target - supply) % 10_000 * REBALANCE_STEP_BPS / 10_000;
+                self.inner_mint(reserve, minted)?;
+                self.env().emit_event(ElasticityRebalanceUp { minted });
+            } else if supply > target.saturating_add(band) {
+                let deviation = (supply - target) / 10_000 * REBALANCE_STEP_BPS
+                    + (supply - target) % 10_000 * REBALANCE_STEP_BPS / 10_000;
+                // 只能销毁储备中的代币, 合约账户里托管的质押和托管款不动
+                let burned = core::cmp::min(deviation, self.unallocated_contract_balance()?);
+                if burned > 0 {
+                    self.inner_burn(reserve, burned)?;
+                    self.env().emit_event(ElasticityRebalanceDown { burned });
+                }
+            }
+            *self.last_rebalance_block = Some(now);
+            Ok(())
+        }
+    }
+    // 转账手续费
+    impl Erc20 {
+        #[ink(message)]
+        pub fn transfer_fee(&self) -> (FeeStrategy, AccountId) {
+            ((*self.fee_strategy).clone(), *self.fee_recipient)
+        }
+
+        /// 按当前策略转账 value 需要的手续费, 不含声誉减免和创世 NFT 免费
+        #[ink(message)]
+        pub fn fee_quote(&self, value: Balance) -> Result<Balance> {
+            if !self.is_feature_enabled(FEATURE_FEES) {
+                return Ok(0);
+            }
+            self.fee_strategy.compute_fee(value)
+        }
+
+        #[ink(message)]
+        pub fn fees_collected(&self) -> Balance {
+            *self.fees_collected
+        }
+
+        #[ink(message)]
+        pub fn set_transfer_fee(&mut self, fee_bps: u16, recipient: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_FEES)?;
+            if fee_bps > 10_000 {
+                return Err(Error::InvalidBps);
+            }
+            *self.fee_strategy = FeeStrategy::Bps(fee_bps);
+            *self.fee_recipient = recipient;
+            Ok(())
+        }
+
+        /// 切换手续费策略, 收款人不变
+        #[ink(message)]
+        pub fn set_fee_strategy(&mut self, strategy: FeeStrategy) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_FEES)?;
+            strategy.validate()?;
+            *self.fee_strategy = strategy;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn protocol_fee(&self) -> u16 {
+            *self.protocol_fee_bps
+        }
+
+        /// 手续费中转入金库的比例
+        #[ink(message)]
+        pub fn set_protocol_fee(&mut self, bps: u16) -> Result<()> {
+            self.ensure_owner()?;
+            if bps > 10_000 {
+                return Err(Error::InvalidBps);
+            }
+            *self.protocol_fee_bps = bps;
+            Ok(())
+        }
+
+        /// 用户发起的转账走这里, 检查最小金额, 手续费从 value 中扣除
+        fn transfer_with_fee(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            self.charge_and_transfer(from, to, value, TransferOrigin::User(value))
+        }
+
+        fn charge_and_transfer(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            origin: TransferOrigin,
+        ) -> Result<()> {
+            let mut changes = TokenChanges::default();
+            self.charge_transfer(from, to, value, origin, &mut changes)?;
+            self.after_token_transfer(&changes)
+        }
+
+        // 转账和手续费的各笔写入都完成后才由调用方通知回调
+        fn charge_transfer(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            origin: TransferOrigin,
+            changes: &mut TokenChanges,
+        ) -> Result<()> {
+            if let TransferOrigin::User(_)
+            | TransferOrigin::Batched(_)
+            | TransferOrigin::Delegated(_) = origin
+            {
+                self.consume_compliance_clearance(from, to, value)?;
+            }
+            self.note_tax_rate();
+            let (fee, contribution, net) =
+                self.transfer_amounts(&self.policy_config(), from, value)?;
+            if net < value && self.stored_balance(from) < value {
+                return Err(Error::InsufficientBalance);
+            }
+            self.write_transfer(from, to, net, TransferKind::Normal, origin, changes)?;
+            if fee > 0 {
+                self.collect_fee(from, fee, changes)?;
+            }
+            self.note_sender_volume(from, value, fee);
+            if contribution > 0 {
+                self.contribute_to_jackpot(from, contribution, changes)?;
+            }
+            self.draw_jackpot(from, to, value, changes)
+        }
+
+        /// 存储中的当前配置
+        fn policy_config(&self) -> PolicyConfig<'_> {
+            PolicyConfig {
+                policy: &self.policy,
+                fee_strategy: &self.fee_strategy,
+                scheduled_rate_bps: self.active_tax_entry().map(|entry| entry.fee_rate_bps),
+                fees_enabled: self.is_feature_enabled(FEATURE_FEES),
+                jackpot_fee_rate: *self.jackpot_fee_rate,
+            }
+        }
+
+        /// config 下 from 转出 value 的 (手续费, 奖池注入, 到账金额)
+        fn transfer_amounts(
+            &self,
+            config: &PolicyConfig,
+            from: AccountId,
+            value: Balance,
+        ) -> Result<(Balance, Balance, Balance)> {
+            let fee = self.compute_transfer_fee(config, from, value)?;
+            let contribution = Self::bps_of(value, config.jackpot_fee_rate);
+            // 手续费和奖池注入都从 value 中扣除, 两者比例之和超过 100% 时拒绝
+            let net = fee
+                .checked_add(contribution)
+                .and_then(|deducted| value.checked_sub(deducted))
+                .ok_or(Error::InvalidBps)?;
+            Ok((fee, contribution, net))
+        }
+
+        // 创世 NFT 持有人转账免手续费
+        fn compute_transfer_fee(
+            &self,
+            config: &PolicyConfig,
+            from: AccountId,
+            value: Balance,
+        ) -> Result<Balance> {
+            if !config.fees_enabled || self.is_genesis_nft_holder(from) {
+                return Ok(0);
+            }
+            let discount = self.reputation_bonus_bps(from);
+            match config.scheduled_rate_bps {
+                Some(bps) => FeeStrategy::Bps(bps).compute_discounted_fee(value, discount),
+                None => config.fee_strategy.compute_discounted_fee(value, discount),
+            }
+        }
+
+        // 手续费转给 fee_recipient, 其中协议手续费转入合约账户
+        fn collect_fee(
+            &mut self,
+            from: AccountId,
+            fee: Balance,
+            changes: &mut TokenChanges,
+        ) -> Result<()> {
+            let protocol_fee = Self::bps_of(fee, *self.protocol_fee_bps);
+            let recipient = *self.fee_recipient;
+            self.write_transfer(
+                from,
+                recipient,
+                fee - protocol_fee,
+                TransferKind::Fee,
+                TransferOrigin::Fee,
+                changes,
+            )?;
+            *self.fees_collected = self.fees_collected.saturating_add(fee);
+            *self.recipient_fees_total =
+                self.recipient_fees_total.saturating_add(fee - protocol_fee);
+            *self.treasury_fees_total = self.treasury_fees_total.saturating_add(protocol_fee);
+            *self.fee_collections_count += 1;
+
+            // 协议手续费转入合约账户记到金库
+            if protocol_fee > 0 {
+                let contract = self.env().account_id();
+                self.write_transfer(
+                    from,
+                    contract,
+                    protocol_fee,
+                    TransferKind::Fee,
+                    TransferOrigin::Fee,
+                    changes,
+                )?;
+                if *self.auto_distribute_threshold == 0 {
+                    *self.treasury_balance += protocol_fee;
+                    self.escrow_in(PoolId::Treasury, protocol_fee);
+                } else {
+                    *self.pending_dividends += protocol_fee;
+                    self.escrow_in(PoolId::PendingDividends, protocol_fee);
+                    if *self.pending_dividends >= *self.auto_distribute_threshold {
+                        self.distribute_dividends()?;
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        /// value * bps / 10000, 先除后乘避免溢出
+        fn bps_of(value: Balance, bps: u16) -> Balance {
+            let bps = u128::from(bps);
+            value / 10_000 * bps + value % 10_000 * bps / 10_000
+        }
+    }
+    // 幂等转账: 中继超时重发同一笔交易时只执行一次
+    impl Erc20 {
+        #[ink(message)]
+        pub fn idempotency_key_ttl(&self) -> u32 {
+            *self.idempotency_key_ttl
+        }
+
+        #[ink(message)]
+        pub fn set_idempotency_key_ttl(&mut self, blocks: u32) -> Result<()> {
+            self.ensure_owner()?;
+            *self.idempotency_key_ttl = blocks;
+            Ok(())
+        }
+
+        /// key 已被 caller 使用过时不做任何转账, 直接返回 Ok
+        #[ink(message)]
+        pub fn transfer_idempotent(
+            &mut self,
+            to: AccountId,
+            value: Balance,
+            key: [u8; 32],
+        ) -> Result<()> {
+            self.ensure_feature(FEATURE_IDEMPOTENCY)?;
+            let caller = self.env().caller();
+            if self.replay_of(caller, key) {
+                return Ok(());
+            }
+            self.transfer_with_fee(caller, to, value)?;
+            self.use_idempotency_key(caller, key);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn transfer_from_idempotent(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            key: [u8; 32],
+        ) -> Result<()> {
+            self.ensure_feature(FEATURE_IDEMPOTENCY)?;
+            let caller = self.env().caller();
+            if self.replay_of(caller, key) {
+                return Ok(());
+            }
+            self.inner_transfer_from(caller, from, to, value)?;
+            self.use_idempotency_key(caller, key);
+            Ok(())
+        }
+
+        // 未过期的 key 视为重放并发出事件, 过期的 key 顺便清理掉
+        fn replay_of(&mut self, caller: AccountId, key: [u8; 32]) -> bool {
+            let used_at = match self.idempotency_keys.get(&(caller, key)) {
+                Some(block) => *block,
+                None => return false,
+            };
+            let now = self.env().block_number();
+            if now >= used_at.saturating_add(*self.idempotency_key_ttl) {
+                self.idempotency_keys.take(&(caller, key));
+                return false;
+            }
+            self.env().emit_event(IdempotentReplay { caller, key });
+            true
+        }
+
+        fn use_idempotency_key(&mut self, caller: AccountId, key: [u8; 32]) {
+            let now = self.env().block_number();
+            self.idempotency_keys.insert((caller, key), now);
+        }
+    }
+    // 价格稳定基金: 价格偏离锚定区间时按锚定价与用户买卖代币
+    impl Erc20 {
+        #[ink(message)]
+        pub fn stability_fund(&self) -> (Balance, Balance) {
+            (*self.stability_fund_balance, *self.native_reserve)
+        }
+
+        #[ink(message)]
+        pub fn stability_params(&self) -> (Balance, u16) {
+            (*self.stability_peg, *self.stability_band_bps)
+        }
+
+        #[ink(message)]
+        pub fn set_price_oracle(&mut self, oracle: Option<AccountId>) -> Result<()> {
+            self.ensure_owner()?;
+            *self.price_oracle = oracle;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_stability_params(&mut self, peg: Balance, band_bps: u16) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_STABILITY)?;
+            if band_bps > 10_000 {
+                return Err(Error::InvalidBps);
+            }
+            *self.stability_peg = peg;
+            *self.stability_band_bps = band_bps;
+            Ok(())
+        }
+
+        #[ink(message, payable)]
+        pub fn fund_stability_native(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_STABILITY)?;
+            let value = self.env().transferred_balance();
+            *self.native_reserve = self
+                .native_reserve
+                .checked_add(value)
+                .ok_or(Error::Overflow)?;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn fund_stability_tokens(&mut self, amount: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_STABILITY)?;
+            let fund = self
+                .stability_fund_balance
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+            let owner = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(owner, contract, amount)?;
+            *self.stability_fund_balance = fund;
+            self.escrow_in(PoolId::StabilityFund, amount);
+            Ok(())
+        }
+
+        /// 价格高于区间上沿时, 用锚定价从基金买入代币, 需要附带恰好的原生币
+        #[ink(message, payable)]
+        pub fn stability_buy(&mut self, amount: Balance) -> Result<()> {
+            self.ensure_feature(FEATURE_STABILITY)?;
+            let (peg, band) = self.stability_band();
+            if self.oracle_price()? <= peg.saturating_add(band) {
+                return Err(Error::PriceWithinBand);
+            }
+            if amount > *self.stability_fund_balance {
+                return Err(Error::InsufficientStabilityFund);
+            }
+            let cost = Self::native_value(amount, peg)?;
+            let paid = self.env().transferred_balance();
+            if paid != cost {
+                return Err(Error::IncorrectPayment);
+            }
+            let reserve = self
+                .native_reserve
+                .checked_add(paid)
+                .ok_or(Error::Overflow)?;
+
+            let buyer = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(contract, buyer, amount)?;
+            *self.stability_fund_balance -= amount;
+            self.escrow_out(PoolId::StabilityFund, amount);
+            *self.native_reserve = reserve;
+            self.env().emit_event(StabilityBuy {
+                buyer,
+                amount,
+                paid,
+            });
+            Ok(())
+        }
+
+        /// 价格低于区间下沿时, 基金用原生储备按锚定价收购代币
+        #[ink(message)]
+        pub fn stability_sell(&mut self, amount: Balance) -> Result<()> {
+            self.ensure_feature(FEATURE_STABILITY)?;
+            self.ensure_not_paused(PAUSE_TRANSFER)?;
+            let (peg, band) = self.stability_band();
+            if self.oracle_price()? >= peg.saturating_sub(band) {
+                return Err(Error::PriceWithinBand);
+            }
+            let seller = self.env().caller();
+            if self.stored_balance(seller) < amount {
+                return Err(Error::InsufficientBalance);
+            }
+            let received = Self::native_value(amount, peg)?;
+            if received > *self.native_reserve {
+                return Err(Error::InsufficientNativeReserve);
+            }
+            let fund = self
+                .stability_fund_balance
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+
+            // 原生币转出失败时代币还没动
+            self.env()
+                .transfer(seller, received)
+                .map_err(|_| Error::NativeTransferFailed)?;
+            *self.native_reserve -= received;
+            let contract = self.env().account_id();
+            self.inner_transfer(seller, contract, amount)?;
+            *self.stability_fund_balance = fund;
+            self.escrow_in(PoolId::StabilityFund, amount);
+            self.env().emit_event(StabilitySell {
+                seller,
+                amount,
+                received,
+            });
+            Ok(())
+        }
+
+        fn oracle_price(&self) -> Result<Balance> {
+            let oracle = (*self.price_oracle).ok_or(Error::OracleNotSet)?;
+            let output = self.do_call(oracle, LATEST_PRICE_SELECTOR, &[], ORACLE_GAS_LIMIT)?;
+            <Balance as scale::Decode>::decode(&mut &output[..]).map_err(|_| Error::ExternalCall {
+                callee: oracle,
+                selector: LATEST_PRICE_SELECTOR,
+                code: call::CALL_ERROR_UNKNOWN,
+            })
+        }
+
+        // 返回 (锚定价, 区间半宽)
+        fn stability_band(&self) -> (Balance, Balance) {
+            let peg = *self.stability_peg;
+            (peg, Self::bps_of(peg, *self.stability_band_bps))
+        }
+
+        fn native_value(amount: Balance, price: Balance) -> Result<Balance> {
+            amount
+                .checked_mul(price)
+                .map(|value| value / PRICE_SCALE)
+                .ok_or(Error::Overflow)
+        }
+    }
+    // 增发锁定期: 账户收到增发后一段时间内不能转出
+    impl Erc20 {
+        #[ink(message)]
+        pub fn mint_transfer_delay(&self) -> u32 {
+            self.policy.mint_transfer_delay_blocks
+        }
+
+        #[ink(message)]
+        pub fn set_mint_transfer_delay(&mut self, blocks: u32) -> Result<()> {
+            self.ensure_owner()?;
+            self.policy.mint_transfer_delay_blocks = blocks;
+            Ok(())
+        }
+
+        /// 账户的代币从哪个区块起可以转出, 0 表示没有锁定
+        #[ink(message)]
+        pub fn tokens_transferable_at(&self, account: AccountId) -> u32 {
+            match self.minted_at.get(&account) {
+                Some(block) => block.saturating_add(self.policy.mint_transfer_delay_blocks),
+                None => 0,
+            }
+        }
+
+        fn apply_fresh_mint_lock(&mut self, account: AccountId, block: u32) {
+            if account == self.env().account_id() {
+                return;
+            }
+            self.minted_at.insert(account, block);
+            let delay = self.policy.mint_transfer_delay_blocks;
+            if delay > 0 {
+                self.env().emit_event(FreshMintLockApplied {
+                    account,
+                    unlock_block: block.saturating_add(delay),
+                });
+            }
+        }
+    }
+    // 最小转账金额
+    impl Erc20 {
+        #[ink(message)]
+        pub fn min_transfer(&self) -> Balance {
+            self.policy.min_transfer
+        }
+
+        #[ink(message)]
+        pub fn set_min_transfer(&mut self, minimum: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            self.policy.min_transfer = minimum;
+            self.env().emit_event(MinTransferChanged { minimum });
+            Ok(())
+        }
+
+        /// 转出调用者的全部余额, 不受最小金额限制, 保证账户总能清空
+        #[ink(message)]
+        pub fn transfer_all(&mut self, to: AccountId) -> Result<()> {
+            let from = self.env().caller();
+            let value = self.stored_balance(from);
+            self.charge_and_transfer(from, to, value, TransferOrigin::Internal)
+        }
+    }
+    // 质押: 按质押量分配奖励池
+    impl Erc20 {
+        #[ink(message)]
+        pub fn stake_of(&self, account: AccountId) -> Balance {
+            self.stakes.get(&account).copied().unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn total_staked(&self) -> Balance {
+            *self.total_staked
+        }
+
+        /// 已累计但还没领取的奖励
+        #[ink(message)]
+        pub fn pending_rewards(&self, account: AccountId) -> Balance {
+            self.earned(account)
+        }
+
+        #[ink(message)]
+        pub fn stake(&mut self, amount: Balance) -> Result<()> {
+            self.ensure_feature(FEATURE_STAKING)?;
+            let caller = self.env().caller();
+            self.total_staked
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+            let contract = self.env().account_id();
+            self.inner_transfer(caller, contract, amount)?;
+            self.escrow_in(PoolId::Staking, amount);
+
+            // 结算可能复投, 之后再读质押量
+            self.settle_rewards(caller);
+            let staked = self.stake_of(caller);
+            if staked == 0 {
+                self.stake_started.insert(caller, self.env().block_number());
+            }
+            let min_lockup = self.current_min_lockup();
+            self.lock_stake(caller, amount, min_lockup);
+            self.stakes.insert(caller, staked + amount);
+            *self.total_staked += amount;
+            self.env().emit_event(Staked {
+                account: caller,
+                amount,
+            });
+            self.note_lockup_tier(min_lockup);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn unstake(&mut self, amount: Balance) -> Result<()> {
+            let caller = self.env().caller();
+            if self.stake_of(caller) < amount {
+                return Err(Error::InsufficientStake);
+            }
+            if self.unlocked_stake_of(caller) < amount {
+                return Err(Error::StakeLocked);
+            }
+            let min_lockup = self.current_min_lockup();
+            // 超过本周期即时额度的部分进入提取队列, 代币留在合约账户等 keeper 处理
+            let queued = !self.instant_withdrawal_allowed(amount);
+            if queued {
+                self.ensure_withdrawal_capacity()?;
+            } else {
+                let contract = self.env().account_id();
+                self.inner_refund(contract, caller, amount)?;
+                self.note_instant_withdrawal(amount);
+            }
+            self.escrow_out(PoolId::Staking, amount);
+            if queued {
+                self.escrow_in(PoolId::WithdrawalQueue, amount);
+            }
+
+            self.settle_rewards(caller);
+            let staked = self.stake_of(caller);
+            self.stakes.insert(caller, staked - amount);
+            *self.total_staked -= amount;
+            self.env().emit_event(Unstaked {
+                account: caller,
+                amount,
+            });
+            if queued {
+                self.enqueue_withdrawal(caller, amount);
+            }
+            self.note_lockup_tier(min_lockup);
+
+            // 质押满 LONG_TERM_HOLDER_BLOCKS 后解除记一次长期持有, 剩余部分重新计时
+            let block = self.env().block_number();
+            let started = self.stake_started.get(&caller).copied().unwrap_or(block);
+            if staked == amount {
+                self.stake_started.take(&caller);
+            } else if block - started >= LONG_TERM_HOLDER_BLOCKS {
+                self.stake_started.insert(caller, block);
+            }
+            if block - started >= LONG_TERM_HOLDER_BLOCKS {
+                self.update_reputation(caller, ReputationAction::LongTermHolder)?;
+            }
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn claim_rewards(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            let amount = self.earned(caller);
+            if amount == 0 {
+                return Err(Error::NothingToWithdraw);
+            }
+            let queued =
+                *self.reward_vest_duration == 0 && !self.instant_withdrawal_allowed(amount);
+            if queued {
+                self.ensure_withdrawal_capacity()?;
+                self.escrow_in(PoolId::WithdrawalQueue, amount);
+            } else if *self.reward_vest_duration == 0 {
+                let contract = self.env().account_id();
+                self.inner_transfer(contract, caller, amount)?;
+                self.note_instant_withdrawal(amount);
+            } else {
+                self.start_reward_vest(caller, amount)?;
+            }
+            self.escrow_out(PoolId::StakingRewards, amount);
+
+            self.reward_per_token_paid
+                .insert(caller, *self.reward_per_token);
+            self.staking_rewards.take(&caller);
+            self.env().emit_event(RewardsClaimed {
+                account: caller,
+                amount,
+            });
+            if queued {
+                self.enqueue_withdrawal(caller, amount);
+            }
+            Ok(())
+        }
+
+        /// 把已经转入合约账户的 amount 加入奖励池, 按当前质押权重分配
+        fn add_rewards(&mut self, added: Balance) -> Result<()> {
+            let total_staked = self.total_reward_weight();
+            let amount = added
+                .checked_add(*self.undistributed_rewards)
+                .ok_or(Error::Overflow)?;
+            if total_staked == 0 {
+                *self.undistributed_rewards = amount;
+                self.escrow_in(PoolId::StakingRewards, added);
+                return Ok(());
+            }
+            let increase = amount
+                .checked_mul(REWARD_PRECISION)
+                .ok_or(Error::Overflow)?
+                / total_staked;
+            *self.reward_per_token = self
+                .reward_per_token
+                .checked_add(increase)
+                .ok_or(Error::Overflow)?;
+            *self.undistributed_rewards = 0;
+            self.escrow_in(PoolId::StakingRewards, added);
+            Ok(())
+        }
+
+        fn earned(&self, account: AccountId) -> Balance {
+            let paid = self
+                .reward_per_token_paid
+                .get(&account)
+                .copied()
+                .unwrap_or_default();
+            let settled = self
+                .staking_rewards
+                .get(&account)
+                .copied()
+                .unwrap_or_default();
+            let accrued = self
+                .reward_weight(account)
+                .saturating_mul(*self.reward_per_token - paid)
+                / REWARD_PRECISION;
+            settled.saturating_add(accrued)
+        }
+
+        /// 创世 NFT 持有人的质押按两倍计算奖励
+        fn reward_weight(&self, account: AccountId) -> Balance {
+            let staked = self.stake_of(account);
+            if self.is_genesis_nft_holder(account) {
+                staked.saturating_mul(2)
+            } else {
+                staked
+            }
+        }
+
+        fn total_reward_weight(&self) -> Balance {
+            self.total_staked
+                .saturating_add(self.stake_of(*self.genesis_nft_holder))
+        }
+
+        // 质押量变化前先把之前累计的奖励结算下来, 开启自动复投时直接计入质押
+        fn settle_rewards(&mut self, account: AccountId) {
+            let earned = self.earned(account);
+            self.reward_per_token_paid
+                .insert(account, *self.reward_per_token);
+            if earned == 0 || !self.auto_compound_enabled(account) {
+                self.staking_rewards.insert(account, earned);
+                return;
+            }
+            // 奖励本来就在合约账户里, 只需要记账
+            self.staking_rewards.take(&account);
+            let staked = self.stake_of(account);
+            self.stakes.insert(account, staked.saturating_add(earned));
+            *self.total_staked = self.total_staked.saturating_add(earned);
+            self.escrow_out(PoolId::StakingRewards, earned);
+            self.escrow_in(PoolId::Staking, earned);
+            self.env().emit_event(AutoCompoundExecuted {
+                account,
+                rewards_restaked: earned,
+            });
+        }
+    }
+
+    // 回购: owner 把从市场回购的代币交给合约, 按比例销毁并分给质押者
+    impl Erc20 {
+        #[ink(message)]
+        pub fn buyback_ratios(&self) -> (u16, u16) {
+            (*self.buyback_burn_ratio, *self.buyback_stake_ratio)
+        }
+
+        #[ink(message)]
+        pub fn set_buyback_ratios(&mut self, burn: u16, stake: u16) -> Result<()> {
+            self.ensure_owner()?;
+            if u32::from(burn) + u32::from(stake) != 10_000 {
+                return Err(Error::InvalidRatios);
+            }
+            *self.buyback_burn_ratio = burn;
+            *self.buyback_stake_ratio = stake;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn execute_buyback(&mut self, amount: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            let owner = self.env().caller();
+            if self.stored_balance(owner) < amount {
+                return Err(Error::InsufficientBalance);
+            }
+            let staking_rewards = Self::bps_of(amount, *self.buyback_stake_ratio);
+            let burned = amount - staking_rewards;
+            if staking_rewards > 0 {
+                self.ensure_feature(FEATURE_STAKING)?;
+            }
+
+            self.inner_burn(owner, burned)?;
+            if staking_rewards > 0 {
+                let contract = self.env().account_id();
+                self.inner_transfer(owner, contract, staking_rewards)?;
+                self.add_rewards(staking_rewards)?;
+            }
+            self.env().emit_event(BuybackDistributed {
+                burned,
+                staking_rewards,
+            });
+            Ok(())
+        }
+    }
+    // 授权扩展: 增减授权, 签名授权 permit, 以及每个 owner 的授权总额
+    impl Erc20 {
+        /// owner 授出的全部授权之和, 含无限授权时为 Balance::MAX. 私有余额模式下无权查看时为 0
+        #[ink(message)]
+        pub fn total_outstanding_allowance(&self, owner: AccountId) -> Balance {
+            if !self.may_view(owner) {
+                return 0;
+            }
+            match self.total_approved.get(&owner) {
+                Some((_, carry)) if *carry > 0 => Balance::MAX,
+                Some((low, _)) => *low,
+                None => 0,
+            }
+        }
+
+        #[ink(message)]
+        pub fn approved_spenders(&self, owner: AccountId) -> Vec<AccountId> {
+            self.approved_spenders
+                .get(&owner)
+                .cloned()
+                .unwrap_or_default()
+        }
+
+        /// owner 所有非零授权 (spender, 额度), 只查 spender 列表, 不遍历整个授权表
+        #[ink(message)]
+        pub fn non_zero_allowances_of(&self, owner: AccountId) -> Vec<(AccountId, Balance)> {
+            self.approved_spenders(owner)
+                .into_iter()
+                .map(|spender| (spender, self.allowance(owner, spender)))
+                .filter(|(_, value)| *value > 0)
+                .collect()
+        }
+
+        /// 把调用者的授权清零, 每个 spender 发一条 Approval 事件;
+        /// 单次最多撤销 MAX_ALLOWANCES_REVOKED 个, 剩下的再调用一次
+        #[ink(message)]
+        pub fn revoke_all_allowances(&mut self) -> Result<()> {
+            let owner = self.env().caller();
+            let spenders = self.approved_spenders(owner);
+            for spender in spenders.into_iter().take(MAX_ALLOWANCES_REVOKED) {
+                self.inner_approve(owner, spender, 0, ApprovalOrigin::Approve)?;
+            }
+            Ok(())
+        }
+
+        /// 无限授权保持不变
+        #[ink(message)]
+        pub fn increase_allowance(&mut self, spender: AccountId, delta: Balance) -> Result<()> {
+            let owner = self.env().caller();
+            let value = self.stored_allowance(owner, spender).saturating_add(delta);
+            self.inner_approve(owner, spender, value, ApprovalOrigin::Increase)
+        }
+
+        #[ink(message)]
+        pub fn decrease_allowance(&mut self, spender: AccountId, delta: Balance) -> Result<()> {
+            let owner = self.env().caller();
+            let allowance = self.stored_allowance(owner, spender);
+            if allowance < delta {
+                return Err(Error::InsufficientAllowance);
+            }
+            self.inner_approve(owner, spender, allowance - delta, ApprovalOrigin::Decrease)
+        }
+
+        #[ink(message)]
+        pub fn permit_nonce(&self, owner: AccountId) -> u64 {
+            self.permit_nonces.get(&owner).copied().unwrap_or_default()
+        }
+
+        /// owner 需要签名的 permit 哈希, 使用 owner 当前的 nonce
+        #[ink(message)]
+        pub fn permit_hash(
+            &self,
+            owner: AccountId,
+            spender: AccountId,
+            value: Balance,
+            deadline: u32,
+        ) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                PERMIT_TYPE_TAG,
+                self.env().account_id(),
+                owner,
+                spender,
+                value,
+                self.permit_nonce(owner),
+                deadline,
+            )))
+        }
+
+        /// permit_single_use 需要签名的哈希, 与 permit 共用 nonce
+        #[ink(message)]
+        pub fn single_use_permit_hash(
+            &self,
+            owner: AccountId,
+            spender: AccountId,
+            max_value: Balance,
+            deadline: u32,
+        ) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                SINGLE_USE_PERMIT_TYPE_TAG,
+                self.env().account_id(),
+                owner,
+                spender,
+                max_value,
+                self.permit_nonce(owner),
+                deadline,
+            )))
+        }
+
+        /// approve_single_use 的签名版本
+        #[ink(message)]
+        pub fn permit_single_use(
+            &mut self,
+            owner: AccountId,
+            spender: AccountId,
+            max_value: Balance,
+            deadline: u32,
+            signature: [u8; 65],
+        ) -> Result<()> {
+            if self.env().block_number() > deadline {
+                return Err(Error::PermitExpired);
+            }
+            let hash = self.single_use_permit_hash(owner, spender, max_value, deadline);
+            let mut message_hash = [0u8; 32];
+            message_hash.copy_from_slice(hash.as_ref());
+            if self.recover_signer(&message_hash, &signature) != Some(owner) {
+                return Err(Error::InvalidPermitSignature);
+            }
+            let nonce = self.permit_nonce(owner);
+            self.permit_nonces.insert(owner, nonce + 1);
+            self.inner_approve(owner, spender, max_value, ApprovalOrigin::PermitSingleUse)
+        }
+
+        /// 任何人都可以提交 owner 签名的授权, deadline 为最后有效的区块
+        #[ink(message)]
+        pub fn permit(
+            &mut self,
+            owner: AccountId,
+            spender: AccountId,
+            value: Balance,
+            deadline: u32,
+            signature: [u8; 65],
+        ) -> Result<()> {
+            if self.env().block_number() > deadline {
+                return Err(Error::PermitExpired);
+            }
+            let hash = self.permit_hash(owner, spender, value, deadline);
+            let mut message_hash = [0u8; 32];
+            message_hash.copy_from_slice(hash.as_ref());
+            if self.recover_signer(&message_hash, &signature) != Some(owner) {
+                return Err(Error::InvalidPermitSignature);
+            }
+            let nonce = self.permit_nonce(owner);
+            self.permit_nonces.insert(owner, nonce + 1);
+            self.inner_approve(owner, spender, value, ApprovalOrigin::Permit)
+        }
+
+        /// owner 最近的授权变化, 旧的在前
+        #[ink(message)]
+        pub fn approval_history(&self, owner: AccountId) -> Vec<ApprovalChange> {
+            self.approval_history
+                .get(&owner)
+                .cloned()
+                .unwrap_or_default()
+        }
+
+        // 消耗授权时额度不变 (无限授权, 或花费 0) 不算一次变化
+        fn note_approval_change(
+            &mut self,
+            owner: AccountId,
+            spender: AccountId,
+            old_value: Balance,
+            new_value: Balance,
+            origin: ApprovalOrigin,
+        ) {
+            if !self.is_feature_enabled(FEATURE_APPROVAL_HISTORY)
+                || (origin == ApprovalOrigin::TransferFromConsumption && old_value == new_value)
+            {
+                return;
+            }
+            let mut history = self.approval_history(owner);
+            if history.len() >= APPROVAL_HISTORY_LEN {
+                history.remove(0);
+            }
+            history.push(ApprovalChange {
+                spender,
+                old_value,
+                new_value,
+                block: self.env().block_number(),
+                origin,
+            });
+            self.approval_history.insert(owner, history);
+        }
+
+        // 所有授权额度的写入都经过这里, 同步维护授权总额和 spender 列表, 并记录授权历史
+        fn set_allowance(
+            &mut self,
+            owner: AccountId,
+            spender: AccountId,
+            value: Balance,
+            origin: ApprovalOrigin,
+        ) {
+            let old = self.stored_allowance(owner, spender);
+            let kind = match origin {
+                ApprovalOrigin::Approve | ApprovalOrigin::Permit => AllowanceKind::Standard,
+                ApprovalOrigin::ApproveSingleUse | ApprovalOrigin::PermitSingleUse => {
+                    AllowanceKind::SingleUse
+                }
+                _ => self.allowance_kind(owner, spender),
+            };
+            self.note_approval_change(owner, spender, old, value, origin);
+            let (mut low, mut carry) = self.total_approved.get(&owner).copied().unwrap_or_default();
+            let (sub, borrow) = low.overflowing_sub(old);
+            low = sub;
+            carry -= borrow as u32;
+            let (add, overflow) = low.overflowing_add(value);
+            low = add;
+            carry += overflow as u32;
+            if low == 0 && carry == 0 {
+                self.total_approved.take(&owner);
+            } else {
+                self.total_approved.insert(owner, (low, carry));
+            }
+
+            if value == 0 {
+                self.allowances.take(&(owner, spender));
+            } else {
+                self.allowances.insert((owner, spender), (value, kind));
+            }
+            if old == 0 && value > 0 {
+                let mut spenders = self.approved_spenders(owner);
+                spenders.push(spender);
+                self.approved_spenders.insert(owner, spenders);
+            } else if old > 0 && value == 0 {
+                let mut spenders = self.approved_spenders(owner);
+                spenders.retain(|account| *account != spender);
+                if spenders.is_empty() {
+                    self.approved_spenders.take(&owner);
+                } else {
+                    self.approved_spenders.insert(owner, spenders);
+                }
+            }
+            self.notify_allowance_listener(owner, spender, old, value);
+        }
+    }
+    // 质押奖励线性释放
+    impl Erc20 {
+        #[ink(message)]
+        pub fn reward_vest_duration(&self) -> u32 {
+            *self.reward_vest_duration
+        }
+
+        #[ink(message)]
+        pub fn set_reward_vest_duration(&mut self, blocks: u32) -> Result<()> {
+            self.ensure_owner()?;
+            *self.reward_vest_duration = blocks;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn reward_vest(&self, account: AccountId) -> Option<(Balance, u32, u32)> {
+            self.pending_reward_vests.get(&account).copied()
+        }
+
+        /// 当前可以释放的奖励
+        #[ink(message)]
+        pub fn vested_rewards(&self, account: AccountId) -> Balance {
+            match self.reward_vest(account) {
+                Some(vest) => Self::vested_amount(vest, self.env().block_number()),
+                None => 0,
+            }
+        }
+
+        #[ink(message)]
+        pub fn release_vested_rewards(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if self.release_reward_vest(caller)? == 0 {
+                return Err(Error::NothingToWithdraw);
+            }
+            Ok(())
+        }
+
+        // 新领取的奖励并入已有的释放计划: 先释放已到期部分, 剩余部分与新奖励一起重新开始释放
+        fn start_reward_vest(&mut self, account: AccountId, amount: Balance) -> Result<()> {
+            self.release_reward_vest(account)?;
+            let remaining = self
+                .reward_vest(account)
+                .map(|(remaining, _, _)| remaining)
+                .unwrap_or_default();
+            let total = remaining.checked_add(amount).ok_or(Error::Overflow)?;
+            let duration = *self.reward_vest_duration;
+            let now = self.env().block_number();
+            self.pending_reward_vests
+                .insert(account, (total, now, duration));
+            self.escrow_in(PoolId::RewardVesting, amount);
+            self.env().emit_event(RewardVestStarted {
+                account,
+                amount: total,
+                duration,
+            });
+            Ok(())
+        }
+
+        // 释放到期部分, 剩余部分从当前区块起按剩余区块数继续线性释放
+        fn release_reward_vest(&mut self, account: AccountId) -> Result<Balance> {
+            let (amount, start, duration) = match self.reward_vest(account) {
+                Some(vest) => vest,
+                None => return Ok(0),
+            };
+            let now = self.env().block_number();
+            let vested = Self::vested_amount((amount, start, duration), now);
+            if vested == 0 {
+                return Ok(0);
+            }
+            // 释放的部分同样受即时提取额度限制, 超出时转入提取队列
+            let queued = !self.instant_withdrawal_allowed(vested);
+            if queued {
+                self.ensure_withdrawal_capacity()?;
+                self.escrow_in(PoolId::WithdrawalQueue, vested);
+            } else {
+                let contract = self.env().account_id();
+                self.inner_refund(contract, account, vested)?;
+                self.note_instant_withdrawal(vested);
+            }
+            self.escrow_out(PoolId::RewardVesting, vested);
+
+            if vested == amount {
+                self.pending_reward_vests.take(&account);
+            } else {
+                let elapsed = now.saturating_sub(start).min(duration);
+                self.pending_reward_vests
+                    .insert(account, (amount - vested, now, duration - elapsed));
+            }
+            self.env().emit_event(RewardVestReleased {
+                account,
+                amount: vested,
+            });
+            if queued {
+                self.enqueue_withdrawal(account, vested);
+            }
+            Ok(vested)
+        }
+
+        fn vested_amount((amount, start, duration): (Balance, u32, u32), now: u32) -> Balance {
+            let elapsed = now.saturating_sub(start);
+            if elapsed >= duration {
+                return amount;
+            }
+            let (elapsed, duration) = (u128::from(elapsed), u128::from(duration));
+            amount / duration * elapsed + amount % duration * elapsed / duration
+        }
+    }
+    // 定时转账: 到期后由任意 keeper 执行并领取赏金
+    impl Erc20 {
+        #[ink(message)]
+        pub fn scheduled_transfer(&self, id: u64) -> Option<ScheduledTransfer> {
+            self.scheduled_transfers.get(&id).cloned()
+        }
+
+        /// 托管 value 并返回任务 id
+        #[ink(message)]
+        pub fn schedule_transfer(
+            &mut self,
+            to: AccountId,
+            value: Balance,
+            execute_at: u32,
+        ) -> Result<u64> {
+            self.ensure_feature(FEATURE_SCHEDULED_TRANSFERS)?;
+            if execute_at <= self.env().block_number() {
+                return Err(Error::InvalidSchedule);
+            }
+            let creator = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(creator, contract, value)?;
+            self.escrow_in(PoolId::ScheduledTransfers, value);
+
+            let id = *self.next_scheduled_id;
+            *self.next_scheduled_id += 1;
+            self.scheduled_transfers.insert(
+                id,
+                ScheduledTransfer {
+                    creator,
+                    to,
+                    value,
+                    execute_at,
+                },
+            );
+            self.env().emit_event(TransferScheduled {
+                id,
+                creator,
+                to,
+                value,
+                execute_at,
+            });
+            Ok(id)
+        }
+
+        #[ink(message)]
+        pub fn execute_scheduled(&mut self, id: u64) -> Result<()> {
+            let job = self.scheduled_transfer(id).ok_or(Error::ScheduleNotFound)?;
+            let now = self.env().block_number();
+            if now < job.execute_at {
+                return Err(Error::ScheduleNotReady);
+            }
+            if now >= job.execute_at.saturating_add(SCHEDULE_GRACE_BLOCKS) {
+                return Err(Error::ScheduleExpired);
+            }
+
+            let keeper = self.env().caller();
+            let (bounty, deducted) =
+                self.pay_bounty(TaskKind::ScheduledTransfer, keeper, job.value)?;
+            let contract = self.env().account_id();
+            self.inner_transfer(contract, job.to, job.value - deducted)?;
+            self.escrow_out(PoolId::ScheduledTransfers, job.value);
+            self.scheduled_transfers.take(&id);
+            self.env()
+                .emit_event(ScheduledTransferExecuted { id, keeper, bounty });
+            Ok(())
+        }
+
+        /// 创建者在执行前取消, 托管金额原路退回
+        #[ink(message)]
+        pub fn cancel_scheduled(&mut self, id: u64) -> Result<()> {
+            let job = self.scheduled_transfer(id).ok_or(Error::ScheduleNotFound)?;
+            if self.env().caller() != job.creator {
+                return Err(Error::NotScheduleCreator);
+            }
+            self.refund_scheduled(id, job)?;
+            self.env().emit_event(ScheduledTransferCancelled { id });
+            Ok(())
+        }
+
+        /// 超过宽限期未执行的任务, 任何人都可以把托管金额退给创建者
+        #[ink(message)]
+        pub fn expire_scheduled(&mut self, id: u64) -> Result<()> {
+            let job = self.scheduled_transfer(id).ok_or(Error::ScheduleNotFound)?;
+            let now = self.env().block_number();
+            if now < job.execute_at.saturating_add(SCHEDULE_GRACE_BLOCKS) {
+                return Err(Error::ScheduleNotExpired);
+            }
+            self.refund_scheduled(id, job)?;
+            self.env().emit_event(ScheduledTransferExpired { id });
+            Ok(())
+        }
+
+        fn refund_scheduled(&mut self, id: u64, job: ScheduledTransfer) -> Result<()> {
+            let contract = self.env().account_id();
+            self.inner_refund(contract, job.creator, job.value)?;
+            self.escrow_out(PoolId::ScheduledTransfers, job.value);
+            self.scheduled_transfers.take(&id);
+            Ok(())
+        }
+    }
+    // 质押奖励自动复投
+    impl Erc20 {
+        #[ink(message)]
+        pub fn auto_compound_enabled(&self, account: AccountId) -> bool {
+            self.auto_compound_enabled
+                .get(&account)
+                .copied()
+                .unwrap_or(false)
+        }
+
+        #[ink(message)]
+        pub fn enable_auto_compound(&mut self) -> Result<()> {
+            self.ensure_feature(FEATURE_STAKING)?;
+            let caller = self.env().caller();
+            self.settle_rewards(caller);
+            self.auto_compound_enabled.insert(caller, true);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn disable_auto_compound(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            self.settle_rewards(caller);
+            self.auto_compound_enabled.take(&caller);
+            Ok(())
+        }
+
+        /// 把 account 已累计的奖励复投, 任何人(例如 keeper)都可以触发
+        #[ink(message)]
+        pub fn compound(&mut self, account: AccountId) -> Result<()> {
+            if !self.auto_compound_enabled(account) {
+                return Err(Error::AutoCompoundDisabled);
+            }
+            self.settle_rewards(account);
+            Ok(())
+        }
+    }
+    // 供应调节白名单
+    impl Erc20 {
+        #[ink(message)]
+        pub fn is_authorized_rebaser(&self, account: AccountId) -> bool {
+            self.authorized_rebasers
+                .get(&account)
+                .copied()
+                .unwrap_or(false)
+        }
+
+        #[ink(message)]
+        pub fn add_rebaser(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.authorized_rebasers.insert(account, true);
+            self.env().emit_event(RebaserAdded { account });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_rebaser(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.authorized_rebasers.take(&account);
+            self.env().emit_event(RebaserRemoved { account });
+            Ok(())
+        }
+
+        fn ensure_rebaser(&self) -> Result<()> {
+            if !self.is_authorized_rebaser(self.env().caller()) {
+                return Err(Error::NotAuthorizedRebaser);
+            }
+            Ok(())
+        }
+    }
+    // 监控指标: 只读取已经维护好的计数器, 不做遍历
+    impl Erc20 {
+        #[ink(message)]
+        pub fn metrics(&self) -> Metrics {
+            Metrics {
+                transfer_count: *self.transfer_count,
+                total_volume: *self.total_volume,
+                holder_count: *self.holder_count,
+                total_supply: self.total_supply(),
+                paused: self.paused(),
+                fees_collected: self.fees_collected(),
+                last_activity_block: *self.last_activity_block,
+            }
+        }
+
+        #[ink(message)]
+        pub fn holder_count(&self) -> u64 {
+            *self.holder_count
+        }
+
+        /// 持有者索引中第 index 个账户, index 小于 holder_count. 账户清零时由最后一个账户填补它的位置.
+        /// 私有余额模式下无权查看该账户时返回 None
+        #[ink(message)]
+        pub fn holder_at(&self, index: u64) -> Option<AccountId> {
+            self.holder_index
+                .get(&index)
+                .copied()
+                .filter(|account| self.may_view(*account))
+        }
+
+        // 余额在 0 和非 0 之间变化时同步持有者数量和索引
+        /// had_entry 为变化前账户是否已有余额记录
+        fn note_holder_change(
+            &mut self,
+            account: AccountId,
+            had_entry: bool,
+            old_balance: Balance,
+            new_balance: Balance,
+        ) {
+            match HolderTransition::of(old_balance, new_balance) {
+                HolderTransition::Joined => {
+                    if !had_entry {
+                        self.note_first_balance(account);
+                    }
+                    let position = *self.holder_count;
+                    crate::metering::note_aux_write();
+                    self.holder_index.insert(position, account);
+                    crate::metering::note_aux_write();
+                    self.holder_positions.insert(account, position);
+                    crate::metering::note_aux_write();
+                    *self.holder_count += 1;
+                }
+                HolderTransition::Left => {
+                    crate::metering::note_aux_write();
+                    *self.holder_count -= 1;
+                    let last = *self.holder_count;
+                    crate::metering::note_aux_write();
+                    if let Some(position) = self.holder_positions.take(&account) {
+                        crate::metering::note_aux_write();
+                        let moved = self.holder_index.take(&last);
+                        if let (Some(moved), true) = (moved, position != last) {
+                            crate::metering::note_aux_write();
+                            self.holder_index.insert(position, moved);
+                            crate::metering::note_aux_write();
+                            self.holder_positions.insert(moved, position);
+                        }
+                    }
+                }
+                HolderTransition::Unchanged => {}
+            }
+        }
+
+        fn note_activity(&mut self) {
+            crate::metering::note_aux_write();
+            *self.last_activity_block = self.env().block_number();
+        }
+    }
+    // 持有者等级: 按等级限制单个账户的持仓
+    impl Erc20 {
+        #[ink(message)]
+        pub fn holder_tier(&self, account: AccountId) -> HolderTier {
+            self.holder_tiers
+                .get(&account)
+                .copied()
+                .unwrap_or(HolderTier::Retail)
+        }
+
+        /// 等级的持仓上限, 未配置时为 Balance::MAX
+        #[ink(message)]
+        pub fn tier_cap(&self, tier: HolderTier) -> Balance {
+            self.policy.tier_cap(tier).unwrap_or(Balance::MAX)
+        }
+
+        #[ink(message)]
+        pub fn assign_tier(&mut self, account: AccountId, tier: HolderTier) -> Result<()> {
+            self.ensure_owner()?;
+            if tier == HolderTier::Retail {
+                self.holder_tiers.take(&account);
+            } else {
+                self.holder_tiers.insert(account, tier);
+            }
+            self.env().emit_event(TierAssigned { account, tier });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_tier_cap(&mut self, tier: HolderTier, cap: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            self.policy.set_tier_cap(tier, cap);
+            Ok(())
+        }
+    }
+    // 确认亏损: 价格跌破成本价时销毁代币, 并在证明合约中铸造亏损证明
+    impl Erc20 {
+        #[ink(message)]
+        pub fn basis_cost(&self, account: AccountId) -> Balance {
+            self.token_basis_cost
+                .get(&account)
+                .copied()
+                .unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn harvested_loss(&self, account: AccountId) -> Balance {
+            self.harvested_losses
+                .get(&account)
+                .copied()
+                .unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn set_tax_loss_certificate(&mut self, contract: Option<AccountId>) -> Result<()> {
+            self.ensure_owner()?;
+            *self.tax_loss_certificate = contract;
+            Ok(())
+        }
+
+        /// 只有预言机合约可以写入成本价
+        #[ink(message)]
+        pub fn set_basis_cost(&mut self, account: AccountId, cost: Balance) -> Result<()> {
+            let oracle = (*self.price_oracle).ok_or(Error::OracleNotSet)?;
+            if self.env().caller() != oracle {
+                return Err(Error::NotOracle);
+            }
+            self.token_basis_cost.insert(account, cost);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn harvest_tax_loss(&mut self, amount: Balance) -> Result<()> {
+            self.ensure_feature(FEATURE_TAX_LOSS)?;
+            let caller = self.env().caller();
+            let price = self.oracle_price()?;
+            let basis = self.basis_cost(caller);
+            if price >= basis {
+                return Err(Error::NoPriceDrop);
+            }
+            let loss_value = Self::native_value(amount, basis - price)?;
+            let total_loss = self
+                .harvested_loss(caller)
+                .checked_add(loss_value)
+                .ok_or(Error::Overflow)?;
+
+            self.inner_burn(caller, amount)?;
+            self.harvested_losses.insert(caller, total_loss);
+            self.env().emit_event(TaxLossHarvested {
+                account: caller,
+                amount_burned: amount,
+                loss_value,
+            });
+
+            if let Some(certificate) = *self.tax_loss_certificate {
+                let input = scale::Encode::encode(&(caller, loss_value));
+                self.do_call(
+                    certificate,
+                    MINT_CERTIFICATE_SELECTOR,
+                    &input,
+                    ORACLE_GAS_LIMIT,
+                )?;
+            }
+            Ok(())
+        }
+    }
+    // 接口版本, 供集成方确认对接的合约版本
+    impl Erc20 {
+        #[ink(message)]
+        pub fn abi_version(&self) -> (u16, u16, u16) {
+            crate::ABI_VERSION
+        }
+
+        #[ink(message)]
+        pub fn abi_hash(&self) -> Hash {
+            Hash::from(crate::ABI_HASH)
+        }
+    }
+    // 定点精度
+    impl Erc20 {
+        #[ink(message)]
+        pub fn precision_mode(&self) -> PrecisionMode {
+            *self.precision_mode
+        }
+
+        #[ink(message)]
+        pub fn set_precision_mode(&mut self, mode: PrecisionMode) -> Result<()> {
+            self.ensure_owner()?;
+            *self.precision_mode = mode;
+            Ok(())
+        }
+
+        /// 按精度模式拆分以当前面额计的余额, 返回 (整数部分, 小数部分)
+        #[ink(message)]
+        pub fn balance_of_display(&self, account: AccountId) -> (u128, u128) {
+            let unit = self.precision_mode().unit();
+            let balance = self.denominated_balance_of(account);
+            (balance / unit, balance % unit)
+        }
+
+        /// value 为原始数值, decimals 必须与 decimals() 一致, 防止按错误的小数位数转账
+        #[ink(message)]
+        pub fn transfer_fixed(&mut self, to: AccountId, value: u128, decimals: u8) -> Result<()> {
+            if decimals != self.decimals() {
+                return Err(Error::PrecisionMismatch);
+            }
+            let from = self.env().caller();
+            self.transfer_with_fee(from, to, value)
+        }
+    }
+    // 收款开关: 账户可以拒收别人转来的代币, 但托管资金退回不受影响
+    impl Erc20 {
+        #[ink(message)]
+        pub fn is_receiving(&self, who: AccountId) -> bool {
+            !self.receiving_disabled.get(&who).copied().unwrap_or(false)
+        }
+
+        #[ink(message)]
+        pub fn set_receiving(&mut self, enabled: bool) -> Result<()> {
+            let caller = self.env().caller();
+            if enabled {
+                self.receiving_disabled.take(&caller);
+            } else {
+                self.receiving_disabled.insert(caller, true);
+            }
+            self.env().emit_event(ReceivingChanged {
+                account: caller,
+                enabled,
+            });
+            Ok(())
+        }
+    }
+    // 点对点借贷
+    impl Erc20 {
+        #[ink(message)]
+        pub fn loan(&self, loan_id: u64) -> Option<Loan> {
+            self.loans.get(&loan_id).cloned()
+        }
+
+        /// 到期需要归还的本息
+        #[ink(message)]
+        pub fn loan_repayment(&self, loan_id: u64) -> Result<Balance> {
+            let loan = self.loan(loan_id).ok_or(Error::LoanNotFound)?;
+            loan.principal
+                .checked_add(Self::bps_of(loan.principal, loan.interest_rate))
+                .ok_or(Error::Overflow)
+        }
+
+        /// 出借 amount, 代币先托管在合约账户
+        #[ink(message)]
+        pub fn create_loan_offer(
+            &mut self,
+            amount: Balance,
+            interest_rate: u16,
+            collateral_required: Balance,
+            duration_blocks: u32,
+        ) -> Result<u64> {
+            self.ensure_feature(FEATURE_LENDING)?;
+            if interest_rate > 10_000 {
+                return Err(Error::InvalidBps);
+            }
+            let lender = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(lender, contract, amount)?;
+            self.escrow_in(PoolId::Loans, amount);
+
+            let loan_id = *self.next_loan_id;
+            *self.next_loan_id += 1;
+            self.loans.insert(
+                loan_id,
+                Loan {
+                    id: loan_id,
+                    lender,
+                    borrower: None,
+                    principal: amount,
+                    collateral: collateral_required,
+                    interest_rate,
+                    duration_blocks,
+                    due_block: 0,
+                    repaid: false,
+                    defaulted: false,
+                },
+            );
+            self.env().emit_event(LoanCreated {
+                loan_id,
+                lender,
+                principal: amount,
+                interest_rate,
+                collateral: collateral_required,
+            });
+            Ok(loan_id)
+        }
+
+        /// 出借人撤回还没被接受的借款
+        #[ink(message)]
+        pub fn cancel_loan_offer(&mut self, loan_id: u64) -> Result<()> {
+            let loan = self.loan(loan_id).ok_or(Error::LoanNotFound)?;
+            if self.env().caller() != loan.lender {
+                return Err(Error::NotLender);
+            }
+            if loan.borrower.is_some() {
+                return Err(Error::LoanAlreadyAccepted);
+            }
+            let contract = self.env().account_id();
+            self.inner_refund(contract, loan.lender, loan.principal)?;
+            self.escrow_out(PoolId::Loans, loan.principal);
+            self.loans.take(&loan_id);
+            Ok(())
+        }
+
+        /// 附带恰好 collateral 数量的原生币接受借款
+        #[ink(message, payable)]
+        pub fn accept_loan(&mut self, loan_id: u64) -> Result<()> {
+            let mut loan = self.loan(loan_id).ok_or(Error::LoanNotFound)?;
+            if loan.borrower.is_some() {
+                return Err(Error::LoanAlreadyAccepted);
+            }
+            let borrower = self.env().caller();
+            if borrower == loan.lender {
+                return Err(Error::SelfLoan);
+            }
+            if self.env().transferred_balance() != loan.collateral {
+                return Err(Error::IncorrectPayment);
+            }
+            let contract = self.env().account_id();
+            self.inner_transfer(contract, borrower, loan.principal)?;
+            self.escrow_out(PoolId::Loans, loan.principal);
+
+            let due_block = self
+                .env()
+                .block_number()
+                .saturating_add(loan.duration_blocks);
+            loan.borrower = Some(borrower);
+            loan.due_block = due_block;
+            self.loans.insert(loan_id, loan);
+            self.env().emit_event(LoanAccepted {
+                loan_id,
+                borrower,
+                due_block,
+            });
+            Ok(())
+        }
+
+        /// 借款人归还本息给出借人, 取回抵押. 本息先转入合约账户再按退款付给出借人,
+        /// 出借人关闭收款或超过等级上限都不能让还款失败, 从而拖到到期后清算
+        #[ink(message)]
+        pub fn repay_loan(&mut self, loan_id: u64) -> Result<()> {
+            let mut loan = self.active_loan(loan_id)?;
+            let borrower = self.env().caller();
+            if loan.borrower != Some(borrower) {
+                return Err(Error::NotBorrower);
+            }
+            let repayment = self.loan_repayment(loan_id)?;
+            let contract = self.env().account_id();
+            self.inner_transfer(borrower, contract, repayment)?;
+            self.inner_refund(contract, loan.lender, repayment)?;
+            self.env()
+                .transfer(borrower, loan.collateral)
+                .map_err(|_| Error::NativeTransferFailed)?;
+
+            // 借出时间太短或本金太小的借款不加分, 避免和自己控制的出借人反复借还刷分
+            let accepted_block = loan.due_block.saturating_sub(loan.duration_blocks);
+            let outstanding = self.env().block_number().saturating_sub(accepted_block);
+            let earns_reputation = outstanding >= MIN_REPUTATION_LOAN_BLOCKS
+                && loan.principal >= Self::bps_of(self.total_supply(), MIN_REPUTATION_LOAN_BPS);
+            loan.repaid = true;
+            self.loans.insert(loan_id, loan);
+            self.env().emit_event(LoanRepaid { loan_id, repayment });
+            if earns_reputation {
+                self.update_reputation(borrower, ReputationAction::CompletedLoan)?;
+            }
+            Ok(())
+        }
+
+        /// 到期未还时出借人拿走抵押
+        #[ink(message)]
+        pub fn liquidate_loan(&mut self, loan_id: u64) -> Result<()> {
+            let mut loan = self.active_loan(loan_id)?;
+            let lender = self.env().caller();
+            if lender != loan.lender {
+                return Err(Error::NotLender);
+            }
+            if self.env().block_number() <= loan.due_block {
+                return Err(Error::LoanNotDue);
+            }
+            self.env()
+                .transfer(lender, loan.collateral)
+                .map_err(|_| Error::NativeTransferFailed)?;
+
+            loan.defaulted = true;
+            let collateral = loan.collateral;
+            let borrower = loan.borrower;
+            self.loans.insert(loan_id, loan);
+            self.env().emit_event(LoanDefaulted {
+                loan_id,
+                collateral,
+            });
+            match borrower {
+                Some(borrower) => self.update_reputation(borrower, ReputationAction::Defaulted),
+                None => Ok(()),
+            }
+        }
+
+        fn active_loan(&self, loan_id: u64) -> Result<Loan> {
+            let loan = self.loan(loan_id).ok_or(Error::LoanNotFound)?;
+            if loan.borrower.is_none() || loan.repaid || loan.defaulted {
+                return Err(Error::LoanNotActive);
+            }
+            Ok(loan)
+        }
+    }
+    // 转账回调: 外部合约注册后在每笔账户间转账完成时收到通知
+    impl Erc20 {
+        #[ink(message)]
+        pub fn transfer_hooks(&self) -> Vec<AccountId> {
+            (*self.transfer_hooks).clone()
+        }
+
+        #[ink(message)]
+        pub fn hook_failure_mode(&self) -> HookFailureMode {
+            *self.hook_failure_mode
+        }
+
+        #[ink(message)]
+        pub fn register_transfer_hook(&mut self, hook: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_TRANSFER_HOOKS)?;
+            if self.transfer_hooks.contains(&hook) {
+                return Err(Error::HookAlreadyRegistered);
+            }
+            if self.transfer_hooks.len() >= MAX_TRANSFER_HOOKS {
+                return Err(Error::TooManyHooks);
+            }
+            self.transfer_hooks.push(hook);
+            self.env().emit_event(HookRegistered { hook });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_transfer_hook(&mut self, hook: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            let index = self
+                .transfer_hooks
+                .iter()
+                .position(|registered| *registered == hook)
+                .ok_or(Error::HookNotFound)?;
+            self.transfer_hooks.remove(index);
+            self.env().emit_event(HookRemoved { hook });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_hook_failure_mode(&mut self, mode: HookFailureMode) -> Result<()> {
+            self.ensure_owner()?;
+            *self.hook_failure_mode = mode;
+            Ok(())
+        }
+
+        fn notify_transfer_hooks(
+            &mut self,
+            transfers: &[(AccountId, AccountId, Balance)],
+        ) -> Result<()> {
+            if transfers.is_empty() || !self.is_feature_enabled(FEATURE_TRANSFER_HOOKS) {
+                return Ok(());
+            }
+            let hooks = (*self.transfer_hooks).clone();
+            for transfer in transfers {
+                let input = scale::Encode::encode(transfer);
+                for hook in hooks.iter().copied() {
+                    let result = self.do_call(
+                        hook,
+                        ON_TOKEN_TRANSFER_SELECTOR,
+                        &input,
+                        TRANSFER_HOOK_GAS_LIMIT,
+                    );
+                    match (result, *self.hook_failure_mode) {
+                        (Ok(_), _) => {}
+                        (Err(error), HookFailureMode::Strict) => return Err(error),
+                        (Err(error), HookFailureMode::Lenient) => {
+                            let reason = match error {
+                                Error::ExternalCall { code, .. } => code,
+                                _ => call::CALL_ERROR_UNKNOWN,
+                            };
+                            self.env().emit_event(HookCallFailed { hook, reason });
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+    // 投票委托: 账户可以把投票权按比例交给一个或多个账户, 被委托人的票数随委托人余额变化
+    impl Erc20 {
+        /// 账户的投票权当前由谁持有, 未委托时为自己; 拆分委托时为份额最大的被委托人
+        #[ink(message)]
+        pub fn delegates(&self, account: AccountId) -> AccountId {
+            self.split_delegations_of(account)
+                .iter()
+                .rev()
+                .max_by_key(|(_, bps)| *bps)
+                .map_or(account, |(delegatee, _)| *delegatee)
+        }
+
+        /// 账户的全部委托 (被委托人, 基点), 未委托时为空
+        #[ink(message)]
+        pub fn split_delegations_of(&self, account: AccountId) -> Vec<(AccountId, u16)> {
+            self.split_delegations
+                .get(&account)
+                .cloned()
+                .unwrap_or_default()
+        }
+
+        /// 委托给 delegatee, 委托给自己即取消委托
+        #[ink(message)]
+        pub fn delegate(&mut self, delegatee: AccountId) -> Result<()> {
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            let delegator = self.env().caller();
+            self.inner_delegate(delegator, delegatee);
+            Ok(())
+        }
+
+        /// 按基点把投票权拆分给多个账户, 基点之和必须为 10000, 被委托人不能重复.
+        /// 整体替换之前的委托
+        #[ink(message)]
+        pub fn delegate_split(&mut self, delegations: Vec<(AccountId, u16)>) -> Result<()> {
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            let bps_sum: u32 = delegations.iter().map(|(_, bps)| u32::from(*bps)).sum();
+            let duplicated = delegations
+                .iter()
+                .enumerate()
+                .any(|(index, (delegatee, _))| {
+                    delegations[..index]
+                        .iter()
+                        .any(|(earlier, _)| earlier == delegatee)
+                });
+            if delegations.is_empty()
+                || delegations.len() > MAX_SPLIT_DELEGATEES
+                || delegations.iter().any(|(_, bps)| *bps == 0)
+                || bps_sum != 10_000
+                || duplicated
+            {
+                return Err(Error::InvalidDelegationSplit);
+            }
+            let delegator = self.env().caller();
+            self.set_delegations(delegator, delegations.clone());
+            self.env().emit_event(SplitDelegationSet {
+                delegator,
+                delegations,
+            });
+            Ok(())
+        }
+
+        /// 取消调用者的全部委托, 投票权回到自己
+        #[ink(message)]
+        pub fn clear_delegations(&mut self) -> Result<()> {
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            let delegator = self.env().caller();
+            self.set_delegations(delegator, Vec::new());
+            self.env().emit_event(SplitDelegationSet {
+                delegator,
+                delegations: Vec::new(),
+            });
+            Ok(())
+        }
+
+        /// delegator 需要签名的委托哈希
+        #[ink(message)]
+        pub fn delegation_hash(
+            &self,
+            delegator: AccountId,
+            delegatee: AccountId,
+            nonce: u64,
+            expiry: u32,
+        ) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                DELEGATION_TYPE_TAG,
+                self.env().account_id(),
+                delegator,
+                delegatee,
+                nonce,
+                expiry,
+            )))
+        }
+
+        /// 任何人都可以提交 delegator 签名的委托, 消耗 delegator 的 permit nonce,
+        /// expiry 为最后有效的区块
+        #[ink(message)]
+        pub fn delegate_by_sig(
+            &mut self,
+            delegator: AccountId,
+            delegatee: AccountId,
+            nonce: u64,
+            expiry: u32,
+            signature: [u8; 65],
+        ) -> Result<()> {
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            if self.env().block_number() > expiry {
+                return Err(Error::PermitExpired);
+            }
+            if nonce != self.permit_nonce(delegator) {
+                return Err(Error::InvalidNonce);
+            }
+            let hash = self.delegation_hash(delegator, delegatee, nonce, expiry);
+            let mut message_hash = [0u8; 32];
+            message_hash.copy_from_slice(hash.as_ref());
+            if self.recover_signer(&message_hash, &signature) != Some(delegator) {
+                return Err(Error::InvalidPermitSignature);
+            }
+            self.permit_nonces.insert(delegator, nonce + 1);
+            self.inner_delegate(delegator, delegatee);
+            Ok(())
+        }
+
+        fn delegated_votes_of(&self, account: AccountId) -> Balance {
+            self.delegated_votes
+                .get(&account)
+                .copied()
+                .unwrap_or_default()
+        }
+
+        /// 自己持有的票 (拆分委托后为 0) 加上别人委托的票
+        fn base_votes(&self, account: AccountId) -> Balance {
+            let own_votes = if self.split_delegations.contains_key(&account) {
+                0
+            } else {
+                self.stored_balance(account)
+            };
+            own_votes.saturating_add(self.delegated_votes_of(account))
+        }
+
+        // 投票权变化后记录检查点, 同一区块内多次变化只保留最后的值
+        fn write_vote_checkpoint(&mut self, account: AccountId) {
+            let votes = self.base_votes(account);
+            let block = self.env().block_number();
+            let mut checkpoints = self
+                .vote_checkpoints
+                .get(&account)
+                .cloned()
+                .unwrap_or_default();
+            match checkpoints.last_mut() {
+                Some((at, last_votes)) if *at == block => *last_votes = votes,
+                Some((_, last_votes)) if *last_votes == votes => return,
+                None if votes == 0 => return,
+                _ => checkpoints.push((block, votes)),
+            }
+            crate::metering::note_aux_write();
+            self.vote_checkpoints.insert(account, checkpoints);
+        }
+
+        // 全部委托给一个账户, 等同于该账户占 10000 基点的拆分委托
+        fn inner_delegate(&mut self, delegator: AccountId, delegatee: AccountId) {
+            let current = self.split_delegations_of(delegator);
+            let delegations = if delegatee == delegator {
+                Vec::new()
+            } else {
+                vec![(delegatee, 10_000)]
+            };
+            if current == delegations {
+                return;
+            }
+            let from_delegate = self.delegates(delegator);
+            self.set_delegations(delegator, delegations);
+            self.env().emit_event(DelegateChanged {
+                delegator,
+                from_delegate,
+                to_delegate: delegatee,
+            });
+        }
+
+        // 先按旧的拆分收回票数, 再按新的拆分分配
+        fn set_delegations(&mut self, delegator: AccountId, delegations: Vec<(AccountId, u16)>) {
+            let balance = self.stored_balance(delegator);
+            let current = self.split_delegations_of(delegator);
+            for (delegatee, votes) in Self::split_votes(balance, &current) {
+                self.move_delegated_votes(delegatee, votes, 0);
+            }
+            for (delegatee, votes) in Self::split_votes(balance, &delegations) {
+                self.move_delegated_votes(delegatee, 0, votes);
+            }
+            if delegations.is_empty() {
+                self.split_delegations.take(&delegator);
+            } else {
+                self.split_delegations.insert(delegator, delegations);
+            }
+            self.write_vote_checkpoint(delegator);
+        }
+
+        /// 按基点拆分 balance, 舍入的余数归最后一个被委托人, 各份之和恰好等于 balance
+        fn split_votes(
+            balance: Balance,
+            delegations: &[(AccountId, u16)],
+        ) -> Vec<(AccountId, Balance)> {
+            let mut remaining = balance;
+            let mut shares = Vec::with_capacity(delegations.len());
+            for (index, (delegatee, bps)) in delegations.iter().enumerate() {
+                let share = if index + 1 == delegations.len() {
+                    remaining
+                } else {
+                    Self::bps_of(balance, *bps).min(remaining)
+                };
+                remaining -= share;
+                shares.push((*delegatee, share));
+            }
+            shares
+        }
+
+        // 委托人余额变化时同步各被委托人的票数
+        fn note_vote_change(
+            &mut self,
+            account: AccountId,
+            old_balance: Balance,
+            new_balance: Balance,
+        ) {
+            let delegations = self.split_delegations_of(account);
+            let old_shares = Self::split_votes(old_balance, &delegations);
+            let new_shares = Self::split_votes(new_balance, &delegations);
+            for ((delegatee, removed), (_, added)) in old_shares.into_iter().zip(new_shares) {
+                self.move_delegated_votes(delegatee, removed, added);
+            }
+            self.write_vote_checkpoint(account);
+        }
+
+        fn move_delegated_votes(&mut self, delegatee: AccountId, removed: Balance, added: Balance) {
+            let votes = self
+                .delegated_votes_of(delegatee)
+                .saturating_sub(removed)
+                .saturating_add(added);
+            crate::metering::note_aux_write();
+            if votes == 0 {
+                self.delegated_votes.take(&delegatee);
+            } else {
+                self.delegated_votes.insert(delegatee, votes);
+            }
+            self.write_vote_checkpoint(delegatee);
+        }
+    }
+    // 交易承诺: 第三方不依赖事件索引也能在链上验证一笔历史转账
+    impl Erc20 {
+        #[ink(message)]
+        pub fn get_transaction_commitments(&self, account: AccountId) -> Vec<Hash> {
+            self.transaction_commitments
+                .get(&account)
+                .cloned()
+                .unwrap_or_default()
+        }
+
+        /// balance_after 为转账后 from 的余额. 只能验证 from 最近的转账
+        #[ink(message)]
+        pub fn verify_transaction(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            block: u32,
+            balance_after: Balance,
+        ) -> bool {
+            let tx_hash = self.transaction_hash(from, to, value, block, balance_after);
+            self.transaction_commitments
+                .get(&from)
+                .map_or(false, |commitments| commitments.contains(&tx_hash))
+        }
+
+        fn transaction_hash(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            block: u32,
+            balance_after: Balance,
+        ) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                from,
+                to,
+                value,
+                block,
+                balance_after,
+            )))
+        }
+
+        fn commit_transaction(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            balance_after: Balance,
+        ) {
+            if !self.is_feature_enabled(FEATURE_TX_COMMITMENTS) {
+                return;
+            }
+            let block = self.env().block_number();
+            let tx_hash = self.transaction_hash(from, to, value, block, balance_after);
+            let mut commitments = self.get_transaction_commitments(from);
+            if commitments.len() >= MAX_TRANSACTION_COMMITMENTS {
+                commitments.remove(0);
+            }
+            commitments.push(tx_hash);
+            crate::metering::note_aux_write();
+            self.transaction_commitments.insert(from, commitments);
+        }
+    }
+    // 销毁凭证: 用户发起的销毁都会留下可查询的凭证, 过了保留期后任何人都可以清理
+    impl Erc20 {
+        /// 销毁调用者的代币并注明用途, 返回凭证 id
+        #[ink(message)]
+        pub fn burn_with_purpose(&mut self, value: Balance, purpose: [u8; 16]) -> Result<u64> {
+            let caller = self.env().caller();
+            self.burn_with_receipt(caller, value, purpose)
+        }
+
+        /// 消耗授权销毁 from 的代币
+        #[ink(message)]
+        pub fn burn_from(&mut self, from: AccountId, value: Balance) -> Result<()> {
+            let spender = self.env().caller();
+            self.ensure_allowance_active(from, spender)?;
+            let allowance = self.stored_allowance(from, spender);
+            if allowance < value {
+                return Err(Error::InsufficientAllowance);
+            }
+            self.burn_with_receipt(from, value, [0; 16])?;
+            self.spend_allowance(from, spender, allowance, value);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn burn_receipt(&self, receipt_id: u64) -> Option<BurnReceipt> {
+            self.burn_receipts.get(&receipt_id).copied()
+        }
+
+        /// burner 最近的凭证, 旧的在前, 从第 start 张起最多 limit 张
+        #[ink(message)]
+        pub fn receipts_of(
+            &self,
+            burner: AccountId,
+            start: u32,
+            limit: u32,
+        ) -> Vec<(u64, BurnReceipt)> {
+            self.burn_receipts_of
+                .get(&burner)
+                .map(|ids| {
+                    ids.iter()
+                        .skip(start as usize)
+                        .take((limit as usize).min(MAX_BATCH_LEN))
+                        .filter_map(|id| self.burn_receipt(*id).map(|receipt| (*id, receipt)))
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+
+        #[ink(message)]
+        pub fn burn_receipt_retention(&self) -> u32 {
+            *self.burn_receipt_retention
+        }
+
+        /// 清理赏金通过 set_task_bounty(TaskKind::ReceiptPruning, ..) 配置
+        #[ink(message)]
+        pub fn set_burn_receipt_retention(&mut self, blocks: u32) -> Result<()> {
+            self.ensure_owner()?;
+            *self.burn_receipt_retention = blocks;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn receipt_bounty_min_amount(&self) -> Balance {
+            *self.receipt_bounty_min_amount
+        }
+
+        /// 清理销毁数量低于 amount 的凭证不发赏金, 固定金额的赏金应不高于该值
+        #[ink(message)]
+        pub fn set_receipt_bounty_min_amount(&mut self, amount: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            *self.receipt_bounty_min_amount = amount;
+            Ok(())
+        }
+
+        /// 从最早的凭证开始清理超过保留期的凭证, 最多 limit 张, 返回清理的数量.
+        /// 只有销毁数量不低于 receipt_bounty_min_amount 的凭证发赏金
+        #[ink(message)]
+        pub fn prune_receipts(&mut self, limit: u32) -> Result<u32> {
+            let retention = *self.burn_receipt_retention;
+            if retention == 0 {
+                return Err(Error::ReceiptPruningDisabled);
+            }
+            let now = self.env().block_number();
+            let caller = self.env().caller();
+            let mut pruned = 0;
+            while pruned < limit && *self.oldest_burn_receipt_id < *self.next_burn_receipt_id {
+                let id = *self.oldest_burn_receipt_id;
+                if let Some(receipt) = self.burn_receipt(id) {
+                    // 凭证按区块顺序生成, 遇到还在保留期内的就可以停下
+                    if now.saturating_sub(receipt.block) <= retention {
+                        break;
+                    }
+                    self.burn_receipts.take(&id);
+                    let mut ids = self
+                        .burn_receipts_of
+                        .get(&receipt.burner)
+                        .cloned()
+                        .unwrap_or_default();
+                    ids.retain(|indexed| *indexed != id);
+                    if ids.is_empty() {
+                        self.burn_receipts_of.take(&receipt.burner);
+                    } else {
+                        self.burn_receipts_of.insert(receipt.burner, ids);
+                    }
+                    pruned += 1;
+                    if receipt.amount >= *self.receipt_bounty_min_amount {
+                        self.pay_bounty(TaskKind::ReceiptPruning, caller, 0)?;
+                    }
+                }
+                *self.oldest_burn_receipt_id += 1;
+            }
+            Ok(pruned)
+        }
+
+        fn burn_with_receipt(
+            &mut self,
+            burner: AccountId,
+            amount: Balance,
+            purpose: [u8; 16],
+        ) -> Result<u64> {
+            // 0 销毁不留凭证, 否则可以免费生成凭证刷清理赏金
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            self.inner_burn(burner, amount)?;
+            let receipt_id = *self.next_burn_receipt_id;
+            *self.next_burn_receipt_id += 1;
+            self.burn_receipts.insert(
+                receipt_id,
+                BurnReceipt {
+                    burner,
+                    amount,
+                    block: self.env().block_number(),
+                    purpose,
+                },
+            );
+            let mut ids = self
+                .burn_receipts_of
+                .get(&burner)
+                .cloned()
+                .unwrap_or_default();
+            if ids.len() >= MAX_BURN_RECEIPTS_PER_ACCOUNT {
+                ids.remove(0);
+            }
+            ids.push(receipt_id);
+            self.burn_receipts_of.insert(burner, ids);
+            self.env().emit_event(Burn {
+                receipt_id,
+                burner,
+                amount,
+                purpose,
+            });
+            Ok(receipt_id)
+        }
+    }
+    // 使用许可: 授权人允许被授权人使用, 每次行使时收取版税
+    impl Erc20 {
+        #[ink(message)]
+        pub fn license(&self, license_id: u64) -> Option<License> {
+            self.licenses.get(&license_id).copied()
+        }
+
+        /// 授予 licensee 许可, 有效期为 duration 个区块
+        #[ink(message)]
+        pub fn grant_license(
+            &mut self,
+            licensee: AccountId,
+            rights: u8,
+            royalty_rate: u16,
+            duration: u32,
+        ) -> Result<u64> {
+            if royalty_rate > 10_000 {
+                return Err(Error::InvalidBps);
+            }
+            let grantor = self.env().caller();
+            let id = *self.next_license_id;
+            *self.next_license_id += 1;
+            let expiry_block = self.env().block_number().saturating_add(duration);
+            self.licenses.insert(
+                id,
+                License {
+                    id,
+                    grantor,
+                    licensee,
+                    usage_rights: rights,
+                    royalty_rate,
+                    expiry_block,
+                },
+            );
+            self.env().emit_event(LicenseGranted {
+                id,
+                grantor,
+                licensee,
+                usage_rights: rights,
+                royalty_rate,
+                expiry_block,
+            });
+            Ok(id)
+        }
+
+        /// 被授权人按 usage_value * royalty_rate / 10000 向授权人支付版税
+        #[ink(message)]
+        pub fn exercise_license(&mut self, license_id: u64, usage_value: Balance) -> Result<()> {
+            let license = self.license(license_id).ok_or(Error::LicenseNotFound)?;
+            let licensee = self.env().caller();
+            if licensee != license.licensee {
+                return Err(Error::UnauthorizedLicenseExercise);
+            }
+            if self.env().block_number() > license.expiry_block {
+                return Err(Error::LicenseExpired);
+            }
+            let royalty = Self::bps_of(usage_value, license.royalty_rate);
+            self.inner_transfer(licensee, license.grantor, royalty)?;
+            self.env().emit_event(LicenseExercised {
+                id: license_id,
+                royalty_paid: royalty,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn revoke_license(&mut self, license_id: u64) -> Result<()> {
+            let license = self.license(license_id).ok_or(Error::LicenseNotFound)?;
+            if self.env().caller() != license.grantor {
+                return Err(Error::NotLicenseGrantor);
+            }
+            self.licenses.take(&license_id);
+            self.env().emit_event(LicenseRevoked { id: license_id });
+            Ok(())
+        }
+    }
+    // 荷兰式拍卖: owner 出售一批代币, 价格随时间下降, 先到先得
+    impl Erc20 {
+        #[ink(message)]
+        pub fn auction(&self) -> Option<DutchAuction> {
+            *self.auction
+        }
+
+        /// 当前区块的拍卖价格, 没有拍卖时为 None
+        #[ink(message)]
+        pub fn current_auction_price(&self) -> Option<Balance> {
+            self.auction
+                .map(|auction| auction.price_at(self.env().block_number()))
+        }
+
+        /// 从 owner 余额中拿出 tokens_for_sale 开始拍卖
+        #[ink(message)]
+        pub fn start_auction(
+            &mut self,
+            tokens_for_sale: Balance,
+            start_price: Balance,
+            end_price: Balance,
+            duration_blocks: u32,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_DUTCH_AUCTION)?;
+            if self.auction.is_some() {
+                return Err(Error::AuctionActive);
+            }
+            if end_price == 0 || start_price < end_price || duration_blocks == 0 {
+                return Err(Error::InvalidAuction);
+            }
+            let owner = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(owner, contract, tokens_for_sale)?;
+            self.escrow_in(PoolId::Auction, tokens_for_sale);
+
+            let auction = DutchAuction {
+                tokens_for_sale,
+                remaining: tokens_for_sale,
+                start_price,
+                end_price,
+                start_block: self.env().block_number(),
+                duration_blocks,
+                proceeds: 0,
+            };
+            *self.auction = Some(auction);
+            self.env().emit_event(AuctionStarted {
+                tokens_for_sale,
+                start_price,
+                end_price,
+                end_block: auction.end_block(),
+            });
+            Ok(())
+        }
+
+        /// 按当前价格买入, 超出剩余数量或不足 1 个代币的部分退回
+        #[ink(message, payable)]
+        pub fn bid(&mut self) -> Result<()> {
+            let mut auction = self.auction.ok_or(Error::NoAuction)?;
+            let block = self.env().block_number();
+            if auction.is_over(block) {
+                return Err(Error::AuctionEnded);
+            }
+            let price = auction.price_at(block);
+            let paid = self.env().transferred_balance();
+            let tokens = paid.checked_mul(PRICE_SCALE).ok_or(Error::Overflow)? / price;
+            let tokens = tokens.min(auction.remaining);
+            if tokens == 0 {
+                return Err(Error::IncorrectPayment);
+            }
+            // 成本向上取整, 零头归卖方
+            let cost = tokens
+                .checked_mul(price)
+                .ok_or(Error::Overflow)?
+                .saturating_add(PRICE_SCALE - 1)
+                / PRICE_SCALE;
+
+            let buyer = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(contract, buyer, tokens)?;
+            self.escrow_out(PoolId::Auction, tokens);
+            if paid > cost {
+                self.env()
+                    .transfer(buyer, paid - cost)
+                    .map_err(|_| Error::NativeTransferFailed)?;
+            }
+            auction.remaining -= tokens;
+            auction.proceeds += cost;
+            *self.auction = Some(auction);
+            self.env().emit_event(AuctionPurchase {
+                buyer,
+                tokens,
+                price,
+                cost,
+            });
+            Ok(())
+        }
+
+        /// 拍卖结束后任何人都可以结算: 收入转给 owner, 没卖出的代币退回 owner
+        #[ink(message)]
+        pub fn finalize_auction(&mut self) -> Result<()> {
+            let auction = self.auction.ok_or(Error::NoAuction)?;
+            if !auction.is_over(self.env().block_number()) {
+                return Err(Error::AuctionNotEnded);
+            }
+            let owner = *self.owner;
+            if auction.proceeds > 0 {
+                self.env()
+                    .transfer(owner, auction.proceeds)
+                    .map_err(|_| Error::NativeTransferFailed)?;
+            }
+            if auction.remaining > 0 {
+                let contract = self.env().account_id();
+                self.inner_refund(contract, owner, auction.remaining)?;
+                self.escrow_out(PoolId::Auction, auction.remaining);
+            }
+            *self.auction = None;
+            self.env().emit_event(AuctionFinalized {
+                proceeds: auction.proceeds,
+                unsold: auction.remaining,
+            });
+            Ok(())
+        }
+    }
+    // 转账奖池: 每笔转账按比例注入奖池, 并有一定概率让转出方赢走整个奖池.
+    // 随机数来自区块号和转账参数的哈希, 出块者和转账人都可以预先算出结果并挑选参数,
+    // 只适合奖池金额不值得操纵的场景
+    impl Erc20 {
+        #[ink(message)]
+        pub fn jackpot_params(&self) -> (Balance, u16, u16) {
+            (
+                *self.jackpot_pool,
+                *self.jackpot_probability_bps,
+                *self.jackpot_fee_rate,
+            )
+        }
+
+        #[ink(message)]
+        pub fn set_jackpot_params(&mut self, probability: u16, fee_rate: u16) -> Result<()> {
+            self.ensure_owner()?;
+            if probability > 10_000 || fee_rate > 10_000 {
+                return Err(Error::InvalidBps);
+            }
+            *self.jackpot_probability_bps = probability;
+            *self.jackpot_fee_rate = fee_rate;
+            Ok(())
+        }
+
+        fn contribute_to_jackpot(
+            &mut self,
+            from: AccountId,
+            amount: Balance,
+            changes: &mut TokenChanges,
+        ) -> Result<()> {
+            let contract = self.env().account_id();
+            self.write_transfer(
+                from,
+                contract,
+                amount,
+                TransferKind::Fee,
+                TransferOrigin::Fee,
+                changes,
+            )?;
+            *self.jackpot_pool = self.jackpot_pool.saturating_add(amount);
+            self.escrow_in(PoolId::Jackpot, amount);
+            *self.jackpot_contributions_total =
+                self.jackpot_contributions_total.saturating_add(amount);
+            self.env().emit_event(JackpotContribution { from, amount });
+            Ok(())
+        }
+
+        /// hash(block_number, from, to, value) 对 10000 取模, 小于中奖概率即中奖
+        fn jackpot_roll(&self, from: AccountId, to: AccountId, value: Balance) -> u16 {
+            let hash = self.env().hash_encoded::<Blake2x256, _>(&(
+                self.env().block_number(),
+                from,
+                to,
+                value,
+            ));
+            let mut head = [0u8; 16];
+            head.copy_from_slice(&hash[..16]);
+            (u128::from_le_bytes(head) % 10_000) as u16
+        }
+
+        fn draw_jackpot(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            changes: &mut TokenChanges,
+        ) -> Result<()> {
+            let pool = *self.jackpot_pool;
+            if pool == 0 || self.jackpot_roll(from, to, value) >= *self.jackpot_probability_bps {
+                return Ok(());
+            }
+            let contract = self.env().account_id();
+            self.write_transfer(
+                contract,
+                from,
+                pool,
+                TransferKind::Normal,
+                TransferOrigin::Internal,
+                changes,
+            )?;
+            *self.jackpot_pool = 0;
+            self.escrow_out(PoolId::Jackpot, pool);
+            self.env().emit_event(JackpotWon {
+                winner: from,
+                amount: pool,
+            });
+            Ok(())
+        }
+    }
+    // 场外成交: 双方线下谈好价格, 链上一手交钱一手交货
+    impl Erc20 {
+        #[ink(message)]
+        pub fn deal(&self, id: u64) -> Option<Deal> {
+            self.deals.get(&id).copied()
+        }
+
+        /// 托管 token_amount 代币, 等待 counterparty 支付 native_price
+        #[ink(message)]
+        pub fn create_deal(
+            &mut self,
+            counterparty: AccountId,
+            token_amount: Balance,
+            native_price: Balance,
+            expiry: u32,
+        ) -> Result<u64> {
+            if self.env().block_number() > expiry {
+                return Err(Error::DealExpired);
+            }
+            let creator = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(creator, contract, token_amount)?;
+            self.escrow_in(PoolId::Deals, token_amount);
+
+            let id = *self.next_deal_id;
+            *self.next_deal_id += 1;
+            self.deals.insert(
+                id,
+                Deal {
+                    creator,
+                    counterparty,
+                    token_amount,
+                    native_price,
+                    expiry,
+                    status: DealStatus::Open,
+                },
+            );
+            self.env().emit_event(DealCreated {
+                id,
+                creator,
+                counterparty,
+                token_amount,
+                native_price,
+                expiry,
+            });
+            Ok(id)
+        }
+
+        /// 对手方附带恰好 native_price 的原生币成交, 代币给买方, 原生币给卖方
+        #[ink(message, payable)]
+        pub fn accept_deal(&mut self, id: u64) -> Result<()> {
+            let mut deal = self.open_deal(id)?;
+            let buyer = self.env().caller();
+            if buyer != deal.counterparty {
+                return Err(Error::NotDealCounterparty);
+            }
+            if self.env().block_number() > deal.expiry {
+                return Err(Error::DealExpired);
+            }
+            if self.env().transferred_balance() != deal.native_price {
+                return Err(Error::IncorrectPayment);
+            }
+            let contract = self.env().account_id();
+            self.inner_transfer(contract, buyer, deal.token_amount)?;
+            self.escrow_out(PoolId::Deals, deal.token_amount);
+            self.env()
+                .transfer(deal.creator, deal.native_price)
+                .map_err(|_| Error::NativeTransferFailed)?;
+
+            deal.status = DealStatus::Settled;
+            self.deals.insert(id, deal);
+            self.env().emit_event(DealAccepted { id });
+            Ok(())
+        }
+
+        /// 创建者随时可以取消, 过期后任何人都可以取消, 代币退回创建者
+        #[ink(message)]
+        pub fn cancel_deal(&mut self, id: u64) -> Result<()> {
+            let mut deal = self.open_deal(id)?;
+            let caller = self.env().caller();
+            if caller != deal.creator && self.env().block_number() <= deal.expiry {
+                return Err(Error::NotDealCreator);
+            }
+            let contract = self.env().account_id();
+            self.inner_refund(contract, deal.creator, deal.token_amount)?;
+            self.escrow_out(PoolId::Deals, deal.token_amount);
+
+            deal.status = DealStatus::Cancelled;
+            self.deals.insert(id, deal);
+            self.env().emit_event(DealCancelled {
+                id,
+                cancelled_by: caller,
+            });
+            Ok(())
+        }
+
+        fn open_deal(&self, id: u64) -> Result<Deal> {
+            let deal = self.deal(id).ok_or(Error::DealNotFound)?;
+            if deal.status != DealStatus::Open {
+                return Err(Error::DealAlreadySettled);
+            }
+            Ok(deal)
+        }
+    }
+    // 账户活跃度: 合规需要找出长期不活跃的账户, 部署时可以关闭
+    impl Erc20 {
+        /// 账户最近一次活动的 (区块, 时间戳), 从未活动或未开启记录时为 None
+        #[ink(message)]
+        pub fn last_activity_of(&self, who: AccountId) -> Option<(u32, Timestamp)> {
+            self.account_activity.get(&who).copied()
+        }
+
+        /// accounts 中最近 older_than_blocks 个区块内没有活动的账户, 保持传入顺序
+        #[ink(message)]
+        pub fn inactive_among(
+            &self,
+            accounts: Vec<AccountId>,
+            older_than_blocks: u32,
+        ) -> Result<Vec<AccountId>> {
+            self.ensure_feature(FEATURE_ACTIVITY_TRACKING)?;
+            Self::ensure_batch_len(accounts.len())?;
+            let now = self.env().block_number();
+            Ok(accounts
+                .into_iter()
+                .filter(|account| match self.last_activity_of(*account) {
+                    Some((block, _)) => now.saturating_sub(block) > older_than_blocks,
+                    None => true,
+                })
+                .collect())
+        }
+
+        fn note_account_activity(&mut self, account: AccountId) {
+            if !self.is_feature_enabled(FEATURE_ACTIVITY_TRACKING) {
+                return;
+            }
+            let now = (self.env().block_number(), self.env().block_timestamp());
+            crate::metering::note_aux_write();
+            self.account_activity.insert(account, now);
+        }
+    }
+    // 创世 NFT: 代表部署者的创始仓位, 持有人免转账手续费, 质押奖励翻倍, 并可紧急取回质押
+    impl Erc20 {
+        #[ink(message)]
+        pub fn genesis_nft_holder(&self) -> AccountId {
+            *self.genesis_nft_holder
+        }
+
+        #[ink(message)]
+        pub fn is_genesis_nft_holder(&self, account: AccountId) -> bool {
+            account == *self.genesis_nft_holder
+        }
+
+        /// 转让前后两个账户的奖励先按原倍数结算
+        #[ink(message)]
+        pub fn transfer_genesis_nft(&mut self, new_holder: AccountId) -> Result<()> {
+            let holder = self.env().caller();
+            if !self.is_genesis_nft_holder(holder) {
+                return Err(Error::NotGenesisHolder);
+            }
+            self.settle_rewards(holder);
+            self.settle_rewards(new_holder);
+            *self.genesis_nft_holder = new_holder;
+            self.env().emit_event(GenesisNFTTransferred {
+                from: holder,
+                to: new_holder,
+            });
+            Ok(())
+        }
+
+        /// 持有人取回 amount 质押, 暂停, 增发锁定等转账限制都不生效.
+        /// 只能动用自己的质押, 合约账户中其他人的托管资金不受影响
+        #[ink(message)]
+        pub fn emergency_withdraw(&mut self, amount: Balance) -> Result<()> {
+            let holder = self.env().caller();
+            if !self.is_genesis_nft_holder(holder) {
+                return Err(Error::NotGenesisHolder);
+            }
+            if self.stake_of(holder) < amount {
+                return Err(Error::InsufficientStake);
+            }
+            let contract = self.env().account_id();
+            let mut changes = TokenChanges::default();
+            self.write_transfer(
+                contract,
+                holder,
+                amount,
+                TransferKind::Forced,
+                TransferOrigin::Emergency,
+                &mut changes,
+            )?;
+            self.escrow_out(PoolId::Staking, amount);
+
+            self.settle_rewards(holder);
+            let staked = self.stake_of(holder);
+            self.stakes.insert(holder, staked - amount);
+            *self.total_staked -= amount;
+            self.env().emit_event(Unstaked {
+                account: holder,
+                amount,
+            });
+            self.after_token_transfer(&changes)
+        }
+    }
+    // 合作质押池: 小额持有人凑在一起达到人数门槛后享受全额收益, 收益由合约增发
+    impl Erc20 {
+        #[ink(message)]
+        pub fn stake_pool(&self, pool_id: u64) -> Option<StakePool> {
+            self.stake_pools.get(&pool_id).cloned()
+        }
+
+        /// member 在池中尚未领取的收益, 包含上次计息后新产生的部分
+        #[ink(message)]
+        pub fn pool_rewards_of(&self, pool_id: u64, member: AccountId) -> Balance {
+            match self.stake_pool(pool_id) {
+                Some(mut pool) => {
+                    pool.accrue(self.env().block_number());
+                    self.pool_earned(&pool, member)
+                }
+                None => 0,
+            }
+        }
+
+        /// 收益由增发支付, 只有 owner 可以创建
+        #[ink(message)]
+        pub fn create_stake_pool(
+            &mut self,
+            min_members: u8,
+            max_members: u8,
+            yield_rate: u16,
+        ) -> Result<u64> {
+            self.ensure_feature(FEATURE_STAKING)?;
+            self.ensure_owner()?;
+            if min_members == 0 || min_members > max_members {
+                return Err(Error::InvalidPool);
+            }
+            if yield_rate > 10_000 {
+                return Err(Error::InvalidBps);
+            }
+            let pool_id = *self.next_stake_pool_id;
+            *self.next_stake_pool_id += 1;
+            self.stake_pools.insert(
+                pool_id,
+                StakePool {
+                    id: pool_id,
+                    members: Vec::new(),
+                    total_staked: 0,
+                    shares: BTreeMap::new(),
+                    yield_rate_bps: yield_rate,
+                    min_members,
+                    max_members,
+                    min_contribution: 0,
+                    reward_per_share: 0,
+                    last_accrual_block: self.env().block_number(),
+                },
+            );
+            self.env().emit_event(PoolCreated {
+                pool_id,
+                min_members,
+                max_members,
+                yield_rate_bps: yield_rate,
+            });
+            Ok(pool_id)
+        }
+
+        /// 新成员第一次加入时生效, 防止用空账户凑人数拿到全额收益率或占满名额
+        #[ink(message)]
+        pub fn set_pool_min_contribution(&mut self, pool_id: u64, amount: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            let mut pool = self.stake_pool(pool_id).ok_or(Error::PoolNotFound)?;
+            pool.min_contribution = amount;
+            self.stake_pools.insert(pool_id, pool);
+            Ok(())
+        }
+
+        /// 托管 amount 代币加入池, 已是成员时追加份额. amount 不能为 0,
+        /// 新成员第一次加入不能少于池的 min_contribution
+        #[ink(message)]
+        pub fn join_pool(&mut self, pool_id: u64, amount: Balance) -> Result<()> {
+            self.ensure_feature(FEATURE_STAKING)?;
+            let mut pool = self.stake_pool(pool_id).ok_or(Error::PoolNotFound)?;
+            let member = self.env().caller();
+            if amount == 0 {
+                return Err(Error::ZeroAmount);
+            }
+            let is_member = pool.shares.contains_key(&member);
+            if !is_member && amount < pool.min_contribution {
+                return Err(Error::BelowMinContribution);
+            }
+            if !is_member && pool.members.len() >= usize::from(pool.max_members) {
+                return Err(Error::PoolFull);
+            }
+            let shares = pool
+                .shares_of(&member)
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+            let total_staked = pool
+                .total_staked
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+            let contract = self.env().account_id();
+            self.inner_transfer(member, contract, amount)?;
+            self.escrow_in(PoolId::CooperativePools, amount);
+
+            // 按加入前的人数计息, 之后再改份额
+            pool.accrue(self.env().block_number());
+            self.settle_pool_rewards(&pool, member);
+            if !is_member {
+                pool.members.push(member);
+            }
+            pool.shares.insert(member, shares);
+            pool.total_staked = total_staked;
+            self.stake_pools.insert(pool_id, pool);
+            self.env().emit_event(PoolJoined {
+                pool_id,
+                member,
+                amount,
+            });
+            Ok(())
+        }
+
+        /// 取回全部份额并领取未领的收益
+        #[ink(message)]
+        pub fn leave_pool(&mut self, pool_id: u64) -> Result<()> {
+            let mut pool = self.stake_pool(pool_id).ok_or(Error::PoolNotFound)?;
+            let member = self.env().caller();
+            if !pool.shares.contains_key(&member) {
+                return Err(Error::NotPoolMember);
+            }
+            pool.accrue(self.env().block_number());
+            let rewards = self.pool_earned(&pool, member);
+            let amount = pool.shares_of(&member);
+            let contract = self.env().account_id();
+            self.inner_refund(contract, member, amount)?;
+            self.escrow_out(PoolId::CooperativePools, amount);
+            if rewards > 0 {
+                self.inner_mint(member, rewards)?;
+            }
+
+            pool.members.retain(|m| *m != member);
+            pool.shares.remove(&member);
+            pool.total_staked -= amount;
+            self.stake_pools.insert(pool_id, pool);
+            self.pool_reward_per_share_paid.take(&(pool_id, member));
+            self.pool_rewards.take(&(pool_id, member));
+            self.env().emit_event(PoolLeft {
+                pool_id,
+                member,
+                amount,
+            });
+            if rewards > 0 {
+                self.env().emit_event(PoolRewardsClaimed {
+                    pool_id,
+                    member,
+                    amount: rewards,
+                });
+            }
+            Ok(())
+        }
+
+        /// 按份额比例领取自己在池中累计的收益
+        #[ink(message)]
+        pub fn claim_pool_rewards(&mut self, pool_id: u64) -> Result<()> {
+            let mut pool = self.stake_pool(pool_id).ok_or(Error::PoolNotFound)?;
+            let member = self.env().caller();
+            if !pool.shares.contains_key(&member) {
+                return Err(Error::NotPoolMember);
+            }
+            pool.accrue(self.env().block_number());
+            let amount = self.pool_earned(&pool, member);
+            if amount == 0 {
+                return Err(Error::NothingToWithdraw);
+            }
+            self.inner_mint(member, amount)?;
+
+            self.pool_reward_per_share_paid
+                .insert((pool_id, member), pool.reward_per_share);
+            self.pool_rewards.take(&(pool_id, member));
+            self.stake_pools.insert(pool_id, pool);
+            self.env().emit_event(PoolRewardsClaimed {
+                pool_id,
+                member,
+                amount,
+            });
+            Ok(())
+        }
+
+        fn pool_earned(&self, pool: &StakePool, member: AccountId) -> Balance {
+            let key = (pool.id, member);
+            let paid = self
+                .pool_reward_per_share_paid
+                .get(&key)
+                .copied()
+                .unwrap_or_default();
+            let settled = self.pool_rewards.get(&key).copied().unwrap_or_default();
+            let accrued = pool
+                .shares_of(&member)
+                .saturating_mul(pool.reward_per_share - paid)
+                / REWARD_PRECISION;
+            settled.saturating_add(accrued)
+        }
+
+        fn settle_pool_rewards(&mut self, pool: &StakePool, member: AccountId) {
+            let earned = self.pool_earned(pool, member);
+            self.pool_reward_per_share_paid
+                .insert((pool.id, member), pool.reward_per_share);
+            self.pool_rewards.insert((pool.id, member), earned);
+        }
+    }
+    // 开户代授权: 用户线下签名同意后, 运营方为其预先授权结算合约
+    impl Erc20 {
+        #[ink(message)]
+        pub fn is_onboarder(&self, account: AccountId) -> bool {
+            self.onboarders.get(&account).copied().unwrap_or(false)
+        }
+
+        #[ink(message)]
+        pub fn add_onboarder(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.onboarders.insert(account, true);
+            self.env().emit_event(OnboarderAdded { account });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_onboarder(&mut self, account: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.onboarders.take(&account);
+            self.env().emit_event(OnboarderRemoved { account });
+            Ok(())
+        }
+
+        /// owner 需要签名的同意哈希, 使用 owner 当前的 nonce. 与 permit 不同, 没有截止区块
+        #[ink(message)]
+        pub fn approval_on_behalf_hash(
+            &self,
+            owner: AccountId,
+            spender: AccountId,
+            value: Balance,
+        ) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                ONBOARDING_TYPE_TAG,
+                self.env().account_id(),
+                owner,
+                spender,
+                value,
+                self.permit_nonce(owner),
+            )))
+        }
+
+        /// 开户运营方提交 owner 的同意签名, 为 owner 写入授权
+        #[ink(message)]
+        pub fn approve_on_behalf(
+            &mut self,
+            owner: AccountId,
+            spender: AccountId,
+            value: Balance,
+            consent_sig: [u8; 65],
+        ) -> Result<()> {
+            let onboarder = self.env().caller();
+            if !self.is_onboarder(onboarder) {
+                return Err(Error::NotOnboarder);
+            }
+            let hash = self.approval_on_behalf_hash(owner, spender, value);
+            let mut message_hash = [0u8; 32];
+            message_hash.copy_from_slice(hash.as_ref());
+            if self.recover_signer(&message_hash, &consent_sig) != Some(owner) {
+                return Err(Error::InvalidPermitSignature);
+            }
+            let nonce = self.permit_nonce(owner);
+            self.permit_nonces.insert(owner, nonce + 1);
+            self.inner_approve(owner, spender, value, ApprovalOrigin::Permit)?;
+            self.env().emit_event(ApprovalOnBehalf {
+                owner,
+                spender,
+                value,
+                onboarder,
+            });
+            Ok(())
+        }
+    }
+    // 声誉: 借贷, 治理和质押中的表现累积成分数, 分数高的账户手续费更低, 投票权重更高
+    impl Erc20 {
+        #[ink(message)]
+        pub fn reputation_score_of(&self, account: AccountId) -> u32 {
+            self.reputation_score
+                .get(&account)
+                .copied()
+                .unwrap_or_default()
+        }
+
+        /// 最近的 limit 条声誉变动, 新的在前
+        #[ink(message)]
+        pub fn reputation_history(&self, account: AccountId, limit: u8) -> Vec<ReputationEvent> {
+            self.reputation_actions
+                .get(&account)
+                .map(|events| {
+                    events
+                        .iter()
+                        .rev()
+                        .take(usize::from(limit))
+                        .copied()
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+
+        /// 每 1000 分减免 1 个基点的手续费并增加 1 个基点的投票权重, 至多 MAX_REPUTATION_BONUS_BPS
+        fn reputation_bonus_bps(&self, account: AccountId) -> u16 {
+            let bonus = self.reputation_score_of(account) / 1_000;
+            bonus.min(u32::from(MAX_REPUTATION_BONUS_BPS)) as u16
+        }
+
+        /// 只由借贷, 治理和质押逻辑调用, 分数最低为 0
+        fn update_reputation(
+            &mut self,
+            account: AccountId,
+            action: ReputationAction,
+        ) -> Result<()> {
+            let delta = action.delta();
+            let score = self.reputation_score_of(account);
+            let score = if delta >= 0 {
+                score.checked_add(delta as u32).ok_or(Error::Overflow)?
+            } else {
+                score.saturating_sub(delta.unsigned_abs())
+            };
+            self.reputation_score.insert(account, score);
+
+            let mut events = self
+                .reputation_actions
+                .get(&account)
+                .cloned()
+                .unwrap_or_default();
+            if events.len() >= MAX_REPUTATION_EVENTS {
+                events.remove(0);
+            }
+            events.push(ReputationEvent {
+                action,
+                block: self.env().block_number(),
+                delta,
+            });
+            self.reputation_actions.insert(account, events);
+            self.env().emit_event(ReputationChanged {
+                account,
+                action,
+                delta,
+                score,
+            });
+            Ok(())
+        }
+    }
+    // 白皮书: 链上保存文档哈希, 更新需要经过等待期
+    impl Erc20 {
+        #[ink(message)]
+        pub fn whitepaper_hash(&self) -> Hash {
+            *self.whitepaper_hash
+        }
+
+        #[ink(message)]
+        pub fn whitepaper_uri(&self) -> String {
+            (*self.whitepaper_uri).clone()
+        }
+
+        #[ink(message)]
+        pub fn pending_whitepaper(&self) -> Option<(Hash, String, u32)> {
+            (*self.pending_whitepaper).clone()
+        }
+
+        /// document_bytes 的 Blake2x256 哈希是否与链上记录一致
+        #[ink(message)]
+        pub fn verify_whitepaper(&self, document_bytes: Vec<u8>) -> bool {
+            Hash::from(self.env().hash_bytes::<Blake2x256>(&document_bytes))
+                == *self.whitepaper_hash
+        }
+
+        /// 排队更新白皮书, WHITEPAPER_TIMELOCK_BLOCKS 个区块后才能生效. 再次调用会覆盖排队中的更新并重新计时
+        #[ink(message)]
+        pub fn update_whitepaper(&mut self, new_hash: Hash, new_uri: String) -> Result<()> {
+            self.ensure_owner()?;
+            let eta = self
+                .env()
+                .block_number()
+                .saturating_add(WHITEPAPER_TIMELOCK_BLOCKS);
+            *self.pending_whitepaper = Some((new_hash, new_uri.clone(), eta));
+            self.env().emit_event(WhitepaperUpdateQueued {
+                new_hash,
+                new_uri,
+                eta,
+            });
+            Ok(())
+        }
+
+        /// 等待期结束后任何人都可以让排队的更新生效
+        #[ink(message)]
+        pub fn execute_whitepaper_update(&mut self) -> Result<()> {
+            let (new_hash, new_uri, eta) = self
+                .pending_whitepaper()
+                .ok_or(Error::NoPendingWhitepaper)?;
+            if self.env().block_number() < eta {
+                return Err(Error::TimelockNotExpired);
+            }
+            let old_hash = *self.whitepaper_hash;
+            *self.whitepaper_hash = new_hash;
+            *self.whitepaper_uri = new_uri.clone();
+            *self.pending_whitepaper = None;
+            self.env().emit_event(WhitepaperUpdated {
+                old_hash,
+                new_hash,
+                new_uri,
+            });
+            Ok(())
+        }
+    }
+    // 账户迁移: 换密钥时把余额和授权一次搬到新账户, 旧账户在一段时间内继续转发收款
+    impl Erc20 {
+        /// 旧账户转发收款的 (新账户, 截止区块), 过期后仍保留记录
+        #[ink(message)]
+        pub fn forwarding_of(&self, account: AccountId) -> Option<(AccountId, u32)> {
+            self.account_forwarding.get(&account).copied()
+        }
+
+        /// old_account 需要签名的迁移哈希, 使用 old_account 当前的 nonce
+        #[ink(message)]
+        pub fn migration_hash(&self, old_account: AccountId, new_account: AccountId) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                MIGRATION_TYPE_TAG,
+                self.env().account_id(),
+                old_account,
+                new_account,
+                self.permit_nonce(old_account),
+            )))
+        }
+
+        /// 调用者把全部余额和发出的授权迁到 new_account, 并在
+        /// MIGRATION_FORWARDING_BLOCKS 个区块内把转入旧账户的代币转发给新账户.
+        /// 收到的授权无法按 spender 索引, 不会迁移
+        #[ink(message)]
+        pub fn migrate_account(
+            &mut self,
+            new_account: AccountId,
+            signature: [u8; 65],
+        ) -> Result<()> {
+            let old_account = self.env().caller();
+            if new_account == old_account {
+                return Err(Error::InvalidMigration);
+            }
+            let hash = self.migration_hash(old_account, new_account);
+            let mut message_hash = [0u8; 32];
+            message_hash.copy_from_slice(hash.as_ref());
+            if self.recover_signer(&message_hash, &signature) != Some(old_account) {
+                return Err(Error::InvalidPermitSignature);
+            }
+            let balance = self.stored_balance(old_account);
+            if balance > 0 {
+                self.inner_transfer(old_account, new_account, balance)?;
+            }
+            let nonce = self.permit_nonce(old_account);
+            self.permit_nonces.insert(old_account, nonce + 1);
+
+            // 旧授权清零 (发出 value 为 0 的 Approval), 额度累加到新账户名下并保留授权种类,
+            // 一次性授权迁移后仍然是一次性的
+            for spender in self.approved_spenders(old_account) {
+                let value = self.stored_allowance(old_account, spender);
+                let origin = match self.allowance_kind(old_account, spender) {
+                    AllowanceKind::Standard => ApprovalOrigin::Approve,
+                    AllowanceKind::SingleUse => ApprovalOrigin::ApproveSingleUse,
+                };
+                self.inner_approve(old_account, spender, 0, ApprovalOrigin::Approve)?;
+                let migrated = self
+                    .stored_allowance(new_account, spender)
+                    .saturating_add(value);
+                self.inner_approve(new_account, spender, migrated, origin)?;
+            }
+
+            let forward_until = self
+                .env()
+                .block_number()
+                .saturating_add(MIGRATION_FORWARDING_BLOCKS);
+            self.account_forwarding
+                .insert(old_account, (new_account, forward_until));
+            self.env().emit_event(AccountMigrated {
+                old_account,
+                new_account,
+                balance,
+                forward_until,
+            });
+            Ok(())
+        }
+
+        // 转发期内记到新账户名下, 只转发一层
+        fn forwarding_target(&self, account: AccountId) -> AccountId {
+            match self.account_forwarding.get(&account) {
+                Some(&(new_account, until)) if self.env().block_number() <= until => new_account,
+                _ => account,
+            }
+        }
+    }
+
+    // 提取队列: 大额解除质押排队, 由 keeper 按先进先出处理, 避免一次性掏空合约账户
+    impl Erc20 {
+        #[ink(message)]
+        pub fn instant_withdrawal_limit(&self) -> Option<Balance> {
+            *self.instant_withdrawal_limit
+        }
+
+        /// 每个 WITHDRAWAL_PERIOD_BLOCKS 周期内解除质押, 领取奖励和释放奖励合计即时到账的上限.
+        /// limit 为 None 时关闭排队, 所有提取立即到账
+        #[ink(message)]
+        pub fn set_instant_withdrawal_limit(&mut self, limit: Option<Balance>) -> Result<()> {
+            self.ensure_owner()?;
+            *self.instant_withdrawal_limit = limit;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn withdrawal_request(&self, id: u64) -> Option<WithdrawalRequest> {
+            self.withdrawal_requests.get(&id).copied()
+        }
+
+        /// 排队中的请求数量
+        #[ink(message)]
+        pub fn withdrawal_queue_len(&self) -> u32 {
+            (*self.withdrawal_head..*self.next_withdrawal_id)
+                .filter(|id| self.withdrawal_requests.contains_key(id))
+                .count() as u32
+        }
+
+        /// who 最靠前的请求前面还有几个请求, 没有排队时为 None
+        #[ink(message)]
+        pub fn withdrawal_position(&self, who: AccountId) -> Option<u32> {
+            (*self.withdrawal_head..*self.next_withdrawal_id)
+                .filter_map(|id| self.withdrawal_requests.get(&id))
+                .position(|request| request.who == who)
+                .map(|position| position as u32)
+        }
+
+        /// 任何人都可以按先进先出处理至多 limit 个请求, 每个请求按 TaskKind::WithdrawalQueue
+        /// 的配置给调用者赏金. 队列托管的余额不足以支付队首请求时停止, 不动用其他池的资金,
+        /// 返回处理的数量
+        #[ink(message)]
+        pub fn process_withdrawals(&mut self, limit: u32) -> Result<u32> {
+            let keeper = self.env().caller();
+            let contract = self.env().account_id();
+            let mut processed = 0;
+            while processed < limit && *self.withdrawal_head < *self.next_withdrawal_id {
+                let id = *self.withdrawal_head;
+                let request = match self.withdrawal_request(id) {
+                    Some(request) => request,
+                    None => {
+                        *self.withdrawal_head += 1;
+                        continue;
+                    }
+                };
+                let available = self
+                    .obligation(PoolId::WithdrawalQueue)
+                    .min(self.stored_balance(contract));
+                if available < request.amount {
+                    break;
+                }
+                let (bounty, deducted) =
+                    self.pay_bounty(TaskKind::WithdrawalQueue, keeper, request.amount)?;
+                self.inner_refund(contract, request.who, request.amount - deducted)?;
+                self.escrow_out(PoolId::WithdrawalQueue, request.amount);
+                self.withdrawal_requests.take(&id);
+                *self.withdrawal_head += 1;
+                processed += 1;
+                self.env().emit_event(WithdrawalProcessed {
+                    id,
+                    who: request.who,
+                    amount: request.amount,
+                    keeper,
+                    bounty,
+                });
+            }
+            Ok(processed)
+        }
+
+        /// 请求人取消排队中的请求, 金额恢复为质押
+        #[ink(message)]
+        pub fn cancel_withdrawal(&mut self, id: u64) -> Result<()> {
+            let request = self
+                .withdrawal_request(id)
+                .ok_or(Error::WithdrawalNotFound)?;
+            let caller = self.env().caller();
+            if caller != request.who {
+                return Err(Error::NotWithdrawalOwner);
+            }
+            self.settle_rewards(caller);
+            let staked = self.stake_of(caller);
+            if staked == 0 {
+                self.stake_started.insert(caller, self.env().block_number());
+            }
+            self.stakes.insert(caller, staked + request.amount);
+            *self.total_staked += request.amount;
+            self.escrow_out(PoolId::WithdrawalQueue, request.amount);
+            self.escrow_in(PoolId::Staking, request.amount);
+            self.withdrawal_requests.take(&id);
+            self.env().emit_event(WithdrawalCancelled { id });
+            Ok(())
+        }
+
+        /// 本周期剩余的即时提取额度, 没有上限时为 None
+        #[ink(message)]
+        pub fn instant_withdrawal_remaining(&self) -> Option<Balance> {
+            let limit = (*self.instant_withdrawal_limit)?;
+            Some(limit.saturating_sub(self.instant_withdrawn_this_period()))
+        }
+
+        fn instant_withdrawn_this_period(&self) -> Balance {
+            let period = self.env().block_number() / WITHDRAWAL_PERIOD_BLOCKS;
+            match *self.instant_withdrawal_window {
+                (last, withdrawn) if last == period => withdrawn,
+                _ => 0,
+            }
+        }
+
+        fn instant_withdrawal_allowed(&self, amount: Balance) -> bool {
+            match self.instant_withdrawal_remaining() {
+                Some(remaining) => amount <= remaining,
+                None => true,
+            }
+        }
+
+        // 即时提取成功后调用, 计入本周期额度
+        fn note_instant_withdrawal(&mut self, amount: Balance) {
+            let period = self.env().block_number() / WITHDRAWAL_PERIOD_BLOCKS;
+            let withdrawn = self.instant_withdrawn_this_period().saturating_add(amount);
+            *self.instant_withdrawal_window = (period, withdrawn);
+        }
+
+        fn ensure_withdrawal_capacity(&self) -> Result<()> {
+            if *self.next_withdrawal_id - *self.withdrawal_head >= MAX_QUEUED_WITHDRAWALS {
+                return Err(Error::WithdrawalQueueFull);
+            }
+            Ok(())
+        }
+
+        fn enqueue_withdrawal(&mut self, who: AccountId, amount: Balance) {
+            let id = *self.next_withdrawal_id;
+            *self.next_withdrawal_id += 1;
+            self.withdrawal_requests.insert(
+                id,
+                WithdrawalRequest {
+                    who,
+                    amount,
+                    requested_at: self.env().block_number(),
+                },
+            );
+            self.env().emit_event(WithdrawalQueued { id, who, amount });
+        }
+    }
+
+    // 税率时间表: 按区块区间临时调整手续费率, 例如上线初期的高税率, 之后回落到 fee_strategy
+    impl Erc20 {
+        #[ink(message)]
+        pub fn tax_schedule(&self) -> Vec<TaxScheduleEntry> {
+            (*self.tax_schedule).clone()
+        }
+
+        /// 当前区块的手续费率: 时间表中的费率, 否则为 fee_strategy 的比例费率.
+        /// fee_strategy 不是按比例收费时为 0
+        #[ink(message)]
+        pub fn current_effective_tax_rate(&self) -> u16 {
+            match self.active_tax_entry() {
+                Some(entry) => entry.fee_rate_bps,
+                None => self.fee_strategy.rate_bps(),
+            }
+        }
+
+        /// 整体替换时间表, 传入空列表即清空
+        #[ink(message)]
+        pub fn set_tax_schedule(&mut self, schedule: Vec<TaxScheduleEntry>) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_FEES)?;
+            if schedule.len() > MAX_TAX_SCHEDULE_ENTRIES
+                || schedule.iter().any(|e| e.start_block >= e.end_block)
+                || schedule
+                    .windows(2)
+                    .any(|w| w[0].end_block > w[1].start_block)
+            {
+                return Err(Error::InvalidTaxSchedule);
+            }
+            if schedule.iter().any(|e| e.fee_rate_bps > 10_000) {
+                return Err(Error::InvalidBps);
+            }
+            let entries_count = schedule.len() as u32;
+            *self.tax_schedule = schedule;
+            self.env().emit_event(TaxScheduleUpdated { entries_count });
+            self.note_tax_rate();
+            Ok(())
+        }
+
+        fn active_tax_entry(&self) -> Option<TaxScheduleEntry> {
+            let block = self.env().block_number();
+            self.tax_schedule.iter().find(|e| e.covers(block)).copied()
+        }
+
+        // 费率随区块变化, 没有交易触发, 在下一次转账时补发 TaxRateChanged
+        fn note_tax_rate(&mut self) {
+            let new_rate = self.current_effective_tax_rate();
+            let old_rate = *self.last_tax_rate;
+            if new_rate != old_rate {
+                *self.last_tax_rate = new_rate;
+                self.env().emit_event(TaxRateChanged {
+                    old_rate,
+                    new_rate,
+                    block: self.env().block_number(),
+                });
+            }
+        }
+    }
+
+    // 遗产: 账户长期没有主动操作时, 预先指定的受益人可以领取全部余额
+    impl Erc20 {
+        #[ink(message)]
+        pub fn beneficiary_of(&self, account: AccountId) -> Option<Beneficiary> {
+            self.beneficiaries.get(&account).copied()
+        }
+
+        /// 指定受益人, 从当前区块开始计时. 再次调用会覆盖之前的设置
+        #[ink(message)]
+        pub fn set_beneficiary(&mut self, who: AccountId, inactivity_blocks: u32) -> Result<()> {
+            let account = self.env().caller();
+            if who == account || inactivity_blocks == 0 {
+                return Err(Error::InvalidBeneficiary);
+            }
+            self.beneficiaries.insert(
+                account,
+                Beneficiary {
+                    who,
+                    inactivity_blocks,
+                    last_active_block: self.env().block_number(),
+                },
+            );
+            self.env().emit_event(BeneficiarySet {
+                account,
+                beneficiary: Some(who),
+                inactivity_blocks,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn clear_beneficiary(&mut self) -> Result<()> {
+            let account = self.env().caller();
+            if self.beneficiaries.take(&account).is_none() {
+                return Err(Error::NoBeneficiary);
+            }
+            self.env().emit_event(BeneficiarySet {
+                account,
+                beneficiary: None,
+                inactivity_blocks: 0,
+            });
+            Ok(())
+        }
+
+        /// 受益人在 claimable_at 及之后领取 from 的全部余额, 并清空 from 发出的授权
+        #[ink(message)]
+        pub fn claim_inheritance(&mut self, from: AccountId) -> Result<()> {
+            let beneficiary = self.env().caller();
+            let arrangement = match self.beneficiary_of(from) {
+                Some(arrangement) if arrangement.who == beneficiary => arrangement,
+                _ => return Err(Error::NotBeneficiary),
+            };
+            if self.env().block_number() < arrangement.claimable_at() {
+                return Err(Error::InheritanceNotClaimable);
+            }
+            let amount = self.stored_balance(from);
+            self.inner_transfer(from, beneficiary, amount)?;
+            for spender in self.approved_spenders(from) {
+                self.inner_approve(from, spender, 0, ApprovalOrigin::Approve)?;
+            }
+            self.beneficiaries.take(&from);
+            self.env().emit_event(InheritanceClaimed {
+                from,
+                beneficiary,
+                amount,
+            });
+            Ok(())
+        }
+
+        // 转出, 销毁和授权都说明账户仍在使用, 收款不算
+        fn reset_inheritance_clock(&mut self, account: AccountId) {
+            if let Some(mut arrangement) = self.beneficiary_of(account) {
+                arrangement.last_active_block = self.env().block_number();
+                crate::metering::note_aux_write();
+                self.beneficiaries.insert(account, arrangement);
+            }
+        }
+    }
+
+    // 动态锁定期: 全局质押量越大, 新质押的最短锁定期越长, 已有的质押保持质押时的锁定期
+    impl Erc20 {
+        #[ink(message)]
+        pub fn dynamic_lockup(&self) -> (bool, Vec<(Balance, u32)>) {
+            (
+                *self.dynamic_lockup_enabled,
+                (*self.dynamic_lockup_tiers).clone(),
+            )
+        }
+
+        /// 按当前 total_staked 取不超过它的最高门槛对应的锁定区块数, 未开启或低于最低门槛时为 0
+        #[ink(message)]
+        pub fn current_min_lockup(&self) -> u32 {
+            if !*self.dynamic_lockup_enabled {
+                return 0;
+            }
+            let total = *self.total_staked;
+            self.dynamic_lockup_tiers
+                .iter()
+                .rev()
+                .find(|(threshold, _)| *threshold <= total)
+                .map(|(_, blocks)| *blocks)
+                .unwrap_or(0)
+        }
+
+        #[ink(message)]
+        pub fn stake_locks_of(&self, account: AccountId) -> Vec<(Balance, u32)> {
+            let now = self.env().block_number();
+            self.stake_locks
+                .get(&account)
+                .map(|locks| {
+                    locks
+                        .iter()
+                        .filter(|(_, until)| *until > now)
+                        .copied()
+                        .collect()
+                })
+                .unwrap_or_default()
+        }
+
+        /// 现在可以解除质押的数量
+        #[ink(message)]
+        pub fn unlocked_stake_of(&self, account: AccountId) -> Balance {
+            let locked = self
+                .stake_locks_of(account)
+                .iter()
+                .fold(0, |acc: Balance, (amount, _)| acc.saturating_add(*amount));
+            self.stake_of(account).saturating_sub(locked)
+        }
+
+        #[ink(message)]
+        pub fn set_dynamic_lockup_enabled(&mut self, enabled: bool) -> Result<()> {
+            self.ensure_owner()?;
+            let old = self.current_min_lockup();
+            *self.dynamic_lockup_enabled = enabled;
+            self.note_lockup_tier(old);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_dynamic_lockup_tiers(&mut self, tiers: Vec<(Balance, u32)>) -> Result<()> {
+            self.ensure_owner()?;
+            if tiers.len() > MAX_LOCKUP_TIERS
+                || tiers
+                    .windows(2)
+                    .any(|w| w[0].0 >= w[1].0 || w[0].1 > w[1].1)
+            {
+                return Err(Error::InvalidLockupTiers);
+            }
+            let old = self.current_min_lockup();
+            *self.dynamic_lockup_tiers = tiers;
+            self.note_lockup_tier(old);
+            Ok(())
+        }
+
+        // 记录一批新质押的锁定期, 顺便清掉已经到期的批次
+        fn lock_stake(&mut self, account: AccountId, amount: Balance, lock_blocks: u32) {
+            let mut locks = self.stake_locks_of(account);
+            if lock_blocks > 0 && amount > 0 {
+                let until = self.env().block_number().saturating_add(lock_blocks);
+                match locks.last_mut() {
+                    Some(last) if locks.len() >= MAX_STAKE_LOCKS => {
+                        last.0 = last.0.saturating_add(amount);
+                        last.1 = last.1.max(until);
+                    }
+                    _ => locks.push((amount, until)),
+                }
+            }
+            if locks.is_empty() {
+                self.stake_locks.take(&account);
+            } else {
+                self.stake_locks.insert(account, locks);
+            }
+        }
+
+        fn note_lockup_tier(&self, old_min_blocks: u32) {
+            let new_min_blocks = self.current_min_lockup();
+            if new_min_blocks != old_min_blocks {
+                self.env().emit_event(LockupTierChanged { new_min_blocks });
+            }
+        }
+    }
+
+    // 压测用: 批量生成测试持有人, 只在 dev feature 部署的合约上可用
+    impl Erc20 {
+        /// 第 index 个测试持有人的账户: Blake2x256(前缀, index)
+        #[ink(message)]
+        pub fn test_holder_account(&self, index: u32) -> AccountId {
+            AccountId::from(
+                self.env()
+                    .hash_encoded::<Blake2x256, _>(&(b"erc20/test-holder", index)),
+            )
+        }
+
+        /// 从 start_index 开始给测试持有人各增发 balance_each, 单次最多 MAX_POPULATE_CHUNK 个,
+        /// 返回下一次调用的 start_index, 等于 count 时表示已经全部写完
+        #[ink(message)]
+        pub fn populate_test_holders(
+            &mut self,
+            start_index: u32,
+            count: u32,
+            balance_each: Balance,
+        ) -> Result<u32> {
+            self.ensure_owner()?;
+            if !*self.dev_mode {
+                return Err(Error::DevModeDisabled);
+            }
+            let end = count.min(start_index.saturating_add(MAX_POPULATE_CHUNK));
+            for index in start_index..end {
+                let holder = self.test_holder_account(index);
+                self.inner_mint(holder, balance_each)?;
+            }
+            Ok(end.max(start_index))
+        }
+    }
+
+    // keeper 赏金: 所有任何人都可以调用的维护任务统一在这里配置和发放赏金
+    impl Erc20 {
+        #[ink(message)]
+        pub fn task_bounty(&self, kind: TaskKind) -> BountyConfig {
+            self.task_bounties.get(&kind).copied().unwrap_or_default()
+        }
+
+        /// BountySource::Escrow 只适用于托管了被处理金额的任务 (定时转账, 提取队列)
+        #[ink(message)]
+        pub fn set_task_bounty(
+            &mut self,
+            kind: TaskKind,
+            bounty: Bounty,
+            source: BountySource,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            if !bounty.is_valid() {
+                return Err(Error::InvalidBps);
+            }
+            self.task_bounties
+                .insert(kind, BountyConfig { bounty, source });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn bounty_pool(&self) -> Balance {
+            *self.bounty_pool
+        }
+
+        /// 任何人都可以向赏金池注入代币
+        #[ink(message)]
+        pub fn fund_bounty_pool(&mut self, amount: Balance) -> Result<()> {
+            let from = self.env().caller();
+            let pool = self
+                .bounty_pool
+                .checked_add(amount)
+                .ok_or(Error::Overflow)?;
+            let contract = self.env().account_id();
+            self.inner_transfer(from, contract, amount)?;
+            *self.bounty_pool = pool;
+            self.escrow_in(PoolId::BountyPool, amount);
+            self.env().emit_event(BountyPoolFunded { from, amount });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn keeper_stats(&self, who: AccountId) -> KeeperStats {
+            self.keeper_stats.get(&who).copied().unwrap_or_default()
+        }
+
+        /// 按 kind 的配置给 keeper 支付处理金额为 basis 的一项的赏金,
+        /// 返回 (实际支付的赏金, 其中从被处理金额扣除的部分), 调用方需要从托管的金额中扣掉后者
+        fn pay_bounty(
+            &mut self,
+            kind: TaskKind,
+            to: AccountId,
+            basis: Balance,
+        ) -> Result<(Balance, Balance)> {
+            let config = self.task_bounty(kind);
+            let contract = self.env().account_id();
+            let amount = match config.source {
+                BountySource::Mint => config.bounty.amount(basis),
+                BountySource::Pool => config.bounty.amount(basis).min(*self.bounty_pool),
+                BountySource::Escrow => config.bounty.amount(basis).min(basis),
+            };
+            if amount == 0 {
+                return Ok((0, 0));
+            }
+            match config.source {
+                BountySource::Mint => self.inner_mint(to, amount)?,
+                BountySource::Pool => {
+                    self.inner_transfer(contract, to, amount)?;
+                    *self.bounty_pool -= amount;
+                    self.escrow_out(PoolId::BountyPool, amount);
+                }
+                BountySource::Escrow => self.inner_transfer(contract, to, amount)?,
+            }
+            let mut stats = self.keeper_stats(to);
+            stats.earned = stats.earned.saturating_add(amount);
+            stats.tasks += 1;
+            self.keeper_stats.insert(to, stats);
+            let deducted = match config.source {
+                BountySource::Escrow => amount,
+                BountySource::Mint | BountySource::Pool => 0,
+            };
+            Ok((amount, deducted))
+        }
+    }
+
+    // 治理金库: 协议手续费累积到金库, 持币人提案并投票决定支出
+    impl Erc20 {
+        #[ink(message)]
+        pub fn treasury_balance(&self) -> Balance {
+            *self.treasury_balance
+        }
+
+        #[ink(message)]
+        pub fn treasury_proposal(&self, id: u64) -> Option<TreasuryProposal> {
+            self.treasury_proposals.get(&id).cloned()
+        }
+
+        /// (发起提案的持有门槛, 法定人数基点)
+        #[ink(message)]
+        pub fn treasury_params(&self) -> (Balance, u16) {
+            (*self.treasury_proposal_threshold, *self.treasury_quorum_bps)
+        }
+
+        #[ink(message)]
+        pub fn set_treasury_params(
+            &mut self,
+            proposal_threshold: Balance,
+            quorum_bps: u16,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            if quorum_bps > 10_000 {
+                return Err(Error::InvalidBps);
+            }
+            *self.treasury_proposal_threshold = proposal_threshold;
+            *self.treasury_quorum_bps = quorum_bps;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn submit_treasury_proposal(
+            &mut self,
+            recipient: AccountId,
+            amount: Balance,
+            description_hash: Hash,
+        ) -> Result<u64> {
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            let proposer = self.env().caller();
+            if self.stored_balance(proposer) < *self.treasury_proposal_threshold {
+                return Err(Error::BelowProposalThreshold);
+            }
+            let id = *self.next_treasury_proposal_id;
+            *self.next_treasury_proposal_id += 1;
+            self.treasury_proposals.insert(
+                id,
+                TreasuryProposal {
+                    id,
+                    recipient,
+                    amount,
+                    description_hash,
+                    votes_for: 0,
+                    votes_against: 0,
+                    executed: false,
+                    created_block: self.env().block_number(),
+                },
+            );
+            self.env().emit_event(TreasuryProposalSubmitted {
+                id,
+                proposer,
+                recipient,
+                amount,
+                description_hash,
+            });
+            Ok(id)
+        }
+
+        /// 从提案创建的下一个区块开始投票, 投票期为 TREASURY_VOTING_PERIOD_BLOCKS.
+        /// 权重为创建区块结束时的投票权, 投票后转走代币不能在别的账户上再投一次
+        #[ink(message)]
+        pub fn vote_treasury_proposal(&mut self, id: u64, support: bool) -> Result<()> {
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            let voter = self.env().caller();
+            let mut proposal = self.treasury_proposal(id).ok_or(Error::ProposalNotFound)?;
+            let block = self.env().block_number();
+            if block <= proposal.created_block {
+                return Err(Error::VotingNotStarted);
+            }
+            if block > proposal.voting_end() {
+                return Err(Error::VotingClosed);
+            }
+            if self.treasury_votes.contains_key(&(id, voter)) {
+                return Err(Error::AlreadyVoted);
+            }
+
+            let weight = self.snapshot_votes(voter, proposal.created_block);
+            if support {
+                proposal.votes_for = proposal.votes_for.saturating_add(weight);
+            } else {
+                proposal.votes_against = proposal.votes_against.saturating_add(weight);
+            }
+            self.treasury_proposals.insert(id, proposal);
+            self.treasury_votes.insert((id, voter), support);
+            self.last_vote_block
+                .insert(voter, self.env().block_number());
+            self.env().emit_event(TreasuryVoteCast {
+                id,
+                voter,
+                support,
+                weight,
+            });
+            Ok(())
+        }
+
+        /// 投票期结束后任何人都可以执行: 投票权重达到法定人数且赞成多于反对时从金库支付
+        #[ink(message)]
+        pub fn execute_treasury_proposal(&mut self, id: u64) -> Result<()> {
+            let mut proposal = self.treasury_proposal(id).ok_or(Error::ProposalNotFound)?;
+            if proposal.executed {
+                return Err(Error::ProposalAlreadyExecuted);
+            }
+            if self.env().block_number() <= proposal.voting_end() {
+                return Err(Error::VotingOpen);
+            }
+            let turnout = proposal.votes_for.saturating_add(proposal.votes_against);
+            if turnout < Self::bps_of(self.total_supply(), *self.treasury_quorum_bps) {
+                return Err(Error::QuorumNotReached);
+            }
+            if proposal.votes_for <= proposal.votes_against {
+                return Err(Error::ProposalNotPassed);
+            }
+            if proposal.amount > *self.treasury_balance {
+                return Err(Error::TreasuryInsufficientFunds);
+            }
+
+            let contract = self.env().account_id();
+            self.inner_transfer(contract, proposal.recipient, proposal.amount)?;
+            *self.treasury_balance -= proposal.amount;
+            self.escrow_out(PoolId::Treasury, proposal.amount);
+            proposal.executed = true;
+            let (recipient, amount) = (proposal.recipient, proposal.amount);
+            self.treasury_proposals.insert(id, proposal);
+            self.env().emit_event(TreasurySpend {
+                id,
+                recipient,
+                amount,
+            });
+            Ok(())
+        }
+    }
+
+    // 托管与仲裁: 付款人托管代币, 争议期内任何一方可以发起争议, 由指定的仲裁人按比例分配.
+    // 仲裁人逾期未裁决时, 任何人都可以把托管全额退回付款人
+    impl Erc20 {
+        #[ink(message)]
+        pub fn escrow(&self, escrow_id: u64) -> Option<Escrow> {
+            self.escrows.get(&escrow_id).copied()
+        }
+
+        #[ink(message)]
+        pub fn dispute_period_blocks(&self) -> u32 {
+            *self.dispute_period_blocks
+        }
+
+        #[ink(message)]
+        pub fn set_dispute_period_blocks(&mut self, blocks: u32) -> Result<()> {
+            self.ensure_owner()?;
+            *self.dispute_period_blocks = blocks;
+            Ok(())
+        }
+
+        /// 调用者把 amount 托管到合约账户, 返回托管 id
+        #[ink(message)]
+        pub fn create_escrow(
+            &mut self,
+            payee: AccountId,
+            amount: Balance,
+            arbitrator: AccountId,
+        ) -> Result<u64> {
+            let payer = self.env().caller();
+            if payee == payer || arbitrator == payer || arbitrator == payee {
+                return Err(Error::NotEscrowParty);
+            }
+            let contract = self.env().account_id();
+            self.inner_transfer(payer, contract, amount)?;
+            self.escrow_in(PoolId::Escrows, amount);
+
+            let escrow_id = *self.next_escrow_id;
+            *self.next_escrow_id += 1;
+            let created_block = self.env().block_number();
+            self.escrows.insert(
+                escrow_id,
+                Escrow {
+                    payer,
+                    payee,
+                    arbitrator,
+                    amount,
+                    created_block,
+                    dispute_deadline: created_block.saturating_add(*self.dispute_period_blocks),
+                    arbitration_deadline: 0,
+                    status: EscrowStatus::Open,
+                },
+            );
+            self.env().emit_event(EscrowCreated {
+                escrow_id,
+                payer,
+                payee,
+                amount,
+                arbitrator,
+            });
+            Ok(escrow_id)
+        }
+
+        /// 付款人随时可以放款; 争议期结束且没有争议时任何人都可以替收款人放款
+        #[ink(message)]
+        pub fn release_escrow(&mut self, escrow_id: u64) -> Result<()> {
+            let mut escrow = self.escrow(escrow_id).ok_or(Error::EscrowNotFound)?;
+            if escrow.status != EscrowStatus::Open {
+                return Err(Error::EscrowNotOpen);
+            }
+            if self.env().caller() != escrow.payer
+                && self.env().block_number() <= escrow.dispute_deadline
+            {
+                return Err(Error::EscrowLocked);
+            }
+            let contract = self.env().account_id();
+            self.inner_refund(contract, escrow.payee, escrow.amount)?;
+            self.escrow_out(PoolId::Escrows, escrow.amount);
+            escrow.status = EscrowStatus::Released;
+            self.escrows.insert(escrow_id, escrow);
+            self.env().emit_event(EscrowReleased { escrow_id });
+            Ok(())
+        }
+
+        /// 付款人或收款人在争议期内发起争议, 之后由仲裁人在 ARBITRATION_PERIOD_BLOCKS 内处理
+        #[ink(message)]
+        pub fn open_dispute(&mut self, escrow_id: u64) -> Result<()> {
+            let mut escrow = self.escrow(escrow_id).ok_or(Error::EscrowNotFound)?;
+            let opener = self.env().caller();
+            if !escrow.is_party(opener) {
+                return Err(Error::NotEscrowParty);
+            }
+            if escrow.status != EscrowStatus::Open {
+                return Err(Error::EscrowNotOpen);
+            }
+            if self.env().block_number() > escrow.dispute_deadline {
+                return Err(Error::DisputePeriodExpired);
+            }
+            escrow.status = EscrowStatus::Disputed;
+            escrow.arbitration_deadline = self
+                .env()
+                .block_number()
+                .saturating_add(ARBITRATION_PERIOD_BLOCKS);
+            self.escrows.insert(escrow_id, escrow);
+            self.env().emit_event(EscrowDisputed { escrow_id, opener });
+            Ok(())
+        }
+
+        /// 仲裁: recipient 得到 amount * split / 10000, 余下的给另一方
+        #[ink(message)]
+        pub fn arbitrate_escrow(
+            &mut self,
+            escrow_id: u64,
+            recipient: AccountId,
+            split: u16,
+        ) -> Result<()> {
+            let mut escrow = self.escrow(escrow_id).ok_or(Error::EscrowNotFound)?;
+            if self.env().caller() != escrow.arbitrator {
+                return Err(Error::NotArbitrator);
+            }
+            if escrow.status != EscrowStatus::Disputed {
+                return Err(Error::NoDisputeOpen);
+            }
+            if self.env().block_number() > escrow.arbitration_deadline {
+                return Err(Error::ArbitrationExpired);
+            }
+            if !escrow.is_party(recipient) {
+                return Err(Error::NotEscrowParty);
+            }
+            if split > 10_000 {
+                return Err(Error::InvalidBps);
+            }
+            let other = if recipient == escrow.payer {
+                escrow.payee
+            } else {
+                escrow.payer
+            };
+            let share = Self::bps_of(escrow.amount, split);
+            let contract = self.env().account_id();
+            self.inner_refund(contract, recipient, share)?;
+            self.inner_refund(contract, other, escrow.amount - share)?;
+            self.escrow_out(PoolId::Escrows, escrow.amount);
+            escrow.status = EscrowStatus::Arbitrated;
+            self.escrows.insert(escrow_id, escrow);
+            self.env().emit_event(EscrowArbitrated {
+                escrow_id,
+                recipient,
+                split,
+            });
+            Ok(())
+        }
+
+        /// 仲裁期限过后任何人都可以调用, 托管全额退回付款人, 相当于没有成交
+        #[ink(message)]
+        pub fn resolve_expired_dispute(&mut self, escrow_id: u64) -> Result<()> {
+            let mut escrow = self.escrow(escrow_id).ok_or(Error::EscrowNotFound)?;
+            if escrow.status != EscrowStatus::Disputed {
+                return Err(Error::NoDisputeOpen);
+            }
+            if self.env().block_number() <= escrow.arbitration_deadline {
+                return Err(Error::EscrowLocked);
+            }
+            let contract = self.env().account_id();
+            self.inner_refund(contract, escrow.payer, escrow.amount)?;
+            self.escrow_out(PoolId::Escrows, escrow.amount);
+            escrow.status = EscrowStatus::DisputeExpired;
+            self.escrows.insert(escrow_id, escrow);
+            self.env().emit_event(EscrowDisputeExpired {
+                escrow_id,
+                payer: escrow.payer,
+                amount: escrow.amount,
+            });
+            Ok(())
+        }
+    }
+
+    // 按持有量平方根和持有时长加权的投票权重
+    impl Erc20 {
+        #[ink(message)]
+        pub fn quadratic_votes(&self, account: AccountId) -> Balance {
+            if !self.may_view(account) {
+                return 0;
+            }
+            integer_sqrt(self.stored_balance(account))
+        }
+
+        /// 基点, 从 10000 开始每持有 HOLDING_MULTIPLIER_STEP_BLOCKS 增加一档, 最多 MAX_HOLDING_MULTIPLIER_BPS
+        #[ink(message)]
+        pub fn holding_duration_multiplier(&self, account: AccountId) -> u16 {
+            self.holding_multiplier_at(account, self.env().block_number())
+        }
+
+        fn holding_multiplier_at(&self, account: AccountId, block: u32) -> u16 {
+            let held = self
+                .first_hold_block
+                .get(&account)
+                .map(|first| block.saturating_sub(*first))
+                .unwrap_or(0);
+            let steps = held / HOLDING_MULTIPLIER_STEP_BLOCKS;
+            let bonus = steps.saturating_mul(u32::from(HOLDING_MULTIPLIER_STEP_BPS));
+            10_000u32
+                .saturating_add(bonus)
+                .min(u32::from(MAX_HOLDING_MULTIPLIER_BPS)) as u16
+        }
+
+        /// quadratic_votes * holding_duration_multiplier / 10000, 私有余额模式下无权查看时为 0
+        #[ink(message)]
+        pub fn get_weighted_votes(&self, account: AccountId) -> Balance {
+            self.quadratic_votes(account)
+                .saturating_mul(Balance::from(self.holding_duration_multiplier(account)))
+                / 10_000
+        }
+
+        /// 投票时使用的加权票数: 按 block 结束时检查点上的投票权开方, 持有时长也算到 block 为止
+        fn snapshot_weighted_votes(&self, account: AccountId, block: u32) -> Balance {
+            integer_sqrt(self.checkpointed_votes(account, block))
+                .saturating_mul(Balance::from(self.holding_multiplier_at(account, block)))
+                / 10_000
+        }
+
+        #[ink(message)]
+        pub fn weighted_voting_enabled(&self) -> bool {
+            *self.weighted_voting_enabled
+        }
+
+        #[ink(message)]
+        pub fn set_weighted_voting(&mut self, enabled: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            *self.weighted_voting_enabled = enabled;
+            Ok(())
+        }
+
+        fn note_first_hold(
+            &mut self,
+            account: AccountId,
+            old_balance: Balance,
+            new_balance: Balance,
+        ) {
+            match HolderTransition::of(old_balance, new_balance) {
+                HolderTransition::Joined => {
+                    crate::metering::note_aux_write();
+                    self.first_hold_block
+                        .insert(account, self.env().block_number());
+                }
+                HolderTransition::Left => {
+                    crate::metering::note_aux_write();
+                    self.first_hold_block.take(&account);
+                }
+                HolderTransition::Unchanged => {}
+            }
+        }
+    }
+
+    // 组合持仓: 一次读取 owner 在本代币和其他 PSP22 代币中的余额
+    impl Erc20 {
+        /// 第一项为本合约的余额, 之后依次为 tokens 中各代币的余额; 只查询前 MAX_PORTFOLIO_TOKENS 个,
+        /// 调用失败或返回值无法解码的代币记为 0, 不影响其他代币
+        #[ink(message)]
+        pub fn portfolio_of(
+            &self,
+            owner: AccountId,
+            tokens: Vec<AccountId>,
+        ) -> Vec<(AccountId, Balance)> {
+            let input = scale::Encode::encode(&owner);
+            let mut portfolio = Vec::with_capacity(tokens.len().min(MAX_PORTFOLIO_TOKENS) + 1);
+            portfolio.push((self.env().account_id(), self.balance_of(owner)));
+            for token in tokens.into_iter().take(MAX_PORTFOLIO_TOKENS) {
+                let balance = self
+                    .do_call(
+                        token,
+                        PSP22_BALANCE_OF_SELECTOR,
+                        &input,
+                        PORTFOLIO_GAS_LIMIT,
+                    )
+                    .ok()
+                    .and_then(|output| <Balance as scale::Decode>::decode(&mut &output[..]).ok())
+                    .unwrap_or_default();
+                portfolio.push((token, balance));
+            }
+            portfolio
+        }
+    }
+
+    // 供应年增长率上限: 限制 owner 每年通过 mint() 增发的总量
+    impl Erc20 {
+        /// (年增长率基点, 本年度已增发, 年初供应量, 年度开始区块)
+        #[ink(message)]
+        pub fn annual_mint_status(&self) -> (u16, Balance, Balance, u32) {
+            (
+                *self.max_annual_growth_rate_bps,
+                *self.total_minted_this_year,
+                *self.year_start_supply,
+                *self.year_start_block,
+            )
+        }
+
+        #[ink(message)]
+        pub fn set_max_annual_growth_rate(&mut self, rate_bps: u16) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            if rate_bps > 10_000 {
+                return Err(Error::InvalidBps);
+            }
+            *self.max_annual_growth_rate_bps = rate_bps;
+            Ok(())
+        }
+
+        // owner 增发 value 后本年度的累计增发量, 超过年增长率上限时报错.
+        // 只用于和上限比较, 饱和即可; 不限增长率时不能因为计数溢出而拒绝增发
+        fn annual_minted_after(&self, value: Balance) -> Result<Balance> {
+            let minted = self.total_minted_this_year.saturating_add(value);
+            let rate = *self.max_annual_growth_rate_bps;
+            if rate > 0 && minted > Self::bps_of(*self.year_start_supply, rate) {
+                return Err(Error::AnnualMintCapExceeded);
+            }
+            Ok(minted)
+        }
+
+        /// 本年度满一年后任何人都可以调用, 以当前供应量开始新的年度
+        #[ink(message)]
+        pub fn reset_annual_mint_counter(&mut self) -> Result<()> {
+            let now = self.env().block_number();
+            if now < self.year_start_block.saturating_add(BLOCKS_PER_YEAR) {
+                return Err(Error::YearNotElapsed);
+            }
+            self.env().emit_event(AnnualCounterReset {
+                year: *self.mint_year,
+                minted: *self.total_minted_this_year,
+                start_supply: *self.year_start_supply,
+            });
+            *self.mint_year += 1;
+            *self.total_minted_this_year = 0;
+            *self.year_start_supply = self.total_supply();
+            *self.year_start_block = now;
+            Ok(())
+        }
+    }
+
+    // 销毁投票: 设置了 burn_vote_cost 后, 每次投票销毁固定数量的代币, 票数等于销毁数量
+    impl Erc20 {
+        #[ink(message)]
+        pub fn burn_vote_cost(&self) -> Balance {
+            *self.burn_vote_cost
+        }
+
+        /// 0 表示关闭销毁投票, 恢复按持币数 (或加权票数) 计票
+        #[ink(message)]
+        pub fn set_burn_vote_cost(&mut self, cost: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            self.ensure_feature(FEATURE_GOVERNANCE)?;
+            *self.burn_vote_cost = cost;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn total_voting_burn(&self) -> Balance {
+            *self.burned_vote_total
+        }
+
+        #[ink(message)]
+        pub fn per_proposal_burn(&self, proposal_id: u64) -> Balance {
+            self.proposal_vote_burns
+                .get(&proposal_id)
+                .copied()
+                .unwrap_or(0)
+        }
+    }
+
+    // 代币元数据: 钱包展示用的元数据地址和 logo 哈希
+    impl Erc20 {
+        #[ink(message)]
+        pub fn metadata_uri(&self) -> Option<String> {
+            (*self.metadata_uri).clone()
+        }
+
+        #[ink(message)]
+        pub fn logo_hash(&self) -> Option<Hash> {
+            *self.logo_hash
+        }
+
+        #[ink(message)]
+        pub fn pending_metadata(&self) -> Option<(Option<String>, Option<Hash>, u32)> {
+            (*self.pending_metadata).clone()
+        }
+
+        #[ink(message)]
+        pub fn contract_info(&self) -> ContractInfo {
+            ContractInfo {
+                abi_version: crate::ABI_VERSION,
+                owner: *self.owner,
+                features: *self.features,
+                whitepaper_hash: *self.whitepaper_hash,
+                metadata_uri: self.metadata_uri(),
+                logo_hash: self.logo_hash(),
+            }
+        }
+
+        /// None 表示清除. 开启 FEATURE_METADATA_TIMELOCK 时只排队, 等待期后由 execute_metadata_update 生效
+        #[ink(message)]
+        pub fn set_metadata_uri(&mut self, uri: Option<String>) -> Result<()> {
+            self.ensure_owner()?;
+            if uri
+                .as_ref()
+                .map_or(false, |uri| uri.len() > MAX_METADATA_URI_LEN)
+            {
+                return Err(Error::UriTooLong);
+            }
+            let (_, logo_hash) = self.next_metadata();
+            self.update_metadata(uri, logo_hash);
+            Ok(())
+        }
+
+        /// 与 set_metadata_uri 相同, 受 FEATURE_METADATA_TIMELOCK 约束
+        #[ink(message)]
+        pub fn set_logo_hash(&mut self, logo_hash: Option<Hash>) -> Result<()> {
+            self.ensure_owner()?;
+            let (uri, _) = self.next_metadata();
+            self.update_metadata(uri, logo_hash);
+            Ok(())
+        }
+
+        /// 等待期结束后任何人都可以让排队的元数据更新生效
+        #[ink(message)]
+        pub fn execute_metadata_update(&mut self) -> Result<()> {
+            let (uri, logo_hash, eta) = self.pending_metadata().ok_or(Error::NoPendingMetadata)?;
+            if self.env().block_number() < eta {
+                return Err(Error::TimelockNotExpired);
+            }
+            *self.pending_metadata = None;
+            self.apply_metadata(uri, logo_hash);
+            Ok(())
+        }
+
+        // 排队中的更新基于排队的值继续修改, 否则基于当前值
+        fn next_metadata(&self) -> (Option<String>, Option<Hash>) {
+            match self.pending_metadata() {
+                Some((uri, logo_hash, _)) => (uri, logo_hash),
+                None => (self.metadata_uri(), self.logo_hash()),
+            }
+        }
+
+        // 再次排队会覆盖排队中的更新并重新计时
+        fn update_metadata(&mut self, uri: Option<String>, logo_hash: Option<Hash>) {
+            if !self.is_feature_enabled(FEATURE_METADATA_TIMELOCK) {
+                self.apply_metadata(uri, logo_hash);
+                return;
+            }
+            let eta = self
+                .env()
+                .block_number()
+                .saturating_add(METADATA_TIMELOCK_BLOCKS);
+            *self.pending_metadata = Some((uri.clone(), logo_hash, eta));
+            self.env().emit_event(MetadataUpdateQueued {
+                metadata_uri: uri,
+                logo_hash,
+                eta,
+            });
+        }
+
+        fn apply_metadata(&mut self, uri: Option<String>, logo_hash: Option<Hash>) {
+            let content_hash = Hash::from(
+                self.env()
+                    .hash_encoded::<Blake2x256, _>(&(uri.clone(), logo_hash)),
+            );
+            *self.metadata_uri = uri.clone();
+            *self.logo_hash = logo_hash;
+            self.env().emit_event(MetadataUpdated {
+                content_hash,
+                metadata_uri: uri,
+                logo_hash,
+            });
+        }
+    }
+
+    // 创世领取: 按其他链 ERC-20 的快照, 持有人凭 merkle 证明领取换算后的代币
+    impl Erc20 {
+        /// (快照根, 累计上限, 换算比例基点, 已领取总量)
+        #[ink(message)]
+        pub fn genesis_snapshot(&self) -> (Hash, Balance, u16, Balance) {
+            (
+                *self.genesis_snapshot_root,
+                *self.genesis_claim_cap,
+                *self.genesis_conversion_rate,
+                *self.genesis_total_claimed,
+            )
+        }
+
+        #[ink(message)]
+        pub fn genesis_claimed(&self, account: AccountId) -> bool {
+            self.genesis_snapshot_claimed
+                .get(&account)
+                .copied()
+                .unwrap_or(false)
+        }
+
+        /// 更换快照不清除已领取记录, cap 是所有快照共用的累计上限
+        #[ink(message)]
+        pub fn set_genesis_snapshot(
+            &mut self,
+            root: Hash,
+            cap: Balance,
+            conversion_rate: u16,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            *self.genesis_snapshot_root = root;
+            *self.genesis_claim_cap = cap;
+            *self.genesis_conversion_rate = conversion_rate;
+            Ok(())
+        }
+
+        /// 叶子为 Blake2x256(0x00 || original_chain_address || amount 的 SCALE 编码 || 领取账户),
+        /// 领取账户由快照发布方写入, 只有该账户能用这条证明领取, 公开快照后别人无法抢先领走.
+        /// 证明中每一层为 Blake2x256(0x01 || 较小的哈希 || 较大的哈希), 前缀不同使叶子无法冒充中间节点.
+        /// 领取数量为 amount * 换算比例 / 10000
+        #[ink(message)]
+        pub fn genesis_claim(
+            &mut self,
+            amount: Balance,
+            merkle_proof: Vec<Hash>,
+            original_chain_address: Vec<u8>,
+        ) -> Result<()> {
+            let claimer = self.env().caller();
+            let mut leaf_input = Vec::with_capacity(original_chain_address.len() + 49);
+            leaf_input.push(MERKLE_LEAF_PREFIX);
+            leaf_input.extend_from_slice(&original_chain_address);
+            leaf_input.extend_from_slice(&scale::Encode::encode(&amount));
+            leaf_input.extend_from_slice(claimer.as_ref());
+            let leaf = Hash::from(self.env().hash_bytes::<Blake2x256>(&leaf_input));
+            if !self.verify_merkle_proof(leaf, &merkle_proof) {
+                return Err(Error::InvalidMerkleProof);
+            }
+            if self.genesis_claimed(claimer) || self.genesis_claimed_leaves.contains_key(&leaf) {
+                return Err(Error::GenesisAlreadyClaimed);
+            }
+            let rate = Balance::from(*self.genesis_conversion_rate);
+            let minted = (amount / 10_000)
+                .checked_mul(rate)
+                .and_then(|whole| whole.checked_add(amount % 10_000 * rate / 10_000))
+                .ok_or(Error::Overflow)?;
+            let total = self
+                .genesis_total_claimed
+                .checked_add(minted)
+                .ok_or(Error::Overflow)?;
+            if total > *self.genesis_claim_cap {
+                return Err(Error::GenesisClaimCapExceeded);
+            }
+
+            self.inner_mint(claimer, minted)?;
+            *self.genesis_total_claimed = total;
+            self.genesis_snapshot_claimed.insert(claimer, true);
+            self.genesis_claimed_leaves.insert(leaf, ());
+            self.env().emit_event(GenesisClaimed {
+                claimer,
+                original_address: original_chain_address,
+                amount,
+                minted,
+            });
+            Ok(())
+        }
+
+        fn verify_merkle_proof(&self, leaf: Hash, proof: &[Hash]) -> bool {
+            let mut node = leaf;
+            for sibling in proof {
+                let (first, second) = if node.as_ref() <= sibling.as_ref() {
+                    (node, *sibling)
+                } else {
+                    (*sibling, node)
+                };
+                let mut input = [0u8; 65];
+                input[0] = MERKLE_NODE_PREFIX;
+                input[1..33].copy_from_slice(first.as_ref());
+                input[33..].copy_from_slice(second.as_ref());
+                node = Hash::from(self.env().hash_bytes::<Blake2x256>(&input));
+            }
+            node == *self.genesis_snapshot_root
+        }
+    }
+
+    // 可追回增发: 积分类增发在窗口期内锁定, 发现欺诈时由发起人追回销毁, 窗口结束后归接收人所有
+    impl Erc20 {
+        #[ink(message)]
+        pub fn clawback_mint(&self, mint_id: u64) -> Option<ClawbackMint> {
+            self.clawback_mints.get(&mint_id).copied()
+        }
+
+        #[ink(message)]
+        pub fn clawback_ids_of(&self, account: AccountId) -> Vec<u64> {
+            self.clawback_ids_of
+                .get(&account)
+                .cloned()
+                .unwrap_or_default()
+        }
+
+        /// 账户余额中还在追回窗口内, 不能转出或销毁的数量
+        #[ink(message)]
+        pub fn clawback_locked_of(&self, account: AccountId) -> Balance {
+            TransferCtx::clawback_locked(
+                &self.clawback_ids_of,
+                &self.clawback_mints,
+                &account,
+                self.env().block_number(),
+            )
+        }
+
+        /// 与 mint 相同, 另外在 window_blocks 个区块内锁定这部分代币, 期间 owner 可以追回. 返回 mint_id
+        #[ink(message)]
+        pub fn mint_with_clawback(
+            &mut self,
+            to: AccountId,
+            value: Balance,
+            window_blocks: u32,
+        ) -> Result<u64> {
+            self.ensure_owner()?;
+            if window_blocks == 0 {
+                return Err(Error::InvalidClawbackWindow);
+            }
+            let to = self.forwarding_target(to);
+            let mut ids = self.clawback_ids_of(to);
+            if ids.len() >= MAX_CLAWBACK_ENTRIES {
+                return Err(Error::TooManyClawbackEntries);
+            }
+            let minted = self.annual_minted_after(value)?;
+            self.inner_mint(to, value)?;
+            *self.total_minted_this_year = minted;
+
+            let minter = self.env().caller();
+            let mint_id = *self.next_clawback_id;
+            *self.next_clawback_id += 1;
+            let expires_at = self.env().block_number().saturating_add(window_blocks);
+            self.clawback_mints.insert(
+                mint_id,
+                ClawbackMint {
+                    minter,
+                    recipient: to,
+                    amount: value,
+                    expires_at,
+                },
+            );
+            ids.push(mint_id);
+            self.clawback_ids_of.insert(to, ids);
+            self.env().emit_event(ClawbackMinted {
+                mint_id,
+                recipient: to,
+                minter,
+                amount: value,
+                expires_at,
+            });
+            Ok(mint_id)
+        }
+
+        /// 窗口内由发起人调用, 从接收人余额中销毁这笔增发
+        #[ink(message)]
+        pub fn clawback(&mut self, mint_id: u64) -> Result<()> {
+            let mint = self.clawback_mint(mint_id).ok_or(Error::ClawbackNotFound)?;
+            if self.env().caller() != mint.minter {
+                return Err(Error::NotMinter);
+            }
+            if self.env().block_number() >= mint.expires_at {
+                return Err(Error::ClawbackWindowClosed);
+            }
+            self.ensure_not_paused(PAUSE_BURN)?;
+            self.remove_clawback(mint_id, &mint);
+            // 锁定期内代币不能转出, 只有 owner 强制转移等绕过限制的操作会让余额少于锁定数量
+            let amount = mint.amount.min(self.stored_balance(mint.recipient));
+            self.inner_burn(mint.recipient, amount)?;
+            self.env().emit_event(ClawedBack {
+                mint_id,
+                recipient: mint.recipient,
+                amount,
+            });
+            Ok(())
+        }
+
+        /// 窗口结束后任何人都可以调用, 删除记录并确认代币归接收人所有
+        #[ink(message)]
+        pub fn mature_clawback(&mut self, mint_id: u64) -> Result<()> {
+            let mint = self.clawback_mint(mint_id).ok_or(Error::ClawbackNotFound)?;
+            if self.env().block_number() < mint.expires_at {
+                return Err(Error::ClawbackWindowOpen);
+            }
+            self.remove_clawback(mint_id, &mint);
+            self.env().emit_event(ClawbackMatured {
+                mint_id,
+                recipient: mint.recipient,
+                amount: mint.amount,
+            });
+            Ok(())
+        }
+
+        fn remove_clawback(&mut self, mint_id: u64, mint: &ClawbackMint) {
+            self.clawback_mints.take(&mint_id);
+            let mut ids = self.clawback_ids_of(mint.recipient);
+            ids.retain(|id| *id != mint_id);
+            if ids.is_empty() {
+                self.clawback_ids_of.take(&mint.recipient);
+            } else {
+                self.clawback_ids_of.insert(mint.recipient, ids);
+            }
+        }
+    }
+
+    // 团队与投资人分配: 按预先写好的阶段分批解锁
+    impl Erc20 {
+        #[ink(message)]
+        pub fn allocation_info(&self, id: u64) -> Option<TeamAllocation> {
+            self.team_allocations.get(&id).cloned()
+        }
+
+        /// total 从调用者 (owner) 转入合约托管. stages 的比例之和必须为 10000
+        #[ink(message)]
+        pub fn create_team_allocation(
+            &mut self,
+            beneficiary: AccountId,
+            total: Balance,
+            cliff: u32,
+            stages: Vec<(u32, u16)>,
+        ) -> Result<u64> {
+            self.ensure_owner()?;
+            let increasing = stages.windows(2).all(|pair| pair[0].0 < pair[1].0);
+            let bps_sum: u32 = stages.iter().map(|(_, bps)| u32::from(*bps)).sum();
+            if stages.is_empty()
+                || stages.len() > MAX_ALLOCATION_STAGES
+                || !increasing
+                || bps_sum != 10_000
+            {
+                return Err(Error::InvalidAllocationStages);
+            }
+            let owner = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(owner, contract, total)?;
+            self.escrow_in(PoolId::TeamAllocations, total);
+
+            let id = *self.next_team_allocation_id;
+            *self.next_team_allocation_id += 1;
+            self.team_allocations.insert(
+                id,
+                TeamAllocation {
+                    beneficiary,
+                    total,
+                    cliff_block: cliff,
+                    stages,
+                    released: 0,
+                },
+            );
+            self.env().emit_event(AllocationCreated {
+                id,
+                beneficiary,
+                total,
+                cliff_block: cliff,
+            });
+            Ok(id)
+        }
+
+        /// 任何人都可以调用, 把已解锁但未释放的部分转给受益人
+        #[ink(message)]
+        pub fn release_team_allocation(&mut self, allocation_id: u64) -> Result<()> {
+            let mut allocation = self
+                .allocation_info(allocation_id)
+                .ok_or(Error::AllocationNotFound)?;
+            let now = self.env().block_number();
+            if now < allocation.cliff_block {
+                return Err(Error::CliffNotReached);
+            }
+            let (unlocked, stage_index) = allocation.unlocked_at(now);
+            let amount = unlocked.saturating_sub(allocation.released);
+            let stage_index = match stage_index {
+                Some(index) if amount > 0 => index,
+                _ => return Err(Error::NothingToRelease),
+            };
+
+            let contract = self.env().account_id();
+            self.inner_refund(contract, allocation.beneficiary, amount)?;
+            self.escrow_out(PoolId::TeamAllocations, amount);
+            allocation.released += amount;
+            self.team_allocations.insert(allocation_id, allocation);
+            self.env().emit_event(AllocationStageReleased {
+                id: allocation_id,
+                stage_index,
+                amount,
+            });
+            Ok(())
+        }
+    }
+
+    // 手续费返还: 按发送者在统计窗口内的发送量分档, 从金库返还一部分已付手续费
+    impl Erc20 {
+        /// (返还档位, 统计窗口区块数)
+        #[ink(message)]
+        pub fn fee_rebate_config(&self) -> (Vec<(Balance, u16)>, u32) {
+            (
+                (*self.fee_rebate_tiers).clone(),
+                *self.fee_rebate_window_blocks,
+            )
+        }
+
+        /// 传入空列表关闭返还, 之后的转账不再统计发送量
+        #[ink(message)]
+        pub fn set_fee_rebate_tiers(&mut self, tiers: Vec<(Balance, u16)>) -> Result<()> {
+            self.ensure_owner()?;
+            let increasing = tiers.windows(2).all(|pair| pair[0].0 < pair[1].0);
+            if tiers.len() > MAX_FEE_REBATE_TIERS
+                || !increasing
+                || tiers.iter().any(|(_, bps)| *bps > 10_000)
+            {
+                return Err(Error::InvalidRebateTiers);
+            }
+            *self.fee_rebate_tiers = tiers;
+            Ok(())
+        }
+
+        /// 修改窗口长度后窗口编号随之改变, 所有账户从新窗口重新统计
+        #[ink(message)]
+        pub fn set_fee_rebate_window(&mut self, blocks: u32) -> Result<()> {
+            self.ensure_owner()?;
+            if blocks == 0 {
+                return Err(Error::InvalidRebateTiers);
+            }
+            *self.fee_rebate_window_blocks = blocks;
+            Ok(())
+        }
+
+        /// 当前窗口的统计, 窗口内还没有转出时为 None
+        #[ink(message)]
+        pub fn sender_fee_window(&self, account: AccountId) -> Option<SenderFeeWindow> {
+            self.sender_fee_windows
+                .get(&account)
+                .copied()
+                .filter(|stats| stats.window == self.fee_rebate_window())
+        }
+
+        /// 当前窗口发送量达到的最高档位下标, 一档都没有达到时为 None
+        #[ink(message)]
+        pub fn fee_rebate_tier_of(&self, account: AccountId) -> Option<u32> {
+            let volume = self.sender_fee_window(account)?.volume;
+            self.fee_rebate_tiers
+                .iter()
+                .rposition(|(threshold, _)| volume >= *threshold)
+                .map(|index| index as u32)
+        }
+
+        /// 可领取的返还: 已经结束但还没领取的窗口, 加上最近一个窗口按当前档位计算的返还
+        #[ink(message)]
+        pub fn pending_fee_rebate(&self, account: AccountId) -> Balance {
+            let settled = self
+                .settled_fee_rebates
+                .get(&account)
+                .map_or(0, |(_, _, rebate)| *rebate);
+            let latest = self
+                .sender_fee_windows
+                .get(&account)
+                .map_or(0, |stats| self.window_rebate(stats));
+            settled.saturating_add(latest)
+        }
+
+        /// 一次领取所有可领取的返还, 最近一个窗口随之标记为已领取,
+        /// 它还是当前窗口时, 领取之后本窗口内再付的手续费不再返还
+        #[ink(message)]
+        pub fn claim_fee_rebate(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            let settled = self.settled_fee_rebates.get(&caller).copied();
+            if settled.is_none()
+                && self
+                    .sender_fee_window(caller)
+                    .map_or(false, |stats| stats.claimed)
+            {
+                return Err(Error::FeeRebateAlreadyClaimed);
+            }
+            let rebate = self.pending_fee_rebate(caller);
+            if rebate == 0 {
+                return Err(Error::NothingToWithdraw);
+            }
+            if rebate > *self.treasury_balance {
+                return Err(Error::TreasuryInsufficientFunds);
+            }
+
+            let contract = self.env().account_id();
+            self.inner_transfer(contract, caller, rebate)?;
+            *self.treasury_balance -= rebate;
+            self.escrow_out(PoolId::Treasury, rebate);
+            let (mut window, mut fees_paid, _) = settled.unwrap_or_default();
+            self.settled_fee_rebates.take(&caller);
+            if let Some(mut stats) = self.sender_fee_windows.get(&caller).copied() {
+                if self.window_rebate(&stats) > 0 {
+                    window = stats.window;
+                    fees_paid = fees_paid.saturating_add(stats.fees_paid);
+                    stats.claimed = true;
+                    self.sender_fee_windows.insert(caller, stats);
+                }
+            }
+            self.env().emit_event(FeeRebateClaimed {
+                account: caller,
+                window,
+                fees_paid,
+                rebate,
+            });
+            Ok(())
+        }
+
+        // 未领取时按当前档位计算, 一档都没有达到时为 0
+        fn window_rebate(&self, stats: &SenderFeeWindow) -> Balance {
+            if stats.claimed {
+                return 0;
+            }
+            self.fee_rebate_tiers
+                .iter()
+                .rev()
+                .find(|(threshold, _)| stats.volume >= *threshold)
+                .map_or(0, |(_, bps)| Self::bps_of(stats.fees_paid, *bps))
+        }
+
+        fn fee_rebate_window(&self) -> u32 {
+            self.env().block_number() / *self.fee_rebate_window_blocks
+        }
+
+        // 没有配置返还档位时不统计, 省一次存储写入
+        fn note_sender_volume(&mut self, sender: AccountId, value: Balance, fee: Balance) {
+            if self.fee_rebate_tiers.is_empty() {
+                return;
+            }
+            let window = self.fee_rebate_window();
+            // 进入新窗口前把上一个窗口没领取的返还结转, 之后仍然可以领取
+            if let Some(previous) = self.sender_fee_windows.get(&sender).copied() {
+                let rebate = self.window_rebate(&previous);
+                if previous.window != window && rebate > 0 {
+                    let (_, fees_paid, settled) = self
+                        .settled_fee_rebates
+                        .get(&sender)
+                        .copied()
+                        .unwrap_or_default();
+                    self.settled_fee_rebates.insert(
+                        sender,
+                        (
+                            previous.window,
+                            fees_paid.saturating_add(previous.fees_paid),
+                            settled.saturating_add(rebate),
+                        ),
+                    );
+                }
+            }
+            let mut stats = self.sender_fee_window(sender).unwrap_or(SenderFeeWindow {
+                window,
+                volume: 0,
+                fees_paid: 0,
+                claimed: false,
+            });
+            stats.volume = stats.volume.saturating_add(value);
+            stats.fees_paid = stats.fees_paid.saturating_add(fee);
+            self.sender_fee_windows.insert(sender, stats);
+        }
+    }
+
+    // 乐观结算: 运营方在链下批量处理转账后上链结算, 挑战期内可以凭欺诈证明回滚整个批次
+    impl Erc20 {
+        /// (运营方, 下一个批次编号, 挑战期区块数)
+        #[ink(message)]
+        pub fn settlement_config(&self) -> (Option<AccountId>, u64, u32) {
+            (
+                *self.settlement_operator,
+                *self.optimistic_batch_nonce,
+                *self.challenge_period_blocks,
+            )
+        }
+
+        #[ink(message)]
+        pub fn settled_batch(&self, nonce: u64) -> Option<SettledBatch> {
+            self.settled_batches.get(&nonce).cloned()
+        }
+
+        #[ink(message)]
+        pub fn is_optimistic_participant(&self, account: AccountId) -> bool {
+            self.optimistic_participants
+                .get(&account)
+                .copied()
+                .unwrap_or(false)
+        }
+
+        #[ink(message)]
+        pub fn set_settlement_operator(&mut self, operator: Option<AccountId>) -> Result<()> {
+            self.ensure_owner()?;
+            *self.settlement_operator = operator;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_challenge_period_blocks(&mut self, blocks: u32) -> Result<()> {
+            self.ensure_owner()?;
+            *self.challenge_period_blocks = blocks;
+            Ok(())
+        }
+
+        /// 调用者同意 (或撤回同意) 运营方在批次中提交自己签名的转账
+        #[ink(message)]
+        pub fn set_optimistic_participation(&mut self, enabled: bool) -> Result<()> {
+            let caller = self.env().caller();
+            if enabled {
+                self.optimistic_participants.insert(caller, true);
+            } else {
+                self.optimistic_participants.take(&caller);
+            }
+            Ok(())
+        }
+
+        /// from 下一笔批量转账需要使用的 nonce
+        #[ink(message)]
+        pub fn batched_transfer_nonce(&self, from: AccountId) -> u64 {
+            self.batched_transfer_nonces
+                .get(&from)
+                .copied()
+                .unwrap_or_default()
+        }
+
+        /// 转出账户需要为批次中的每一笔转账签名的哈希
+        #[ink(message)]
+        pub fn batched_transfer_hash(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            nonce: u64,
+        ) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                BATCHED_TRANSFER_TYPE_TAG,
+                self.env().account_id(),
+                from,
+                to,
+                value,
+                nonce,
+            )))
+        }
+
+        /// 运营方需要签名的批次哈希, 覆盖每笔转账及其转出账户的签名
+        #[ink(message)]
+        pub fn settlement_hash(
+            &self,
+            nonce: u64,
+            state_root: Hash,
+            transfers: Vec<BatchedTransfer>,
+        ) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                SETTLEMENT_TYPE_TAG,
+                self.env().account_id(),
+                nonce,
+                state_root,
+                transfers,
+            )))
+        }
+
+        /// 运营方提交按顺序编号的批次. 转出账户需要事先同意, 每笔转账的 nonce 按顺序递增,
+        /// 转出账户的签名不在这里验证, 而是在挑战期内由任何人用欺诈证明指出.
+        /// 转出的代币先托管在合约账户, 挑战期结束后由 finalize_batch 付给收款方,
+        /// 余额不足时整个批次都不执行
+        #[ink(message)]
+        pub fn settle_batch(
+            &mut self,
+            nonce: u64,
+            state_root: Hash,
+            transfers: Vec<BatchedTransfer>,
+            signature: [u8; 65],
+        ) -> Result<()> {
+            let operator = (*self.settlement_operator).ok_or(Error::NotSettlementOperator)?;
+            if self.env().caller() != operator {
+                return Err(Error::NotSettlementOperator);
+            }
+            // 批次中的转账没有合规签名
+            if *self.compliance_required {
+                return Err(Error::ComplianceSignatureMissing);
+            }
+            if nonce != *self.optimistic_batch_nonce {
+                return Err(Error::InvalidNonce);
+            }
+            Self::ensure_batch_len(transfers.len())?;
+            let batch_hash = self.settlement_hash(nonce, state_root, transfers.clone());
+            let mut message_hash = [0u8; 32];
+            message_hash.copy_from_slice(batch_hash.as_ref());
+            if self.recover_signer(&message_hash, &signature) != Some(operator) {
+                return Err(Error::InvalidSettlementSignature);
+            }
+            if transfers
+                .iter()
+                .any(|transfer| !self.is_optimistic_participant(transfer.from))
+            {
+                return Err(Error::NotOptimisticParticipant);
+            }
+            // (转出账户, 合计转出, 下一个 nonce)
+            let mut debits: Vec<(AccountId, Balance, u64)> = Vec::new();
+            for transfer in transfers.iter() {
+                let index = match debits
+                    .iter()
+                    .position(|(from, _, _)| *from == transfer.from)
+                {
+                    Some(index) => index,
+                    None => {
+                        let next_nonce = self.batched_transfer_nonce(transfer.from);
+                        debits.push((transfer.from, 0, next_nonce));
+                        debits.len() - 1
+                    }
+                };
+                let (_, total, next_nonce) = &mut debits[index];
+                if transfer.nonce != *next_nonce {
+                    return Err(Error::InvalidNonce);
+                }
+                *next_nonce += 1;
+                *total = total.checked_add(transfer.value).ok_or(Error::Overflow)?;
+            }
+            let contract = self.env().account_id();
+            let config = self.policy_config();
+            for (from, total, _) in debits.iter() {
+                if self.stored_balance(*from) < *total {
+                    return Err(Error::InsufficientBalance);
+                }
+                config.policy.check(&self.transfer_ctx(
+                    *from,
+                    contract,
+                    *total,
+                    TransferOrigin::Internal,
+                ))?;
+            }
+            let mut pre_state: Vec<(AccountId, Balance)> = Vec::new();
+            for transfer in transfers.iter() {
+                for account in [transfer.from, transfer.to].iter() {
+                    if !pre_state.iter().any(|(known, _)| known == account) {
+                        pre_state.push((*account, self.stored_balance(*account)));
+                    }
+                }
+            }
+
+            let mut escrowed: Balance = 0;
+            for (from, total, next_nonce) in debits {
+                self.batched_transfer_nonces.insert(from, next_nonce);
+                if total > 0 {
+                    self.inner_transfer(from, contract, total)?;
+                    escrowed += total;
+                }
+            }
+            self.escrow_in(PoolId::SettlementBatches, escrowed);
+            *self.optimistic_batch_nonce += 1;
+            let transfer_count = transfers.len() as u32;
+            self.settled_batches.insert(
+                nonce,
+                SettledBatch {
+                    batch_hash,
+                    state_root,
+                    transfers: transfers
+                        .iter()
+                        .map(|transfer| (transfer.from, transfer.to, transfer.value))
+                        .collect(),
+                    pre_state,
+                    settled_block: self.env().block_number(),
+                    rolled_back: false,
+                    finalized: false,
+                },
+            );
+            self.env().emit_event(BatchSettled {
+                nonce,
+                transfer_count,
+            });
+            Ok(())
+        }
+
+        /// 挑战期内任何人都可以提交欺诈证明. 证明成立时托管的代币全部退回转出账户,
+        /// 收款方在批次完成前没有收到任何代币, 回滚不会从他们那里扣回
+        #[ink(message)]
+        pub fn challenge_settlement(&mut self, batch_nonce: u64, proof: FraudProof) -> Result<()> {
+            let mut batch = self
+                .settled_batch(batch_nonce)
+                .ok_or(Error::BatchNotFound)?;
+            if batch.rolled_back {
+                return Err(Error::BatchAlreadyRolledBack);
+            }
+            let deadline = batch
+                .settled_block
+                .saturating_add(*self.challenge_period_blocks);
+            if batch.finalized || self.env().block_number() > deadline {
+                return Err(Error::ChallengePeriodOver);
+            }
+            if !self.is_fraudulent(batch_nonce, &batch, proof) {
+                return Err(Error::InvalidFraudProof);
+            }
+
+            let contract = self.env().account_id();
+            let mut refunds: Vec<(AccountId, Balance)> = Vec::new();
+            for (from, _, value) in batch.transfers.iter() {
+                match refunds.iter_mut().find(|(account, _)| account == from) {
+                    Some((_, total)) => *total += *value,
+                    None => refunds.push((*from, *value)),
+                }
+            }
+            for (from, value) in refunds {
+                if value > 0 {
+                    self.inner_refund(contract, from, value)?;
+                    self.escrow_out(PoolId::SettlementBatches, value);
+                }
+            }
+            batch.rolled_back = true;
+            self.settled_batches.insert(batch_nonce, batch);
+            self.env()
+                .emit_event(BatchChallenged { nonce: batch_nonce });
+            Ok(())
+        }
+
+        /// 挑战期结束后任何人都可以调用, 把托管的代币付给各收款方
+        #[ink(message)]
+        pub fn finalize_batch(&mut self, batch_nonce: u64) -> Result<()> {
+            let mut batch = self
+                .settled_batch(batch_nonce)
+                .ok_or(Error::BatchNotFound)?;
+            if batch.rolled_back {
+                return Err(Error::BatchAlreadyRolledBack);
+            }
+            if batch.finalized {
+                return Err(Error::BatchAlreadyFinalized);
+            }
+            let deadline = batch
+                .settled_block
+                .saturating_add(*self.challenge_period_blocks);
+            if self.env().block_number() <= deadline {
+                return Err(Error::ChallengePeriodNotOver);
+            }
+
+            let contract = self.env().account_id();
+            for (_, to, value) in batch.transfers.iter() {
+                if *value > 0 {
+                    self.inner_refund(contract, *to, *value)?;
+                    self.escrow_out(PoolId::SettlementBatches, *value);
+                }
+            }
+            batch.finalized = true;
+            self.settled_batches.insert(batch_nonce, batch);
+            self.env().emit_event(BatchFinalized { nonce: batch_nonce });
+            Ok(())
+        }
+
+        fn is_fraudulent(&self, batch_nonce: u64, batch: &SettledBatch, proof: FraudProof) -> bool {
+            match proof {
+                FraudProof::UnauthorizedTransfer { transfers, index } => {
+                    if self.settlement_hash(batch_nonce, batch.state_root, transfers.clone())
+                        != batch.batch_hash
+                    {
+                        return false;
+                    }
+                    let transfer = match transfers.get(index as usize) {
+                        Some(transfer) => transfer,
+                        None => return false,
+                    };
+                    let hash = self.batched_transfer_hash(
+                        transfer.from,
+                        transfer.to,
+                        transfer.value,
+                        transfer.nonce,
+                    );
+                    let mut message_hash = [0u8; 32];
+                    message_hash.copy_from_slice(hash.as_ref());
+                    self.recover_signer(&message_hash, &transfer.signature) != Some(transfer.from)
+                }
+                FraudProof::WrongStateRoot { post_state } => {
+                    let claimed: Vec<(AccountId, Balance)> =
+                        match scale::Decode::decode(&mut &post_state[..]) {
+                            Ok(claimed) => claimed,
+                            Err(_) => return false,
+                        };
+                    let proof_root = Hash::from(self.env().hash_bytes::<Blake2x256>(&post_state));
+                    claimed == batch.post_state() && proof_root != batch.state_root
+                }
+            }
+        }
+    }
+
+    // 欢迎奖励: 活动期间账户第一次收到转账时, 从 owner 注入的奖励池额外支付一份固定奖励
+    impl Erc20 {
+        /// (是否进行中, 每份奖励, 奖励池剩余)
+        #[ink(message)]
+        pub fn campaign_info(&self) -> (bool, Balance, Balance) {
+            (
+                *self.campaign_active,
+                *self.welcome_bonus,
+                *self.welcome_pool,
+            )
+        }
+
+        #[ink(message)]
+        pub fn is_welcomed(&self, account: AccountId) -> bool {
+            self.welcomed.get(&account).is_some()
+        }
+
+        /// 开始活动, pool 从 owner 转入合约托管
+        #[ink(message)]
+        pub fn start_campaign(&mut self, bonus: Balance, pool: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            if *self.campaign_active {
+                return Err(Error::CampaignAlreadyActive);
+            }
+            if bonus == 0 || pool < bonus {
+                return Err(Error::InvalidCampaign);
+            }
+            let owner = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(owner, contract, pool)?;
+            self.escrow_in(PoolId::WelcomeBonus, pool);
+            *self.campaign_active = true;
+            *self.welcome_bonus = bonus;
+            *self.welcome_pool = pool;
+            self.env().emit_event(CampaignStarted { bonus, pool });
+            Ok(())
+        }
+
+        /// 结束活动, 剩余奖励池退回 owner
+        #[ink(message)]
+        pub fn stop_campaign(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            if !*self.campaign_active {
+                return Err(Error::NoActiveCampaign);
+            }
+            *self.campaign_active = false;
+            let refunded = *self.welcome_pool;
+            *self.welcome_pool = 0;
+            if refunded > 0 {
+                let contract = self.env().account_id();
+                self.inner_refund(contract, *self.owner, refunded)?;
+                self.escrow_out(PoolId::WelcomeBonus, refunded);
+            }
+            self.env().emit_event(CampaignStopped { refunded });
+            Ok(())
+        }
+
+        // 在 write_transfer 写入收款方余额之后调用. 收款前余额为 0 才算新账户,
+        // 奖励本身的入账不会再触发 (收款方已标记且余额不为 0). 奖励发放失败不影响原转账
+        fn pay_welcome_bonus(
+            &mut self,
+            account: AccountId,
+            old_balance: Balance,
+            value: Balance,
+            changes: &mut TokenChanges,
+        ) {
+            if !*self.campaign_active || value == 0 || self.is_welcomed(account) {
+                return;
+            }
+            let contract = self.env().account_id();
+            if account == contract {
+                return;
+            }
+            self.welcomed.insert(account, ());
+            let bonus = *self.welcome_bonus;
+            if old_balance > 0 || *self.welcome_pool < bonus {
+                return;
+            }
+            if self
+                .write_transfer(
+                    contract,
+                    account,
+                    bonus,
+                    TransferKind::Normal,
+                    TransferOrigin::Internal,
+                    changes,
+                )
+                .is_err()
+            {
+                self.welcomed.take(&account);
+                return;
+            }
+            *self.welcome_pool -= bonus;
+            self.escrow_out(PoolId::WelcomeBonus, bonus);
+            self.env().emit_event(WelcomeBonusPaid { account, bonus });
+            if *self.welcome_pool < bonus {
+                self.env().emit_event(WelcomePoolExhausted {
+                    remaining: *self.welcome_pool,
+                });
+            }
+        }
+    }
+
+    // 手续费统计: 一次调用返回各去向的累计手续费, 以及相对某个快照的增量
+    impl Erc20 {
+        #[ink(message)]
+        pub fn fee_statistics(&self) -> FeeStatistics {
+            let total_fees_collected = *self.fees_collected;
+            let fee_collections_count = *self.fee_collections_count;
+            FeeStatistics {
+                total_fees_collected,
+                recipient_fees: *self.recipient_fees_total,
+                treasury_fees: *self.treasury_fees_total,
+                jackpot_contributions: *self.jackpot_contributions_total,
+                fee_collections_count,
+                avg_fee_per_transfer: FeeStatistics::average(
+                    total_fees_collected,
+                    fee_collections_count,
+                ),
+            }
+        }
+
+        /// 自 block 处的快照以来的统计, block 必须是 snapshot_fee_statistics 记录过的区块
+        #[ink(message)]
+        pub fn fee_statistics_since(&self, block: u32) -> Result<FeeStatistics> {
+            let snapshot = self
+                .fee_snapshots
+                .get(&block)
+                .ok_or(Error::FeeSnapshotNotFound)?;
+            Ok(self.fee_statistics().since(snapshot))
+        }
+
+        /// 任何人都可以记录当前统计的快照, 每 FEE_SNAPSHOT_INTERVAL_BLOCKS 个区块最多一次, 返回快照的区块号
+        #[ink(message)]
+        pub fn snapshot_fee_statistics(&mut self) -> Result<u32> {
+            let now = self.env().block_number();
+            if let Some(last) = *self.last_fee_snapshot_block {
+                if now < last.saturating_add(FEE_SNAPSHOT_INTERVAL_BLOCKS) {
+                    return Err(Error::FeeSnapshotTooSoon);
+                }
+            }
+            let statistics = self.fee_statistics();
+            self.fee_snapshots.insert(now, statistics);
+            *self.last_fee_snapshot_block = Some(now);
+            Ok(now)
+        }
+    }
+
+    // 哈希时间锁 (HTLC): 与其他链上的同一 hashlock 配合完成跨链原子交换
+    impl Erc20 {
+        #[ink(message)]
+        pub fn htlc(&self, id: u64) -> Option<Htlc> {
+            self.htlcs.get(&id).copied()
+        }
+
+        /// 已经领取的哈希时间锁公开的原像
+        #[ink(message)]
+        pub fn htlc_preimage(&self, id: u64) -> Option<Vec<u8>> {
+            self.htlc_preimages.get(&id).cloned()
+        }
+
+        /// 托管 value 代币, 锁定到 hashlock 和 timelock
+        #[ink(message)]
+        pub fn htlc_lock(
+            &mut self,
+            recipient: AccountId,
+            value: Balance,
+            hashlock: Hash,
+            timelock: Timestamp,
+        ) -> Result<u64> {
+            if timelock <= self.env().block_timestamp() {
+                return Err(Error::InvalidTimelock);
+            }
+            let sender = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(sender, contract, value)?;
+            self.escrow_in(PoolId::Htlcs, value);
+
+            let id = *self.next_htlc_id;
+            *self.next_htlc_id += 1;
+            self.htlcs.insert(
+                id,
+                Htlc {
+                    sender,
+                    recipient,
+                    value,
+                    hashlock,
+                    timelock,
+                    status: HtlcStatus::Locked,
+                },
+            );
+            self.env().emit_event(HtlcLocked {
+                id,
+                hashlock,
+                sender,
+                recipient,
+                value,
+                timelock,
+            });
+            Ok(id)
+        }
+
+        /// recipient 在时间锁之前出示原像领取托管的代币, 原像随后公开
+        #[ink(message)]
+        pub fn htlc_claim(&mut self, id: u64, preimage: Vec<u8>) -> Result<()> {
+            let mut htlc = self.locked_htlc(id)?;
+            if self.env().caller() != htlc.recipient {
+                return Err(Error::NotHtlcRecipient);
+            }
+            if self.env().block_timestamp() >= htlc.timelock {
+                return Err(Error::HtlcExpired);
+            }
+            if preimage.len() > MAX_HTLC_PREIMAGE_LEN
+                || Hash::from(self.env().hash_bytes::<Blake2x256>(&preimage)) != htlc.hashlock
+            {
+                return Err(Error::WrongPreimage);
+            }
+            let contract = self.env().account_id();
+            self.inner_transfer(contract, htlc.recipient, htlc.value)?;
+            self.escrow_out(PoolId::Htlcs, htlc.value);
+
+            htlc.status = HtlcStatus::Claimed;
+            self.htlcs.insert(id, htlc);
+            self.htlc_preimages.insert(id, preimage.clone());
+            self.env().emit_event(HtlcClaimed {
+                id,
+                hashlock: htlc.hashlock,
+                preimage,
+            });
+            Ok(())
+        }
+
+        /// 到达时间锁后任何人都可以把代币退回 sender
+        #[ink(message)]
+        pub fn htlc_refund(&mut self, id: u64) -> Result<()> {
+            let mut htlc = self.locked_htlc(id)?;
+            if self.env().block_timestamp() < htlc.timelock {
+                return Err(Error::HtlcNotExpired);
+            }
+            let contract = self.env().account_id();
+            self.inner_refund(contract, htlc.sender, htlc.value)?;
+            self.escrow_out(PoolId::Htlcs, htlc.value);
+
+            htlc.status = HtlcStatus::Refunded;
+            self.htlcs.insert(id, htlc);
+            self.env().emit_event(HtlcRefunded {
+                id,
+                hashlock: htlc.hashlock,
+            });
+            Ok(())
+        }
+
+        fn locked_htlc(&self, id: u64) -> Result<Htlc> {
+            let htlc = self.htlc(id).ok_or(Error::HtlcNotFound)?;
+            if htlc.status != HtlcStatus::Locked {
+                return Err(Error::HtlcAlreadySettled);
+            }
+            Ok(htlc)
+        }
+    }
+
+    // 持有者抽奖: owner 先承诺一个秘密, 若干区块后公开秘密开奖, 中奖概率与余额成正比.
+    // 随机数来自秘密和开奖时的区块号, 时间戳, owner 可以通过选择开奖时机影响结果,
+    // 只适合奖品不值得操纵的营销活动
+    impl Erc20 {
+        /// 私有余额模式下只有 owner 能看到开奖进度中累计的余额, 其他人看到的 cumulative 为 0
+        #[ink(message)]
+        pub fn giveaway(&self) -> Option<Giveaway> {
+            let mut giveaway = (*self.giveaway)?;
+            if *self.private_balances && self.env().caller() != *self.owner {
+                giveaway.cumulative = 0;
+            }
+            Some(giveaway)
+        }
+
+        /// 托管奖品并承诺 commit = Blake2x256(secret)
+        #[ink(message)]
+        pub fn start_giveaway(&mut self, prize: Balance, commit: Hash) -> Result<()> {
+            self.ensure_owner()?;
+            if self.giveaway.is_some() {
+                return Err(Error::GiveawayActive);
+            }
+            let owner = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(owner, contract, prize)?;
+            self.escrow_in(PoolId::Giveaway, prize);
+            *self.giveaway = Some(Giveaway {
+                prize,
+                commit,
+                start_block: self.env().block_number(),
+                target: None,
+                cursor: 0,
+                cumulative: 0,
+            });
+            self.env().emit_event(GiveawayStarted { commit, prize });
+            Ok(())
+        }
+
+        /// 公开秘密开奖, 每次最多检查 max_holders 个持有者. 还没找到中奖者时返回 None,
+        /// 再次调用从上次的位置继续, 秘密公开后任何人都可以继续. 合约账户不参与抽奖.
+        /// 从第一次返回 None 到开出中奖者之间所有转账, 增发和销毁都会失败 (GiveawayDrawInProgress),
+        /// 否则可以在两页之间把余额挪到还没走到的持有者上重复计权
+        #[ink(message)]
+        pub fn draw_giveaway(
+            &mut self,
+            secret: Vec<u8>,
+            max_holders: u32,
+        ) -> Result<Option<AccountId>> {
+            let mut giveaway = (*self.giveaway).ok_or(Error::NoGiveaway)?;
+            let now = self.env().block_number();
+            if now
+                < giveaway
+                    .start_block
+                    .saturating_add(GIVEAWAY_MIN_DELAY_BLOCKS)
+            {
+                return Err(Error::GiveawayTooEarly);
+            }
+            if Hash::from(self.env().hash_bytes::<Blake2x256>(&secret)) != giveaway.commit {
+                return Err(Error::InvalidGiveawaySecret);
+            }
+            let contract = self.env().account_id();
+            let target = match giveaway.target {
+                Some(target) => target,
+                None => {
+                    let total_weight = self
+                        .total_supply()
+                        .saturating_sub(self.stored_balance(contract));
+                    if total_weight == 0 {
+                        return Err(Error::NoEligibleHolders);
+                    }
+                    self.giveaway_seed(&secret) % total_weight
+                }
+            };
+
+            let mut checked = 0;
+            let mut winner = None;
+            while checked < max_holders {
+                // 余额冻结时走完索引的累计值恰好是 total_weight, 一定能开出中奖者
+                if giveaway.cursor >= *self.holder_count {
+                    self.reset_giveaway_draw(giveaway);
+                    return Err(Error::NoEligibleHolders);
+                }
+                let account = self
+                    .holder_index
+                    .get(&giveaway.cursor)
+                    .copied()
+                    .unwrap_or(contract);
+                giveaway.cursor += 1;
+                checked += 1;
+                if account == contract {
+                    continue;
+                }
+                giveaway.cumulative = giveaway
+                    .cumulative
+                    .saturating_add(self.stored_balance(account));
+                if giveaway.cumulative > target {
+                    winner = Some(account);
+                    break;
+                }
+            }
+
+            let winner = match winner {
+                Some(winner) => winner,
+                None => {
+                    giveaway.target = Some(target);
+                    *self.giveaway = Some(giveaway);
+                    self.env().emit_event(GiveawayProgress {
+                        cursor: giveaway.cursor,
+                        cumulative: giveaway.cumulative,
+                    });
+                    return Ok(None);
+                }
+            };
+            // 先结束开奖解除冻结才能支付奖品; 支付失败时回到开奖前的状态, 可以重新开奖
+            *self.giveaway = None;
+            if let Err(error) = self.inner_transfer(contract, winner, giveaway.prize) {
+                self.reset_giveaway_draw(giveaway);
+                return Err(error);
+            }
+            self.escrow_out(PoolId::Giveaway, giveaway.prize);
+            self.env().emit_event(GiveawayWinner {
+                winner,
+                prize: giveaway.prize,
+                target,
+            });
+            Ok(Some(winner))
+        }
+
+        fn reset_giveaway_draw(&mut self, giveaway: Giveaway) {
+            *self.giveaway = Some(Giveaway {
+                target: None,
+                cursor: 0,
+                cumulative: 0,
+                ..giveaway
+            });
+        }
+
+        // 分页开奖期间任何余额都不能变化, 包括奖品以外的托管支付
+        fn ensure_no_draw_in_progress(&self) -> Result<()> {
+            match *self.giveaway {
+                Some(Giveaway {
+                    target: Some(_), ..
+                }) => Err(Error::GiveawayDrawInProgress),
+                _ => Ok(()),
+            }
+        }
+
+        /// Blake2x256(secret, 区块号, 时间戳) 的前 16 字节
+        fn giveaway_seed(&self, secret: &[u8]) -> Balance {
+            let hash = self.env().hash_encoded::<Blake2x256, _>(&(
+                secret,
+                self.env().block_number(),
+                self.env().block_timestamp(),
+            ));
+            let mut head = [0u8; 16];
+            head.copy_from_slice(&hash[..16]);
+            u128::from_le_bytes(head)
+        }
+    }
+
+    // 合规证明: attester 为账户签发有期限的证明, 开启证明模式后只有持证账户之间可以转账
+    impl Erc20 {
+        #[ink(message)]
+        pub fn attester(&self) -> Option<AccountId> {
+            *self.attester
+        }
+
+        #[ink(message)]
+        pub fn set_attester(&mut self, attester: Option<AccountId>) -> Result<()> {
+            self.ensure_owner()?;
+            *self.attester = attester;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn attestation_mode(&self) -> bool {
+            self.policy.require_attestation
+        }
+
+        #[ink(message)]
+        pub fn set_attestation_mode(&mut self, enabled: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.policy.require_attestation = enabled;
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn attestation(&self, account: AccountId) -> Option<ComplianceAttestation> {
+            self.attestations.get(&account).copied()
+        }
+
+        /// 账户有证明且当前区块早于 expiry_block
+        #[ink(message)]
+        pub fn is_attested(&self, account: AccountId) -> bool {
+            self.attestation(account).map_or(false, |attestation| {
+                self.env().block_number() < attestation.expiry_block
+            })
+        }
+
+        /// 签发或替换 subject 的证明
+        #[ink(message)]
+        pub fn issue_attestation(
+            &mut self,
+            subject: AccountId,
+            attestation_hash: Hash,
+            expiry_block: u32,
+        ) -> Result<()> {
+            let attester = self.ensure_attester()?;
+            let now = self.env().block_number();
+            if expiry_block <= now {
+                return Err(Error::InvalidAttestationExpiry);
+            }
+            self.attestations.insert(
+                subject,
+                ComplianceAttestation {
+                    attestation_hash,
+                    attester,
+                    attestation_block: now,
+                    expiry_block,
+                },
+            );
+            self.env().emit_event(AttestationIssued {
+                subject,
+                hash: attestation_hash,
+                expiry: expiry_block,
+            });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn revoke_attestation(&mut self, subject: AccountId) -> Result<()> {
+            self.ensure_attester()?;
+            self.attestations
+                .take(&subject)
+                .ok_or(Error::AttestationNotFound)?;
+            self.env().emit_event(AttestationRevoked { subject });
+            Ok(())
+        }
+
+        fn ensure_attester(&self) -> Result<AccountId> {
+            let caller = self.env().caller();
+            if *self.attester != Some(caller) {
+                return Err(Error::NotAttester);
+            }
+            Ok(caller)
+        }
+    }
+
+    // 账户是否出现过: balance_of 对从未出现和余额为 0 的账户都返回 0, 这里把两者区分开
+    impl Erc20 {
+        /// 账户有余额记录, 或者曾经持有过代币. 余额归零后仍为 true
+        #[ink(message)]
+        pub fn has_account(&self, who: AccountId) -> bool {
+            self.balances.contains_key(&who) || self.has_ever_held(who)
+        }
+
+        /// 开启 FEATURE_EVER_HELD 期间余额曾经大于 0
+        #[ink(message)]
+        pub fn has_ever_held(&self, who: AccountId) -> bool {
+            self.ever_held.contains_key(&who)
+        }
+
+        /// 曾经持有过代币的账户数, 只增不减
+        #[ink(message)]
+        pub fn accounts_seen_count(&self) -> u64 {
+            *self.accounts_seen_count
+        }
+
+        // 只在第一次写入余额记录时调用. 余额记录只为持有过代币的账户创建, 也不会删除,
+        // 所以此时账户一定不在 ever_held 中, 不需要再查一次
+        fn note_first_balance(&mut self, account: AccountId) {
+            if !self.is_feature_enabled(FEATURE_EVER_HELD) {
+                return;
+            }
+            crate::metering::note_aux_write();
+            self.ever_held.insert(account, ());
+            crate::metering::note_aux_write();
+            *self.accounts_seen_count += 1;
+        }
+    }
+
+    // 回收市场: 挂单人押入代币开出回收额度, 持有人销毁代币换取另一个合约的积分
+    impl Erc20 {
+        #[ink(message)]
+        pub fn recycling_listing(&self, id: u64) -> Option<RecyclingListing> {
+            self.recycling_listings.get(&id).copied()
+        }
+
+        /// 积分代币每销毁 1 个代币最多给多少积分, 不在白名单中时为 None
+        #[ink(message)]
+        pub fn credit_token_rate(&self, credit_token: AccountId) -> Option<Balance> {
+            self.credit_token_rates.get(&credit_token).copied()
+        }
+
+        /// 把积分代币加入白名单或调整上限, max_credit_per_burned 为 0 时移出白名单.
+        /// 本合约可能拥有多个代币的 mint 权限, 只有这里登记的代币才会被调用 mint
+        #[ink(message)]
+        pub fn set_credit_token_rate(
+            &mut self,
+            credit_token: AccountId,
+            max_credit_per_burned: Balance,
+        ) -> Result<()> {
+            self.ensure_owner()?;
+            if max_credit_per_burned == 0 {
+                self.credit_token_rates.take(&credit_token);
+            } else {
+                self.credit_token_rates
+                    .insert(credit_token, max_credit_per_burned);
+            }
+            Ok(())
+        }
+
+        /// 押入 amount 代币开出回收额度, 积分代币和比例要在 owner 的白名单范围内
+        #[ink(message)]
+        pub fn create_recycling_listing(
+            &mut self,
+            amount: Balance,
+            credit_token: AccountId,
+            credit_per_burned: Balance,
+        ) -> Result<u64> {
+            if amount == 0 || credit_per_burned == 0 {
+                return Err(Error::InvalidRecyclingListing);
+            }
+            self.ensure_credit_rate_allowed(credit_token, credit_per_burned)?;
+            let lister = self.env().caller();
+            let contract = self.env().account_id();
+            self.inner_transfer(lister, contract, amount)?;
+            self.escrow_in(PoolId::Recycling, amount);
+
+            let id = *self.next_recycling_listing_id;
+            *self.next_recycling_listing_id += 1;
+            self.recycling_listings.insert(
+                id,
+                RecyclingListing {
+                    id,
+                    lister,
+                    amount,
+                    credit_token,
+                    credit_per_burned,
+                    filled: 0,
+                },
+            );
+            self.env().emit_event(RecyclingListingCreated {
+                id,
+                lister,
+                amount,
+                credit_token,
+                credit_per_burned,
+            });
+            Ok(id)
+        }
+
+        /// 销毁调用者的 amount 代币, 由积分代币为调用者增发 amount * credit_per_burned 积分
+        #[ink(message)]
+        pub fn fill_recycling_listing(&mut self, listing_id: u64, amount: Balance) -> Result<()> {
+            let mut listing = self
+                .recycling_listing(listing_id)
+                .ok_or(Error::RecyclingListingNotFound)?;
+            if amount > listing.amount - listing.filled {
+                return Err(Error::RecyclingAmountExceeded);
+            }
+            // 挂单后 owner 可能下调或撤销了白名单
+            self.ensure_credit_rate_allowed(listing.credit_token, listing.credit_per_burned)?;
+            let credits = amount
+                .checked_mul(listing.credit_per_burned)
+                .ok_or(Error::Overflow)?;
+            let filler = self.env().caller();
+            self.inner_burn(filler, amount)?;
+            listing.filled += amount;
+            self.recycling_listings.insert(listing_id, listing);
+            self.env().emit_event(RecyclingListingFilled {
+                id: listing_id,
+                filler,
+                amount,
+                credits,
+            });
+
+            let input = scale::Encode::encode(&(filler, credits));
+            self.do_call(
+                listing.credit_token,
+                PSP22_MINT_SELECTOR,
+                &input,
+                CREDIT_MINT_GAS_LIMIT,
+            )?;
+            Ok(())
+        }
+
+        /// 挂单人撤单并取回全部押金, 已完成的回收不受影响
+        #[ink(message)]
+        pub fn cancel_recycling_listing(&mut self, id: u64) -> Result<()> {
+            let listing = self
+                .recycling_listing(id)
+                .ok_or(Error::RecyclingListingNotFound)?;
+            if self.env().caller() != listing.lister {
+                return Err(Error::NotRecyclingLister);
+            }
+            let contract = self.env().account_id();
+            self.inner_refund(contract, listing.lister, listing.amount)?;
+            self.escrow_out(PoolId::Recycling, listing.amount);
+            self.recycling_listings.take(&id);
+            self.env().emit_event(RecyclingListingCancelled {
+                id,
+                refunded: listing.amount,
+            });
+            Ok(())
+        }
+
+        fn ensure_credit_rate_allowed(
+            &self,
+            credit_token: AccountId,
+            credit_per_burned: Balance,
+        ) -> Result<()> {
+            match self.credit_token_rate(credit_token) {
+                Some(max) if credit_per_burned <= max => Ok(()),
+                _ => Err(Error::CreditTokenNotWhitelisted),
+            }
+        }
+    }
+
+    // 自动分红: 协议手续费攒到阈值后立即按质押权重分给质押者
+    impl Erc20 {
+        #[ink(message)]
+        pub fn auto_distribute_threshold(&self) -> Balance {
+            *self.auto_distribute_threshold
+        }
+
+        #[ink(message)]
+        pub fn pending_dividends(&self) -> Balance {
+            *self.pending_dividends
+        }
+
+        /// 距离下一次自动分红还差多少协议手续费, 关闭时返回 0
+        #[ink(message)]
+        pub fn next_distribution_in(&self) -> Balance {
+            self.auto_distribute_threshold
+                .saturating_sub(*self.pending_dividends)
+        }
+
+        #[ink(message)]
+        pub fn dividend_distribution(&self, snapshot_id: u32) -> Option<DividendDistribution> {
+            self.dividend_distributions.get(&snapshot_id).copied()
+        }
+
+        /// amount 为 0 时关闭自动分红, 尚未分配的部分转回金库.
+        /// 新阈值不超过已累计的数量时立即分配
+        #[ink(message)]
+        pub fn set_auto_distribute_threshold(&mut self, amount: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            if amount == 0 {
+                let pending = *self.pending_dividends;
+                *self.treasury_balance = self
+                    .treasury_balance
+                    .checked_add(pending)
+                    .ok_or(Error::Overflow)?;
+                *self.pending_dividends = 0;
+                self.escrow_out(PoolId::PendingDividends, pending);
+                self.escrow_in(PoolId::Treasury, pending);
+                *self.auto_distribute_threshold = 0;
+                return Ok(());
+            }
+            self.ensure_feature(FEATURE_STAKING)?;
+            *self.auto_distribute_threshold = amount;
+            if *self.pending_dividends >= amount {
+                self.distribute_dividends()?;
+            }
+            Ok(())
+        }
+
+        // 记录一次分红快照, 再交给质押奖励的累计器按当前权重分配
+        fn distribute_dividends(&mut self) -> Result<()> {
+            let total_amount = *self.pending_dividends;
+            let snapshot_id = *self.next_dividend_snapshot_id;
+            self.add_rewards(total_amount)?;
+            self.dividend_distributions.insert(
+                snapshot_id,
+                DividendDistribution {
+                    block: self.env().block_number(),
+                    total_amount,
+                    total_weight: self.total_reward_weight(),
+                },
+            );
+            *self.next_dividend_snapshot_id += 1;
+            *self.pending_dividends = 0;
+            self.escrow_out(PoolId::PendingDividends, total_amount);
+            self.env().emit_event(AutoDistributionTriggered {
+                snapshot_id,
+                total_amount,
+            });
+            Ok(())
+        }
+    }
+
+    // 策略试算: owner 修改手续费或转账限制之前, 看一组样本转账会受到什么影响
+    impl Erc20 {
+        /// 对每个 (from, to, value) 分别按当前配置和应用 change 后的配置试算 from 调用 transfer 的结果,
+        /// 不写存储. 只检查转给 to 的这一笔, 不检查手续费划转; change 本身不合法时候选结果都是对应的错误.
+        /// 私有余额模式下调用者无权查看 from 或 to 时两个结果都是 NotAuthorizedToView
+        #[ink(message)]
+        pub fn simulate_policy(
+            &self,
+            change: ParamChange,
+            samples: Vec<(AccountId, AccountId, Balance)>,
+        ) -> Vec<SimResult> {
+            let current = self.policy_config();
+            let mut policy = (*self.policy).clone();
+            let mut fee_strategy = (*self.fee_strategy).clone();
+            let applied = self.apply_param_change(change, &mut policy, &mut fee_strategy);
+            let candidate = PolicyConfig {
+                policy: &policy,
+                fee_strategy: &fee_strategy,
+                ..current
+            };
+            samples
+                .into_iter()
+                .map(|(from, to, value)| {
+                    if !self.may_view(from) || !self.may_view(to) {
+                        return SimResult {
+                            current: Err(Error::NotAuthorizedToView),
+                            candidate: Err(Error::NotAuthorizedToView),
+                        };
+                    }
+                    SimResult {
+                        current: self.evaluate_transfer(
+                            &current,
+                            from,
+                            to,
+                            value,
+                            TransferOrigin::User(value),
+                        ),
+                        candidate: match &applied {
+                            Ok(()) => self.evaluate_transfer(
+                                &candidate,
+                                from,
+                                to,
+                                value,
+                                TransferOrigin::User(value),
+                            ),
+                            Err(error) => Err(error.clone()),
+                        },
+                    }
+                })
+                .collect()
+        }
+
+        // 与对应 set_* 消息的校验一致, 不检查调用者
+        fn apply_param_change(
+            &self,
+            change: ParamChange,
+            policy: &mut TransferPolicy,
+            fee_strategy: &mut FeeStrategy,
+        ) -> Result<()> {
+            match change {
+                ParamChange::MinTransfer(minimum) => policy.min_transfer = minimum,
+                ParamChange::MintTransferDelay(blocks) => {
+                    policy.mint_transfer_delay_blocks = blocks
+                }
+                ParamChange::TierCap(tier, cap) => policy.set_tier_cap(tier, cap),
+                ParamChange::RequireAttestation(enabled) => policy.require_attestation = enabled,
+                ParamChange::FeeStrategy(strategy) => {
+                    self.ensure_feature(FEATURE_FEES)?;
+                    strategy.validate()?;
+                    *fee_strategy = strategy;
+                }
+            }
+            Ok(())
+        }
+
+        /// 按 charge_transfer 和 write_transfer 的顺序检查转给 to 的一笔, 返回手续费
+        fn evaluate_transfer(
+            &self,
+            config: &PolicyConfig,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            origin: TransferOrigin,
+        ) -> Result<Balance> {
+            let (fee, _, net) = self.transfer_amounts(config, from, value)?;
+            let from_balance = self.stored_balance(from);
+            if net < value && from_balance < value {
+                return Err(Error::InsufficientBalance);
+            }
+            let to = self.forwarding_target(to);
+            config
+                .policy
+                .check(&self.transfer_ctx(from, to, net, origin))?;
+            if from_balance < net {
+                return Err(Error::InsufficientBalance);
+            }
+            if from != to {
+                self.stored_balance(to)
+                    .checked_add(net)
+                    .ok_or(Error::Overflow)?;
+            }
+            Ok(fee)
+        }
+    }
+
+    // 合规模式: 每笔转账都要附带合规签名者对 (from, to, value, nonce, deadline) 的签名.
+    // 检查在 charge_transfer 中统一进行, 只有 transfer_with_compliance 和 transfer_from_with_compliance
+    // 验证签名后放行对应的那一笔, 其余用户入口 (批量, 幂等, UserOp, 签名转账等) 在合规模式下都会被拒绝
+    impl Erc20 {
+        #[ink(message)]
+        pub fn compliance_signer(&self) -> Option<[u8; 33]> {
+            *self.compliance_signer
+        }
+
+        #[ink(message)]
+        pub fn compliance_required(&self) -> bool {
+            *self.compliance_required
+        }
+
+        #[ink(message)]
+        pub fn compliance_nonce(&self, from: AccountId) -> u64 {
+            self.compliance_nonces
+                .get(&from)
+                .copied()
+                .unwrap_or_default()
+        }
+
+        /// 合规签名者需要签名的哈希, 使用 from 当前的 nonce
+        #[ink(message)]
+        pub fn compliance_hash(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            deadline: u32,
+        ) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                COMPLIANCE_TYPE_TAG,
+                self.env().account_id(),
+                from,
+                to,
+                value,
+                self.compliance_nonce(from),
+                deadline,
+            )))
+        }
+
+        /// 更换签名者后, 旧签名者签出的签名全部失效
+        #[ink(message)]
+        pub fn set_compliance_signer(&mut self, signer: [u8; 33]) -> Result<()> {
+            self.ensure_owner()?;
+            *self.compliance_signer = Some(signer);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn require_compliance(&mut self, enabled: bool) -> Result<()> {
+            self.ensure_owner()?;
+            if enabled && self.compliance_signer.is_none() {
+                return Err(Error::ComplianceSignerNotSet);
+            }
+            *self.compliance_required = enabled;
+            Ok(())
+        }
+
+        /// 未开启合规模式时 compliance_sig 可以为 None, 等同于 transfer
+        #[ink(message)]
+        pub fn transfer_with_compliance(
+            &mut self,
+            to: AccountId,
+            value: Balance,
+            deadline: u32,
+            compliance_sig: Option<[u8; 65]>,
+        ) -> Result<()> {
+            let from = self.env().caller();
+            let checked = self.check_compliance(from, to, value, deadline, compliance_sig)?;
+            self.grant_compliance_clearance(checked, from, to, value);
+            let result = self.transfer_with_fee(from, to, value);
+            *self.compliance_clearance = None;
+            result?;
+            self.note_compliance_passed(checked, from, to, value);
+            Ok(())
+        }
+
+        /// 签名针对的是代币的转出账户 from, 与调用的 spender 无关
+        #[ink(message)]
+        pub fn transfer_from_with_compliance(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            deadline: u32,
+            compliance_sig: Option<[u8; 65]>,
+        ) -> Result<()> {
+            let caller = self.env().caller();
+            let checked = self.check_compliance(from, to, value, deadline, compliance_sig)?;
+            self.grant_compliance_clearance(checked, from, to, value);
+            let result = self.inner_transfer_from(caller, from, to, value);
+            *self.compliance_clearance = None;
+            result?;
+            self.note_compliance_passed(checked, from, to, value);
+            Ok(())
+        }
+
+        // 只验证不写存储, 转账成功后才消耗 nonce. 返回是否验证了签名
+        fn check_compliance(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            deadline: u32,
+            compliance_sig: Option<[u8; 65]>,
+        ) -> Result<bool> {
+            let signature = match compliance_sig {
+                Some(signature) => signature,
+                None if *self.compliance_required => return Err(Error::ComplianceSignatureMissing),
+                None => return Ok(false),
+            };
+            if self.env().block_number() > deadline {
+                return Err(Error::ComplianceSignatureExpired);
+            }
+            let signer = (*self.compliance_signer).ok_or(Error::ComplianceSignerNotSet)?;
+            let hash = self.compliance_hash(from, to, value, deadline);
+            let mut message_hash = [0u8; 32];
+            message_hash.copy_from_slice(hash.as_ref());
+            match self.env().ecdsa_recover(&signature, &message_hash) {
+                Ok(recovered) if recovered == signer => Ok(true),
+                _ => Err(Error::InvalidComplianceSignature),
+            }
+        }
+
+        // 只放行签名覆盖的这一笔, 转账过程中回调触发的其他转账仍然需要签名
+        fn grant_compliance_clearance(
+            &mut self,
+            checked: bool,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) {
+            if checked {
+                *self.compliance_clearance = Some((from, to, value));
+            }
+        }
+
+        // 所有用户发起的转账都经过这里, 合规模式下没有对应签名的一律拒绝
+        fn consume_compliance_clearance(
+            &mut self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) -> Result<()> {
+            if !*self.compliance_required {
+                return Ok(());
+            }
+            if *self.compliance_clearance != Some((from, to, value)) {
+                return Err(Error::ComplianceSignatureMissing);
+            }
+            *self.compliance_clearance = None;
+            Ok(())
+        }
+
+        fn note_compliance_passed(
+            &mut self,
+            checked: bool,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) {
+            if !checked {
+                return;
+            }
+            let nonce = self.compliance_nonce(from);
+            self.compliance_nonces.insert(from, nonce + 1);
+            self.env()
+                .emit_event(ComplianceCheckPassed { from, to, value });
+        }
+    }
+
+    // 面额调整: 账本始终以原始数值记账, 面额只是一个全局比例, 在以新面额计的读写接口上换算.
+    // 余额, 授权以及合约内部的托管, 质押等记录都不需要逐条修改, 调整瞬间全部一致.
+    // 读取时向下取整, 不足新面额最小单位的零头仍在原始余额中, 可以用原始接口转出;
+    // 写入时要求换算结果是整数, 否则拒绝
+    impl Erc20 {
+        #[ink(message)]
+        pub fn denomination(&self) -> (Balance, Balance) {
+            *self.denomination
+        }
+
+        #[ink(message)]
+        pub fn pending_redenomination(&self) -> Option<(Balance, Balance, u32)> {
+            *self.pending_redenomination
+        }
+
+        /// 排队把面额乘以 factor_num / factor_den, 例如 (1, 1000) 表示 1000 个旧单位合为 1 个新单位.
+        /// REDENOMINATION_TIMELOCK_BLOCKS 个区块后由 execute_redenomination 生效, 再次调用会覆盖并重新计时
+        #[ink(message)]
+        pub fn redenominate(&mut self, factor_num: Balance, factor_den: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            Self::compose_denomination(*self.denomination, factor_num, factor_den)?;
+            let eta = self
+                .env()
+                .block_number()
+                .saturating_add(REDENOMINATION_TIMELOCK_BLOCKS);
+            *self.pending_redenomination = Some((factor_num, factor_den, eta));
+            self.env().emit_event(RedenominationQueued {
+                factor_num,
+                factor_den,
+                eta,
+            });
+            Ok(())
+        }
+
+        /// 等待期结束后任何人都可以让排队的面额调整生效
+        #[ink(message)]
+        pub fn execute_redenomination(&mut self) -> Result<()> {
+            let (factor_num, factor_den, eta) = self
+                .pending_redenomination()
+                .ok_or(Error::NoPendingRedenomination)?;
+            if self.env().block_number() < eta {
+                return Err(Error::TimelockNotExpired);
+            }
+            *self.denomination =
+                Self::compose_denomination(*self.denomination, factor_num, factor_den)?;
+            *self.pending_redenomination = None;
+            self.env().emit_event(Redenominated {
+                factor_num,
+                factor_den,
+            });
+            Ok(())
+        }
+
+        /// 标准接口 (balance_of, transfer 等) 中原始数值的小数位数. 面额是 10 的整数次幂时折算进来,
+        /// 例如 (1, 1000) 在精度模式的小数位数上加 3, 钱包按 balance_of 和 decimals 显示的就是新面额.
+        /// 其他面额无法用小数位数表示, 返回精度模式的小数位数, 需要改用 denominated_* 接口
+        #[ink(message)]
+        pub fn decimals(&self) -> u8 {
+            let decimals = self.precision_mode().decimals();
+            let (num, den) = *self.denomination;
+            Self::exact_log10(den)
+                .and_then(|den| decimals.checked_add(den))
+                .zip(Self::exact_log10(num))
+                .and_then(|(decimals, num)| decimals.checked_sub(num))
+                .unwrap_or(decimals)
+        }
+
+        #[ink(message)]
+        pub fn denominated_total_supply(&self) -> Balance {
+            self.to_denominated(self.total_supply())
+        }
+
+        #[ink(message)]
+        pub fn denominated_balance_of(&self, account: AccountId) -> Balance {
+            self.to_denominated(self.balance_of(account))
+        }
+
+        /// 无限授权仍为 INFINITE_ALLOWANCE
+        #[ink(message)]
+        pub fn denominated_allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            match self.allowance(owner, spender) {
+                INFINITE_ALLOWANCE => INFINITE_ALLOWANCE,
+                allowance => self.to_denominated(allowance),
+            }
+        }
+
+        /// amount 以当前面额计, 与 transfer 相同
+        #[ink(message)]
+        pub fn transfer_denominated(&mut self, to: AccountId, amount: Balance) -> Result<()> {
+            let value = self.from_denominated(amount)?;
+            self.transfer(to, value)
+        }
+
+        /// amount 以当前面额计, INFINITE_ALLOWANCE 表示无限授权
+        #[ink(message)]
+        pub fn approve_denominated(&mut self, spender: AccountId, amount: Balance) -> Result<()> {
+            let value = match amount {
+                INFINITE_ALLOWANCE => INFINITE_ALLOWANCE,
+                amount => self.from_denominated(amount)?,
+            };
+            self.approve(spender, value)
+        }
+
+        fn to_denominated(&self, raw: Balance) -> Balance {
+            let (num, den) = *self.denomination;
+            // 先除后乘避免溢出, 两部分都向下取整
+            (raw / den)
+                .saturating_mul(num)
+                .saturating_add((raw % den).saturating_mul(num) / den)
+        }
+
+        fn from_denominated(&self, amount: Balance) -> Result<Balance> {
+            let (num, den) = *self.denomination;
+            let scaled = amount.checked_mul(den).ok_or(Error::Overflow)?;
+            if scaled % num != 0 {
+                return Err(Error::InexactDenomination);
+            }
+            Ok(scaled / num)
+        }
+
+        /// 在 (num, den) 上叠加 factor_num / factor_den 并约分
+        fn compose_denomination(
+            (num, den): (Balance, Balance),
+            factor_num: Balance,
+            factor_den: Balance,
+        ) -> Result<(Balance, Balance)> {
+            if factor_num == 0 || factor_den == 0 {
+                return Err(Error::InvalidDenomination);
+            }
+            let num = num.checked_mul(factor_num).ok_or(Error::Overflow)?;
+            let den = den.checked_mul(factor_den).ok_or(Error::Overflow)?;
+            let divisor = Self::gcd(num, den);
+            Ok((num / divisor, den / divisor))
+        }
+
+        /// value 恰好是 10 的 n 次幂时返回 n
+        fn exact_log10(mut value: Balance) -> Option<u8> {
+            let mut exponent = 0;
+            while value >= 10 && value % 10 == 0 {
+                value /= 10;
+                exponent += 1;
+            }
+            if value == 1 {
+                Some(exponent)
+            } else {
+                None
+            }
+        }
+
+        fn gcd(mut a: Balance, mut b: Balance) -> Balance {
+            while b != 0 {
+                let remainder = a % b;
+                a = b;
+                b = remainder;
+            }
+            a
+        }
+    }
+
+    // 离线签名转账: 交易所把用户签好的提现打包, 一次调用提交, 每笔独立成功或失败
+    impl Erc20 {
+        #[ink(message)]
+        pub fn signed_transfer_nonce(&self, from: AccountId) -> u64 {
+            self.signed_transfer_nonces
+                .get(&from)
+                .copied()
+                .unwrap_or_default()
+        }
+
+        /// from 需要签名的哈希
+        #[ink(message)]
+        pub fn signed_transfer_hash(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            relayer_fee: Balance,
+            nonce: u64,
+            deadline: u32,
+        ) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                SIGNED_TRANSFER_TYPE_TAG,
+                self.env().account_id(),
+                from,
+                to,
+                value,
+                relayer_fee,
+                nonce,
+                deadline,
+            )))
+        }
+
+        /// 按顺序执行每一笔, 返回逐笔的结果, 一笔失败不影响其他笔. nonce 只在成功时消耗,
+        /// 所以同一个账户的多笔需要按 nonce 顺序排列. 超过 MAX_BATCH_LEN 时全部不执行
+        #[ink(message)]
+        pub fn settle_signed_transfers(
+            &mut self,
+            transfers: Vec<SignedTransfer>,
+        ) -> Vec<Result<()>> {
+            if let Err(error) = Self::ensure_batch_len(transfers.len()) {
+                return vec![Err(error); transfers.len()];
+            }
+            let relayer = self.env().caller();
+            transfers
+                .iter()
+                .map(|transfer| self.settle_signed_transfer(relayer, transfer))
+                .collect()
+        }
+
+        // 写入前检查签名, nonce, 合计余额以及本金和中继费用两笔划转的转账限制.
+        // nonce 在任何划转之前消耗, 即使之后的划转意外失败, 同一个签名也不能再次提交
+        fn settle_signed_transfer(
+            &mut self,
+            relayer: AccountId,
+            transfer: &SignedTransfer,
+        ) -> Result<()> {
+            if *self.compliance_required {
+                return Err(Error::ComplianceSignatureMissing);
+            }
+            if self.env().block_number() > transfer.deadline {
+                return Err(Error::SignedTransferExpired);
+            }
+            let nonce = self.signed_transfer_nonce(transfer.from);
+            if transfer.nonce != nonce {
+                return Err(Error::InvalidNonce);
+            }
+            let hash = self.signed_transfer_hash(
+                transfer.from,
+                transfer.to,
+                transfer.value,
+                transfer.relayer_fee,
+                transfer.nonce,
+                transfer.deadline,
+            );
+            let mut message_hash = [0u8; 32];
+            message_hash.copy_from_slice(hash.as_ref());
+            if self.recover_signer(&message_hash, &transfer.signature) != Some(transfer.from) {
+                return Err(Error::InvalidTransferSignature);
+            }
+            let required = transfer
+                .value
+                .checked_add(transfer.relayer_fee)
+                .ok_or(Error::Overflow)?;
+            let balance = self.stored_balance(transfer.from);
+            if balance < required {
+                return Err(Error::InsufficientBalance);
+            }
+            let config = self.policy_config();
+            self.evaluate_transfer(
+                &config,
+                transfer.from,
+                transfer.to,
+                transfer.value,
+                TransferOrigin::User(transfer.value),
+            )?;
+            if transfer.relayer_fee > 0 {
+                let fee_recipient = self.forwarding_target(relayer);
+                config.policy.check(&self.transfer_ctx(
+                    transfer.from,
+                    fee_recipient,
+                    transfer.relayer_fee,
+                    TransferOrigin::Internal,
+                ))?;
+                self.stored_balance(fee_recipient)
+                    .checked_add(transfer.relayer_fee)
+                    .ok_or(Error::Overflow)?;
+                // 单独检查每一笔时追回锁定只扣除了各自的金额
+                if balance - required < self.clawback_locked_of(transfer.from) {
+                    return Err(Error::ClawbackLocked);
+                }
+            }
+
+            self.signed_transfer_nonces.insert(transfer.from, nonce + 1);
+            self.transfer_with_fee(transfer.from, transfer.to, transfer.value)?;
+            if transfer.relayer_fee > 0 {
+                self.inner_transfer(transfer.from, relayer, transfer.relayer_fee)?;
+            }
+            self.env().emit_event(SignedTransferExecuted {
+                from: transfer.from,
+                nonce,
+                relayer,
+                relayer_fee: transfer.relayer_fee,
+            });
+            Ok(())
+        }
+    }
+
+    // 授权回调: spender 合约注册后, 自己的授权额度变化时收到通知
+    impl Erc20 {
+        #[ink(message)]
+        pub fn is_allowance_listener(&self, account: AccountId) -> bool {
+            self.allowance_listeners.contains_key(&account)
+        }
+
+        #[ink(message)]
+        pub fn allowance_listeners_enabled(&self) -> bool {
+            *self.allowance_listeners_enabled
+        }
+
+        /// 调用者为自己注册授权回调, 需要实现 `AllowanceListener`
+        #[ink(message)]
+        pub fn register_allowance_listener(&mut self) -> Result<()> {
+            let listener = self.env().caller();
+            if self.is_allowance_listener(listener) {
+                return Err(Error::AllowanceListenerAlreadyRegistered);
+            }
+            self.allowance_listeners.insert(listener, ());
+            self.env()
+                .emit_event(AllowanceListenerRegistered { listener });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn unregister_allowance_listener(&mut self) -> Result<()> {
+            let listener = self.env().caller();
+            self.allowance_listeners
+                .take(&listener)
+                .ok_or(Error::AllowanceListenerNotRegistered)?;
+            self.env().emit_event(AllowanceListenerRemoved { listener });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_allowance_listeners_enabled(&mut self, enabled: bool) -> Result<()> {
+            self.ensure_owner()?;
+            *self.allowance_listeners_enabled = enabled;
+            Ok(())
+        }
+
+        // 尽力通知, 失败只发出 HookCallFailed, 授权照常生效
+        fn notify_allowance_listener(
+            &mut self,
+            owner: AccountId,
+            spender: AccountId,
+            old_value: Balance,
+            new_value: Balance,
+        ) {
+            if old_value == new_value
+                || !*self.allowance_listeners_enabled
+                || !self.is_allowance_listener(spender)
+            {
+                return;
+            }
+            let input = scale::Encode::encode(&(owner, old_value, new_value));
+            if let Err(Error::ExternalCall { code, .. }) = self.do_call(
+                spender,
+                ON_ALLOWANCE_CHANGED_SELECTOR,
+                &input,
+                ALLOWANCE_LISTENER_GAS_LIMIT,
+            ) {
+                self.env().emit_event(HookCallFailed {
+                    hook: spender,
+                    reason: code,
+                });
+            }
+        }
+    }
+
+    // 存储占用预估: 按存储计押金的链上, 钱包据此提示一笔转账是否会新建存储项
+    impl Erc20 {
+        /// from 向 to 转 value 时的存储写入预估, 转账会失败时返回同样的错误.
+        /// 只描述 from 到 to 这一笔, 不含手续费, 奖池和欢迎奖励带来的额外划转.
+        /// 结果取决于双方的余额, 私有余额模式下调用者需要能查看 from 和 to
+        #[ink(message)]
+        pub fn transfer_footprint(
+            &self,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+        ) -> Result<Footprint> {
+            const BALANCE_BYTES: i64 = 16;
+            // holder_index 中的 AccountId 加上 holder_positions 中的 u64, 再加 first_hold_block
+            const HOLDER_BYTES: i64 = 32 + 8 + 4;
+            const AGE_BUCKET_BYTES: i64 = 16 + 4;
+            const ACTIVITY_BYTES: i64 = 4 + 8;
+            const COMMITMENT_BYTES: i64 = 32;
+
+            self.ensure_can_view(from, None)?;
+            self.ensure_can_view(to, None)?;
+            let config = self.policy_config();
+            self.evaluate_transfer(&config, from, to, value, TransferOrigin::User(value))?;
+            let (_, _, value) = self.transfer_amounts(&config, from, value)?;
+            let to = self.forwarding_target(to);
+            let from_entry = self.balances.get(&from).copied();
+            let to_entry = self.balances.get(&to).copied();
+            let from_balance = from_entry.unwrap_or(0);
+            let to_balance = to_entry.unwrap_or(0);
+            let legs = [
+                (
+                    from,
+                    from_entry.is_some(),
+                    from_balance,
+                    from_balance - value,
+                ),
+                (
+                    to,
+                    to_entry.is_some(),
+                    to_balance,
+                    to_balance.saturating_add(value),
+                ),
+            ];
+
+            let mut footprint = Footprint::default();
+            for (account, had_entry, old_balance, new_balance) in legs.iter().copied() {
+                match BalanceWrite::of(had_entry, new_balance) {
+                    BalanceWrite::Skip => {}
+                    BalanceWrite::Create => {
+                        footprint.balance_writes += 1;
+                        footprint.bytes_delta += BALANCE_BYTES;
+                        footprint.recipient_entry_created = account == to;
+                    }
+                    BalanceWrite::Update => footprint.balance_writes += 1,
+                }
+                match HolderTransition::of(old_balance, new_balance) {
+                    HolderTransition::Joined => {
+                        // holder_index, holder_positions, holder_count 和 first_hold_block
+                        footprint.aux_writes += 4;
+                        footprint.bytes_delta += HOLDER_BYTES;
+                        if !had_entry && self.is_feature_enabled(FEATURE_EVER_HELD) {
+                            footprint.aux_writes += 2;
+                        }
+                    }
+                    HolderTransition::Left => {
+                        footprint.aux_writes += 4;
+                        footprint.bytes_delta -= HOLDER_BYTES;
+                        let last = self.holder_count().saturating_sub(1);
+                        if self.holder_positions.get(&account) != Some(&last) {
+                            // 最后一个持有者移到空出的位置
+                            footprint.aux_writes += 2;
+                        }
+                    }
+                    HolderTransition::Unchanged => {}
+                }
+                if self.is_feature_enabled(FEATURE_ACTIVITY_TRACKING) {
+                    footprint.aux_writes += 1;
+                    if self.last_activity_of(account).is_none() {
+                        footprint.bytes_delta += ACTIVITY_BYTES;
+                    }
+                }
+            }
+
+            self.vote_footprint(&legs, &mut footprint);
+            if value > 0 && self.is_feature_enabled(FEATURE_AGE_TRACKING) {
+                let consumed = self.age_consumption(from, value);
+                // 转出方每个经过的批次一次, 再加批次列表一次
+                footprint.aux_writes += consumed.len() as u32 + 1;
+                let mut to_blocks = self.age_bucket_blocks.get(&to).cloned().unwrap_or_default();
+                for (block, amount, bucket) in consumed {
+                    if amount == bucket {
+                        footprint.bytes_delta -= AGE_BUCKET_BYTES;
+                    }
+                    if amount > 0 {
+                        footprint.aux_writes += 1;
+                        if let Err(pos) = to_blocks.binary_search(&block) {
+                            to_blocks.insert(pos, block);
+                            footprint.aux_writes += 1;
+                            footprint.bytes_delta += AGE_BUCKET_BYTES;
+                            if to_blocks.len() > MAX_AGE_BUCKETS {
+                                // 收款方最早的批次并入下一批
+                                to_blocks.remove(0);
+                                footprint.aux_writes += 2;
+                                footprint.bytes_delta -= AGE_BUCKET_BYTES;
+                            }
+                        }
+                    }
+                }
+            }
+            if self.beneficiary_of(from).is_some() {
+                footprint.aux_writes += 1;
+            }
+            if self.is_feature_enabled(FEATURE_TX_COMMITMENTS) {
+                footprint.aux_writes += 1;
+                if self.get_transaction_commitments(from).len() < MAX_TRANSACTION_COMMITMENTS {
+                    footprint.bytes_delta += COMMITMENT_BYTES;
+                }
+            }
+            // transfer_count, total_volume 和 last_activity_block
+            footprint.aux_writes += 3;
+            Ok(footprint)
+        }
+
+        // 按 note_vote_change 的顺序模拟: 每笔转账先改各委托对象的票数并记检查点, 再记账户自己的检查点.
+        // 检查点读到的余额是两边都写入之后的余额
+        fn vote_footprint(
+            &self,
+            legs: &[(AccountId, bool, Balance, Balance)],
+            footprint: &mut Footprint,
+        ) {
+            let mut delegated: Vec<(AccountId, Balance)> = Vec::new();
+            let mut checkpointed: Vec<AccountId> = Vec::new();
+            for (account, _, old_balance, new_balance) in legs.iter().copied() {
+                let delegations = self.split_delegations_of(account);
+                let old_shares = Self::split_votes(old_balance, &delegations);
+                let new_shares = Self::split_votes(new_balance, &delegations);
+                for ((delegatee, removed), (_, added)) in old_shares.into_iter().zip(new_shares) {
+                    let votes = self
+                        .delegated_after(&delegated, delegatee)
+                        .saturating_sub(removed)
+                        .saturating_add(added);
+                    delegated.retain(|(other, _)| *other != delegatee);
+                    delegated.push((delegatee, votes));
+                    footprint.aux_writes += 1;
+                    self.checkpoint_footprint(
+                        delegatee,
+                        legs,
+                        &delegated,
+                        &mut checkpointed,
+                        footprint,
+                    );
+                }
+                self.checkpoint_footprint(account, legs, &delegated, &mut checkpointed, footprint);
+            }
+        }
+
+        fn delegated_after(
+            &self,
+            delegated: &[(AccountId, Balance)],
+            account: AccountId,
+        ) -> Balance {
+            delegated
+                .iter()
+                .find(|(other, _)| *other == account)
+                .map_or_else(|| self.delegated_votes_of(account), |(_, votes)| *votes)
+        }
+
+        // 与 write_vote_checkpoint 的判断一致; checkpointed 中的账户本次已经在当前区块写过检查点
+        fn checkpoint_footprint(
+            &self,
+            account: AccountId,
+            legs: &[(AccountId, bool, Balance, Balance)],
+            delegated: &[(AccountId, Balance)],
+            checkpointed: &mut Vec<AccountId>,
+            footprint: &mut Footprint,
+        ) {
+            const CHECKPOINT_BYTES: i64 = 4 + 16;
+            let own_votes = if self.split_delegations.contains_key(&account) {
+                0
+            } else {
+                legs.iter()
+                    .rev()
+                    .find(|(leg, ..)| *leg == account)
+                    .map_or_else(
+                        || self.stored_balance(account),
+                        |(.., new_balance)| *new_balance,
+                    )
+            };
+            let votes = own_votes.saturating_add(self.delegated_after(delegated, account));
+            let block = self.env().block_number();
+            let bytes = if checkpointed.contains(&account) {
+                Some(0)
+            } else {
+                match self
+                    .vote_checkpoints
+                    .get(&account)
+                    .and_then(|checkpoints| checkpoints.last())
+                {
+                    Some((at, _)) if *at == block => Some(0),
+                    Some((_, last_votes)) if *last_votes == votes => None,
+                    None if votes == 0 => None,
+                    _ => Some(CHECKPOINT_BYTES),
+                }
+            };
+            if let Some(bytes) = bytes {
+                footprint.aux_writes += 1;
+                footprint.bytes_delta += bytes;
+                checkpointed.push(account);
+            }
+        }
+    }
+
+    // spender 迁移: 协议升级到新合约地址时, 凭各 owner 的签名把授给旧地址的授权转给新地址
+    impl Erc20 {
+        /// owner 同意把授给 old_spender 的授权转给 new_spender 时签名的哈希, 与 permit 共用 nonce
+        #[ink(message)]
+        pub fn reassign_allowance_hash(
+            &self,
+            owner: AccountId,
+            old_spender: AccountId,
+            new_spender: AccountId,
+        ) -> Hash {
+            Hash::from(self.env().hash_encoded::<Blake2x256, _>(&(
+                ALLOWANCE_REASSIGN_TYPE_TAG,
+                self.env().account_id(),
+                owner,
+                old_spender,
+                new_spender,
+                self.permit_nonce(owner),
+            )))
+        }
+
+        /// 任何人都可以提交. 每个 owner 独立处理, 返回逐个的结果, nonce 只在成功时消耗.
+        /// 额度累加到 new_spender 已有的授权上, 种类沿用旧授权
+        #[ink(message)]
+        pub fn reassign_allowance(
+            &mut self,
+            old_spender: AccountId,
+            new_spender: AccountId,
+            owners: Vec<AccountId>,
+            consent_sigs: Vec<[u8; 65]>,
+        ) -> Result<Vec<Result<()>>> {
+            if owners.len() != consent_sigs.len() {
+                return Err(Error::LengthMismatch);
+            }
+            Self::ensure_batch_len(owners.len())?;
+            if old_spender == new_spender {
+                return Err(Error::InvalidMigration);
+            }
+            Ok(owners
+                .iter()
+                .zip(consent_sigs.iter())
+                .map(|(owner, signature)| {
+                    self.reassign_owner_allowance(*owner, old_spender, new_spender, signature)
+                })
+                .collect())
+        }
+
+        fn reassign_owner_allowance(
+            &mut self,
+            owner: AccountId,
+            old_spender: AccountId,
+            new_spender: AccountId,
+            signature: &[u8; 65],
+        ) -> Result<()> {
+            let hash = self.reassign_allowance_hash(owner, old_spender, new_spender);
+            let mut message_hash = [0u8; 32];
+            message_hash.copy_from_slice(hash.as_ref());
+            if self.recover_signer(&message_hash, signature) != Some(owner) {
+                return Err(Error::InvalidPermitSignature);
+            }
+            let value = self.stored_allowance(owner, old_spender);
+            if value == 0 {
+                return Err(Error::InsufficientAllowance);
+            }
+            // 先检查暂停, 避免旧授权清零后新授权写入失败
+            self.ensure_not_paused(PAUSE_APPROVE)?;
+            let origin = match self.allowance_kind(owner, old_spender) {
+                AllowanceKind::Standard => ApprovalOrigin::Permit,
+                AllowanceKind::SingleUse => ApprovalOrigin::PermitSingleUse,
+            };
+            let moved = self
+                .stored_allowance(owner, new_spender)
+                .saturating_add(value);
+            let nonce = self.permit_nonce(owner);
+            self.permit_nonces.insert(owner, nonce + 1);
+            self.inner_approve(owner, old_spender, 0, ApprovalOrigin::Permit)?;
+            self.inner_approve(owner, new_spender, moved, origin)
+        }
+    }
+
+    // 托管账本: 升级前核对合约账户余额与各功能托管的代币是否一致
+    impl Erc20 {
+        #[ink(message)]
+        pub fn obligation(&self, pool: PoolId) -> Balance {
+            self.obligations.get(&pool).copied().unwrap_or_default()
+        }
+
+        /// 托管余额不为 0 的池, 按 PoolId 顺序
+        #[ink(message)]
+        pub fn obligations_summary(&self) -> Vec<(PoolId, Balance)> {
+            PoolId::ALL
+                .iter()
+                .map(|pool| (*pool, self.obligation(*pool)))
+                .filter(|(_, amount)| *amount > 0)
+                .collect()
+        }
+
+        /// 合约账户余额中不属于任何托管池的部分: 弹性供应增发的储备, 以及直接转入合约账户的代币.
+        /// 手续费, 赏金池, 未领取的奖励等欠下的代币都记在各自的池中. 托管总额超过合约账户余额时返回 EscrowDeficit,
+        /// 而不是当作 0 掩盖缺口
+        #[ink(message)]
+        pub fn unallocated_contract_balance(&self) -> Result<Balance> {
+            let escrowed = PoolId::ALL
+                .iter()
+                .try_fold(0, |total: Balance, pool| {
+                    total.checked_add(self.obligation(*pool))
+                })
+                .ok_or(Error::EscrowDeficit)?;
+            self.stored_balance(self.env().account_id())
+                .checked_sub(escrowed)
+                .ok_or(Error::EscrowDeficit)
+        }
+
+        // 托管转入合约账户成功后调用, 奖励转为质押等不经过转账的托管也在这里记账
+        fn escrow_in(&mut self, pool: PoolId, amount: Balance) {
+            let total = self.obligation(pool).saturating_add(amount);
+            self.obligations.insert(pool, total);
+        }
+
+        // 托管从合约账户转出成功后调用
+        fn escrow_out(&mut self, pool: PoolId, amount: Balance) {
+            let total = self.obligation(pool).saturating_sub(amount);
+            if total == 0 {
+                self.obligations.take(&pool);
+            } else {
+                self.obligations.insert(pool, total);
+            }
+        }
+    }
+
+    // 私有余额: 联盟链部署中成员之间不能互相查看余额, 只影响查询, 不影响内部校验.
+    // 余额, 投票权, 持有者索引和从它们推算出的查询 (转账试算, 存储预估, 抽奖进度) 都按 ensure_can_view 限制.
+    // 这只是查询接口上的限制: 合约存储和 Transfer 等事件中的数额仍然对链上所有人公开,
+    // 需要真正保密的部署不能依赖这个模式
+    impl Erc20 {
+        #[ink(message)]
+        pub fn private_balances(&self) -> bool {
+            *self.private_balances
+        }
+
+        #[ink(message)]
+        pub fn set_private_balances(&mut self, enabled: bool) -> Result<()> {
+            self.ensure_owner()?;
+            *self.private_balances = enabled;
+            Ok(())
+        }
+
+        /// viewer 能否查看 account 的余额, 不考虑模式是否开启
+        #[ink(message)]
+        pub fn can_view(&self, account: AccountId, viewer: AccountId) -> bool {
+            viewer == account
+                || viewer == *self.owner
+                || self.view_grants.contains_key(&(account, viewer))
+        }
+
+        /// 允许 viewer 查看调用者的余额和调用者授出的授权
+        #[ink(message)]
+        pub fn grant_view(&mut self, viewer: AccountId) -> Result<()> {
+            let account = self.env().caller();
+            self.view_grants.insert((account, viewer), ());
+            self.env().emit_event(ViewGranted { account, viewer });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn revoke_view(&mut self, viewer: AccountId) -> Result<()> {
+            let account = self.env().caller();
+            self.view_grants
+                .take(&(account, viewer))
+                .ok_or(Error::ViewNotGranted)?;
+            self.env().emit_event(ViewRevoked { account, viewer });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn balance_of_restricted(&self, who: AccountId) -> Result<Balance> {
+            self.ensure_can_view(who, None)?;
+            Ok(self.stored_balance(who))
+        }
+
+        /// 授权的双方都可以查看, 其他人需要 owner 授予的查看权限
+        #[ink(message)]
+        pub fn allowance_restricted(
+            &self,
+            owner: AccountId,
+            spender: AccountId,
+        ) -> Result<Balance> {
+            self.ensure_can_view(owner, Some(spender))?;
+            Ok(self.stored_allowance(owner, spender))
+        }
+
+        fn ensure_can_view(
+            &self,
+            account: AccountId,
+            counterparty: Option<AccountId>,
+        ) -> Result<()> {
+            let viewer = self.env().caller();
+            if !*self.private_balances
+                || self.can_view(account, viewer)
+                || counterparty == Some(viewer)
+            {
+                return Ok(());
+            }
+            Err(Error::NotAuthorizedToView)
+        }
+
+        // 返回 Balance 的查询在无权查看时返回 0, 与 balance_of 一致
+        fn may_view(&self, account: AccountId) -> bool {
+            self.ensure_can_view(account, None).is_ok()
+        }
+    }
+
+    // 授权自动补足: 授权低于下限时任何 keeper 都可以替 owner 补到目标额度, 赏金由 owner 支付
+    impl Erc20 {
+        /// (floor, target)
+        #[ink(message)]
+        pub fn allowance_maintenance(
+            &self,
+            owner: AccountId,
+            spender: AccountId,
+        ) -> Option<(Balance, Balance)> {
+            self.allowance_maintenance.get(&(owner, spender)).copied()
+        }
+
+        #[ink(message)]
+        pub fn maintenance_bounty(&self, owner: AccountId) -> Balance {
+            self.maintenance_bounties
+                .get(&owner)
+                .copied()
+                .unwrap_or_default()
+        }
+
+        /// 授权低于 floor 时补到 target, 再次调用覆盖原规则
+        #[ink(message)]
+        pub fn set_allowance_maintenance(
+            &mut self,
+            spender: AccountId,
+            floor: Balance,
+            target: Balance,
+        ) -> Result<()> {
+            if floor == 0 || floor > target || target == INFINITE_ALLOWANCE {
+                return Err(Error::InvalidMaintenanceRule);
+            }
+            let owner = self.env().caller();
+            self.allowance_maintenance
+                .insert((owner, spender), (floor, target));
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn remove_allowance_maintenance(&mut self, spender: AccountId) -> Result<()> {
+            let owner = self.env().caller();
+            self.allowance_maintenance
+                .take(&(owner, spender))
+                .ok_or(Error::MaintenanceRuleNotFound)?;
+            Ok(())
+        }
+
+        /// 调用者的所有规则每次补足时付给 keeper 的代币, 0 表示不付
+        #[ink(message)]
+        pub fn set_maintenance_bounty(&mut self, amount: Balance) -> Result<()> {
+            let owner = self.env().caller();
+            if amount == 0 {
+                self.maintenance_bounties.take(&owner);
+            } else {
+                self.maintenance_bounties.insert(owner, amount);
+            }
+            Ok(())
+        }
+
+        /// 任何人都可以调用. 授权不低于下限时什么都不做并返回 false.
+        /// 暂停授权期间规则失效; 赏金按普通划转从 owner 扣除, owner 无法转出时不会补足
+        #[ink(message)]
+        pub fn maintain_allowance(&mut self, owner: AccountId, spender: AccountId) -> Result<bool> {
+            let (floor, target) = self
+                .allowance_maintenance(owner, spender)
+                .ok_or(Error::MaintenanceRuleNotFound)?;
+            self.ensure_not_paused(PAUSE_APPROVE)?;
+            let old_value = self.stored_allowance(owner, spender);
+            if old_value >= floor {
+                return Ok(false);
+            }
+
+            let keeper = self.env().caller();
+            let bounty = self.maintenance_bounty(owner);
+            if bounty > 0 && keeper != owner {
+                self.inner_transfer(owner, keeper, bounty)?;
+                let mut stats = self.keeper_stats(keeper);
+                stats.earned = stats.earned.saturating_add(bounty);
+                stats.tasks += 1;
+                self.keeper_stats.insert(keeper, stats);
+            }
+            self.inner_approve(owner, spender, target, ApprovalOrigin::Increase)?;
+            self.env().emit_event(AllowanceMaintained {
+                owner,
+                spender,
+                old_value,
+                new_value: target,
+                keeper,
+                bounty,
+            });
+            Ok(true)
+        }
+    }
+
+    // 授权暂停: owner 怀疑某个协议出问题时先冻结它的授权而不撤销, 解除后额度原样恢复
+    impl Erc20 {
+        #[ink(message)]
+        pub fn is_allowance_paused(&self, owner: AccountId, spender: AccountId) -> bool {
+            self.paused_allowances.contains_key(&(owner, spender))
+        }
+
+        /// 暂停期间为 0, 否则同 allowance
+        #[ink(message)]
+        pub fn effective_allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+            if self.is_allowance_paused(owner, spender) {
+                return 0;
+            }
+            self.allowance(owner, spender)
+        }
+
+        /// 暂停调用者授给 spender 的授权, 之后 transfer_from 等花费授权的操作都会失败
+        #[ink(message)]
+        pub fn pause_allowance(&mut self, spender: AccountId) -> Result<()> {
+            let owner = self.env().caller();
+            if self.is_allowance_paused(owner, spender) {
+                return Err(Error::AllowancePaused);
+            }
+            self.paused_allowances.insert((owner, spender), ());
+            self.env().emit_event(AllowancePaused { owner, spender });
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn resume_allowance(&mut self, spender: AccountId) -> Result<()> {
+            let owner = self.env().caller();
+            self.paused_allowances
+                .take(&(owner, spender))
+                .ok_or(Error::AllowanceNotPaused)?;
+            self.env().emit_event(AllowanceResumed { owner, spender });
+            Ok(())
+        }
+
+        fn ensure_allowance_active(&self, owner: AccountId, spender: AccountId) -> Result<()> {
+            if self.is_allowance_paused(owner, spender) {
+                return Err(Error::AllowancePaused);
+            }
+            Ok(())
+        }
+    }
+
+    //测试模块, 重点参考https://paritytech.github.io/ink/ink_env/test/index.html 文档
+    //和https://paritytech.github.io/ink-docs/basics/contract-testing
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::abi;
+
+        // 将事件收敛为一个类型, 参考 https://paritytech.github.io/ink/ink_lang/reflect/trait.ContractEventBase.html
+        // ink_lang::reflect 模块是合约的静态反射, 用来检查合约编译时信息
+        type Event = <Erc20 as ::ink_lang::reflect::ContractEventBase>::Type;
+
+        use ink_lang as ink;
+        use std::{
+            cell::{Cell, RefCell},
+            rc::Rc,
+        };
+
+        fn assert_transfer_event(
+            event: &ink_env::test::EmittedEvent, // 参考https://paritytech.github.io/ink/ink_env/test/struct.EmittedEvent.html
+            expected_from: Option<AccountId>,
+            expected_to: Option<AccountId>,
+            expected_value: Balance,
+            expected_kind: TransferKind,
+        ) {
+            let decode_event = <Event as scale::Decode>::decode(&mut &event.data[..])
+                .expect("encountered invalid contract event data buffer");
+            if let Event::Transfer(Transfer {
+                from,
+                to,
+                value,
+                kind,
+            }) = decode_event
+            {
+                assert_eq!(from, expected_from, "encountered invalid transfer.from");
+                assert_eq!(to, expected_to, "encountered invalid transfer.to");
+                assert_eq!(value, expected_value, "encountered invalid transfer.value");
+                assert_eq!(
+                    TransferKind::from_u8(kind),
+                    Some(expected_kind),
+                    "encountered invalid transfer.kind"
+                );
+            } else {
+                panic!("encountered unexpected evnet kind: expect a Transfer event")
+            }
+            let expected_topics = vec![
+                abi::transfer_topic(),
+                abi::transfer_from_topic(expected_from),
+                abi::transfer_to_topic(expected_to),
+            ];
+
+            for (n, (actual_topic, expect_topic)) in
+                event.topics.iter().zip(expected_topics).enumerate()
+            {
+                let topic = actual_topic
+                    .decode::<Hash>()
+                    .expect("encountered invalid topic encoding");
+                assert_eq!(topic, expect_topic, "encountered invalid topic at {}", n);
+            }
+        }
+        #[ink::test]
+        fn new_works() {
+            let _erc20 = Erc20::new(100);
+
+            let emit_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+
+            assert_eq!(1, emit_events.len());
+
+            assert_transfer_event(
+                &emit_events[0],
+                None,
+                Some(AccountId::from([0x01; 32])),
+                100,
+                TransferKind::Mint,
+            );
+        }
+
+        #[ink::test]
+        fn total_supply_works() {
+            let erc20 = Erc20::new(100);
+
+            let emit_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_transfer_event(
+                &emit_events[0],
+                None,
+                Some(AccountId::from([0x01; 32])),
+                100,
+                TransferKind::Mint,
+            );
+
+            assert_eq!(erc20.total_supply(), 100);
+        }
+
+        #[ink::test]
+        fn balance_of_works() {
+            let erc20 = Erc20::new(100);
+
+            let emit_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_transfer_event(
+                &emit_events[0],
+                None,
+                Some(AccountId::from([0x01; 32])),
+                100,
+                TransferKind::Mint,
+            );
+
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+
+            assert_eq!(erc20.balance_of(accounts.alice), 100);
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn transfer_works() {
+            // 此处小坑, 一定要定义为mut
+            let mut erc20 = Erc20::new(100);
+
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+
+            assert_eq!(erc20.balance_of(accounts.alice), 100);
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+
+            //transfer 10 to bob
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+
+            assert_eq!(erc20.balance_of(accounts.alice), 90);
+            assert_eq!(erc20.balance_of(accounts.bob), 10);
+
+            let emit_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emit_events.len(), 2);
+            assert_transfer_event(
+                &emit_events[0],
+                None,
+                Some(AccountId::from([0x01; 32])),
+                100,
+                TransferKind::Mint,
+            );
+            assert_transfer_event(
+                &emit_events[1],
+                Some(AccountId::from([0x01; 32])),
+                Some(AccountId::from([0x02; 32])),
+                10,
+                TransferKind::Normal,
+            );
+        }
+
+        #[ink::test]
+        fn trasfer_fails_when_not_enough_balance() {
+            let mut erc20 = Erc20::new(100);
+
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>();
+
+            let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4])); // 表示4th 的call方法, 即transfer
+
+            data.push_arg(&accounts.bob);
+
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                accounts.bob,
+                callee,
+                1000000,
+                1000000,
+                data,
+            );
+
+            assert_eq!(
+                erc20.transfer(accounts.eve, 10),
+                Err(Error::InsufficientBalance)
+            );
+
+            assert_eq!(erc20.balance_of(accounts.alice), 100);
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+            assert_eq!(erc20.balance_of(accounts.eve), 0);
+
+            let emit_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emit_events.len(), 1);
+            assert_transfer_event(
+                &emit_events[0],
+                None,
+                Some(AccountId::from([0x01; 32])),
+                100,
+                TransferKind::Mint,
+            );
+        }
+
+        #[ink::test]
+        fn transfer_from_works() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.eve, 10),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(erc20.approve(accounts.bob, 10), Ok(()));
+
+            assert_eq!(ink_env::test::recorded_events().count(), 2);
+
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&accounts.bob);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                accounts.bob,
+                callee,
+                1000000,
+                1000000,
+                data,
+            );
+
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.eve, 10),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(accounts.eve), 10);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            // 额度恰好用尽, 额外发出 Approval(0) 和 AllowanceExhausted
+            assert_eq!(emitted_events.len(), 5);
+            assert_transfer_event(
+                &emitted_events[0],
+                None,
+                Some(AccountId::from([0x01; 32])),
+                100,
+                TransferKind::Mint,
+            );
+            assert_transfer_event(
+                &emitted_events[2],
+                Some(AccountId::from([0x01; 32])),
+                Some(AccountId::from([0x05; 32])),
+                10,
+                TransferKind::Normal,
+            );
+        }
+
+        #[ink::test]
+        fn allowance_must_not_change_on_failed_transfer() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+
+            let alice_balance = erc20.balance_of(accounts.alice);
+            let initial_allowance = alice_balance + 2;
+            assert_eq!(erc20.approve(accounts.bob, initial_allowance), Ok(()));
+
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&accounts.bob);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                accounts.bob,
+                callee,
+                1000000,
+                1000000,
+                data,
+            );
+
+            let emitted_events_before = ink_env::test::recorded_events();
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.eve, alice_balance + 1),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(
+                erc20.allowance(accounts.alice, accounts.bob),
+                initial_allowance
+            );
+            let emitted_events_after = ink_env::test::recorded_events();
+            assert_eq!(emitted_events_before.count(), emitted_events_after.count());
+        }
+
+        // 切换后续调用的 caller
+        fn set_caller(caller: AccountId) {
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&caller);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller, callee, 1000000, 1000000, data,
+            );
+        }
+
+        // 记录 on_balance_change 调用的奖励合约桩
+        struct StubRewardsHook {
+            calls: Rc<RefCell<Vec<(AccountId, Balance, Balance)>>>,
+            fail: bool,
+        }
+
+        impl call::CallLayer for StubRewardsHook {
+            fn call(
+                &mut self,
+                _callee: AccountId,
+                selector: [u8; 4],
+                input: &[u8],
+                _gas_limit: u64,
+            ) -> core::result::Result<Vec<u8>, ink_env::Error> {
+                assert_eq!(selector, ON_BALANCE_CHANGE_SELECTOR);
+                let args =
+                    <(AccountId, Balance, Balance) as scale::Decode>::decode(&mut &input[..])
+                        .expect("encountered invalid hook input");
+                self.calls.borrow_mut().push(args);
+                if self.fail {
+                    Err(ink_env::Error::CalleeTrapped)
+                } else {
+                    Ok(Vec::new())
+                }
+            }
+        }
+
+        fn install_rewards_hook(
+            erc20: &mut Erc20,
+            policy: HookFailurePolicy,
+            fail: bool,
+        ) -> Rc<RefCell<Vec<(AccountId, Balance, Balance)>>> {
+            let calls = Rc::new(RefCell::new(Vec::new()));
+            call::set_call_layer(StubRewardsHook {
+                calls: calls.clone(),
+                fail,
+            });
+            let hook = AccountId::from([0x10; 32]);
+            assert_eq!(erc20.set_rewards_hook(Some(hook), 50_000, policy), Ok(()));
+            calls
+        }
+
+        #[ink::test]
+        fn rewards_hook_only_owner_can_set() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.set_rewards_hook(Some(accounts.bob), 1, HookFailurePolicy::Ignore),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(erc20.rewards_hook(), None);
+        }
+
+        #[ink::test]
+        fn rewards_hook_notified_on_transfer() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let calls = install_rewards_hook(&mut erc20, HookFailurePolicy::Revert, false);
+
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(
+                *calls.borrow(),
+                vec![(accounts.alice, 100, 90), (accounts.bob, 0, 10)]
+            );
+        }
+
+        #[ink::test]
+        fn rewards_hook_failure_reverts_with_revert_policy() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let calls = install_rewards_hook(&mut erc20, HookFailurePolicy::Revert, true);
+
+            assert_eq!(
+                erc20.transfer(accounts.bob, 10),
+                Err(Error::ExternalCall {
+                    callee: AccountId::from([0x10; 32]),
+                    selector: ON_BALANCE_CHANGE_SELECTOR,
+                    code: call::CALL_ERROR_CALLEE_TRAPPED,
+                })
+            );
+            // 第一次回调失败就会中止, 不再通知后续账户
+            assert_eq!(calls.borrow().len(), 1);
+        }
+
+        #[ink::test]
+        fn rewards_hook_failure_ignored_with_ignore_policy() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let calls = install_rewards_hook(&mut erc20, HookFailurePolicy::Ignore, true);
+
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 10);
+            assert_eq!(calls.borrow().len(), 2);
+        }
+
+        #[ink::test]
+        fn rewards_hook_notified_on_mint_and_burn() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let calls = install_rewards_hook(&mut erc20, HookFailurePolicy::Revert, false);
+
+            assert_eq!(erc20.mint(accounts.bob, 50), Ok(()));
+            assert_eq!(erc20.burn(30), Ok(()));
+            assert_eq!(erc20.total_supply(), 120);
+            assert_eq!(
+                *calls.borrow(),
+                vec![(accounts.bob, 0, 50), (accounts.alice, 100, 70)]
+            );
+        }
+
+        fn voucher_hash(code: &[u8]) -> Hash {
+            let mut output =
+                <<Blake2x256 as ink_env::hash::HashOutput>::Type as Default>::default();
+            ink_env::hash_bytes::<Blake2x256>(code, &mut output);
+            Hash::from(output)
+        }
+
+        #[ink::test]
+        fn voucher_redeem_works() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+
+            assert_eq!(erc20.create_voucher(voucher_hash(b"gift-1"), 30), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 70);
+            assert_eq!(erc20.balance_of(contract), 30);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.redeem_voucher(b"gift-1".to_vec()), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 30);
+            assert_eq!(erc20.balance_of(contract), 0);
+            assert_eq!(erc20.voucher(voucher_hash(b"gift-1")), Some((30, true)));
+        }
+
+        #[ink::test]
+        fn voucher_cannot_be_redeemed_twice() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.create_voucher(voucher_hash(b"gift-1"), 30), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.redeem_voucher(b"gift-1".to_vec()), Ok(()));
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.redeem_voucher(b"gift-1".to_vec()),
+                Err(Error::VoucherAlreadyRedeemed)
+            );
+            assert_eq!(erc20.balance_of(accounts.eve), 0);
+
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.cancel_voucher(voucher_hash(b"gift-1")),
+                Err(Error::VoucherAlreadyRedeemed)
+            );
+        }
+
+        #[ink::test]
+        fn voucher_codes_do_not_collide() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_ne!(voucher_hash(b"gift-1"), voucher_hash(b"gift-2"));
+            assert_eq!(erc20.create_voucher(voucher_hash(b"gift-1"), 30), Ok(()));
+            assert_eq!(
+                erc20.create_voucher(voucher_hash(b"gift-1"), 5),
+                Err(Error::VoucherAlreadyExists)
+            );
+
+            set_caller(accounts.bob);
+            // 相近的原文, 以及直接提交哈希本身, 都不能领取
+            assert_eq!(
+                erc20.redeem_voucher(b"gift-2".to_vec()),
+                Err(Error::VoucherNotFound)
+            );
+            assert_eq!(
+                erc20.redeem_voucher(voucher_hash(b"gift-1").as_ref().to_vec()),
+                Err(Error::VoucherNotFound)
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn voucher_cancel_returns_funds() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.create_voucher(voucher_hash(b"gift-1"), 30), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.cancel_voucher(voucher_hash(b"gift-1")),
+                Err(Error::NotOwner)
+            );
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.cancel_voucher(voucher_hash(b"gift-1")), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 100);
+            assert_eq!(erc20.voucher(voucher_hash(b"gift-1")), None);
+        }
+
+        #[ink::test]
+        fn batch_transfer_works() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+
+            assert_eq!(
+                erc20.batch_transfer(
+                    vec![(accounts.bob, 10), (accounts.eve, 20)],
+                    EventMode::PerRecipient
+                ),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(accounts.alice), 70);
+            assert_eq!(erc20.balance_of(accounts.bob), 10);
+            assert_eq!(erc20.balance_of(accounts.eve), 20);
+            assert_eq!(ink_env::test::recorded_events().count(), 3);
+        }
+
+        #[ink::test]
+        fn batch_transfer_rejects_oversized_list() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+
+            let recipients = vec![(accounts.bob, 0); MAX_BATCH_LEN + 1];
+            assert_eq!(
+                erc20.batch_transfer(recipients, EventMode::PerRecipient),
+                Err(Error::BatchTooLarge)
+            );
+            assert_eq!(ink_env::test::recorded_events().count(), 1);
+        }
+
+        #[ink::test]
+        fn batch_transfer_detects_aggregate_overflow() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+
+            // 简单的线性同余生成器, 构造前缀和不溢出但总和溢出的列表
+            let mut seed: u128 = 0x2545_f491_4f6c_dd1d;
+            for round in 0..50 {
+                let len = 2 + round % 5;
+                let mut values = Vec::new();
+                let mut prefix: Balance = 0;
+                for _ in 0..len - 1 {
+                    seed = seed
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    let value = Balance::MAX / len as Balance - seed % 1000;
+                    prefix += value;
+                    values.push(value);
+                }
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                values.push(Balance::MAX - prefix + 1 + seed % 1000);
+
+                let recipients = values
+                    .into_iter()
+                    .map(|value| (accounts.bob, value))
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    erc20.batch_transfer(recipients, EventMode::PerRecipient),
+                    Err(Error::AggregateOverflow)
+                );
+            }
+
+            assert_eq!(erc20.balance_of(accounts.alice), 100);
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+            assert_eq!(ink_env::test::recorded_events().count(), 1);
+        }
+
+        fn advance_blocks(n: u32) {
+            for _ in 0..n {
+                ink_env::test::advance_block::<ink_env::DefaultEnvironment>()
+                    .expect("Cannot advance block");
+            }
+        }
+
+        #[ink::test]
+        fn token_age_follows_fifo_through_transfers() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let start = ink_env::block_number::<ink_env::DefaultEnvironment>();
+
+            advance_blocks(5);
+            assert_eq!(erc20.mint(accounts.alice, 50), Ok(()));
+
+            // bob 先拿到最早的 100, 再拿到第二批中的 20
+            assert_eq!(erc20.transfer(accounts.bob, 120), Ok(()));
+            assert_eq!(erc20.oldest_tokens_block(accounts.alice), Some(start + 5));
+            assert_eq!(erc20.oldest_tokens_block(accounts.bob), Some(start));
+            assert_eq!(
+                erc20.age_buckets.get(&(accounts.alice, start + 5)),
+                Some(&30)
+            );
+            assert_eq!(erc20.age_buckets.get(&(accounts.bob, start)), Some(&100));
+            assert_eq!(erc20.age_buckets.get(&(accounts.bob, start + 5)), Some(&20));
+
+            advance_blocks(5);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.eve, 110), Ok(()));
+            assert_eq!(erc20.oldest_tokens_block(accounts.bob), Some(start + 5));
+            assert_eq!(erc20.age_buckets.get(&(accounts.bob, start + 5)), Some(&10));
+            assert_eq!(erc20.age_buckets.get(&(accounts.eve, start)), Some(&100));
+            assert_eq!(erc20.age_buckets.get(&(accounts.eve, start + 5)), Some(&10));
+
+            // (100 * 10 + 10 * 5) / 110
+            assert_eq!(erc20.average_token_age_blocks(accounts.eve), 9);
+            assert_eq!(erc20.average_token_age_blocks(accounts.bob), 5);
+        }
+
+        #[ink::test]
+        fn token_age_partial_bucket_and_burn() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let start = ink_env::block_number::<ink_env::DefaultEnvironment>();
+
+            advance_blocks(3);
+            assert_eq!(erc20.mint(accounts.alice, 10), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 40), Ok(()));
+            assert_eq!(erc20.age_buckets.get(&(accounts.alice, start)), Some(&60));
+
+            // 销毁同样先消耗最早的批次
+            assert_eq!(erc20.burn(60), Ok(()));
+            assert_eq!(erc20.oldest_tokens_block(accounts.alice), Some(start + 3));
+            assert_eq!(erc20.age_buckets.get(&(accounts.alice, start)), None);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.eve, 40), Ok(()));
+            assert_eq!(erc20.oldest_tokens_block(accounts.bob), None);
+            assert_eq!(erc20.average_token_age_blocks(accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn token_age_buckets_are_capped() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let start = ink_env::block_number::<ink_env::DefaultEnvironment>();
+
+            for _ in 0..MAX_AGE_BUCKETS {
+                advance_blocks(1);
+                assert_eq!(erc20.mint(accounts.alice, 1), Ok(()));
+            }
+            // alice 第一批 100 并入第二批, 总量不变
+            assert_eq!(
+                erc20.age_bucket_blocks.get(&accounts.alice).map(Vec::len),
+                Some(MAX_AGE_BUCKETS)
+            );
+            assert_eq!(erc20.oldest_tokens_block(accounts.alice), Some(start + 1));
+            assert_eq!(erc20.age_buckets.get(&(accounts.alice, start)), None);
+            assert_eq!(
+                erc20.age_buckets.get(&(accounts.alice, start + 1)),
+                Some(&101)
+            );
+
+            // 收款方批次已满: 转入的更早批次立即被合并, 写入次数与预估一致
+            let bob_start = start + MAX_AGE_BUCKETS as u32 + 1;
+            for _ in 0..MAX_AGE_BUCKETS {
+                advance_blocks(1);
+                assert_eq!(erc20.mint(accounts.bob, 1), Ok(()));
+            }
+            let footprint = erc20
+                .transfer_footprint(accounts.alice, accounts.bob, 102)
+                .unwrap();
+            crate::metering::take_aux_writes();
+            assert_eq!(erc20.transfer(accounts.bob, 102), Ok(()));
+            assert_eq!(crate::metering::take_aux_writes(), footprint.aux_writes);
+            assert_eq!(
+                erc20.age_bucket_blocks.get(&accounts.bob).map(Vec::len),
+                Some(MAX_AGE_BUCKETS)
+            );
+            assert_eq!(erc20.oldest_tokens_block(accounts.bob), Some(bob_start));
+            assert_eq!(
+                erc20.age_buckets.get(&(accounts.bob, bob_start)),
+                Some(&103)
+            );
+        }
+
+        fn decode_event(event: &ink_env::test::EmittedEvent) -> Event {
+            <Event as scale::Decode>::decode(&mut &event.data[..])
+                .expect("encountered invalid contract event data buffer")
+        }
+
+        #[ink::test]
+        fn allowance_exhaustion_emits_zero_approval() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.approve(accounts.bob, 10), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer_from(accounts.alice, accounts.eve, 4), Ok(()));
+            assert_eq!(erc20.transfer_from(accounts.alice, accounts.eve, 6), Ok(()));
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 0);
+            assert!(!erc20
+                .allowances
+                .contains_key(&(accounts.alice, accounts.bob)));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 6);
+            match decode_event(&emitted_events[4]) {
+                Event::Approval(Approval {
+                    owner,
+                    spender,
+                    value,
+                    ..
+                }) => {
+                    assert_eq!(owner, accounts.alice);
+                    assert_eq!(spender, accounts.bob);
+                    assert_eq!(value, 0);
+                }
+                _ => panic!("encountered unexpected event kind: expect an Approval event"),
+            }
+            match decode_event(&emitted_events[5]) {
+                Event::AllowanceExhausted(AllowanceExhausted { total_spent, .. }) => {
+                    assert_eq!(total_spent, 10)
+                }
+                _ => panic!("encountered unexpected event kind: expect AllowanceExhausted"),
+            }
+        }
+
+        #[ink::test]
+        fn allowance_partial_spend_keeps_grant() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.approve(accounts.bob, 10), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer_from(accounts.alice, accounts.eve, 9), Ok(()));
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 1);
+            assert_eq!(erc20.allowance_spent(accounts.alice, accounts.bob), 9);
+            // 只有 Transfer, Approval, Transfer
+            assert_eq!(ink_env::test::recorded_events().count(), 3);
+        }
+
+        #[ink::test]
+        fn allowance_spent_counter_resets_per_grant() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.approve(accounts.bob, 10), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer_from(accounts.alice, accounts.eve, 3), Ok(()));
+            assert_eq!(erc20.allowance_spent(accounts.alice, accounts.bob), 3);
+
+            // 重新授权后从 0 开始计数
+            set_caller(accounts.alice);
+            assert_eq!(erc20.approve(accounts.bob, 5), Ok(()));
+            assert_eq!(erc20.allowance_spent(accounts.alice, accounts.bob), 0);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer_from(accounts.alice, accounts.eve, 2), Ok(()));
+            assert_eq!(erc20.transfer_from(accounts.alice, accounts.eve, 3), Ok(()));
+            assert_eq!(erc20.allowance_spent(accounts.alice, accounts.bob), 0);
+
+            let last = ink_env::test::recorded_events().last().expect("no events");
+            match decode_event(&last) {
+                Event::AllowanceExhausted(AllowanceExhausted { total_spent, .. }) => {
+                    assert_eq!(total_spent, 5)
+                }
+                _ => panic!("encountered unexpected event kind: expect AllowanceExhausted"),
+            }
+        }
+
+        // 固定私钥的测试签名者, 账户为压缩公钥的 Blake2x256 哈希
+        fn test_signer(seed: u8) -> (secp256k1::SecretKey, AccountId) {
+            let secp = secp256k1::Secp256k1::new();
+            let secret = secp256k1::SecretKey::from_slice(&[seed; 32]).expect("invalid secret key");
+            let public = secp256k1::PublicKey::from_secret_key(&secp, &secret).serialize();
+            let mut account = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(&public, &mut account);
+            (secret, AccountId::from(account))
+        }
+
+        fn sign_hash(secret: &secp256k1::SecretKey, message_hash: &[u8]) -> [u8; 65] {
+            let secp = secp256k1::Secp256k1::new();
+            let message = secp256k1::Message::from_slice(message_hash).expect("invalid message");
+            let (recovery_id, compact) =
+                secp.sign_recoverable(&message, secret).serialize_compact();
+            let mut signature = [0u8; 65];
+            signature[..64].copy_from_slice(&compact);
+            signature[64] = recovery_id.to_i32() as u8;
+            signature
+        }
+
+        fn signed_user_op(
+            erc20: &Erc20,
+            secret: &secp256k1::SecretKey,
+            sender: AccountId,
+            nonce: u64,
+            call_data: Vec<u8>,
+            (valid_after, valid_until): (u32, u32),
+        ) -> UserOp {
+            let mut user_op = UserOp {
+                sender,
+                nonce,
+                call_data,
+                signature: Vec::new(),
+                valid_after,
+                valid_until,
+            };
+            let hash = erc20.user_op_hash(user_op.clone());
+            user_op.signature = sign_hash(secret, hash.as_ref()).to_vec();
+            user_op
+        }
+
+        fn transfer_call_data(to: AccountId, value: Balance) -> Vec<u8> {
+            let mut call_data = TRANSFER_SELECTOR.to_vec();
+            call_data.extend(scale::Encode::encode(&(to, value)));
+            call_data
+        }
+
+        #[ink::test]
+        fn user_op_validate_and_execute_works() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, sender) = test_signer(0x42);
+            assert_eq!(erc20.transfer(sender, 50), Ok(()));
+
+            let block = ink_env::block_number::<ink_env::DefaultEnvironment>();
+            let user_op = signed_user_op(
+                &erc20,
+                &secret,
+                sender,
+                0,
+                transfer_call_data(accounts.bob, 20),
+                (block + 1, block + 0x0102),
+            );
+            let hash = erc20.user_op_hash(user_op.clone());
+            let validation = erc20
+                .validate_user_op(user_op.clone(), hash, 0)
+                .expect("nonce should be valid");
+            assert_eq!(
+                validation,
+                ValidationData {
+                    sig_failed: false,
+                    valid_until: block + 0x0102,
+                    valid_after: block + 1,
+                }
+            );
+            // 大端 uint256: validAfter 在最高 48 位, validUntil 紧随其后, authorizer 为 0
+            let mut packed = [0u8; 32];
+            packed[..6].copy_from_slice(&u64::from(block + 1).to_be_bytes()[2..]);
+            packed[6..12].copy_from_slice(&u64::from(block + 0x0102).to_be_bytes()[2..]);
+            assert_eq!(validation.pack(), packed);
+
+            // 还没到 valid_after 时不能执行
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.execute_user_op(user_op.clone()),
+                Err(Error::UserOpOutsideWindow)
+            );
+            assert_eq!(erc20.user_op_nonce(sender), 0);
+
+            // 任何人都可以作为 bundler 提交
+            advance_blocks(1);
+            assert_eq!(erc20.execute_user_op(user_op.clone()), Ok(()));
+            assert_eq!(erc20.balance_of(sender), 30);
+            assert_eq!(erc20.balance_of(accounts.bob), 20);
+            assert_eq!(erc20.user_op_nonce(sender), 1);
+
+            // 重放同一个 UserOp 会因为 nonce 失败
+            assert_eq!(erc20.execute_user_op(user_op), Err(Error::InvalidNonce));
+        }
+
+        #[ink::test]
+        fn user_op_rejects_foreign_signature() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (_, sender) = test_signer(0x42);
+            let (attacker, _) = test_signer(0x43);
+            assert_eq!(erc20.transfer(sender, 50), Ok(()));
+
+            let user_op = signed_user_op(
+                &erc20,
+                &attacker,
+                sender,
+                0,
+                transfer_call_data(accounts.bob, 20),
+                (0, 0),
+            );
+            let hash = erc20.user_op_hash(user_op.clone());
+            let validation = erc20.validate_user_op(user_op.clone(), hash, 0).unwrap();
+            assert!(validation.sig_failed);
+            assert_eq!(validation.pack()[31], 1);
+            assert_eq!(
+                erc20.execute_user_op(user_op),
+                Err(Error::InvalidUserOpSignature)
+            );
+            assert_eq!(erc20.balance_of(sender), 50);
+            assert_eq!(erc20.user_op_nonce(sender), 0);
+        }
+
+        #[ink::test]
+        fn user_op_rejects_unknown_selector() {
+            let mut erc20 = Erc20::new(100);
+            let (secret, sender) = test_signer(0x42);
+            let user_op = signed_user_op(
+                &erc20,
+                &secret,
+                sender,
+                0,
+                vec![0xde, 0xad, 0xbe, 0xef],
+                (0, 0),
+            );
+            assert_eq!(
+                erc20.execute_user_op(user_op),
+                Err(Error::UnsupportedUserOpCall)
+            );
+        }
+
+        #[ink::test]
+        fn user_op_expires_after_valid_until() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, sender) = test_signer(0x42);
+            assert_eq!(erc20.transfer(sender, 50), Ok(()));
+            let block = ink_env::block_number::<ink_env::DefaultEnvironment>();
+            let user_op = signed_user_op(
+                &erc20,
+                &secret,
+                sender,
+                0,
+                transfer_call_data(accounts.bob, 20),
+                (0, block + 1),
+            );
+
+            // 有效期是签名内容的一部分, 改动后签名失效
+            let mut extended = user_op.clone();
+            extended.valid_until = 0;
+            let hash = erc20.user_op_hash(extended.clone());
+            assert_eq!(
+                erc20
+                    .validate_user_op(extended, hash, 0)
+                    .map(|v| v.sig_failed),
+                Ok(true)
+            );
+
+            advance_blocks(2);
+            assert_eq!(
+                erc20.execute_user_op(user_op),
+                Err(Error::UserOpOutsideWindow)
+            );
+            assert_eq!(erc20.balance_of(sender), 50);
+        }
+
+        #[ink::test]
+        fn features_reported_as_constructed() {
+            let erc20 = Erc20::new(100);
+            assert_eq!(erc20.features(), DEFAULT_FEATURES);
+            assert!(erc20.is_feature_enabled(FEATURE_PAUSABLE | FEATURE_VOUCHERS));
+
+            let erc20 = Erc20::new_with_features(100, FEATURE_PAUSABLE | FEATURE_USER_OPS);
+            assert_eq!(erc20.features(), FEATURE_PAUSABLE | FEATURE_USER_OPS);
+            assert!(erc20.is_feature_enabled(FEATURE_USER_OPS));
+            assert!(!erc20.is_feature_enabled(FEATURE_VOUCHERS));
+            assert!(!erc20.is_feature_enabled(FEATURE_PAUSABLE | FEATURE_VOUCHERS));
+            assert!(!erc20.is_feature_enabled(0));
+        }
+
+        #[ink::test]
+        fn disabled_features_do_not_run() {
+            let mut erc20 = Erc20::new_with_features(100, 0);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(
+                erc20.set_rewards_hook(Some(accounts.eve), 1, HookFailurePolicy::Revert),
+                Err(Error::FeatureDisabled)
+            );
+            assert_eq!(erc20.pause(), Err(Error::FeatureDisabled));
+            assert_eq!(
+                erc20.create_voucher(voucher_hash(b"gift-1"), 1),
+                Err(Error::FeatureDisabled)
+            );
+
+            // 即使存储中的开关被设置, 对应功能也不会运行
+            let calls = Rc::new(RefCell::new(Vec::new()));
+            call::set_call_layer(StubRewardsHook {
+                calls: calls.clone(),
+                fail: true,
+            });
+            *erc20.rewards_hook = Some(accounts.eve);
+            erc20.policy.paused_ops = PAUSE_ALL;
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert!(calls.borrow().is_empty());
+            assert_eq!(erc20.oldest_tokens_block(accounts.bob), None);
+        }
+
+        #[ink::test]
+        fn pause_blocks_token_movement() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.pause(), Err(Error::NotOwner));
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.pause(), Ok(()));
+            assert!(erc20.paused());
+            assert_eq!(erc20.transfer(accounts.bob, 10), Err(Error::Paused));
+            assert_eq!(erc20.mint(accounts.bob, 10), Err(Error::Paused));
+            assert_eq!(erc20.burn(10), Err(Error::Paused));
+
+            assert_eq!(erc20.unpause(), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+        }
+
+        #[ink::test]
+        fn voting_decay_formula() {
+            // 每 1000 个区块衰减 10%
+            assert_eq!(Erc20::decayed_votes(1_000, 1_000, 0), 1_000);
+            assert_eq!(Erc20::decayed_votes(1_000, 1_000, 500), 950);
+            assert_eq!(Erc20::decayed_votes(1_000, 1_000, 1_000), 900);
+            assert_eq!(Erc20::decayed_votes(1_000, 1_000, 5_000), 500);
+            assert_eq!(Erc20::decayed_votes(1_000, 1_000, 10_000), 0);
+            assert_eq!(Erc20::decayed_votes(1_000, 1_000, 20_000), 0);
+            // 大额也不会溢出, 结果为 floor(MAX * 0.9)
+            assert_eq!(
+                Erc20::decayed_votes(Balance::MAX, 1_000, 1_000),
+                Balance::MAX / 10 * 9 + 4
+            );
+            assert_eq!(Erc20::decayed_votes(1_000, 0, 1_000_000), 1_000);
+        }
+
+        #[ink::test]
+        fn voting_resets_decay_clock() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.set_voting_decay_params(true, 5_000),
+                Err(Error::NotOwner)
+            );
+            set_caller(accounts.alice);
+            // 每 1000 个区块衰减 50%, 即每 20 个区块 1%
+            assert_eq!(erc20.set_voting_decay_params(true, 5_000), Ok(()));
+            assert_eq!(erc20.get_votes(accounts.alice), 1_000);
+
+            advance_blocks(39);
+            let id = erc20
+                .propose(Hash::from([0x01; 32]), 100)
+                .expect("propose failed");
+            assert_eq!(erc20.cast_vote(id, true), Err(Error::VotingNotStarted));
+            advance_blocks(1);
+            // 查询不发事件, 衰减在投票时记录
+            let events_before = ink_env::test::recorded_events().count();
+            assert_eq!(erc20.get_votes(accounts.alice), 980);
+            assert_eq!(ink_env::test::recorded_events().count(), events_before);
+
+            assert_eq!(erc20.cast_vote(id, true), Ok(()));
+            let decayed = ink_env::test::recorded_events()
+                .nth(events_before)
+                .expect("no events");
+            match decode_event(&decayed) {
+                Event::VotingWeightDecayed(VotingWeightDecayed {
+                    old_weight,
+                    new_weight,
+                    ..
+                }) => {
+                    assert_eq!(old_weight, 1_000);
+                    assert_eq!(new_weight, 980);
+                }
+                _ => panic!("encountered unexpected event kind: expect VotingWeightDecayed"),
+            }
+            assert_eq!(erc20.proposal(id).map(|p| p.for_votes), Some(980));
+            assert_eq!(erc20.get_votes(accounts.alice), 1_000);
+            assert_eq!(erc20.cast_vote(id, true), Err(Error::AlreadyVoted));
+
+            advance_blocks(20);
+            assert_eq!(erc20.get_votes(accounts.alice), 990);
+        }
+
+        #[ink::test]
+        fn voting_closes_after_end_block() {
+            let mut erc20 = Erc20::new(1_000);
+            let id = erc20
+                .propose(Hash::from([0x01; 32]), 2)
+                .expect("propose failed");
+            assert_eq!(erc20.cast_vote(id, false), Err(Error::VotingNotStarted));
+            advance_blocks(3);
+            assert_eq!(erc20.cast_vote(id, false), Err(Error::VotingClosed));
+            assert_eq!(erc20.cast_vote(id + 1, false), Err(Error::ProposalNotFound));
+        }
+
+        fn assert_approval_event(
+            event: &ink_env::test::EmittedEvent,
+            expected_owner: AccountId,
+            expected_spender: AccountId,
+            expected_value: Balance,
+        ) {
+            if let Event::Approval(Approval {
+                owner,
+                spender,
+                value_bucket: bucket,
+                value,
+            }) = decode_event(event)
+            {
+                assert_eq!(owner, expected_owner, "encountered invalid approval.owner");
+                assert_eq!(
+                    spender, expected_spender,
+                    "encountered invalid approval.spender"
+                );
+                assert_eq!(
+                    bucket,
+                    value_bucket(expected_value),
+                    "encountered invalid approval.value_bucket"
+                );
+                assert_eq!(value, expected_value, "encountered invalid approval.value");
+            } else {
+                panic!("encountered unexpected event kind: expect an Approval event")
+            }
+            let expected_topics = vec![
+                abi::approval_topic(),
+                abi::approval_owner_topic(expected_owner),
+                abi::approval_spender_topic(expected_spender),
+                abi::approval_value_bucket_topic(value_bucket(expected_value)),
+            ];
+            assert_eq!(event.topics.len(), expected_topics.len());
+            for (n, (actual_topic, expect_topic)) in
+                event.topics.iter().zip(expected_topics).enumerate()
+            {
+                let topic = actual_topic
+                    .decode::<Hash>()
+                    .expect("encountered invalid topic encoding");
+                assert_eq!(topic, expect_topic, "encountered invalid topic at {}", n);
+            }
+        }
+
+        #[ink::test]
+        fn value_bucket_works() {
+            assert_eq!(value_bucket(0), 0);
+            assert_eq!(value_bucket(1), 1);
+            assert_eq!(value_bucket(2), 2);
+            assert_eq!(value_bucket(3), 2);
+            assert_eq!(value_bucket(1 << 20), 21);
+            assert_eq!(value_bucket((1 << 21) - 1), 21);
+            assert_eq!(value_bucket(Balance::MAX), 128);
+        }
+
+        fn golden(hex: &str) -> Hash {
+            Hash::from(crate::decode_abi_hash(hex))
+        }
+
+        #[ink::test]
+        fn abi_event_topics_are_pinned() {
+            // 这些值与事件命名绑定, 改名或调整 topic 字段后这里会失败, 索引方也需要同步升级
+            let alice = AccountId::from([0x01; 32]);
+            let bob = AccountId::from([0x02; 32]);
+            assert_eq!(
+                abi::transfer_topic(),
+                golden("0045726332303a3a5472616e7366657200000000000000000000000000000000")
+            );
+            assert_eq!(
+                abi::approval_topic(),
+                golden("0045726332303a3a417070726f76616c00000000000000000000000000000000")
+            );
+            assert_eq!(
+                abi::transfer_topic_for_account(alice),
+                [
+                    golden("e47cd21e360dc8925f1308a582a11aff39329ef383085ec2b3217d0a64f16ddf"),
+                    golden("cd8b0f069d566a1dded63c9bf06ce003bb56203e5fdd6ff0862ead64d00767f8"),
+                ]
+            );
+            assert_eq!(
+                abi::transfer_from_topic(None),
+                golden("5445726332303a3a5472616e736665723a3a66726f6d00000000000000000000")
+            );
+            assert_eq!(
+                abi::transfer_to_topic(None),
+                golden("4c45726332303a3a5472616e736665723a3a746f000000000000000000000000")
+            );
+            assert_eq!(
+                abi::approval_owner_topic(alice),
+                golden("3b7c1e53a2236f335b8f4369e129713fafc08e9b2acb48baae2f8e040b5305bd")
+            );
+            assert_eq!(
+                abi::approval_spender_topic(bob),
+                golden("85b58fa7043be2e865bcab02b0763fc5c8ad0fae7535f43e5051eed6b78d0881")
+            );
+            assert_eq!(
+                abi::approval_value_bucket_topic(7),
+                golden("7445726332303a3a417070726f76616c3a3a76616c75655f6275636b65740700")
+            );
+        }
+
+        #[ink::test]
+        fn abi_selectors_match_message_names() {
+            use ink_env::hash::{Blake2x256, HashOutput};
+            for &(name, selector) in [
+                ("transfer", abi::TRANSFER_SELECTOR),
+                ("approve", abi::APPROVE_SELECTOR),
+                ("transfer_from", abi::TRANSFER_FROM_SELECTOR),
+            ]
+            .iter()
+            {
+                let mut output = <Blake2x256 as HashOutput>::Type::default();
+                ink_env::hash_bytes::<Blake2x256>(name.as_bytes(), &mut output);
+                assert_eq!(output[..4], selector[..], "selector of {}", name);
+            }
+        }
+
+        #[ink::test]
+        fn abi_topics_match_emitted_events() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let mut erc20 = Erc20::new(100);
+            assert_eq!(erc20.approve(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            let events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let topics_of = |event: &ink_env::test::EmittedEvent| {
+                event
+                    .topics
+                    .iter()
+                    .map(|topic| topic.decode::<Hash>().expect("invalid topic"))
+                    .collect::<Vec<_>>()
+            };
+            let approval = events
+                .iter()
+                .map(topics_of)
+                .find(|topics| topics[0] == abi::approval_topic())
+                .expect("no Approval event");
+            assert_eq!(approval[1], abi::approval_owner_topic(accounts.alice));
+            assert_eq!(approval[2], abi::approval_spender_topic(accounts.bob));
+            let [from_alice, _] = abi::transfer_topic_for_account(accounts.alice);
+            let [_, to_bob] = abi::transfer_topic_for_account(accounts.bob);
+            assert!(events
+                .iter()
+                .map(topics_of)
+                .any(|topics| topics[0] == abi::transfer_topic()
+                    && topics[1] == from_alice
+                    && topics[2] == to_bob));
+        }
+
+        #[ink::test]
+        fn approvals_filterable_by_value_bucket() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let large = 3_000_000_000_000_000_000_000;
+
+            assert_eq!(erc20.approve(accounts.bob, 5), Ok(()));
+            assert_eq!(erc20.approve(accounts.charlie, large), Ok(()));
+            assert_eq!(erc20.approve(accounts.eve, 0), Ok(()));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_approval_event(&emitted_events[1], accounts.alice, accounts.bob, 5);
+            assert_approval_event(&emitted_events[2], accounts.alice, accounts.charlie, large);
+            assert_approval_event(&emitted_events[3], accounts.alice, accounts.eve, 0);
+
+            // 模拟节点上 "金额超过 2^70" 的告警过滤
+            let large_topics = (71..=128u8)
+                .map(abi::approval_value_bucket_topic)
+                .collect::<Vec<_>>();
+            let matched = emitted_events
+                .iter()
+                .filter(|event| {
+                    event.topics.get(3).map_or(false, |topic| {
+                        let topic = topic
+                            .decode::<Hash>()
+                            .expect("encountered invalid topic encoding");
+                        large_topics.contains(&topic)
+                    })
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(matched.len(), 1);
+            assert_approval_event(matched[0], accounts.alice, accounts.charlie, large);
+        }
+
+        #[ink::test]
+        fn elasticity_rebalance_converges_up() {
+            let mut erc20 = Erc20::new(100);
+            let reserve = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(
+                erc20.elasticity_rebalance(),
+                Err(Error::ElasticityNotConfigured)
+            );
+            assert_eq!(erc20.set_elasticity_params(1_000, 500), Ok(()));
+
+            // 每次补上偏差的 10%: 100 -> 190 -> 271 -> 343.
+            // 部署所在的区块也受间隔限制
+            let mut expected = vec![190, 271, 343];
+            expected.reverse();
+            while let Some(supply) = expected.pop() {
+                assert_eq!(erc20.elasticity_rebalance(), Ok(()));
+                assert_eq!(erc20.total_supply(), supply);
+                assert_eq!(erc20.elasticity_rebalance(), Err(Error::RebalanceTooSoon));
+                advance_blocks(REBALANCE_INTERVAL_BLOCKS);
+            }
+            assert_eq!(erc20.balance_of(reserve), 243);
+
+            for _ in 0..30 {
+                assert_eq!(erc20.elasticity_rebalance(), Ok(()));
+                advance_blocks(REBALANCE_INTERVAL_BLOCKS);
+            }
+            // 进入 ±5% 区间(>= 950)后不再调节
+            assert_eq!(erc20.total_supply(), 953);
+        }
+
+        #[ink::test]
+        fn elasticity_rebalance_burns_from_reserve() {
+            let mut erc20 = Erc20::new(2_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let reserve = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.transfer(reserve, 150), Ok(()));
+            assert_eq!(erc20.set_elasticity_params(1_000, 500), Ok(()));
+            advance_blocks(1);
+
+            assert_eq!(erc20.elasticity_rebalance(), Ok(()));
+            assert_eq!(erc20.total_supply(), 1_900);
+            advance_blocks(REBALANCE_INTERVAL_BLOCKS);
+            // 储备只剩 50, 偏差的 10% 是 90
+            assert_eq!(erc20.elasticity_rebalance(), Ok(()));
+            assert_eq!(erc20.total_supply(), 1_850);
+            assert_eq!(erc20.balance_of(reserve), 0);
+            assert_eq!(erc20.balance_of(accounts.alice), 1_850);
+        }
+
+        #[ink::test]
+        fn elasticity_rebalance_down_keeps_escrowed_balances() {
+            let mut erc20 = Erc20::new(2_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let reserve = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.stake(300), Ok(()));
+            assert_eq!(
+                erc20.create_escrow(accounts.bob, 200, accounts.charlie),
+                Ok(0)
+            );
+            assert_eq!(erc20.transfer(reserve, 30), Ok(()));
+            assert_eq!(erc20.set_elasticity_params(1_000, 500), Ok(()));
+            advance_blocks(1);
+
+            // 偏差的 10% 是 100, 但储备只有 30
+            assert_eq!(erc20.elasticity_rebalance(), Ok(()));
+            assert_eq!(erc20.total_supply(), 1_970);
+            assert_eq!(erc20.balance_of(reserve), 500);
+            assert_eq!(erc20.unallocated_contract_balance(), Ok(0));
+            assert_eq!(erc20.stake_of(accounts.alice), 300);
+            assert_eq!(erc20.escrow(0).map(|escrow| escrow.amount), Some(200));
+            assert_obligations_reconcile(&erc20);
+
+            // 储备耗尽后不再销毁, 也不发出事件
+            advance_blocks(REBALANCE_INTERVAL_BLOCKS);
+            let events = ink_env::test::recorded_events().count();
+            assert_eq!(erc20.elasticity_rebalance(), Ok(()));
+            assert_eq!(erc20.total_supply(), 1_970);
+            assert_eq!(erc20.balance_of(reserve), 500);
+            assert_eq!(ink_env::test::recorded_events().count(), events);
+        }
+
+        // 按顺序返回预设错误的调用层
+        struct FailingCallLayer {
+            errors: Vec<ink_env::Error>,
+        }
+
+        impl call::CallLayer for FailingCallLayer {
+            fn call(
+                &mut self,
+                _callee: AccountId,
+                _selector: [u8; 4],
+                _input: &[u8],
+                _gas_limit: u64,
+            ) -> core::result::Result<Vec<u8>, ink_env::Error> {
+                Err(self.errors.remove(0))
+            }
+        }
+
+        #[ink::test]
+        fn external_call_errors_are_categorized() {
+            let erc20 = Erc20::new(100);
+            let callee = AccountId::from([0x11; 32]);
+            let selector = [0x01, 0x02, 0x03, 0x04];
+            call::set_call_layer(FailingCallLayer {
+                errors: vec![
+                    ink_env::Error::CalleeTrapped,
+                    ink_env::Error::CalleeReverted,
+                    ink_env::Error::NotCallable,
+                    ink_env::Error::CodeNotFound,
+                ],
+            });
+
+            for code in [
+                call::CALL_ERROR_CALLEE_TRAPPED,
+                call::CALL_ERROR_CALLEE_REVERTED,
+                call::CALL_ERROR_NOT_CALLABLE,
+                call::CALL_ERROR_UNKNOWN,
+            ]
+            .iter()
+            {
+                assert_eq!(
+                    erc20.do_call(callee, selector, &[], 1_000),
+                    Err(Error::ExternalCall {
+                        callee,
+                        selector,
+                        code: *code,
+                    })
+                );
+            }
+        }
+
+        #[ink::test]
+        fn rewards_hook_error_reports_callee_and_selector() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let hook = AccountId::from([0x12; 32]);
+            assert_eq!(
+                erc20.set_rewards_hook(Some(hook), 1_000, HookFailurePolicy::Revert),
+                Ok(())
+            );
+            call::set_call_layer(FailingCallLayer {
+                errors: vec![ink_env::Error::CalleeReverted],
+            });
+
+            assert_eq!(
+                erc20.mint(accounts.bob, 1),
+                Err(Error::ExternalCall {
+                    callee: hook,
+                    selector: ON_BALANCE_CHANGE_SELECTOR,
+                    code: call::CALL_ERROR_CALLEE_REVERTED,
+                })
+            );
+        }
+
+        #[ink::test]
+        fn transfer_fee_goes_to_fee_recipient() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(
+                erc20.set_transfer_fee(10_001, accounts.django),
+                Err(Error::InvalidBps)
+            );
+            assert_eq!(erc20.set_transfer_fee(100, accounts.django), Ok(()));
+            // alice 持有创世 NFT 免手续费, 先转走
+            assert_eq!(
+                erc20.transfer_genesis_nft(AccountId::from([0xff; 32])),
+                Ok(())
+            );
+
+            assert_eq!(erc20.transfer(accounts.bob, 1_000), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 990);
+            assert_eq!(erc20.balance_of(accounts.django), 10);
+            assert_eq!(erc20.balance_of(contract), 0);
+            assert_eq!(erc20.fees_collected(), 10);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.eve, 500), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.eve), 495);
+            assert_eq!(erc20.balance_of(accounts.django), 15);
+            assert_eq!(erc20.fees_collected(), 15);
+        }
+
+        #[ink::test]
+        fn fee_legs_ignore_recipient_opt_out_and_caps() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_transfer_fee(100, accounts.django), Ok(()));
+            // alice 持有创世 NFT 免手续费, 先转走
+            assert_eq!(
+                erc20.transfer_genesis_nft(AccountId::from([0xff; 32])),
+                Ok(())
+            );
+            // fee_recipient 关闭收款, 所在等级的上限为 0
+            assert_eq!(
+                erc20.assign_tier(accounts.bob, HolderTier::Institutional),
+                Ok(())
+            );
+            assert_eq!(erc20.set_tier_cap(HolderTier::Retail, 0), Ok(()));
+            set_caller(accounts.django);
+            assert_eq!(erc20.set_receiving(false), Ok(()));
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 500), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 495);
+            assert_eq!(erc20.balance_of(accounts.django), 5);
+
+            // 直接转给 fee_recipient 仍然被拒
+            assert_eq!(
+                erc20.transfer(accounts.django, 10),
+                Err(Error::RecipientOptedOut)
+            );
+        }
+
+        #[ink::test]
+        fn idempotent_transfer_runs_once_per_caller_and_key() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let key = [7u8; 32];
+
+            assert_eq!(erc20.transfer_idempotent(accounts.bob, 100, key), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 100);
+            let events = ink_env::test::recorded_events().count();
+
+            // 重放不转账, 只发 IdempotentReplay
+            assert_eq!(erc20.transfer_idempotent(accounts.bob, 100, key), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 100);
+            let recorded: Vec<_> = ink_env::test::recorded_events().collect();
+            assert_eq!(recorded.len(), events + 1);
+            match decode_event(&recorded[events]) {
+                Event::IdempotentReplay(replay) => {
+                    assert_eq!(replay.caller, accounts.alice);
+                    assert_eq!(replay.key, key);
+                }
+                _ => {
+                    panic!("encountered unexpected event kind: expected an IdempotentReplay event")
+                }
+            }
+
+            // 其他调用者使用相同 key 互不影响
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer_idempotent(accounts.charlie, 40, key), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 40);
+
+            assert_eq!(erc20.approve(accounts.charlie, 20), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(
+                erc20.transfer_from_idempotent(accounts.bob, accounts.eve, 10, key),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.transfer_from_idempotent(accounts.bob, accounts.eve, 10, key),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(accounts.eve), 10);
+            assert_eq!(erc20.allowance(accounts.bob, accounts.charlie), 10);
+        }
+
+        #[ink::test]
+        fn idempotency_key_expires() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let key = [1u8; 32];
+            assert_eq!(erc20.set_idempotency_key_ttl(3), Ok(()));
+
+            assert_eq!(erc20.transfer_idempotent(accounts.bob, 100, key), Ok(()));
+            advance_blocks(2);
+            assert_eq!(erc20.transfer_idempotent(accounts.bob, 100, key), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 100);
+
+            advance_blocks(1);
+            assert_eq!(erc20.transfer_idempotent(accounts.bob, 100, key), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 200);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_idempotency_key_ttl(1), Err(Error::NotOwner));
+        }
+
+        // 返回可调价格的预言机桩
+        struct StubPriceOracle {
+            price: Rc<Cell<Balance>>,
+        }
+
+        impl call::CallLayer for StubPriceOracle {
+            fn call(
+                &mut self,
+                _callee: AccountId,
+                selector: [u8; 4],
+                _input: &[u8],
+                _gas_limit: u64,
+            ) -> core::result::Result<Vec<u8>, ink_env::Error> {
+                assert_eq!(selector, LATEST_PRICE_SELECTOR);
+                Ok(scale::Encode::encode(&self.price.get()))
+            }
+        }
+
+        fn install_price_oracle(erc20: &mut Erc20, price: Balance) -> Rc<Cell<Balance>> {
+            let price = Rc::new(Cell::new(price));
+            call::set_call_layer(StubPriceOracle {
+                price: price.clone(),
+            });
+            assert_eq!(
+                erc20.set_price_oracle(Some(AccountId::from([0x0c; 32]))),
+                Ok(())
+            );
+            price
+        }
+
+        // 带原生币转账的调用
+        fn set_caller_with_value(caller: AccountId, value: Balance) {
+            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4]));
+            data.push_arg(&caller);
+            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
+                caller, callee, 1000000, value, data,
+            );
+        }
+
+        #[ink::test]
+        fn stability_fund_trades_only_outside_band() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.stability_buy(10), Err(Error::OracleNotSet));
+            let price = install_price_oracle(&mut erc20, PRICE_SCALE);
+            // 锚定价 2 原生币, 区间 ±5%
+            assert_eq!(erc20.set_stability_params(2 * PRICE_SCALE, 500), Ok(()));
+            assert_eq!(erc20.fund_stability_tokens(1_000), Ok(()));
+            set_caller_with_value(accounts.alice, 400);
+            assert_eq!(erc20.fund_stability_native(), Ok(()));
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(contract, 400)
+                .expect("Cannot set contract balance");
+            assert_eq!(erc20.stability_fund(), (1_000, 400));
+            assert_eq!(erc20.transfer(accounts.bob, 500), Ok(()));
+
+            // 价格在区间内, 两个方向都不成交
+            price.set(2 * PRICE_SCALE + PRICE_SCALE / 10);
+            set_caller_with_value(accounts.bob, 20);
+            assert_eq!(erc20.stability_buy(10), Err(Error::PriceWithinBand));
+            price.set(2 * PRICE_SCALE - PRICE_SCALE / 10);
+            assert_eq!(erc20.stability_sell(10), Err(Error::PriceWithinBand));
+
+            // 价格偏高, 按锚定价卖出基金中的代币
+            price.set(3 * PRICE_SCALE);
+            assert_eq!(erc20.stability_buy(10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 510);
+            assert_eq!(erc20.stability_fund(), (990, 420));
+            set_caller_with_value(accounts.bob, 19);
+            assert_eq!(erc20.stability_buy(10), Err(Error::IncorrectPayment));
+            set_caller_with_value(accounts.bob, 2_000);
+            assert_eq!(
+                erc20.stability_buy(1_000),
+                Err(Error::InsufficientStabilityFund)
+            );
+
+            // 价格偏低, 用原生储备收购代币
+            price.set(PRICE_SCALE);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stability_sell(100), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 410);
+            assert_eq!(erc20.stability_fund(), (1_090, 220));
+            assert_eq!(erc20.balance_of(contract), 1_090);
+            assert_eq!(
+                erc20.stability_sell(200),
+                Err(Error::InsufficientNativeReserve)
+            );
+            assert_eq!(erc20.stability_fund(), (1_090, 220));
+        }
+
+        #[ink::test]
+        fn fee_transfers_are_tagged_with_fee_kind() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_transfer_fee(100, accounts.django), Ok(()));
+            // alice 持有创世 NFT 免手续费, 先转走
+            assert_eq!(
+                erc20.transfer_genesis_nft(AccountId::from([0xff; 32])),
+                Ok(())
+            );
+            assert_eq!(erc20.transfer(accounts.bob, 500), Ok(()));
+            assert_eq!(erc20.burn(100), Ok(()));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 6);
+            assert_transfer_event(
+                &emitted_events[2],
+                Some(accounts.alice),
+                Some(accounts.bob),
+                495,
+                TransferKind::Normal,
+            );
+            assert_transfer_event(
+                &emitted_events[3],
+                Some(accounts.alice),
+                Some(accounts.django),
+                5,
+                TransferKind::Fee,
+            );
+            assert_transfer_event(
+                &emitted_events[4],
+                Some(accounts.alice),
+                None,
+                100,
+                TransferKind::Burn,
+            );
+        }
+
+        #[ink::test]
+        fn freshly_minted_tokens_are_locked_for_delay() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.set_mint_transfer_delay(5), Ok(()));
+            advance_blocks(1);
+            assert_eq!(erc20.mint(accounts.charlie, 50), Ok(()));
+            assert_eq!(erc20.tokens_transferable_at(accounts.charlie), 6);
+
+            // 构造时的余额和没有收到增发的账户不受影响
+            assert_eq!(erc20.tokens_transferable_at(accounts.bob), 0);
+            assert_eq!(erc20.transfer(accounts.eve, 10), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.eve, 10), Ok(()));
+
+            set_caller(accounts.charlie);
+            assert_eq!(
+                erc20.transfer(accounts.eve, 10),
+                Err(Error::FreshlyMintedTokensLocked)
+            );
+            advance_blocks(4);
+            assert_eq!(
+                erc20.transfer(accounts.eve, 10),
+                Err(Error::FreshlyMintedTokensLocked)
+            );
+            advance_blocks(1);
+            assert_eq!(erc20.transfer(accounts.eve, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 40);
+        }
+
+        #[ink::test]
+        fn fresh_mint_lock_does_not_block_escrow_payouts() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.set_mint_transfer_delay(50), Ok(()));
+            assert_eq!(erc20.set_elasticity_params(2_000, 500), Ok(()));
+            assert_eq!(erc20.create_voucher(voucher_hash(b"gift-1"), 100), Ok(()));
+            assert_eq!(erc20.create_voucher(voucher_hash(b"gift-2"), 100), Ok(()));
+            advance_blocks(1);
+
+            // 弹性调节增发到合约账户, 合约账户不被锁定
+            assert_eq!(erc20.elasticity_rebalance(), Ok(()));
+            assert_eq!(erc20.balance_of(contract), 300);
+            assert_eq!(erc20.tokens_transferable_at(contract), 0);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.redeem_voucher(b"gift-1".to_vec()), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 100);
+            set_caller(accounts.alice);
+            assert_eq!(erc20.cancel_voucher(voucher_hash(b"gift-2")), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 900);
+
+            // 持有人不能把新增发的代币存入托管来绕过锁定
+            assert_eq!(erc20.mint(accounts.charlie, 100), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.stake(100), Err(Error::FreshlyMintedTokensLocked));
+        }
+
+        #[ink::test]
+        fn fresh_mint_lock_emits_unlock_block() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_mint_transfer_delay(3), Ok(()));
+            assert_eq!(erc20.mint(accounts.bob, 10), Ok(()));
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), 3);
+            match decode_event(&emitted_events[1]) {
+                Event::FreshMintLockApplied(lock) => {
+                    assert_eq!(lock.account, accounts.bob);
+                    assert_eq!(lock.unlock_block, 3);
+                }
+                _ => panic!(
+                    "encountered unexpected event kind: expected a FreshMintLockApplied event"
+                ),
+            }
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_mint_transfer_delay(0), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn min_transfer_is_enforced_at_boundary() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 1), Ok(()));
+            assert_eq!(erc20.set_min_transfer(10), Ok(()));
+            assert_eq!(erc20.min_transfer(), 10);
+
+            assert_eq!(
+                erc20.transfer(accounts.bob, 9),
+                Err(Error::BelowMinimum { minimum: 10 })
+            );
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(
+                erc20.batch_transfer(
+                    vec![(accounts.bob, 20), (accounts.charlie, 5)],
+                    EventMode::PerRecipient
+                ),
+                Err(Error::BelowMinimum { minimum: 10 })
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 11);
+
+            assert_eq!(erc20.approve(accounts.charlie, 100), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.eve, 5),
+                Err(Error::BelowMinimum { minimum: 10 })
+            );
+
+            // 中途调低最小金额后立即生效
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_min_transfer(5), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.transfer_from(accounts.alice, accounts.eve, 5), Ok(()));
+            assert_eq!(erc20.set_min_transfer(0), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn transfer_all_ignores_min_transfer() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 3), Ok(()));
+            assert_eq!(erc20.set_min_transfer(10), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer(accounts.charlie, 3),
+                Err(Error::BelowMinimum { minimum: 10 })
+            );
+            assert_eq!(erc20.transfer_all(accounts.charlie), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+            assert_eq!(erc20.balance_of(accounts.charlie), 3);
+            // 销毁不受限制
+            set_caller(accounts.alice);
+            assert_eq!(erc20.burn(1), Ok(()));
+        }
+
+        #[ink::test]
+        fn staking_rewards_are_shared_by_stake() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 300), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 100), Ok(()));
+            assert_eq!(erc20.set_buyback_ratios(0, 10_000), Ok(()));
+
+            // 没有质押者时奖励留到下一次分配
+            assert_eq!(erc20.execute_buyback(40), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stake(300), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.stake(100), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.execute_buyback(40), Ok(()));
+            assert_eq!(erc20.pending_rewards(accounts.bob), 60);
+            assert_eq!(erc20.pending_rewards(accounts.charlie), 20);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.unstake(301), Err(Error::InsufficientStake));
+            assert_eq!(erc20.unstake(300), Ok(()));
+            assert_eq!(erc20.claim_rewards(), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 360);
+            assert_eq!(erc20.claim_rewards(), Err(Error::NothingToWithdraw));
+            assert_eq!(erc20.total_staked(), 100);
+        }
+
+        #[ink::test]
+        fn buyback_splits_between_burn_and_stakers() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stake(100), Ok(()));
+            assert_eq!(erc20.set_buyback_ratios(3_000, 7_000), Err(Error::NotOwner));
+
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.set_buyback_ratios(6_000, 3_000),
+                Err(Error::InvalidRatios)
+            );
+            assert_eq!(erc20.set_buyback_ratios(7_000, 3_000), Ok(()));
+            assert_eq!(erc20.execute_buyback(1_001), Ok(()));
+
+            // 1001 * 30% = 300 分给质押者, 其余 701 销毁
+            assert_eq!(erc20.total_supply(), 10_000 - 701);
+            assert_eq!(erc20.balance_of(accounts.alice), 9_900 - 1_001);
+            assert_eq!(erc20.pending_rewards(accounts.bob), 300);
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            match decode_event(emitted_events.last().unwrap()) {
+                Event::BuybackDistributed(distributed) => {
+                    assert_eq!(distributed.burned, 701);
+                    assert_eq!(distributed.staking_rewards, 300);
+                }
+                _ => {
+                    panic!("encountered unexpected event kind: expected a BuybackDistributed event")
+                }
+            }
+        }
+
+        // 授权总额应当等于 spender 列表上各授权的饱和求和
+        fn assert_allowance_aggregate(erc20: &Erc20, owner: AccountId) {
+            let sum = erc20
+                .approved_spenders(owner)
+                .into_iter()
+                .fold(0, |acc: Balance, spender| {
+                    acc.saturating_add(erc20.allowance(owner, spender))
+                });
+            assert_eq!(erc20.total_outstanding_allowance(owner), sum);
+        }
+
+        #[ink::test]
+        fn total_outstanding_allowance_tracks_every_update() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let owner = accounts.alice;
+
+            assert_eq!(erc20.approve(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.approve(accounts.charlie, 50), Ok(()));
+            assert_allowance_aggregate(&erc20, owner);
+            assert_eq!(erc20.total_outstanding_allowance(owner), 150);
+
+            // 覆盖旧额度: 先减旧值再加新值
+            assert_eq!(erc20.approve(accounts.bob, 30), Ok(()));
+            assert_eq!(erc20.total_outstanding_allowance(owner), 80);
+            assert_eq!(erc20.increase_allowance(accounts.charlie, 20), Ok(()));
+            assert_eq!(
+                erc20.decrease_allowance(accounts.bob, 31),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(erc20.decrease_allowance(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.total_outstanding_allowance(owner), 90);
+            assert_allowance_aggregate(&erc20, owner);
+
+            // transfer_from 消耗额度, 用尽时移出 spender 列表
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer_from(owner, accounts.eve, 5), Ok(()));
+            assert_eq!(erc20.total_outstanding_allowance(owner), 85);
+            assert_eq!(erc20.transfer_from(owner, accounts.eve, 15), Ok(()));
+            assert_eq!(erc20.approved_spenders(owner), vec![accounts.charlie]);
+            assert_allowance_aggregate(&erc20, owner);
+
+            set_caller(owner);
+            assert_eq!(erc20.approve(accounts.charlie, 0), Ok(()));
+            assert_eq!(erc20.total_outstanding_allowance(owner), 0);
+            assert!(erc20.approved_spenders(owner).is_empty());
+        }
+
+        #[ink::test]
+        fn infinite_allowances_keep_aggregate_consistent() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let owner = accounts.alice;
+
+            assert_eq!(erc20.approve(accounts.bob, INFINITE_ALLOWANCE), Ok(()));
+            assert_eq!(erc20.approve(accounts.charlie, 40), Ok(()));
+            assert_eq!(erc20.total_outstanding_allowance(owner), Balance::MAX);
+            assert_allowance_aggregate(&erc20, owner);
+
+            // 无限授权不会被 transfer_from 扣减
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer_from(owner, accounts.eve, 100), Ok(()));
+            assert_eq!(erc20.allowance(owner, accounts.bob), INFINITE_ALLOWANCE);
+            assert_eq!(erc20.allowance_spent(owner, accounts.bob), 100);
+
+            // 两个接近上限的授权之和超出 u128, 撤销其中一个后总额仍然精确
+            set_caller(owner);
+            assert_eq!(erc20.approve(accounts.eve, Balance::MAX - 1), Ok(()));
+            assert_allowance_aggregate(&erc20, owner);
+            assert_eq!(erc20.approve(accounts.bob, 0), Ok(()));
+            assert_eq!(erc20.total_outstanding_allowance(owner), Balance::MAX);
+            assert_eq!(erc20.approve(accounts.eve, 0), Ok(()));
+            assert_eq!(erc20.total_outstanding_allowance(owner), 40);
+            assert_allowance_aggregate(&erc20, owner);
+        }
+
+        #[ink::test]
+        fn permit_updates_allowance_aggregate() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, owner) = test_signer(9);
+            let hash = erc20.permit_hash(owner, accounts.bob, 70, 10);
+            let signature = sign_hash(&secret, hash.as_ref());
+
+            assert_eq!(
+                erc20.permit(owner, accounts.bob, 71, 10, signature),
+                Err(Error::InvalidPermitSignature)
+            );
+            assert_eq!(erc20.permit(owner, accounts.bob, 70, 10, signature), Ok(()));
+            assert_eq!(erc20.allowance(owner, accounts.bob), 70);
+            assert_eq!(erc20.total_outstanding_allowance(owner), 70);
+            assert_eq!(erc20.permit_nonce(owner), 1);
+            // nonce 已经变化, 同一个签名不能重放
+            assert_eq!(
+                erc20.permit(owner, accounts.bob, 70, 10, signature),
+                Err(Error::InvalidPermitSignature)
+            );
+
+            advance_blocks(11);
+            let hash = erc20.permit_hash(owner, accounts.bob, 0, 10);
+            let signature = sign_hash(&secret, hash.as_ref());
+            assert_eq!(
+                erc20.permit(owner, accounts.bob, 0, 10, signature),
+                Err(Error::PermitExpired)
+            );
+        }
+
+        // bob 质押后获得 rewards 的奖励
+        fn setup_staking_rewards(erc20: &mut Erc20, rewards: Balance) -> AccountId {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.set_buyback_ratios(0, 10_000), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stake(100), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.execute_buyback(rewards), Ok(()));
+            accounts.bob
+        }
+
+        #[ink::test]
+        fn claimed_rewards_vest_linearly() {
+            let mut erc20 = Erc20::new(1_000);
+            let bob = setup_staking_rewards(&mut erc20, 100);
+            assert_eq!(erc20.set_reward_vest_duration(10), Ok(()));
+
+            set_caller(bob);
+            assert_eq!(erc20.claim_rewards(), Ok(()));
+            assert_eq!(erc20.balance_of(bob), 0);
+            assert_eq!(erc20.reward_vest(bob), Some((100, 0, 10)));
+            assert_eq!(
+                erc20.release_vested_rewards(),
+                Err(Error::NothingToWithdraw)
+            );
+
+            advance_blocks(3);
+            assert_eq!(erc20.vested_rewards(bob), 30);
+            assert_eq!(erc20.release_vested_rewards(), Ok(()));
+            assert_eq!(erc20.balance_of(bob), 30);
+            // 未到期部分不能提前领取
+            assert_eq!(
+                erc20.release_vested_rewards(),
+                Err(Error::NothingToWithdraw)
+            );
+            assert_eq!(erc20.reward_vest(bob), Some((70, 3, 7)));
+
+            advance_blocks(4);
+            assert_eq!(erc20.release_vested_rewards(), Ok(()));
+            assert_eq!(erc20.balance_of(bob), 70);
+            advance_blocks(10);
+            assert_eq!(erc20.release_vested_rewards(), Ok(()));
+            assert_eq!(erc20.balance_of(bob), 100);
+            assert_eq!(erc20.reward_vest(bob), None);
+        }
+
+        #[ink::test]
+        fn repeated_claims_accumulate_into_one_vest() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let bob = setup_staking_rewards(&mut erc20, 100);
+            assert_eq!(erc20.set_reward_vest_duration(10), Ok(()));
+            set_caller(bob);
+            assert_eq!(erc20.claim_rewards(), Ok(()));
+
+            advance_blocks(5);
+            set_caller(accounts.alice);
+            assert_eq!(erc20.execute_buyback(60), Ok(()));
+            set_caller(bob);
+            assert_eq!(erc20.claim_rewards(), Ok(()));
+            // 已到期的 50 先释放, 剩余 50 与新的 60 一起重新开始释放
+            assert_eq!(erc20.balance_of(bob), 50);
+            assert_eq!(erc20.reward_vest(bob), Some((110, 5, 10)));
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_reward_vest_duration(0), Ok(()));
+            set_caller(bob);
+            assert_eq!(erc20.set_reward_vest_duration(5), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn scheduled_transfer_pays_keeper_bounty() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(
+                erc20.set_task_bounty(
+                    TaskKind::ScheduledTransfer,
+                    Bounty::Bps(10_001),
+                    BountySource::Escrow
+                ),
+                Err(Error::InvalidBps)
+            );
+            assert_eq!(
+                erc20.set_task_bounty(
+                    TaskKind::ScheduledTransfer,
+                    Bounty::Bps(250),
+                    BountySource::Escrow
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.schedule_transfer(accounts.bob, 400, 0),
+                Err(Error::InvalidSchedule)
+            );
+            assert_eq!(erc20.schedule_transfer(accounts.bob, 401, 5), Ok(0));
+            assert_eq!(erc20.balance_of(accounts.alice), 599);
+            assert_eq!(erc20.balance_of(contract), 401);
+
+            set_caller(accounts.charlie);
+            advance_blocks(4);
+            assert_eq!(erc20.execute_scheduled(0), Err(Error::ScheduleNotReady));
+            assert_eq!(erc20.cancel_scheduled(0), Err(Error::NotScheduleCreator));
+            advance_blocks(1);
+            assert_eq!(erc20.execute_scheduled(0), Ok(()));
+            // 401 * 2.5% = 10
+            assert_eq!(erc20.balance_of(accounts.charlie), 10);
+            assert_eq!(erc20.balance_of(accounts.bob), 391);
+            assert_eq!(erc20.balance_of(contract), 0);
+            assert_eq!(erc20.execute_scheduled(0), Err(Error::ScheduleNotFound));
+        }
+
+        #[ink::test]
+        fn scheduled_transfer_cancel_and_expiry_refund_creator() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.schedule_transfer(accounts.bob, 100, 2), Ok(0));
+            assert_eq!(erc20.schedule_transfer(accounts.bob, 200, 2), Ok(1));
+            assert_eq!(erc20.cancel_scheduled(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 800);
+
+            set_caller(accounts.charlie);
+            advance_blocks(2 + SCHEDULE_GRACE_BLOCKS - 1);
+            assert_eq!(erc20.expire_scheduled(1), Err(Error::ScheduleNotExpired));
+            advance_blocks(1);
+            assert_eq!(erc20.execute_scheduled(1), Err(Error::ScheduleExpired));
+            assert_eq!(erc20.expire_scheduled(1), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 1_000);
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn auto_compound_beats_claiming_at_the_end() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 100), Ok(()));
+            assert_eq!(erc20.set_buyback_ratios(0, 10_000), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stake(100), Ok(()));
+            assert_eq!(erc20.enable_auto_compound(), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.stake(100), Ok(()));
+            assert_eq!(
+                erc20.compound(accounts.charlie),
+                Err(Error::AutoCompoundDisabled)
+            );
+
+            // 100 个区块内每 10 个区块分配一次奖励, keeper 随后为 bob 复投
+            for _ in 0..10 {
+                advance_blocks(10);
+                set_caller(accounts.alice);
+                assert_eq!(erc20.execute_buyback(100), Ok(()));
+                set_caller(accounts.eve);
+                assert_eq!(erc20.compound(accounts.bob), Ok(()));
+            }
+            assert_eq!(erc20.pending_rewards(accounts.bob), 0);
+
+            // charlie 最后一次性领取再质押
+            set_caller(accounts.charlie);
+            let rewards = erc20.pending_rewards(accounts.charlie);
+            assert_eq!(erc20.claim_rewards(), Ok(()));
+            assert_eq!(erc20.stake(rewards), Ok(()));
+
+            assert!(erc20.stake_of(accounts.bob) > erc20.stake_of(accounts.charlie));
+            assert_eq!(
+                erc20.total_staked(),
+                erc20.stake_of(accounts.bob) + erc20.stake_of(accounts.charlie)
+            );
+        }
+
+        #[ink::test]
+        fn auto_compound_restakes_on_settlement() {
+            let mut erc20 = Erc20::new(1_000);
+            let bob = setup_staking_rewards(&mut erc20, 50);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            set_caller(bob);
+            // 开启前的奖励在下一次结算时一起复投
+            assert_eq!(erc20.enable_auto_compound(), Ok(()));
+            assert_eq!(erc20.pending_rewards(bob), 50);
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.execute_buyback(30), Ok(()));
+            set_caller(bob);
+            assert_eq!(erc20.unstake(10), Ok(()));
+            assert_eq!(erc20.stake_of(bob), 50 + 30 + 100 - 10);
+            assert_eq!(erc20.total_staked(), 170);
+            assert_eq!(erc20.pending_rewards(bob), 0);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let restaked = emitted_events
+                .iter()
+                .filter_map(|event| match decode_event(event) {
+                    Event::AutoCompoundExecuted(executed) => Some(executed.rewards_restaked),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(restaked, vec![80]);
+        }
+
+        // 记录回调中收到的 (账户, 新余额) 的奖励合约桩, 调用返回后再与合约余额比对
+        struct ObservingRewardsHook {
+            observed: Rc<RefCell<Vec<(AccountId, Balance)>>>,
+        }
+
+        impl call::CallLayer for ObservingRewardsHook {
+            fn call(
+                &mut self,
+                _callee: AccountId,
+                _selector: [u8; 4],
+                input: &[u8],
+                _gas_limit: u64,
+            ) -> core::result::Result<Vec<u8>, ink_env::Error> {
+                let (account, _, new_balance) =
+                    <(AccountId, Balance, Balance) as scale::Decode>::decode(&mut &input[..])
+                        .expect("encountered invalid hook input");
+                self.observed.borrow_mut().push((account, new_balance));
+                Ok(Vec::new())
+            }
+        }
+
+        #[ink::test]
+        fn rewards_hook_receives_final_balances() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_transfer_fee(100, accounts.django), Ok(()));
+            // alice 持有创世 NFT 免手续费, 先转走
+            assert_eq!(
+                erc20.transfer_genesis_nft(AccountId::from([0xff; 32])),
+                Ok(())
+            );
+            let observed = Rc::new(RefCell::new(Vec::new()));
+            call::set_call_layer(ObservingRewardsHook {
+                observed: observed.clone(),
+            });
+            assert_eq!(
+                erc20.set_rewards_hook(
+                    Some(AccountId::from([0x10; 32])),
+                    50_000,
+                    HookFailurePolicy::Revert
+                ),
+                Ok(())
+            );
+
+            // 转账本身和手续费两笔写入都完成后才回调, 每个账户只通知一次, 通知的是最终余额
+            assert_eq!(erc20.transfer(accounts.bob, 500), Ok(()));
+            assert_eq!(
+                *observed.borrow(),
+                vec![
+                    (accounts.alice, 500),
+                    (accounts.bob, 495),
+                    (accounts.django, 5),
+                ]
+            );
+            for (account, new_balance) in observed.borrow().iter() {
+                assert_eq!(*new_balance, erc20.balance_of(*account));
+            }
+
+            observed.borrow_mut().clear();
+            assert_eq!(
+                erc20.batch_transfer(
+                    vec![(accounts.bob, 100), (accounts.eve, 200)],
+                    EventMode::PerRecipient
+                ),
+                Ok(())
+            );
+            for (account, new_balance) in observed.borrow().iter() {
+                assert_eq!(*new_balance, erc20.balance_of(*account));
+            }
+            assert_eq!(observed.borrow().len(), 4);
+        }
+
+        #[ink::test]
+        fn only_authorized_rebasers_can_rebalance() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_elasticity_params(1_000, 500), Ok(()));
+            assert!(erc20.is_authorized_rebaser(accounts.alice));
+            assert!(!erc20.is_authorized_rebaser(accounts.bob));
+            advance_blocks(1);
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.elasticity_rebalance(),
+                Err(Error::NotAuthorizedRebaser)
+            );
+            assert_eq!(erc20.add_rebaser(accounts.bob), Err(Error::NotOwner));
+            assert_eq!(erc20.total_supply(), 100);
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.add_rebaser(accounts.bob), Ok(()));
+            assert_eq!(erc20.remove_rebaser(accounts.alice), Ok(()));
+            assert_eq!(
+                erc20.elasticity_rebalance(),
+                Err(Error::NotAuthorizedRebaser)
+            );
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.elasticity_rebalance(), Ok(()));
+            assert_eq!(erc20.total_supply(), 190);
+            advance_blocks(REBALANCE_INTERVAL_BLOCKS);
+            assert_eq!(erc20.elasticity_rebalance(), Ok(()));
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.remove_rebaser(accounts.bob), Ok(()));
+            assert!(!erc20.is_authorized_rebaser(accounts.bob));
+        }
+
+        #[ink::test]
+        fn metrics_match_individual_getters() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_transfer_fee(100, accounts.django), Ok(()));
+            // alice 持有创世 NFT 免手续费, 先转走
+            assert_eq!(
+                erc20.transfer_genesis_nft(AccountId::from([0xff; 32])),
+                Ok(())
+            );
+            assert_eq!(erc20.transfer(accounts.bob, 500), Ok(()));
+            advance_blocks(3);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer_all(accounts.charlie), Ok(()));
+
+            let metrics = erc20.metrics();
+            // 两次转账各有一笔手续费: 495 + 5, 491 + 4
+            assert_eq!(metrics.transfer_count, 4);
+            assert_eq!(metrics.total_volume, 995);
+            assert_eq!(metrics.holder_count, erc20.holder_count());
+            assert_eq!(metrics.holder_count, 3);
+            assert_eq!(metrics.total_supply, erc20.total_supply());
+            assert_eq!(metrics.paused, erc20.paused());
+            assert_eq!(metrics.fees_collected, erc20.fees_collected());
+            assert_eq!(metrics.last_activity_block, 3);
+        }
+
+        #[ink::test]
+        fn metrics_prometheus_text_format() {
+            let metrics = Metrics {
+                transfer_count: 4,
+                total_volume: 990,
+                holder_count: 3,
+                total_supply: 1_000,
+                paused: true,
+                fees_collected: 9,
+                last_activity_block: 3,
+            };
+            assert_eq!(
+                metrics_to_prometheus(&metrics),
+                "# TYPE erc20_transfer_count counter\n\
+                 erc20_transfer_count 4\n\
+                 # TYPE erc20_total_volume counter\n\
+                 erc20_total_volume 990\n\
+                 # TYPE erc20_holder_count gauge\n\
+                 erc20_holder_count 3\n\
+                 # TYPE erc20_total_supply gauge\n\
+                 erc20_total_supply 1000\n\
+                 # TYPE erc20_paused gauge\n\
+                 erc20_paused 1\n\
+                 # TYPE erc20_fees_collected counter\n\
+                 erc20_fees_collected 9\n\
+                 # TYPE erc20_last_activity_block gauge\n\
+                 erc20_last_activity_block 3\n"
+            );
+        }
+
+        #[ink::test]
+        fn tier_caps_limit_recipient_balances() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_tier_cap(HolderTier::Retail, 100), Ok(()));
+            assert_eq!(erc20.set_tier_cap(HolderTier::Institutional, 1_000), Ok(()));
+            assert_eq!(
+                erc20.assign_tier(accounts.charlie, HolderTier::Institutional),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.assign_tier(accounts.django, HolderTier::Protocol),
+                Ok(())
+            );
+
+            // 未指定等级的账户按 Retail 处理
+            assert_eq!(erc20.holder_tier(accounts.bob), HolderTier::Retail);
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 1), Err(Error::TierCapExceeded));
+
+            assert_eq!(erc20.transfer(accounts.charlie, 1_000), Ok(()));
+            assert_eq!(
+                erc20.transfer(accounts.charlie, 1),
+                Err(Error::TierCapExceeded)
+            );
+
+            // Protocol 没有配置上限
+            assert_eq!(erc20.tier_cap(HolderTier::Protocol), Balance::MAX);
+            assert_eq!(erc20.transfer(accounts.django, 5_000), Ok(()));
+            assert_eq!(erc20.set_tier_cap(HolderTier::Protocol, 5_000), Ok(()));
+            assert_eq!(
+                erc20.transfer(accounts.django, 1),
+                Err(Error::TierCapExceeded)
+            );
+
+            // 转出不受收款上限影响
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.eve, 100), Ok(()));
+            assert_eq!(
+                erc20.assign_tier(accounts.bob, HolderTier::Protocol),
+                Err(Error::NotOwner)
+            );
+        }
+
+        #[ink::test]
+        fn tier_caps_do_not_limit_escrow_and_internal_credits() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.set_tier_cap(HolderTier::Retail, 100), Ok(()));
+            assert_eq!(erc20.holder_tier(contract), HolderTier::Retail);
+
+            // 合约账户的托管总额不受 Retail 上限限制
+            assert_eq!(erc20.stake(500), Ok(()));
+            assert_eq!(erc20.create_voucher(voucher_hash(b"gift-1"), 300), Ok(()));
+            assert_eq!(erc20.balance_of(contract), 800);
+
+            // 托管的支付和退款也不受收款方上限限制
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.redeem_voucher(b"gift-1".to_vec()), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 400);
+            // 用户之间的转账仍然受限
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 1), Err(Error::TierCapExceeded));
+        }
+
+        #[ink::test]
+        fn tier_assignment_emits_event() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(
+                erc20.assign_tier(accounts.bob, HolderTier::Institutional),
+                Ok(())
+            );
+            assert_eq!(erc20.holder_tier(accounts.bob), HolderTier::Institutional);
+            assert_eq!(erc20.assign_tier(accounts.bob, HolderTier::Retail), Ok(()));
+            assert_eq!(erc20.holder_tier(accounts.bob), HolderTier::Retail);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            match decode_event(&emitted_events[1]) {
+                Event::TierAssigned(assigned) => {
+                    assert_eq!(assigned.account, accounts.bob);
+                    assert_eq!(assigned.tier, HolderTier::Institutional);
+                }
+                _ => panic!("encountered unexpected event kind: expected a TierAssigned event"),
+            }
+        }
+
+        #[ink::test]
+        fn transfer_from_to_self_keeps_allowance() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.approve(accounts.bob, 50), Ok(()));
+            let events = ink_env::test::recorded_events().count();
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.alice, 50),
+                Ok(())
+            );
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 50);
+            assert_eq!(erc20.allowance_spent(accounts.alice, accounts.bob), 0);
+            assert_eq!(erc20.balance_of(accounts.alice), 100);
+            assert_eq!(ink_env::test::recorded_events().count(), events);
+        }
+
+        #[ink::test]
+        fn transfer_from_to_self_still_validates() {
+            let mut erc20 = Erc20::new(100);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.charlie, 100), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.approve(accounts.bob, 500), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from(accounts.charlie, accounts.charlie, 101),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(
+                erc20.transfer_from(accounts.charlie, accounts.charlie, 501),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(erc20.allowance(accounts.charlie, accounts.bob), 500);
+        }
+
+        // 同时模拟价格预言机和亏损证明合约
+        struct StubTaxLossContracts {
+            price: Balance,
+            certificates: Rc<RefCell<Vec<(AccountId, Balance)>>>,
+        }
+
+        impl call::CallLayer for StubTaxLossContracts {
+            fn call(
+                &mut self,
+                _callee: AccountId,
+                selector: [u8; 4],
+                input: &[u8],
+                _gas_limit: u64,
+            ) -> core::result::Result<Vec<u8>, ink_env::Error> {
+                match selector {
+                    LATEST_PRICE_SELECTOR => Ok(scale::Encode::encode(&self.price)),
+                    MINT_CERTIFICATE_SELECTOR => {
+                        let args = <(AccountId, Balance) as scale::Decode>::decode(&mut &input[..])
+                            .expect("encountered invalid certificate input");
+                        self.certificates.borrow_mut().push(args);
+                        Ok(Vec::new())
+                    }
+                    _ => Err(ink_env::Error::NotCallable),
+                }
+            }
+        }
+
+        #[ink::test]
+        fn harvest_tax_loss_burns_below_basis() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let oracle = AccountId::from([0x0c; 32]);
+            let certificate = AccountId::from([0x0d; 32]);
+            assert_eq!(erc20.harvest_tax_loss(10), Err(Error::OracleNotSet));
+            let certificates = Rc::new(RefCell::new(Vec::new()));
+            call::set_call_layer(StubTaxLossContracts {
+                price: PRICE_SCALE / 4,
+                certificates: certificates.clone(),
+            });
+            assert_eq!(erc20.set_price_oracle(Some(oracle)), Ok(()));
+            assert_eq!(erc20.set_tax_loss_certificate(Some(certificate)), Ok(()));
+            assert_eq!(
+                erc20.set_basis_cost(accounts.alice, PRICE_SCALE),
+                Err(Error::NotOracle)
+            );
+
+            set_caller(oracle);
+            assert_eq!(erc20.set_basis_cost(accounts.alice, PRICE_SCALE), Ok(()));
+            assert_eq!(erc20.set_basis_cost(accounts.bob, PRICE_SCALE / 8), Ok(()));
+
+            // 成本 1, 现价 0.25, 每个代币亏损 0.75
+            set_caller(accounts.alice);
+            assert_eq!(erc20.harvest_tax_loss(400), Ok(()));
+            assert_eq!(erc20.total_supply(), 600);
+            assert_eq!(erc20.harvested_loss(accounts.alice), 300);
+            assert_eq!(*certificates.borrow(), vec![(accounts.alice, 300)]);
+
+            // bob 的成本低于现价, 没有亏损
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.harvest_tax_loss(100), Err(Error::NoPriceDrop));
+            assert_eq!(erc20.balance_of(accounts.bob), 100);
+        }
+
+        extern "Rust" {
+            fn __ink_generate_metadata() -> ink_metadata::MetadataVersioned;
+        }
+
+        // 在生成的 metadata 中收集所有 constructor 和 message 的 selector
+        fn collect_selectors(value: &serde_json::Value, selectors: &mut Vec<[u8; 4]>) {
+            match value {
+                serde_json::Value::Object(map) => {
+                    for (key, item) in map {
+                        if key == "constructors" || key == "messages" {
+                            for entry in item.as_array().expect("expected a list of entries") {
+                                let hex = entry["selector"]
+                                    .as_str()
+                                    .expect("expected a selector")
+                                    .trim_start_matches("0x");
+                                let value = u32::from_str_radix(hex, 16).expect("invalid selector");
+                                selectors.push(value.to_be_bytes());
+                            }
+                        } else {
+                            collect_selectors(item, selectors);
+                        }
+                    }
+                }
+                serde_json::Value::Array(items) => {
+                    items
+                        .iter()
+                        .for_each(|item| collect_selectors(item, selectors));
+                }
+                _ => {}
+            }
+        }
+
+        #[ink::test]
+        fn abi_hash_matches_metadata_and_pinned_version() {
+            let erc20 = Erc20::new(100);
+            let metadata = serde_json::to_value(unsafe { __ink_generate_metadata() })
+                .expect("cannot serialize metadata");
+            let mut selectors = Vec::new();
+            collect_selectors(&metadata, &mut selectors);
+            selectors.sort_unstable();
+            let mut hash = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(&selectors.concat(), &mut hash);
+
+            assert_eq!(hash, crate::ABI_HASH);
+            assert_eq!(erc20.abi_hash(), Hash::from(hash));
+            assert_eq!(erc20.abi_version(), crate::ABI_VERSION);
+            // 接口变了却没有升级版本时这里会失败
+            assert_eq!(env!("ERC20_ABI_HASH"), crate::ABI_HASH_PINNED);
+        }
+
+        #[ink::test]
+        fn fixed_point_modes_split_balances() {
+            let mut erc20 = Erc20::new(5 * 10u128.pow(18) + 25);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(
+                erc20.balance_of_display(accounts.alice),
+                (5 * 10u128.pow(18) + 25, 0)
+            );
+
+            assert_eq!(
+                erc20.set_precision_mode(PrecisionMode::FixedPoint18),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of_display(accounts.alice), (5, 25));
+            assert_eq!(
+                erc20.transfer_fixed(accounts.bob, 10u128.pow(18) / 2, 6),
+                Err(Error::PrecisionMismatch)
+            );
+            assert_eq!(
+                erc20.transfer_fixed(accounts.bob, 10u128.pow(18) / 2, 18),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.balance_of_display(accounts.bob),
+                (0, 10u128.pow(18) / 2)
+            );
+            assert_eq!(
+                erc20.balance_of_display(accounts.alice),
+                (4, 10u128.pow(18) / 2 + 25)
+            );
+        }
+
+        #[ink::test]
+        fn fixed_point6_matches_stablecoin_units() {
+            let mut erc20 = Erc20::new(1_000_000_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_precision_mode(PrecisionMode::FixedPoint6), Ok(()));
+            assert_eq!(erc20.balance_of_display(accounts.alice), (1_000, 0));
+
+            // 12.345678 个代币
+            assert_eq!(erc20.transfer_fixed(accounts.bob, 12_345_678, 6), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 12_345_678);
+            assert_eq!(erc20.balance_of_display(accounts.bob), (12, 345_678));
+            assert_eq!(erc20.balance_of_display(accounts.alice), (987, 654_322));
+            assert_eq!(
+                erc20.transfer_fixed(accounts.bob, 1, 0),
+                Err(Error::PrecisionMismatch)
+            );
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.set_precision_mode(PrecisionMode::Standard),
+                Err(Error::NotOwner)
+            );
+        }
+
+        #[ink::test]
+        fn opted_out_accounts_reject_transfers_and_mints() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_receiving(false), Ok(()));
+            assert!(!erc20.is_receiving(accounts.bob));
+
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.transfer(accounts.bob, 10),
+                Err(Error::RecipientOptedOut)
+            );
+            assert_eq!(erc20.mint(accounts.bob, 10), Err(Error::RecipientOptedOut));
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_receiving(true), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 10);
+        }
+
+        #[ink::test]
+        fn escrow_refunds_ignore_receiving_opt_out() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 300), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.schedule_transfer(accounts.charlie, 100, 5), Ok(0));
+            assert_eq!(erc20.stake(100), Ok(()));
+            assert_eq!(erc20.set_receiving(false), Ok(()));
+
+            // 退款和解除质押照常到账
+            assert_eq!(erc20.cancel_scheduled(0), Ok(()));
+            assert_eq!(erc20.unstake(100), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 300);
+
+            // 普通转账仍然被拒
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.transfer(accounts.bob, 1),
+                Err(Error::RecipientOptedOut)
+            );
+        }
+
+        #[ink::test]
+        fn loan_repaid_with_interest() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            // 出借 500, 利率 5%, 抵押 300 原生币, 期限 10 个区块
+            assert_eq!(erc20.create_loan_offer(500, 500, 300, 10), Ok(0));
+            assert_eq!(erc20.balance_of(contract), 500);
+            assert_eq!(erc20.loan_repayment(0), Ok(525));
+
+            set_caller_with_value(accounts.bob, 299);
+            assert_eq!(erc20.accept_loan(0), Err(Error::IncorrectPayment));
+            set_caller_with_value(accounts.bob, 300);
+            assert_eq!(erc20.accept_loan(0), Ok(()));
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(contract, 300)
+                .expect("Cannot set contract balance");
+            assert_eq!(erc20.balance_of(accounts.bob), 600);
+            assert_eq!(erc20.loan(0).map(|loan| loan.due_block), Some(10));
+            assert_eq!(erc20.accept_loan(0), Err(Error::LoanAlreadyAccepted));
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.repay_loan(0), Err(Error::NotBorrower));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.repay_loan(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 75);
+            assert_eq!(erc20.balance_of(accounts.alice), 400 + 525);
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(contract),
+                Ok(0)
+            );
+            assert_eq!(erc20.repay_loan(0), Err(Error::LoanNotActive));
+        }
+
+        #[ink::test]
+        fn lender_cannot_block_repayment_or_borrow_from_self() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.create_loan_offer(500, 500, 300, 10), Ok(0));
+            set_caller_with_value(accounts.alice, 300);
+            assert_eq!(erc20.accept_loan(0), Err(Error::SelfLoan));
+            set_caller_with_value(accounts.bob, 300);
+            assert_eq!(erc20.accept_loan(0), Ok(()));
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(contract, 300)
+                .expect("Cannot set contract balance");
+
+            // 出借人关闭收款并把自己的持仓上限压到当前余额
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_receiving(false), Ok(()));
+            assert_eq!(
+                erc20.assign_tier(accounts.alice, HolderTier::Institutional),
+                Ok(())
+            );
+            assert_eq!(erc20.set_tier_cap(HolderTier::Institutional, 400), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.repay_loan(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 400 + 525);
+            assert_eq!(erc20.balance_of(contract), 0);
+            assert_eq!(erc20.loan(0).map(|loan| loan.repaid), Some(true));
+            set_caller(accounts.alice);
+            advance_blocks(11);
+            assert_eq!(erc20.liquidate_loan(0), Err(Error::LoanNotActive));
+        }
+
+        #[ink::test]
+        fn overdue_loan_is_liquidated() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.create_loan_offer(200, 1_000, 50, 5), Ok(0));
+            set_caller_with_value(accounts.bob, 50);
+            assert_eq!(erc20.accept_loan(0), Ok(()));
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(contract, 50)
+                .expect("Cannot set contract balance");
+
+            set_caller(accounts.alice);
+            advance_blocks(5);
+            assert_eq!(erc20.liquidate_loan(0), Err(Error::LoanNotDue));
+            advance_blocks(1);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.liquidate_loan(0), Err(Error::NotLender));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.liquidate_loan(0), Ok(()));
+            assert_eq!(erc20.loan(0).map(|loan| loan.defaulted), Some(true));
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(contract),
+                Ok(0)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(erc20.repay_loan(0), Err(Error::LoanNotActive));
+        }
+
+        #[ink::test]
+        fn transfer_policy_checks_run_in_order() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_mint_transfer_delay(10), Ok(()));
+            assert_eq!(erc20.mint(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.set_min_transfer(10), Ok(()));
+            assert_eq!(erc20.set_tier_cap(HolderTier::Retail, 5), Ok(()));
+            assert_eq!(erc20.pause(), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.set_receiving(false), Ok(()));
+            assert_eq!(
+                erc20.policy(),
+                TransferPolicy {
+                    paused_ops: PAUSE_ALL,
+                    min_transfer: 10,
+                    mint_transfer_delay_blocks: 10,
+                    tier_caps: vec![(HolderTier::Retail, 5)],
+                    require_attestation: false,
+                }
+            );
+
+            // 每次只解除最前面的一项限制, 报错依次后移
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 9), Err(Error::Paused));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.unpause(), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer(accounts.charlie, 9),
+                Err(Error::BelowMinimum { minimum: 10 })
+            );
+            assert_eq!(
+                erc20.transfer(accounts.charlie, 10),
+                Err(Error::FreshlyMintedTokensLocked)
+            );
+            advance_blocks(10);
+            assert_eq!(
+                erc20.transfer(accounts.charlie, 10),
+                Err(Error::RecipientOptedOut)
+            );
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.set_receiving(true), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer(accounts.charlie, 10),
+                Err(Error::TierCapExceeded)
+            );
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_tier_cap(HolderTier::Retail, 10), Ok(()));
+            assert_eq!(erc20.policy().tier_caps, vec![(HolderTier::Retail, 10)]);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 10);
+        }
+
+        // 记录每个回调收到的 (hook, from, to, value), failing 中的回调返回错误
+        struct StubTransferHooks {
+            calls: Rc<RefCell<Vec<(AccountId, AccountId, AccountId, Balance)>>>,
+            failing: Vec<AccountId>,
+        }
+
+        impl call::CallLayer for StubTransferHooks {
+            fn call(
+                &mut self,
+                callee: AccountId,
+                selector: [u8; 4],
+                input: &[u8],
+                _gas_limit: u64,
+            ) -> core::result::Result<Vec<u8>, ink_env::Error> {
+                assert_eq!(selector, ON_TOKEN_TRANSFER_SELECTOR);
+                let (from, to, value) =
+                    <(AccountId, AccountId, Balance) as scale::Decode>::decode(&mut &input[..])
+                        .expect("encountered invalid hook input");
+                self.calls.borrow_mut().push((callee, from, to, value));
+                if self.failing.contains(&callee) {
+                    Err(ink_env::Error::CalleeReverted)
+                } else {
+                    Ok(Vec::new())
+                }
+            }
+        }
+
+        fn install_transfer_hooks(
+            erc20: &mut Erc20,
+            hooks: &[AccountId],
+            failing: Vec<AccountId>,
+        ) -> Rc<RefCell<Vec<(AccountId, AccountId, AccountId, Balance)>>> {
+            let calls = Rc::new(RefCell::new(Vec::new()));
+            call::set_call_layer(StubTransferHooks {
+                calls: calls.clone(),
+                failing,
+            });
+            for hook in hooks {
+                assert_eq!(erc20.register_transfer_hook(*hook), Ok(()));
+            }
+            calls
+        }
+
+        #[ink::test]
+        fn transfer_hooks_are_notified_in_order() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let calls = install_transfer_hooks(&mut erc20, &[accounts.eve, accounts.frank], vec![]);
+            assert_eq!(erc20.transfer_hooks(), vec![accounts.eve, accounts.frank]);
+
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(
+                *calls.borrow(),
+                vec![
+                    (accounts.eve, accounts.alice, accounts.bob, 10),
+                    (accounts.frank, accounts.alice, accounts.bob, 10),
+                ]
+            );
+
+            // 增发和销毁不通知
+            calls.borrow_mut().clear();
+            assert_eq!(erc20.mint(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.burn(10), Ok(()));
+            assert!(calls.borrow().is_empty());
+
+            assert_eq!(erc20.remove_transfer_hook(accounts.eve), Ok(()));
+            assert_eq!(
+                erc20.remove_transfer_hook(accounts.eve),
+                Err(Error::HookNotFound)
+            );
+            assert_eq!(erc20.transfer(accounts.bob, 5), Ok(()));
+            assert_eq!(
+                *calls.borrow(),
+                vec![(accounts.frank, accounts.alice, accounts.bob, 5)]
+            );
+        }
+
+        #[ink::test]
+        fn transfer_hook_registry_is_bounded() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let hooks: Vec<AccountId> = (1..=MAX_TRANSFER_HOOKS as u8)
+                .map(|i| AccountId::from([0x40 + i; 32]))
+                .collect();
+            install_transfer_hooks(&mut erc20, &hooks, vec![]);
+            assert_eq!(
+                erc20.register_transfer_hook(hooks[0]),
+                Err(Error::HookAlreadyRegistered)
+            );
+            assert_eq!(
+                erc20.register_transfer_hook(accounts.eve),
+                Err(Error::TooManyHooks)
+            );
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.remove_transfer_hook(hooks[0]), Err(Error::NotOwner));
+            assert_eq!(
+                erc20.set_hook_failure_mode(HookFailureMode::Lenient),
+                Err(Error::NotOwner)
+            );
+            set_caller(accounts.alice);
+            let events = ink_env::test::recorded_events().count();
+            assert_eq!(erc20.remove_transfer_hook(hooks[0]), Ok(()));
+            assert_eq!(erc20.register_transfer_hook(accounts.eve), Ok(()));
+            let emitted: Vec<_> = ink_env::test::recorded_events().skip(events).collect();
+            assert!(matches!(
+                decode_event(&emitted[0]),
+                Event::HookRemoved(HookRemoved { hook }) if hook == hooks[0]
+            ));
+            assert!(matches!(
+                decode_event(&emitted[1]),
+                Event::HookRegistered(HookRegistered { hook }) if hook == accounts.eve
+            ));
+        }
+
+        #[ink::test]
+        fn transfer_hook_failure_modes() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let calls = install_transfer_hooks(
+                &mut erc20,
+                &[accounts.eve, accounts.frank],
+                vec![accounts.eve],
+            );
+
+            // Strict: 第一个回调失败就返回错误, 后面的回调不再调用
+            assert_eq!(erc20.hook_failure_mode(), HookFailureMode::Strict);
+            assert_eq!(
+                erc20.transfer(accounts.bob, 10),
+                Err(Error::ExternalCall {
+                    callee: accounts.eve,
+                    selector: ON_TOKEN_TRANSFER_SELECTOR,
+                    code: call::CALL_ERROR_CALLEE_REVERTED,
+                })
+            );
+            assert_eq!(calls.borrow().len(), 1);
+
+            // Lenient: 记录失败, 继续调用后面的回调, 转账成功
+            calls.borrow_mut().clear();
+            assert_eq!(
+                erc20.set_hook_failure_mode(HookFailureMode::Lenient),
+                Ok(())
+            );
+            let events = ink_env::test::recorded_events().count();
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(calls.borrow().len(), 2);
+            let last = ink_env::test::recorded_events()
+                .nth(events + 1)
+                .expect("HookCallFailed not emitted");
+            assert!(matches!(
+                decode_event(&last),
+                Event::HookCallFailed(HookCallFailed { hook, reason })
+                    if hook == accounts.eve && reason == call::CALL_ERROR_CALLEE_REVERTED
+            ));
+        }
+
+        #[ink::test]
+        fn delegate_by_sig_moves_voting_power() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, delegator) = test_signer(11);
+            assert_eq!(erc20.transfer(delegator, 300), Ok(()));
+            assert_eq!(erc20.get_votes(delegator), 300);
+
+            let hash = erc20.delegation_hash(delegator, accounts.bob, 0, 10);
+            let signature = sign_hash(&secret, hash.as_ref());
+            assert_eq!(
+                erc20.delegate_by_sig(delegator, accounts.charlie, 0, 10, signature),
+                Err(Error::InvalidPermitSignature)
+            );
+            // 任何人都可以代为提交
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.delegate_by_sig(delegator, accounts.bob, 0, 10, signature),
+                Ok(())
+            );
+            assert_eq!(erc20.delegates(delegator), accounts.bob);
+            assert_eq!(erc20.get_votes(delegator), 0);
+            assert_eq!(erc20.get_votes(accounts.bob), 300);
+            assert_eq!(erc20.permit_nonce(delegator), 1);
+            assert_eq!(
+                erc20.delegate_by_sig(delegator, accounts.bob, 0, 10, signature),
+                Err(Error::InvalidNonce)
+            );
+
+            // 被委托人的票数跟随委托人余额
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(delegator, 50), Ok(()));
+            assert_eq!(erc20.get_votes(accounts.bob), 350);
+            assert_eq!(erc20.get_votes(accounts.alice), 650);
+
+            set_caller(delegator);
+            assert_eq!(erc20.delegate(accounts.charlie), Ok(()));
+            assert_eq!(erc20.get_votes(accounts.bob), 0);
+            assert_eq!(erc20.get_votes(accounts.charlie), 350);
+            assert_eq!(erc20.delegate(delegator), Ok(()));
+            assert_eq!(erc20.get_votes(accounts.charlie), 0);
+            assert_eq!(erc20.get_votes(delegator), 350);
+        }
+
+        #[ink::test]
+        fn delegate_by_sig_rejects_expired_and_permit_signatures() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, delegator) = test_signer(11);
+
+            // 同样 nonce 的 permit 签名不能当作委托提交, 反之亦然
+            let permit_hash = erc20.permit_hash(delegator, accounts.bob, 0, 10);
+            let permit_signature = sign_hash(&secret, permit_hash.as_ref());
+            assert_eq!(
+                erc20.delegate_by_sig(delegator, accounts.bob, 0, 10, permit_signature),
+                Err(Error::InvalidPermitSignature)
+            );
+            let delegation_hash = erc20.delegation_hash(delegator, accounts.bob, 0, 10);
+            let delegation_signature = sign_hash(&secret, delegation_hash.as_ref());
+            assert_eq!(
+                erc20.permit(delegator, accounts.bob, 0, 10, delegation_signature),
+                Err(Error::InvalidPermitSignature)
+            );
+            assert_eq!(erc20.permit_nonce(delegator), 0);
+
+            advance_blocks(11);
+            assert_eq!(
+                erc20.delegate_by_sig(delegator, accounts.bob, 0, 10, delegation_signature),
+                Err(Error::PermitExpired)
+            );
+            assert_eq!(erc20.delegates(delegator), delegator);
+        }
+
+        #[ink::test]
+        fn transfers_are_committed_and_verifiable() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            advance_blocks(3);
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+
+            let mut expected = [0u8; 32];
+            ink_env::hash_encoded::<Blake2x256, _>(
+                &(
+                    accounts.alice,
+                    accounts.bob,
+                    100 as Balance,
+                    3u32,
+                    900 as Balance,
+                ),
+                &mut expected,
+            );
+            assert_eq!(
+                erc20.get_transaction_commitments(accounts.alice),
+                vec![Hash::from(expected)]
+            );
+            // 只记在转出方名下
+            assert!(erc20.get_transaction_commitments(accounts.bob).is_empty());
+
+            assert!(erc20.verify_transaction(accounts.alice, accounts.bob, 100, 3, 900));
+            assert!(!erc20.verify_transaction(accounts.alice, accounts.bob, 101, 3, 900));
+            assert!(!erc20.verify_transaction(accounts.alice, accounts.bob, 100, 4, 900));
+            assert!(!erc20.verify_transaction(accounts.alice, accounts.bob, 100, 3, 899));
+            assert!(!erc20.verify_transaction(accounts.alice, accounts.charlie, 100, 3, 900));
+            assert!(!erc20.verify_transaction(accounts.bob, accounts.bob, 100, 3, 900));
+        }
+
+        #[ink::test]
+        fn transaction_commitments_keep_the_latest_twenty() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            for i in 1..=MAX_TRANSACTION_COMMITMENTS as Balance + 1 {
+                assert_eq!(erc20.transfer(accounts.bob, i), Ok(()));
+            }
+            let commitments = erc20.get_transaction_commitments(accounts.alice);
+            assert_eq!(commitments.len(), MAX_TRANSACTION_COMMITMENTS);
+
+            // 第一笔已被挤出, 第二笔和最后一笔都还在
+            assert!(!erc20.verify_transaction(accounts.alice, accounts.bob, 1, 0, 999));
+            assert!(erc20.verify_transaction(accounts.alice, accounts.bob, 2, 0, 997));
+            assert!(erc20.verify_transaction(accounts.alice, accounts.bob, 21, 0, 769));
+        }
+
+        #[ink::test]
+        fn each_burn_path_leaves_a_receipt() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let purpose = *b"bridge:polkadot\0";
+            assert_eq!(erc20.burn(10), Ok(()));
+            advance_blocks(2);
+            assert_eq!(erc20.burn_with_purpose(20, purpose), Ok(1));
+            assert_eq!(erc20.approve(accounts.bob, 50), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.burn_from(accounts.alice, 51),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(erc20.burn_from(accounts.alice, 30), Ok(()));
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 20);
+
+            assert_eq!(
+                erc20.burn_receipt(0),
+                Some(BurnReceipt {
+                    burner: accounts.alice,
+                    amount: 10,
+                    block: 0,
+                    purpose: [0; 16],
+                })
+            );
+            assert_eq!(
+                erc20
+                    .burn_receipt(1)
+                    .map(|receipt| (receipt.block, receipt.purpose)),
+                Some((2, purpose))
+            );
+            assert_eq!(
+                erc20.burn_receipt(2).map(|receipt| receipt.amount),
+                Some(30)
+            );
+            assert_eq!(erc20.burn_receipt(3), None);
+            assert_eq!(erc20.total_supply(), 940);
+
+            // 失败的销毁不产生凭证
+            assert_eq!(erc20.burn(1_000), Err(Error::InsufficientBalance));
+            assert_eq!(erc20.burn_receipt(3), None);
+
+            let last = ink_env::test::recorded_events()
+                .filter_map(|event| match decode_event(&event) {
+                    Event::Burn(burn) => Some(burn),
+                    _ => None,
+                })
+                .last()
+                .expect("no Burn event");
+            assert_eq!(
+                (last.receipt_id, last.burner, last.amount),
+                (2, accounts.alice, 30)
+            );
+        }
+
+        #[ink::test]
+        fn burn_receipts_are_indexed_per_account() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            for _ in 0..MAX_BURN_RECEIPTS_PER_ACCOUNT + 2 {
+                assert_eq!(erc20.burn(1), Ok(()));
+            }
+            set_caller(accounts.bob);
+            assert_eq!(erc20.burn(5), Ok(()));
+
+            // alice 的索引只保留最近的 MAX_BURN_RECEIPTS_PER_ACCOUNT 张, 最早的仍可按 id 查询
+            let alice_receipts = erc20.receipts_of(accounts.alice, 0, 100);
+            assert_eq!(alice_receipts.len(), MAX_BURN_RECEIPTS_PER_ACCOUNT);
+            assert_eq!(alice_receipts[0].0, 2);
+            assert!(erc20.burn_receipt(0).is_some());
+
+            let page = erc20.receipts_of(accounts.alice, 48, 10);
+            assert_eq!(
+                page.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+                vec![50, 51]
+            );
+            assert_eq!(erc20.receipts_of(accounts.bob, 0, 10)[0].0, 52);
+            assert!(erc20.receipts_of(accounts.bob, 1, 10).is_empty());
+            assert!(erc20.receipts_of(accounts.charlie, 0, 10).is_empty());
+        }
+
+        #[ink::test]
+        fn burn_receipts_prune_after_retention() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.burn(1), Ok(()));
+            assert_eq!(erc20.burn(1), Ok(()));
+            advance_blocks(5);
+            assert_eq!(erc20.burn(1), Ok(()));
+            assert_eq!(erc20.prune_receipts(10), Err(Error::ReceiptPruningDisabled));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_burn_receipt_retention(10), Err(Error::NotOwner));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_burn_receipt_retention(10), Ok(()));
+            assert_eq!(
+                erc20.set_task_bounty(
+                    TaskKind::ReceiptPruning,
+                    Bounty::Flat(2),
+                    BountySource::Mint
+                ),
+                Ok(())
+            );
+
+            // 刚好 10 个区块还在保留期内
+            advance_blocks(5);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.prune_receipts(10), Ok(0));
+            advance_blocks(1);
+            assert_eq!(erc20.prune_receipts(1), Ok(1));
+            assert_eq!(erc20.balance_of(accounts.bob), 2);
+            assert_eq!(erc20.prune_receipts(10), Ok(1));
+            assert_eq!(erc20.balance_of(accounts.bob), 4);
+            assert_eq!(erc20.burn_receipt(1), None);
+            assert!(erc20.burn_receipt(2).is_some());
+            assert_eq!(
+                erc20
+                    .receipts_of(accounts.alice, 0, 10)
+                    .iter()
+                    .map(|(id, _)| *id)
+                    .collect::<Vec<_>>(),
+                vec![2]
+            );
+
+            advance_blocks(5);
+            assert_eq!(erc20.prune_receipts(10), Ok(1));
+            assert!(erc20.receipts_of(accounts.alice, 0, 10).is_empty());
+            assert_eq!(erc20.prune_receipts(10), Ok(0));
+        }
+
+        #[ink::test]
+        fn small_burn_receipts_earn_no_pruning_bounty() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.burn(0), Err(Error::ZeroAmount));
+            assert_eq!(erc20.burn_with_purpose(0, [1; 16]), Err(Error::ZeroAmount));
+            assert_eq!(erc20.burn_receipt(0), None);
+
+            assert_eq!(erc20.set_burn_receipt_retention(1), Ok(()));
+            assert_eq!(
+                erc20.set_task_bounty(
+                    TaskKind::ReceiptPruning,
+                    Bounty::Flat(5),
+                    BountySource::Mint
+                ),
+                Ok(())
+            );
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_receipt_bounty_min_amount(5), Err(Error::NotOwner));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_receipt_bounty_min_amount(5), Ok(()));
+            for _ in 0..3 {
+                assert_eq!(erc20.burn(1), Ok(()));
+            }
+            assert_eq!(erc20.burn(5), Ok(()));
+
+            advance_blocks(2);
+            set_caller(accounts.bob);
+            // 小额凭证照样清理, 只有最后一张发赏金
+            assert_eq!(erc20.prune_receipts(10), Ok(4));
+            assert_eq!(erc20.balance_of(accounts.bob), 5);
+            assert_eq!(erc20.keeper_stats(accounts.bob).tasks, 1);
+        }
+
+        #[ink::test]
+        fn exercising_a_license_pays_royalty() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+            assert_eq!(
+                erc20.grant_license(accounts.bob, 0b11, 10_001, 10),
+                Err(Error::InvalidBps)
+            );
+            assert_eq!(erc20.grant_license(accounts.bob, 0b11, 250, 10), Ok(0));
+
+            set_caller(accounts.charlie);
+            assert_eq!(
+                erc20.exercise_license(0, 1_000),
+                Err(Error::UnauthorizedLicenseExercise)
+            );
+            // 2.5% 版税, 向下取整: 12_345 * 250 / 10_000 = 308.625
+            set_caller(accounts.bob);
+            assert_eq!(erc20.exercise_license(0, 12_345), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 10_000 - 308);
+            assert_eq!(erc20.balance_of(accounts.alice), 90_000 + 308);
+            // 不足 1 个单位的版税为 0
+            assert_eq!(erc20.exercise_license(0, 39), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 10_000 - 308);
+            // 超过余额的版税转账失败
+            assert_eq!(
+                erc20.exercise_license(0, 1_000_000),
+                Err(Error::InsufficientBalance)
+            );
+
+            let last = ink_env::test::recorded_events().last().expect("no events");
+            match decode_event(&last) {
+                Event::LicenseExercised(LicenseExercised { id, royalty_paid }) => {
+                    assert_eq!((id, royalty_paid), (0, 0));
+                }
+                _ => panic!("expected LicenseExercised"),
+            }
+        }
+
+        #[ink::test]
+        fn licenses_expire_and_can_be_revoked() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 500), Ok(()));
+            assert_eq!(erc20.grant_license(accounts.bob, 1, 10_000, 5), Ok(0));
+            assert_eq!(erc20.grant_license(accounts.bob, 1, 100, 50), Ok(1));
+            assert_eq!(
+                erc20.license(0).map(|license| license.expiry_block),
+                Some(5)
+            );
+
+            set_caller(accounts.bob);
+            advance_blocks(5);
+            assert_eq!(erc20.exercise_license(0, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 490);
+            advance_blocks(1);
+            assert_eq!(erc20.exercise_license(0, 10), Err(Error::LicenseExpired));
+
+            assert_eq!(erc20.revoke_license(1), Err(Error::NotLicenseGrantor));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.revoke_license(1), Ok(()));
+            assert_eq!(erc20.license(1), None);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.exercise_license(1, 10), Err(Error::LicenseNotFound));
+        }
+
+        #[ink::test]
+        fn auction_price_declines_linearly() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.current_auction_price(), None);
+            assert_eq!(
+                erc20.start_auction(1_000, PRICE_SCALE, 3 * PRICE_SCALE, 100),
+                Err(Error::InvalidAuction)
+            );
+            assert_eq!(
+                erc20.start_auction(1_000, 3 * PRICE_SCALE, PRICE_SCALE, 100),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.start_auction(1_000, 3 * PRICE_SCALE, PRICE_SCALE, 100),
+                Err(Error::AuctionActive)
+            );
+            assert_eq!(erc20.balance_of(accounts.alice), 9_000);
+
+            let samples = [
+                (0, 3_000_000),
+                (25, 2_500_000),
+                (33, 2_340_000),
+                (50, 2_000_000),
+                (99, 1_020_000),
+                (100, 1_000_000),
+                (150, 1_000_000),
+            ];
+            let mut block = 0;
+            for (offset, price) in samples.iter() {
+                advance_blocks(offset - block);
+                block = *offset;
+                assert_eq!(
+                    erc20.current_auction_price(),
+                    Some(*price),
+                    "block {}",
+                    offset
+                );
+            }
+        }
+
+        #[ink::test]
+        fn auction_bids_are_capped_by_inventory_with_refund() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(contract, 1_000)
+                .expect("Cannot set contract balance");
+            assert_eq!(
+                erc20.start_auction(100, 2 * PRICE_SCALE, PRICE_SCALE, 10),
+                Ok(())
+            );
+
+            // 价格 2: 151 原生币买 75 个, 多出的 1 退回
+            let bob_before =
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob)
+                    .expect("Cannot get balance");
+            set_caller_with_value(accounts.bob, 151);
+            assert_eq!(erc20.bid(), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 75);
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.bob),
+                Ok(bob_before + 1)
+            );
+
+            // 只剩 25 个, 100 原生币中 50 退回
+            let charlie_before =
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.charlie)
+                    .expect("Cannot get balance");
+            set_caller_with_value(accounts.charlie, 100);
+            assert_eq!(erc20.bid(), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 25);
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.charlie),
+                Ok(charlie_before + 50)
+            );
+            assert_eq!(
+                erc20
+                    .auction()
+                    .map(|auction| (auction.remaining, auction.proceeds)),
+                Some((0, 200))
+            );
+
+            // 售罄即结束, 不必等到结束区块
+            set_caller_with_value(accounts.django, 10);
+            assert_eq!(erc20.bid(), Err(Error::AuctionEnded));
+            let alice_before =
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.alice)
+                    .expect("Cannot get balance");
+            assert_eq!(erc20.finalize_auction(), Ok(()));
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.alice),
+                Ok(alice_before + 200)
+            );
+            assert_eq!(erc20.balance_of(accounts.alice), 9_900);
+            assert_eq!(erc20.auction(), None);
+        }
+
+        #[ink::test]
+        fn auction_rejects_bids_after_expiry() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(
+                erc20.start_auction(100, 4 * PRICE_SCALE, 2 * PRICE_SCALE, 10),
+                Ok(())
+            );
+            set_caller_with_value(accounts.bob, 1);
+            assert_eq!(erc20.bid(), Err(Error::IncorrectPayment));
+            assert_eq!(erc20.finalize_auction(), Err(Error::AuctionNotEnded));
+
+            // 结束区块当天仍可出价, 之后拒绝
+            advance_blocks(10);
+            set_caller_with_value(accounts.bob, 20);
+            assert_eq!(erc20.bid(), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 10);
+            advance_blocks(1);
+            assert_eq!(erc20.bid(), Err(Error::AuctionEnded));
+
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(contract, 20)
+                .expect("Cannot set contract balance");
+            assert_eq!(erc20.finalize_auction(), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 9_990);
+            assert_eq!(erc20.balance_of(contract), 0);
+            assert_eq!(erc20.finalize_auction(), Err(Error::NoAuction));
+        }
+
+        #[ink::test]
+        fn jackpot_pays_the_pool_on_a_winning_roll() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_jackpot_params(5_000, 100), Ok(()));
+            // 结果只取决于区块和转账参数, 可以预先挑出输赢各一笔
+            let roll = |value: Balance| erc20.jackpot_roll(accounts.alice, accounts.bob, value);
+            let losing = (10..)
+                .map(|i| i * 100)
+                .find(|value| roll(*value) >= 5_000)
+                .expect("no losing value");
+            let winning = (10..)
+                .map(|i| i * 100)
+                .find(|value| roll(*value) < 5_000)
+                .expect("no winning value");
+
+            assert_eq!(erc20.transfer(accounts.bob, losing), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), losing - losing / 100);
+            assert_eq!(erc20.jackpot_params().0, losing / 100);
+
+            let events = ink_env::test::recorded_events().count();
+            assert_eq!(erc20.transfer(accounts.bob, winning), Ok(()));
+            let pool = losing / 100 + winning / 100;
+            assert_eq!(erc20.jackpot_params().0, 0);
+            assert_eq!(
+                erc20.balance_of(accounts.alice),
+                100_000 - losing - winning + pool
+            );
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.balance_of(contract), 0);
+
+            let emitted: Vec<_> = ink_env::test::recorded_events()
+                .skip(events)
+                .map(|event| decode_event(&event))
+                .collect();
+            assert!(emitted.iter().any(|event| matches!(
+                event,
+                Event::JackpotContribution(JackpotContribution { from, amount })
+                    if *from == accounts.alice && *amount == winning / 100
+            )));
+            assert!(matches!(
+                emitted.last(),
+                Some(Event::JackpotWon(JackpotWon { winner, amount }))
+                    if *winner == accounts.alice && *amount == pool
+            ));
+        }
+
+        #[ink::test]
+        fn jackpot_params_are_bounded() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_jackpot_params(10_001, 0), Err(Error::InvalidBps));
+            assert_eq!(erc20.set_jackpot_params(0, 10_001), Err(Error::InvalidBps));
+
+            // 概率为 0 时奖池只进不出
+            assert_eq!(erc20.set_jackpot_params(0, 500), Ok(()));
+            for _ in 0..3 {
+                assert_eq!(erc20.transfer(accounts.bob, 1_000), Ok(()));
+            }
+            assert_eq!(erc20.jackpot_params(), (150, 0, 500));
+            assert_eq!(erc20.balance_of(accounts.bob), 2_850);
+
+            // 概率 100% 时下一笔转账必中
+            assert_eq!(erc20.set_jackpot_params(10_000, 0), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 50), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 2_800 + 150);
+            assert_eq!(erc20.set_jackpot_params(0, 0), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn otc_deal_settles_atomically() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.create_deal(accounts.bob, 1_000, 50, 10), Ok(0));
+            assert_eq!(erc20.balance_of(contract), 1_000);
+
+            set_caller_with_value(accounts.charlie, 50);
+            assert_eq!(erc20.accept_deal(0), Err(Error::NotDealCounterparty));
+            set_caller_with_value(accounts.bob, 49);
+            assert_eq!(erc20.accept_deal(0), Err(Error::IncorrectPayment));
+            set_caller_with_value(accounts.bob, 51);
+            assert_eq!(erc20.accept_deal(0), Err(Error::IncorrectPayment));
+
+            ink_env::test::set_account_balance::<ink_env::DefaultEnvironment>(contract, 50)
+                .expect("Cannot set contract balance");
+            let seller_before =
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.alice)
+                    .expect("Cannot get balance");
+            set_caller_with_value(accounts.bob, 50);
+            assert_eq!(erc20.accept_deal(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 1_000);
+            assert_eq!(erc20.balance_of(contract), 0);
+            assert_eq!(
+                ink_env::test::get_account_balance::<ink_env::DefaultEnvironment>(accounts.alice),
+                Ok(seller_before + 50)
+            );
+            assert_eq!(
+                erc20.deal(0).map(|deal| deal.status),
+                Some(DealStatus::Settled)
+            );
+
+            assert_eq!(erc20.accept_deal(0), Err(Error::DealAlreadySettled));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.cancel_deal(0), Err(Error::DealAlreadySettled));
+            assert_eq!(erc20.accept_deal(1), Err(Error::DealNotFound));
+
+            let last = ink_env::test::recorded_events().last().expect("no events");
+            assert!(matches!(
+                decode_event(&last),
+                Event::DealAccepted(DealAccepted { id: 0 })
+            ));
+        }
+
+        #[ink::test]
+        fn otc_deal_expires_and_can_be_cancelled() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.create_deal(accounts.bob, 1_000, 50, 5), Ok(0));
+            assert_eq!(erc20.create_deal(accounts.bob, 500, 20, 5), Ok(1));
+
+            // 创建者可以在到期前取消
+            assert_eq!(erc20.cancel_deal(1), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 9_000);
+            assert_eq!(
+                erc20.deal(1).map(|deal| deal.status),
+                Some(DealStatus::Cancelled)
+            );
+            set_caller_with_value(accounts.bob, 20);
+            assert_eq!(erc20.accept_deal(1), Err(Error::DealAlreadySettled));
+
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.cancel_deal(0), Err(Error::NotDealCreator));
+            advance_blocks(6);
+            set_caller_with_value(accounts.bob, 50);
+            assert_eq!(erc20.accept_deal(0), Err(Error::DealExpired));
+
+            // 过期后任何人都可以取消
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.cancel_deal(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 10_000);
+            let last = ink_env::test::recorded_events().last().expect("no events");
+            assert!(matches!(
+                decode_event(&last),
+                Event::DealCancelled(DealCancelled { id: 0, cancelled_by })
+                    if cancelled_by == accounts.charlie
+            ));
+
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.create_deal(accounts.bob, 1, 1, 5),
+                Err(Error::DealExpired)
+            );
+        }
+
+        #[ink::test]
+        fn account_activity_is_tracked_per_account() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let deployed = erc20
+                .last_activity_of(accounts.alice)
+                .expect("deployer not seeded");
+            assert_eq!(deployed.0, 0);
+            assert_eq!(erc20.last_activity_of(accounts.bob), None);
+
+            advance_blocks(3);
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            let (block, timestamp) = erc20
+                .last_activity_of(accounts.bob)
+                .expect("receive not tracked");
+            assert_eq!(block, 3);
+            assert!(timestamp > deployed.1);
+            assert_eq!(
+                erc20
+                    .last_activity_of(accounts.alice)
+                    .map(|(block, _)| block),
+                Some(3)
+            );
+
+            advance_blocks(2);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.approve(accounts.charlie, 5), Ok(()));
+            assert_eq!(
+                erc20.last_activity_of(accounts.bob).map(|(block, _)| block),
+                Some(5)
+            );
+            // 被授权不算活动
+            assert_eq!(erc20.last_activity_of(accounts.charlie), None);
+
+            advance_blocks(2);
+            assert_eq!(erc20.burn(1), Ok(()));
+            assert_eq!(
+                erc20.last_activity_of(accounts.bob).map(|(block, _)| block),
+                Some(7)
+            );
+        }
+
+        #[ink::test]
+        fn inactive_among_filters_by_age() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            advance_blocks(10);
+            assert_eq!(erc20.transfer(accounts.charlie, 10), Ok(()));
+            advance_blocks(5);
+
+            let candidates = vec![accounts.bob, accounts.charlie, accounts.alice, accounts.eve];
+            // bob 15 个区块未活动, alice 和 charlie 5 个, eve 从未活动
+            assert_eq!(
+                erc20.inactive_among(candidates.clone(), 5),
+                Ok(vec![accounts.bob, accounts.eve])
+            );
+            assert_eq!(
+                erc20.inactive_among(candidates.clone(), 4),
+                Ok(vec![
+                    accounts.bob,
+                    accounts.charlie,
+                    accounts.alice,
+                    accounts.eve
+                ])
+            );
+            assert_eq!(erc20.inactive_among(candidates, 15), Ok(vec![accounts.eve]));
+            assert_eq!(
+                erc20.inactive_among(vec![accounts.bob; MAX_BATCH_LEN + 1], 0),
+                Err(Error::BatchTooLarge)
+            );
+        }
+
+        #[ink::test]
+        fn activity_tracking_can_be_disabled_at_construction() {
+            let mut erc20 =
+                Erc20::new_with_features(1_000, DEFAULT_FEATURES & !FEATURE_ACTIVITY_TRACKING);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.last_activity_of(accounts.alice), None);
+            assert_eq!(erc20.last_activity_of(accounts.bob), None);
+            assert_eq!(
+                erc20.inactive_among(vec![accounts.bob], 0),
+                Err(Error::FeatureDisabled)
+            );
+        }
+
+        #[ink::test]
+        fn genesis_holder_pays_no_transfer_fee() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert!(erc20.is_genesis_nft_holder(accounts.alice));
+            assert_eq!(erc20.set_transfer_fee(100, accounts.eve), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 1_000), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 1_000);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 100), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.eve), 1);
+
+            // NFT 转给 bob 后免手续费的是 bob
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer_genesis_nft(accounts.bob), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 100), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.eve), 1);
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.charlie, 100), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.eve), 2);
+        }
+
+        #[ink::test]
+        fn genesis_holder_earns_double_staking_rewards() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.set_buyback_ratios(0, 10_000), Ok(()));
+            assert_eq!(erc20.stake(100), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stake(100), Ok(()));
+
+            // 权重 200 : 100, 奖励池不会超发
+            set_caller(accounts.alice);
+            assert_eq!(erc20.execute_buyback(300), Ok(()));
+            assert_eq!(erc20.pending_rewards(accounts.alice), 200);
+            assert_eq!(erc20.pending_rewards(accounts.bob), 100);
+
+            // 转让后已结算的奖励不变, 之后按新的倍数累计
+            assert_eq!(erc20.transfer_genesis_nft(accounts.bob), Ok(()));
+            assert_eq!(erc20.execute_buyback(300), Ok(()));
+            assert_eq!(erc20.pending_rewards(accounts.alice), 300);
+            assert_eq!(erc20.pending_rewards(accounts.bob), 300);
+        }
+
+        #[ink::test]
+        fn genesis_holder_can_emergency_withdraw_while_paused() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.stake(500), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stake(100), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.pause(), Ok(()));
+
+            assert_eq!(erc20.unstake(100), Err(Error::Paused));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.emergency_withdraw(100), Err(Error::NotGenesisHolder));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.emergency_withdraw(501), Err(Error::InsufficientStake));
+            assert_eq!(erc20.emergency_withdraw(500), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 9_900);
+            assert_eq!(erc20.stake_of(accounts.alice), 0);
+            assert_eq!(erc20.total_staked(), 100);
+            // bob 的质押仍在合约账户里
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.balance_of(contract), 100);
+        }
+
+        #[ink::test]
+        fn genesis_nft_transfer_is_holder_only() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.genesis_nft_holder(), accounts.alice);
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_genesis_nft(accounts.bob),
+                Err(Error::NotGenesisHolder)
+            );
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer_genesis_nft(accounts.bob), Ok(()));
+            assert!(erc20.is_genesis_nft_holder(accounts.bob));
+            assert!(!erc20.is_genesis_nft_holder(accounts.alice));
+            assert_eq!(
+                erc20.transfer_genesis_nft(accounts.charlie),
+                Err(Error::NotGenesisHolder)
+            );
+
+            let last = ink_env::test::recorded_events().last().expect("no events");
+            assert!(matches!(
+                decode_event(&last),
+                Event::GenesisNFTTransferred(GenesisNFTTransferred { from, to })
+                    if from == accounts.alice && to == accounts.bob
+            ));
+        }
+
+        #[ink::test]
+        fn stake_pool_yield_doubles_once_member_threshold_reached() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 1_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 1_000), Ok(()));
+            assert_eq!(erc20.create_stake_pool(2, 3, 1_000), Ok(0));
+
+            // 只有 bob 一人, 不足 2 人时收益减半
+            set_caller(accounts.bob);
+            assert_eq!(erc20.join_pool(0, 1_000), Ok(()));
+            assert_eq!(
+                erc20.stake_pool(0).map(|p| p.effective_yield_bps()),
+                Some(500)
+            );
+            advance_blocks(POOL_YIELD_PERIOD_BLOCKS);
+            assert_eq!(erc20.pool_rewards_of(0, accounts.bob), 50);
+
+            // charlie 加入后达到门槛, 按全额收益计息
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.join_pool(0, 1_000), Ok(()));
+            assert_eq!(
+                erc20.stake_pool(0).map(|p| p.effective_yield_bps()),
+                Some(1_000)
+            );
+            advance_blocks(POOL_YIELD_PERIOD_BLOCKS);
+            assert_eq!(erc20.pool_rewards_of(0, accounts.bob), 150);
+            assert_eq!(erc20.pool_rewards_of(0, accounts.charlie), 100);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.claim_pool_rewards(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 150);
+            assert_eq!(erc20.pool_rewards_of(0, accounts.bob), 0);
+            assert_eq!(erc20.claim_pool_rewards(0), Err(Error::NothingToWithdraw));
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            match decode_event(emitted_events.last().unwrap()) {
+                Event::PoolRewardsClaimed(PoolRewardsClaimed {
+                    pool_id,
+                    member,
+                    amount,
+                }) => {
+                    assert_eq!((pool_id, member, amount), (0, accounts.bob, 150));
+                }
+                _ => panic!("expected PoolRewardsClaimed"),
+            }
+
+            // charlie 退出时取回本金和收益, 人数回落后收益再次减半
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.leave_pool(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 1_100);
+            assert_eq!(erc20.leave_pool(0), Err(Error::NotPoolMember));
+            let pool = erc20.stake_pool(0).unwrap();
+            assert_eq!(pool.members, vec![accounts.bob]);
+            assert_eq!(pool.total_staked, 1_000);
+            assert_eq!(pool.effective_yield_bps(), 500);
+            advance_blocks(POOL_YIELD_PERIOD_BLOCKS);
+            assert_eq!(erc20.pool_rewards_of(0, accounts.bob), 50);
+        }
+
+        #[ink::test]
+        fn stake_pool_enforces_member_limits() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.create_stake_pool(0, 1, 100), Err(Error::InvalidPool));
+            assert_eq!(erc20.create_stake_pool(3, 2, 100), Err(Error::InvalidPool));
+            assert_eq!(
+                erc20.create_stake_pool(1, 2, 10_001),
+                Err(Error::InvalidBps)
+            );
+            assert_eq!(erc20.create_stake_pool(1, 1, 100), Ok(0));
+            assert_eq!(erc20.join_pool(1, 10), Err(Error::PoolNotFound));
+            assert_eq!(erc20.set_pool_min_contribution(0, 10), Ok(()));
+            // 空账户或小额不能凑人数
+            assert_eq!(erc20.join_pool(0, 0), Err(Error::ZeroAmount));
+            assert_eq!(erc20.join_pool(0, 9), Err(Error::BelowMinContribution));
+            assert!(erc20.stake_pool(0).unwrap().members.is_empty());
+            assert_eq!(erc20.join_pool(0, 10), Ok(()));
+            // 已是成员时追加份额, 不占新的名额, 也不受最低数量限制
+            assert_eq!(erc20.join_pool(0, 0), Err(Error::ZeroAmount));
+            assert_eq!(erc20.join_pool(0, 1), Ok(()));
+            assert_eq!(erc20.join_pool(0, 9), Ok(()));
+            assert_eq!(erc20.stake_pool(0).map(|p| p.total_staked), Some(20));
+            assert_eq!(erc20.balance_of(accounts.alice), 9_980);
+
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.create_stake_pool(1, 1, 100), Err(Error::NotOwner));
+            assert_eq!(erc20.set_pool_min_contribution(0, 1), Err(Error::NotOwner));
+            assert_eq!(erc20.join_pool(0, 10), Err(Error::PoolFull));
+            assert_eq!(erc20.balance_of(accounts.bob), 10);
+            assert_eq!(erc20.claim_pool_rewards(0), Err(Error::NotPoolMember));
+        }
+
+        #[ink::test]
+        fn stake_pool_accepts_18_decimal_amounts() {
+            let unit = 1_000_000_000_000_000_000;
+            let mut erc20 = Erc20::new(10_000 * unit);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.create_stake_pool(1, 2, 1_000), Ok(0));
+
+            // 远超 u64::MAX 的数量也按 1:1 记份额
+            assert_eq!(erc20.join_pool(0, 1_000 * unit), Ok(()));
+            let pool = erc20.stake_pool(0).unwrap();
+            assert_eq!(pool.shares.get(&accounts.alice), Some(&(1_000 * unit)));
+            assert_eq!(pool.total_staked, 1_000 * unit);
+            advance_blocks(POOL_YIELD_PERIOD_BLOCKS);
+            assert_eq!(erc20.pool_rewards_of(0, accounts.alice), 100 * unit);
+
+            assert_eq!(erc20.leave_pool(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 10_100 * unit);
+        }
+
+        #[ink::test]
+        fn approve_on_behalf_requires_onboarder_and_consent() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, owner) = test_signer(11);
+            let hash = erc20.approval_on_behalf_hash(owner, accounts.eve, 500);
+            let consent = sign_hash(&secret, hash.as_ref());
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.approve_on_behalf(owner, accounts.eve, 500, consent),
+                Err(Error::NotOnboarder)
+            );
+            assert_eq!(erc20.add_onboarder(accounts.bob), Err(Error::NotOwner));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.add_onboarder(accounts.bob), Ok(()));
+            assert!(erc20.is_onboarder(accounts.bob));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.approve_on_behalf(owner, accounts.eve, 501, consent),
+                Err(Error::InvalidPermitSignature)
+            );
+            assert_eq!(
+                erc20.approve_on_behalf(accounts.charlie, accounts.eve, 500, consent),
+                Err(Error::InvalidPermitSignature)
+            );
+            assert_eq!(erc20.allowance(owner, accounts.eve), 0);
+            assert_eq!(
+                erc20.approve_on_behalf(owner, accounts.eve, 500, consent),
+                Ok(())
+            );
+            assert_eq!(erc20.allowance(owner, accounts.eve), 500);
+            assert_eq!(erc20.permit_nonce(owner), 1);
+            // nonce 已经变化, 同一个签名不能重放
+            assert_eq!(
+                erc20.approve_on_behalf(owner, accounts.eve, 500, consent),
+                Err(Error::InvalidPermitSignature)
+            );
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.remove_onboarder(accounts.bob), Ok(()));
+            set_caller(accounts.bob);
+            let hash = erc20.approval_on_behalf_hash(owner, accounts.eve, 0);
+            let consent = sign_hash(&secret, hash.as_ref());
+            assert_eq!(
+                erc20.approve_on_behalf(owner, accounts.eve, 0, consent),
+                Err(Error::NotOnboarder)
+            );
+        }
+
+        #[ink::test]
+        fn approve_on_behalf_emits_distinct_event() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, owner) = test_signer(12);
+            assert_eq!(erc20.add_onboarder(accounts.bob), Ok(()));
+            let hash = erc20.approval_on_behalf_hash(owner, accounts.eve, 300);
+            let consent = sign_hash(&secret, hash.as_ref());
+            // permit 的签名不能当作开户同意提交
+            let permit_hash = erc20.permit_hash(owner, accounts.eve, 300, 10);
+            let permit_sig = sign_hash(&secret, permit_hash.as_ref());
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.approve_on_behalf(owner, accounts.eve, 300, permit_sig),
+                Err(Error::InvalidPermitSignature)
+            );
+            assert_eq!(
+                erc20.approve_on_behalf(owner, accounts.eve, 300, consent),
+                Ok(())
+            );
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            let n = emitted_events.len();
+            assert!(matches!(
+                decode_event(&emitted_events[n - 2]),
+                Event::Approval(Approval { value: 300, .. })
+            ));
+            match decode_event(&emitted_events[n - 1]) {
+                Event::ApprovalOnBehalf(ApprovalOnBehalf {
+                    owner: o,
+                    spender,
+                    value,
+                    onboarder,
+                }) => {
+                    assert_eq!(o, owner);
+                    assert_eq!(spender, accounts.eve);
+                    assert_eq!(value, 300);
+                    assert_eq!(onboarder, accounts.bob);
+                }
+                _ => panic!("expected ApprovalOnBehalf"),
+            }
+        }
+
+        #[ink::test]
+        fn reputation_tracks_loan_outcomes() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            // 借出后马上还清不加分
+            assert_eq!(erc20.create_loan_offer(200, 0, 0, 5), Ok(0));
+            set_caller_with_value(accounts.bob, 0);
+            assert_eq!(erc20.accept_loan(0), Ok(()));
+            assert_eq!(erc20.repay_loan(0), Ok(()));
+            assert_eq!(erc20.reputation_score_of(accounts.bob), 0);
+
+            // 本金太小也不加分
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.create_loan_offer(0, 0, 0, MIN_REPUTATION_LOAN_BLOCKS),
+                Ok(1)
+            );
+            assert_eq!(
+                erc20.create_loan_offer(200, 0, 0, MIN_REPUTATION_LOAN_BLOCKS),
+                Ok(2)
+            );
+            set_caller_with_value(accounts.bob, 0);
+            assert_eq!(erc20.accept_loan(1), Ok(()));
+            assert_eq!(erc20.accept_loan(2), Ok(()));
+            advance_blocks(MIN_REPUTATION_LOAN_BLOCKS);
+            assert_eq!(erc20.repay_loan(1), Ok(()));
+            assert_eq!(erc20.reputation_score_of(accounts.bob), 0);
+            assert_eq!(erc20.repay_loan(2), Ok(()));
+            assert_eq!(erc20.reputation_score_of(accounts.bob), 1_000);
+            assert_eq!(
+                erc20.reputation_history(accounts.bob, 10),
+                vec![ReputationEvent {
+                    action: ReputationAction::CompletedLoan,
+                    block: MIN_REPUTATION_LOAN_BLOCKS,
+                    delta: 1_000,
+                }]
+            );
+
+            // 违约扣分, 最低为 0
+            set_caller(accounts.alice);
+            assert_eq!(erc20.create_loan_offer(200, 0, 0, 5), Ok(3));
+            set_caller_with_value(accounts.bob, 0);
+            assert_eq!(erc20.accept_loan(3), Ok(()));
+            advance_blocks(6);
+            set_caller(accounts.alice);
+            assert_eq!(erc20.liquidate_loan(3), Ok(()));
+            assert_eq!(erc20.reputation_score_of(accounts.bob), 0);
+            assert_eq!(
+                erc20.reputation_history(accounts.bob, 1),
+                vec![ReputationEvent {
+                    action: ReputationAction::Defaulted,
+                    block: MIN_REPUTATION_LOAN_BLOCKS + 6,
+                    delta: -5_000,
+                }]
+            );
+            assert_eq!(erc20.reputation_history(accounts.bob, 10).len(), 2);
+            assert_eq!(erc20.reputation_score_of(accounts.alice), 0);
+        }
+
+        #[ink::test]
+        fn reputation_tracks_proposal_outcomes() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            for voter in [accounts.charlie, accounts.django, accounts.eve].iter() {
+                assert_eq!(erc20.transfer(*voter, 100), Ok(()));
+            }
+            let passing = erc20
+                .propose(Hash::from([0x01; 32]), 2)
+                .expect("propose failed");
+            // 只有提案人自己投票, 通过也不加分
+            let lonely = erc20
+                .propose(Hash::from([0x03; 32]), 2)
+                .expect("propose failed");
+            advance_blocks(1);
+            assert_eq!(erc20.cast_vote(passing, true), Ok(()));
+            assert_eq!(erc20.cast_vote(lonely, true), Ok(()));
+            for voter in [accounts.charlie, accounts.django, accounts.eve].iter() {
+                set_caller(*voter);
+                assert_eq!(erc20.cast_vote(passing, true), Ok(()));
+            }
+            set_caller(accounts.bob);
+            let failing = erc20
+                .propose(Hash::from([0x02; 32]), 2)
+                .expect("propose failed");
+            advance_blocks(1);
+            set_caller(accounts.alice);
+            assert_eq!(erc20.cast_vote(failing, false), Ok(()));
+            assert_eq!(erc20.finalize_proposal(passing), Err(Error::VotingOpen));
+
+            advance_blocks(3);
+            assert_eq!(erc20.proposal(passing).map(|p| p.voters), Some(3));
+            assert_eq!(erc20.finalize_proposal(passing), Ok(()));
+            assert_eq!(erc20.finalize_proposal(lonely), Ok(()));
+            assert_eq!(erc20.finalize_proposal(failing), Ok(()));
+            assert_eq!(
+                erc20.finalize_proposal(passing),
+                Err(Error::ProposalAlreadyFinalized)
+            );
+            assert_eq!(erc20.finalize_proposal(9), Err(Error::ProposalNotFound));
+            assert_eq!(erc20.proposal(passing).map(|p| p.executed), Some(true));
+
+            assert_eq!(erc20.reputation_score_of(accounts.alice), 2_000);
+            assert_eq!(erc20.reputation_history(accounts.alice, 10).len(), 1);
+            assert_eq!(
+                erc20.reputation_history(accounts.alice, 10)[0].action,
+                ReputationAction::ProposalPassed
+            );
+            assert_eq!(erc20.reputation_score_of(accounts.bob), 0);
+            assert_eq!(
+                erc20.reputation_history(accounts.bob, 10)[0].action,
+                ReputationAction::ProposalFailed
+            );
+            // 2000 分获得 2 个基点的投票加成
+            assert_eq!(erc20.get_votes(accounts.alice), 700);
+            assert_eq!(erc20.mint(accounts.alice, 9_300), Ok(()));
+            assert_eq!(erc20.get_votes(accounts.alice), 10_002);
+        }
+
+        #[ink::test]
+        fn long_term_stakers_earn_reputation_and_fee_discount() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+            assert_eq!(erc20.set_transfer_fee(100, accounts.django), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stake(10_000), Ok(()));
+            advance_blocks(10);
+            assert_eq!(erc20.unstake(5_000), Ok(()));
+            assert_eq!(erc20.reputation_score_of(accounts.bob), 0);
+
+            advance_blocks(LONG_TERM_HOLDER_BLOCKS - 10);
+            assert_eq!(erc20.unstake(5_000), Ok(()));
+            assert_eq!(erc20.reputation_score_of(accounts.bob), 3_000);
+            assert_eq!(
+                erc20.reputation_history(accounts.bob, 10)[0].action,
+                ReputationAction::LongTermHolder
+            );
+
+            // 3000 分减免 3 个基点: 手续费率 97 个基点
+            assert_eq!(erc20.transfer(accounts.eve, 10_000), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.django), 97);
+            assert_eq!(erc20.balance_of(accounts.eve), 9_903);
+        }
+
+        #[ink::test]
+        fn reputation_bonus_and_history_are_capped() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            for _ in 0..60 {
+                assert_eq!(
+                    erc20.update_reputation(accounts.bob, ReputationAction::LongTermHolder),
+                    Ok(())
+                );
+            }
+            assert_eq!(erc20.reputation_score_of(accounts.bob), 180_000);
+            assert_eq!(
+                erc20.reputation_bonus_bps(accounts.bob),
+                MAX_REPUTATION_BONUS_BPS
+            );
+            assert_eq!(
+                erc20.reputation_history(accounts.bob, u8::MAX).len(),
+                MAX_REPUTATION_EVENTS
+            );
+            assert_eq!(erc20.reputation_history(accounts.bob, 0), vec![]);
+        }
+
+        #[ink::test]
+        fn fee_strategy_compute_fee_covers_each_variant() {
+            assert_eq!(FeeStrategy::None.compute_fee(0), Ok(0));
+            assert_eq!(FeeStrategy::None.compute_fee(Balance::MAX), Ok(0));
+
+            let bps = FeeStrategy::Bps(100);
+            assert_eq!(bps.compute_fee(0), Ok(0));
+            assert_eq!(bps.compute_fee(99), Ok(0));
+            assert_eq!(bps.compute_fee(10_000), Ok(100));
+            assert!(bps.compute_fee(Balance::MAX).is_ok());
+            assert_eq!(
+                FeeStrategy::Bps(10_000).compute_fee(Balance::MAX),
+                Ok(Balance::MAX)
+            );
+            assert_eq!(bps.compute_discounted_fee(10_000, 30), Ok(70));
+            assert_eq!(bps.compute_discounted_fee(10_000, 200), Ok(0));
+
+            // 固定手续费不能超过转账金额, 恰好相等时可以
+            let flat = FeeStrategy::FlatPerTransfer(5);
+            assert_eq!(flat.compute_fee(5), Ok(5));
+            assert_eq!(flat.compute_fee(1_000), Ok(5));
+            assert_eq!(flat.compute_fee(4), Err(Error::FeeExceedsValue));
+            assert_eq!(flat.compute_fee(0), Err(Error::FeeExceedsValue));
+            assert_eq!(flat.compute_discounted_fee(1_000, 50), Ok(5));
+            assert_eq!(FeeStrategy::FlatPerTransfer(0).compute_fee(0), Ok(0));
+        }
+
+        #[ink::test]
+        fn tiered_fee_uses_highest_threshold_not_exceeding_value() {
+            let tiered = FeeStrategy::Tiered(vec![(1_000, 100), (10_000, 50), (100_000, 10)]);
+            assert_eq!(tiered.compute_fee(0), Ok(0));
+            assert_eq!(tiered.compute_fee(999), Ok(0));
+            assert_eq!(tiered.compute_fee(1_000), Ok(10));
+            assert_eq!(tiered.compute_fee(9_999), Ok(99));
+            assert_eq!(tiered.compute_fee(10_000), Ok(50));
+            assert_eq!(tiered.compute_fee(99_999), Ok(499));
+            assert_eq!(tiered.compute_fee(100_000), Ok(100));
+            assert!(tiered.compute_fee(Balance::MAX).is_ok());
+
+            let from_zero = FeeStrategy::Tiered(vec![(0, 200)]);
+            assert_eq!(from_zero.compute_fee(0), Ok(0));
+            assert_eq!(from_zero.compute_fee(500), Ok(10));
+            assert_eq!(FeeStrategy::Tiered(Vec::new()).compute_fee(500), Ok(0));
+        }
+
+        #[ink::test]
+        fn fee_strategy_applies_to_transfers() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.set_transfer_fee(0, accounts.django), Ok(()));
+            assert_eq!(
+                erc20.set_fee_strategy(FeeStrategy::Tiered(vec![(10, 100), (10, 200)])),
+                Err(Error::InvalidFeeTiers)
+            );
+            assert_eq!(
+                erc20.set_fee_strategy(FeeStrategy::Tiered(vec![(10, 100), (5, 200)])),
+                Err(Error::InvalidFeeTiers)
+            );
+            assert_eq!(
+                erc20.set_fee_strategy(FeeStrategy::Tiered(vec![(0, 10_001)])),
+                Err(Error::InvalidBps)
+            );
+            assert_eq!(
+                erc20.set_fee_strategy(FeeStrategy::Bps(10_001)),
+                Err(Error::InvalidBps)
+            );
+            assert_eq!(
+                erc20.set_fee_strategy(FeeStrategy::Tiered(vec![(0, 1); MAX_FEE_TIERS + 1])),
+                Err(Error::InvalidFeeTiers)
+            );
+            assert_eq!(
+                erc20.set_fee_strategy(FeeStrategy::FlatPerTransfer(3)),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.transfer_fee(),
+                (FeeStrategy::FlatPerTransfer(3), accounts.django)
+            );
+            assert_eq!(erc20.fee_quote(10), Ok(3));
+            assert_eq!(erc20.fee_quote(2), Err(Error::FeeExceedsValue));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.set_fee_strategy(FeeStrategy::None),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(erc20.transfer(accounts.eve, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.eve), 7);
+            assert_eq!(erc20.balance_of(accounts.django), 3);
+            assert_eq!(erc20.transfer(accounts.eve, 2), Err(Error::FeeExceedsValue));
+            assert_eq!(erc20.balance_of(accounts.bob), 90);
+            assert_eq!(erc20.balance_of(accounts.eve), 7);
+        }
+
+        fn document_hash(document: &[u8]) -> Hash {
+            let mut output = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(document, &mut output);
+            Hash::from(output)
+        }
+
+        #[ink::test]
+        fn whitepaper_verifies_committed_document() {
+            let document = b"erc20 whitepaper v1".to_vec();
+            let erc20 = Erc20::new_with_whitepaper(
+                1_000,
+                DEFAULT_FEATURES,
+                document_hash(&document),
+                String::from("ipfs://whitepaper-v1"),
+            );
+            assert_eq!(erc20.whitepaper_hash(), document_hash(&document));
+            assert_eq!(erc20.whitepaper_uri(), String::from("ipfs://whitepaper-v1"));
+            assert!(erc20.verify_whitepaper(document));
+            assert!(!erc20.verify_whitepaper(b"erc20 whitepaper v2".to_vec()));
+            assert!(!erc20.verify_whitepaper(Vec::new()));
+
+            // 未提交白皮书时哈希为全零
+            let erc20 = Erc20::new(1_000);
+            assert_eq!(erc20.whitepaper_hash(), Hash::default());
+            assert!(!erc20.verify_whitepaper(b"erc20 whitepaper v1".to_vec()));
+        }
+
+        #[ink::test]
+        fn whitepaper_update_waits_for_timelock() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let document = b"erc20 whitepaper v2".to_vec();
+            let new_hash = document_hash(&document);
+            let new_uri = String::from("ipfs://whitepaper-v2");
+            assert_eq!(
+                erc20.execute_whitepaper_update(),
+                Err(Error::NoPendingWhitepaper)
+            );
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.update_whitepaper(new_hash, new_uri.clone()),
+                Err(Error::NotOwner)
+            );
+            set_caller(accounts.alice);
+            assert_eq!(erc20.update_whitepaper(new_hash, new_uri.clone()), Ok(()));
+            assert_eq!(
+                erc20.pending_whitepaper(),
+                Some((new_hash, new_uri.clone(), WHITEPAPER_TIMELOCK_BLOCKS))
+            );
+            advance_blocks(WHITEPAPER_TIMELOCK_BLOCKS - 1);
+            assert_eq!(
+                erc20.execute_whitepaper_update(),
+                Err(Error::TimelockNotExpired)
+            );
+            assert!(!erc20.verify_whitepaper(document.clone()));
+
+            advance_blocks(1);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.execute_whitepaper_update(), Ok(()));
+            assert!(erc20.verify_whitepaper(document));
+            assert_eq!(erc20.whitepaper_uri(), new_uri);
+            assert_eq!(erc20.pending_whitepaper(), None);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            match decode_event(emitted_events.last().unwrap()) {
+                Event::WhitepaperUpdated(WhitepaperUpdated {
+                    old_hash,
+                    new_hash: updated,
+                    new_uri: uri,
+                }) => {
+                    assert_eq!(old_hash, Hash::default());
+                    assert_eq!(updated, new_hash);
+                    assert_eq!(uri, new_uri);
+                }
+                _ => panic!("expected WhitepaperUpdated"),
+            }
+        }
+
+        #[ink::test]
+        fn migrate_account_moves_balance_and_allowances() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, old) = test_signer(13);
+            let new = accounts.eve;
+            assert_eq!(erc20.transfer(old, 300), Ok(()));
+            set_caller(old);
+            assert_eq!(erc20.approve(accounts.bob, 50), Ok(()));
+            assert_eq!(erc20.approve_single_use(accounts.charlie, 20), Ok(()));
+
+            let wrong_hash = erc20.migration_hash(old, accounts.django);
+            let wrong = sign_hash(&secret, wrong_hash.as_ref());
+            assert_eq!(
+                erc20.migrate_account(new, wrong),
+                Err(Error::InvalidPermitSignature)
+            );
+            let hash = erc20.migration_hash(old, new);
+            let signature = sign_hash(&secret, hash.as_ref());
+            assert_eq!(
+                erc20.migrate_account(old, signature),
+                Err(Error::InvalidMigration)
+            );
+            assert_eq!(erc20.migrate_account(new, signature), Ok(()));
+
+            assert_eq!(erc20.balance_of(old), 0);
+            assert_eq!(erc20.balance_of(new), 300);
+            assert_eq!(erc20.allowance(old, accounts.bob), 0);
+            assert_eq!(erc20.allowance(old, accounts.charlie), 0);
+            assert_eq!(erc20.allowance(new, accounts.bob), 50);
+            assert_eq!(erc20.allowance(new, accounts.charlie), 20);
+            assert_eq!(
+                erc20.allowance_kind(new, accounts.bob),
+                AllowanceKind::Standard
+            );
+            assert_eq!(
+                erc20.allowance_kind(new, accounts.charlie),
+                AllowanceKind::SingleUse
+            );
+            assert_eq!(erc20.approved_spenders(old), Vec::<AccountId>::new());
+            assert_eq!(
+                erc20.approved_spenders(new),
+                vec![accounts.bob, accounts.charlie]
+            );
+            let zeroed = ink_env::test::recorded_events()
+                .filter(|e| {
+                    matches!(
+                        decode_event(e),
+                        Event::Approval(Approval { owner, value: 0, .. }) if owner == old
+                    )
+                })
+                .count();
+            assert_eq!(zeroed, 2);
+
+            // nonce 已经变化, 同一个签名不能重放
+            assert_eq!(
+                erc20.migrate_account(new, signature),
+                Err(Error::InvalidPermitSignature)
+            );
+        }
+
+        #[ink::test]
+        fn migrated_account_forwards_credits_within_window() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, old) = test_signer(14);
+            let new = accounts.eve;
+            assert_eq!(erc20.transfer(old, 100), Ok(()));
+            set_caller(old);
+            let hash = erc20.migration_hash(old, new);
+            assert_eq!(
+                erc20.migrate_account(new, sign_hash(&secret, hash.as_ref())),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.forwarding_of(old),
+                Some((new, MIGRATION_FORWARDING_BLOCKS))
+            );
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(old, 10), Ok(()));
+            assert_eq!(erc20.mint(old, 5), Ok(()));
+            assert_eq!(erc20.balance_of(old), 0);
+            assert_eq!(erc20.balance_of(new), 115);
+
+            advance_blocks(MIGRATION_FORWARDING_BLOCKS);
+            assert_eq!(erc20.transfer(old, 10), Ok(()));
+            assert_eq!(erc20.balance_of(new), 125);
+            // 转发期结束后旧账户正常收款
+            advance_blocks(1);
+            assert_eq!(erc20.transfer(old, 10), Ok(()));
+            assert_eq!(erc20.balance_of(old), 10);
+            assert_eq!(erc20.balance_of(new), 125);
+        }
+
+        #[ink::test]
+        fn batch_burn_burns_from_each_account() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 50), Ok(()));
+            let events_before = ink_env::test::recorded_events().count();
+
+            assert_eq!(
+                erc20.batch_burn(vec![accounts.bob, accounts.charlie], vec![60, 50]),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 40);
+            assert_eq!(erc20.balance_of(accounts.charlie), 0);
+            assert_eq!(erc20.total_supply(), 890);
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert_eq!(emitted_events.len(), events_before + 3);
+            assert_transfer_event(
+                &emitted_events[events_before],
+                Some(accounts.bob),
+                None,
+                60,
+                TransferKind::Burn,
+            );
+            match decode_event(&emitted_events[events_before + 2]) {
+                Event::BatchBurnExecuted(BatchBurnExecuted {
+                    accounts_count,
+                    total_burned,
+                }) => {
+                    assert_eq!(accounts_count, 2);
+                    assert_eq!(total_burned, 110);
+                }
+                _ => panic!("expected BatchBurnExecuted"),
+            }
+        }
+
+        #[ink::test]
+        fn batch_burn_rejects_whole_batch_on_any_failure() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 50), Ok(()));
+            let events_before = ink_env::test::recorded_events().count();
+
+            assert_eq!(
+                erc20.batch_burn(vec![accounts.bob, accounts.charlie], vec![60, 51]),
+                Err(Error::InsufficientBalance)
+            );
+            // 同一账户出现多次时按合计检查
+            assert_eq!(
+                erc20.batch_burn(vec![accounts.bob, accounts.bob], vec![60, 60]),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(
+                erc20.batch_burn(vec![accounts.bob, accounts.charlie], vec![60]),
+                Err(Error::LengthMismatch)
+            );
+            assert_eq!(
+                erc20.batch_burn(
+                    vec![accounts.bob; MAX_BATCH_LEN + 1],
+                    vec![0; MAX_BATCH_LEN + 1]
+                ),
+                Err(Error::BatchTooLarge)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.batch_burn(vec![accounts.bob], vec![1]),
+                Err(Error::NotOwner)
+            );
+
+            assert_eq!(erc20.balance_of(accounts.bob), 100);
+            assert_eq!(erc20.balance_of(accounts.charlie), 50);
+            assert_eq!(erc20.total_supply(), 1_000);
+            assert_eq!(ink_env::test::recorded_events().count(), events_before);
+        }
+
+        // bob 和 charlie 各质押 1000
+        fn setup_two_stakers(erc20: &mut Erc20) {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 1_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 1_000), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stake(1_000), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.stake(1_000), Ok(()));
+            set_caller(accounts.alice);
+        }
+
+        #[ink::test]
+        fn unstake_above_instant_limit_is_queued() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            setup_two_stakers(&mut erc20);
+            assert_eq!(erc20.set_instant_withdrawal_limit(Some(100)), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.set_instant_withdrawal_limit(None),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(erc20.unstake(100), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 100);
+            assert_eq!(erc20.withdrawal_queue_len(), 0);
+
+            assert_eq!(erc20.unstake(300), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 100);
+            assert_eq!(erc20.stake_of(accounts.bob), 600);
+            assert_eq!(erc20.total_staked(), 1_600);
+            assert_eq!(erc20.withdrawal_queue_len(), 1);
+            assert_eq!(erc20.withdrawal_position(accounts.bob), Some(0));
+            assert_eq!(erc20.withdrawal_position(accounts.charlie), None);
+            assert_eq!(
+                erc20.withdrawal_request(0),
+                Some(WithdrawalRequest {
+                    who: accounts.bob,
+                    amount: 300,
+                    requested_at: 0,
+                })
+            );
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_instant_withdrawal_limit(None), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.unstake(600), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 700);
+            assert_eq!(erc20.withdrawal_queue_len(), 1);
+        }
+
+        #[ink::test]
+        fn queued_withdrawals_are_processed_in_order() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            setup_two_stakers(&mut erc20);
+            assert_eq!(erc20.set_instant_withdrawal_limit(Some(0)), Ok(()));
+            assert_eq!(
+                erc20.set_task_bounty(
+                    TaskKind::WithdrawalQueue,
+                    Bounty::Bps(1_000),
+                    BountySource::Escrow
+                ),
+                Ok(())
+            );
+            set_caller(accounts.bob);
+            assert_eq!(erc20.unstake(100), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.unstake(200), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.unstake(50), Ok(()));
+            assert_eq!(erc20.withdrawal_queue_len(), 3);
+            assert_eq!(erc20.withdrawal_position(accounts.charlie), Some(1));
+
+            // 每次只处理一部分, 赏金为 10%
+            set_caller(accounts.eve);
+            assert_eq!(erc20.process_withdrawals(2), Ok(2));
+            assert_eq!(erc20.balance_of(accounts.bob), 90);
+            assert_eq!(erc20.balance_of(accounts.charlie), 180);
+            assert_eq!(erc20.balance_of(accounts.eve), 30);
+            assert_eq!(erc20.withdrawal_queue_len(), 1);
+            assert_eq!(erc20.withdrawal_position(accounts.bob), Some(0));
+            assert_eq!(erc20.withdrawal_position(accounts.charlie), None);
+
+            assert_eq!(erc20.process_withdrawals(5), Ok(1));
+            assert_eq!(erc20.balance_of(accounts.bob), 135);
+            assert_eq!(erc20.balance_of(accounts.eve), 35);
+            assert_eq!(erc20.process_withdrawals(5), Ok(0));
+            assert_eq!(erc20.withdrawal_queue_len(), 0);
+        }
+
+        #[ink::test]
+        fn queued_withdrawal_can_be_cancelled_by_owner() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            setup_two_stakers(&mut erc20);
+            assert_eq!(erc20.set_instant_withdrawal_limit(Some(0)), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.unstake(100), Ok(()));
+            assert_eq!(erc20.unstake(200), Ok(()));
+            assert_eq!(erc20.stake_of(accounts.bob), 700);
+
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.cancel_withdrawal(0), Err(Error::NotWithdrawalOwner));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.cancel_withdrawal(0), Ok(()));
+            assert_eq!(erc20.cancel_withdrawal(0), Err(Error::WithdrawalNotFound));
+            assert_eq!(erc20.stake_of(accounts.bob), 800);
+            assert_eq!(erc20.total_staked(), 1_800);
+            assert_eq!(erc20.withdrawal_queue_len(), 1);
+            assert_eq!(erc20.withdrawal_position(accounts.bob), Some(0));
+
+            // 处理时跳过被取消的空位
+            assert_eq!(erc20.process_withdrawals(1), Ok(1));
+            assert_eq!(erc20.balance_of(accounts.bob), 200);
+            assert_eq!(erc20.withdrawal_queue_len(), 0);
+        }
+
+        #[ink::test]
+        fn instant_withdrawal_limit_covers_all_payouts_per_period() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            setup_two_stakers(&mut erc20);
+            assert_eq!(erc20.set_buyback_ratios(0, 10_000), Ok(()));
+            assert_eq!(erc20.execute_buyback(400), Ok(()));
+            assert_eq!(erc20.set_instant_withdrawal_limit(Some(250)), Ok(()));
+
+            // 分多次解除质押也不能超过本周期的额度
+            set_caller(accounts.bob);
+            assert_eq!(erc20.unstake(100), Ok(()));
+            assert_eq!(erc20.unstake(100), Ok(()));
+            assert_eq!(erc20.instant_withdrawal_remaining(), Some(50));
+            assert_eq!(erc20.unstake(100), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 200);
+            assert_eq!(erc20.withdrawal_queue_len(), 1);
+
+            // 领取奖励共用同一个额度
+            let rewards = erc20.pending_rewards(accounts.bob);
+            assert!(rewards > 50);
+            assert_eq!(erc20.claim_rewards(), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 200);
+            assert_eq!(erc20.withdrawal_queue_len(), 2);
+            assert_eq!(erc20.obligation(PoolId::WithdrawalQueue), 100 + rewards);
+
+            // 下一个周期额度恢复
+            advance_blocks(WITHDRAWAL_PERIOD_BLOCKS);
+            assert_eq!(erc20.instant_withdrawal_remaining(), Some(250));
+            assert_eq!(erc20.unstake(100), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 300);
+
+            assert_eq!(erc20.process_withdrawals(5), Ok(2));
+            assert_eq!(erc20.balance_of(accounts.bob), 400 + rewards);
+            assert_eq!(erc20.obligation(PoolId::WithdrawalQueue), 0);
+        }
+
+        #[ink::test]
+        fn withdrawal_queue_is_bounded() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            setup_two_stakers(&mut erc20);
+            assert_eq!(erc20.set_instant_withdrawal_limit(Some(0)), Ok(()));
+            set_caller(accounts.bob);
+            for _ in 0..MAX_QUEUED_WITHDRAWALS {
+                assert_eq!(erc20.unstake(1), Ok(()));
+            }
+            assert_eq!(erc20.unstake(1), Err(Error::WithdrawalQueueFull));
+            assert_eq!(erc20.stake_of(accounts.bob), 900);
+            assert_eq!(erc20.process_withdrawals(1), Ok(1));
+            assert_eq!(erc20.unstake(1), Ok(()));
+        }
+
+        fn tax_entry(start_block: u32, end_block: u32, fee_rate_bps: u16) -> TaxScheduleEntry {
+            TaxScheduleEntry {
+                start_block,
+                end_block,
+                fee_rate_bps,
+            }
+        }
+
+        #[ink::test]
+        fn tax_schedule_selects_rate_for_current_block() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+            assert_eq!(erc20.set_transfer_fee(100, accounts.django), Ok(()));
+            assert_eq!(
+                erc20.set_tax_schedule(vec![
+                    tax_entry(10, 20, 500),
+                    tax_entry(20, 30, 0),
+                    tax_entry(40, 50, 1_000),
+                ]),
+                Ok(())
+            );
+
+            // 区间左闭右开, 没有覆盖的区块回落到 fee_strategy
+            let expected = [
+                (0, 100),
+                (9, 100),
+                (10, 500),
+                (19, 500),
+                (20, 0),
+                (29, 0),
+                (30, 100),
+                (40, 1_000),
+                (49, 1_000),
+                (50, 100),
+            ];
+            let mut block = 0;
+            for (at, rate) in expected.iter() {
+                advance_blocks(at - block);
+                block = *at;
+                assert_eq!(erc20.current_effective_tax_rate(), *rate, "block {}", at);
+            }
+
+            // 转账使用时间表中的费率
+            assert_eq!(erc20.set_tax_schedule(vec![tax_entry(50, 60, 500)]), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.eve, 1_000), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.django), 50);
+            assert_eq!(erc20.balance_of(accounts.eve), 950);
+            advance_blocks(10);
+            assert_eq!(erc20.transfer(accounts.eve, 1_000), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.django), 60);
+        }
+
+        #[ink::test]
+        fn tax_schedule_validation_and_events() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(
+                erc20.set_tax_schedule(vec![tax_entry(10, 10, 100)]),
+                Err(Error::InvalidTaxSchedule)
+            );
+            assert_eq!(
+                erc20.set_tax_schedule(vec![tax_entry(10, 20, 100), tax_entry(15, 30, 100)]),
+                Err(Error::InvalidTaxSchedule)
+            );
+            assert_eq!(
+                erc20.set_tax_schedule(vec![tax_entry(20, 30, 100), tax_entry(0, 10, 100)]),
+                Err(Error::InvalidTaxSchedule)
+            );
+            assert_eq!(
+                erc20.set_tax_schedule(vec![tax_entry(0, 1, 100); MAX_TAX_SCHEDULE_ENTRIES + 1]),
+                Err(Error::InvalidTaxSchedule)
+            );
+            assert_eq!(
+                erc20.set_tax_schedule(vec![tax_entry(0, 10, 10_001)]),
+                Err(Error::InvalidBps)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_tax_schedule(Vec::new()), Err(Error::NotOwner));
+
+            set_caller(accounts.alice);
+            let events_before = ink_env::test::recorded_events().count();
+            assert_eq!(
+                erc20.set_tax_schedule(vec![tax_entry(0, 5, 300), tax_entry(5, 10, 200)]),
+                Ok(())
+            );
+            assert_eq!(erc20.tax_schedule().len(), 2);
+            advance_blocks(5);
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+
+            let emitted_events = ink_env::test::recorded_events()
+                .skip(events_before)
+                .map(|e| decode_event(&e))
+                .collect::<Vec<_>>();
+            assert!(matches!(
+                emitted_events[0],
+                Event::TaxScheduleUpdated(TaxScheduleUpdated { entries_count: 2 })
+            ));
+            assert!(matches!(
+                emitted_events[1],
+                Event::TaxRateChanged(TaxRateChanged {
+                    old_rate: 0,
+                    new_rate: 300,
+                    block: 0,
+                })
+            ));
+            // 费率随区块变化后, 下一笔转账补发事件
+            assert!(matches!(
+                emitted_events[2],
+                Event::TaxRateChanged(TaxRateChanged {
+                    old_rate: 300,
+                    new_rate: 200,
+                    block: 5,
+                })
+            ));
+        }
+
+        #[ink::test]
+        fn first_transfer_does_not_report_a_tax_rate_change() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            // 部署时就记下了有效费率
+            assert_eq!(*erc20.last_tax_rate, erc20.current_effective_tax_rate());
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert!(!ink_env::test::recorded_events()
+                .any(|e| matches!(decode_event(&e), Event::TaxRateChanged(_))));
+        }
+
+        #[ink::test]
+        fn beneficiary_claims_from_boundary_block() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 500), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.set_beneficiary(accounts.bob, 10),
+                Err(Error::InvalidBeneficiary)
+            );
+            assert_eq!(
+                erc20.set_beneficiary(accounts.charlie, 0),
+                Err(Error::InvalidBeneficiary)
+            );
+            assert_eq!(erc20.approve(accounts.eve, 50), Ok(()));
+            assert_eq!(erc20.set_beneficiary(accounts.charlie, 10), Ok(()));
+
+            // 收款不影响计时, 领取时按当时的余额
+            advance_blocks(5);
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 200), Ok(()));
+
+            advance_blocks(4);
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.claim_inheritance(accounts.bob),
+                Err(Error::NotBeneficiary)
+            );
+            set_caller(accounts.charlie);
+            assert_eq!(
+                erc20.claim_inheritance(accounts.bob),
+                Err(Error::InheritanceNotClaimable)
+            );
+            advance_blocks(1);
+            assert_eq!(erc20.claim_inheritance(accounts.bob), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+            assert_eq!(erc20.balance_of(accounts.charlie), 700);
+            assert_eq!(erc20.allowance(accounts.bob, accounts.eve), 0);
+            assert_eq!(erc20.beneficiary_of(accounts.bob), None);
+            assert_eq!(
+                erc20.claim_inheritance(accounts.bob),
+                Err(Error::NotBeneficiary)
+            );
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            match decode_event(emitted_events.last().unwrap()) {
+                Event::InheritanceClaimed(InheritanceClaimed {
+                    from,
+                    beneficiary,
+                    amount,
+                }) => {
+                    assert_eq!(from, accounts.bob);
+                    assert_eq!(beneficiary, accounts.charlie);
+                    assert_eq!(amount, 700);
+                }
+                _ => panic!("expected InheritanceClaimed"),
+            }
+        }
+
+        #[ink::test]
+        fn owner_activity_resets_inheritance_clock() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 500), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_beneficiary(accounts.charlie, 10), Ok(()));
+
+            advance_blocks(8);
+            assert_eq!(erc20.transfer(accounts.eve, 1), Ok(()));
+            assert_eq!(
+                erc20.beneficiary_of(accounts.bob).map(|b| b.claimable_at()),
+                Some(18)
+            );
+            advance_blocks(5);
+            set_caller(accounts.charlie);
+            assert_eq!(
+                erc20.claim_inheritance(accounts.bob),
+                Err(Error::InheritanceNotClaimable)
+            );
+
+            // 取消后受益人不能再领取
+            set_caller(accounts.bob);
+            assert_eq!(erc20.clear_beneficiary(), Ok(()));
+            assert_eq!(erc20.clear_beneficiary(), Err(Error::NoBeneficiary));
+            advance_blocks(10);
+            set_caller(accounts.charlie);
+            assert_eq!(
+                erc20.claim_inheritance(accounts.bob),
+                Err(Error::NotBeneficiary)
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 499);
+        }
+
+        #[ink::test]
+        fn opted_out_beneficiary_cannot_claim_until_receiving_again() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 500), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_beneficiary(accounts.charlie, 3), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.set_receiving(false), Ok(()));
+
+            advance_blocks(3);
+            assert_eq!(
+                erc20.claim_inheritance(accounts.bob),
+                Err(Error::RecipientOptedOut)
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 500);
+            assert!(erc20.beneficiary_of(accounts.bob).is_some());
+
+            assert_eq!(erc20.set_receiving(true), Ok(()));
+            assert_eq!(erc20.claim_inheritance(accounts.bob), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 500);
+        }
+
+        #[ink::test]
+        fn min_lockup_changes_at_total_staked_thresholds() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(
+                erc20.set_dynamic_lockup_tiers(vec![(1_000, 10), (5_000, 100)]),
+                Ok(())
+            );
+            assert_eq!(erc20.current_min_lockup(), 0);
+            assert_eq!(erc20.set_dynamic_lockup_enabled(true), Ok(()));
+
+            assert_eq!(erc20.stake(999), Ok(()));
+            assert_eq!(erc20.current_min_lockup(), 0);
+            assert_eq!(erc20.stake(1), Ok(()));
+            assert_eq!(erc20.current_min_lockup(), 10);
+            assert_eq!(erc20.stake(3_999), Ok(()));
+            assert_eq!(erc20.current_min_lockup(), 10);
+            assert_eq!(erc20.stake(1), Ok(()));
+            assert_eq!(erc20.current_min_lockup(), 100);
+            // 门槛前的两笔不锁定, 之后按质押时的档位锁定
+            assert_eq!(
+                erc20.stake_locks_of(accounts.alice),
+                vec![(3_999, 10), (1, 10)]
+            );
+
+            let changes = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::LockupTierChanged(LockupTierChanged { new_min_blocks }) => {
+                        Some(new_min_blocks)
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(changes, vec![10, 100]);
+
+            // 解除质押回到门槛以下
+            advance_blocks(10);
+            assert_eq!(erc20.unstake(1), Ok(()));
+            assert_eq!(erc20.current_min_lockup(), 10);
+            assert_eq!(erc20.stake_locks_of(accounts.alice), vec![]);
+        }
+
+        #[ink::test]
+        fn existing_stakes_keep_their_lockup() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 2_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 2_000), Ok(()));
+            assert_eq!(
+                erc20.set_dynamic_lockup_tiers(vec![(0, 5), (1_000, 50)]),
+                Ok(())
+            );
+            assert_eq!(erc20.set_dynamic_lockup_enabled(true), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stake(1_000), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.stake(100), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stake(10), Ok(()));
+            assert_eq!(erc20.unstake(1), Err(Error::StakeLocked));
+
+            advance_blocks(5);
+            assert_eq!(erc20.unlocked_stake_of(accounts.bob), 1_000);
+            assert_eq!(erc20.unstake(1_001), Err(Error::StakeLocked));
+            assert_eq!(erc20.unstake(1_000), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.unstake(100), Err(Error::StakeLocked));
+
+            advance_blocks(45);
+            assert_eq!(erc20.unstake(100), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.unstake(10), Ok(()));
+            assert_eq!(erc20.total_staked(), 0);
+        }
+
+        #[ink::test]
+        fn dynamic_lockup_tiers_are_validated() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(
+                erc20.set_dynamic_lockup_tiers(vec![(1_000, 10), (1_000, 20)]),
+                Err(Error::InvalidLockupTiers)
+            );
+            assert_eq!(
+                erc20.set_dynamic_lockup_tiers(vec![(1_000, 20), (2_000, 10)]),
+                Err(Error::InvalidLockupTiers)
+            );
+            let too_many = (0..=MAX_LOCKUP_TIERS as u128).map(|i| (i, 1)).collect();
+            assert_eq!(
+                erc20.set_dynamic_lockup_tiers(too_many),
+                Err(Error::InvalidLockupTiers)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.set_dynamic_lockup_tiers(Vec::new()),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(erc20.set_dynamic_lockup_enabled(true), Err(Error::NotOwner));
+        }
+
+        #[cfg(feature = "dev")]
+        #[ink::test]
+        fn populated_holders_keep_transfers_and_stats_correct() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let mut next = 0;
+            let mut calls = 0;
+            while next < 10_000 {
+                next = erc20.populate_test_holders(next, 10_000, 5).unwrap();
+                calls += 1;
+            }
+            assert_eq!(next, 10_000);
+            assert_eq!(calls, 10_000 / MAX_POPULATE_CHUNK);
+            // 已经写完后继续调用不再增发
+            assert_eq!(erc20.populate_test_holders(next, 10_000, 5), Ok(10_000));
+
+            assert_eq!(erc20.total_supply(), 1_000 + 10_000 * 5);
+            assert_eq!(erc20.holder_count(), 10_001);
+            let first = erc20.test_holder_account(0);
+            let last = erc20.test_holder_account(9_999);
+            assert_ne!(first, last);
+            assert_eq!(erc20.balance_of(first), 5);
+            assert_eq!(erc20.balance_of(last), 5);
+            assert_eq!(erc20.balance_of(erc20.test_holder_account(10_000)), 0);
+
+            let transfers_before = erc20.metrics().transfer_count;
+            set_caller(first);
+            assert_eq!(erc20.transfer(accounts.bob, 5), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(last, 10), Ok(()));
+            assert_eq!(erc20.balance_of(first), 0);
+            assert_eq!(erc20.balance_of(accounts.bob), 5);
+            assert_eq!(erc20.balance_of(last), 15);
+
+            let metrics = erc20.metrics();
+            assert_eq!(metrics.transfer_count, transfers_before + 2);
+            assert_eq!(metrics.holder_count, 10_001);
+            assert_eq!(metrics.total_supply, 51_000);
+        }
+
+        #[cfg(feature = "dev")]
+        #[ink::test]
+        fn populate_test_holders_requires_owner_and_dev_mode() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            set_caller(accounts.bob);
+            assert_eq!(erc20.populate_test_holders(0, 10, 1), Err(Error::NotOwner));
+            set_caller(accounts.alice);
+            // 模拟非 dev 部署后升级到 dev 代码
+            *erc20.dev_mode = false;
+            assert_eq!(
+                erc20.populate_test_holders(0, 10, 1),
+                Err(Error::DevModeDisabled)
+            );
+            assert_eq!(erc20.total_supply(), 1_000);
+        }
+
+        #[cfg(not(feature = "dev"))]
+        #[ink::test]
+        fn populate_test_holders_is_disabled_without_dev_feature() {
+            let mut erc20 = Erc20::new(1_000);
+            assert_eq!(
+                erc20.populate_test_holders(0, 10, 1),
+                Err(Error::DevModeDisabled)
+            );
+            assert_eq!(erc20.holder_count(), 1);
+        }
+
+        #[ink::test]
+        fn non_zero_allowances_of_lists_only_outstanding_approvals() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.non_zero_allowances_of(accounts.alice), vec![]);
+            assert_eq!(erc20.approve(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.approve(accounts.charlie, 200), Ok(()));
+            assert_eq!(erc20.approve(accounts.django, 300), Ok(()));
+            assert_eq!(erc20.approve(accounts.charlie, 0), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.approve(accounts.eve, 50), Ok(()));
+
+            assert_eq!(
+                erc20.non_zero_allowances_of(accounts.alice),
+                vec![(accounts.bob, 100), (accounts.django, 300)]
+            );
+            assert_eq!(
+                erc20.non_zero_allowances_of(accounts.bob),
+                vec![(accounts.eve, 50)]
+            );
+            assert_eq!(erc20.non_zero_allowances_of(accounts.charlie), vec![]);
+        }
+
+        #[ink::test]
+        fn revoke_all_allowances_clears_caller_approvals() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.approve(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.approve(accounts.charlie, Balance::MAX), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.approve(accounts.charlie, 10), Ok(()));
+            set_caller(accounts.alice);
+            let before = ink_env::test::recorded_events().count();
+
+            assert_eq!(erc20.revoke_all_allowances(), Ok(()));
+            assert_eq!(erc20.non_zero_allowances_of(accounts.alice), vec![]);
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 0);
+            assert_eq!(erc20.allowance(accounts.alice, accounts.charlie), 0);
+            assert_eq!(erc20.total_outstanding_allowance(accounts.alice), 0);
+            // 其他 owner 的授权不受影响
+            assert_eq!(erc20.allowance(accounts.bob, accounts.charlie), 10);
+
+            let revoked = ink_env::test::recorded_events()
+                .skip(before)
+                .map(|e| match decode_event(&e) {
+                    Event::Approval(Approval {
+                        owner,
+                        spender,
+                        value,
+                        ..
+                    }) => (owner, spender, value),
+                    _ => panic!("encountered unexpected event kind: expected an Approval event"),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                revoked,
+                vec![
+                    (accounts.alice, accounts.bob, 0),
+                    (accounts.alice, accounts.charlie, 0)
+                ]
+            );
+
+            // 没有授权时不发事件
+            assert_eq!(erc20.revoke_all_allowances(), Ok(()));
+            assert_eq!(ink_env::test::recorded_events().count(), before + 2);
+        }
+
+        #[ink::test]
+        fn revoke_all_allowances_is_bounded_per_call() {
+            let mut erc20 = Erc20::new(1_000);
+            for i in 0..MAX_ALLOWANCES_REVOKED + 3 {
+                let spender = AccountId::from([i as u8 + 1; 32]);
+                assert_eq!(erc20.approve(spender, 1), Ok(()));
+            }
+            let owner = erc20.owner();
+            assert_eq!(erc20.revoke_all_allowances(), Ok(()));
+            assert_eq!(erc20.non_zero_allowances_of(owner).len(), 3);
+            assert_eq!(erc20.revoke_all_allowances(), Ok(()));
+            assert_eq!(erc20.non_zero_allowances_of(owner), vec![]);
+        }
+
+        #[ink::test]
+        fn bounty_amount_per_kind() {
+            assert_eq!(Bounty::None.amount(1_000), 0);
+            assert_eq!(Bounty::Flat(7).amount(0), 7);
+            assert_eq!(Bounty::Flat(7).amount(1_000), 7);
+            assert_eq!(Bounty::Bps(250).amount(401), 10);
+            assert_eq!(Bounty::Bps(10_000).amount(Balance::MAX), Balance::MAX);
+            assert!(Bounty::Bps(10_000).is_valid());
+            assert!(!Bounty::Bps(10_001).is_valid());
+
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(
+                erc20.task_bounty(TaskKind::ScheduledTransfer),
+                BountyConfig {
+                    bounty: Bounty::None,
+                    source: BountySource::Mint
+                }
+            );
+            // 托管扣除的固定赏金不超过被处理的金额
+            assert_eq!(
+                erc20.set_task_bounty(
+                    TaskKind::ScheduledTransfer,
+                    Bounty::Flat(50),
+                    BountySource::Escrow
+                ),
+                Ok(())
+            );
+            assert_eq!(erc20.schedule_transfer(accounts.bob, 30, 1), Ok(0));
+            assert_eq!(erc20.schedule_transfer(accounts.bob, 200, 1), Ok(1));
+            set_caller(accounts.charlie);
+            advance_blocks(1);
+            assert_eq!(erc20.execute_scheduled(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 30);
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+            assert_eq!(erc20.execute_scheduled(1), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 80);
+            assert_eq!(erc20.balance_of(accounts.bob), 150);
+
+            // 增发的比例赏金不从提取金额中扣
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.set_task_bounty(
+                    TaskKind::WithdrawalQueue,
+                    Bounty::Bps(500),
+                    BountySource::Mint
+                ),
+                Ok(())
+            );
+            assert_eq!(erc20.set_instant_withdrawal_limit(Some(0)), Ok(()));
+            assert_eq!(erc20.stake(1_000), Ok(()));
+            assert_eq!(erc20.unstake(1_000), Ok(()));
+            let supply = erc20.total_supply();
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.process_withdrawals(1), Ok(1));
+            assert_eq!(erc20.balance_of(accounts.charlie), 130);
+            assert_eq!(erc20.total_supply(), supply + 50);
+        }
+
+        #[ink::test]
+        fn pool_bounties_stop_when_pool_is_exhausted() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.set_burn_receipt_retention(1), Ok(()));
+            assert_eq!(
+                erc20.set_task_bounty(
+                    TaskKind::ReceiptPruning,
+                    Bounty::Flat(3),
+                    BountySource::Pool
+                ),
+                Ok(())
+            );
+            assert_eq!(erc20.fund_bounty_pool(5), Ok(()));
+            assert_eq!(erc20.bounty_pool(), 5);
+            assert_eq!(erc20.balance_of(contract), 5);
+            for _ in 0..3 {
+                assert_eq!(erc20.burn(1), Ok(()));
+            }
+            let supply = erc20.total_supply();
+
+            advance_blocks(2);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.prune_receipts(10), Ok(3));
+            // 第二张只拿到池子里剩下的 2, 第三张没有赏金
+            assert_eq!(erc20.balance_of(accounts.bob), 5);
+            assert_eq!(erc20.bounty_pool(), 0);
+            assert_eq!(erc20.balance_of(contract), 0);
+            assert_eq!(erc20.total_supply(), supply);
+            assert_eq!(
+                erc20.keeper_stats(accounts.bob),
+                KeeperStats {
+                    earned: 5,
+                    tasks: 2
+                }
+            );
+        }
+
+        #[ink::test]
+        fn keeper_stats_accumulate_across_tasks() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.set_task_bounty(
+                    TaskKind::ReceiptPruning,
+                    Bounty::Flat(1),
+                    BountySource::Mint
+                ),
+                Err(Error::NotOwner)
+            );
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.set_task_bounty(
+                    TaskKind::ReceiptPruning,
+                    Bounty::Flat(1),
+                    BountySource::Mint
+                ),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.set_task_bounty(
+                    TaskKind::ScheduledTransfer,
+                    Bounty::Bps(1_000),
+                    BountySource::Escrow
+                ),
+                Ok(())
+            );
+            assert_eq!(erc20.set_burn_receipt_retention(1), Ok(()));
+            assert_eq!(erc20.burn(1), Ok(()));
+            assert_eq!(erc20.burn(1), Ok(()));
+            assert_eq!(erc20.schedule_transfer(accounts.bob, 100, 1), Ok(0));
+            assert_eq!(erc20.keeper_stats(accounts.eve), KeeperStats::default());
+
+            advance_blocks(2);
+            set_caller(accounts.eve);
+            assert_eq!(erc20.prune_receipts(10), Ok(2));
+            assert_eq!(erc20.execute_scheduled(0), Ok(()));
+            assert_eq!(
+                erc20.keeper_stats(accounts.eve),
+                KeeperStats {
+                    earned: 12,
+                    tasks: 3
+                }
+            );
+            assert_eq!(erc20.balance_of(accounts.eve), 12);
+            assert_eq!(erc20.keeper_stats(accounts.alice), KeeperStats::default());
+        }
+
+        #[ink::test]
+        fn protocol_fee_accumulates_in_treasury() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.set_transfer_fee(100, accounts.django), Ok(()));
+            assert_eq!(erc20.set_protocol_fee(10_001), Err(Error::InvalidBps));
+            assert_eq!(erc20.set_protocol_fee(4_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 5_000), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 1_000), Ok(()));
+            // 手续费 10, 其中 40% 进金库
+            assert_eq!(erc20.balance_of(accounts.charlie), 990);
+            assert_eq!(erc20.balance_of(accounts.django), 6);
+            assert_eq!(erc20.treasury_balance(), 4);
+            assert_eq!(erc20.balance_of(contract), 4);
+            assert_eq!(erc20.fees_collected(), 10);
+            assert_eq!(erc20.set_protocol_fee(0), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn treasury_proposal_spends_after_passing_vote() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(
+                erc20.transfer_genesis_nft(AccountId::from([0xff; 32])),
+                Ok(())
+            );
+            assert_eq!(erc20.set_transfer_fee(1_000, accounts.django), Ok(()));
+            assert_eq!(erc20.set_protocol_fee(10_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+            assert_eq!(erc20.treasury_balance(), 1_000);
+            assert_eq!(erc20.set_treasury_params(1_000, 400), Ok(()));
+
+            set_caller(accounts.charlie);
+            assert_eq!(
+                erc20.submit_treasury_proposal(accounts.eve, 600, Hash::from([1; 32])),
+                Err(Error::BelowProposalThreshold)
+            );
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.submit_treasury_proposal(accounts.eve, 600, Hash::from([1; 32])),
+                Ok(0)
+            );
+            assert_eq!(
+                erc20.vote_treasury_proposal(0, true),
+                Err(Error::VotingNotStarted)
+            );
+            advance_blocks(1);
+            assert_eq!(erc20.vote_treasury_proposal(0, true), Ok(()));
+            assert_eq!(
+                erc20.vote_treasury_proposal(0, true),
+                Err(Error::AlreadyVoted)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(erc20.vote_treasury_proposal(0, false), Ok(()));
+            let proposal = erc20.treasury_proposal(0).unwrap();
+            assert_eq!(proposal.votes_for, 90_000);
+            assert_eq!(proposal.votes_against, 9_000);
+
+            advance_blocks(TREASURY_VOTING_PERIOD_BLOCKS - 1);
+            assert_eq!(erc20.execute_treasury_proposal(0), Err(Error::VotingOpen));
+            advance_blocks(1);
+            assert_eq!(
+                erc20.vote_treasury_proposal(0, true),
+                Err(Error::VotingClosed)
+            );
+            assert_eq!(erc20.execute_treasury_proposal(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.eve), 600);
+            assert_eq!(erc20.treasury_balance(), 400);
+            assert!(erc20.treasury_proposal(0).unwrap().executed);
+            assert_eq!(
+                erc20.execute_treasury_proposal(0),
+                Err(Error::ProposalAlreadyExecuted)
+            );
+
+            let spends = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::TreasurySpend(TreasurySpend {
+                        id,
+                        recipient,
+                        amount,
+                    }) => Some((id, recipient, amount)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(spends, vec![(0, accounts.eve, 600)]);
+        }
+
+        #[ink::test]
+        fn treasury_votes_are_weighted_at_proposal_creation() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+            assert_eq!(
+                erc20.submit_treasury_proposal(accounts.eve, 0, Hash::from([3; 32])),
+                Ok(0)
+            );
+            advance_blocks(1);
+
+            // 投票后把代币转给别的账户, 对方按快照没有投票权
+            set_caller(accounts.bob);
+            assert_eq!(erc20.vote_treasury_proposal(0, true), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 10_000), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.vote_treasury_proposal(0, true), Ok(()));
+            // 快照之后收到代币同样不计入
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.django, 50_000), Ok(()));
+            set_caller(accounts.django);
+            assert_eq!(erc20.vote_treasury_proposal(0, false), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.vote_treasury_proposal(0, false), Ok(()));
+
+            let proposal = erc20.treasury_proposal(0).unwrap();
+            assert_eq!(proposal.votes_for, 10_000);
+            assert_eq!(proposal.votes_against, 90_000);
+            assert_eq!(erc20.get_past_votes(accounts.bob, 0), 10_000);
+            assert_eq!(erc20.get_past_votes(accounts.bob, 1), 0);
+            assert_eq!(erc20.get_past_votes(accounts.charlie, 0), 0);
+            assert_eq!(erc20.get_past_votes(accounts.charlie, 1), 10_000);
+        }
+
+        #[ink::test]
+        fn treasury_proposal_requires_quorum_majority_and_funds() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 3_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 5_000), Ok(()));
+            let hash = Hash::from([2; 32]);
+
+            // 0: 只有 bob 投票, 3% 不足 4% 法定人数
+            assert_eq!(erc20.submit_treasury_proposal(accounts.eve, 0, hash), Ok(0));
+            // 1: 反对票更多
+            assert_eq!(erc20.submit_treasury_proposal(accounts.eve, 0, hash), Ok(1));
+            // 2: 通过但金库没钱
+            assert_eq!(erc20.submit_treasury_proposal(accounts.eve, 1, hash), Ok(2));
+            advance_blocks(1);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.vote_treasury_proposal(0, true), Ok(()));
+            assert_eq!(erc20.vote_treasury_proposal(1, true), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.vote_treasury_proposal(1, false), Ok(()));
+            assert_eq!(erc20.vote_treasury_proposal(2, true), Ok(()));
+
+            advance_blocks(TREASURY_VOTING_PERIOD_BLOCKS);
+            assert_eq!(
+                erc20.execute_treasury_proposal(0),
+                Err(Error::QuorumNotReached)
+            );
+            assert_eq!(
+                erc20.execute_treasury_proposal(1),
+                Err(Error::ProposalNotPassed)
+            );
+            assert_eq!(
+                erc20.execute_treasury_proposal(2),
+                Err(Error::TreasuryInsufficientFunds)
+            );
+            assert_eq!(
+                erc20.execute_treasury_proposal(3),
+                Err(Error::ProposalNotFound)
+            );
+            assert!(!erc20.treasury_proposal(2).unwrap().executed);
+        }
+
+        #[ink::test]
+        fn disputed_escrow_is_split_by_arbitrator() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(
+                erc20.create_escrow(accounts.bob, 400, accounts.bob),
+                Err(Error::NotEscrowParty)
+            );
+            assert_eq!(erc20.create_escrow(accounts.bob, 400, accounts.eve), Ok(0));
+            assert_eq!(erc20.balance_of(contract), 400);
+
+            // 争议期内收款人不能自己放款, 只能发起争议
+            set_caller(accounts.bob);
+            assert_eq!(erc20.release_escrow(0), Err(Error::EscrowLocked));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.open_dispute(0), Err(Error::NotEscrowParty));
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.arbitrate_escrow(0, accounts.bob, 5_000),
+                Err(Error::NoDisputeOpen)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(erc20.open_dispute(0), Ok(()));
+            assert_eq!(erc20.escrow(0).unwrap().status, EscrowStatus::Disputed);
+            set_caller(accounts.alice);
+            assert_eq!(erc20.release_escrow(0), Err(Error::EscrowNotOpen));
+            assert_eq!(
+                erc20.arbitrate_escrow(0, accounts.bob, 7_500),
+                Err(Error::NotArbitrator)
+            );
+
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.arbitrate_escrow(0, accounts.charlie, 7_500),
+                Err(Error::NotEscrowParty)
+            );
+            assert_eq!(
+                erc20.arbitrate_escrow(0, accounts.bob, 10_001),
+                Err(Error::InvalidBps)
+            );
+            assert_eq!(erc20.arbitrate_escrow(0, accounts.bob, 7_500), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 300);
+            assert_eq!(erc20.balance_of(accounts.alice), 700);
+            assert_eq!(erc20.balance_of(contract), 0);
+            assert_eq!(erc20.escrow(0).unwrap().status, EscrowStatus::Arbitrated);
+            assert_eq!(
+                erc20.arbitrate_escrow(0, accounts.bob, 7_500),
+                Err(Error::NoDisputeOpen)
+            );
+
+            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+            match decode_event(&emitted_events[emitted_events.len() - 1]) {
+                Event::EscrowArbitrated(EscrowArbitrated {
+                    escrow_id,
+                    recipient,
+                    split,
+                }) => {
+                    assert_eq!((escrow_id, recipient, split), (0, accounts.bob, 7_500));
+                }
+                _ => {
+                    panic!("encountered unexpected event kind: expected an EscrowArbitrated event")
+                }
+            }
+        }
+
+        #[ink::test]
+        fn escrow_dispute_window_expires() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_dispute_period_blocks(10), Ok(()));
+            assert_eq!(erc20.create_escrow(accounts.bob, 100, accounts.eve), Ok(0));
+            assert_eq!(erc20.create_escrow(accounts.bob, 200, accounts.eve), Ok(1));
+            // 已有托管沿用创建时的争议期
+            assert_eq!(erc20.set_dispute_period_blocks(1_000), Ok(()));
+
+            advance_blocks(10);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.release_escrow(0), Err(Error::EscrowLocked));
+            advance_blocks(1);
+            assert_eq!(erc20.open_dispute(0), Err(Error::DisputePeriodExpired));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.open_dispute(1), Err(Error::DisputePeriodExpired));
+
+            // 争议期过后收款人可以自己放款
+            set_caller(accounts.bob);
+            assert_eq!(erc20.release_escrow(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 100);
+            assert_eq!(erc20.release_escrow(0), Err(Error::EscrowNotOpen));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.release_escrow(1), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 300);
+            assert_eq!(erc20.escrow(1).unwrap().status, EscrowStatus::Released);
+        }
+
+        #[ink::test]
+        fn expired_dispute_refunds_payer() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.create_escrow(accounts.bob, 400, accounts.eve), Ok(0));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.open_dispute(0), Ok(()));
+
+            // 仲裁期限内只能由仲裁人处理
+            advance_blocks(ARBITRATION_PERIOD_BLOCKS);
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.resolve_expired_dispute(0), Err(Error::EscrowLocked));
+
+            // 过期后仲裁人不能再裁决, 任何人都可以退回付款人
+            advance_blocks(1);
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.arbitrate_escrow(0, accounts.bob, 10_000),
+                Err(Error::ArbitrationExpired)
+            );
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.resolve_expired_dispute(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 1_000);
+            assert_eq!(erc20.balance_of(contract), 0);
+            assert_eq!(
+                erc20.escrow(0).unwrap().status,
+                EscrowStatus::DisputeExpired
+            );
+            assert_eq!(erc20.unallocated_contract_balance(), Ok(0));
+            assert_eq!(erc20.resolve_expired_dispute(0), Err(Error::NoDisputeOpen));
+        }
+
+        #[ink::test]
+        fn paused_ops_only_block_their_own_operation() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.approve(accounts.bob, 100), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.set_paused_ops(PAUSE_TRANSFER_FROM),
+                Err(Error::NotOwner)
+            );
+            set_caller(accounts.alice);
+
+            assert_eq!(erc20.set_paused_ops(PAUSE_TRANSFER_FROM), Ok(()));
+            assert_eq!(erc20.paused_ops(), PAUSE_TRANSFER_FROM);
+            // 部分暂停不算 paused
+            assert!(!erc20.paused());
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.charlie, 10),
+                Err(Error::Paused)
+            );
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 100);
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.approve(accounts.charlie, 10), Ok(()));
+            assert_eq!(erc20.mint(accounts.alice, 10), Ok(()));
+            assert_eq!(erc20.burn(10), Ok(()));
+            // 内部划转只在全部暂停时停止
+            assert_eq!(erc20.stake(10), Ok(()));
+
+            assert_eq!(erc20.set_paused_ops(PAUSE_TRANSFER | PAUSE_APPROVE), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10), Err(Error::Paused));
+            assert_eq!(erc20.approve(accounts.charlie, 20), Err(Error::Paused));
+            // 撤销授权不受影响
+            assert_eq!(erc20.approve(accounts.charlie, 0), Ok(()));
+            // 已经托管的款项照常放款
+            assert_eq!(erc20.create_escrow(accounts.bob, 10, accounts.eve), Ok(0));
+            assert_eq!(erc20.release_escrow(0), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.charlie, 10),
+                Ok(())
+            );
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_paused_ops(PAUSE_MINT | PAUSE_BURN), Ok(()));
+            assert_eq!(erc20.mint(accounts.alice, 10), Err(Error::Paused));
+            assert_eq!(erc20.burn(10), Err(Error::Paused));
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+
+            let changes = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::PausedOpsChanged(PausedOpsChanged { old_mask, new_mask }) => {
+                        Some((old_mask, new_mask))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                changes,
+                vec![
+                    (0, PAUSE_TRANSFER_FROM),
+                    (PAUSE_TRANSFER_FROM, PAUSE_TRANSFER | PAUSE_APPROVE),
+                    (PAUSE_TRANSFER | PAUSE_APPROVE, PAUSE_MINT | PAUSE_BURN),
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn legacy_pause_sets_and_clears_all_ops() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_paused_ops(PAUSE_MINT), Ok(()));
+            assert!(!erc20.paused());
+            assert_eq!(erc20.pause(), Ok(()));
+            assert_eq!(erc20.paused_ops(), PAUSE_ALL);
+            assert!(erc20.paused());
+            assert_eq!(erc20.stake(10), Err(Error::Paused));
+            assert_eq!(erc20.approve(accounts.bob, 10), Err(Error::Paused));
+
+            assert_eq!(erc20.unpause(), Ok(()));
+            assert_eq!(erc20.paused_ops(), 0);
+            assert!(!erc20.paused());
+
+            // 逐位设满与 pause() 等价, 未知的位被忽略
+            assert_eq!(erc20.set_paused_ops(u32::MAX), Ok(()));
+            assert_eq!(erc20.paused_ops(), PAUSE_ALL);
+            assert_eq!(erc20.stake(10), Err(Error::Paused));
+            assert_eq!(erc20.set_paused_ops(0), Ok(()));
+            assert_eq!(erc20.stake(10), Ok(()));
+        }
+
+        #[ink::test]
+        fn integer_sqrt_rounds_down() {
+            let cases: [(Balance, Balance); 10] = [
+                (0, 0),
+                (1, 1),
+                (2, 1),
+                (3, 1),
+                (4, 2),
+                (15, 3),
+                (16, 4),
+                (17, 4),
+                (999_999_999_999, 999_999),
+                (1_000_000_000_000, 1_000_000),
+            ];
+            for (n, root) in cases.iter() {
+                assert_eq!(integer_sqrt(*n), *root, "sqrt({})", n);
+            }
+            let max_root = integer_sqrt(Balance::MAX);
+            assert_eq!(max_root, u64::MAX as Balance);
+            assert!(max_root * max_root <= Balance::MAX);
+            assert!((max_root + 1).checked_mul(max_root + 1).is_none());
+        }
+
+        #[ink::test]
+        fn weighted_votes_combine_balance_and_holding_duration() {
+            let mut erc20 = Erc20::new(1_000_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.get_weighted_votes(accounts.bob), 0);
+            assert_eq!(erc20.holding_duration_multiplier(accounts.bob), 10_000);
+
+            advance_blocks(10);
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 250_000), Ok(()));
+            assert_eq!(erc20.quadratic_votes(accounts.bob), 100);
+            assert_eq!(erc20.quadratic_votes(accounts.charlie), 500);
+            assert_eq!(erc20.get_weighted_votes(accounts.bob), 100);
+
+            advance_blocks(HOLDING_MULTIPLIER_STEP_BLOCKS - 1);
+            assert_eq!(erc20.holding_duration_multiplier(accounts.bob), 10_000);
+            advance_blocks(1);
+            assert_eq!(erc20.holding_duration_multiplier(accounts.bob), 12_500);
+            assert_eq!(erc20.get_weighted_votes(accounts.bob), 125);
+            assert_eq!(erc20.get_weighted_votes(accounts.charlie), 625);
+            // 追加买入不重置持有时长
+            assert_eq!(erc20.transfer(accounts.bob, 30_000), Ok(()));
+            assert_eq!(erc20.get_weighted_votes(accounts.bob), 250);
+
+            advance_blocks(3 * HOLDING_MULTIPLIER_STEP_BLOCKS);
+            assert_eq!(erc20.holding_duration_multiplier(accounts.bob), 20_000);
+            assert_eq!(erc20.get_weighted_votes(accounts.bob), 400);
+            advance_blocks(10 * HOLDING_MULTIPLIER_STEP_BLOCKS);
+            assert_eq!(erc20.holding_duration_multiplier(accounts.bob), 20_000);
+
+            // 清仓后重新计时
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 40_000), Ok(()));
+            assert_eq!(erc20.holding_duration_multiplier(accounts.bob), 10_000);
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.transfer(accounts.bob, 90_000), Ok(()));
+            assert_eq!(erc20.get_weighted_votes(accounts.bob), 300);
+        }
+
+        #[ink::test]
+        fn cast_vote_uses_weighted_votes_when_enabled() {
+            let mut erc20 = Erc20::new(1_000_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 40_000), Ok(()));
+            let first = erc20.propose(Hash::from([1; 32]), 10).unwrap();
+            advance_blocks(1);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.cast_vote(first, true), Ok(()));
+            assert_eq!(erc20.proposal(first).unwrap().for_votes, 40_000);
+            assert_eq!(erc20.set_weighted_voting(true), Err(Error::NotOwner));
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_weighted_voting(true), Ok(()));
+            advance_blocks(HOLDING_MULTIPLIER_STEP_BLOCKS);
+            let second = erc20.propose(Hash::from([2; 32]), 10).unwrap();
+            advance_blocks(1);
+            // 提案创建后转入的代币不计入
+            assert_eq!(erc20.transfer(accounts.bob, 50_000), Ok(()));
+            assert_eq!(erc20.cast_vote(second, false), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.cast_vote(second, true), Ok(()));
+            let proposal = erc20.proposal(second).unwrap();
+            // alice: sqrt(960000) = 979, 持有一档 * 1.25
+            assert_eq!(proposal.against_votes, 1_223);
+            // bob: sqrt(40000) = 200 * 1.25
+            assert_eq!(proposal.for_votes, 250);
+        }
+
+        // 按被调合约返回固定余额的 PSP22 桩, 没有登记的合约调用失败
+        struct StubPsp22 {
+            balances: Vec<(AccountId, Option<Balance>)>,
+            calls: Rc<RefCell<Vec<(AccountId, AccountId, u64)>>>,
+        }
+
+        impl call::CallLayer for StubPsp22 {
+            fn call(
+                &mut self,
+                callee: AccountId,
+                selector: [u8; 4],
+                input: &[u8],
+                gas_limit: u64,
+            ) -> core::result::Result<Vec<u8>, ink_env::Error> {
+                assert_eq!(selector, PSP22_BALANCE_OF_SELECTOR);
+                let owner = <AccountId as scale::Decode>::decode(&mut &input[..])
+                    .expect("encountered invalid balance_of input");
+                self.calls.borrow_mut().push((callee, owner, gas_limit));
+                match self.balances.iter().find(|(token, _)| *token == callee) {
+                    Some((_, Some(balance))) => Ok(scale::Encode::encode(balance)),
+                    // 返回无法解码为 Balance 的数据
+                    Some((_, None)) => Ok(vec![0x01]),
+                    None => Err(ink_env::Error::CalleeTrapped),
+                }
+            }
+        }
+
+        #[ink::test]
+        fn portfolio_of_tolerates_failing_tokens() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let (dot, usdt, broken, missing) = (
+                AccountId::from([0x21; 32]),
+                AccountId::from([0x22; 32]),
+                AccountId::from([0x23; 32]),
+                AccountId::from([0x24; 32]),
+            );
+            let calls = Rc::new(RefCell::new(Vec::new()));
+            call::set_call_layer(StubPsp22 {
+                balances: vec![(dot, Some(50)), (usdt, Some(7)), (broken, None)],
+                calls: calls.clone(),
+            });
+            assert_eq!(erc20.transfer(accounts.bob, 300), Ok(()));
+
+            assert_eq!(
+                erc20.portfolio_of(accounts.bob, vec![dot, missing, broken, usdt]),
+                vec![
+                    (contract, 300),
+                    (dot, 50),
+                    (missing, 0),
+                    (broken, 0),
+                    (usdt, 7)
+                ]
+            );
+            assert_eq!(
+                *calls.borrow(),
+                vec![
+                    (dot, accounts.bob, PORTFOLIO_GAS_LIMIT),
+                    (missing, accounts.bob, PORTFOLIO_GAS_LIMIT),
+                    (broken, accounts.bob, PORTFOLIO_GAS_LIMIT),
+                    (usdt, accounts.bob, PORTFOLIO_GAS_LIMIT),
+                ]
+            );
+            assert_eq!(
+                erc20.portfolio_of(accounts.alice, Vec::new()),
+                vec![(contract, 700)]
+            );
+        }
+
+        #[ink::test]
+        fn portfolio_of_queries_at_most_max_tokens() {
+            let erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let tokens = (0..MAX_PORTFOLIO_TOKENS + 4)
+                .map(|i| AccountId::from([i as u8 + 0x30; 32]))
+                .collect::<Vec<_>>();
+            let calls = Rc::new(RefCell::new(Vec::new()));
+            call::set_call_layer(StubPsp22 {
+                balances: tokens.iter().map(|token| (*token, Some(1))).collect(),
+                calls: calls.clone(),
+            });
+            let portfolio = erc20.portfolio_of(accounts.alice, tokens.clone());
+            assert_eq!(portfolio.len(), MAX_PORTFOLIO_TOKENS + 1);
+            assert_eq!(calls.borrow().len(), MAX_PORTFOLIO_TOKENS);
+            assert_eq!(
+                portfolio.last(),
+                Some(&(tokens[MAX_PORTFOLIO_TOKENS - 1], 1))
+            );
+        }
+
+        #[ink::test]
+        fn mint_is_capped_by_annual_growth_rate() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(
+                erc20.set_max_annual_growth_rate(10_001),
+                Err(Error::InvalidBps)
+            );
+            assert_eq!(erc20.set_max_annual_growth_rate(500), Ok(()));
+            assert_eq!(erc20.mint(accounts.bob, 300), Ok(()));
+            assert_eq!(
+                erc20.mint(accounts.bob, 201),
+                Err(Error::AnnualMintCapExceeded)
+            );
+            assert_eq!(erc20.mint(accounts.bob, 200), Ok(()));
+            assert_eq!(
+                erc20.mint(accounts.bob, 1),
+                Err(Error::AnnualMintCapExceeded)
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 500);
+            assert_eq!(erc20.annual_mint_status(), (500, 500, 10_000, 0));
+
+            // 关闭上限后不再限制, 但照样计数
+            assert_eq!(erc20.set_max_annual_growth_rate(0), Ok(()));
+            assert_eq!(erc20.mint(accounts.bob, 1_000), Ok(()));
+            assert_eq!(erc20.annual_mint_status(), (0, 1_500, 10_000, 0));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_max_annual_growth_rate(500), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn annual_mint_counter_resets_after_a_year() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_max_annual_growth_rate(1_000), Ok(()));
+            assert_eq!(erc20.mint(accounts.bob, 1_000), Ok(()));
+            assert_eq!(
+                erc20.mint(accounts.bob, 1),
+                Err(Error::AnnualMintCapExceeded)
+            );
+
+            advance_blocks(BLOCKS_PER_YEAR - 1);
+            assert_eq!(
+                erc20.reset_annual_mint_counter(),
+                Err(Error::YearNotElapsed)
+            );
+            advance_blocks(1);
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.reset_annual_mint_counter(), Ok(()));
+            assert_eq!(
+                erc20.annual_mint_status(),
+                (1_000, 0, 11_000, BLOCKS_PER_YEAR)
+            );
+            assert_eq!(
+                erc20.reset_annual_mint_counter(),
+                Err(Error::YearNotElapsed)
+            );
+
+            // 新年度按 11000 的 10% 计算
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.mint(accounts.bob, 1_101),
+                Err(Error::AnnualMintCapExceeded)
+            );
+            assert_eq!(erc20.mint(accounts.bob, 1_100), Ok(()));
+
+            let resets = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::AnnualCounterReset(AnnualCounterReset {
+                        year,
+                        minted,
+                        start_supply,
+                    }) => Some((year, minted, start_supply)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(resets, vec![(0, 1_000, 10_000)]);
+        }
+
+        #[cfg(feature = "model")]
+        const MODEL_SUPPLY: Balance = 1_000_000;
+
+        // 在新部署的合约和参考模型上执行同一组操作, 返回第一处不一致的步骤和原因
+        #[cfg(feature = "model")]
+        fn diverges_from_model(ops: &[crate::model::Op]) -> Option<(usize, String)> {
+            use crate::model::{Model, Op};
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let all = [
+                accounts.alice,
+                accounts.bob,
+                accounts.charlie,
+                accounts.django,
+                accounts.eve,
+                accounts.frank,
+            ];
+            set_caller(accounts.alice);
+            let mut erc20 = Erc20::new(MODEL_SUPPLY);
+            let mut model = Model::new(accounts.alice, MODEL_SUPPLY);
+
+            for (step, op) in ops.iter().enumerate() {
+                let expected = model.apply(*op);
+                let actual = match *op {
+                    Op::Transfer { from, to, value } => {
+                        set_caller(from);
+                        erc20.transfer(to, value)
+                    }
+                    Op::Approve {
+                        owner,
+                        spender,
+                        value,
+                    } => {
+                        set_caller(owner);
+                        erc20.approve(spender, value)
+                    }
+                    Op::TransferFrom {
+                        spender,
+                        from,
+                        to,
+                        value,
+                    } => {
+                        set_caller(spender);
+                        erc20.transfer_from(from, to, value)
+                    }
+                    Op::Mint { caller, to, value } => {
+                        set_caller(caller);
+                        erc20.mint(to, value)
+                    }
+                    Op::Burn { from, value } => {
+                        set_caller(from);
+                        erc20.burn(value)
+                    }
+                };
+                if actual != expected {
+                    return Some((step, format!("返回 {:?}, 模型为 {:?}", actual, expected)));
+                }
+                if erc20.total_supply() != model.total_supply() {
+                    return Some((
+                        step,
+                        format!(
+                            "总供应量 {}, 模型为 {}",
+                            erc20.total_supply(),
+                            model.total_supply()
+                        ),
+                    ));
+                }
+                for owner in all.iter() {
+                    if erc20.balance_of(*owner) != model.balance_of(*owner) {
+                        return Some((
+                            step,
+                            format!(
+                                "{:?} 余额 {}, 模型为 {}",
+                                owner,
+                                erc20.balance_of(*owner),
+                                model.balance_of(*owner)
+                            ),
+                        ));
+                    }
+                    for spender in all.iter() {
+                        if erc20.allowance(*owner, *spender) != model.allowance(*owner, *spender) {
+                            return Some((
+                                step,
+                                format!(
+                                    "{:?} 给 {:?} 的授权 {}, 模型为 {}",
+                                    owner,
+                                    spender,
+                                    erc20.allowance(*owner, *spender),
+                                    model.allowance(*owner, *spender)
+                                ),
+                            ));
+                        }
+                    }
+                }
+            }
+            None
+        }
+
+        #[cfg(feature = "model")]
+        #[ink::test]
+        fn contract_matches_reference_model() {
+            use crate::model::{minimize, Model, Op};
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let all = [
+                accounts.alice,
+                accounts.bob,
+                accounts.charlie,
+                accounts.django,
+                accounts.eve,
+                accounts.frank,
+            ];
+
+            // 线性同余生成器, 固定种子保证失败可以复现
+            let mut seed: u128 = 0x9e37_79b9_7f4a_7c15;
+            let mut next = |bound: u128| {
+                seed = seed
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                (seed >> 64) % bound
+            };
+            for _ in 0..100 {
+                // 生成时同步推进一份模型, 以便取到余额, 授权, 供应量上限附近的边界值
+                let mut model = Model::new(accounts.alice, MODEL_SUPPLY);
+                let mut ops = Vec::new();
+                for _ in 0..40 {
+                    let a = all[next(6) as usize];
+                    let b = all[next(6) as usize];
+                    let c = all[next(6) as usize];
+                    let kind = next(5);
+                    let exact = match kind {
+                        0 | 4 => model.balance_of(a),
+                        1 => model.allowance(a, b),
+                        2 => model.allowance(b, a),
+                        _ => Balance::MAX - model.total_supply(),
+                    };
+                    let value = match next(6) {
+                        0 => 0,
+                        1 => next(1_000),
+                        2 => exact,
+                        3 => exact.saturating_add(1),
+                        4 => Balance::MAX,
+                        _ => exact / 2,
+                    };
+                    let op = match kind {
+                        0 => Op::Transfer {
+                            from: a,
+                            to: b,
+                            value,
+                        },
+                        1 => Op::Approve {
+                            owner: a,
+                            spender: b,
+                            value,
+                        },
+                        2 => Op::TransferFrom {
+                            spender: a,
+                            from: b,
+                            to: c,
+                            value,
+                        },
+                        // 多数增发由 owner 发起
+                        3 => Op::Mint {
+                            caller: if next(4) == 0 { a } else { accounts.alice },
+                            to: b,
+                            value,
+                        },
+                        _ => Op::Burn { from: a, value },
+                    };
+                    let _ = model.apply(op);
+                    ops.push(op);
+                }
+
+                if diverges_from_model(&ops).is_some() {
+                    let minimal =
+                        minimize(&ops, |candidate| diverges_from_model(candidate).is_some());
+                    let (step, reason) =
+                        diverges_from_model(&minimal).expect("minimized trace must still diverge");
+                    panic!(
+                        "合约与参考模型在第 {} 步不一致: {}\n最小操作序列: {:#?}",
+                        step, reason, minimal
+                    );
+                }
+            }
+        }
+
+        #[ink::test]
+        fn burn_vote_burns_cost_and_counts_it_as_weight() {
+            let mut erc20 = Erc20::new(1_000_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 1_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 50), Ok(()));
+            assert_eq!(erc20.set_burn_vote_cost(100), Ok(()));
+            assert_eq!(erc20.burn_vote_cost(), 100);
+            let proposal_id = erc20.propose(Hash::from([1; 32]), 10).unwrap();
+            advance_blocks(1);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_burn_vote_cost(1), Err(Error::NotOwner));
+            assert_eq!(erc20.cast_vote(proposal_id, true), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 900);
+            assert_eq!(erc20.total_supply(), 999_900);
+            assert_eq!(erc20.proposal(proposal_id).unwrap().for_votes, 100);
+
+            // 余额不足时不销毁也不记票
+            set_caller(accounts.charlie);
+            assert_eq!(
+                erc20.cast_vote(proposal_id, false),
+                Err(Error::InsufficientBalanceForVote)
+            );
+            assert_eq!(erc20.balance_of(accounts.charlie), 50);
+            assert_eq!(erc20.proposal(proposal_id).unwrap().against_votes, 0);
+
+            // 票数只取决于销毁数量, 与持币数无关
+            set_caller(accounts.alice);
+            assert_eq!(erc20.cast_vote(proposal_id, false), Ok(()));
+            let proposal = erc20.proposal(proposal_id).unwrap();
+            assert_eq!(proposal.against_votes, 100);
+            assert_eq!(erc20.total_supply(), 999_800);
+            assert_eq!(erc20.per_proposal_burn(proposal_id), 200);
+            assert_eq!(erc20.per_proposal_burn(proposal_id + 1), 0);
+            assert_eq!(erc20.total_voting_burn(), 200);
+
+            let burned = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::BurnedVoteCast(BurnedVoteCast {
+                        voter,
+                        support,
+                        burned_amount,
+                        ..
+                    }) => Some((voter, support, burned_amount)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                burned,
+                vec![(accounts.bob, true, 100), (accounts.alice, false, 100)]
+            );
+        }
+
+        fn metadata_updates() -> Vec<(Hash, Option<String>, Option<Hash>)> {
+            ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::MetadataUpdated(MetadataUpdated {
+                        content_hash,
+                        metadata_uri,
+                        logo_hash,
+                    }) => Some((content_hash, metadata_uri, logo_hash)),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        #[ink::test]
+        fn metadata_setters_check_length_and_show_in_contract_info() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.contract_info().metadata_uri, None);
+
+            let too_long = "a".repeat(MAX_METADATA_URI_LEN + 1);
+            assert_eq!(
+                erc20.set_metadata_uri(Some(too_long)),
+                Err(Error::UriTooLong)
+            );
+            let longest = "a".repeat(MAX_METADATA_URI_LEN);
+            assert_eq!(erc20.set_metadata_uri(Some(longest.clone())), Ok(()));
+            assert_eq!(erc20.metadata_uri(), Some(longest));
+
+            let uri = String::from("ipfs://token-metadata");
+            let logo = Hash::from([7; 32]);
+            assert_eq!(erc20.set_metadata_uri(Some(uri.clone())), Ok(()));
+            assert_eq!(erc20.set_logo_hash(Some(logo)), Ok(()));
+            assert_eq!(
+                erc20.contract_info(),
+                ContractInfo {
+                    abi_version: crate::ABI_VERSION,
+                    owner: accounts.alice,
+                    features: DEFAULT_FEATURES,
+                    whitepaper_hash: Hash::default(),
+                    metadata_uri: Some(uri.clone()),
+                    logo_hash: Some(logo),
+                }
+            );
+
+            let mut expected = [0u8; 32];
+            ink_env::hash_encoded::<Blake2x256, _>(&(Some(uri.clone()), Some(logo)), &mut expected);
+            let updates = metadata_updates();
+            assert_eq!(updates.len(), 3);
+            assert_eq!(
+                updates[2],
+                (Hash::from(expected), Some(uri.clone()), Some(logo))
+            );
+            // 内容不同, content_hash 也不同
+            assert_ne!(updates[1].0, updates[2].0);
+
+            assert_eq!(erc20.set_logo_hash(None), Ok(()));
+            assert_eq!(erc20.contract_info().logo_hash, None);
+            assert_eq!(erc20.metadata_uri(), Some(uri));
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_metadata_uri(None), Err(Error::NotOwner));
+            assert_eq!(erc20.set_logo_hash(None), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn metadata_updates_wait_for_timelock_when_enabled() {
+            let mut erc20 =
+                Erc20::new_with_features(1_000, DEFAULT_FEATURES | FEATURE_METADATA_TIMELOCK);
+            let uri = String::from("ipfs://token-metadata");
+            let logo = Hash::from([7; 32]);
+            assert_eq!(
+                erc20.execute_metadata_update(),
+                Err(Error::NoPendingMetadata)
+            );
+            assert_eq!(erc20.set_metadata_uri(Some(uri.clone())), Ok(()));
+            advance_blocks(10);
+            // 第二次排队保留已排队的 uri, 并重新计时
+            assert_eq!(erc20.set_logo_hash(Some(logo)), Ok(()));
+            assert_eq!(erc20.metadata_uri(), None);
+            assert_eq!(
+                erc20.pending_metadata(),
+                Some((Some(uri.clone()), Some(logo), 10 + METADATA_TIMELOCK_BLOCKS))
+            );
+
+            advance_blocks(METADATA_TIMELOCK_BLOCKS - 1);
+            assert_eq!(
+                erc20.execute_metadata_update(),
+                Err(Error::TimelockNotExpired)
+            );
+            assert!(metadata_updates().is_empty());
+            advance_blocks(1);
+            assert_eq!(erc20.execute_metadata_update(), Ok(()));
+            assert_eq!(erc20.pending_metadata(), None);
+            assert_eq!(erc20.contract_info().metadata_uri, Some(uri.clone()));
+            assert_eq!(erc20.contract_info().logo_hash, Some(logo));
+            assert_eq!(metadata_updates().len(), 1);
+        }
+
+        #[ink::test]
+        fn proposals_wait_for_grace_period_before_finalizing() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_execution_grace_period(5), Ok(()));
+            let proposal_id = erc20.propose(Hash::from([1; 32]), 2).unwrap();
+            assert_eq!(erc20.proposal(proposal_id).unwrap().eta, 7);
+            // 修改等待期不影响已有提案
+            assert_eq!(erc20.set_execution_grace_period(100), Ok(()));
+            advance_blocks(1);
+            assert_eq!(erc20.cast_vote(proposal_id, true), Ok(()));
+
+            advance_blocks(2);
+            assert_eq!(
+                erc20.finalize_proposal(proposal_id),
+                Err(Error::ProposalInGracePeriod)
+            );
+            advance_blocks(3);
+            assert_eq!(
+                erc20.finalize_proposal(proposal_id),
+                Err(Error::ProposalInGracePeriod)
+            );
+            advance_blocks(1);
+            assert_eq!(erc20.finalize_proposal(proposal_id), Ok(()));
+            assert_eq!(erc20.proposal(proposal_id).map(|p| p.executed), Some(true));
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_execution_grace_period(0), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn veto_guardian_blocks_proposal_during_grace_period() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_execution_grace_period(5), Ok(()));
+            assert_eq!(erc20.set_veto_guardian(Some(accounts.eve)), Ok(()));
+            let vetoed = erc20.propose(Hash::from([1; 32]), 2).unwrap();
+            let passed = erc20.propose(Hash::from([2; 32]), 2).unwrap();
+            advance_blocks(1);
+            assert_eq!(erc20.cast_vote(vetoed, true), Ok(()));
+            assert_eq!(erc20.cast_vote(passed, true), Ok(()));
+
+            // 投票期内不能否决
+            set_caller(accounts.eve);
+            assert_eq!(erc20.veto_proposal(vetoed), Err(Error::NotInGracePeriod));
+            advance_blocks(2);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.veto_proposal(vetoed), Err(Error::NotVetoGuardian));
+            set_caller(accounts.eve);
+            assert_eq!(erc20.veto_proposal(vetoed), Ok(()));
+            assert_eq!(erc20.veto_proposal(vetoed), Err(Error::ProposalWasVetoed));
+            assert_eq!(erc20.veto_proposal(9), Err(Error::ProposalNotFound));
+
+            advance_blocks(4);
+            // 等待期结束后不能再否决
+            assert_eq!(erc20.veto_proposal(passed), Err(Error::NotInGracePeriod));
+            assert_eq!(
+                erc20.finalize_proposal(vetoed),
+                Err(Error::ProposalWasVetoed)
+            );
+            assert_eq!(erc20.proposal(vetoed).map(|p| p.executed), Some(false));
+            assert_eq!(erc20.finalize_proposal(passed), Ok(()));
+
+            let vetoes = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::ProposalVetoed(ProposalVetoed {
+                        proposal_id,
+                        guardian,
+                    }) => Some((proposal_id, guardian)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(vetoes, vec![(vetoed, accounts.eve)]);
+        }
+
+        #[ink::test]
+        fn allowance_stats_accumulate_and_survive_revocation() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.approve(accounts.bob, 500), Ok(()));
+            assert_eq!(
+                erc20.allowance_stats(accounts.alice, accounts.bob),
+                (500, 0, None)
+            );
+
+            set_caller(accounts.bob);
+            advance_blocks(2);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.charlie, 100),
+                Ok(())
+            );
+            advance_blocks(3);
+            assert_eq!(erc20.burn_from(accounts.alice, 50), Ok(()));
+            // 0 金额不算使用
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.charlie, 0),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.allowance_stats(accounts.alice, accounts.bob),
+                (350, 150, Some(5))
+            );
+
+            // 撤销和重新授权都保留历史
+            set_caller(accounts.alice);
+            assert_eq!(erc20.approve(accounts.bob, 0), Ok(()));
+            assert_eq!(
+                erc20.allowance_stats(accounts.alice, accounts.bob),
+                (0, 150, Some(5))
+            );
+            assert_eq!(erc20.approve(accounts.bob, 10), Ok(()));
+            set_caller(accounts.bob);
+            advance_blocks(1);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.charlie, 10),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.allowance_stats(accounts.alice, accounts.bob),
+                (0, 160, Some(6))
+            );
+            assert_eq!(
+                erc20.allowance_stats(accounts.alice, accounts.charlie),
+                (0, 0, None)
+            );
+        }
+
+        #[ink::test]
+        fn allowance_stats_write_nothing_when_disabled() {
+            let mut erc20 =
+                Erc20::new_with_features(1_000, DEFAULT_FEATURES & !FEATURE_ALLOWANCE_STATS);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.approve(accounts.bob, 500), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.charlie, 100),
+                Ok(())
+            );
+            assert_eq!(erc20.burn_from(accounts.alice, 50), Ok(()));
+            assert_eq!(
+                erc20.allowance_stats(accounts.alice, accounts.bob),
+                (350, 0, None)
+            );
+            assert_eq!(
+                erc20.allowance_usage.get(&(accounts.alice, accounts.bob)),
+                None
+            );
+        }
+
+        fn genesis_leaf(original_address: &[u8], amount: Balance, recipient: AccountId) -> Hash {
+            let mut input = vec![MERKLE_LEAF_PREFIX];
+            input.extend_from_slice(original_address);
+            input.extend_from_slice(&amount.to_le_bytes());
+            input.extend_from_slice(recipient.as_ref());
+            let mut output = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(&input, &mut output);
+            Hash::from(output)
+        }
+
+        fn merkle_parent(left: Hash, right: Hash) -> Hash {
+            let (first, second) = if left.as_ref() <= right.as_ref() {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            let mut output = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(
+                &[&[MERKLE_NODE_PREFIX][..], first.as_ref(), second.as_ref()].concat(),
+                &mut output,
+            );
+            Hash::from(output)
+        }
+
+        #[ink::test]
+        fn genesis_claim_verifies_proof_and_converts_amount() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let addresses = [[0x11u8; 20], [0x22; 20], [0x33; 20], [0x44; 20]];
+            let amounts: [Balance; 4] = [10_000, 20_000, 30_000, 40_001];
+            let recipients = [
+                accounts.bob,
+                accounts.charlie,
+                accounts.eve,
+                accounts.django,
+            ];
+            let leaves = (0..4)
+                .map(|i| genesis_leaf(&addresses[i], amounts[i], recipients[i]))
+                .collect::<Vec<_>>();
+            let left = merkle_parent(leaves[0], leaves[1]);
+            let right = merkle_parent(leaves[2], leaves[3]);
+            let root = merkle_parent(left, right);
+
+            // 1 个快照代币换 1.5 个, 上限 70000
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.set_genesis_snapshot(root, 70_000, 15_000),
+                Err(Error::NotOwner)
+            );
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_genesis_snapshot(root, 70_000, 15_000), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.genesis_claim(10_000, vec![leaves[1], right], addresses[0].to_vec()),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 15_000);
+            assert_eq!(erc20.total_supply(), 16_000);
+            assert!(erc20.genesis_claimed(accounts.bob));
+            assert_eq!(
+                erc20.genesis_claim(20_000, vec![leaves[0], right], addresses[1].to_vec()),
+                Err(Error::GenesisAlreadyClaimed)
+            );
+
+            // 数量, 地址或证明不对都不能通过
+            set_caller(accounts.charlie);
+            assert_eq!(
+                erc20.genesis_claim(20_001, vec![leaves[0], right], addresses[1].to_vec()),
+                Err(Error::InvalidMerkleProof)
+            );
+            assert_eq!(
+                erc20.genesis_claim(20_000, vec![leaves[0], right], addresses[2].to_vec()),
+                Err(Error::InvalidMerkleProof)
+            );
+            assert_eq!(
+                erc20.genesis_claim(20_000, vec![leaves[0]], addresses[1].to_vec()),
+                Err(Error::InvalidMerkleProof)
+            );
+            // 证明绑定了领取账户, 拿到别人的叶子和证明也领不走
+            assert_eq!(
+                erc20.genesis_claim(10_000, vec![leaves[1], right], addresses[0].to_vec()),
+                Err(Error::InvalidMerkleProof)
+            );
+            assert_eq!(
+                erc20.genesis_claim(20_000, vec![leaves[0], right], addresses[1].to_vec()),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(accounts.charlie), 30_000);
+
+            // 40001 * 1.5 = 60001, 累计超过上限
+            set_caller(accounts.django);
+            assert_eq!(
+                erc20.genesis_claim(40_001, vec![leaves[2], left], addresses[3].to_vec()),
+                Err(Error::GenesisClaimCapExceeded)
+            );
+            assert_eq!(erc20.balance_of(accounts.django), 0);
+            assert!(!erc20.genesis_claimed(accounts.django));
+            assert_eq!(erc20.genesis_snapshot(), (root, 70_000, 15_000, 45_000));
+
+            let claims = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::GenesisClaimed(GenesisClaimed {
+                        claimer,
+                        original_address,
+                        amount,
+                        minted,
+                    }) => Some((claimer, original_address, amount, minted)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                claims,
+                vec![
+                    (accounts.bob, addresses[0].to_vec(), 10_000, 15_000),
+                    (accounts.charlie, addresses[1].to_vec(), 20_000, 30_000),
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn clawback_mint_locks_tokens_until_clawed_back() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(
+                erc20.mint_with_clawback(accounts.bob, 500, 0),
+                Err(Error::InvalidClawbackWindow)
+            );
+            let mint_id = erc20.mint_with_clawback(accounts.bob, 500, 10).unwrap();
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 600);
+            assert_eq!(erc20.clawback_locked_of(accounts.bob), 500);
+
+            // 只有未锁定的部分可以转出或销毁
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer(accounts.charlie, 101),
+                Err(Error::ClawbackLocked)
+            );
+            assert_eq!(erc20.burn(101), Err(Error::ClawbackLocked));
+            assert_eq!(erc20.transfer(accounts.charlie, 60), Ok(()));
+            assert_eq!(erc20.burn(40), Ok(()));
+            assert_eq!(
+                erc20.transfer(accounts.charlie, 1),
+                Err(Error::ClawbackLocked)
+            );
+            assert_eq!(erc20.clawback(mint_id), Err(Error::NotMinter));
+
+            set_caller(accounts.alice);
+            advance_blocks(9);
+            assert_eq!(erc20.clawback(mint_id), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+            assert_eq!(erc20.total_supply(), 960);
+            assert_eq!(erc20.clawback_locked_of(accounts.bob), 0);
+            assert_eq!(erc20.clawback_ids_of(accounts.bob), Vec::<u64>::new());
+            assert_eq!(erc20.clawback(mint_id), Err(Error::ClawbackNotFound));
+
+            let clawed = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::ClawedBack(ClawedBack {
+                        mint_id,
+                        recipient,
+                        amount,
+                    }) => Some((mint_id, recipient, amount)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(clawed, vec![(mint_id, accounts.bob, 500)]);
+        }
+
+        #[ink::test]
+        fn clawback_mint_matures_after_window() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let mint_id = erc20.mint_with_clawback(accounts.bob, 500, 10).unwrap();
+            assert_eq!(
+                erc20.clawback_mint(mint_id),
+                Some(ClawbackMint {
+                    minter: accounts.alice,
+                    recipient: accounts.bob,
+                    amount: 500,
+                    expires_at: 10,
+                })
+            );
+            assert_eq!(
+                erc20.mature_clawback(mint_id),
+                Err(Error::ClawbackWindowOpen)
+            );
+
+            advance_blocks(10);
+            // 窗口结束后不需要确认就可以转出
+            assert_eq!(erc20.clawback_locked_of(accounts.bob), 0);
+            assert_eq!(erc20.clawback(mint_id), Err(Error::ClawbackWindowClosed));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 500), Ok(()));
+
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.mature_clawback(mint_id), Ok(()));
+            assert_eq!(erc20.clawback_mint(mint_id), None);
+            assert_eq!(erc20.mature_clawback(mint_id), Err(Error::ClawbackNotFound));
+
+            let matured = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::ClawbackMatured(ClawbackMatured {
+                        mint_id,
+                        recipient,
+                        amount,
+                    }) => Some((mint_id, recipient, amount)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(matured, vec![(mint_id, accounts.bob, 500)]);
+        }
+
+        #[ink::test]
+        fn team_allocation_releases_stage_by_stage() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(
+                erc20.create_team_allocation(
+                    accounts.bob,
+                    1_000,
+                    10,
+                    vec![(10, 2_500), (20, 2_500)]
+                ),
+                Err(Error::InvalidAllocationStages)
+            );
+            assert_eq!(
+                erc20.create_team_allocation(
+                    accounts.bob,
+                    1_000,
+                    10,
+                    vec![(20, 5_000), (20, 5_000)]
+                ),
+                Err(Error::InvalidAllocationStages)
+            );
+            let stages = vec![(5, 2_000), (20, 3_000), (30, 5_000)];
+            let id = erc20
+                .create_team_allocation(accounts.bob, 1_001, 10, stages.clone())
+                .unwrap();
+            assert_eq!(erc20.balance_of(accounts.alice), 8_999);
+            assert_eq!(erc20.balance_of(contract), 1_001);
+
+            advance_blocks(9);
+            assert_eq!(
+                erc20.release_team_allocation(id),
+                Err(Error::CliffNotReached)
+            );
+            // cliff 时释放 cliff 之前已到期的第一阶段
+            advance_blocks(1);
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.release_team_allocation(id), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 200);
+            assert_eq!(
+                erc20.release_team_allocation(id),
+                Err(Error::NothingToRelease)
+            );
+
+            // 一次跨过两个阶段, 最后一个阶段取整后的零头也一起释放
+            advance_blocks(25);
+            assert_eq!(erc20.release_team_allocation(id), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 1_001);
+            assert_eq!(erc20.balance_of(contract), 0);
+            assert_eq!(
+                erc20.allocation_info(id),
+                Some(TeamAllocation {
+                    beneficiary: accounts.bob,
+                    total: 1_001,
+                    cliff_block: 10,
+                    stages,
+                    released: 1_001,
+                })
+            );
+            assert_eq!(
+                erc20.release_team_allocation(id),
+                Err(Error::NothingToRelease)
+            );
+            assert_eq!(
+                erc20.release_team_allocation(id + 1),
+                Err(Error::AllocationNotFound)
+            );
+
+            let releases = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::AllocationStageReleased(AllocationStageReleased {
+                        id,
+                        stage_index,
+                        amount,
+                    }) => Some((id, stage_index, amount)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(releases, vec![(id, 0, 200), (id, 2, 801)]);
+        }
+
+        #[ink::test]
+        fn team_allocation_releases_each_stage_in_turn() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let id = erc20
+                .create_team_allocation(
+                    accounts.bob,
+                    1_000,
+                    0,
+                    vec![(10, 2_500), (20, 2_500), (30, 5_000)],
+                )
+                .unwrap();
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.create_team_allocation(accounts.bob, 1, 0, vec![(1, 10_000)]),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(
+                erc20.release_team_allocation(id),
+                Err(Error::NothingToRelease)
+            );
+            let mut expected = Vec::new();
+            for (stage, released) in [250, 500, 1_000].iter().enumerate() {
+                advance_blocks(10);
+                assert_eq!(erc20.release_team_allocation(id), Ok(()));
+                assert_eq!(erc20.balance_of(accounts.bob), *released);
+                assert_eq!(erc20.allocation_info(id).unwrap().released, *released);
+                expected.push(stage as u32);
+            }
+            let stages = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::AllocationStageReleased(AllocationStageReleased {
+                        stage_index, ..
+                    }) => Some(stage_index),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(stages, expected);
+        }
+
+        #[ink::test]
+        fn fee_rebate_follows_volume_tiers_per_window() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            // 1% 手续费全部进入金库; 发送量 1000 起返还 10%, 5000 起返还 50%
+            assert_eq!(erc20.set_fee_strategy(FeeStrategy::Bps(100)), Ok(()));
+            assert_eq!(erc20.set_protocol_fee(10_000), Ok(()));
+            assert_eq!(
+                erc20.set_fee_rebate_tiers(vec![(5_000, 5_000), (1_000, 1_000)]),
+                Err(Error::InvalidRebateTiers)
+            );
+            assert_eq!(
+                erc20.set_fee_rebate_tiers(vec![(1_000, 1_000), (5_000, 5_000)]),
+                Ok(())
+            );
+            assert_eq!(erc20.set_fee_rebate_window(100), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 20_000), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 500), Ok(()));
+            assert_eq!(erc20.fee_rebate_tier_of(accounts.bob), None);
+            assert_eq!(erc20.pending_fee_rebate(accounts.bob), 0);
+            assert_eq!(erc20.claim_fee_rebate(), Err(Error::NothingToWithdraw));
+            assert_eq!(erc20.transfer(accounts.charlie, 3_500), Ok(()));
+            assert_eq!(erc20.fee_rebate_tier_of(accounts.bob), Some(0));
+            assert_eq!(erc20.pending_fee_rebate(accounts.bob), 4);
+            assert_eq!(erc20.transfer(accounts.charlie, 2_000), Ok(()));
+            assert_eq!(erc20.fee_rebate_tier_of(accounts.bob), Some(1));
+            assert_eq!(erc20.pending_fee_rebate(accounts.bob), 30);
+            assert_eq!(erc20.treasury_balance(), 60);
+
+            let before = erc20.balance_of(accounts.bob);
+            assert_eq!(erc20.claim_fee_rebate(), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), before + 30);
+            assert_eq!(erc20.treasury_balance(), 30);
+            assert_eq!(erc20.pending_fee_rebate(accounts.bob), 0);
+            assert_eq!(
+                erc20.claim_fee_rebate(),
+                Err(Error::FeeRebateAlreadyClaimed)
+            );
+            // 同一窗口内领取后再付的手续费不再返还
+            assert_eq!(erc20.transfer(accounts.charlie, 1_000), Ok(()));
+            assert_eq!(
+                erc20.claim_fee_rebate(),
+                Err(Error::FeeRebateAlreadyClaimed)
+            );
+
+            // 进入新窗口后重新统计
+            advance_blocks(100);
+            assert_eq!(erc20.fee_rebate_tier_of(accounts.bob), None);
+            assert_eq!(erc20.claim_fee_rebate(), Err(Error::NothingToWithdraw));
+            assert_eq!(erc20.transfer(accounts.charlie, 1_000), Ok(()));
+            assert_eq!(
+                erc20.sender_fee_window(accounts.bob),
+                Some(SenderFeeWindow {
+                    window: 1,
+                    volume: 1_000,
+                    fees_paid: 10,
+                    claimed: false,
+                })
+            );
+            assert_eq!(erc20.claim_fee_rebate(), Ok(()));
+            assert_eq!(erc20.treasury_balance(), 49);
+
+            let claims = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::FeeRebateClaimed(FeeRebateClaimed { window, rebate, .. }) => {
+                        Some((window, rebate))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(claims, vec![(0, 30), (1, 1)]);
+        }
+
+        #[ink::test]
+        fn fee_rebate_can_be_claimed_after_window_rolls_over() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_fee_strategy(FeeStrategy::Bps(100)), Ok(()));
+            assert_eq!(erc20.set_protocol_fee(10_000), Ok(()));
+            assert_eq!(erc20.set_fee_rebate_tiers(vec![(1_000, 1_000)]), Ok(()));
+            assert_eq!(erc20.set_fee_rebate_window(100), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 20_000), Ok(()));
+
+            // 窗口 0 和窗口 1 都没有领取, 窗口 2 的第一笔转账结转窗口 1 时不丢失窗口 0
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 2_000), Ok(()));
+            advance_blocks(100);
+            assert_eq!(erc20.pending_fee_rebate(accounts.bob), 2);
+            assert_eq!(erc20.transfer(accounts.charlie, 3_000), Ok(()));
+            assert_eq!(erc20.pending_fee_rebate(accounts.bob), 5);
+            advance_blocks(100);
+            assert_eq!(erc20.transfer(accounts.charlie, 500), Ok(()));
+            assert_eq!(erc20.fee_rebate_tier_of(accounts.bob), None);
+            assert_eq!(erc20.pending_fee_rebate(accounts.bob), 5);
+
+            let before = erc20.balance_of(accounts.bob);
+            assert_eq!(erc20.claim_fee_rebate(), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), before + 5);
+            assert_eq!(erc20.pending_fee_rebate(accounts.bob), 0);
+            assert_eq!(erc20.claim_fee_rebate(), Err(Error::NothingToWithdraw));
+
+            // 当前窗口没有达到档位, 领取结转部分不影响之后达到档位时的返还
+            assert_eq!(erc20.transfer(accounts.charlie, 500), Ok(()));
+            assert_eq!(erc20.pending_fee_rebate(accounts.bob), 1);
+            assert_eq!(erc20.claim_fee_rebate(), Ok(()));
+
+            let claims = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::FeeRebateClaimed(FeeRebateClaimed {
+                        window,
+                        fees_paid,
+                        rebate,
+                        ..
+                    }) => Some((window, fees_paid, rebate)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(claims, vec![(1, 50, 5), (2, 10, 1)]);
+        }
+
+        fn signed_batch(
+            erc20: &Erc20,
+            secret: &secp256k1::SecretKey,
+            nonce: u64,
+            state_root: Hash,
+            transfers: &[BatchedTransfer],
+        ) -> [u8; 65] {
+            let hash = erc20.settlement_hash(nonce, state_root, transfers.to_vec());
+            sign_hash(secret, hash.as_ref())
+        }
+
+        fn batched_transfer(
+            erc20: &Erc20,
+            secret: &secp256k1::SecretKey,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            nonce: u64,
+        ) -> BatchedTransfer {
+            let hash = erc20.batched_transfer_hash(from, to, value, nonce);
+            BatchedTransfer {
+                from,
+                to,
+                value,
+                nonce,
+                signature: sign_hash(secret, hash.as_ref()),
+            }
+        }
+
+        fn state_root_of(post_state: &[(AccountId, Balance)]) -> Hash {
+            let mut output = [0u8; 32];
+            ink_env::hash_encoded::<Blake2x256, _>(&post_state.to_vec(), &mut output);
+            Hash::from(output)
+        }
+
+        #[ink::test]
+        fn settle_batch_checks_operator_signature_and_nonce() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, operator) = test_signer(1);
+            let (other_secret, _) = test_signer(2);
+            let (user_secret, user) = test_signer(3);
+            let (payer_secret, payer) = test_signer(4);
+            assert_eq!(erc20.set_settlement_operator(Some(operator)), Ok(()));
+            assert_eq!(erc20.set_challenge_period_blocks(10), Ok(()));
+            assert_eq!(erc20.transfer(user, 1_000), Ok(()));
+            assert_eq!(erc20.transfer(payer, 100), Ok(()));
+            for account in [user, payer].iter() {
+                set_caller(*account);
+                assert_eq!(erc20.set_optimistic_participation(true), Ok(()));
+            }
+
+            let transfers = vec![
+                batched_transfer(&erc20, &user_secret, user, accounts.charlie, 300, 0),
+                batched_transfer(&erc20, &payer_secret, payer, accounts.django, 100, 0),
+                batched_transfer(&erc20, &user_secret, user, accounts.django, 50, 1),
+            ];
+            let root = state_root_of(&[
+                (user, 650),
+                (accounts.charlie, 300),
+                (payer, 0),
+                (accounts.django, 150),
+            ]);
+            let signature = signed_batch(&erc20, &secret, 0, root, &transfers);
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.settle_batch(0, root, transfers.clone(), signature),
+                Err(Error::NotSettlementOperator)
+            );
+            set_caller(operator);
+            assert_eq!(
+                erc20.settle_batch(1, root, transfers.clone(), signature),
+                Err(Error::InvalidNonce)
+            );
+            let forged = signed_batch(&erc20, &other_secret, 0, root, &transfers);
+            assert_eq!(
+                erc20.settle_batch(0, root, transfers.clone(), forged),
+                Err(Error::InvalidSettlementSignature)
+            );
+            // 签名覆盖转账列表
+            let mut tampered = transfers.clone();
+            tampered[0].value = 900;
+            assert_eq!(
+                erc20.settle_batch(0, root, tampered, signature),
+                Err(Error::InvalidSettlementSignature)
+            );
+            let unapproved = vec![batched_transfer(
+                &erc20,
+                &secret,
+                accounts.alice,
+                accounts.bob,
+                1,
+                0,
+            )];
+            assert_eq!(
+                erc20.settle_batch(
+                    0,
+                    root,
+                    unapproved.clone(),
+                    signed_batch(&erc20, &secret, 0, root, &unapproved)
+                ),
+                Err(Error::NotOptimisticParticipant)
+            );
+            // 同一个转出账户的 nonce 必须连续
+            let skipped = vec![batched_transfer(
+                &erc20,
+                &user_secret,
+                user,
+                accounts.charlie,
+                1,
+                1,
+            )];
+            assert_eq!(
+                erc20.settle_batch(
+                    0,
+                    root,
+                    skipped.clone(),
+                    signed_batch(&erc20, &secret, 0, root, &skipped)
+                ),
+                Err(Error::InvalidNonce)
+            );
+            // 收到的代币在批次完成前不能再转出, 合计超过余额时整批不执行
+            let overdrawn = vec![
+                batched_transfer(&erc20, &user_secret, user, payer, 300, 0),
+                batched_transfer(&erc20, &payer_secret, payer, accounts.django, 101, 0),
+            ];
+            assert_eq!(
+                erc20.settle_batch(
+                    0,
+                    root,
+                    overdrawn.clone(),
+                    signed_batch(&erc20, &secret, 0, root, &overdrawn)
+                ),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(erc20.balance_of(user), 1_000);
+            assert_eq!(erc20.batched_transfer_nonce(user), 0);
+
+            assert_eq!(
+                erc20.settle_batch(0, root, transfers.clone(), signature),
+                Ok(())
+            );
+            // 转出的代币托管到挑战期结束
+            assert_eq!(erc20.balance_of(user), 650);
+            assert_eq!(erc20.balance_of(payer), 0);
+            assert_eq!(erc20.balance_of(accounts.charlie), 0);
+            assert_eq!(erc20.obligation(PoolId::SettlementBatches), 450);
+            assert_eq!(erc20.batched_transfer_nonce(user), 2);
+            assert_eq!(erc20.batched_transfer_nonce(payer), 1);
+            assert_eq!(erc20.settlement_config(), (Some(operator), 1, 10));
+            // 同一批次不能重放
+            assert_eq!(
+                erc20.settle_batch(0, root, transfers.clone(), signature),
+                Err(Error::InvalidNonce)
+            );
+            assert_eq!(erc20.finalize_batch(0), Err(Error::ChallengePeriodNotOver));
+
+            advance_blocks(11);
+            set_caller(accounts.eve);
+            assert_eq!(erc20.finalize_batch(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 300);
+            assert_eq!(erc20.balance_of(accounts.django), 150);
+            assert_eq!(erc20.obligation(PoolId::SettlementBatches), 0);
+            assert!(erc20.settled_batch(0).unwrap().finalized);
+            assert_eq!(erc20.finalize_batch(0), Err(Error::BatchAlreadyFinalized));
+            assert_obligations_reconcile(&erc20);
+
+            set_caller(operator);
+            let next = vec![batched_transfer(
+                &erc20,
+                &user_secret,
+                user,
+                accounts.bob,
+                50,
+                2,
+            )];
+            let next_signature = signed_batch(&erc20, &secret, 1, root, &next);
+            assert_eq!(erc20.settle_batch(1, root, next, next_signature), Ok(()));
+            assert_eq!(erc20.settlement_config().1, 2);
+
+            let settled = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::BatchSettled(BatchSettled {
+                        nonce,
+                        transfer_count,
+                    }) => Some((nonce, transfer_count)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(settled, vec![(0, 3), (1, 1)]);
+        }
+
+        #[ink::test]
+        fn unauthorized_batch_transfer_is_refunded_within_challenge_period() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, operator) = test_signer(1);
+            let (user_secret, user) = test_signer(3);
+            let (victim_secret, victim) = test_signer(4);
+            assert_eq!(erc20.set_settlement_operator(Some(operator)), Ok(()));
+            assert_eq!(erc20.set_challenge_period_blocks(10), Ok(()));
+            assert_eq!(erc20.transfer(user, 1_000), Ok(()));
+            assert_eq!(erc20.transfer(victim, 1_000), Ok(()));
+            for account in [user, victim].iter() {
+                set_caller(*account);
+                assert_eq!(erc20.set_optimistic_participation(true), Ok(()));
+            }
+
+            // 运营方用自己的签名冒充 victim, 把它的代币转给自己
+            let root = Hash::from([9; 32]);
+            let transfers = vec![
+                batched_transfer(&erc20, &user_secret, user, accounts.charlie, 100, 0),
+                batched_transfer(&erc20, &secret, victim, operator, 1_000, 0),
+            ];
+            set_caller(operator);
+            let signature = signed_batch(&erc20, &secret, 0, root, &transfers);
+            assert_eq!(
+                erc20.settle_batch(0, root, transfers.clone(), signature),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(operator), 0);
+            assert_eq!(erc20.balance_of(victim), 0);
+
+            set_caller(accounts.eve);
+            // 签名有效的那笔不能作为证明
+            assert_eq!(
+                erc20.challenge_settlement(
+                    0,
+                    FraudProof::UnauthorizedTransfer {
+                        transfers: transfers.clone(),
+                        index: 0,
+                    }
+                ),
+                Err(Error::InvalidFraudProof)
+            );
+            // 提交的转账列表必须与批次一致
+            let mut substituted = transfers.clone();
+            substituted[1] = batched_transfer(&erc20, &victim_secret, victim, operator, 1_000, 0);
+            assert_eq!(
+                erc20.challenge_settlement(
+                    0,
+                    FraudProof::UnauthorizedTransfer {
+                        transfers: substituted,
+                        index: 0,
+                    }
+                ),
+                Err(Error::InvalidFraudProof)
+            );
+            assert_eq!(
+                erc20.challenge_settlement(
+                    0,
+                    FraudProof::UnauthorizedTransfer {
+                        transfers: transfers.clone(),
+                        index: 2,
+                    }
+                ),
+                Err(Error::InvalidFraudProof)
+            );
+            assert_eq!(
+                erc20.challenge_settlement(
+                    1,
+                    FraudProof::UnauthorizedTransfer {
+                        transfers: transfers.clone(),
+                        index: 1,
+                    }
+                ),
+                Err(Error::BatchNotFound)
+            );
+
+            assert_eq!(
+                erc20.challenge_settlement(
+                    0,
+                    FraudProof::UnauthorizedTransfer {
+                        transfers: transfers.clone(),
+                        index: 1,
+                    }
+                ),
+                Ok(())
+            );
+            // 整个批次退回转出账户, 收款方没有被扣款
+            assert_eq!(erc20.balance_of(victim), 1_000);
+            assert_eq!(erc20.balance_of(user), 1_000);
+            assert_eq!(erc20.balance_of(accounts.charlie), 0);
+            assert_eq!(erc20.obligation(PoolId::SettlementBatches), 0);
+            assert!(erc20.settled_batch(0).unwrap().rolled_back);
+            assert_eq!(
+                erc20.challenge_settlement(
+                    0,
+                    FraudProof::UnauthorizedTransfer {
+                        transfers,
+                        index: 1,
+                    }
+                ),
+                Err(Error::BatchAlreadyRolledBack)
+            );
+            assert_eq!(erc20.finalize_batch(0), Err(Error::BatchAlreadyRolledBack));
+            assert_obligations_reconcile(&erc20);
+        }
+
+        #[ink::test]
+        fn wrong_state_root_is_refunded_within_challenge_period() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, operator) = test_signer(1);
+            let (user_secret, user) = test_signer(3);
+            assert_eq!(erc20.set_settlement_operator(Some(operator)), Ok(()));
+            assert_eq!(erc20.set_challenge_period_blocks(10), Ok(()));
+            assert_eq!(erc20.transfer(user, 1_000), Ok(()));
+            set_caller(user);
+            assert_eq!(erc20.set_optimistic_participation(true), Ok(()));
+
+            let post_state = vec![(user, 600), (accounts.charlie, 400)];
+            let honest_root = state_root_of(&post_state);
+            // 运营方提交了错误的状态根
+            let wrong_root = Hash::from([9; 32]);
+            set_caller(operator);
+            for (nonce, root) in [(0u64, honest_root), (1, wrong_root)].iter() {
+                let transfers = vec![batched_transfer(
+                    &erc20,
+                    &user_secret,
+                    user,
+                    accounts.charlie,
+                    400,
+                    *nonce,
+                )];
+                let signature = signed_batch(&erc20, &secret, *nonce, *root, &transfers);
+                assert_eq!(
+                    erc20.settle_batch(*nonce, *root, transfers, signature),
+                    Ok(())
+                );
+            }
+            assert_eq!(erc20.balance_of(user), 200);
+
+            let proof = |state: Vec<(AccountId, Balance)>| FraudProof::WrongStateRoot {
+                post_state: scale::Encode::encode(&state),
+            };
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.challenge_settlement(0, proof(post_state.clone())),
+                Err(Error::InvalidFraudProof)
+            );
+            // 谎报的执行结果不能作为证明
+            assert_eq!(
+                erc20.challenge_settlement(1, proof(vec![(user, 1_000), (accounts.charlie, 0)])),
+                Err(Error::InvalidFraudProof)
+            );
+            assert_eq!(
+                erc20.challenge_settlement(
+                    1,
+                    FraudProof::WrongStateRoot {
+                        post_state: vec![1, 2, 3]
+                    }
+                ),
+                Err(Error::InvalidFraudProof)
+            );
+
+            // 第二批的执行结果按批次自己的执行前余额重算: user 200, charlie 400
+            let second_post = vec![(user, 200), (accounts.charlie, 400)];
+            assert_eq!(
+                erc20.challenge_settlement(1, proof(second_post.clone())),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(user), 600);
+            assert_eq!(erc20.obligation(PoolId::SettlementBatches), 400);
+
+            // 挑战期过后不能再挑战, 只能完成
+            advance_blocks(11);
+            assert_eq!(
+                erc20.challenge_settlement(0, proof(vec![(user, 1_000)])),
+                Err(Error::ChallengePeriodOver)
+            );
+            assert_eq!(erc20.finalize_batch(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 400);
+
+            let challenged = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::BatchChallenged(BatchChallenged { nonce }) => Some(nonce),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(challenged, vec![1]);
+        }
+
+        fn welcome_bonuses() -> Vec<(AccountId, Balance)> {
+            ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::WelcomeBonusPaid(WelcomeBonusPaid { account, bonus }) => {
+                        Some((account, bonus))
+                    }
+                    _ => None,
+                })
+                .collect()
+        }
+
+        #[ink::test]
+        fn welcome_bonus_stops_when_pool_runs_out() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.start_campaign(100, 99), Err(Error::InvalidCampaign));
+            assert_eq!(erc20.start_campaign(0, 100), Err(Error::InvalidCampaign));
+            assert_eq!(erc20.start_campaign(100, 250), Ok(()));
+            assert_eq!(
+                erc20.start_campaign(100, 250),
+                Err(Error::CampaignAlreadyActive)
+            );
+            assert_eq!(erc20.balance_of(accounts.alice), 9_750);
+
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 110);
+            assert_eq!(erc20.balance_of(accounts.charlie), 110);
+            assert_eq!(erc20.campaign_info(), (true, 100, 50));
+
+            // 剩余 50 不够一份奖励, 不发部分奖励
+            assert_eq!(erc20.transfer(accounts.django, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.django), 10);
+            assert_eq!(erc20.campaign_info(), (true, 100, 50));
+            assert_eq!(erc20.total_supply(), 10_000);
+
+            assert_eq!(
+                welcome_bonuses(),
+                vec![(accounts.bob, 100), (accounts.charlie, 100)]
+            );
+            let exhausted = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::WelcomePoolExhausted(WelcomePoolExhausted { remaining }) => {
+                        Some(remaining)
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(exhausted, vec![50]);
+        }
+
+        #[ink::test]
+        fn welcome_bonus_is_paid_once_per_account() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.charlie, 10), Ok(()));
+            assert_eq!(erc20.start_campaign(100, 1_000), Ok(()));
+
+            assert_eq!(erc20.transfer(accounts.bob, 0), Ok(()));
+            assert!(!erc20.is_welcomed(accounts.bob));
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 110);
+            assert!(erc20.is_welcomed(accounts.bob));
+
+            // 转空后再收款不再发放
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.eve, 110), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 10);
+            // 已经持有代币的账户不是新账户
+            assert_eq!(erc20.transfer(accounts.charlie, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 20);
+            assert!(erc20.is_welcomed(accounts.charlie));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 30);
+
+            assert_eq!(
+                welcome_bonuses(),
+                vec![(accounts.bob, 100), (accounts.eve, 100)]
+            );
+            assert_eq!(erc20.campaign_info(), (true, 100, 800));
+            assert_eq!(erc20.balance_of(accounts.eve), 210);
+        }
+
+        #[ink::test]
+        fn stop_campaign_refunds_pool_and_ends_bonuses() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.stop_campaign(), Err(Error::NoActiveCampaign));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.start_campaign(100, 1_000), Err(Error::NotOwner));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.start_campaign(100, 1_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stop_campaign(), Err(Error::NotOwner));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.stop_campaign(), Ok(()));
+            assert_eq!(erc20.campaign_info(), (false, 100, 0));
+            assert_eq!(erc20.balance_of(accounts.alice), 9_890);
+
+            assert_eq!(erc20.transfer(accounts.charlie, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 10);
+            assert!(!erc20.is_welcomed(accounts.charlie));
+
+            // 新一期活动中已经领过的账户不再领取
+            assert_eq!(erc20.start_campaign(50, 500), Ok(()));
+            assert_eq!(erc20.transfer(accounts.django, 10), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.eve, 110), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 10);
+            assert_eq!(
+                welcome_bonuses(),
+                vec![
+                    (accounts.bob, 100),
+                    (accounts.django, 50),
+                    (accounts.eve, 50)
+                ]
+            );
+
+            let stopped = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::CampaignStopped(CampaignStopped { refunded }) => Some(refunded),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(stopped, vec![900]);
+        }
+
+        // 手续费 1%, 其中一半进金库; 另外 1% 注入奖池但不会中奖
+        fn setup_fee_split(erc20: &mut Erc20, recipient: AccountId) {
+            assert_eq!(erc20.set_transfer_fee(100, recipient), Ok(()));
+            // alice 持有创世 NFT 免手续费, 先转走
+            assert_eq!(
+                erc20.transfer_genesis_nft(AccountId::from([0xff; 32])),
+                Ok(())
+            );
+            assert_eq!(erc20.set_protocol_fee(5_000), Ok(()));
+            assert_eq!(erc20.set_jackpot_params(0, 100), Ok(()));
+        }
+
+        #[ink::test]
+        fn fee_statistics_aggregate_every_fee_destination() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.fee_statistics(), FeeStatistics::default());
+            setup_fee_split(&mut erc20, accounts.django);
+
+            assert_eq!(erc20.transfer(accounts.bob, 1_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 2_000), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 2_940);
+            assert_eq!(
+                erc20.fee_statistics(),
+                FeeStatistics {
+                    total_fees_collected: 30,
+                    recipient_fees: 15,
+                    treasury_fees: 15,
+                    jackpot_contributions: 30,
+                    fee_collections_count: 2,
+                    avg_fee_per_transfer: 15,
+                }
+            );
+            assert_eq!(
+                erc20.fee_statistics().total_fees_collected,
+                erc20.fees_collected()
+            );
+            assert_eq!(erc20.balance_of(accounts.django), 15);
+
+            // 免手续费的转账不计入笔数
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.eve, 0), Ok(()));
+            assert_eq!(erc20.fee_statistics().fee_collections_count, 2);
+        }
+
+        #[ink::test]
+        fn fee_statistics_snapshots_are_isolated_and_rate_limited() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            setup_fee_split(&mut erc20, accounts.django);
+            assert_eq!(erc20.transfer(accounts.bob, 1_000), Ok(()));
+
+            let first = erc20.snapshot_fee_statistics().unwrap();
+            assert_eq!(
+                erc20.fee_statistics_since(first),
+                Ok(FeeStatistics::default())
+            );
+            advance_blocks(FEE_SNAPSHOT_INTERVAL_BLOCKS - 1);
+            assert_eq!(
+                erc20.snapshot_fee_statistics(),
+                Err(Error::FeeSnapshotTooSoon)
+            );
+
+            assert_eq!(erc20.transfer(accounts.bob, 3_000), Ok(()));
+            advance_blocks(1);
+            let second = erc20.snapshot_fee_statistics().unwrap();
+            assert_eq!(second, first + FEE_SNAPSHOT_INTERVAL_BLOCKS);
+            assert_eq!(erc20.transfer(accounts.bob, 5_000), Ok(()));
+
+            assert_eq!(
+                erc20.fee_statistics_since(first),
+                Ok(FeeStatistics {
+                    total_fees_collected: 80,
+                    recipient_fees: 40,
+                    treasury_fees: 40,
+                    jackpot_contributions: 80,
+                    fee_collections_count: 2,
+                    avg_fee_per_transfer: 40,
+                })
+            );
+            // 后一个快照不影响前一个快照的基准
+            assert_eq!(
+                erc20.fee_statistics_since(second),
+                Ok(FeeStatistics {
+                    total_fees_collected: 50,
+                    recipient_fees: 25,
+                    treasury_fees: 25,
+                    jackpot_contributions: 50,
+                    fee_collections_count: 1,
+                    avg_fee_per_transfer: 50,
+                })
+            );
+            assert_eq!(erc20.fee_statistics().fee_collections_count, 3);
+            assert_eq!(
+                erc20.fee_statistics_since(first + 1),
+                Err(Error::FeeSnapshotNotFound)
+            );
+        }
+
+        fn hashlock_of(preimage: &[u8]) -> Hash {
+            let mut output = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(preimage, &mut output);
+            Hash::from(output)
+        }
+
+        fn now() -> Timestamp {
+            ink_env::block_timestamp::<ink_env::DefaultEnvironment>().expect("Cannot get timestamp")
+        }
+
+        fn advance_until(timestamp: Timestamp) {
+            while now() < timestamp {
+                advance_blocks(1);
+            }
+        }
+
+        #[ink::test]
+        fn htlc_claim_reveals_preimage() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let preimage = b"cross-chain secret".to_vec();
+            let hashlock = hashlock_of(&preimage);
+            assert_eq!(
+                erc20.htlc_lock(accounts.bob, 300, hashlock, now()),
+                Err(Error::InvalidTimelock)
+            );
+            let timelock = now() + 1;
+            assert_eq!(
+                erc20.htlc_lock(accounts.bob, 300, hashlock, timelock),
+                Ok(0)
+            );
+            assert_eq!(erc20.balance_of(contract), 300);
+            assert_eq!(erc20.htlc_preimage(0), None);
+
+            assert_eq!(
+                erc20.htlc_claim(0, preimage.clone()),
+                Err(Error::NotHtlcRecipient)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.htlc_claim(0, b"guess".to_vec()),
+                Err(Error::WrongPreimage)
+            );
+            assert_eq!(erc20.htlc_refund(0), Err(Error::HtlcNotExpired));
+            assert_eq!(erc20.htlc_claim(0, preimage.clone()), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 300);
+            assert_eq!(erc20.balance_of(contract), 0);
+            assert_eq!(erc20.htlc_preimage(0), Some(preimage.clone()));
+            assert_eq!(erc20.htlc(0).unwrap().status, HtlcStatus::Claimed);
+
+            assert_eq!(
+                erc20.htlc_claim(0, preimage.clone()),
+                Err(Error::HtlcAlreadySettled)
+            );
+            advance_until(timelock);
+            assert_eq!(erc20.htlc_refund(0), Err(Error::HtlcAlreadySettled));
+            assert_eq!(
+                erc20.htlc_claim(1, preimage.clone()),
+                Err(Error::HtlcNotFound)
+            );
+
+            let claimed = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::HtlcClaimed(HtlcClaimed {
+                        id,
+                        hashlock,
+                        preimage,
+                    }) => Some((id, hashlock, preimage)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(claimed, vec![(0, hashlock, preimage)]);
+        }
+
+        #[ink::test]
+        fn htlc_refunds_sender_after_timelock() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let preimage = b"never revealed".to_vec();
+            let hashlock = hashlock_of(&preimage);
+            let timelock = now() + 1;
+            assert_eq!(
+                erc20.htlc_lock(accounts.bob, 400, hashlock, timelock),
+                Ok(0)
+            );
+            assert_eq!(erc20.balance_of(accounts.alice), 600);
+
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.htlc_refund(0), Err(Error::HtlcNotExpired));
+            advance_until(timelock);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.htlc_claim(0, preimage), Err(Error::HtlcExpired));
+            // 任何人都可以触发退款, 代币只退回 sender
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.htlc_refund(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 1_000);
+            assert_eq!(erc20.balance_of(accounts.charlie), 0);
+            assert_eq!(erc20.htlc(0).unwrap().status, HtlcStatus::Refunded);
+            assert_eq!(erc20.htlc_preimage(0), None);
+            assert_eq!(erc20.htlc_refund(0), Err(Error::HtlcAlreadySettled));
+
+            let refunded = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::HtlcRefunded(HtlcRefunded { id, hashlock }) => Some((id, hashlock)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(refunded, vec![(0, hashlock)]);
+        }
+
+        #[ink::test]
+        fn delegate_split_divides_votes_and_preserves_total() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let holders = [
+                accounts.alice,
+                accounts.bob,
+                accounts.charlie,
+                accounts.django,
+                accounts.eve,
+            ];
+            let total_votes = |erc20: &Erc20| -> Balance {
+                holders.iter().map(|holder| erc20.get_votes(*holder)).sum()
+            };
+            assert_eq!(erc20.transfer(accounts.bob, 1_001), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.delegate_split(vec![
+                    (accounts.charlie, 5_000),
+                    (accounts.django, 3_000),
+                    (accounts.eve, 2_000),
+                ]),
+                Ok(())
+            );
+            assert_eq!(erc20.get_votes(accounts.bob), 0);
+            assert_eq!(erc20.get_votes(accounts.charlie), 500);
+            assert_eq!(erc20.get_votes(accounts.django), 300);
+            // 舍入的余数归最后一个被委托人
+            assert_eq!(erc20.get_votes(accounts.eve), 201);
+            assert_eq!(erc20.delegates(accounts.bob), accounts.charlie);
+            assert_eq!(total_votes(&erc20), 10_000);
+
+            // 票数随委托人余额按比例变化
+            assert_eq!(erc20.transfer(accounts.alice, 1), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 1_000), Ok(()));
+            assert_eq!(erc20.get_votes(accounts.charlie), 1_000);
+            assert_eq!(erc20.get_votes(accounts.django), 600);
+            assert_eq!(erc20.get_votes(accounts.eve), 400);
+            assert_eq!(total_votes(&erc20), 10_000);
+
+            // 重新拆分会先收回旧的份额, 可以保留一部分给自己
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.delegate_split(vec![(accounts.bob, 2_500), (accounts.eve, 7_500)]),
+                Ok(())
+            );
+            assert_eq!(erc20.get_votes(accounts.bob), 500);
+            assert_eq!(erc20.get_votes(accounts.charlie), 0);
+            assert_eq!(erc20.get_votes(accounts.django), 0);
+            assert_eq!(erc20.get_votes(accounts.eve), 1_500);
+            assert_eq!(total_votes(&erc20), 10_000);
+
+            // 单一委托覆盖拆分委托
+            assert_eq!(erc20.delegate(accounts.django), Ok(()));
+            assert_eq!(
+                erc20.split_delegations_of(accounts.bob),
+                vec![(accounts.django, 10_000)]
+            );
+            assert_eq!(erc20.get_votes(accounts.django), 2_000);
+            assert_eq!(erc20.get_votes(accounts.eve), 0);
+
+            assert_eq!(erc20.clear_delegations(), Ok(()));
+            assert_eq!(erc20.split_delegations_of(accounts.bob), Vec::new());
+            assert_eq!(erc20.delegates(accounts.bob), accounts.bob);
+            assert_eq!(erc20.get_votes(accounts.bob), 2_000);
+            assert_eq!(erc20.get_votes(accounts.django), 0);
+            assert_eq!(total_votes(&erc20), 10_000);
+
+            let splits = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::SplitDelegationSet(SplitDelegationSet {
+                        delegator,
+                        delegations,
+                    }) => Some((delegator, delegations.len())),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                splits,
+                vec![(accounts.bob, 3), (accounts.bob, 2), (accounts.bob, 0)]
+            );
+        }
+
+        #[ink::test]
+        fn delegate_split_rejects_invalid_shares() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            for delegations in [
+                Vec::new(),
+                vec![(accounts.bob, 5_000), (accounts.charlie, 4_999)],
+                vec![(accounts.bob, 10_000), (accounts.charlie, 0)],
+                vec![(accounts.bob, 5_000), (accounts.bob, 5_000)],
+                vec![(accounts.bob, 1_000); MAX_SPLIT_DELEGATEES + 1],
+            ]
+            .iter()
+            {
+                assert_eq!(
+                    erc20.delegate_split(delegations.clone()),
+                    Err(Error::InvalidDelegationSplit)
+                );
+            }
+            assert_eq!(erc20.get_votes(accounts.alice), 1_000);
+            assert_eq!(erc20.split_delegations_of(accounts.alice), Vec::new());
+        }
+
+        #[ink::test]
+        fn holder_index_tracks_holders_with_swap_remove() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let holders = |erc20: &Erc20| -> Vec<AccountId> {
+                (0..erc20.holder_count())
+                    .map(|index| erc20.holder_at(index).unwrap())
+                    .collect()
+            };
+            assert_eq!(holders(&erc20), vec![accounts.alice]);
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 100), Ok(()));
+            assert_eq!(erc20.transfer(accounts.django, 100), Ok(()));
+            assert_eq!(
+                holders(&erc20),
+                vec![
+                    accounts.alice,
+                    accounts.bob,
+                    accounts.charlie,
+                    accounts.django
+                ]
+            );
+
+            // 清零的账户由最后一个账户补位
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 100), Ok(()));
+            assert_eq!(
+                holders(&erc20),
+                vec![accounts.alice, accounts.django, accounts.charlie]
+            );
+            set_caller(accounts.django);
+            assert_eq!(erc20.burn(100), Ok(()));
+            assert_eq!(holders(&erc20), vec![accounts.alice, accounts.charlie]);
+            assert_eq!(erc20.holder_at(2), None);
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 1), Ok(()));
+            assert_eq!(
+                holders(&erc20),
+                vec![accounts.alice, accounts.charlie, accounts.bob]
+            );
+        }
+
+        fn giveaway_commit(secret: &[u8]) -> Hash {
+            let mut output = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(secret, &mut output);
+            Hash::from(output)
+        }
+
+        // 与合约相同的方法计算当前区块开奖的 target
+        fn expected_giveaway_target(secret: &[u8], total_weight: Balance) -> Balance {
+            let block = ink_env::block_number::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get block number");
+            let mut hash = [0u8; 32];
+            ink_env::hash_encoded::<Blake2x256, _>(&(secret, block, now()), &mut hash);
+            let mut head = [0u8; 16];
+            head.copy_from_slice(&hash[..16]);
+            u128::from_le_bytes(head) % total_weight
+        }
+
+        // 累计余额第一次超过 target 的持有者
+        fn weighted_pick(target: Balance, holders: &[(AccountId, Balance)]) -> AccountId {
+            let mut cumulative = 0;
+            for (account, balance) in holders {
+                cumulative += balance;
+                if cumulative > target {
+                    return *account;
+                }
+            }
+            panic!("target out of range")
+        }
+
+        #[ink::test]
+        fn giveaway_picks_holder_weighted_by_balance() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let secret = b"marketing secret".to_vec();
+            let commit = giveaway_commit(&secret);
+            assert_eq!(erc20.transfer(accounts.bob, 3_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 2_000), Ok(()));
+            assert_eq!(
+                erc20.draw_giveaway(secret.clone(), 10),
+                Err(Error::NoGiveaway)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(erc20.start_giveaway(1_000, commit), Err(Error::NotOwner));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.start_giveaway(1_000, commit), Ok(()));
+            assert_eq!(
+                erc20.start_giveaway(1_000, commit),
+                Err(Error::GiveawayActive)
+            );
+            assert_eq!(erc20.holder_at(3), Some(contract));
+            assert_eq!(erc20.obligation(PoolId::Giveaway), 1_000);
+            assert_eq!(erc20.unallocated_contract_balance(), Ok(0));
+
+            assert_eq!(
+                erc20.draw_giveaway(secret.clone(), 10),
+                Err(Error::GiveawayTooEarly)
+            );
+            advance_blocks(GIVEAWAY_MIN_DELAY_BLOCKS);
+            assert_eq!(
+                erc20.draw_giveaway(b"guess".to_vec(), 10),
+                Err(Error::InvalidGiveawaySecret)
+            );
+
+            // 合约托管的奖品不参与, 区间为 alice [0, 4000), bob [4000, 7000), charlie [7000, 9000)
+            let target = expected_giveaway_target(&secret, 9_000);
+            let expected = weighted_pick(
+                target,
+                &[
+                    (accounts.alice, 4_000),
+                    (accounts.bob, 3_000),
+                    (accounts.charlie, 2_000),
+                ],
+            );
+            assert_eq!(erc20.draw_giveaway(secret.clone(), 10), Ok(Some(expected)));
+            assert_eq!(erc20.giveaway(), None);
+            assert_eq!(erc20.balance_of(contract), 0);
+            assert_eq!(erc20.obligation(PoolId::Giveaway), 0);
+            let before = match expected {
+                winner if winner == accounts.alice => 4_000,
+                winner if winner == accounts.bob => 3_000,
+                _ => 2_000,
+            };
+            assert_eq!(erc20.balance_of(expected), before + 1_000);
+            assert_eq!(erc20.draw_giveaway(secret, 10), Err(Error::NoGiveaway));
+
+            let winners = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::GiveawayWinner(GiveawayWinner {
+                        winner,
+                        prize,
+                        target,
+                    }) => Some((winner, prize, target)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(winners, vec![(expected, 1_000, target)]);
+        }
+
+        #[ink::test]
+        fn giveaway_draw_resumes_from_cursor() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let secret = b"paged".to_vec();
+            // alice 只留 1 个, 其余几乎必然落在后面的持有者
+            assert_eq!(erc20.transfer(accounts.bob, 3_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 3_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.django, 2_999), Ok(()));
+            assert_eq!(
+                erc20.start_giveaway(1_000, giveaway_commit(&secret)),
+                Ok(())
+            );
+            advance_blocks(GIVEAWAY_MIN_DELAY_BLOCKS);
+
+            let holders = [
+                (accounts.alice, 1),
+                (accounts.bob, 3_000),
+                (accounts.charlie, 3_000),
+                (accounts.django, 2_999),
+            ];
+            let target = expected_giveaway_target(&secret, 9_000);
+            let expected = weighted_pick(target, &holders);
+            let position = holders
+                .iter()
+                .position(|(account, _)| *account == expected)
+                .unwrap();
+
+            let mut calls = 0;
+            let winner = loop {
+                calls += 1;
+                if let Some(winner) = erc20.draw_giveaway(secret.clone(), 1).unwrap() {
+                    break winner;
+                }
+                // 之后的调用沿用第一次确定的 target
+                assert_eq!(erc20.giveaway().unwrap().target, Some(target));
+                advance_blocks(1);
+            };
+            assert_eq!(winner, expected);
+            assert_eq!(calls, position + 1);
+
+            let progress = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::GiveawayProgress(GiveawayProgress { cursor, cumulative }) => {
+                        Some((cursor, cumulative))
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let expected_progress = (0..position)
+                .map(|index| {
+                    let cumulative = holders[..=index]
+                        .iter()
+                        .map(|(_, balance)| balance)
+                        .sum::<Balance>();
+                    (index as u64 + 1, cumulative)
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(progress, expected_progress);
+        }
+
+        #[ink::test]
+        fn giveaway_balances_are_frozen_between_pages() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let secret = b"paged".to_vec();
+            assert_eq!(erc20.transfer(accounts.bob, 3_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 3_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.django, 2_999), Ok(()));
+            assert_eq!(
+                erc20.start_giveaway(1_000, giveaway_commit(&secret)),
+                Ok(())
+            );
+            advance_blocks(GIVEAWAY_MIN_DELAY_BLOCKS);
+            let target = expected_giveaway_target(&secret, 9_000);
+            let expected = weighted_pick(
+                target,
+                &[
+                    (accounts.alice, 1),
+                    (accounts.bob, 3_000),
+                    (accounts.charlie, 3_000),
+                    (accounts.django, 2_999),
+                ],
+            );
+
+            // 第一页只走过 alice, 把已走过的余额挪给还没走到的账户会被重复计权
+            assert_eq!(erc20.draw_giveaway(secret.clone(), 1), Ok(None));
+            assert_eq!(
+                erc20.transfer(accounts.django, 1),
+                Err(Error::GiveawayDrawInProgress)
+            );
+            assert_eq!(erc20.burn(1), Err(Error::GiveawayDrawInProgress));
+            assert_eq!(
+                erc20.mint(accounts.django, 1),
+                Err(Error::GiveawayDrawInProgress)
+            );
+            assert_eq!(erc20.balance_of(accounts.django), 2_999);
+
+            // 秘密公开后任何人都可以继续开奖, 结束后解除冻结
+            set_caller(accounts.eve);
+            assert_eq!(erc20.draw_giveaway(secret.clone(), 10), Ok(Some(expected)));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.django, 1), Ok(()));
+        }
+
+        #[ink::test]
+        fn attestations_expire_and_can_be_revoked() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let document = Hash::from([7; 32]);
+            assert_eq!(
+                erc20.issue_attestation(accounts.bob, document, 10),
+                Err(Error::NotAttester)
+            );
+            assert_eq!(erc20.set_attester(Some(accounts.eve)), Ok(()));
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.issue_attestation(accounts.bob, document, 0),
+                Err(Error::InvalidAttestationExpiry)
+            );
+            assert_eq!(erc20.issue_attestation(accounts.bob, document, 10), Ok(()));
+            assert_eq!(
+                erc20.attestation(accounts.bob),
+                Some(ComplianceAttestation {
+                    attestation_hash: document,
+                    attester: accounts.eve,
+                    attestation_block: 0,
+                    expiry_block: 10,
+                })
+            );
+            assert!(erc20.is_attested(accounts.bob));
+            advance_blocks(9);
+            assert!(erc20.is_attested(accounts.bob));
+            advance_blocks(1);
+            assert!(!erc20.is_attested(accounts.bob));
+
+            assert_eq!(erc20.issue_attestation(accounts.bob, document, 20), Ok(()));
+            assert!(erc20.is_attested(accounts.bob));
+            assert_eq!(erc20.revoke_attestation(accounts.bob), Ok(()));
+            assert!(!erc20.is_attested(accounts.bob));
+            assert_eq!(
+                erc20.revoke_attestation(accounts.bob),
+                Err(Error::AttestationNotFound)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.revoke_attestation(accounts.eve),
+                Err(Error::NotAttester)
+            );
+
+            let issued = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::AttestationIssued(AttestationIssued {
+                        subject,
+                        hash,
+                        expiry,
+                    }) => Some((subject, hash, expiry)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                issued,
+                vec![(accounts.bob, document, 10), (accounts.bob, document, 20)]
+            );
+        }
+
+        #[ink::test]
+        fn attestation_mode_requires_both_parties_attested() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let document = Hash::from([7; 32]);
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.set_attester(Some(accounts.eve)), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.set_attestation_mode(true), Err(Error::NotOwner));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_attestation_mode(true), Ok(()));
+            assert!(erc20.attestation_mode());
+
+            assert_eq!(erc20.transfer(accounts.bob, 10), Err(Error::NotAttested));
+            set_caller(accounts.eve);
+            assert_eq!(erc20.issue_attestation(accounts.alice, document, 5), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 10), Err(Error::NotAttested));
+            set_caller(accounts.eve);
+            assert_eq!(erc20.issue_attestation(accounts.bob, document, 100), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 110);
+
+            // transfer_from 同样检查双方
+            assert_eq!(erc20.approve(accounts.charlie, 50), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.bob, 10),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.django, 10),
+                Err(Error::NotAttested)
+            );
+
+            // alice 的证明过期后不能再转
+            advance_blocks(5);
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 10), Err(Error::NotAttested));
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.issue_attestation(accounts.alice, document, 100),
+                Ok(())
+            );
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+
+            // 撤销收款方的证明
+            set_caller(accounts.eve);
+            assert_eq!(erc20.revoke_attestation(accounts.bob), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.transfer(accounts.bob, 10), Err(Error::NotAttested));
+
+            assert_eq!(erc20.set_attestation_mode(false), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 140);
+        }
+
+        #[ink::test]
+        fn attestation_mode_covers_internal_payouts() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let document = Hash::from([7; 32]);
+            assert_eq!(erc20.transfer(accounts.bob, 300), Ok(()));
+            assert_eq!(erc20.set_buyback_ratios(0, 10_000), Ok(()));
+            assert_eq!(erc20.set_attester(Some(accounts.eve)), Ok(()));
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.issue_attestation(accounts.alice, document, 100),
+                Ok(())
+            );
+            assert_eq!(erc20.issue_attestation(accounts.bob, document, 100), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_attestation_mode(true), Ok(()));
+
+            // 存入合约账户不要求合约账户持证
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stake(300), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.execute_buyback(40), Ok(()));
+
+            // 证明被撤销后不能再领取奖励, 但可以取回自己的质押
+            set_caller(accounts.eve);
+            assert_eq!(erc20.revoke_attestation(accounts.bob), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.claim_rewards(), Err(Error::NotAttested));
+            assert_eq!(erc20.unstake(300), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 300);
+            assert_eq!(erc20.stake(100), Err(Error::NotAttested));
+
+            set_caller(accounts.eve);
+            assert_eq!(erc20.issue_attestation(accounts.bob, document, 100), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.claim_rewards(), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 340);
+        }
+
+        #[ink::test]
+        fn has_account_survives_balance_returning_to_zero() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert!(erc20.has_account(accounts.alice));
+            assert_eq!(erc20.accounts_seen_count(), 1);
+            assert!(!erc20.has_account(accounts.bob));
+
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 100), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+            assert!(erc20.has_account(accounts.bob));
+            assert!(erc20.has_ever_held(accounts.bob));
+            assert_eq!(erc20.holder_count(), 2);
+
+            // 再次收款不重复计数
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.transfer(accounts.bob, 1), Ok(()));
+            assert_eq!(erc20.accounts_seen_count(), 3);
+            assert!(!erc20.has_account(accounts.django));
+            assert!(!erc20.has_ever_held(accounts.django));
+        }
+
+        #[ink::test]
+        fn ever_held_tracking_is_optional() {
+            let mut erc20 = Erc20::new_with_features(1_000, DEFAULT_FEATURES & !FEATURE_EVER_HELD);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.accounts_seen_count(), 0);
+            assert!(!erc20.has_ever_held(accounts.bob));
+            // 余额记录仍然能区分出现过的账户
+            assert!(erc20.has_account(accounts.bob));
+            assert!(!erc20.has_account(accounts.charlie));
+        }
+
+        // 记录 mint 调用的积分代币桩
+        struct StubCreditToken {
+            mints: Rc<RefCell<Vec<(AccountId, AccountId, Balance)>>>,
+        }
+
+        impl call::CallLayer for StubCreditToken {
+            fn call(
+                &mut self,
+                callee: AccountId,
+                selector: [u8; 4],
+                input: &[u8],
+                _gas_limit: u64,
+            ) -> core::result::Result<Vec<u8>, ink_env::Error> {
+                assert_eq!(selector, PSP22_MINT_SELECTOR);
+                let (account, amount) =
+                    <(AccountId, Balance) as scale::Decode>::decode(&mut &input[..])
+                        .expect("encountered invalid mint input");
+                self.mints.borrow_mut().push((callee, account, amount));
+                Ok(Vec::new())
+            }
+        }
+
+        #[ink::test]
+        fn recycling_listing_burns_and_mints_credits() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let credit_token = AccountId::from([0x0e; 32]);
+            let mints = Rc::new(RefCell::new(Vec::new()));
+            call::set_call_layer(StubCreditToken {
+                mints: mints.clone(),
+            });
+            assert_eq!(erc20.transfer(accounts.bob, 300), Ok(()));
+            assert_eq!(
+                erc20.create_recycling_listing(0, credit_token, 3),
+                Err(Error::InvalidRecyclingListing)
+            );
+            assert_eq!(
+                erc20.create_recycling_listing(200, credit_token, 3),
+                Err(Error::CreditTokenNotWhitelisted)
+            );
+            assert_eq!(erc20.set_credit_token_rate(credit_token, 3), Ok(()));
+            assert_eq!(erc20.create_recycling_listing(200, credit_token, 3), Ok(0));
+            assert_eq!(erc20.balance_of(contract), 200);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.fill_recycling_listing(0, 120), Ok(()));
+            assert_eq!(
+                erc20.fill_recycling_listing(0, 81),
+                Err(Error::RecyclingAmountExceeded)
+            );
+            assert_eq!(erc20.fill_recycling_listing(0, 80), Ok(()));
+            assert_eq!(
+                erc20.fill_recycling_listing(0, 1),
+                Err(Error::RecyclingAmountExceeded)
+            );
+            assert_eq!(
+                erc20.fill_recycling_listing(1, 1),
+                Err(Error::RecyclingListingNotFound)
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 100);
+            assert_eq!(erc20.total_supply(), 800);
+            assert_eq!(erc20.recycling_listing(0).unwrap().filled, 200);
+            assert_eq!(
+                *mints.borrow(),
+                vec![
+                    (credit_token, accounts.bob, 360),
+                    (credit_token, accounts.bob, 240)
+                ]
+            );
+
+            let filled = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::RecyclingListingFilled(RecyclingListingFilled {
+                        amount,
+                        credits,
+                        ..
+                    }) => Some((amount, credits)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(filled, vec![(120, 360), (80, 240)]);
+        }
+
+        #[ink::test]
+        fn cancel_recycling_listing_refunds_lister() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let credit_token = AccountId::from([0x0e; 32]);
+            let mints = Rc::new(RefCell::new(Vec::new()));
+            call::set_call_layer(StubCreditToken {
+                mints: mints.clone(),
+            });
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.set_credit_token_rate(credit_token, 2), Ok(()));
+            assert_eq!(erc20.create_recycling_listing(500, credit_token, 2), Ok(0));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.fill_recycling_listing(0, 50), Ok(()));
+            assert_eq!(
+                erc20.cancel_recycling_listing(0),
+                Err(Error::NotRecyclingLister)
+            );
+
+            set_caller(accounts.alice);
+            assert_eq!(erc20.cancel_recycling_listing(0), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.alice), 900);
+            assert_eq!(erc20.recycling_listing(0), None);
+            assert_eq!(
+                erc20.cancel_recycling_listing(0),
+                Err(Error::RecyclingListingNotFound)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.fill_recycling_listing(0, 10),
+                Err(Error::RecyclingListingNotFound)
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 50);
+            assert_eq!(*mints.borrow(), vec![(credit_token, accounts.bob, 100)]);
+
+            let cancelled = ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::RecyclingListingCancelled(RecyclingListingCancelled {
+                        id,
+                        refunded,
+                    }) => Some((id, refunded)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(cancelled, vec![(0, 500)]);
+        }
+
+        #[ink::test]
+        fn batch_transfer_event_modes() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let recipients = vec![
+                (accounts.bob, 10),
+                (accounts.charlie, 20),
+                (accounts.django, 30),
+                (accounts.eve, 40),
+                (accounts.frank, 50),
+            ];
+            let count_events =
+                |erc20: &mut Erc20, mode: EventMode| -> (usize, Vec<(Hash, Balance, u32)>) {
+                    let before = ink_env::test::recorded_events().count();
+                    assert_eq!(erc20.batch_transfer(recipients.clone(), mode), Ok(()));
+                    let events = ink_env::test::recorded_events()
+                        .skip(before)
+                        .map(|e| decode_event(&e))
+                        .collect::<Vec<_>>();
+                    let transfers = events
+                        .iter()
+                        .filter(|event| matches!(event, Event::Transfer(_)))
+                        .count();
+                    let chunks = events
+                        .into_iter()
+                        .filter_map(|event| match event {
+                            Event::BatchTransferChunk(BatchTransferChunk {
+                                from,
+                                recipients_hash,
+                                total,
+                                count,
+                            }) => {
+                                assert_eq!(from, accounts.alice);
+                                Some((recipients_hash, total, count))
+                            }
+                            _ => None,
+                        })
+                        .collect();
+                    (transfers, chunks)
+                };
+
+            assert_eq!(
+                count_events(&mut erc20, EventMode::PerRecipient),
+                (5, Vec::new())
+            );
+            assert_eq!(
+                count_events(&mut erc20, EventMode::SummaryOnly),
+                (0, vec![(batch_chunk_hash(&recipients), 150, 5)])
+            );
+            assert_eq!(
+                count_events(&mut erc20, EventMode::Chunked(2)),
+                (
+                    0,
+                    vec![
+                        (batch_chunk_hash(&recipients[0..2]), 30, 2),
+                        (batch_chunk_hash(&recipients[2..4]), 70, 2),
+                        (batch_chunk_hash(&recipients[4..]), 50, 1),
+                    ]
+                )
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 30);
+            assert_eq!(erc20.balance_of(accounts.frank), 150);
+            assert_eq!(
+                erc20.batch_transfer(recipients.clone(), EventMode::Chunked(0)),
+                Err(Error::InvalidEventMode)
+            );
+        }
+
+        #[ink::test]
+        fn batch_chunk_hash_matches_encoded_chunk() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let chunk = vec![(accounts.bob, 10), (accounts.charlie, 20)];
+            let mut expected = [0u8; 32];
+            ink_env::hash_bytes::<Blake2x256>(&scale::Encode::encode(&chunk), &mut expected);
+            assert_eq!(batch_chunk_hash(&chunk), Hash::from(expected));
+            // 顺序或金额不同哈希都不同
+            assert_ne!(
+                batch_chunk_hash(&[(accounts.charlie, 20), (accounts.bob, 10)]),
+                batch_chunk_hash(&chunk)
+            );
+            assert_ne!(
+                batch_chunk_hash(&[(accounts.bob, 10), (accounts.charlie, 21)]),
+                batch_chunk_hash(&chunk)
+            );
+        }
+
+        fn auto_distributions() -> Vec<(u32, Balance)> {
+            ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::AutoDistributionTriggered(AutoDistributionTriggered {
+                        snapshot_id,
+                        total_amount,
+                    }) => Some((snapshot_id, total_amount)),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        #[ink::test]
+        fn protocol_fees_auto_distribute_at_threshold() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_transfer_fee(100, accounts.django), Ok(()));
+            assert_eq!(erc20.set_protocol_fee(10_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+            assert_eq!(erc20.set_auto_distribute_threshold(25), Ok(()));
+            assert_eq!(erc20.next_distribution_in(), 25);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.stake(1_000), Ok(()));
+            assert_eq!(erc20.set_auto_distribute_threshold(1), Err(Error::NotOwner));
+            // 每笔手续费 10, 全部进入待分配
+            assert_eq!(erc20.transfer(accounts.charlie, 1_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 1_000), Ok(()));
+            assert_eq!(erc20.pending_dividends(), 20);
+            assert_eq!(erc20.next_distribution_in(), 5);
+            assert!(auto_distributions().is_empty());
+            assert_eq!(erc20.pending_rewards(accounts.bob), 0);
+
+            // 第三笔越过阈值, 累计的 30 全部分出
+            assert_eq!(erc20.transfer(accounts.charlie, 1_000), Ok(()));
+            assert_eq!(auto_distributions(), vec![(0, 30)]);
+            assert_eq!(erc20.pending_dividends(), 0);
+            assert_eq!(erc20.next_distribution_in(), 25);
+            assert_eq!(erc20.pending_rewards(accounts.bob), 30);
+            assert_eq!(erc20.treasury_balance(), 0);
+            assert_eq!(
+                erc20.dividend_distribution(0),
+                Some(DividendDistribution {
+                    block: 0,
+                    total_amount: 30,
+                    total_weight: 1_000,
+                })
+            );
+            assert_eq!(erc20.dividend_distribution(1), None);
+        }
+
+        #[ink::test]
+        fn disabling_auto_distribution_returns_pending_to_treasury() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_transfer_fee(100, accounts.django), Ok(()));
+            assert_eq!(erc20.set_protocol_fee(10_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+            assert_eq!(erc20.set_auto_distribute_threshold(100), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 1_000), Ok(()));
+            assert_eq!(erc20.pending_dividends(), 10);
+
+            set_caller(accounts.alice);
+            // 降低阈值到已累计数量以下时立即分配
+            assert_eq!(erc20.set_auto_distribute_threshold(10), Ok(()));
+            assert_eq!(auto_distributions(), vec![(0, 10)]);
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 500), Ok(()));
+            assert_eq!(erc20.pending_dividends(), 5);
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_auto_distribute_threshold(0), Ok(()));
+            assert_eq!(erc20.pending_dividends(), 0);
+            assert_eq!(erc20.treasury_balance(), 5);
+            assert_eq!(erc20.next_distribution_in(), 0);
+        }
+
+        #[ink::test]
+        fn policy_config_evaluates_candidate_without_writing() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_transfer_fee(100, accounts.django), Ok(()));
+            assert_eq!(erc20.set_jackpot_params(0, 200), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+
+            assert_eq!(
+                erc20.transfer_amounts(&erc20.policy_config(), accounts.bob, 1_000),
+                Ok((10, 20, 970))
+            );
+            let strategy = FeeStrategy::Bps(500);
+            let policy = TransferPolicy {
+                min_transfer: 2_000,
+                ..(*erc20.policy).clone()
+            };
+            let candidate = PolicyConfig {
+                policy: &policy,
+                fee_strategy: &strategy,
+                ..erc20.policy_config()
+            };
+            assert_eq!(
+                erc20.transfer_amounts(&candidate, accounts.bob, 1_000),
+                Ok((50, 20, 930))
+            );
+            assert_eq!(
+                erc20.evaluate_transfer(
+                    &candidate,
+                    accounts.bob,
+                    accounts.charlie,
+                    1_000,
+                    TransferOrigin::User(1_000)
+                ),
+                Err(Error::BelowMinimum { minimum: 2_000 })
+            );
+            // 税率时间表优先于候选的 fee_strategy
+            let scheduled = PolicyConfig {
+                scheduled_rate_bps: Some(300),
+                ..candidate
+            };
+            assert_eq!(
+                erc20.transfer_amounts(&scheduled, accounts.bob, 1_000),
+                Ok((30, 20, 950))
+            );
+            assert_eq!(*erc20.fee_strategy, FeeStrategy::Bps(100));
+            assert_eq!(erc20.min_transfer(), 0);
+
+            // 实际转账按当前配置扣费
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 1_000), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.charlie), 970);
+            assert_eq!(erc20.balance_of(accounts.django), 10);
+        }
+
+        #[ink::test]
+        fn simulate_policy_matches_behavior_after_change() {
+            let mut erc20 = Erc20::new(100_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.set_transfer_fee(100, accounts.django), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.charlie, 10_000), Ok(()));
+            let samples = vec![
+                (accounts.bob, accounts.eve, 1_000),
+                (accounts.charlie, accounts.eve, 100),
+                (accounts.bob, accounts.eve, 20_000),
+            ];
+
+            let change = ParamChange::FeeStrategy(FeeStrategy::Tiered(vec![(500, 200)]));
+            let results = erc20.simulate_policy(change, samples.clone());
+            assert_eq!(
+                results,
+                vec![
+                    SimResult {
+                        current: Ok(10),
+                        candidate: Ok(20),
+                    },
+                    SimResult {
+                        current: Ok(1),
+                        candidate: Ok(0),
+                    },
+                    SimResult {
+                        current: Err(Error::InsufficientBalance),
+                        candidate: Err(Error::InsufficientBalance),
+                    },
+                ]
+            );
+            // 试算不改变配置
+            assert_eq!(*erc20.fee_strategy, FeeStrategy::Bps(100));
+
+            let results = erc20.simulate_policy(ParamChange::MinTransfer(500), samples.clone());
+            assert_eq!(
+                results[1].candidate,
+                Err(Error::BelowMinimum { minimum: 500 })
+            );
+            assert_eq!(results[0].candidate, Ok(10));
+
+            let invalid = ParamChange::FeeStrategy(FeeStrategy::Bps(10_001));
+            let results = erc20.simulate_policy(invalid, samples.clone());
+            assert_eq!(results[0].current, Ok(10));
+            assert_eq!(results[0].candidate, Err(Error::InvalidBps));
+
+            // 真正修改后逐笔执行, 结果与试算的候选结果一致
+            assert_eq!(
+                erc20.set_fee_strategy(FeeStrategy::Tiered(vec![(500, 200)])),
+                Ok(())
+            );
+            let expected = vec![Ok(20), Ok(0), Err(Error::InsufficientBalance)];
+            for ((from, to, value), expected) in samples.into_iter().zip(expected) {
+                let fees_before = erc20.fees_collected();
+                set_caller(from);
+                let result = erc20
+                    .transfer(to, value)
+                    .map(|()| erc20.fees_collected() - fees_before);
+                assert_eq!(result, expected);
+            }
+        }
+
+        #[ink::test]
+        fn approval_history_records_every_origin_and_evicts_oldest() {
+            let mut erc20 =
+                Erc20::new_with_features(1_000, DEFAULT_FEATURES | FEATURE_APPROVAL_HISTORY);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, owner) = test_signer(9);
+            assert_eq!(erc20.transfer(owner, 500), Ok(()));
+
+            set_caller(owner);
+            assert_eq!(erc20.approve(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.increase_allowance(accounts.bob, 50), Ok(()));
+            assert_eq!(erc20.decrease_allowance(accounts.bob, 30), Ok(()));
+            let hash = erc20.permit_hash(owner, accounts.bob, 200, 10);
+            let signature = sign_hash(&secret, hash.as_ref());
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.permit(owner, accounts.bob, 200, 10, signature),
+                Ok(())
+            );
+            advance_blocks(2);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer_from(owner, accounts.charlie, 20), Ok(()));
+            // 转给自己不消耗授权, 不留记录
+            assert_eq!(erc20.transfer_from(owner, owner, 20), Ok(()));
+
+            let change = |old_value, new_value, block, origin| ApprovalChange {
+                spender: accounts.bob,
+                old_value,
+                new_value,
+                block,
+                origin,
+            };
+            assert_eq!(
+                erc20.approval_history(owner),
+                vec![
+                    change(0, 100, 0, ApprovalOrigin::Approve),
+                    change(100, 150, 0, ApprovalOrigin::Increase),
+                    change(150, 120, 0, ApprovalOrigin::Decrease),
+                    change(120, 200, 0, ApprovalOrigin::Permit),
+                    change(200, 180, 2, ApprovalOrigin::TransferFromConsumption),
+                ]
+            );
+            assert!(erc20.approval_history(accounts.bob).is_empty());
+
+            set_caller(owner);
+            for value in 1..=6 {
+                assert_eq!(erc20.approve(accounts.charlie, value), Ok(()));
+            }
+            let history = erc20.approval_history(owner);
+            assert_eq!(history.len(), APPROVAL_HISTORY_LEN);
+            assert_eq!(history[0], change(100, 150, 0, ApprovalOrigin::Increase));
+            assert_eq!(
+                history[9],
+                ApprovalChange {
+                    spender: accounts.charlie,
+                    old_value: 5,
+                    new_value: 6,
+                    block: 2,
+                    origin: ApprovalOrigin::Approve,
+                }
+            );
+        }
+
+        #[ink::test]
+        fn approval_history_is_opt_in() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.approve(accounts.bob, 100), Ok(()));
+            assert!(erc20.approval_history(accounts.alice).is_empty());
+        }
+
+        fn compressed_key(secret: &secp256k1::SecretKey) -> [u8; 33] {
+            let secp = secp256k1::Secp256k1::new();
+            secp256k1::PublicKey::from_secret_key(&secp, secret).serialize()
+        }
+
+        fn compliance_checks() -> Vec<(AccountId, AccountId, Balance)> {
+            ink_env::test::recorded_events()
+                .filter_map(|e| match decode_event(&e) {
+                    Event::ComplianceCheckPassed(ComplianceCheckPassed { from, to, value }) => {
+                        Some((from, to, value))
+                    }
+                    _ => None,
+                })
+                .collect()
+        }
+
+        #[ink::test]
+        fn compliance_mode_validates_signatures() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, _) = test_signer(7);
+            let (other_secret, _) = test_signer(8);
+            assert_eq!(
+                erc20.require_compliance(true),
+                Err(Error::ComplianceSignerNotSet)
+            );
+            assert_eq!(erc20.set_compliance_signer(compressed_key(&secret)), Ok(()));
+            assert_eq!(erc20.require_compliance(true), Ok(()));
+            assert_eq!(
+                erc20.transfer(accounts.bob, 100),
+                Err(Error::ComplianceSignatureMissing)
+            );
+            assert_eq!(
+                erc20.transfer_with_compliance(accounts.bob, 100, 10, None),
+                Err(Error::ComplianceSignatureMissing)
+            );
+
+            let hash = erc20.compliance_hash(accounts.alice, accounts.bob, 100, 10);
+            let forged = sign_hash(&other_secret, hash.as_ref());
+            assert_eq!(
+                erc20.transfer_with_compliance(accounts.bob, 100, 10, Some(forged)),
+                Err(Error::InvalidComplianceSignature)
+            );
+            let signature = sign_hash(&secret, hash.as_ref());
+            // 签名绑定金额和收款人
+            assert_eq!(
+                erc20.transfer_with_compliance(accounts.bob, 101, 10, Some(signature)),
+                Err(Error::InvalidComplianceSignature)
+            );
+            assert_eq!(
+                erc20.transfer_with_compliance(accounts.charlie, 100, 10, Some(signature)),
+                Err(Error::InvalidComplianceSignature)
+            );
+            // 转账本身失败时不消耗 nonce
+            let hash = erc20.compliance_hash(accounts.alice, accounts.bob, 5_000, 10);
+            let too_much = sign_hash(&secret, hash.as_ref());
+            assert_eq!(
+                erc20.transfer_with_compliance(accounts.bob, 5_000, 10, Some(too_much)),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(erc20.compliance_nonce(accounts.alice), 0);
+            assert_eq!(
+                erc20.transfer_with_compliance(accounts.bob, 100, 10, Some(signature)),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 100);
+            assert_eq!(erc20.compliance_nonce(accounts.alice), 1);
+            assert_eq!(
+                compliance_checks(),
+                vec![(accounts.alice, accounts.bob, 100)]
+            );
+            // nonce 已经变化, 不能重放
+            assert_eq!(
+                erc20.transfer_with_compliance(accounts.bob, 100, 10, Some(signature)),
+                Err(Error::InvalidComplianceSignature)
+            );
+
+            let hash = erc20.compliance_hash(accounts.alice, accounts.bob, 100, 10);
+            let signature = sign_hash(&secret, hash.as_ref());
+            advance_blocks(11);
+            assert_eq!(
+                erc20.transfer_with_compliance(accounts.bob, 100, 10, Some(signature)),
+                Err(Error::ComplianceSignatureExpired)
+            );
+
+            assert_eq!(erc20.require_compliance(false), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 100), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.set_compliance_signer(compressed_key(&other_secret)),
+                Err(Error::NotOwner)
+            );
+            assert_eq!(erc20.require_compliance(true), Err(Error::NotOwner));
+        }
+
+        #[ink::test]
+        fn compliance_mode_blocks_every_unsigned_transfer_path() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, _) = test_signer(7);
+            assert_eq!(erc20.set_compliance_signer(compressed_key(&secret)), Ok(()));
+            assert_eq!(erc20.approve(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.require_compliance(true), Ok(()));
+            let decimals = erc20.precision_mode().decimals();
+
+            assert_eq!(
+                erc20.batch_transfer(vec![(accounts.bob, 10)], EventMode::PerRecipient),
+                Err(Error::ComplianceSignatureMissing)
+            );
+            assert_eq!(
+                erc20.batch_transfer(vec![(accounts.bob, 10)], EventMode::SummaryOnly),
+                Err(Error::ComplianceSignatureMissing)
+            );
+            assert_eq!(
+                erc20.transfer_fixed(accounts.bob, 10, decimals),
+                Err(Error::ComplianceSignatureMissing)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from_batch(accounts.alice, vec![(accounts.charlie, 10)]),
+                Err(Error::ComplianceSignatureMissing)
+            );
+            assert_eq!(erc20.balance_of(accounts.alice), 1_000);
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 100);
+
+            // 签名只放行它覆盖的那一笔
+            set_caller(accounts.alice);
+            let hash = erc20.compliance_hash(accounts.alice, accounts.bob, 10, 10);
+            let signature = sign_hash(&secret, hash.as_ref());
+            assert_eq!(
+                erc20.transfer_with_compliance(accounts.bob, 10, 10, Some(signature)),
+                Ok(())
+            );
+            assert_eq!(*erc20.compliance_clearance, None);
+            assert_eq!(
+                erc20.transfer(accounts.bob, 10),
+                Err(Error::ComplianceSignatureMissing)
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 10);
+        }
+
+        #[ink::test]
+        fn transfer_from_with_compliance_signs_for_token_owner() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, _) = test_signer(7);
+            assert_eq!(erc20.set_compliance_signer(compressed_key(&secret)), Ok(()));
+            assert_eq!(erc20.require_compliance(true), Ok(()));
+            assert_eq!(erc20.approve(accounts.bob, 100), Ok(()));
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.charlie, 50),
+                Err(Error::ComplianceSignatureMissing)
+            );
+            // 按 spender 签的名不能用
+            let hash = erc20.compliance_hash(accounts.bob, accounts.charlie, 50, 10);
+            let wrong_from = sign_hash(&secret, hash.as_ref());
+            assert_eq!(
+                erc20.transfer_from_with_compliance(
+                    accounts.alice,
+                    accounts.charlie,
+                    50,
+                    10,
+                    Some(wrong_from)
+                ),
+                Err(Error::InvalidComplianceSignature)
+            );
+            let hash = erc20.compliance_hash(accounts.alice, accounts.charlie, 50, 10);
+            let signature = sign_hash(&secret, hash.as_ref());
+            assert_eq!(
+                erc20.transfer_from_with_compliance(
+                    accounts.alice,
+                    accounts.charlie,
+                    50,
+                    10,
+                    Some(signature)
+                ),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(accounts.charlie), 50);
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 50);
+            assert_eq!(erc20.compliance_nonce(accounts.alice), 1);
+            assert_eq!(erc20.compliance_nonce(accounts.bob), 0);
+        }
+
+        /// 跳过前 skip 条事件后的 Transfer, Approval 和 AllowanceExhausted
+        fn batch_pull_events(skip: usize) -> Vec<(&'static str, Balance)> {
+            ink_env::test::recorded_events()
+                .skip(skip)
+                .filter_map(|e| match decode_event(&e) {
+                    Event::Transfer(Transfer { value, .. }) => Some(("Transfer", value)),
+                    Event::Approval(Approval { value, .. }) => Some(("Approval", value)),
+                    Event::AllowanceExhausted(AllowanceExhausted { total_spent, .. }) => {
+                        Some(("AllowanceExhausted", total_spent))
+                    }
+                    _ => None,
+                })
+                .collect()
+        }
+
+        #[ink::test]
+        fn transfer_from_batch_consumes_allowance_once() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let recipients = vec![
+                (accounts.charlie, 10),
+                (accounts.django, 20),
+                (accounts.eve, 30),
+            ];
+
+            // 差一个单位时整体拒绝
+            assert_eq!(erc20.approve(accounts.bob, 59), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from_batch(accounts.alice, recipients.clone()),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(erc20.balance_of(accounts.alice), 1_000);
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 59);
+
+            // 恰好等于合计时用尽授权
+            set_caller(accounts.alice);
+            assert_eq!(erc20.approve(accounts.bob, 60), Ok(()));
+            let skip = ink_env::test::recorded_events().count();
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from_batch(accounts.alice, recipients.clone()),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(accounts.alice), 940);
+            assert_eq!(erc20.balance_of(accounts.django), 20);
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 0);
+            assert_eq!(
+                batch_pull_events(skip),
+                vec![
+                    ("Transfer", 10),
+                    ("Transfer", 20),
+                    ("Transfer", 30),
+                    ("Approval", 0),
+                    ("AllowanceExhausted", 60),
+                ]
+            );
+
+            // 有剩余时只发一条剩余额度的 Approval
+            set_caller(accounts.alice);
+            assert_eq!(erc20.approve(accounts.bob, 100), Ok(()));
+            let skip = ink_env::test::recorded_events().count();
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from_batch(accounts.alice, recipients.clone()),
+                Ok(())
+            );
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 40);
+            assert_eq!(
+                batch_pull_events(skip),
+                vec![
+                    ("Transfer", 10),
+                    ("Transfer", 20),
+                    ("Transfer", 30),
+                    ("Approval", 40),
+                ]
+            );
+
+            // 无限授权不扣减
+            set_caller(accounts.alice);
+            assert_eq!(erc20.approve(accounts.bob, INFINITE_ALLOWANCE), Ok(()));
+            let skip = ink_env::test::recorded_events().count();
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from_batch(accounts.alice, recipients),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.allowance(accounts.alice, accounts.bob),
+                INFINITE_ALLOWANCE
+            );
+            assert_eq!(
+                batch_pull_events(skip),
+                vec![("Transfer", 10), ("Transfer", 20), ("Transfer", 30)]
+            );
+            assert_eq!(erc20.balance_of(accounts.alice), 820);
+        }
+
+        #[ink::test]
+        fn transfer_from_batch_is_all_or_nothing() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.approve(accounts.bob, 500), Ok(()));
+            set_caller(accounts.django);
+            assert_eq!(erc20.set_receiving(false), Ok(()));
+
+            set_caller(accounts.bob);
+            let recipients = vec![(accounts.charlie, 10), (accounts.django, 20)];
+            assert_eq!(
+                erc20.transfer_from_batch(accounts.alice, recipients),
+                Err(Error::RecipientOptedOut)
+            );
+            assert_eq!(erc20.balance_of(accounts.charlie), 0);
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 500);
+
+            assert_eq!(
+                erc20.transfer_from_batch(accounts.alice, vec![(accounts.charlie, 600)]),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(
+                erc20.transfer_from_batch(
+                    accounts.alice,
+                    vec![(accounts.charlie, 0); MAX_BATCH_LEN + 1]
+                ),
+                Err(Error::BatchTooLarge)
+            );
+            // 转回 from 自己的项不消耗授权
+            let recipients = vec![(accounts.alice, 300), (accounts.charlie, 500)];
+            assert_eq!(
+                erc20.transfer_from_batch(accounts.alice, recipients),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(accounts.alice), 500);
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 0);
+        }
+
+        #[ink::test]
+        fn balance_writes_depend_on_existing_entry() {
+            const POLICY: u32 = 1;
+            const RECEIVING: u32 = 1;
+
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let transfer_ops = |erc20: &mut Erc20, to: AccountId, value: Balance| {
+                crate::metering::take_storage_reads();
+                crate::metering::take_storage_writes();
+                assert_eq!(erc20.transfer(to, value), Ok(()));
+                (
+                    crate::metering::take_storage_reads(),
+                    crate::metering::take_storage_writes(),
+                )
+            };
+
+            // 新收款方: 两次余额读取, 两次余额写入. ever_held 直接写入而不先查询, 计入其他写入
+            assert_eq!(
+                transfer_ops(&mut erc20, accounts.bob, 10),
+                (2 + POLICY + RECEIVING, 2)
+            );
+            assert_eq!(erc20.accounts_seen_count(), 2);
+            // 已有余额的收款方只写两次余额
+            assert_eq!(
+                transfer_ops(&mut erc20, accounts.bob, 10),
+                (2 + POLICY + RECEIVING, 2)
+            );
+
+            // 余额归零后记录仍在, 再次收款不再写 ever_held
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.alice, 20), Ok(()));
+            set_caller(accounts.alice);
+            assert_eq!(
+                transfer_ops(&mut erc20, accounts.bob, 10),
+                (2 + POLICY + RECEIVING, 2)
+            );
+            assert_eq!(erc20.accounts_seen_count(), 2);
+
+            // 转给新账户 0 不创建余额记录
+            assert_eq!(
+                transfer_ops(&mut erc20, accounts.charlie, 0),
+                (2 + POLICY + RECEIVING, 1)
+            );
+            assert!(!erc20.has_account(accounts.charlie));
+            assert_eq!(erc20.balance_entry(accounts.charlie), None);
+            assert_eq!(erc20.balance_entry(accounts.bob), Some(10));
+        }
+
+        #[ink::test]
+        fn redenomination_waits_for_timelock() {
+            let mut erc20 = Erc20::new(1_000_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(
+                erc20.execute_redenomination(),
+                Err(Error::NoPendingRedenomination)
+            );
+            assert_eq!(erc20.redenominate(0, 1), Err(Error::InvalidDenomination));
+            assert_eq!(erc20.redenominate(1, 1_000), Ok(()));
+            assert_eq!(
+                erc20.pending_redenomination(),
+                Some((1, 1_000, REDENOMINATION_TIMELOCK_BLOCKS))
+            );
+            advance_blocks(REDENOMINATION_TIMELOCK_BLOCKS - 1);
+            assert_eq!(
+                erc20.execute_redenomination(),
+                Err(Error::TimelockNotExpired)
+            );
+            assert_eq!(erc20.denominated_balance_of(accounts.alice), 1_000_000);
+
+            advance_blocks(1);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.redenominate(1, 1), Err(Error::NotOwner));
+            assert_eq!(erc20.execute_redenomination(), Ok(()));
+            assert_eq!(erc20.denomination(), (1, 1_000));
+            assert_eq!(erc20.pending_redenomination(), None);
+            let redenominated = ink_env::test::recorded_events()
+                .filter(|e| {
+                    matches!(
+                        decode_event(e),
+                        Event::Redenominated(Redenominated {
+                            factor_num: 1,
+                            factor_den: 1_000,
+                        })
+                    )
+                })
+                .count();
+            assert_eq!(redenominated, 1);
+        }
+
+        #[ink::test]
+        fn redenomination_scales_balances_and_allowances() {
+            let mut erc20 = Erc20::new(1_000_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 2_500), Ok(()));
+            assert_eq!(erc20.approve(accounts.bob, 5_999), Ok(()));
+            assert_eq!(erc20.approve(accounts.charlie, INFINITE_ALLOWANCE), Ok(()));
+            assert_eq!(erc20.redenominate(1, 1_000), Ok(()));
+            assert_eq!(erc20.decimals(), 0);
+            advance_blocks(REDENOMINATION_TIMELOCK_BLOCKS);
+            assert_eq!(erc20.execute_redenomination(), Ok(()));
+
+            // 面额是 10 的幂, 折算进标准接口的小数位数
+            assert_eq!(erc20.decimals(), 3);
+            assert_eq!(erc20.set_precision_mode(PrecisionMode::FixedPoint6), Ok(()));
+            assert_eq!(erc20.decimals(), 9);
+            assert_eq!(
+                erc20.transfer_fixed(accounts.eve, 1, 6),
+                Err(Error::PrecisionMismatch)
+            );
+            assert_eq!(erc20.transfer_fixed(accounts.eve, 1, 9), Ok(()));
+            assert_eq!(erc20.set_precision_mode(PrecisionMode::Standard), Ok(()));
+
+            // 原始账本不变, 以新面额计的读数按比例缩小并向下取整
+            assert_eq!(erc20.balance_of(accounts.bob), 2_500);
+            assert_eq!(erc20.denominated_balance_of(accounts.bob), 2);
+            assert_eq!(erc20.denominated_balance_of(accounts.alice), 997);
+            assert_eq!(erc20.denominated_total_supply(), 1_000);
+            assert_eq!(erc20.balance_of_display(accounts.alice), (997, 0));
+            assert_eq!(erc20.denominated_allowance(accounts.alice, accounts.bob), 5);
+            assert_eq!(
+                erc20.denominated_allowance(accounts.alice, accounts.charlie),
+                INFINITE_ALLOWANCE
+            );
+
+            // 新面额的最小单位对应 1000 个原始单位
+            assert_eq!(erc20.transfer_denominated(accounts.django, 1), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.django), 1_000);
+            assert_eq!(erc20.denominated_balance_of(accounts.django), 1);
+            assert_eq!(erc20.approve_denominated(accounts.bob, 3), Ok(()));
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 3_000);
+
+            // 叠加 3/2 后为 3/2000, 1 个新单位不对应整数个原始单位
+            assert_eq!(erc20.redenominate(3, 2), Ok(()));
+            advance_blocks(REDENOMINATION_TIMELOCK_BLOCKS);
+            assert_eq!(erc20.execute_redenomination(), Ok(()));
+            assert_eq!(erc20.denomination(), (3, 2_000));
+            assert_eq!(erc20.decimals(), 0);
+            assert_eq!(
+                erc20.transfer_denominated(accounts.django, 1),
+                Err(Error::InexactDenomination)
+            );
+            assert_eq!(erc20.transfer_denominated(accounts.django, 3), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.django), 3_000);
+            assert_eq!(erc20.denominated_balance_of(accounts.django), 4);
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        fn sign_transfer(
+            erc20: &Erc20,
+            secret: &secp256k1::SecretKey,
+            from: AccountId,
+            to: AccountId,
+            value: Balance,
+            relayer_fee: Balance,
+            nonce: u64,
+            deadline: u32,
+        ) -> SignedTransfer {
+            let hash = erc20.signed_transfer_hash(from, to, value, relayer_fee, nonce, deadline);
+            SignedTransfer {
+                from,
+                to,
+                value,
+                relayer_fee,
+                nonce,
+                deadline,
+                signature: sign_hash(secret, hash.as_ref()),
+            }
+        }
+
+        #[ink::test]
+        fn settle_signed_transfers_reports_each_item() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret_a, user_a) = test_signer(1);
+            let (secret_b, user_b) = test_signer(2);
+            assert_eq!(erc20.transfer(user_a, 1_000), Ok(()));
+            assert_eq!(erc20.transfer(user_b, 1_000), Ok(()));
+
+            let valid_a = sign_transfer(&erc20, &secret_a, user_a, accounts.charlie, 100, 0, 0, 10);
+            let mut forged =
+                sign_transfer(&erc20, &secret_b, user_a, accounts.charlie, 100, 0, 0, 10);
+            let valid_b = sign_transfer(&erc20, &secret_b, user_b, accounts.charlie, 50, 5, 0, 10);
+            // 改动签名之外的中继费用会使签名失效
+            let mut tampered =
+                sign_transfer(&erc20, &secret_a, user_a, accounts.charlie, 10, 1, 1, 10);
+            tampered.relayer_fee = 100;
+            let too_much =
+                sign_transfer(&erc20, &secret_a, user_a, accounts.charlie, 950, 0, 1, 10);
+
+            set_caller(accounts.django);
+            let results = erc20.settle_signed_transfers(vec![
+                valid_a.clone(),
+                forged,
+                valid_b,
+                valid_a.clone(),
+                tampered,
+                too_much,
+            ]);
+            assert_eq!(
+                results,
+                vec![
+                    Ok(()),
+                    Err(Error::InvalidNonce),
+                    Ok(()),
+                    Err(Error::InvalidNonce),
+                    Err(Error::InvalidTransferSignature),
+                    Err(Error::InsufficientBalance),
+                ]
+            );
+            assert_eq!(erc20.balance_of(accounts.charlie), 150);
+            assert_eq!(erc20.balance_of(user_a), 900);
+            assert_eq!(erc20.balance_of(user_b), 945);
+            assert_eq!(erc20.balance_of(accounts.django), 5);
+            // 失败的项不消耗 nonce
+            assert_eq!(erc20.signed_transfer_nonce(user_a), 1);
+            assert_eq!(erc20.signed_transfer_nonce(user_b), 1);
+
+            // 整批重放全部被拒
+            assert_eq!(
+                erc20.settle_signed_transfers(vec![valid_a]),
+                vec![Err(Error::InvalidNonce)]
+            );
+        }
+
+        #[ink::test]
+        fn settle_signed_transfers_checks_signer_deadline_and_batch_len() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret_a, user_a) = test_signer(1);
+            let (secret_b, _) = test_signer(2);
+            assert_eq!(erc20.transfer(user_a, 1_000), Ok(()));
+
+            let forged = sign_transfer(&erc20, &secret_b, user_a, accounts.bob, 100, 0, 0, 10);
+            let expiring = sign_transfer(&erc20, &secret_a, user_a, accounts.bob, 100, 3, 0, 10);
+            assert_eq!(
+                erc20.settle_signed_transfers(vec![forged]),
+                vec![Err(Error::InvalidTransferSignature)]
+            );
+            advance_blocks(11);
+            assert_eq!(
+                erc20.settle_signed_transfers(vec![expiring.clone()]),
+                vec![Err(Error::SignedTransferExpired)]
+            );
+            assert_eq!(
+                erc20.settle_signed_transfers(vec![expiring; MAX_BATCH_LEN + 1]),
+                vec![Err(Error::BatchTooLarge); MAX_BATCH_LEN + 1]
+            );
+            assert_eq!(erc20.balance_of(user_a), 1_000);
+            assert_eq!(erc20.signed_transfer_nonce(user_a), 0);
+        }
+
+        #[ink::test]
+        fn settle_signed_transfers_rejects_unpayable_relayer_fee_up_front() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret_a, user_a) = test_signer(1);
+            assert_eq!(erc20.transfer(user_a, 1_000), Ok(()));
+            let signed = sign_transfer(&erc20, &secret_a, user_a, accounts.bob, 100, 5, 0, 10);
+
+            // 中继关闭了收款, 费用那一笔无法入账, 本金也不能转出
+            let relayer = accounts.django;
+            set_caller(relayer);
+            assert_eq!(erc20.set_receiving(false), Ok(()));
+            for _ in 0..3 {
+                assert_eq!(
+                    erc20.settle_signed_transfers(vec![signed.clone()]),
+                    vec![Err(Error::RecipientOptedOut)]
+                );
+            }
+            assert_eq!(erc20.balance_of(user_a), 1_000);
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+            assert_eq!(erc20.signed_transfer_nonce(user_a), 0);
+
+            // 恢复收款后这个签名只能执行一次
+            assert_eq!(erc20.set_receiving(true), Ok(()));
+            assert_eq!(
+                erc20.settle_signed_transfers(vec![signed.clone(), signed]),
+                vec![Ok(()), Err(Error::InvalidNonce)]
+            );
+            assert_eq!(erc20.balance_of(user_a), 895);
+            assert_eq!(erc20.balance_of(accounts.bob), 100);
+            assert_eq!(erc20.balance_of(relayer), 5);
+            assert_eq!(erc20.signed_transfer_nonce(user_a), 1);
+        }
+
+        // 记录每次授权回调收到的 (listener, owner, old, new), trapping 中的合约执行失败
+        struct StubAllowanceListeners {
+            calls: Rc<RefCell<Vec<(AccountId, AccountId, Balance, Balance)>>>,
+            trapping: Vec<AccountId>,
+        }
+
+        impl call::CallLayer for StubAllowanceListeners {
+            fn call(
+                &mut self,
+                callee: AccountId,
+                selector: [u8; 4],
+                input: &[u8],
+                gas_limit: u64,
+            ) -> core::result::Result<Vec<u8>, ink_env::Error> {
+                assert_eq!(selector, ON_ALLOWANCE_CHANGED_SELECTOR);
+                assert_eq!(gas_limit, ALLOWANCE_LISTENER_GAS_LIMIT);
+                let (owner, old_value, new_value) =
+                    <(AccountId, Balance, Balance) as scale::Decode>::decode(&mut &input[..])
+                        .expect("encountered invalid listener input");
+                self.calls
+                    .borrow_mut()
+                    .push((callee, owner, old_value, new_value));
+                if self.trapping.contains(&callee) {
+                    Err(ink_env::Error::CalleeTrapped)
+                } else {
+                    Ok(Vec::new())
+                }
+            }
+        }
+
+        fn install_allowance_listeners(
+            erc20: &mut Erc20,
+            listeners: &[AccountId],
+            trapping: Vec<AccountId>,
+        ) -> Rc<RefCell<Vec<(AccountId, AccountId, Balance, Balance)>>> {
+            let calls = Rc::new(RefCell::new(Vec::new()));
+            call::set_call_layer(StubAllowanceListeners {
+                calls: calls.clone(),
+                trapping,
+            });
+            let caller = ink_env::caller::<ink_env::DefaultEnvironment>().unwrap();
+            for listener in listeners {
+                set_caller(*listener);
+                assert_eq!(erc20.register_allowance_listener(), Ok(()));
+            }
+            set_caller(caller);
+            calls
+        }
+
+        #[ink::test]
+        fn allowance_listeners_see_old_and_new_values() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let calls = install_allowance_listeners(&mut erc20, &[accounts.eve], vec![]);
+            assert!(erc20.is_allowance_listener(accounts.eve));
+            assert!(!erc20.is_allowance_listener(accounts.bob));
+
+            // 未注册的 spender 不会被调用
+            assert_eq!(erc20.approve(accounts.bob, 100), Ok(()));
+            assert!(calls.borrow().is_empty());
+
+            assert_eq!(erc20.approve(accounts.eve, 100), Ok(()));
+            assert_eq!(erc20.increase_allowance(accounts.eve, 50), Ok(()));
+            assert_eq!(erc20.decrease_allowance(accounts.eve, 30), Ok(()));
+            // 额度不变不通知
+            assert_eq!(erc20.approve(accounts.eve, 120), Ok(()));
+            assert_eq!(erc20.approve(accounts.eve, 0), Ok(()));
+            assert_eq!(
+                *calls.borrow(),
+                vec![
+                    (accounts.eve, accounts.alice, 0, 100),
+                    (accounts.eve, accounts.alice, 100, 150),
+                    (accounts.eve, accounts.alice, 150, 120),
+                    (accounts.eve, accounts.alice, 120, 0),
+                ]
+            );
+
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.register_allowance_listener(),
+                Err(Error::AllowanceListenerAlreadyRegistered)
+            );
+            assert_eq!(erc20.unregister_allowance_listener(), Ok(()));
+            assert_eq!(
+                erc20.unregister_allowance_listener(),
+                Err(Error::AllowanceListenerNotRegistered)
+            );
+            set_caller(accounts.alice);
+            calls.borrow_mut().clear();
+            assert_eq!(erc20.approve(accounts.eve, 10), Ok(()));
+            assert!(calls.borrow().is_empty());
+        }
+
+        #[ink::test]
+        fn trapping_allowance_listener_never_blocks_approval() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let calls =
+                install_allowance_listeners(&mut erc20, &[accounts.eve], vec![accounts.eve]);
+
+            assert_eq!(erc20.approve(accounts.eve, 100), Ok(()));
+            assert_eq!(erc20.allowance(accounts.alice, accounts.eve), 100);
+            assert_eq!(
+                *calls.borrow(),
+                vec![(accounts.eve, accounts.alice, 0, 100)]
+            );
+            assert!(ink_env::test::recorded_events().any(|event| matches!(
+                decode_event(&event),
+                Event::HookCallFailed(HookCallFailed { hook, reason })
+                    if hook == accounts.eve && reason == call::CALL_ERROR_CALLEE_TRAPPED
+            )));
+
+            // transfer_from 消耗授权同样通知, 失败也不影响转账
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.bob, 40),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(accounts.bob), 40);
+            assert_eq!(
+                calls.borrow().last(),
+                Some(&(accounts.eve, accounts.alice, 100, 60))
+            );
+
+            // owner 整体关闭后不再调用
+            assert_eq!(
+                erc20.set_allowance_listeners_enabled(false),
+                Err(Error::NotOwner)
+            );
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_allowance_listeners_enabled(false), Ok(()));
+            assert!(!erc20.allowance_listeners_enabled());
+            calls.borrow_mut().clear();
+            assert_eq!(erc20.approve(accounts.eve, 0), Ok(()));
+            assert!(calls.borrow().is_empty());
+        }
+
+        #[ink::test]
+        fn single_use_allowance_vanishes_after_first_spend() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.approve_single_use(accounts.bob, 100), Ok(()));
+            // 使用前报告最大额度
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 100);
+            assert_eq!(
+                erc20.allowance_kind(accounts.alice, accounts.bob),
+                AllowanceKind::SingleUse
+            );
+
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.eve, 30),
+                Ok(())
+            );
+            assert_eq!(erc20.balance_of(accounts.eve), 30);
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 0);
+            assert!(!erc20
+                .allowances
+                .contains_key(&(accounts.alice, accounts.bob)));
+            let emitted = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert!(matches!(
+                decode_event(emitted.last().unwrap()),
+                Event::Approval(Approval { owner, spender, value: 0, .. })
+                    if owner == accounts.alice && spender == accounts.bob
+            ));
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.eve, 1),
+                Err(Error::InsufficientAllowance)
+            );
+
+            // 无限额度的一次性授权同样只能用一次
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.approve_single_use(accounts.bob, INFINITE_ALLOWANCE),
+                Ok(())
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.eve, 10),
+                Ok(())
+            );
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 0);
+            assert_eq!(erc20.total_outstanding_allowance(accounts.alice), 0);
+        }
+
+        #[ink::test]
+        fn latest_approval_decides_allowance_kind() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.approve(accounts.bob, 50), Ok(()));
+            assert_eq!(erc20.approve_single_use(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 100);
+            // 调整额度不改变种类
+            assert_eq!(erc20.increase_allowance(accounts.bob, 20), Ok(()));
+            assert_eq!(
+                erc20.allowance_kind(accounts.alice, accounts.bob),
+                AllowanceKind::SingleUse
+            );
+
+            // 普通 approve 覆盖一次性授权
+            assert_eq!(erc20.approve(accounts.bob, 70), Ok(()));
+            assert_eq!(
+                erc20.allowance_kind(accounts.alice, accounts.bob),
+                AllowanceKind::Standard
+            );
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.eve, 30),
+                Ok(())
+            );
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.eve, 30),
+                Ok(())
+            );
+            assert_eq!(erc20.allowance(accounts.alice, accounts.bob), 10);
+            // 其他 spender 的授权不受影响
+            set_caller(accounts.alice);
+            assert_eq!(erc20.approve_single_use(accounts.charlie, 5), Ok(()));
+            assert_eq!(
+                erc20.allowance_kind(accounts.alice, accounts.bob),
+                AllowanceKind::Standard
+            );
+        }
+
+        #[ink::test]
+        fn permit_single_use_works_once() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, owner) = test_signer(1);
+            assert_eq!(erc20.transfer(owner, 100), Ok(()));
+
+            let hash = erc20.single_use_permit_hash(owner, accounts.bob, 60, 10);
+            let signature = sign_hash(&secret, hash.as_ref());
+            // 不能当作普通 permit 提交
+            assert_eq!(
+                erc20.permit(owner, accounts.bob, 60, 10, signature),
+                Err(Error::InvalidPermitSignature)
+            );
+            assert_eq!(
+                erc20.permit_single_use(owner, accounts.bob, 60, 10, signature),
+                Ok(())
+            );
+            assert_eq!(erc20.permit_nonce(owner), 1);
+            assert_eq!(
+                erc20.permit_single_use(owner, accounts.bob, 60, 10, signature),
+                Err(Error::InvalidPermitSignature)
+            );
+
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer_from(owner, accounts.eve, 20), Ok(()));
+            assert_eq!(
+                erc20.transfer_from(owner, accounts.eve, 20),
+                Err(Error::InsufficientAllowance)
+            );
+            assert_eq!(erc20.balance_of(owner), 80);
+        }
+
+        #[ink::test]
+        fn transfer_footprint_matches_observed_writes() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            // 余额写入和其他存储写入分别计数, 都必须与预估完全一致
+            let observe = |erc20: &mut Erc20, from: AccountId, to: AccountId, value: Balance| {
+                let footprint = erc20
+                    .transfer_footprint(from, to, value)
+                    .expect("transfer should be allowed");
+                let holders = erc20.holder_count();
+                set_caller(from);
+                crate::metering::take_storage_writes();
+                crate::metering::take_aux_writes();
+                assert_eq!(erc20.transfer(to, value), Ok(()));
+                assert_eq!(
+                    crate::metering::take_storage_writes(),
+                    footprint.balance_writes
+                );
+                assert_eq!(crate::metering::take_aux_writes(), footprint.aux_writes);
+                (footprint, erc20.holder_count() as i64 - holders as i64)
+            };
+
+            // 新收款方: 新建余额记录, 加入持有者列表, 记入 ever_held
+            let (created, holders) = observe(&mut erc20, accounts.alice, accounts.bob, 10);
+            assert!(created.recipient_entry_created);
+            assert!(!created.sender_entry_removed);
+            assert_eq!(created.balance_writes, 2);
+            assert_eq!(holders, 1);
+            assert!(erc20.has_account(accounts.bob));
+
+            // 已有记录的收款方只更新
+            let (updated, holders) = observe(&mut erc20, accounts.alice, accounts.bob, 10);
+            assert!(!updated.recipient_entry_created);
+            assert_eq!(holders, 0);
+            assert!(updated.aux_writes < created.aux_writes);
+            assert!(updated.bytes_delta < created.bytes_delta);
+
+            // 转 0 给新账户不写收款方
+            let (empty, _) = observe(&mut erc20, accounts.alice, accounts.charlie, 0);
+            assert!(!empty.recipient_entry_created);
+            assert_eq!(empty.balance_writes, 1);
+            assert_eq!(erc20.balance_entry(accounts.charlie), None);
+
+            // 拆分委托: 每个被委托人的票数和检查点都要写入
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.delegate_split(vec![(accounts.django, 4_000), (accounts.eve, 6_000)]),
+                Ok(())
+            );
+            advance_blocks(1);
+            let (split, _) = observe(&mut erc20, accounts.alice, accounts.bob, 10);
+            assert!(split.aux_writes > updated.aux_writes);
+
+            // 转出方清空: 离开持有者列表, 余额记录保留
+            let (emptied, holders) = observe(&mut erc20, accounts.bob, accounts.alice, 30);
+            assert!(!emptied.sender_entry_removed);
+            assert!(emptied.bytes_delta < 0);
+            assert_eq!(holders, -1);
+            assert_eq!(erc20.holder_count(), 1);
+            assert_eq!(erc20.balance_entry(accounts.bob), Some(0));
+
+            // 与真实转账返回同样的错误
+            assert_eq!(
+                erc20.transfer_footprint(accounts.bob, accounts.alice, 1),
+                Err(Error::InsufficientBalance)
+            );
         }
 
-        impl<X> scale::Encode for PrefixedValue<'_, '_, X>
-        where
-            X: scale::Encode,
-        {
-            #[inline]
-            fn size_hint(&self) -> usize {
-                self.prefix.size_hint() + self.value.size_hint()
-            }
-
-            #[inline]
-            fn encode_to<T: scale::Output + ?Sized>(&self, dest: &mut T) {
-                self.prefix.encode_to(dest);
-                self.value.encode_to(dest);
-            }
+        #[ink::test]
+        fn transfer_footprint_follows_enabled_features() {
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let full = Erc20::new(1_000)
+                .transfer_footprint(accounts.alice, accounts.bob, 10)
+                .unwrap();
+            let without_activity =
+                Erc20::new_with_features(1_000, DEFAULT_FEATURES & !FEATURE_ACTIVITY_TRACKING)
+                    .transfer_footprint(accounts.alice, accounts.bob, 10)
+                    .unwrap();
+            let without_ever_held =
+                Erc20::new_with_features(1_000, DEFAULT_FEATURES & !FEATURE_EVER_HELD)
+                    .transfer_footprint(accounts.alice, accounts.bob, 10)
+                    .unwrap();
+            // 每个账户一次活动记录写入
+            assert_eq!(full.aux_writes - without_activity.aux_writes, 2);
+            assert!(without_activity.bytes_delta < full.bytes_delta);
+            // ever_held 和 accounts_seen_count
+            assert_eq!(full.aux_writes - without_ever_held.aux_writes, 2);
+            assert_eq!(full.balance_writes, without_activity.balance_writes);
         }
 
-        #[cfg(test)]
-        fn encoded_into_hash<T>(entity: &T) -> Hash
-        where
-            T: scale::Encode,
-        {
-            use ink_env::{
-                hash::{Blake2x256, CryptoHash, HashOutput},
-                Clear,
+        #[ink::test]
+        fn reassign_allowance_processes_each_owner() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (old_spender, new_spender) = (accounts.eve, accounts.frank);
+            let (secret_a, owner_a) = test_signer(1);
+            let (secret_b, owner_b) = test_signer(2);
+            let (secret_c, owner_c) = test_signer(3);
+            set_caller(owner_a);
+            assert_eq!(erc20.approve(old_spender, 100), Ok(()));
+            assert_eq!(erc20.approve(new_spender, 10), Ok(()));
+            set_caller(owner_b);
+            assert_eq!(erc20.approve(old_spender, 50), Ok(()));
+
+            let consent = |erc20: &Erc20, secret, owner, new_spender| {
+                let hash = erc20.reassign_allowance_hash(owner, old_spender, new_spender);
+                sign_hash(secret, hash.as_ref())
             };
-            let mut result = Hash::clear();
-            let len_result = result.as_ref().len();
-            let encoded = entity.encode();
-            let len_encoded = encoded.len();
-            if len_encoded <= len_result {
-                result.as_mut()[..len_encoded].copy_from_slice(&encoded);
-                return result;
-            }
-            let mut hash_output = <<Blake2x256 as HashOutput>::Type as Default>::default();
-            <Blake2x256 as CryptoHash>::hash(&encoded, &mut hash_output);
-            let copy_len = core::cmp::min(hash_output.len(), len_result);
-            result.as_mut()[0..copy_len].copy_from_slice(&hash_output[0..copy_len]);
-            result
-        }
+            let sig_a = consent(&erc20, &secret_a, owner_a, new_spender);
+            // B 同意的是另一个新地址
+            let sig_b = consent(&erc20, &secret_b, owner_b, accounts.django);
+            // C 没有授权
+            let sig_c = consent(&erc20, &secret_c, owner_c, new_spender);
 
-        fn assert_transfer_event(
-            event: &ink_env::test::EmittedEvent, // 参考https://paritytech.github.io/ink/ink_env/test/struct.EmittedEvent.html
-            expected_from: Option<AccountId>,
-            expected_to: Option<AccountId>,
-            expected_value: Balance,
-        ) {
-            let decode_event = <Event as scale::Decode>::decode(&mut &event.data[..])
-                .expect("encountered invalid contract event data buffer");
-            if let Event::Transfer(Transfer { from, to, value }) = decode_event {
-                assert_eq!(from, expected_from, "encountered invalid transfer.from");
-                assert_eq!(to, expected_to, "encountered invalid transfer.to");
-                assert_eq!(value, expected_value, "encountered invalid transfer.value");
-            } else {
-                panic!("encountered unexpected evnet kind: expect a Transfer event")
-            }
-            let expected_topics = vec![
-                encoded_into_hash(&PrefixedValue {
-                    value: b"Erc20::Transfer",
-                    prefix: b"",
-                }),
-                encoded_into_hash(&PrefixedValue {
-                    prefix: b"Erc20::Transfer::from",
-                    value: &expected_from,
-                }),
-                encoded_into_hash(&PrefixedValue {
-                    prefix: b"Erc20::Transfer::to",
-                    value: &expected_to,
-                }),
-                encoded_into_hash(&PrefixedValue {
-                    prefix: b"Erc20::Transfer::value",
-                    value: &expected_value,
-                }),
-            ];
+            set_caller(accounts.django);
+            let emitted_before = ink_env::test::recorded_events().count();
+            assert_eq!(
+                erc20.reassign_allowance(
+                    old_spender,
+                    new_spender,
+                    vec![owner_a, owner_b, owner_c],
+                    vec![sig_a, sig_b, sig_c],
+                ),
+                Ok(vec![
+                    Ok(()),
+                    Err(Error::InvalidPermitSignature),
+                    Err(Error::InsufficientAllowance),
+                ])
+            );
+            assert_eq!(erc20.allowance(owner_a, old_spender), 0);
+            assert_eq!(erc20.allowance(owner_a, new_spender), 110);
+            assert_eq!(erc20.allowance(owner_b, old_spender), 50);
+            assert_eq!(erc20.allowance(owner_b, new_spender), 0);
+            assert_eq!(erc20.permit_nonce(owner_a), 1);
+            assert_eq!(erc20.permit_nonce(owner_b), 0);
+            assert_eq!(erc20.permit_nonce(owner_c), 0);
 
-            for (n, (actual_topic, expect_topic)) in
-                event.topics.iter().zip(expected_topics).enumerate()
-            {
-                let topic = actual_topic
-                    .decode::<Hash>()
-                    .expect("encountered invalid topic encoding");
-                assert_eq!(topic, expect_topic, "encountered invalid topic at {}", n);
-            }
+            let approvals = ink_env::test::recorded_events()
+                .skip(emitted_before)
+                .filter_map(|event| match decode_event(&event) {
+                    Event::Approval(Approval {
+                        owner,
+                        spender,
+                        value,
+                        ..
+                    }) => Some((owner, spender, value)),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                approvals,
+                vec![(owner_a, old_spender, 0), (owner_a, new_spender, 110)]
+            );
+
+            // 同一份签名不能重放
+            assert_eq!(
+                erc20.reassign_allowance(old_spender, new_spender, vec![owner_a], vec![sig_a]),
+                Ok(vec![Err(Error::InvalidPermitSignature)])
+            );
         }
-        #[ink::test]
-        fn new_works() {
-            let _erc20 = Erc20::new(100);
 
-            let emit_events = ink_env::test::recorded_events().collect::<Vec<_>>();
+        #[ink::test]
+        fn reassign_allowance_rejects_malformed_batches() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (secret, owner) = test_signer(1);
+            set_caller(owner);
+            assert_eq!(erc20.approve_single_use(accounts.eve, 40), Ok(()));
+            let hash = erc20.reassign_allowance_hash(owner, accounts.eve, accounts.frank);
+            let signature = sign_hash(&secret, hash.as_ref());
 
-            assert_eq!(1, emit_events.len());
+            assert_eq!(
+                erc20.reassign_allowance(accounts.eve, accounts.frank, vec![owner], vec![]),
+                Err(Error::LengthMismatch)
+            );
+            assert_eq!(
+                erc20.reassign_allowance(accounts.eve, accounts.eve, vec![owner], vec![signature]),
+                Err(Error::InvalidMigration)
+            );
+            assert_eq!(
+                erc20.reassign_allowance(
+                    accounts.eve,
+                    accounts.frank,
+                    vec![owner; MAX_BATCH_LEN + 1],
+                    vec![signature; MAX_BATCH_LEN + 1],
+                ),
+                Err(Error::BatchTooLarge)
+            );
+            // 一次性授权迁移后仍是一次性的
+            assert_eq!(
+                erc20.reassign_allowance(
+                    accounts.eve,
+                    accounts.frank,
+                    vec![owner],
+                    vec![signature]
+                ),
+                Ok(vec![Ok(())])
+            );
+            assert_eq!(
+                erc20.allowance_kind(owner, accounts.frank),
+                AllowanceKind::SingleUse
+            );
+        }
 
-            assert_transfer_event(
-                &emit_events[0],
-                None,
-                Some(AccountId::from([0x01; 32])),
-                100,
+        fn assert_obligations_reconcile(erc20: &Erc20) {
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            let escrowed: Balance = erc20
+                .obligations_summary()
+                .iter()
+                .map(|(_, amount)| amount)
+                .sum();
+            assert_eq!(
+                escrowed + erc20.unallocated_contract_balance().unwrap(),
+                erc20.balance_of(contract)
             );
         }
 
         #[ink::test]
-        fn total_supply_works() {
-            let erc20 = Erc20::new(100);
+        fn obligations_follow_escrow_features() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert!(erc20.obligations_summary().is_empty());
+            let unallocated = erc20.unallocated_contract_balance().unwrap();
 
-            let emit_events = ink_env::test::recorded_events().collect::<Vec<_>>();
-            assert_transfer_event(
-                &emit_events[0],
-                None,
-                Some(AccountId::from([0x01; 32])),
-                100,
+            assert_eq!(erc20.create_voucher(voucher_hash(b"gift-1"), 100), Ok(()));
+            assert_eq!(erc20.stake(500), Ok(()));
+            let id = erc20
+                .schedule_transfer(
+                    accounts.bob,
+                    200,
+                    ink_env::block_number::<ink_env::DefaultEnvironment>().unwrap() + 5,
+                )
+                .expect("schedule should succeed");
+            let preimage = b"obligations".to_vec();
+            assert_eq!(
+                erc20.htlc_lock(accounts.bob, 300, hashlock_of(&preimage), now() + 1),
+                Ok(0)
+            );
+            let escrow_id = erc20
+                .create_escrow(accounts.bob, 50, accounts.charlie)
+                .expect("escrow should be created");
+            assert_eq!(
+                erc20.obligations_summary(),
+                vec![
+                    (PoolId::Vouchers, 100),
+                    (PoolId::Staking, 500),
+                    (PoolId::ScheduledTransfers, 200),
+                    (PoolId::Escrows, 50),
+                    (PoolId::Htlcs, 300),
+                ]
             );
+            assert_eq!(erc20.unallocated_contract_balance(), Ok(unallocated));
+            assert_obligations_reconcile(&erc20);
 
-            assert_eq!(erc20.total_supply(), 100);
+            assert_eq!(erc20.unstake(200), Ok(()));
+            assert_eq!(erc20.cancel_voucher(voucher_hash(b"gift-1")), Ok(()));
+            assert_eq!(erc20.cancel_scheduled(id), Ok(()));
+            assert_eq!(erc20.release_escrow(escrow_id), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.htlc_claim(0, preimage), Ok(()));
+            assert_eq!(erc20.obligations_summary(), vec![(PoolId::Staking, 300)]);
+            assert_eq!(erc20.unallocated_contract_balance(), Ok(unallocated));
+            assert_obligations_reconcile(&erc20);
         }
 
         #[ink::test]
-        fn balance_of_works() {
-            let erc20 = Erc20::new(100);
+        fn direct_transfers_to_contract_are_unallocated() {
+            let mut erc20 = Erc20::new(10_000);
+            let contract = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            assert_eq!(erc20.stake(400), Ok(()));
+            assert_eq!(erc20.transfer(contract, 70), Ok(()));
+            assert_eq!(erc20.obligation(PoolId::Staking), 400);
+            assert_eq!(erc20.unallocated_contract_balance(), Ok(70));
+            assert_obligations_reconcile(&erc20);
 
-            let emit_events = ink_env::test::recorded_events().collect::<Vec<_>>();
-            assert_transfer_event(
-                &emit_events[0],
-                None,
-                Some(AccountId::from([0x01; 32])),
-                100,
+            assert_eq!(erc20.unstake(400), Ok(()));
+            assert_eq!(erc20.obligation(PoolId::Staking), 0);
+            assert!(erc20.obligations_summary().is_empty());
+            assert_eq!(erc20.unallocated_contract_balance(), Ok(70));
+            assert_obligations_reconcile(&erc20);
+
+            // 账本记的托管超过实际余额时报告缺口
+            erc20.escrow_in(PoolId::Staking, 71);
+            assert_eq!(
+                erc20.unallocated_contract_balance(),
+                Err(Error::EscrowDeficit)
             );
+        }
 
+        #[ink::test]
+        fn protocol_balances_are_recorded_as_obligations() {
+            let mut erc20 = Erc20::new(100_000);
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                 .expect("Cannot get accounts");
+            let unallocated = erc20.unallocated_contract_balance().unwrap();
+            // 每个池都与对应功能自己的计数一致, 未分配余额不变
+            let check = |erc20: &Erc20| {
+                assert_eq!(erc20.obligation(PoolId::BountyPool), erc20.bounty_pool());
+                assert_eq!(
+                    erc20.obligation(PoolId::StabilityFund),
+                    erc20.stability_fund().0
+                );
+                assert_eq!(erc20.obligation(PoolId::Jackpot), erc20.jackpot_params().0);
+                assert_eq!(
+                    erc20.obligation(PoolId::WelcomeBonus),
+                    erc20.campaign_info().2
+                );
+                assert_eq!(erc20.obligation(PoolId::Treasury), erc20.treasury_balance());
+                assert_eq!(
+                    erc20.obligation(PoolId::PendingDividends),
+                    erc20.pending_dividends()
+                );
+                assert_eq!(erc20.unallocated_contract_balance(), Ok(unallocated));
+                assert_obligations_reconcile(erc20);
+            };
 
-            assert_eq!(erc20.balance_of(accounts.alice), 100);
-            assert_eq!(erc20.balance_of(accounts.bob), 0);
+            setup_fee_split(&mut erc20, accounts.django);
+            assert_eq!(erc20.stake(1_000), Ok(()));
+            assert_eq!(erc20.start_campaign(10, 50), Ok(()));
+            assert_eq!(erc20.fund_bounty_pool(300), Ok(()));
+            assert_eq!(erc20.fund_stability_tokens(200), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+            assert!(erc20.treasury_balance() > 0);
+            assert!(erc20.jackpot_params().0 > 0);
+            check(&erc20);
+
+            // 协议手续费先攒着, 关闭自动分红时转回金库
+            assert_eq!(erc20.set_auto_distribute_threshold(1_000_000), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+            assert!(erc20.pending_dividends() > 0);
+            check(&erc20);
+            assert_eq!(erc20.set_auto_distribute_threshold(0), Ok(()));
+            check(&erc20);
+
+            // 分给质押者的奖励在领取前一直记在 StakingRewards
+            assert_eq!(erc20.set_auto_distribute_threshold(1), Ok(()));
+            assert_eq!(erc20.transfer(accounts.bob, 10_000), Ok(()));
+            assert!(erc20.obligation(PoolId::StakingRewards) > 0);
+            check(&erc20);
+            assert_eq!(erc20.claim_rewards(), Ok(()));
+            check(&erc20);
         }
 
         #[ink::test]
-        fn transfer_works() {
-            // 此处小坑, 一定要定义为mut
-            let mut erc20 = Erc20::new(100);
-
+        fn private_balances_limit_who_can_view() {
+            let mut erc20 = Erc20::new(1_000);
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                 .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 300), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.approve(accounts.eve, 40), Ok(()));
+            assert_eq!(erc20.set_private_balances(true), Err(Error::NotOwner));
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_private_balances(true), Ok(()));
 
-            assert_eq!(erc20.balance_of(accounts.alice), 100);
-            assert_eq!(erc20.balance_of(accounts.bob), 0);
-
-            //transfer 10 to bob
-            assert_eq!(erc20.transfer(accounts.bob, 10), Ok(()));
-
-            assert_eq!(erc20.balance_of(accounts.alice), 90);
-            assert_eq!(erc20.balance_of(accounts.bob), 10);
+            // 本人和 owner 可以查看
+            assert_eq!(erc20.balance_of_restricted(accounts.alice), Ok(700));
+            assert_eq!(erc20.balance_of_restricted(accounts.bob), Ok(300));
+            set_caller(accounts.bob);
+            assert_eq!(erc20.balance_of(accounts.bob), 300);
+            assert_eq!(erc20.allowance(accounts.bob, accounts.eve), 40);
+            set_caller(accounts.eve);
+            assert_eq!(
+                erc20.allowance_restricted(accounts.bob, accounts.eve),
+                Ok(40)
+            );
 
-            let emit_events = ink_env::test::recorded_events().collect::<Vec<_>>();
-            assert_eq!(emit_events.len(), 2);
-            assert_transfer_event(
-                &emit_events[0],
-                None,
-                Some(AccountId::from([0x01; 32])),
-                100,
+            // 其他人看不到
+            set_caller(accounts.charlie);
+            assert_eq!(
+                erc20.balance_of_restricted(accounts.bob),
+                Err(Error::NotAuthorizedToView)
             );
-            assert_transfer_event(
-                &emit_events[1],
-                Some(AccountId::from([0x01; 32])),
-                Some(AccountId::from([0x02; 32])),
-                10,
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+            assert_eq!(erc20.denominated_balance_of(accounts.bob), 0);
+            assert_eq!(
+                erc20.allowance_restricted(accounts.bob, accounts.eve),
+                Err(Error::NotAuthorizedToView)
             );
+            assert_eq!(erc20.allowance(accounts.bob, accounts.eve), 0);
+
+            // 授权后可以查看, 撤销后恢复
+            set_caller(accounts.bob);
+            assert_eq!(erc20.grant_view(accounts.charlie), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.balance_of_restricted(accounts.bob), Ok(300));
+            assert_eq!(erc20.allowance(accounts.bob, accounts.eve), 40);
+            set_caller(accounts.bob);
+            assert_eq!(erc20.revoke_view(accounts.charlie), Ok(()));
+            assert_eq!(
+                erc20.revoke_view(accounts.charlie),
+                Err(Error::ViewNotGranted)
+            );
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+
+            // 关闭后所有人都能查看
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_private_balances(false), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.balance_of(accounts.bob), 300);
         }
 
         #[ink::test]
-        fn trasfer_fails_when_not_enough_balance() {
-            let mut erc20 = Erc20::new(100);
-
+        fn private_balances_gate_balance_derived_views() {
+            let mut erc20 = Erc20::new(1_000);
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                 .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 300), Ok(()));
+            assert_eq!(erc20.set_private_balances(true), Ok(()));
+            advance_blocks(1);
+            let sample = vec![(accounts.bob, accounts.alice, 301)];
 
-            assert_eq!(erc20.balance_of(accounts.bob), 0);
+            // 本人可以查看
+            set_caller(accounts.bob);
+            assert_eq!(erc20.get_votes(accounts.bob), 300);
+            assert_eq!(erc20.get_past_votes(accounts.bob, 0), 300);
+            assert_eq!(erc20.quadratic_votes(accounts.bob), 17);
 
-            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>();
+            // 其他人不能通过投票权, 试算或存储预估推出余额
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.get_votes(accounts.bob), 0);
+            assert_eq!(erc20.get_past_votes(accounts.bob, 0), 0);
+            assert_eq!(erc20.quadratic_votes(accounts.bob), 0);
+            assert_eq!(erc20.get_weighted_votes(accounts.bob), 0);
+            assert_eq!(erc20.total_outstanding_allowance(accounts.bob), 0);
+            assert_eq!(
+                erc20.transfer_footprint(accounts.bob, accounts.charlie, 301),
+                Err(Error::NotAuthorizedToView)
+            );
+            assert_eq!(
+                erc20.transfer_footprint(accounts.charlie, accounts.bob, 0),
+                Err(Error::NotAuthorizedToView)
+            );
+            assert_eq!(
+                erc20.simulate_policy(ParamChange::MinTransfer(0), sample.clone()),
+                vec![SimResult {
+                    current: Err(Error::NotAuthorizedToView),
+                    candidate: Err(Error::NotAuthorizedToView),
+                }]
+            );
+            assert_eq!(erc20.holder_count(), 2);
+            assert_eq!(erc20.holder_at(0), None);
+            assert_eq!(erc20.holder_at(1), None);
 
-            let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4])); // 表示4th 的call方法, 即transfer
+            // owner 仍然可以试算
+            set_caller(accounts.alice);
+            assert_eq!(
+                erc20.simulate_policy(ParamChange::MinTransfer(0), sample)[0].current,
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(erc20.holder_at(1), Some(accounts.bob));
+        }
 
-            data.push_arg(&accounts.bob);
+        #[ink::test]
+        fn private_balances_do_not_affect_transfers() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            assert_eq!(erc20.transfer(accounts.bob, 300), Ok(()));
+            assert_eq!(erc20.approve(accounts.charlie, 100), Ok(()));
+            assert_eq!(erc20.set_private_balances(true), Ok(()));
 
-            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
-                accounts.bob,
-                callee,
-                1000000,
-                1000000,
-                data,
+            // 调用者看不到 bob 的余额, 但转账仍按真实余额校验
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.balance_of(accounts.bob), 0);
+            assert_eq!(
+                erc20.transfer_from(accounts.alice, accounts.bob, 100),
+                Ok(())
             );
-
             assert_eq!(
-                erc20.transfer(accounts.eve, 10),
+                erc20.transfer_from(accounts.alice, accounts.bob, 1),
+                Err(Error::InsufficientAllowance)
+            );
+            set_caller(accounts.bob);
+            assert_eq!(erc20.transfer(accounts.charlie, 400), Ok(()));
+            assert_eq!(
+                erc20.transfer(accounts.charlie, 1),
                 Err(Error::InsufficientBalance)
             );
-
-            assert_eq!(erc20.balance_of(accounts.alice), 100);
             assert_eq!(erc20.balance_of(accounts.bob), 0);
-            assert_eq!(erc20.balance_of(accounts.eve), 0);
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.balance_of(accounts.charlie), 400);
+        }
 
-            let emit_events = ink_env::test::recorded_events().collect::<Vec<_>>();
-            assert_eq!(emit_events.len(), 1);
-            assert_transfer_event(
-                &emit_events[0],
-                None,
-                Some(AccountId::from([0x01; 32])),
-                100,
+        #[ink::test]
+        fn maintain_allowance_tops_up_below_floor() {
+            let mut erc20 = Erc20::new(10_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (owner, dex, keeper) = (accounts.alice, accounts.bob, accounts.django);
+            assert_eq!(
+                erc20.set_allowance_maintenance(dex, 200, 100),
+                Err(Error::InvalidMaintenanceRule)
             );
+            assert_eq!(erc20.set_allowance_maintenance(dex, 100, 1_000), Ok(()));
+            assert_eq!(erc20.set_maintenance_bounty(5), Ok(()));
+            assert_eq!(erc20.approve(dex, 150), Ok(()));
+
+            // 不低于下限时什么都不做, 也不付赏金
+            set_caller(keeper);
+            assert_eq!(erc20.maintain_allowance(owner, dex), Ok(false));
+            assert_eq!(erc20.balance_of(keeper), 0);
+            assert_eq!(erc20.allowance(owner, dex), 150);
+
+            set_caller(dex);
+            assert_eq!(erc20.transfer_from(owner, accounts.eve, 60), Ok(()));
+            set_caller(keeper);
+            assert_eq!(erc20.maintain_allowance(owner, dex), Ok(true));
+            assert_eq!(erc20.allowance(owner, dex), 1_000);
+            assert_eq!(erc20.balance_of(keeper), 5);
+            assert_eq!(erc20.balance_of(owner), 10_000 - 60 - 5);
+            assert_eq!(erc20.keeper_stats(keeper).earned, 5);
+            let emitted = ink_env::test::recorded_events().collect::<Vec<_>>();
+            assert!(matches!(
+                decode_event(&emitted[emitted.len() - 2]),
+                Event::Approval(Approval { value: 1_000, .. })
+            ));
+            assert!(matches!(
+                decode_event(emitted.last().unwrap()),
+                Event::AllowanceMaintained(AllowanceMaintained {
+                    old_value: 90,
+                    new_value: 1_000,
+                    bounty: 5,
+                    ..
+                })
+            ));
+            assert_eq!(erc20.maintain_allowance(owner, dex), Ok(false));
+            assert_eq!(erc20.balance_of(keeper), 5);
         }
 
         #[ink::test]
-        fn transfer_from_works() {
-            let mut erc20 = Erc20::new(100);
+        fn allowance_maintenance_rules_can_be_removed_or_paused() {
+            let mut erc20 = Erc20::new(10_000);
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                 .expect("Cannot get accounts");
+            let (owner, dex) = (accounts.alice, accounts.bob);
+            assert_eq!(erc20.set_allowance_maintenance(dex, 100, 500), Ok(()));
+            assert_eq!(erc20.allowance_maintenance(owner, dex), Some((100, 500)));
 
-            assert_eq!(
-                erc20.transfer_from(accounts.alice, accounts.eve, 10),
-                Err(Error::InsufficientAllowance)
-            );
-            assert_eq!(erc20.approve(accounts.bob, 10), Ok(()));
+            // 暂停授权期间规则失效
+            assert_eq!(erc20.set_paused_ops(PAUSE_APPROVE), Ok(()));
+            set_caller(accounts.django);
+            assert_eq!(erc20.maintain_allowance(owner, dex), Err(Error::Paused));
+            assert_eq!(erc20.allowance(owner, dex), 0);
+            set_caller(owner);
+            assert_eq!(erc20.set_paused_ops(0), Ok(()));
 
-            assert_eq!(ink_env::test::recorded_events().count(), 2);
+            // 没有设置赏金时免费补足
+            set_caller(accounts.django);
+            assert_eq!(erc20.maintain_allowance(owner, dex), Ok(true));
+            assert_eq!(erc20.balance_of(accounts.django), 0);
+            assert_eq!(erc20.allowance(owner, dex), 500);
 
-            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>();
-            let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4])); 
-            data.push_arg(&accounts.bob);
-            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
-                accounts.bob,
-                callee,
-                1000000,
-                1000000,
-                data,
+            set_caller(owner);
+            assert_eq!(erc20.remove_allowance_maintenance(dex), Ok(()));
+            assert_eq!(
+                erc20.remove_allowance_maintenance(dex),
+                Err(Error::MaintenanceRuleNotFound)
             );
-
+            assert_eq!(erc20.approve(dex, 0), Ok(()));
+            set_caller(accounts.django);
             assert_eq!(
-                erc20.transfer_from(accounts.alice, accounts.eve, 10),
-                Ok(())
+                erc20.maintain_allowance(owner, dex),
+                Err(Error::MaintenanceRuleNotFound)
             );
-            assert_eq!(erc20.balance_of(accounts.eve), 10);
+        }
 
-            let emitted_events = ink_env::test::recorded_events().collect::<Vec<_>>();
-            assert_eq!(emitted_events.len(), 3);
-            assert_transfer_event(
-                &emitted_events[0],
-                None,
-                Some(AccountId::from([0x01; 32])),
-                100,
+        #[ink::test]
+        fn paused_allowance_cannot_be_spent_until_resumed() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let (owner, dex) = (accounts.alice, accounts.bob);
+            assert_eq!(erc20.approve(dex, 300), Ok(()));
+            assert_eq!(erc20.pause_allowance(dex), Ok(()));
+            assert!(matches!(
+                decode_event(&ink_env::test::recorded_events().last().unwrap()),
+                Event::AllowancePaused(AllowancePaused { .. })
+            ));
+            assert_eq!(erc20.pause_allowance(dex), Err(Error::AllowancePaused));
+            assert!(erc20.is_allowance_paused(owner, dex));
+            assert_eq!(erc20.allowance(owner, dex), 300);
+            assert_eq!(erc20.effective_allowance(owner, dex), 0);
+
+            // 暂停期间所有花费授权的路径都失败, 额度和余额都不变
+            set_caller(dex);
+            assert_eq!(
+                erc20.transfer_from(owner, accounts.eve, 100),
+                Err(Error::AllowancePaused)
             );
-            assert_transfer_event(
-                &emitted_events[2],
-                Some(AccountId::from([0x01; 32])),
-                Some(AccountId::from([0x05; 32])),
-                10,
+            assert_eq!(
+                erc20.transfer_from_batch(owner, vec![(accounts.eve, 100)]),
+                Err(Error::AllowancePaused)
             );
+            assert_eq!(erc20.burn_from(owner, 100), Err(Error::AllowancePaused));
+            assert_eq!(erc20.allowance(owner, dex), 300);
+            assert_eq!(erc20.allowance_spent(owner, dex), 0);
+            assert_eq!(erc20.balance_of(owner), 1_000);
+            // 只有 owner 自己能解除
+            assert_eq!(erc20.resume_allowance(dex), Err(Error::AllowanceNotPaused));
+
+            set_caller(owner);
+            assert_eq!(erc20.resume_allowance(dex), Ok(()));
+            assert!(matches!(
+                decode_event(&ink_env::test::recorded_events().last().unwrap()),
+                Event::AllowanceResumed(AllowanceResumed { .. })
+            ));
+            assert_eq!(erc20.effective_allowance(owner, dex), 300);
+            set_caller(dex);
+            assert_eq!(erc20.transfer_from(owner, accounts.eve, 100), Ok(()));
+            assert_eq!(erc20.balance_of(accounts.eve), 100);
+            assert_eq!(erc20.allowance(owner, dex), 200);
         }
 
         #[ink::test]
-        fn allowance_must_not_change_on_failed_transfer() {
-            let mut erc20 = Erc20::new(100);
+        fn paused_allowance_is_per_spender_and_survives_reapproval() {
+            let mut erc20 = Erc20::new(1_000);
             let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
                 .expect("Cannot get accounts");
+            let owner = accounts.alice;
+            assert_eq!(erc20.approve(accounts.bob, 100), Ok(()));
+            assert_eq!(erc20.approve(accounts.charlie, 100), Ok(()));
+            assert_eq!(erc20.pause_allowance(accounts.bob), Ok(()));
+            // 暂停期间仍可调整额度, 调整后依然处于暂停状态
+            assert_eq!(erc20.approve(accounts.bob, 500), Ok(()));
+            assert!(erc20.is_allowance_paused(owner, accounts.bob));
+            assert_eq!(erc20.effective_allowance(owner, accounts.bob), 0);
 
-            let alice_balance = erc20.balance_of(accounts.alice);
-            let initial_allowance = alice_balance + 2;
-            assert_eq!(erc20.approve(accounts.bob, initial_allowance), Ok(()));
+            set_caller(accounts.charlie);
+            assert_eq!(erc20.transfer_from(owner, accounts.eve, 50), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.transfer_from(owner, accounts.eve, 50),
+                Err(Error::AllowancePaused)
+            );
+            assert_eq!(erc20.allowance(owner, accounts.bob), 500);
+        }
 
-            let callee = ink_env::account_id::<ink_env::DefaultEnvironment>();
-            let mut data = ink_env::test::CallData::new(ink_env::call::Selector::new([0x00; 4])); 
-            data.push_arg(&accounts.bob);
-            ink_env::test::push_execution_context::<ink_env::DefaultEnvironment>(
-                accounts.bob,
-                callee,
-                1000000,
-                1000000,
-                data,
+        #[ink::test]
+        fn recycling_credits_are_limited_to_whitelisted_tokens() {
+            let mut erc20 = Erc20::new(1_000);
+            let accounts = ink_env::test::default_accounts::<ink_env::DefaultEnvironment>()
+                .expect("Cannot get accounts");
+            let credit_token = AccountId::from([0x0e; 32]);
+            // 本合约有 mint 权限但没有登记的代币
+            let certificate_token = AccountId::from([0x0f; 32]);
+            let mints = Rc::new(RefCell::new(Vec::new()));
+            call::set_call_layer(StubCreditToken {
+                mints: mints.clone(),
+            });
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.set_credit_token_rate(credit_token, 5),
+                Err(Error::NotOwner)
             );
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_credit_token_rate(credit_token, 5), Ok(()));
+            assert_eq!(erc20.credit_token_rate(credit_token), Some(5));
+            assert_eq!(erc20.transfer(accounts.bob, 300), Ok(()));
 
-            let emitted_events_before = ink_env::test::recorded_events();
+            set_caller(accounts.bob);
             assert_eq!(
-                erc20.transfer_from(accounts.alice, accounts.eve, alice_balance + 1),
-                Err(Error::InsufficientBalance)
+                erc20.create_recycling_listing(100, certificate_token, 1),
+                Err(Error::CreditTokenNotWhitelisted)
             );
             assert_eq!(
-                erc20.allowance(accounts.alice, accounts.bob),
-                initial_allowance
+                erc20.create_recycling_listing(100, credit_token, Balance::MAX),
+                Err(Error::CreditTokenNotWhitelisted)
             );
-            let emitted_events_after = ink_env::test::recorded_events();
-            assert_eq!(emitted_events_before.count(), emitted_events_after.count());
+            assert_eq!(erc20.create_recycling_listing(100, credit_token, 5), Ok(0));
+
+            // owner 下调上限后, 按旧比例开出的挂单不能再兑换
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_credit_token_rate(credit_token, 4), Ok(()));
+            set_caller(accounts.bob);
+            assert_eq!(
+                erc20.fill_recycling_listing(0, 10),
+                Err(Error::CreditTokenNotWhitelisted)
+            );
+            set_caller(accounts.alice);
+            assert_eq!(erc20.set_credit_token_rate(credit_token, 0), Ok(()));
+            assert_eq!(erc20.credit_token_rate(credit_token), None);
+            assert_eq!(erc20.balance_of(accounts.bob), 200);
+            assert!(mints.borrow().is_empty());
         }
     }
 }
@@ -0,0 +1,50 @@
+//! 存储读写计数. 链上不做任何事, 链下测试中用来核对热路径上的读写次数.
+//! 计数来自代码在读写处的主动上报, 不是存储层的真实统计: 测试能发现上报次数的变化,
+//! 发现不了漏报的读写
+
+/// 记录一次存储读取
+pub fn note_storage_read() {
+    #[cfg(test)]
+    stub::STORAGE_READS.with(|reads| reads.set(reads.get() + 1));
+}
+
+/// 记录一次余额写入
+pub fn note_storage_write() {
+    #[cfg(test)]
+    stub::STORAGE_WRITES.with(|writes| writes.set(writes.get() + 1));
+}
+
+/// 记录一次转账路径上余额以外的存储写入, 与 transfer_footprint 的 aux_writes 对应
+pub fn note_aux_write() {
+    #[cfg(test)]
+    stub::AUX_WRITES.with(|writes| writes.set(writes.get() + 1));
+}
+
+#[cfg(test)]
+pub use stub::{take_aux_writes, take_storage_reads, take_storage_writes};
+
+#[cfg(test)]
+mod stub {
+    use std::cell::Cell;
+
+    thread_local! {
+        pub(super) static STORAGE_READS: Cell<u32> = Cell::new(0);
+        pub(super) static STORAGE_WRITES: Cell<u32> = Cell::new(0);
+        pub(super) static AUX_WRITES: Cell<u32> = Cell::new(0);
+    }
+
+    /// 返回上次调用以来记录的读取次数并清零
+    pub fn take_storage_reads() -> u32 {
+        STORAGE_READS.with(|reads| reads.replace(0))
+    }
+
+    /// 返回上次调用以来记录的写入次数并清零
+    pub fn take_storage_writes() -> u32 {
+        STORAGE_WRITES.with(|writes| writes.replace(0))
+    }
+
+    /// 返回上次调用以来记录的其他写入次数并清零
+    pub fn take_aux_writes() -> u32 {
+        AUX_WRITES.with(|writes| writes.replace(0))
+    }
+}
@@ -0,0 +1,162 @@
+//! ERC20 核心语义的参考模型, 只用 BTreeMap 实现余额, 授权和总供应量.
+//! 不包含手续费, 暂停, 转发等扩展功能, 对应合约以默认配置部署时的行为.
+//! 差分测试把同一组操作同时作用于合约和模型并逐步比较, 也可以当作可执行的语义说明
+
+use crate::erc20::{Error, INFINITE_ALLOWANCE};
+use ink_env::{AccountId, DefaultEnvironment, Environment};
+use ink_prelude::{collections::BTreeMap, vec::Vec};
+
+type Balance = <DefaultEnvironment as Environment>::Balance;
+
+/// 一次外部调用, 字段中第一个账户为调用者
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Transfer {
+        from: AccountId,
+        to: AccountId,
+        value: Balance,
+    },
+    Approve {
+        owner: AccountId,
+        spender: AccountId,
+        value: Balance,
+    },
+    TransferFrom {
+        spender: AccountId,
+        from: AccountId,
+        to: AccountId,
+        value: Balance,
+    },
+    Mint {
+        caller: AccountId,
+        to: AccountId,
+        value: Balance,
+    },
+    Burn {
+        from: AccountId,
+        value: Balance,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Model {
+    owner: AccountId,
+    total_supply: Balance,
+    balances: BTreeMap<AccountId, Balance>,
+    allowances: BTreeMap<(AccountId, AccountId), Balance>,
+}
+
+impl Model {
+    /// 对应 owner 调用 Erc20::new(supply)
+    pub fn new(owner: AccountId, supply: Balance) -> Self {
+        let mut balances = BTreeMap::new();
+        balances.insert(owner, supply);
+        Model {
+            owner,
+            total_supply: supply,
+            balances,
+            allowances: BTreeMap::new(),
+        }
+    }
+
+    pub fn total_supply(&self) -> Balance {
+        self.total_supply
+    }
+
+    pub fn balance_of(&self, who: AccountId) -> Balance {
+        self.balances.get(&who).copied().unwrap_or(0)
+    }
+
+    pub fn allowance(&self, owner: AccountId, spender: AccountId) -> Balance {
+        self.allowances.get(&(owner, spender)).copied().unwrap_or(0)
+    }
+
+    /// 执行一次调用. 返回 Err 时状态不变
+    pub fn apply(&mut self, op: Op) -> Result<(), Error> {
+        match op {
+            Op::Transfer { from, to, value } => self.transfer(from, to, value),
+            Op::Approve {
+                owner,
+                spender,
+                value,
+            } => {
+                self.allowances.insert((owner, spender), value);
+                Ok(())
+            }
+            Op::TransferFrom {
+                spender,
+                from,
+                to,
+                value,
+            } => {
+                // 先检查授权再检查余额
+                let allowance = self.allowance(from, spender);
+                if allowance < value {
+                    return Err(Error::InsufficientAllowance);
+                }
+                self.transfer(from, to, value)?;
+                // 转给自己和无限授权都不消耗额度
+                if from != to && allowance != INFINITE_ALLOWANCE {
+                    self.allowances.insert((from, spender), allowance - value);
+                }
+                Ok(())
+            }
+            Op::Mint { caller, to, value } => {
+                if caller != self.owner {
+                    return Err(Error::NotOwner);
+                }
+                let new_supply = self
+                    .total_supply
+                    .checked_add(value)
+                    .ok_or(Error::Overflow)?;
+                // 单个余额不超过总供应量, 不会溢出
+                let new_balance = self.balance_of(to) + value;
+                self.total_supply = new_supply;
+                self.balances.insert(to, new_balance);
+                Ok(())
+            }
+            Op::Burn { from, value } => {
+                let balance = self.balance_of(from);
+                if balance < value {
+                    return Err(Error::InsufficientBalance);
+                }
+                self.balances.insert(from, balance - value);
+                self.total_supply -= value;
+                Ok(())
+            }
+        }
+    }
+
+    fn transfer(&mut self, from: AccountId, to: AccountId, value: Balance) -> Result<(), Error> {
+        let from_balance = self.balance_of(from);
+        if from_balance < value {
+            return Err(Error::InsufficientBalance);
+        }
+        if from == to {
+            return Ok(());
+        }
+        let to_balance = self
+            .balance_of(to)
+            .checked_add(value)
+            .ok_or(Error::Overflow)?;
+        self.balances.insert(from, from_balance - value);
+        self.balances.insert(to, to_balance);
+        Ok(())
+    }
+}
+
+/// 贪心地逐个删除操作, 直到删掉任何一个都不再触发 fails, 得到的序列用于打印差异
+pub fn minimize(ops: &[Op], mut fails: impl FnMut(&[Op]) -> bool) -> Vec<Op> {
+    let mut ops = ops.to_vec();
+    let mut index = 0;
+    while index < ops.len() {
+        let mut candidate = ops.clone();
+        candidate.remove(index);
+        if fails(&candidate) {
+            ops = candidate;
+        } else {
+            index += 1;
+        }
+    }
+    ops
+}